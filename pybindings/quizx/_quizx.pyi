@@ -65,6 +65,7 @@ class VecGraph:
     def outputs(self) -> list[int]: ...
     def num_outputs(self) -> int: ...
     def set_outputs(self, outputs: list[int]) -> None: ...
+    def to_svg(self) -> str: ...
 
 @final
 class Circuit:
@@ -86,6 +87,10 @@ class CircuitStats:
     def moreq(self) -> int: ...
     def cliff(self) -> int: ...
     def non_cliff(self) -> int: ...
+    def tcount(self) -> int: ...
+    def tdepth(self) -> int: ...
+    def twoq_depth(self) -> int: ...
+    def depth(self) -> int: ...
     def to_string(self) -> str: ...
 
 @final
@@ -105,4 +110,5 @@ def dummy(a: int) -> str: ...
 def interior_clifford_simp(g: VecGraph): ...
 def clifford_simp(g: VecGraph): ...
 def full_simp(g: VecGraph): ...
+def simplify_with_strategy(g: VecGraph, strategy: str) -> bool: ...
 def extract_circuit(g: VecGraph) -> Circuit: ...
@@ -44,6 +44,10 @@ class VecGraph(BaseGraph[int, Tuple[int, int]]):
         """Return the underlying Rust graph instance."""
         return self._g
 
+    def _repr_svg_(self) -> str:
+        """Render the graph as SVG, for display in Jupyter notebooks."""
+        return self._g.to_svg()
+
     @staticmethod
     def from_raw_graph(rust_g):
         g = VecGraph()
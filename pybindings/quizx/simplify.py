@@ -11,3 +11,11 @@ def clifford_simp(g):
 
 def full_simp(g):
     _quizx.full_simp(g._g)
+
+
+def simplify_with_strategy(g, strategy: str) -> bool:
+    """Simplify g with a strategy such as "repeat(seq(clifford, fuse_gadgets))".
+
+    Returns True if the strategy made progress. Raises ValueError if the
+    strategy cannot be parsed."""
+    return _quizx.simplify_with_strategy(g._g, strategy)
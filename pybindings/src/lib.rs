@@ -3,6 +3,7 @@ pub mod scalar;
 use crate::scalar::Scalar;
 
 use num::Rational64;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
 use quizx::extract::ToCircuit;
@@ -16,6 +17,7 @@ fn _quizx(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(clifford_simp, m)?)?;
     m.add_function(wrap_pyfunction!(fuse_gadgets, m)?)?;
     m.add_function(wrap_pyfunction!(full_simp, m)?)?;
+    m.add_function(wrap_pyfunction!(simplify_with_strategy, m)?)?;
     m.add_function(wrap_pyfunction!(extract_circuit, m)?)?;
     m.add_class::<VecGraph>()?;
     m.add_class::<Circuit>()?;
@@ -50,6 +52,14 @@ fn full_simp(g: &mut VecGraph) {
     quizx::simplify::full_simp(&mut g.g);
 }
 
+#[pyfunction]
+fn simplify_with_strategy(g: &mut VecGraph, strategy: &str) -> PyResult<bool> {
+    let s: quizx::strategy::Strategy = strategy
+        .parse()
+        .map_err(|e: quizx::strategy::StrategyError| PyValueError::new_err(e.to_string()))?;
+    Ok(s.run(&mut g.g))
+}
+
 #[pyfunction]
 fn extract_circuit(g: &mut VecGraph) -> Circuit {
     Circuit {
@@ -105,7 +115,9 @@ impl Circuit {
         if self.s.is_none() {
             self.s = Some(self.c.stats());
         }
-        CircuitStats { s: self.s.unwrap() }
+        CircuitStats {
+            s: self.s.clone().unwrap(),
+        }
     }
 }
 
@@ -132,6 +144,18 @@ impl CircuitStats {
     fn non_cliff(&self) -> usize {
         self.s.non_cliff
     }
+    fn tcount(&self) -> usize {
+        self.s.tcount
+    }
+    fn tdepth(&self) -> usize {
+        self.s.tdepth
+    }
+    fn twoq_depth(&self) -> usize {
+        self.s.twoq_depth
+    }
+    fn depth(&self) -> usize {
+        self.s.depth
+    }
     #[allow(clippy::inherent_to_string)]
     fn to_string(&self) -> String {
         self.s.to_string()
@@ -302,6 +326,11 @@ impl VecGraph {
         self.g.set_outputs(outputs)
     }
 
+    /// Renders the graph as SVG.
+    fn to_svg(&self) -> String {
+        self.g.to_svg()
+    }
+
     /// Returns the graph scalar.
     #[getter]
     fn get_scalar(&self) -> Scalar {
@@ -0,0 +1,81 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares the graph backends on the clone-then-mutate pattern used by the decomposer.
+//!
+//! Run with `cargo run --release --example clone_bench`.
+
+use quizx::circuit::*;
+use quizx::graph::*;
+use quizx::simplify::full_simp;
+use std::time::Instant;
+
+const ROUNDS: usize = 2000;
+
+/// Clone the graph repeatedly, then replace the first T-like spider by a Clifford,
+/// which is roughly what a single branch of a decomposition does.
+fn bench<G: GraphLike>(name: &str, c: &Circuit) {
+    let mut g: G = c.to_graph();
+    g.plug_inputs(&vec![BasisElem::Z0; c.num_qubits()]);
+    g.plug_outputs(&vec![BasisElem::Z0; c.num_qubits()]);
+    full_simp(&mut g);
+    let t = g.find_vertex(|v| g.vertex_data(v).is_t_like());
+
+    let time = Instant::now();
+    for _ in 0..ROUNDS {
+        let h = g.clone();
+        std::hint::black_box(&h);
+    }
+    let clone_time = time.elapsed();
+
+    let time = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut h = g.clone();
+        if let Some(t) = t {
+            h.set_phase(t, 0);
+            let ns = h.neighbor_vec(t);
+            for n in ns {
+                h.add_to_phase(n, (1, 2));
+            }
+        }
+        std::hint::black_box(&h);
+    }
+    let mutate_time = time.elapsed();
+
+    println!(
+        "{:>12}: clone {:>10.2?}   clone+mutate {:>10.2?}",
+        name,
+        clone_time / ROUNDS as u32,
+        mutate_time / ROUNDS as u32
+    );
+}
+
+fn main() {
+    for qs in [50, 100] {
+        let c = Circuit::random()
+            .qubits(qs)
+            .depth(20 * qs)
+            .seed(1337)
+            .p_t(0.05)
+            .with_cliffords()
+            .build();
+        println!("{} qubits, {} gates:", qs, c.num_gates());
+        bench::<quizx::vec_graph::Graph>("vec_graph", &c);
+        bench::<quizx::hash_graph::Graph>("hash_graph", &c);
+        bench::<quizx::compact_graph::Graph>("compact_graph", &c);
+        bench::<quizx::persistent_graph::Graph>("persistent", &c);
+    }
+}
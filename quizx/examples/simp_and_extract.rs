@@ -18,6 +18,7 @@
 use quizx::circuit::*;
 use quizx::extract::*;
 use quizx::simplify::*;
+use quizx::tbound::TCountReport;
 use quizx::tensor::*;
 use quizx::vec_graph::*;
 
@@ -40,6 +41,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     //     .build();
     let mut g: Graph = c.to_graph();
     full_simp(&mut g);
+    println!("{}", TCountReport::new(&g));
     // println!("{}", g.to_dot());
     // println!("{:?}", g);
     // assert_eq!(c.to_tensor4(), g.to_tensor4());
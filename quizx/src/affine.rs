@@ -0,0 +1,468 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The affine-with-phases (AP) normal form
+//!
+//! A diagram with `n` boundaries is in AP form if its tensor is
+//!
+//! ```text
+//! c * sum_{x : A x = b} e^(i pi phi(x)) |x>
+//! ```
+//!
+//! for an affine subspace `A x = b` of `F_2^n` and a phase polynomial `phi`,
+//! i.e. a sum of phases on parities of the variables `x`. As for
+//! [crate::clifford::Gslc], inputs and outputs are treated alike, so the variables
+//! are the inputs followed by the outputs.
+//!
+//! [ApForm::from_graph] reads the diagram as a sum over paths: each spider is a
+//! variable, each Hadamard edge a phase on the product of its ends, and each plain
+//! edge an equation. The variables of the interior spiders are then summed out.
+//! This always works for Clifford diagrams, and for diagrams whose non-Clifford
+//! phases can be moved to the boundaries, e.g. CNOT+phase circuits or the output
+//! of [crate::simplify::full_simp] on such a circuit.
+//!
+//! The tensor of a Clifford diagram is computed from its AP form, see
+//! [ApForm::solutions], which avoids contracting the diagram. For other diagrams
+//! with an AP form, its [ToTensor](crate::tensor::ToTensor) implementation can be
+//! used directly.
+//!
+//! ```
+//! # use quizx::affine::ApForm;
+//! # use quizx::circuit::Circuit;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; t q[1]; cx q[0], q[1];").unwrap();
+//! let g: Graph = c.to_graph();
+//! let ap = ApForm::from_graph(&g).unwrap();
+//! // the two wires, and a T phase on the parity of the inputs
+//! assert_eq!(ap.constraints.len(), 2);
+//! assert_eq!(ap.phases.len(), 1);
+//! let h: Graph = ap.to_graph();
+//! ```
+
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use num::{One, Zero};
+use rustc_hash::FxHashMap;
+use std::collections::BTreeMap;
+
+/// A set of variables, standing for their sum mod 2, as a sorted list of indices
+pub type Parity = Vec<usize>;
+
+/// The symmetric difference of two parities
+pub(crate) fn xor(p: &[usize], q: &[usize]) -> Parity {
+    let mut r = Vec::with_capacity(p.len() + q.len());
+    let (mut i, mut j) = (0, 0);
+    while i < p.len() && j < q.len() {
+        match p[i].cmp(&q[j]) {
+            std::cmp::Ordering::Less => {
+                r.push(p[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                r.push(q[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    r.extend_from_slice(&p[i..]);
+    r.extend_from_slice(&q[j..]);
+    r
+}
+
+/// A sum over the variables of some spiders, used to compute the AP form
+struct PathSum {
+    constraints: Vec<(Parity, bool)>,
+    phases: FxHashMap<Parity, Phase>,
+    scalar: ScalarN,
+}
+
+impl PathSum {
+    /// Add the phase `a` times the parity `p`, plus 1 if `c` is set
+    fn add_phase(&mut self, p: Parity, c: bool, a: Phase) {
+        let a = if c {
+            self.scalar.mul_phase(a);
+            -a
+        } else {
+            a
+        };
+        if p.is_empty() {
+            return;
+        }
+        let b = self.phases.get(&p).copied().unwrap_or_else(Phase::zero) + a;
+        if b.is_zero() {
+            self.phases.remove(&p);
+        } else {
+            self.phases.insert(p, b);
+        }
+    }
+
+    /// Replace the variable `j` by the parity `p`, plus 1 if `c` is set
+    ///
+    /// This replaces `j` in every constraint and phase.
+    fn substitute(&mut self, j: usize, p: &[usize], c: bool) {
+        let jp = xor(&[j], p);
+        for (q, d) in self.constraints.iter_mut() {
+            if q.binary_search(&j).is_ok() {
+                *q = xor(q, &jp);
+                *d ^= c;
+                if q.is_empty() && *d {
+                    self.scalar = ScalarN::zero();
+                }
+            }
+        }
+        let terms: Vec<Parity> = self
+            .phases
+            .keys()
+            .filter(|q| q.binary_search(&j).is_ok())
+            .cloned()
+            .collect();
+        for q in terms {
+            let a = self.phases.remove(&q).unwrap();
+            self.add_phase(xor(&q, &jp), c, a);
+        }
+    }
+
+    /// Sum over the variable `j`, which does not occur in any constraint
+    ///
+    /// This works if every phase on a parity containing `j` is a multiple of π/2,
+    /// and otherwise returns false.
+    fn sum_out(&mut self, j: usize) -> bool {
+        let terms: Vec<(Parity, Phase)> = self
+            .phases
+            .iter()
+            .filter(|(q, _)| q.binary_search(&j).is_ok())
+            .map(|(q, &a)| (q.clone(), a))
+            .collect();
+        if terms.iter().any(|(_, a)| *a.to_rational().denom() > 2) {
+            return false;
+        }
+
+        // a * (x_j + x_q) = a * x_q + a * x_j * (1 - 2 x_q), so summing over x_j
+        // leaves the phases on the x_q and a factor 1 + e^(i pi c0) (-1)^(x_l), where
+        // c0 is the sum of the phases, and l the sum of the q whose phase is ±π/2
+        let mut c0 = Phase::zero();
+        let mut l = vec![];
+        for (q, a) in terms {
+            self.phases.remove(&q);
+            let q = xor(&q, &[j]);
+            if *a.to_rational().denom() == 2 {
+                l = xor(&l, &q);
+            }
+            c0 += a;
+            self.add_phase(q, false, a);
+        }
+
+        if c0.is_pauli() {
+            // 1 + (-1)^(c0 + x_l) = 2 if x_l = c0, and 0 otherwise
+            if l.is_empty() && c0.is_one() {
+                self.scalar = ScalarN::zero();
+            } else if !l.is_empty() {
+                self.constraints.push((l, c0.is_one()));
+            }
+            self.scalar.mul_sqrt2_pow(2);
+        } else {
+            // 1 + e^(i pi c0) (-1)^(x_l) = sqrt(2) e^(i pi c0 / 2) e^(-i pi c0 x_l)
+            self.scalar.mul_sqrt2_pow(1);
+            self.scalar.mul_phase(c0 / 2);
+            self.add_phase(l, false, -c0);
+        }
+        true
+    }
+}
+
+/// A diagram in AP normal form, see the [module docs](self)
+///
+/// The constraints are in reduced echelon form: the first variable of each
+/// constraint does not occur in any other constraint, or in the phase polynomial.
+/// The phase polynomial need not be unique, as different sums of parities can
+/// give the same phase on every point of the affine subspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApForm {
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+    /// The affine subspace, as a list of parities of the variables and their values
+    pub constraints: Vec<(Parity, bool)>,
+    /// The phase polynomial, as the phase on each parity of the variables
+    pub phases: BTreeMap<Parity, Phase>,
+    pub scalar: ScalarN,
+}
+
+impl ApForm {
+    /// Compute the AP form of a diagram
+    ///
+    /// Returns None if the diagram has H-boxes or symbolic phases, or if a
+    /// non-Clifford phase is stuck on an interior spider, so the diagram is not in
+    /// AP form.
+    pub fn from_graph(g: &impl GraphLike) -> Option<ApForm> {
+        let mut h = g.clone();
+        h.x_to_z();
+        let bs: Vec<V> = h.inputs().iter().chain(h.outputs()).copied().collect();
+        let mut index: FxHashMap<V, usize> = bs.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+        for v in h.vertices() {
+            match h.vertex_type(v) {
+                VType::Z if !h.has_symbolic_phase(v) => {
+                    let i = index.len();
+                    index.insert(v, i);
+                }
+                VType::B if index.contains_key(&v) => {}
+                _ => return None,
+            }
+        }
+
+        let mut ps = PathSum {
+            constraints: vec![],
+            phases: FxHashMap::default(),
+            scalar: h.scalar().clone(),
+        };
+        for v in h.vertices() {
+            ps.add_phase(vec![index[&v]], false, h.phase(v));
+        }
+        for (s, t, et) in h.edges() {
+            let (i, j) = (index[&s].min(index[&t]), index[&s].max(index[&t]));
+            match et {
+                EType::N => ps.constraints.push((vec![i, j], false)),
+                _ => {
+                    // pi x_i x_j = (x_i + x_j - (x_i + x_j mod 2)) * pi/2
+                    let a = Phase::from((1, 2));
+                    ps.add_phase(vec![i], false, a);
+                    ps.add_phase(vec![j], false, a);
+                    ps.add_phase(vec![i, j], false, -a);
+                    ps.scalar.mul_sqrt2_pow(-1);
+                }
+            }
+        }
+
+        // sum out the interior spiders
+        let nb = bs.len();
+        let mut interior: Vec<usize> = (nb..index.len()).collect();
+        while !interior.is_empty() && !ps.scalar.is_zero() {
+            let solved = ps
+                .constraints
+                .iter()
+                .position(|(p, _)| p.last() >= Some(&nb));
+            if let Some(i) = solved {
+                let (p, c) = ps.constraints.remove(i);
+                let j = *p.last().unwrap();
+                ps.substitute(j, &p[..p.len() - 1], c);
+                interior.retain(|&k| k != j);
+            } else {
+                let k = interior.iter().position(|&j| ps.sum_out(j))?;
+                interior.remove(k);
+            }
+        }
+
+        // reduce the constraints on the boundaries
+        let mut i = 0;
+        while i < ps.constraints.len() {
+            let (p, c) = ps.constraints.remove(i);
+            if p.is_empty() {
+                continue;
+            }
+            ps.substitute(p[0], &p[1..], c);
+            ps.constraints.insert(i, (p, c));
+            i += 1;
+        }
+        ps.constraints.sort();
+
+        if ps.scalar.is_zero() {
+            ps.constraints.clear();
+            ps.phases.clear();
+        }
+        Some(ApForm {
+            num_inputs: h.inputs().len(),
+            num_outputs: h.outputs().len(),
+            constraints: ps.constraints,
+            phases: ps.phases.into_iter().collect(),
+            scalar: ps.scalar,
+        })
+    }
+
+    /// The number of variables, i.e. the number of boundaries
+    pub fn num_qubits(&self) -> usize {
+        self.num_inputs + self.num_outputs
+    }
+
+    /// The points of the affine subspace, i.e. the values of the variables where
+    /// the tensor can be non-zero
+    ///
+    /// The first variable of each constraint is fixed by the others, so this
+    /// runs over the values of the remaining variables.
+    pub fn solutions(&self) -> impl Iterator<Item = Vec<bool>> + '_ {
+        let n = self.num_qubits();
+        let mut pivot = vec![false; n];
+        for (p, _) in &self.constraints {
+            pivot[p[0]] = true;
+        }
+        let free: Vec<usize> = (0..n).filter(|&i| !pivot[i]).collect();
+        assert!(free.len() < 64, "Too many solutions to enumerate");
+        (0..1u64 << free.len()).map(move |bits| {
+            let mut x = vec![false; n];
+            for (k, &i) in free.iter().enumerate() {
+                x[i] = (bits >> k) & 1 == 1;
+            }
+            for (p, c) in &self.constraints {
+                x[p[0]] = p[1..].iter().fold(*c, |b, &i| b ^ x[i]);
+            }
+            x
+        })
+    }
+
+    /// The entry of the tensor at the given values of the variables
+    pub fn amplitude(&self, x: &[bool]) -> ScalarN {
+        let parity = |p: &Parity| p.iter().fold(false, |b, &i| b ^ x[i]);
+        if self.constraints.iter().any(|(p, c)| parity(p) != *c) {
+            return ScalarN::zero();
+        }
+        let mut s = self.scalar.clone();
+        for (p, &a) in &self.phases {
+            if parity(p) {
+                s.mul_phase(a);
+            }
+        }
+        s
+    }
+
+    /// The diagram of the AP form
+    ///
+    /// Each variable is a Z spider on a boundary, each constraint an X spider and
+    /// each phase on a parity of more than one variable a phase gadget.
+    pub fn to_graph<G: GraphLike>(&self) -> G {
+        let mut g = G::new();
+        let n = self.num_qubits();
+        let mut spiders = Vec::with_capacity(n);
+        let mut bs = Vec::with_capacity(n);
+        for i in 0..n {
+            let (qubit, row) = if i < self.num_inputs {
+                (i, 0)
+            } else {
+                (i - self.num_inputs, 4)
+            };
+            let b = g.add_vertex(VType::B);
+            g.set_qubit(b, qubit as i32);
+            g.set_row(b, row);
+            let v = g.add_vertex(VType::Z);
+            g.set_qubit(v, qubit as i32);
+            g.set_row(v, if row == 0 { 1 } else { 3 });
+            g.add_edge(b, v);
+            spiders.push(v);
+            bs.push(b);
+        }
+
+        *g.scalar_mut() = self.scalar.clone();
+        for (p, c) in &self.constraints {
+            let x = g.add_vertex_with_phase(VType::X, if *c { 1 } else { 0 });
+            g.set_row(x, 2);
+            for &i in p {
+                g.add_edge(x, spiders[i]);
+            }
+            // an X spider is 2^(1 - k/2) times the projector on its parity
+            g.scalar_mut().mul_sqrt2_pow(p.len() as i32 - 2);
+        }
+        for (p, &a) in &self.phases {
+            if p.len() == 1 {
+                g.add_to_phase(spiders[p[0]], a);
+                continue;
+            }
+            let hub = g.add_vertex(VType::Z);
+            let leaf = g.add_vertex_with_phase(VType::Z, a);
+            g.set_row(hub, 2);
+            g.set_row(leaf, 2);
+            g.add_edge_with_type(hub, leaf, EType::H);
+            for &i in p {
+                g.add_edge_with_type(hub, spiders[i], EType::H);
+            }
+            // a phase gadget is 2^(1 - (k+1)/2) times the phase on its parity
+            g.scalar_mut().mul_sqrt2_pow(p.len() as i32 - 1);
+        }
+
+        g.set_inputs(bs[..self.num_inputs].to_vec());
+        g.set_outputs(bs[self.num_inputs..].to_vec());
+        g
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::full_simp;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn ap_clifford() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(3)
+                .depth(20)
+                .clifford_t(0.0)
+                .build();
+            let g: Graph = c.to_graph();
+            let ap = ApForm::from_graph(&g).unwrap();
+            assert_eq!(ap.to_tensor4(), c.to_tensor4());
+            let h: Graph = ap.to_graph();
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+            // each input has some non-zero outputs
+            assert!(ap.solutions().count() >= 8);
+        }
+    }
+
+    #[test]
+    fn ap_phase_polynomial() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            cx q[0], q[1];
+            t q[1];
+            cx q[1], q[2];
+            tdg q[2];
+            cx q[0], q[2];
+            t q[2];
+            s q[0];
+        "#,
+        )
+        .unwrap();
+        let g: Graph = c.to_graph();
+        let ap = ApForm::from_graph(&g).unwrap();
+        // the outputs are a linear function of the inputs
+        assert_eq!(ap.constraints.len(), 3);
+        assert!(ap.phases.values().any(|p| p.is_t()));
+        let h: Graph = ap.to_graph();
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        // the amplitudes of the wire
+        let wire: Graph = Circuit::new(1).to_graph();
+        let ap = ApForm::from_graph(&wire).unwrap();
+        assert!(ap.amplitude(&[false, false]).is_one());
+        assert!(ap.amplitude(&[true, true]).is_one());
+        assert!(ap.amplitude(&[true, false]).is_zero());
+        let xs: Vec<Vec<bool>> = ap.solutions().collect();
+        assert_eq!(xs, vec![vec![false, false], vec![true, true]]);
+
+        // a T gate between two Hadamards is not in AP form
+        let c = Circuit::from_qasm("qreg q[1]; h q[0]; t q[0]; h q[0];").unwrap();
+        let mut g: Graph = c.to_graph();
+        assert_eq!(ApForm::from_graph(&g), None);
+        full_simp(&mut g);
+        assert_eq!(ApForm::from_graph(&g), None);
+    }
+}
@@ -14,11 +14,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Optimisation of graph-like diagrams by simulated annealing
+//!
+//! The [Annealer] repeatedly applies a random action to a diagram, such as a
+//! local complementation or a pivot, and keeps the result with a probability
+//! which depends on the change in score and the current temperature. Two scores
+//! are provided: the two-qubit gate count of the extracted circuit, and the
+//! T-count after fusing phase gadgets.
+
 // use crate::circuit::*;
 // use crate::gate::*;
 use crate::basic_rules::*;
 use crate::extract::*;
 use crate::graph::*;
+use crate::simplify::gadget_simp;
 
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
@@ -39,6 +48,13 @@ impl<G: GraphLike> Annealer<G> {
         c.stats().twoq
     }
 
+    /// The T-count of the diagram after fusing its phase gadgets
+    pub fn tcount_score(g: &G) -> usize {
+        let mut h = g.clone();
+        gadget_simp(&mut h);
+        h.tcount()
+    }
+
     pub fn random_local_comp(rng: &mut StdRng, g: &mut G) {
         let candidates: Vec<_> = g.vertices().filter(|&v| check_local_comp(g, v)).collect();
         if candidates.is_empty() {
@@ -72,6 +88,19 @@ impl<G: GraphLike> Annealer<G> {
         gen_pivot(g, candidates[i].0, candidates[i].1);
     }
 
+    /// Apply a local complementation congruence to a random interior spider
+    ///
+    /// See [lc_cong_unchecked]. Unlike [Annealer::random_local_comp], this applies to
+    /// spiders with any phase.
+    pub fn random_lc_cong(rng: &mut StdRng, g: &mut G) {
+        let candidates: Vec<_> = g.vertices().filter(|&v| check_lc_cong(g, v)).collect();
+        if candidates.is_empty() {
+            return;
+        }
+        let i = rng.gen_range(0..candidates.len());
+        lc_cong(g, candidates[i]);
+    }
+
     pub fn new(g: G) -> Self {
         Annealer {
             g,
@@ -84,6 +113,20 @@ impl<G: GraphLike> Annealer<G> {
         }
     }
 
+    /// An annealer which reduces the T-count of a graph-like diagram
+    ///
+    /// This scores by [Annealer::tcount_score], and applies random congruences,
+    /// namely [Annealer::random_lc_cong] and [Annealer::random_gen_pivot], which
+    /// keep the diagram graph-like and preserve gflow. The result can have many
+    /// unfused phase gadgets, so it should be simplified with
+    /// [crate::simplify::full_simp] before extraction.
+    pub fn tcount(g: G) -> Self {
+        let mut a = Annealer::new(g);
+        a.scoref(Annealer::tcount_score)
+            .actions(vec![Annealer::random_lc_cong, Annealer::random_gen_pivot]);
+        a
+    }
+
     pub fn seed(&mut self, seed: u64) -> &mut Self {
         self.rng = StdRng::seed_from_u64(seed);
         self
@@ -92,6 +135,10 @@ impl<G: GraphLike> Annealer<G> {
         self.scoref = scoref;
         self
     }
+    pub fn actions(&mut self, actions: Vec<fn(&mut StdRng, &mut G)>) -> &mut Self {
+        self.actions = actions;
+        self
+    }
 
     pub fn temp(&mut self, temp: f64) -> &mut Self {
         self.temp = temp;
@@ -106,14 +153,19 @@ impl<G: GraphLike> Annealer<G> {
         self
     }
 
-    pub fn anneal(&mut self) {
+    /// Run the annealer, leaving the best diagram found in `self.g`
+    ///
+    /// The temperature starts at `temp` and is multiplied by `1 - cool` after each
+    /// iteration. Returns the score of the best diagram.
+    pub fn anneal(&mut self) -> usize {
+        let mut current_score = (self.scoref)(&self.g) as isize;
         if self.actions.is_empty() {
-            return;
+            return current_score as usize;
         }
         let mut temp = self.temp;
-        let mut current_score = (self.scoref)(&self.g) as isize;
+        let mut best = (self.g.clone(), current_score);
 
-        let chunk = self.iters / 20;
+        let chunk = usize::max(self.iters / 20, 1);
         for it in 0..self.iters {
             if it % chunk == 0 {
                 println!("{}/{}", it, self.iters);
@@ -122,7 +174,7 @@ impl<G: GraphLike> Annealer<G> {
             let i = self.rng.gen_range(0..self.actions.len());
             let mut g = self.g.clone();
             self.actions[i](&mut self.rng, &mut g);
-            let new_score = (self.scoref)(&self.g) as isize;
+            let new_score = (self.scoref)(&g) as isize;
             if new_score < current_score
                 || (temp != 0.0
                     && self.rng.gen_bool(f64::min(
@@ -132,11 +184,74 @@ impl<G: GraphLike> Annealer<G> {
             {
                 self.g = g;
                 current_score = new_score;
+                if current_score < best.1 {
+                    best = (self.g.clone(), current_score);
+                }
             }
 
             temp *= 1.0 - self.cool;
         }
 
         println!("{}/{}", self.iters, self.iters);
+        self.g = best.0;
+        best.1 as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::*;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn anneal_tcount() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.3)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+        let mut h = g.clone();
+        clifford_simp(&mut h);
+        let t = Annealer::tcount_score(&h);
+
+        let mut a = Annealer::tcount(h);
+        a.seed(1337).temp(2.0).cool(0.01).iters(200);
+        let t1 = a.anneal();
+        assert!(t1 <= t);
+        assert_eq!(Annealer::tcount_score(&a.g), t1);
+        assert!(a.g.is_graph_like());
+        assert_eq!(g.to_tensor4(), a.g.to_tensor4());
+
+        let mut h = a.g.clone();
+        full_simp(&mut h);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert!(h.to_circuit().is_ok());
+    }
+
+    #[test]
+    fn lc_cong() {
+        let c = Circuit::random()
+            .seed(42)
+            .qubits(3)
+            .depth(20)
+            .p_t(0.3)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        clifford_simp(&mut g);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10 {
+            let mut h = g.clone();
+            Annealer::random_lc_cong(&mut rng, &mut h);
+            assert!(h.is_graph_like());
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+            g = h;
+        }
     }
 }
@@ -0,0 +1,357 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Qubit connectivity of hardware, and routing of two-qubit gates
+//!
+//! An [Architecture] is a connected graph on the qubits, whose edges are the pairs
+//! of qubits that a two-qubit gate can act on. A CNOT, CZ or SWAP between qubits
+//! which are not connected is routed along a shortest path between them, without
+//! moving any qubits:
+//!
+//! - a CNOT along a path of length `k > 1` becomes `4(k - 1)` CNOTs, which leave
+//!   the qubits in the middle of the path unchanged,
+//! - a CZ becomes such a CNOT between Hadamards, and
+//! - a SWAP becomes `2k - 1` SWAPs along the path.
+//!
+//! For circuit extraction, [Architecture::gauss] does Gaussian elimination with
+//! row operations that follow Steiner trees in the architecture, as in Kissinger and
+//! Meijer-van de Griend, "CNOT circuit extraction for topologically-constrained
+//! quantum memories" (2019). See [Extractor::with_architecture].
+//!
+//! ```
+//! # use quizx::architecture::Architecture;
+//! # use quizx::circuit::Circuit;
+//! let arch = Architecture::line(3);
+//! let c = Circuit::from_qasm("qreg q[3]; cx q[0], q[2];").unwrap();
+//! assert!(!arch.supports(&c));
+//! let c1 = arch.route(&c);
+//! assert!(arch.supports(&c1));
+//! assert_eq!(c1.num_gates(), 4);
+//! ```
+//!
+//! [Extractor::with_architecture]: crate::extract::Extractor::with_architecture
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::linalg::{Mat2, RowOps};
+use std::collections::VecDeque;
+
+/// A connectivity graph on the qubits, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Architecture {
+    nhd: Vec<Vec<usize>>,
+    /// The length of a shortest path between each pair of qubits
+    dist: Vec<Vec<usize>>,
+}
+
+impl Architecture {
+    /// The architecture on `n` qubits with the given edges
+    ///
+    /// Panics if an edge is out of range, or if the graph is not connected.
+    pub fn new(n: usize, edges: &[(usize, usize)]) -> Self {
+        let mut nhd = vec![vec![]; n];
+        for &(a, b) in edges {
+            assert!(a < n && b < n, "Edge ({}, {}) is out of range", a, b);
+            if a != b && !nhd[a].contains(&b) {
+                nhd[a].push(b);
+                nhd[b].push(a);
+            }
+        }
+        for ns in &mut nhd {
+            ns.sort();
+        }
+
+        let dist: Vec<Vec<usize>> = (0..n)
+            .map(|a| {
+                let mut d = vec![usize::MAX; n];
+                d[a] = 0;
+                let mut queue = VecDeque::from([a]);
+                while let Some(v) = queue.pop_front() {
+                    for &w in &nhd[v] {
+                        if d[w] == usize::MAX {
+                            d[w] = d[v] + 1;
+                            queue.push_back(w);
+                        }
+                    }
+                }
+                d
+            })
+            .collect();
+        assert!(
+            dist.iter().flatten().all(|&d| d != usize::MAX),
+            "Architecture must be connected"
+        );
+        Architecture { nhd, dist }
+    }
+
+    /// Qubits in a line, with `q` connected to `q + 1`
+    pub fn line(n: usize) -> Self {
+        let edges: Vec<_> = (1..n).map(|q| (q - 1, q)).collect();
+        Architecture::new(n, &edges)
+    }
+
+    /// Qubits in a ring, with `q` connected to `q + 1` modulo `n`
+    pub fn ring(n: usize) -> Self {
+        let edges: Vec<_> = (0..n).map(|q| (q, (q + 1) % n)).collect();
+        Architecture::new(n, &edges)
+    }
+
+    /// A `rows` by `cols` grid, where the qubit in row `r` and column `c` is
+    /// `r * cols + c`
+    pub fn grid(rows: usize, cols: usize) -> Self {
+        let mut edges = vec![];
+        for r in 0..rows {
+            for c in 0..cols {
+                let q = r * cols + c;
+                if c + 1 < cols {
+                    edges.push((q, q + 1));
+                }
+                if r + 1 < rows {
+                    edges.push((q, q + cols));
+                }
+            }
+        }
+        Architecture::new(rows * cols, &edges)
+    }
+
+    /// All-to-all connectivity
+    pub fn complete(n: usize) -> Self {
+        let mut edges = vec![];
+        for a in 0..n {
+            for b in a + 1..n {
+                edges.push((a, b));
+            }
+        }
+        Architecture::new(n, &edges)
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.nhd.len()
+    }
+
+    pub fn neighbors(&self, q: usize) -> &[usize] {
+        &self.nhd[q]
+    }
+
+    pub fn connected(&self, a: usize, b: usize) -> bool {
+        self.dist[a][b] == 1
+    }
+
+    /// The number of edges on a shortest path from `a` to `b`
+    pub fn distance(&self, a: usize, b: usize) -> usize {
+        self.dist[a][b]
+    }
+
+    /// A shortest path from `a` to `b`, including both ends
+    pub fn shortest_path(&self, a: usize, b: usize) -> Vec<usize> {
+        let mut path = vec![a];
+        let mut v = a;
+        while v != b {
+            v = *self.nhd[v]
+                .iter()
+                .find(|&&w| self.dist[w][b] + 1 == self.dist[v][b])
+                .unwrap();
+            path.push(v);
+        }
+        path
+    }
+
+    /// Whether a gate acts on at most one qubit, or on two connected qubits
+    ///
+    /// Barriers are always allowed.
+    pub fn supports_gate(&self, g: &Gate) -> bool {
+        match g.qs[..] {
+            _ if g.t == Barrier => true,
+            [] | [_] => true,
+            [a, b] => self.connected(a, b),
+            _ => false,
+        }
+    }
+
+    /// Whether every gate of the circuit is allowed, see [Architecture::supports_gate]
+    pub fn supports(&self, c: &Circuit) -> bool {
+        c.num_qubits() <= self.num_qubits() && c.gates.iter().all(|g| self.supports_gate(g))
+    }
+
+    /// Push a gate on to the end of `c`, routing it if its qubits are not connected
+    ///
+    /// Panics if the gate can't be routed, i.e. if it acts on more than two qubits,
+    /// or on two qubits and isn't a CNOT, CZ or SWAP.
+    pub fn push_routed(&self, c: &mut Circuit, g: Gate) {
+        if self.supports_gate(&g) {
+            c.push(g);
+            return;
+        }
+        let path = match g.qs[..] {
+            [a, b] if g.condition.is_none() => self.shortest_path(a, b),
+            _ => panic!("Cannot route the gate {}", g.to_qasm()),
+        };
+        let k = path.len() - 1;
+        let cnot = |i: usize| Gate::new(CNOT, vec![path[i], path[i + 1]]);
+        match g.t {
+            CNOT | CZ => {
+                if g.t == CZ {
+                    c.push(Gate::new(HAD, vec![path[k]]));
+                }
+                // add the parity of path[start..k] to path[k] and restore the
+                // rest, first for start = 0 and then for start = 1
+                for start in 0..2 {
+                    for i in start..k {
+                        c.push(cnot(i));
+                    }
+                    for i in (start..k - 1).rev() {
+                        c.push(cnot(i));
+                    }
+                }
+                if g.t == CZ {
+                    c.push(Gate::new(HAD, vec![path[k]]));
+                }
+            }
+            SWAP => {
+                let swap = |i: usize| Gate::new(SWAP, vec![path[i], path[i + 1]]);
+                for i in 0..k {
+                    c.push(swap(i));
+                }
+                for i in (0..k - 1).rev() {
+                    c.push(swap(i));
+                }
+            }
+            _ => panic!("Cannot route the gate {}", g.to_qasm()),
+        }
+    }
+
+    /// The circuit with every gate routed, see [Architecture::push_routed]
+    pub fn route(&self, c: &Circuit) -> Circuit {
+        let mut c1 = Circuit::new(c.num_qubits());
+        for g in &c.gates {
+            self.push_routed(&mut c1, g.clone());
+        }
+        c1
+    }
+
+    /// The edges of an approximate minimal Steiner tree on `terminals`, rooted at
+    /// the first one, where `qubits[i]` is the qubit of terminal `i`
+    ///
+    /// This is a minimum spanning tree of the terminals, where the weight of an
+    /// edge is the distance between its qubits in the architecture. The edges are
+    /// returned as `(parent, child)` pairs, with each parent before its children.
+    fn steiner_tree(&self, terminals: &[usize], qubits: &[usize]) -> Vec<(usize, usize)> {
+        let mut edges = vec![];
+        let Some((&root, rest)) = terminals.split_first() else {
+            return edges;
+        };
+        let mut in_tree = vec![root];
+        let mut rest = rest.to_vec();
+        while !rest.is_empty() {
+            let (i, p) = rest
+                .iter()
+                .enumerate()
+                .flat_map(|(i, &t)| in_tree.iter().map(move |&p| (i, p, t)))
+                .min_by_key(|&(_, p, t)| self.distance(qubits[p], qubits[t]))
+                .map(|(i, p, _)| (i, p))
+                .unwrap();
+            let t = rest.swap_remove(i);
+            edges.push((p, t));
+            in_tree.push(t);
+        }
+        edges
+    }
+
+    /// Gaussian elimination, where row `i` of `m` lives on the qubit `qubits[i]`
+    ///
+    /// For each column, the rows with a 1 below the pivot are cleared along a
+    /// Steiner tree, from the leaves towards the pivot row, and then the pivot row
+    /// is added to any earlier rows with a 1 in that column. Rows are never swapped,
+    /// so the result is in reduced echelon form up to a permutation of the rows.
+    /// Each row operation is also applied to `x`. Returns the rank.
+    pub fn gauss(&self, m: &mut Mat2, qubits: &[usize], x: &mut impl RowOps) -> usize {
+        let mut pivoted = vec![false; m.num_rows()];
+        let mut rank = 0;
+        for j in 0..m.num_cols() {
+            let terminals: Vec<usize> = (0..m.num_rows())
+                .filter(|&i| !pivoted[i] && m[(i, j)] == 1)
+                .collect();
+            let Some(&pivot) = terminals.first() else {
+                continue;
+            };
+            // children come after their parents, so clear them in reverse
+            for (p, t) in self.steiner_tree(&terminals, qubits).into_iter().rev() {
+                m.row_add(p, t);
+                x.row_add(p, t);
+            }
+            for i in 0..m.num_rows() {
+                if pivoted[i] && m[(i, j)] == 1 {
+                    m.row_add(pivot, i);
+                    x.row_add(pivot, i);
+                }
+            }
+            pivoted[pivot] = true;
+            rank += 1;
+        }
+        rank
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+
+    #[test]
+    fn routing() {
+        let arch = Architecture::grid(2, 3);
+        assert_eq!(arch.distance(0, 5), 3);
+        assert_eq!(arch.shortest_path(3, 2).len(), 4);
+
+        let c = Circuit::from_qasm(
+            "qreg q[6];
+            h q[0];
+            cx q[0], q[5];
+            t q[5];
+            cz q[2], q[3];
+            swap q[1], q[5];
+            cx q[4], q[3];",
+        )
+        .unwrap();
+        let c1 = arch.route(&c);
+        assert!(arch.supports(&c1));
+        assert!(!arch.supports(&c));
+        assert!(Tensor4::compare(&c, &c1));
+        assert_eq!(c1.num_gates_of_type(CNOT), 8 + 8 + 1);
+        assert_eq!(c1.num_gates_of_type(SWAP), 3);
+    }
+
+    #[test]
+    fn steiner_gauss() {
+        let arch = Architecture::line(4);
+        let m0 = Mat2::new(vec![
+            vec![1, 0, 1, 1],
+            vec![0, 1, 1, 0],
+            vec![1, 1, 1, 0],
+            vec![1, 0, 0, 1],
+        ]);
+        let mut m = m0.clone();
+        let mut ops = Mat2::id(4);
+        assert_eq!(arch.gauss(&mut m, &[0, 1, 2, 3], &mut ops), 4);
+        assert_eq!(m.unit_rows().len(), 4);
+        assert_eq!(&ops * &m0, m);
+
+        // the row operations give CNOTs between distant qubits, which are routed
+        let mut c = Circuit::new(4);
+        arch.gauss(&mut m0.clone(), &[0, 1, 2, 3], &mut c);
+        assert!(Tensor4::compare(&c, &arch.route(&c)));
+    }
+}
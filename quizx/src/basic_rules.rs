@@ -120,14 +120,17 @@ pub fn check_spider_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
 /// assert_ne!(g.to_tensor4(), h.to_tensor4());
 /// ```
 pub fn spider_fusion_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    g.begin_rewrite("spider_fusion", &[v0, v1]);
     for (v, et) in Vec::from_iter(g.incident_edges(v1)) {
         if v != v0 {
             g.add_edge_smart(v0, v, et);
         }
     }
 
+    g.fuse_phases(v0, v1);
     g.add_to_phase(v0, g.phase(v1));
     g.remove_vertex(v1);
+    g.end_rewrite();
 }
 
 /// A checked implementation of the rule
@@ -177,8 +180,9 @@ pub fn check_pi_copy(g: &impl GraphLike, v: V) -> bool {
         _ => return false,
     };
 
-    // No pi-copy on empty spiders.
-    if g.degree(v) == 0 {
+    // No pi-copy on empty spiders, or on symbolic spiders, as the
+    // phase ends up in the scalar.
+    if g.degree(v) == 0 || g.has_symbolic_phase(v) {
         return false;
     }
 
@@ -203,15 +207,18 @@ pub fn check_pi_copy(g: &impl GraphLike, v: V) -> bool {
 /// color and connected by a normal edge. In particular, this means
 /// that this rule can always be applied when the graph is in gh form.
 pub fn pi_copy_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("pi_copy", &[v]);
     // Flip the phase of this node
     let phase = g.phase(v);
     g.scalar_mut().mul_phase(phase);
+    g.phase_negate(v);
     g.set_phase(v, -phase);
 
     // Push a pi to all the surrounding nodes
     for neighbor in g.neighbor_vec(v) {
         g.add_to_phase(neighbor, 1);
     }
+    g.end_rewrite();
 }
 
 checked_rule1!(check_pi_copy, pi_copy_unchecked, pi_copy);
@@ -220,7 +227,10 @@ checked_rule1!(check_pi_copy, pi_copy_unchecked, pi_copy);
 pub fn check_remove_id(g: &impl GraphLike, v: V) -> bool {
     let vt = g.vertex_type(v);
 
-    (vt == VType::Z || vt == VType::X) && g.phase(v).is_zero() && g.degree(v) == 2
+    (vt == VType::Z || vt == VType::X)
+        && g.phase(v).is_zero()
+        && !g.has_symbolic_phase(v)
+        && g.degree(v) == 2
 }
 
 /// Remove an arity-2 spider with phase 0
@@ -230,6 +240,7 @@ pub fn check_remove_id(g: &impl GraphLike, v: V) -> bool {
 /// original 2 edges, namely: {N,N} -> N, {N,H} -> H, and
 /// {H, H} -> N.
 pub fn remove_id_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("remove_id", &[v]);
     let nhd: Vec<(V, EType)> = g.incident_edges(v).collect();
     let new_et = match (nhd[0].1, nhd[1].1) {
         (EType::N, EType::N) => EType::N,
@@ -240,6 +251,7 @@ pub fn remove_id_unchecked(g: &mut impl GraphLike, v: V) {
     };
     g.add_edge_smart(nhd[0].0, nhd[1].0, new_et);
     g.remove_vertex(v);
+    g.end_rewrite();
 }
 
 checked_rule1!(check_remove_id, remove_id_unchecked, remove_id);
@@ -255,11 +267,13 @@ pub fn check_color_change(g: &impl GraphLike, v: V) -> bool {
 /// All of the neighboring edge types are toggled, i.e. N -> H,
 /// H -> N.
 pub fn color_change_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("color_change", &[v]);
     let vt = g.vertex_type(v);
     g.set_vertex_type(v, if vt == VType::X { VType::Z } else { VType::X });
     for w in Vec::from_iter(g.neighbors(v)) {
         g.toggle_edge_type(v, w);
     }
+    g.end_rewrite();
 }
 
 checked_rule1!(check_color_change, color_change_unchecked, color_change);
@@ -271,6 +285,7 @@ checked_rule1!(check_color_change, color_change_unchecked, color_change);
 pub fn check_local_comp(g: &impl GraphLike, v: V) -> bool {
     g.vertex_type(v) == VType::Z
         && g.phase(v).is_proper_clifford()
+        && !g.has_symbolic_phase(v)
         && g.incident_edges(v)
             .all(|(v0, et)| g.vertex_type(v0) == VType::Z && et == EType::H)
 }
@@ -281,6 +296,7 @@ pub fn check_local_comp(g: &impl GraphLike, v: V) -> bool {
 /// other words, it is an N-ary generalization of the Euler
 /// decomposition rule.
 pub fn local_comp_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("local_comp", &[v]);
     let p = g.phase(v);
 
     // add a totally connected graph of the nhd of v
@@ -297,10 +313,53 @@ pub fn local_comp_unchecked(g: &mut impl GraphLike, v: V) {
     g.scalar_mut().mul_sqrt2_pow(((x - 1) * (x - 2)) / 2);
     g.scalar_mut()
         .mul_phase(Rational64::new(*p.to_rational().numer(), 4));
+    g.end_rewrite();
 }
 
 checked_rule1!(check_local_comp, local_comp_unchecked, local_comp);
 
+/// Check [lc_cong_unchecked] applies
+///
+/// The vertex must be a Z spider with at least one neighbour, and be surrounded
+/// by H-edges connected to other Z spiders.
+pub fn check_lc_cong(g: &impl GraphLike, v: V) -> bool {
+    g.vertex_type(v) == VType::Z
+        && g.degree(v) > 0
+        && g.incident_edges(v)
+            .all(|(v0, et)| g.vertex_type(v0) == VType::Z && et == EType::H)
+}
+
+/// Apply a local complementation to a spider with any phase
+///
+/// If the phase is not ±π/2, all of it but π/2 is first unfused into a phase
+/// gadget on the spider, then [local_comp_unchecked] is applied. This does not
+/// reduce the diagram, but it changes which spiders the non-Clifford phases act
+/// on, so afterwards other gadgets might fuse. It is used by the randomised
+/// optimisers in [crate::annealer] and [crate::beam].
+pub fn lc_cong_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("lc_cong", &[v]);
+    let p = g.phase(v);
+    if !p.is_proper_clifford() || g.has_symbolic_phase(v) {
+        let vd = VData {
+            ty: VType::Z,
+            phase: Phase::zero(),
+            row: g.row(v),
+            qubit: g.qubit(v),
+        };
+        let hub = g.add_vertex_with_data(vd);
+        let leaf = g.add_vertex_with_data(vd);
+        g.fuse_phases(leaf, v);
+        g.set_phase(leaf, p - Phase::from((1, 2)));
+        g.set_phase(v, (1, 2));
+        g.add_edge_with_type(v, hub, EType::H);
+        g.add_edge_with_type(hub, leaf, EType::H);
+    }
+    local_comp_unchecked(g, v);
+    g.end_rewrite();
+}
+
+checked_rule1!(check_lc_cong, lc_cong_unchecked, lc_cong);
+
 /// Check [pivot_unchecked] applies
 ///
 /// Both vertices must be Z, have a phase 0 or pi, and be
@@ -311,6 +370,8 @@ pub fn check_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
         && g.edge_type_opt(v0, v1) == Some(EType::H)
         && g.phase(v0).is_pauli()
         && g.phase(v1).is_pauli()
+        && !g.has_symbolic_phase(v0)
+        && !g.has_symbolic_phase(v1)
         && g.incident_edges(v0)
             .all(|(w, et)| g.vertex_type(w) == VType::Z && et == EType::H)
         && g.incident_edges(v1)
@@ -323,6 +384,7 @@ pub fn check_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
 /// effectively a generalised version of the strong complementarity
 /// rule.
 pub fn pivot_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    g.begin_rewrite("pivot", &[v0, v1]);
     let p0 = g.phase(v0);
     let p1 = g.phase(v1);
 
@@ -355,6 +417,7 @@ pub fn pivot_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
     if !p0.is_zero() && !p1.is_zero() {
         g.scalar_mut().mul_phase(Rational64::new(1, 1));
     }
+    g.end_rewrite();
 }
 
 checked_rule2!(check_pivot, pivot_unchecked, pivot);
@@ -383,7 +446,7 @@ fn unfuse_boundary(g: &mut impl GraphLike, v: V, b: V) {
 ///
 /// If the vertex already has a Pauli phase, this is a noop.
 fn unfuse_gadget(g: &mut impl GraphLike, v: V) {
-    if g.phase(v).is_pauli() {
+    if g.phase(v).is_pauli() && !g.has_symbolic_phase(v) {
         return;
     }
     let vd = VData {
@@ -394,6 +457,7 @@ fn unfuse_gadget(g: &mut impl GraphLike, v: V) {
     };
     let v1 = g.add_vertex_with_data(vd);
     let v2 = g.add_vertex_with_data(vd);
+    g.fuse_phases(v2, v);
     g.set_phase(v2, g.phase(v));
     g.set_phase(v, Rational64::zero());
     g.add_edge_with_type(v, v1, EType::H);
@@ -433,14 +497,17 @@ pub fn check_gen_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
 // a phase gadget
 fn is_interior_pauli(g: &impl GraphLike, v: V) -> bool {
     g.phase(v).is_pauli()
+        && !g.has_symbolic_phase(v)
         && g.neighbors(v)
             .all(|n| g.vertex_type(n) == VType::Z && g.degree(n) > 1)
 }
 
-// check that a vertex is interior, has phase 0 or pi, and is not
-// a phase gadget
+// check that a vertex has phase 0 or pi, and is connected to a
+// boundary
 fn is_boundary_pauli(g: &impl GraphLike, v: V) -> bool {
-    g.phase(v).is_pauli() && g.neighbors(v).any(|n| g.vertex_type(n) == VType::B)
+    g.phase(v).is_pauli()
+        && !g.has_symbolic_phase(v)
+        && g.neighbors(v).any(|n| g.vertex_type(n) == VType::B)
 }
 
 /// Check gen_pivot applies and at least one vertex is interior Pauli
@@ -456,6 +523,31 @@ pub fn check_boundary_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
     check_gen_pivot(g, v0, v1) && is_boundary_pauli(g, v0)
 }
 
+/// Check [gen_pivot_unchecked] removes an interior Pauli spider next to a gadget
+///
+/// This requires `v0` to be an interior Pauli spider, and `v1` to be an interior
+/// spider with a non-Pauli phase which is not part of a phase gadget. Pivoting
+/// turns the phase of `v1` into a new phase gadget. This is `pivot_gadget` in PyZX.
+pub fn check_pivot_gadget(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    check_gen_pivot(g, v0, v1)
+        && is_interior_pauli(g, v0)
+        && (!g.phase(v1).is_pauli() || g.has_symbolic_phase(v1))
+        && g.degree(v1) > 1
+        && g.neighbors(v1)
+            .all(|n| g.vertex_type(n) == VType::Z && g.degree(n) > 1)
+}
+
+/// Check [gen_pivot_unchecked] removes an interior Pauli spider next to a boundary
+///
+/// This requires `v0` to be an interior Pauli spider, and `v1` to be connected to
+/// a boundary. Pivoting first unfuses `v1` from the boundary, and from its phase if
+/// it is not Pauli. This is `pivot_boundary` in PyZX.
+pub fn check_pivot_boundary(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    check_gen_pivot(g, v0, v1)
+        && is_interior_pauli(g, v0)
+        && g.neighbors(v1).any(|n| g.vertex_type(n) == VType::B)
+}
+
 /// Generic version of the pivot rule
 ///
 /// This version of the pivoting rule allows either of the vertices
@@ -464,6 +556,7 @@ pub fn check_boundary_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
 /// non-Pauli spiders produce phase gadgets and boundary non-Pauli spiders
 /// produce phase gates on inputs/outputs.
 pub fn gen_pivot_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    g.begin_rewrite("gen_pivot", &[v0, v1]);
     let nhd0 = g.neighbor_vec(v0);
     unfuse_gadget(g, v0);
     for &n in &nhd0 {
@@ -483,10 +576,13 @@ pub fn gen_pivot_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
     //          if remove_id(g, n) { println!("REMOVED EXTRA: {}", n); }
     //      }
     //  }
+    g.end_rewrite();
 }
 
 checked_rule2!(check_gen_pivot, gen_pivot_unchecked, gen_pivot);
 checked_rule2!(check_boundary_pivot, gen_pivot_unchecked, boundary_pivot);
+checked_rule2!(check_pivot_gadget, gen_pivot_unchecked, pivot_gadget);
+checked_rule2!(check_pivot_boundary, gen_pivot_unchecked, pivot_boundary);
 
 pub fn check_gadget_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
     if v0 == v1 {
@@ -525,6 +621,7 @@ pub fn check_gadget_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
 }
 
 pub fn gadget_fusion_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    g.begin_rewrite("gadget_fusion", &[v0, v1]);
     let gphase0 = g
         .neighbors(v0)
         .find(|&n| g.degree(n) == 1)
@@ -533,26 +630,30 @@ pub fn gadget_fusion_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
         .neighbors(v1)
         .find(|&n| g.degree(n) == 1)
         .expect("v1 isn't a gadget");
+    g.fuse_phases(gphase0, gphase1);
     g.add_to_phase(gphase0, g.phase(gphase1));
     g.remove_vertex(v1);
     g.remove_vertex(gphase1);
 
     let d = g.degree(v0) as i32;
     g.scalar_mut().mul_sqrt2_pow(2 - d);
+    g.end_rewrite();
 }
 
 checked_rule2!(check_gadget_fusion, gadget_fusion_unchecked, gadget_fusion);
 
 pub fn check_remove_single(g: &impl GraphLike, v: V) -> bool {
     let t = g.vertex_type(v);
-    g.neighbors(v).len() == 0 && (t == VType::Z || t == VType::X)
+    g.neighbors(v).len() == 0 && (t == VType::Z || t == VType::X) && !g.has_symbolic_phase(v)
 }
 
 /// Remove an isolated Z or X vertex and add it as a global scalar
 pub fn remove_single_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("remove_single", &[v]);
     let p = g.phase(v);
     *g.scalar_mut() *= ScalarN::one_plus_phase(p);
     g.remove_vertex(v);
+    g.end_rewrite();
 }
 
 checked_rule1!(check_remove_single, remove_single_unchecked, remove_single);
@@ -566,10 +667,13 @@ pub fn check_remove_pair(g: &impl GraphLike, v0: V, v1: V) -> bool {
         && (t0 == VType::Z || t0 == VType::X)
         && (t1 == VType::Z || t1 == VType::X)
         && g.connected(v0, v1)
+        && !g.has_symbolic_phase(v0)
+        && !g.has_symbolic_phase(v1)
 }
 
 /// Remove an isolated Z or X vertex and add it as a global scalar
 pub fn remove_pair_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    g.begin_rewrite("remove_pair", &[v0, v1]);
     let t0 = g.vertex_type(v0);
     let t1 = g.vertex_type(v1);
     let et = g.edge_type(v0, v1);
@@ -591,10 +695,320 @@ pub fn remove_pair_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
 
     g.remove_vertex(v0);
     g.remove_vertex(v1);
+    g.end_rewrite();
 }
 
 checked_rule2!(check_remove_pair, remove_pair_unchecked, remove_pair);
 
+/// The color a Z or X spider appears to have through an edge of the given type
+fn color_through(ty: VType, et: EType) -> VType {
+    match (ty, et) {
+        (VType::Z, EType::H) => VType::X,
+        (VType::X, EType::H) => VType::Z,
+        _ => ty,
+    }
+}
+
+/// Check [copy_unchecked] applies
+///
+/// The vertex must be a Z or X spider of arity 1 with phase 0 or pi, whose
+/// neighbour is a spider of the opposite color (taking Hadamard edges into
+/// account). Every other neighbour of that spider must be a boundary, or a
+/// spider of the color the state will have when it arrives.
+pub fn check_copy(g: &impl GraphLike, v: V) -> bool {
+    let vt = g.vertex_type(v);
+    if !(vt == VType::Z || vt == VType::X)
+        || g.degree(v) != 1
+        || !g.phase(v).is_pauli()
+        || g.has_symbolic_phase(v)
+    {
+        return false;
+    }
+
+    let (w, et) = g.incident_edges(v).next().unwrap();
+    let wt = g.vertex_type(w);
+    let st = color_through(vt, et);
+    // the phase of w can end up in the scalar
+    if w == v || !(wt == VType::Z || wt == VType::X) || st == wt || g.has_symbolic_phase(w) {
+        return false;
+    }
+
+    g.incident_edges(w).all(|(n, et)| {
+        let nt = g.vertex_type(n);
+        n == v || (n != w && (nt == VType::B || nt == color_through(st, et)))
+    })
+}
+
+/// Copy a Pauli state through a spider of the opposite color
+///
+/// The arity-1 spider `v` and its neighbour are removed, and a copy of the
+/// state is fused into each of the other neighbours. A boundary neighbour is
+/// instead connected to a new arity-1 spider carrying the copied state.
+pub fn copy_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("copy", &[v]);
+    let (w, et) = g.incident_edges(v).next().unwrap();
+    let st = color_through(g.vertex_type(v), et);
+    let a = g.phase(v);
+    let beta = g.phase(w);
+
+    let nhd: Vec<(V, EType)> = g.incident_edges(w).filter(|&(n, _)| n != v).collect();
+    for &(n, et) in &nhd {
+        if g.vertex_type(n) == VType::B {
+            let u = g.add_vertex_with_data(VData {
+                ty: color_through(st, et),
+                phase: a,
+                qubit: g.qubit(n),
+                row: g.row(w),
+            });
+            g.add_edge(u, n);
+        } else {
+            g.add_to_phase(n, a);
+        }
+    }
+
+    g.remove_vertex(v);
+    g.remove_vertex(w);
+
+    g.scalar_mut().mul_sqrt2_pow(1 - nhd.len() as i32);
+    if a.is_one() {
+        g.scalar_mut().mul_phase(beta);
+    }
+    g.end_rewrite();
+}
+
+checked_rule1!(check_copy, copy_unchecked, copy);
+
+/// Check [hbox_to_edge_unchecked] applies
+///
+/// The vertex must be an arity-2 H-box with the default label -1, connected by
+/// normal or Hadamard edges. If its neighbours are already connected, or are the
+/// same vertex, they must be Z or X spiders, so the parallel edge or self-loop
+/// can be simplified.
+pub fn check_hbox_to_edge(g: &impl GraphLike, v: V) -> bool {
+    if g.vertex_type(v) != VType::H
+        || g.degree(v) != 2
+        || !g.phase(v).is_one()
+        || g.has_symbolic_phase(v)
+    {
+        return false;
+    }
+    let nhd: Vec<(V, EType)> = g.incident_edges(v).collect();
+    if nhd.iter().any(|&(n, et)| n == v || et == EType::Wio) {
+        return false;
+    }
+    let (n0, n1) = (nhd[0].0, nhd[1].0);
+    let is_spider = |n| matches!(g.vertex_type(n), VType::Z | VType::X);
+    !(n0 == n1 || g.connected(n0, n1)) || (is_spider(n0) && is_spider(n1))
+}
+
+/// Replace an arity-2 H-box by a Hadamard edge
+///
+/// The H-box is removed and its neighbours are connected by an edge which is a
+/// Hadamard edge if an odd number of Hadamards (counting the H-box) separated
+/// them, and a normal edge otherwise. In particular, two H-boxes or Hadamard
+/// edges in a row cancel.
+pub fn hbox_to_edge_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("hbox_to_edge", &[v]);
+    let nhd: Vec<(V, EType)> = g.incident_edges(v).collect();
+    let hs = nhd.iter().filter(|&&(_, et)| et == EType::H).count();
+    let et = if hs % 2 == 0 { EType::H } else { EType::N };
+    g.remove_vertex(v);
+    g.add_edge_smart(nhd[0].0, nhd[1].0, et);
+    // the H-box is sqrt(2) times a Hadamard gate
+    g.scalar_mut().mul_sqrt2_pow(1);
+    g.end_rewrite();
+}
+
+checked_rule1!(check_hbox_to_edge, hbox_to_edge_unchecked, hbox_to_edge);
+
+/// Check [hbox_fusion_unchecked] applies
+///
+/// The vertices must be H-boxes connected by a Hadamard edge, at least one of
+/// them with the default label -1, and they must not have any other neighbours
+/// in common.
+pub fn check_hbox_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    v0 != v1
+        && g.vertex_type(v0) == VType::H
+        && g.vertex_type(v1) == VType::H
+        && g.edge_type_opt(v0, v1) == Some(EType::H)
+        && ((g.phase(v0).is_one() && !g.has_symbolic_phase(v0))
+            || (g.phase(v1).is_one() && !g.has_symbolic_phase(v1)))
+        && !g.neighbors(v0).any(|n| n != v1 && g.connected(n, v1))
+}
+
+/// Fuse two H-boxes connected by a Hadamard edge
+///
+/// This is rule (HS1) of the ZH-calculus. The H-box with the default label is
+/// removed, and its other edges are moved to the remaining H-box, which keeps
+/// its label.
+pub fn hbox_fusion_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    g.begin_rewrite("hbox_fusion", &[v0, v1]);
+    let (v0, v1) = if g.phase(v1).is_one() && !g.has_symbolic_phase(v1) {
+        (v0, v1)
+    } else {
+        (v1, v0)
+    };
+    for (n, et) in g.incident_edge_vec(v1) {
+        if n != v0 {
+            g.add_edge_with_type(v0, n, et);
+        }
+    }
+    g.remove_vertex(v1);
+    // the Hadamard edge is 1/sqrt(2) times an arity-2 H-box, and contracting
+    // it with the removed H-box gives a factor of 2
+    g.scalar_mut().mul_sqrt2_pow(1);
+    g.end_rewrite();
+}
+
+checked_rule2!(check_hbox_fusion, hbox_fusion_unchecked, hbox_fusion);
+
+/// Check [hbox_multiply_unchecked] applies
+///
+/// The vertices must be distinct H-boxes, connected by normal edges to the
+/// same Z spiders, and to nothing else.
+pub fn check_hbox_multiply(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    v0 != v1
+        && g.vertex_type(v0) == VType::H
+        && g.vertex_type(v1) == VType::H
+        && g.degree(v0) == g.degree(v1)
+        && !g.has_symbolic_phase(v0)
+        && !g.has_symbolic_phase(v1)
+        && g.incident_edges(v0).all(|(n, et)| {
+            et == EType::N
+                && g.vertex_type(n) == VType::Z
+                && g.edge_type_opt(n, v1) == Some(EType::N)
+        })
+}
+
+/// Multiply two H-boxes with the same neighbours
+///
+/// This is rule (M) of the ZH-calculus: H-boxes with labels a and b on the same
+/// legs combine into a single H-box with label ab. Since labels are stored as
+/// phases, the phase of `v1` is added to `v0` and `v1` is removed.
+pub fn hbox_multiply_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    g.begin_rewrite("hbox_multiply", &[v0, v1]);
+    g.add_to_phase(v0, g.phase(v1));
+    g.remove_vertex(v1);
+    g.end_rewrite();
+}
+
+checked_rule2!(check_hbox_multiply, hbox_multiply_unchecked, hbox_multiply);
+
+/// Returns true if `w` is a neighbour of `v` which is a Z-basis state, i.e. an
+/// arity-1 X spider connected by a normal edge or an arity-1 Z spider connected
+/// by a Hadamard edge, with phase 0 or π
+fn is_basis_state(g: &impl GraphLike, v: V, w: V) -> bool {
+    g.degree(w) == 1
+        && g.phase(w).is_pauli()
+        && !g.has_symbolic_phase(w)
+        && matches!(
+            (g.vertex_type(w), g.edge_type_opt(v, w)),
+            (VType::X, Some(EType::N)) | (VType::Z, Some(EType::H))
+        )
+}
+
+/// Remove an H-box which is constantly 1, plugging each of its legs with a
+/// phase-free Z spider
+fn remove_constant_hbox(g: &mut impl GraphLike, v: V) {
+    for (n, et) in g.incident_edge_vec(v) {
+        let z = g.add_vertex_with_data(VData {
+            ty: VType::Z,
+            phase: Phase::zero(),
+            qubit: g.qubit(v),
+            row: g.row(v),
+        });
+        g.add_edge_with_type(z, n, et);
+    }
+    g.remove_vertex(v);
+}
+
+/// Check [hbox_absorb_unchecked] applies
+///
+/// The vertex must be an H-box with label 1, or an H-box with a Z-basis state
+/// on one of its legs.
+pub fn check_hbox_absorb(g: &impl GraphLike, v: V) -> bool {
+    g.vertex_type(v) == VType::H
+        && !g.has_symbolic_phase(v)
+        && (g.phase(v).is_zero() || g.neighbors(v).any(|w| is_basis_state(g, v, w)))
+}
+
+/// Absorb a Z-basis state into an H-box
+///
+/// An H-box with |0> plugged into one of its legs, or with label 1, is the
+/// all-ones tensor, so it is removed and each of its other legs is plugged with
+/// a phase-free Z spider. Plugging |1> into an H-box just removes that leg, and
+/// an H-box with no legs left is replaced by its label, as a scalar.
+pub fn hbox_absorb_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("hbox_absorb", &[v]);
+    let w = g.neighbors(v).find(|&w| is_basis_state(g, v, w));
+    if let Some(w) = w {
+        // the basis state is sqrt(2) times |0> or |1>
+        g.scalar_mut().mul_sqrt2_pow(1);
+        let one = g.phase(w).is_one();
+        g.remove_vertex(w);
+        if one && !g.phase(v).is_zero() {
+            if g.degree(v) == 0 {
+                let p = g.phase(v);
+                g.scalar_mut().mul_phase(p);
+                g.remove_vertex(v);
+            }
+            g.end_rewrite();
+            return;
+        }
+    }
+    remove_constant_hbox(g, v);
+    g.end_rewrite();
+}
+
+checked_rule1!(check_hbox_absorb, hbox_absorb_unchecked, hbox_absorb);
+
+/// Returns the NOT between `v` and one of its Z spider neighbours, if there is one
+///
+/// A NOT is an arity-2 X spider with phase π and normal edges, or an arity-2 Z
+/// spider with phase π and Hadamard edges.
+fn hbox_ortho_not(g: &impl GraphLike, v: V) -> Option<V> {
+    g.incident_edges(v).find_map(|(n, et)| {
+        let not = g.degree(n) == 2
+            && g.phase(n).is_one()
+            && !g.has_symbolic_phase(n)
+            && match g.vertex_type(n) {
+                VType::X => g.incident_edges(n).all(|(_, et)| et == EType::N),
+                VType::Z => g.incident_edges(n).all(|(_, et)| et == EType::H),
+                _ => false,
+            };
+        if !not || (et == EType::N) != (g.vertex_type(n) == VType::X) {
+            return None;
+        }
+        let x = g.neighbors(n).find(|&x| x != v)?;
+        (g.vertex_type(x) == VType::Z && g.edge_type_opt(v, x) == Some(EType::N)).then_some(n)
+    })
+}
+
+/// Check [hbox_ortho_unchecked] applies
+///
+/// The vertex must be an H-box connected to some Z spider both directly, by a
+/// normal edge, and through a NOT.
+pub fn check_hbox_ortho(g: &impl GraphLike, v: V) -> bool {
+    g.vertex_type(v) == VType::H && !g.has_symbolic_phase(v) && hbox_ortho_not(g, v).is_some()
+}
+
+/// Remove an H-box connected to a Z spider both directly and through a NOT
+///
+/// The two legs always carry orthogonal values x and ¬x, so the product of the
+/// H-box inputs is 0, and the H-box is the all-ones tensor. It is removed along
+/// with the NOT, and its other legs are plugged with phase-free Z spiders.
+pub fn hbox_ortho_unchecked(g: &mut impl GraphLike, v: V) {
+    g.begin_rewrite("hbox_ortho", &[v]);
+    let not = hbox_ortho_not(g, v).unwrap();
+    let x = g.neighbors(not).find(|&x| x != v).unwrap();
+    g.remove_vertex(not);
+    g.remove_edge(v, x);
+    remove_constant_hbox(g, v);
+    g.end_rewrite();
+}
+
+checked_rule1!(check_hbox_ortho, hbox_ortho_unchecked, hbox_ortho);
+
 // Tests {{{
 
 #[cfg(test)]
@@ -910,6 +1324,54 @@ mod tests {
         assert_eq!(g.to_tensor4(), h.to_tensor4());
     }
 
+    #[test]
+    fn pivot_gadget_and_boundary() {
+        // an interior Pauli spider 1, connected to an interior T spider 2 and
+        // to a spider 3 on the output
+        let mut g = Graph::new();
+        let vs: Vec<V> = (0..8).map(|_| g.add_vertex(VType::Z)).collect();
+        g.set_vertex_type(vs[0], VType::B);
+        g.set_vertex_type(vs[7], VType::B);
+        g.set_phase(vs[1], Rational64::new(1, 1));
+        g.set_phase(vs[2], Rational64::new(1, 4));
+        g.set_phase(vs[3], Rational64::new(3, 4));
+        g.add_edge(vs[0], vs[4]);
+        for &(s, t) in &[
+            (4, 1),
+            (4, 2),
+            (1, 2),
+            (1, 3),
+            (2, 5),
+            (5, 6),
+            (6, 3),
+            (1, 6),
+        ] {
+            g.add_edge_with_type(vs[s], vs[t], EType::H);
+        }
+        g.add_edge(vs[3], vs[7]);
+        g.set_inputs(vec![vs[0]]);
+        g.set_outputs(vec![vs[7]]);
+
+        assert!(check_pivot_gadget(&g, vs[1], vs[2]));
+        assert!(!check_pivot_gadget(&g, vs[2], vs[1]));
+        assert!(!check_pivot_gadget(&g, vs[1], vs[3]));
+        assert!(check_pivot_boundary(&g, vs[1], vs[3]));
+        assert!(!check_pivot_boundary(&g, vs[1], vs[2]));
+        assert!(!check_pivot_boundary(&g, vs[3], vs[1]));
+
+        let mut h = g.clone();
+        assert!(pivot_gadget(&mut h, vs[1], vs[2]));
+        assert!(!h.contains_vertex(vs[1]));
+        assert_eq!(h.tcount(), g.tcount());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        let mut h = g.clone();
+        assert!(pivot_boundary(&mut h, vs[1], vs[3]));
+        assert!(!h.contains_vertex(vs[1]));
+        assert_eq!(h.tcount(), g.tcount());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
     #[test]
     fn gadget_fusion_1() {
         // fuse gadgets of various sizes
@@ -1026,6 +1488,219 @@ mod tests {
         pi_copy(&mut g, vs[4]);
         assert_eq!(g.to_tensor4(), h.to_tensor4());
     }
+
+    #[test]
+    fn copy_state() {
+        // a pi state on a Z spider, with an H-edge to a Z spider
+        let mut g = Graph::new();
+        let v = g.add_vertex_with_phase(VType::Z, 1);
+        let w = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let n0 = g.add_vertex_with_phase(VType::Z, (1, 2));
+        let n1 = g.add_vertex_with_phase(VType::X, (1, 4));
+        let b0 = g.add_vertex(VType::B);
+        let b1 = g.add_vertex(VType::B);
+        let b2 = g.add_vertex(VType::B);
+        g.add_edge_with_type(v, w, EType::H);
+        g.add_edge_with_type(w, n0, EType::H);
+        g.add_edge(w, n1);
+        g.add_edge_with_type(w, b0, EType::H);
+        g.add_edge(n0, b1);
+        g.add_edge(n1, b2);
+        g.set_outputs(vec![b0, b1, b2]);
+
+        assert!(check_copy(&g, v));
+        assert!(!check_copy(&g, n0));
+        let h = g.clone();
+        copy_unchecked(&mut g, v);
+        assert_eq!(g.num_vertices(), 6);
+        assert_eq!(g.phase(n0), Rational64::new(3, 2).into());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        // a zero state on an X spider, with a normal edge to a Z spider
+        let mut g = Graph::new();
+        let v = g.add_vertex(VType::X);
+        let w = g.add_vertex_with_phase(VType::Z, (3, 4));
+        let n0 = g.add_vertex(VType::X);
+        let b0 = g.add_vertex(VType::B);
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(v, w);
+        g.add_edge(w, n0);
+        g.add_edge(w, b0);
+        g.add_edge(n0, b1);
+        g.set_outputs(vec![b0, b1]);
+
+        let h = g.clone();
+        assert!(super::copy(&mut g, v));
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        // the neighbour has the wrong color
+        let mut g = Graph::new();
+        let v = g.add_vertex(VType::Z);
+        let w = g.add_vertex(VType::X);
+        let n0 = g.add_vertex(VType::Z);
+        g.add_edge(v, w);
+        g.add_edge_with_type(w, n0, EType::H);
+        assert!(!check_copy(&g, v));
+    }
+
+    #[test]
+    fn hbox_edges() {
+        let mut g = Graph::new();
+        let b0 = g.add_vertex(VType::B);
+        let z0 = g.add_vertex(VType::Z);
+        let h0 = g.add_vertex_with_phase(VType::H, 1);
+        let h1 = g.add_vertex_with_phase(VType::H, 1);
+        let h2 = g.add_vertex_with_phase(VType::H, 1);
+        let z1 = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(b0, z0);
+        g.add_edge(z0, h0);
+        g.add_edge(h0, h1);
+        g.add_edge_with_type(h1, h2, EType::H);
+        g.add_edge(h2, z1);
+        g.add_edge(z1, b1);
+        g.set_inputs(vec![b0]);
+        g.set_outputs(vec![b1]);
+
+        assert!(!check_hbox_to_edge(&g, z0));
+        assert!(hbox_to_edge(&mut g, h0));
+        assert!(hbox_to_edge(&mut g, h1));
+        assert!(hbox_to_edge(&mut g, h2));
+        assert_eq!(g.num_vertices(), 4);
+        assert_eq!(g.edge_type(z0, z1), EType::N);
+
+        // the four Hadamards cancel, up to the scalar of each H-box
+        let mut h = Graph::new();
+        let b0 = h.add_vertex(VType::B);
+        let z0 = h.add_vertex(VType::Z);
+        let z1 = h.add_vertex_with_phase(VType::Z, (1, 4));
+        let b1 = h.add_vertex(VType::B);
+        h.add_edge(b0, z0);
+        h.add_edge(z0, z1);
+        h.add_edge(z1, b1);
+        h.set_inputs(vec![b0]);
+        h.set_outputs(vec![b1]);
+        h.scalar_mut().mul_sqrt2_pow(3);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    /// A graph with a phase-free Z spider on each of `n` wires
+    fn zh_wires(n: usize) -> (Graph, Vec<V>) {
+        let mut g = Graph::new();
+        let mut zs = vec![];
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        for _ in 0..n {
+            let i = g.add_vertex(VType::B);
+            let z = g.add_vertex(VType::Z);
+            let o = g.add_vertex(VType::B);
+            g.add_edge(i, z);
+            g.add_edge(z, o);
+            zs.push(z);
+            inputs.push(i);
+            outputs.push(o);
+        }
+        g.set_inputs(inputs);
+        g.set_outputs(outputs);
+        (g, zs)
+    }
+
+    #[test]
+    fn hbox_fusion_multiply() {
+        let (mut g, zs) = zh_wires(3);
+        let h0 = g.add_vertex_with_phase(VType::H, (1, 2));
+        let h1 = g.add_vertex_with_phase(VType::H, 1);
+        g.add_edge(h0, zs[0]);
+        g.add_edge(h0, zs[1]);
+        g.add_edge(h1, zs[2]);
+        g.add_edge_with_type(h0, h1, EType::H);
+        let t = g.to_tensor4();
+
+        assert!(!check_hbox_fusion(&g, h0, zs[0]));
+        // the H-box with the default label is the one removed
+        assert!(hbox_fusion(&mut g, h1, h0));
+        assert!(!g.contains_vertex(h1));
+        assert!(g.connected(h0, zs[2]));
+        assert_eq!(g.phase(h0), Phase::from((1, 2)));
+        assert_eq!(t, g.to_tensor4());
+
+        let (mut g, zs) = zh_wires(2);
+        let h0 = g.add_vertex_with_phase(VType::H, (1, 2));
+        let h1 = g.add_vertex_with_phase(VType::H, (1, 4));
+        let h2 = g.add_vertex_with_phase(VType::H, 1);
+        for &z in &zs {
+            g.add_edge(h0, z);
+            g.add_edge(h1, z);
+        }
+        g.add_edge(h2, zs[0]);
+        let t = g.to_tensor4();
+
+        assert!(!check_hbox_multiply(&g, h0, h2));
+        assert!(hbox_multiply(&mut g, h0, h1));
+        assert!(!g.contains_vertex(h1));
+        assert_eq!(g.phase(h0), Phase::from((3, 4)));
+        assert_eq!(t, g.to_tensor4());
+    }
+
+    #[test]
+    fn hbox_absorb_ortho() {
+        // plugging |1> removes a leg
+        let (mut g, zs) = zh_wires(2);
+        let h = g.add_vertex_with_phase(VType::H, (1, 2));
+        let one = g.add_vertex_with_phase(VType::X, 1);
+        g.add_edge(h, zs[0]);
+        g.add_edge(h, zs[1]);
+        g.add_edge(h, one);
+        let t = g.to_tensor4();
+        assert!(hbox_absorb(&mut g, h));
+        assert!(!g.contains_vertex(one));
+        assert_eq!(g.degree(h), 2);
+        assert_eq!(t, g.to_tensor4());
+
+        // plugging |0> removes the H-box
+        let zero = g.add_vertex(VType::Z);
+        g.add_edge_with_type(h, zero, EType::H);
+        let t = g.to_tensor4();
+        assert!(hbox_absorb(&mut g, h));
+        assert!(!g.contains_vertex(h));
+        assert_eq!(t, g.to_tensor4());
+
+        // an H-box with no legs left is a scalar
+        let mut g = Graph::new();
+        let h = g.add_vertex_with_phase(VType::H, (1, 2));
+        let one = g.add_vertex_with_phase(VType::Z, 1);
+        g.add_edge_with_type(h, one, EType::H);
+        let t = g.to_tensor4();
+        assert!(hbox_absorb(&mut g, h));
+        assert_eq!(g.num_vertices(), 0);
+        assert_eq!(t, g.to_tensor4());
+
+        // an H-box with label 1 is constant
+        let (mut g, zs) = zh_wires(2);
+        let h = g.add_vertex(VType::H);
+        g.add_edge(h, zs[0]);
+        g.add_edge_with_type(h, zs[1], EType::H);
+        let t = g.to_tensor4();
+        assert!(!check_hbox_absorb(&g, zs[0]));
+        assert!(hbox_absorb(&mut g, h));
+        assert_eq!(g.num_vertices_of_type(VType::H), 0);
+        assert_eq!(t, g.to_tensor4());
+
+        // x AND NOT x is always 0
+        let (mut g, zs) = zh_wires(2);
+        let h = g.add_vertex_with_phase(VType::H, (1, 2));
+        let not = g.add_vertex_with_phase(VType::X, 1);
+        g.add_edge(h, zs[0]);
+        g.add_edge(h, zs[1]);
+        g.add_edge(zs[0], not);
+        g.add_edge(not, h);
+        let t = g.to_tensor4();
+        assert!(!check_hbox_ortho(&g, zs[0]));
+        assert!(hbox_ortho(&mut g, h));
+        assert_eq!(g.num_vertices_of_type(VType::H), 0);
+        assert_eq!(g.num_vertices_of_type(VType::X), 0);
+        assert_eq!(t, g.to_tensor4());
+    }
 }
 
 // }}}
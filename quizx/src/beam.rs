@@ -0,0 +1,252 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Beam search over rewrite sequences
+//!
+//! Starting from a graph-like diagram, e.g. the output of
+//! [crate::simplify::clifford_simp], [BeamSearch] applies every available
+//! local complementation congruence ([lc_cong_unchecked]) and generalised pivot
+//! ([gen_pivot_unchecked]) to each diagram in the beam, and keeps the `width`
+//! cheapest results for the next round. The cheapest diagram seen overall is
+//! returned. Unlike [crate::annealer], this is deterministic for a given seed, and
+//! explores several sequences of rewrites side by side.
+//!
+//! ```
+//! # use quizx::beam::*;
+//! # use quizx::circuit::Circuit;
+//! # use quizx::simplify::*;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::random().seed(1).qubits(4).depth(30).p_t(0.3).with_cliffords().build();
+//! let mut g: Graph = c.to_graph();
+//! clifford_simp(&mut g);
+//! let (mut h, cost) = BeamSearch::new().width(4).depth(3).cost(Cost::TCount).run(&g);
+//! assert!(cost <= Cost::TCount.cost(&g));
+//! full_simp(&mut h);
+//! ```
+
+use crate::basic_rules::*;
+use crate::graph::*;
+use crate::simplify::gadget_simp;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+/// The cost which [BeamSearch] minimises
+///
+/// All costs are computed after fusing phase gadgets with [gadget_simp].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cost {
+    /// The number of non-Clifford phases
+    TCount,
+    /// The number of edges between non-boundary spiders, which estimates the
+    /// number of two-qubit gates in the extracted circuit
+    TwoQubit,
+    /// The number of vertices
+    Vertices,
+}
+
+impl Cost {
+    /// The cost of a graph-like diagram
+    pub fn cost(&self, g: &impl GraphLike) -> usize {
+        let mut h = g.clone();
+        gadget_simp(&mut h);
+        match self {
+            Cost::TCount => h.tcount(),
+            Cost::TwoQubit => h
+                .edges()
+                .filter(|&(s, t, _)| h.vertex_type(s) != VType::B && h.vertex_type(t) != VType::B)
+                .count(),
+            Cost::Vertices => h.num_vertices(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Move {
+    LcCong(V),
+    GenPivot(V, V),
+}
+
+/// A beam search over sequences of rewrites, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct BeamSearch {
+    width: usize,
+    depth: usize,
+    branch: usize,
+    cost: Cost,
+    seed: u64,
+}
+
+impl Default for BeamSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BeamSearch {
+    pub fn new() -> Self {
+        BeamSearch {
+            width: 8,
+            depth: 10,
+            branch: 32,
+            cost: Cost::TCount,
+            seed: 1337,
+        }
+    }
+
+    /// The number of diagrams kept after each round
+    pub fn width(&mut self, width: usize) -> &mut Self {
+        self.width = width;
+        self
+    }
+
+    /// The maximum number of rounds
+    pub fn depth(&mut self, depth: usize) -> &mut Self {
+        self.depth = depth;
+        self
+    }
+
+    /// The maximum number of rewrites tried on each diagram in a round
+    ///
+    /// If there are more places to rewrite, a random subset is tried.
+    pub fn branch(&mut self, branch: usize) -> &mut Self {
+        self.branch = branch;
+        self
+    }
+
+    pub fn cost(&mut self, cost: Cost) -> &mut Self {
+        self.cost = cost;
+        self
+    }
+
+    /// The seed for choosing rewrites when there are more than `branch` of them
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    fn moves(&self, g: &impl GraphLike, rng: &mut StdRng) -> Vec<Move> {
+        let mut moves: Vec<Move> = g
+            .vertices()
+            .filter(|&v| check_lc_cong(g, v))
+            .map(Move::LcCong)
+            .collect();
+        moves.extend(
+            g.edges()
+                .filter(|&(s, t, _)| check_gen_pivot(g, s, t))
+                .map(|(s, t, _)| Move::GenPivot(s, t)),
+        );
+        if moves.len() > self.branch {
+            moves.shuffle(rng);
+            moves.truncate(self.branch);
+        }
+        moves
+    }
+
+    /// Run the search from `g`, returning the cheapest diagram found and its cost
+    ///
+    /// The result is equal to `g`, and still graph-like, but it can have many
+    /// unfused phase gadgets, so it should be simplified with
+    /// [crate::simplify::full_simp] before extraction.
+    pub fn run<G: GraphLike>(&self, g: &G) -> (G, usize) {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut best = (g.clone(), self.cost.cost(g));
+        let mut beam = vec![best.clone()];
+
+        for _ in 0..self.depth {
+            let candidates: Vec<(&G, Move)> = beam
+                .iter()
+                .flat_map(|(h, _)| {
+                    self.moves(h, &mut rng)
+                        .into_iter()
+                        .map(move |m| (h, m))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            if candidates.is_empty() {
+                break;
+            }
+
+            let mut next: Vec<(G, usize)> = candidates
+                .into_par_iter()
+                .map(|(h, m)| {
+                    let mut h = h.clone();
+                    match m {
+                        Move::LcCong(v) => lc_cong_unchecked(&mut h, v),
+                        Move::GenPivot(v0, v1) => gen_pivot_unchecked(&mut h, v0, v1),
+                    }
+                    let c = self.cost.cost(&h);
+                    (h, c)
+                })
+                .collect();
+            next.sort_by_key(|&(_, c)| c);
+            next.truncate(self.width);
+
+            if next[0].1 < best.1 {
+                best = next[0].clone();
+            }
+            beam = next;
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::extract::ToCircuit;
+    use crate::simplify::*;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn beam_search() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.3)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        clifford_simp(&mut g);
+
+        for cost in [Cost::TCount, Cost::TwoQubit, Cost::Vertices] {
+            let (h, c) = BeamSearch::new()
+                .width(3)
+                .depth(3)
+                .branch(8)
+                .cost(cost)
+                .run(&g);
+            assert!(c <= cost.cost(&g));
+            assert_eq!(c, cost.cost(&h));
+            assert!(h.is_graph_like());
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+            let mut h = h;
+            full_simp(&mut h);
+            assert!(h.to_circuit().is_ok());
+        }
+
+        // the same seed gives the same result
+        let (h0, _) = BeamSearch::new().depth(2).seed(5).run(&g);
+        let (h1, _) = BeamSearch::new().depth(2).seed(5).run(&g);
+        assert_eq!(h0, h1);
+    }
+}
@@ -0,0 +1,495 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compact binary encoding of graphs.
+//!
+//! This is intended for checkpointing and caching large numbers of diagrams, where
+//! json is too slow and too big. Integers are written as LEB128 varints (signed values
+//! are zigzag-encoded first), so a typical vertex takes 5-6 bytes and an edge 3-5 bytes.
+//!
+//! Vertex names are not preserved: vertices are renumbered densely in the order they
+//! are iterated. Everything else, i.e. vertex data, edge types, the order of inputs and
+//! outputs, their qubit indices, and the scalar, survives a round trip exactly.
+//!
+//! Encoded graphs are self-delimiting, so several of them can be concatenated into one
+//! buffer with [encode_graph_into] and read back one at a time with [decode_graph_from].
+//!
+//! ```
+//! # use quizx::graph::*;
+//! # use quizx::vec_graph::Graph;
+//! let mut g = Graph::new();
+//! let v0 = g.add_vertex(VType::B);
+//! let v1 = g.add_vertex_with_phase(VType::Z, (1, 4));
+//! let v2 = g.add_vertex(VType::B);
+//! g.add_edge(v0, v1);
+//! g.add_edge_with_type(v1, v2, EType::H);
+//! g.set_inputs(vec![v0]);
+//! g.set_outputs(vec![v2]);
+//!
+//! let bytes = quizx::binary::encode_graph(&g);
+//! let h: Graph = quizx::binary::decode_graph(&bytes).unwrap();
+//! assert_eq!(g, h);
+//! ```
+
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use num::{Complex, Rational64};
+use rustc_hash::FxHashMap;
+use std::path::Path;
+
+/// Marks the start of an encoded graph.
+const MAGIC: &[u8; 4] = b"QZXB";
+
+/// The current version of the encoding.
+const VERSION: u8 = 2;
+
+const SCALAR_EXACT: u8 = 0;
+const SCALAR_FLOAT: u8 = 1;
+
+/// An error that can occur when decoding a binary graph.
+#[derive(Debug, thiserror::Error)]
+pub enum BinaryError {
+    /// The input ended in the middle of a graph.
+    #[error("Unexpected end of input")]
+    UnexpectedEof,
+    /// The input does not start with the expected magic bytes.
+    #[error("Input is not a binary-encoded graph")]
+    BadMagic,
+    /// The graph was written by an incompatible version of the encoder.
+    #[error("Unsupported encoding version {0}")]
+    UnsupportedVersion(u8),
+    /// A varint was too long for its type.
+    #[error("Integer overflow while decoding")]
+    Overflow,
+    /// Found an unknown vertex type.
+    #[error("Got an invalid vertex type {0}")]
+    InvalidVertexType(u8),
+    /// Found an unknown edge type.
+    #[error("Got an invalid edge type {0}")]
+    InvalidEdgeType(u8),
+    /// Found an unknown scalar tag.
+    #[error("Got an invalid scalar tag {0}")]
+    InvalidScalar(u8),
+    /// Found a phase with a zero denominator.
+    #[error("Got a phase with zero denominator")]
+    InvalidPhase,
+    /// An edge or boundary refers to a vertex that does not exist.
+    #[error("Reference to unknown vertex {0}")]
+    UnknownVertex(usize),
+    /// Error reading or writing a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+fn write_uvarint(buf: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        buf.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+    buf.push(x as u8);
+}
+
+fn write_ivarint(buf: &mut Vec<u8>, x: i64) {
+    write_uvarint(buf, ((x << 1) ^ (x >> 63)) as u64);
+}
+
+fn vtype_to_byte(ty: VType) -> u8 {
+    match ty {
+        VType::B => 0,
+        VType::Z => 1,
+        VType::X => 2,
+        VType::H => 3,
+        VType::WInput => 4,
+        VType::WOutput => 5,
+        VType::ZBox => 6,
+    }
+}
+
+fn vtype_from_byte(b: u8) -> Result<VType, BinaryError> {
+    match b {
+        0 => Ok(VType::B),
+        1 => Ok(VType::Z),
+        2 => Ok(VType::X),
+        3 => Ok(VType::H),
+        4 => Ok(VType::WInput),
+        5 => Ok(VType::WOutput),
+        6 => Ok(VType::ZBox),
+        _ => Err(BinaryError::InvalidVertexType(b)),
+    }
+}
+
+fn etype_to_byte(ty: EType) -> u8 {
+    match ty {
+        EType::N => 0,
+        EType::H => 1,
+        EType::Wio => 2,
+    }
+}
+
+fn etype_from_byte(b: u8) -> Result<EType, BinaryError> {
+    match b {
+        0 => Ok(EType::N),
+        1 => Ok(EType::H),
+        2 => Ok(EType::Wio),
+        _ => Err(BinaryError::InvalidEdgeType(b)),
+    }
+}
+
+/// A cursor over a byte slice, used for decoding
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, BinaryError> {
+        let (&b, rest) = self.bytes.split_first().ok_or(BinaryError::UnexpectedEof)?;
+        self.bytes = rest;
+        Ok(b)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BinaryError> {
+        if self.bytes.len() < n {
+            return Err(BinaryError::UnexpectedEof);
+        }
+        let (b, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(b)
+    }
+
+    fn uvarint(&mut self) -> Result<u64, BinaryError> {
+        let mut x = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift > 63 || (shift == 63 && b > 1) {
+                return Err(BinaryError::Overflow);
+            }
+            x |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(x);
+            }
+            shift += 7;
+        }
+    }
+
+    fn ivarint(&mut self) -> Result<i64, BinaryError> {
+        let x = self.uvarint()?;
+        Ok(((x >> 1) as i64) ^ -((x & 1) as i64))
+    }
+
+    fn usize(&mut self) -> Result<usize, BinaryError> {
+        usize::try_from(self.uvarint()?).map_err(|_| BinaryError::Overflow)
+    }
+
+    fn i32(&mut self) -> Result<i32, BinaryError> {
+        i32::try_from(self.ivarint()?).map_err(|_| BinaryError::Overflow)
+    }
+
+    fn f64(&mut self) -> Result<f64, BinaryError> {
+        let b = self.take(8)?;
+        Ok(f64::from_le_bytes(b.try_into().unwrap()))
+    }
+}
+
+fn write_scalar(buf: &mut Vec<u8>, s: &ScalarN) {
+    match s {
+        Scalar::Exact(pow, coeffs) => {
+            buf.push(SCALAR_EXACT);
+            write_ivarint(buf, *pow as i64);
+            write_uvarint(buf, coeffs.len() as u64);
+            for &c in coeffs {
+                write_ivarint(buf, c as i64);
+            }
+        }
+        Scalar::Float(c) => {
+            buf.push(SCALAR_FLOAT);
+            buf.extend_from_slice(&c.re.to_le_bytes());
+            buf.extend_from_slice(&c.im.to_le_bytes());
+        }
+    }
+}
+
+fn read_scalar(r: &mut Reader) -> Result<ScalarN, BinaryError> {
+    match r.byte()? {
+        SCALAR_EXACT => {
+            let pow = r.i32()?;
+            let len = r.usize()?;
+            let coeffs = (0..len)
+                .map(|_| {
+                    let c = r.ivarint()?;
+                    isize::try_from(c).map_err(|_| BinaryError::Overflow)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Scalar::Exact(pow, coeffs))
+        }
+        SCALAR_FLOAT => {
+            let re = r.f64()?;
+            let im = r.f64()?;
+            Ok(Scalar::Float(Complex::new(re, im)))
+        }
+        t => Err(BinaryError::InvalidScalar(t)),
+    }
+}
+
+/// Appends the binary encoding of a graph to a buffer.
+pub fn encode_graph_into(graph: &impl GraphLike, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(MAGIC);
+    buf.push(VERSION);
+
+    let mut index: FxHashMap<V, u64> = FxHashMap::default();
+    write_uvarint(buf, graph.num_vertices() as u64);
+    for (i, v) in graph.vertices().enumerate() {
+        index.insert(v, i as u64);
+        let d = graph.vertex_data(v);
+        let p = d.phase.to_rational();
+        buf.push(vtype_to_byte(d.ty));
+        write_ivarint(buf, *p.numer());
+        write_uvarint(buf, *p.denom() as u64);
+        write_ivarint(buf, d.qubit as i64);
+        write_ivarint(buf, d.row as i64);
+    }
+
+    write_uvarint(buf, graph.num_edges() as u64);
+    for (s, t, et) in graph.edges() {
+        write_uvarint(buf, index[&s]);
+        write_uvarint(buf, index[&t]);
+        buf.push(etype_to_byte(et));
+    }
+
+    // each boundary is followed by its qubit index plus one, or 0 if it has none
+    for bs in [graph.inputs(), graph.outputs()] {
+        write_uvarint(buf, bs.len() as u64);
+        for b in bs {
+            write_uvarint(buf, index[b]);
+            write_uvarint(buf, graph.boundary_qubit(*b).map_or(0, |q| q as u64 + 1));
+        }
+    }
+
+    write_scalar(buf, graph.scalar());
+}
+
+/// Returns the binary encoding of a graph.
+pub fn encode_graph(graph: &impl GraphLike) -> Vec<u8> {
+    // each vertex and edge take a handful of bytes in the common case
+    let mut buf = Vec::with_capacity(16 + 6 * graph.num_vertices() + 4 * graph.num_edges());
+    encode_graph_into(graph, &mut buf);
+    buf
+}
+
+/// Decodes a single graph from the front of a byte slice.
+///
+/// On success, `bytes` is advanced past the graph that was read.
+pub fn decode_graph_from<G: GraphLike>(bytes: &mut &[u8]) -> Result<G, BinaryError> {
+    let mut r = Reader { bytes };
+    if r.take(MAGIC.len())? != MAGIC {
+        return Err(BinaryError::BadMagic);
+    }
+    let version = r.byte()?;
+    if version != VERSION {
+        return Err(BinaryError::UnsupportedVersion(version));
+    }
+
+    let mut graph = G::new();
+    let numv = r.usize()?;
+    let mut vs = Vec::with_capacity(numv.min(r.bytes.len()));
+    for _ in 0..numv {
+        let ty = vtype_from_byte(r.byte()?)?;
+        let numer = r.ivarint()?;
+        let denom = i64::try_from(r.uvarint()?).map_err(|_| BinaryError::Overflow)?;
+        if denom == 0 {
+            return Err(BinaryError::InvalidPhase);
+        }
+        let qubit = r.i32()?;
+        let row = r.i32()?;
+        vs.push(graph.add_vertex_with_data(VData {
+            ty,
+            phase: Phase::new(Rational64::new(numer, denom)),
+            qubit,
+            row,
+        }));
+    }
+
+    let vertex = |i: usize| vs.get(i).copied().ok_or(BinaryError::UnknownVertex(i));
+
+    let nume = r.usize()?;
+    for _ in 0..nume {
+        let s = vertex(r.usize()?)?;
+        let t = vertex(r.usize()?)?;
+        let et = etype_from_byte(r.byte()?)?;
+        graph.add_edge_with_type(s, t, et);
+    }
+
+    let mut boundaries = [vec![], vec![]];
+    for bs in boundaries.iter_mut() {
+        let len = r.usize()?;
+        for _ in 0..len {
+            let b = vertex(r.usize()?)?;
+            bs.push((b, r.usize()?.checked_sub(1)));
+        }
+    }
+    let [inputs, outputs] = boundaries;
+    graph.set_inputs(inputs.iter().map(|&(b, _)| b).collect());
+    graph.set_outputs(outputs.iter().map(|&(b, _)| b).collect());
+    for (b, q) in inputs {
+        if let Some(q) = q {
+            graph.set_input_qubit(b, q);
+        }
+    }
+    for (b, q) in outputs {
+        if let Some(q) = q {
+            graph.set_output_qubit(b, q);
+        }
+    }
+
+    *graph.scalar_mut() = read_scalar(&mut r)?;
+
+    *bytes = r.bytes;
+    Ok(graph)
+}
+
+/// Decodes a graph from its binary encoding.
+pub fn decode_graph<G: GraphLike>(bytes: &[u8]) -> Result<G, BinaryError> {
+    let mut bytes = bytes;
+    decode_graph_from(&mut bytes)
+}
+
+/// Writes the binary encoding of a graph to a file.
+pub fn write_graph(graph: &impl GraphLike, filename: &Path) -> Result<(), BinaryError> {
+    std::fs::write(filename, encode_graph(graph))?;
+    Ok(())
+}
+
+/// Reads a graph from a file containing its binary encoding.
+pub fn read_graph<G: GraphLike>(filename: &Path) -> Result<G, BinaryError> {
+    let bytes = std::fs::read(filename)?;
+    decode_graph(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::full_simp;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+    use num::{One, Zero};
+
+    fn random_graph() -> Graph {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(50)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        full_simp(&mut g);
+        g
+    }
+
+    #[test]
+    fn binary_roundtrip() {
+        let mut g = random_graph();
+        g.add_vertex_with_phase(VType::Z, (-3, 8));
+        g.add_vertex_with_phase(VType::X, Rational64::new(5, 7));
+        let bytes = encode_graph(&g);
+        let h: Graph = decode_graph(&bytes).unwrap();
+
+        // vertices are renumbered, but in iteration order
+        let vmap: FxHashMap<V, V> = g.vertices().zip(h.vertices()).collect();
+        assert_eq!(g.num_vertices(), h.num_vertices());
+        for (v, w) in &vmap {
+            assert_eq!(g.vertex_data(*v), h.vertex_data(*w));
+        }
+        assert_eq!(g.num_edges(), h.num_edges());
+        for (s, t, et) in g.edges() {
+            assert_eq!(h.edge_type_opt(vmap[&s], vmap[&t]), Some(et));
+        }
+        let inputs: Vec<V> = g.inputs().iter().map(|v| vmap[v]).collect();
+        assert_eq!(&inputs, h.inputs());
+        assert_eq!(g.scalar(), h.scalar());
+        assert_eq!(g.tcount(), h.tcount());
+
+        // the 5/7 phase makes the tensors floating point, so allow for rounding
+        let (t, u) = (g.to_tensor4(), h.to_tensor4());
+        assert_eq!(t.shape(), u.shape());
+        assert!(t.iter().zip(u.iter()).all(|(a, b)| a.approx_eq(b, 1e-10)));
+    }
+
+    #[test]
+    fn binary_roundtrip_hash_graph() {
+        let c = Circuit::random()
+            .seed(42)
+            .qubits(3)
+            .depth(20)
+            .p_t(0.3)
+            .with_cliffords()
+            .build();
+        let g: crate::hash_graph::Graph = c.to_graph();
+        let h: Graph = decode_graph(&encode_graph(&g)).unwrap();
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn binary_scalars() {
+        for s in [
+            ScalarN::one(),
+            ScalarN::zero(),
+            ScalarN::from_int_coeffs(&[1, -2, 3, 0]),
+            ScalarN::sqrt2_pow(-7),
+            ScalarN::complex(0.25, -1.5),
+        ] {
+            let mut g = Graph::new();
+            *g.scalar_mut() = s.clone();
+            let h: Graph = decode_graph(&encode_graph(&g)).unwrap();
+            assert_eq!(&s, h.scalar());
+        }
+    }
+
+    #[test]
+    fn binary_stream() {
+        let g = random_graph();
+        let mut h = g.clone();
+        h.adjoint();
+
+        let mut buf = vec![];
+        encode_graph_into(&g, &mut buf);
+        encode_graph_into(&h, &mut buf);
+
+        let mut bytes = &buf[..];
+        let g1: Graph = decode_graph_from(&mut bytes).unwrap();
+        let h1: Graph = decode_graph_from(&mut bytes).unwrap();
+        assert!(bytes.is_empty());
+        assert_eq!(g.scalar(), g1.scalar());
+        assert_eq!(h.scalar(), h1.scalar());
+        assert_eq!(g.num_edges(), g1.num_edges());
+        assert_eq!(h.to_tensor4(), h1.to_tensor4());
+    }
+
+    #[test]
+    fn binary_errors() {
+        let bytes = encode_graph(&random_graph());
+        assert!(matches!(
+            decode_graph::<Graph>(&bytes[..bytes.len() - 1]),
+            Err(BinaryError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decode_graph::<Graph>(b"JSON"),
+            Err(BinaryError::BadMagic)
+        ));
+    }
+}
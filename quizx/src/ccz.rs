@@ -0,0 +1,201 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recognition of CCZ gates
+//!
+//! The usual decomposition of a CCZ gate into T gates gives the phase polynomial
+//! `π/4 (a + b + c - a⊕b - a⊕c - b⊕c + a⊕b⊕c) = π abc`. After simplification, this
+//! is a cluster of seven T spiders: the phases of the three spiders `a`, `b` and
+//! `c`, and phase gadgets on the four other parities. More generally, the same holds
+//! for any three parities in place of `a`, `b` and `c`, and for any seven terms
+//! which are odd multiples of `π/4`, up to a Clifford phase on each term (see
+//! [crate::spider_nest] for the phase polynomial of a diagram).
+//!
+//! [ccz_simp] finds such clusters and replaces each by an H-box with phase `π`,
+//! which is a CCZ on its three neighbours, leaving only Clifford phases behind. When
+//! a parity is not a single spider, it is computed by an X spider.
+//!
+//! ```
+//! # use quizx::ccz::ccz_simp;
+//! # use quizx::circuit::Circuit;
+//! # use quizx::graph::*;
+//! # use quizx::simplify::full_simp;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[3]; ccz q[0], q[1], q[2];").unwrap();
+//! let mut g: Graph = c.to_graph();
+//! full_simp(&mut g);
+//! assert_eq!(g.tcount(), 7);
+//! assert!(ccz_simp(&mut g));
+//! assert_eq!(g.tcount(), 0);
+//! assert_eq!(g.num_vertices_of_type(VType::H), 1);
+//! ```
+
+use crate::affine::Parity;
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::spider_nest::{phase_terms, span, touched};
+
+/// Find three parities which span seven terms whose phases are odd multiples of
+/// `π/4`
+///
+/// Each parity is given as a sorted list of spiders. Parities which are single
+/// spiders are preferred. See [apply_ccz].
+pub fn find_ccz(g: &impl GraphLike) -> Option<Vec<Parity>> {
+    let terms = phase_terms(g);
+    let mut ts: Vec<&Parity> = terms
+        .iter()
+        .filter(|(_, (phase, _))| phase.is_t())
+        .map(|(p, _)| p)
+        .collect();
+    ts.sort_by(|p, q| p.len().cmp(&q.len()).then(p.cmp(q)));
+
+    for (i, &p0) in ts.iter().enumerate() {
+        for (j, &p1) in ts.iter().enumerate().skip(i + 1) {
+            for &p2 in &ts[j + 1..] {
+                let sp = span(&[p0, p1, p2]);
+                if sp
+                    .iter()
+                    .all(|(p, _)| terms.get(p).is_some_and(|t| t.0.is_t()))
+                    && g.can_rewrite_at(&touched(&terms, &sp))
+                {
+                    return Some(vec![p0.clone(), p1.clone(), p2.clone()]);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Replace the T terms spanned by three parities by a CCZ on the parities
+///
+/// The seven terms must all be in the diagram. Each loses `±π/4`, which leaves a
+/// Clifford phase if it was an odd multiple of `π/4`, and an H-box with phase `π` is
+/// connected to the three parities.
+pub fn apply_ccz(g: &mut impl GraphLike, gens: &[Parity]) {
+    let terms = phase_terms(g);
+    let sp = span(&gens.iter().collect::<Vec<_>>());
+    g.begin_rewrite("ccz", &touched(&terms, &sp));
+    for (p, size) in &sp {
+        let phase = if size % 2 == 1 {
+            Phase::from((-1, 4))
+        } else {
+            Phase::from((1, 4))
+        };
+        let &(_, v) = terms.get(p).expect("Missing term for CCZ");
+        g.add_to_phase(v, phase);
+    }
+
+    let h = g.add_vertex_with_phase(VType::H, 1);
+    for p in gens {
+        if let [v] = p[..] {
+            g.add_edge(v, h);
+        } else {
+            let x = g.add_vertex(VType::X);
+            g.add_edge(x, h);
+            for &v in p {
+                g.add_edge(v, x);
+            }
+            g.scalar_mut().mul_sqrt2_pow(p.len() as i32 - 1);
+        }
+    }
+    g.end_rewrite();
+}
+
+/// Replace clusters of T spiders by CCZs, as long as there are any
+///
+/// See the [module docs](self).
+pub fn ccz_simp(g: &mut impl GraphLike) -> bool {
+    let mut got_match = false;
+    while g.can_rewrite() {
+        match find_ccz(g) {
+            Some(gens) => {
+                apply_ccz(g, &gens);
+                got_match = true;
+            }
+            None => break,
+        }
+    }
+    got_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::full_simp;
+    use crate::spider_nest::add_gadget;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn ccz_circuits() {
+        for qasm in [
+            "qreg q[3]; ccz q[0], q[1], q[2];",
+            "qreg q[3]; ccx q[0], q[1], q[2];",
+        ] {
+            let c = Circuit::from_qasm(qasm).unwrap();
+            let mut g: Graph = c.to_graph();
+            full_simp(&mut g);
+            let h = g.clone();
+            assert_eq!(g.tcount(), 7);
+            assert!(ccz_simp(&mut g));
+            assert_eq!(g.tcount(), 0);
+            assert_eq!(g.num_vertices_of_type(VType::H), 1);
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+        }
+    }
+
+    #[test]
+    fn ccz_parities() {
+        // a CCZ on the parities q0 + q1, q2 and q3
+        let mut g = Graph::new();
+        let mut vs = vec![];
+        let (mut ins, mut outs) = (vec![], vec![]);
+        for _ in 0..4 {
+            let i = g.add_vertex(VType::B);
+            let v = g.add_vertex(VType::Z);
+            let o = g.add_vertex(VType::B);
+            g.add_edge(i, v);
+            g.add_edge(v, o);
+            ins.push(i);
+            vs.push(v);
+            outs.push(o);
+        }
+        g.set_inputs(ins);
+        g.set_outputs(outs);
+        let gens = [vec![vs[0], vs[1]], vec![vs[2]], vec![vs[3]]];
+        for (p, size) in span(&gens.iter().collect::<Vec<_>>()) {
+            let phase = if size % 2 == 1 {
+                Phase::from((1, 4))
+            } else {
+                Phase::from((-1, 4))
+            };
+            if p.len() == 1 {
+                g.add_to_phase(p[0], phase);
+            } else {
+                add_gadget(&mut g, &p, phase);
+            }
+        }
+
+        let h = g.clone();
+        assert_eq!(g.tcount(), 7);
+        assert!(ccz_simp(&mut g));
+        assert_eq!(g.tcount(), 0);
+        assert_eq!(g.num_vertices_of_type(VType::X), 1);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert!(!ccz_simp(&mut g));
+    }
+}
@@ -20,8 +20,11 @@ use crate::linalg::RowOps;
 use crate::phase::Phase;
 use num::{Rational64, Zero};
 use openqasm::{ast::Symbol, translate::Value, GenericError, ProgramVisitor};
+use rustc_hash::FxHashSet;
 use std::collections::VecDeque;
 use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str;
 
 /// A type for quantum circuits
@@ -31,7 +34,8 @@ pub struct Circuit {
     pub gates: VecDeque<Gate>,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+/// Gate counts and depths of a circuit, see [Circuit::stats]
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub struct CircuitStats {
     pub qubits: usize,
     pub total: usize,
@@ -40,20 +44,60 @@ pub struct CircuitStats {
     pub moreq: usize,
     pub cliff: usize,
     pub non_cliff: usize,
+    /// The number of gates with a non-Clifford phase, e.g. T gates, which doesn't
+    /// include Toffoli or CCZ gates
+    pub tcount: usize,
+    /// The number of layers with gates counted by `tcount`
+    pub tdepth: usize,
+    /// The number of layers with gates on two or more qubits
+    pub twoq_depth: usize,
+    pub depth: usize,
+    /// The number of gates of each type, in order of first appearance
+    pub gate_counts: Vec<(GType, usize)>,
+}
+
+/// The depth of a circuit, where only the gates for which `counts` holds take a time
+/// step
+fn depth_of(c: &Circuit, counts: impl Fn(&Gate) -> bool) -> usize {
+    let mut depth = vec![0; c.num_qubits()];
+    for g in &c.gates {
+        let d = g.qs.iter().map(|&q| depth[q]).max().unwrap_or(0) + counts(g) as usize;
+        for &q in &g.qs {
+            depth[q] = d;
+        }
+    }
+    depth.into_iter().max().unwrap_or(0)
 }
 
 impl CircuitStats {
     pub fn make(c: &Circuit) -> Self {
+        let has_t = |g: &Gate| match g.t {
+            T | Tdg => true,
+            ZPhase | XPhase | ParityPhase => !g.phase.is_clifford(),
+            _ => false,
+        };
+        // barriers aren't gates, but the gates after them start a new layer
+        let is_gate = |g: &Gate| g.t != Barrier;
         let mut s = CircuitStats {
             qubits: c.num_qubits(),
-            total: c.num_gates(),
+            total: c.gates.iter().filter(|g| is_gate(g)).count(),
             oneq: 0,
             twoq: 0,
             moreq: 0,
             cliff: 0,
             non_cliff: 0,
+            tcount: c.gates.iter().filter(|g| has_t(g)).count(),
+            tdepth: depth_of(c, has_t),
+            twoq_depth: depth_of(c, |g| is_gate(g) && g.qs.len() >= 2),
+            depth: depth_of(c, is_gate),
+            gate_counts: vec![],
         };
-        for g in &c.gates {
+        for g in c.gates.iter().filter(|g| is_gate(g)) {
+            match s.gate_counts.iter_mut().find(|(t, _)| *t == g.t) {
+                Some((_, n)) => *n += 1,
+                None => s.gate_counts.push((g.t, 1)),
+            }
+
             match g.qs.len() {
                 1 => {
                     s.oneq += 1;
@@ -70,6 +114,7 @@ impl CircuitStats {
                 NOT | Z | S | Sdg | CNOT | CZ | SWAP | HAD => {
                     s.cliff += 1;
                 }
+                Measure | Reset => {}
                 ZPhase | XPhase => {
                     if g.phase.is_clifford() {
                         s.cliff += 1;
@@ -85,6 +130,14 @@ impl CircuitStats {
         s
     }
 
+    /// The number of gates of type `t`
+    pub fn count(&self, t: GType) -> usize {
+        self.gate_counts
+            .iter()
+            .find(|(t1, _)| *t1 == t)
+            .map_or(0, |&(_, n)| n)
+    }
+
     pub fn into_array(self) -> [usize; 7] {
         [
             self.qubits,
@@ -100,7 +153,18 @@ impl CircuitStats {
 
 impl fmt::Display for CircuitStats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Circuit with {} qubits, {} gates\n  1-qubit: {}\n  2-qubit: {}\n  n-qubit: {}\n  clifford: {}\n  non-clifford: {}", self.qubits, self.total, self.oneq, self.twoq, self.moreq, self.cliff, self.non_cliff)
+        write!(f, "Circuit with {} qubits, {} gates\n  1-qubit: {}\n  2-qubit: {}\n  n-qubit: {}\n  clifford: {}\n  non-clifford: {}", self.qubits, self.total, self.oneq, self.twoq, self.moreq, self.cliff, self.non_cliff)?;
+        write!(
+            f,
+            "\n  T-count: {}\n  depth: {}\n  2-qubit depth: {}\n  T-depth: {}",
+            self.tcount, self.depth, self.twoq_depth, self.tdepth
+        )?;
+        let counts: Vec<String> = self
+            .gate_counts
+            .iter()
+            .map(|(t, n)| format!("{}: {}", t.qasm_name(), n))
+            .collect();
+        write!(f, "\n  gates: {}", counts.join(", "))
     }
 }
 
@@ -120,6 +184,16 @@ impl Circuit {
         self.gates.len()
     }
 
+    /// The number of classical bits, i.e. one more than the largest bit written
+    /// by a measurement or used in a condition
+    pub fn num_bits(&self) -> usize {
+        self.gates
+            .iter()
+            .flat_map(|g| g.classical_bits())
+            .max()
+            .map_or(0, |b| b + 1)
+    }
+
     pub fn num_gates_of_type(&self, t: GType) -> usize {
         let mut n = 0;
         for g in &self.gates {
@@ -143,17 +217,19 @@ impl Circuit {
     }
 
     pub fn add_gate_with_phase(&mut self, name: &str, qs: Vec<usize>, phase: impl Into<Phase>) {
-        self.push(Gate {
-            t: GType::from_qasm_name(name),
-            qs,
-            phase: phase.into(),
-        });
+        self.push(Gate::new_with_phase(GType::from_qasm_name(name), qs, phase));
     }
 
     pub fn add_gate(&mut self, name: &str, qs: Vec<usize>) {
         self.add_gate_with_phase(name, qs, Rational64::zero());
     }
 
+    /// Removes all [Barrier] gates, e.g. to let gate-level passes optimize across
+    /// them
+    pub fn remove_barriers(&mut self) {
+        self.gates.retain(|g| g.t != Barrier);
+    }
+
     pub fn reverse(&mut self) {
         self.gates.make_contiguous().reverse();
     }
@@ -171,38 +247,34 @@ impl Circuit {
         c
     }
 
+    /// The inverse of the circuit, which is the same as [Circuit::to_adjoint]
+    pub fn inverse(&self) -> Circuit {
+        self.to_adjoint()
+    }
+
+    /// The circuit repeated `n` times, i.e. its `n`-th power
+    pub fn repeat(&self, n: usize) -> Circuit {
+        let mut c = Circuit::new(self.nqubits);
+        for _ in 0..n {
+            c.gates.extend(self.gates.iter().cloned());
+        }
+        c
+    }
+
     pub fn to_qasm(&self) -> String {
         String::from("OPENQASM 2.0;\ninclude \"qelib1.inc\";\n") + &self.to_string()
     }
 
-    fn from_qasm_parser(read: impl FnOnce(&mut openqasm::Parser)) -> Result<Circuit, String> {
+    fn from_qasm_source(source: &str, path: Option<&Path>) -> Result<Circuit, String> {
+        let mut stack: Vec<PathBuf> = path.map(|p| p.to_path_buf()).into_iter().collect();
+        let source = inline_includes(source, path.and_then(|p| p.parent()), &mut stack)?;
+        let prelude = qasm_prelude(&source);
+
         let mut cache = openqasm::SourceCache::new();
         let mut parser = openqasm::Parser::new(&mut cache)
             .with_file_policy(openqasm::parser::FilePolicy::Ignore);
-        read(&mut parser);
-        parser.parse_source::<String>(
-            "
-            opaque rz(phase) q;
-            opaque rx(phase) q;
-            opaque x q;
-            opaque z q;
-            opaque s q;
-            opaque t q;
-            opaque sdg q;
-            opaque tdg q;
-            opaque h q;
-            opaque cx a, b;
-            opaque cz a, b;
-            opaque ccx a, b, c;
-            opaque ccz a, b, c;
-            opaque swap a, b;
-            opaque xcx a, b;
-            opaque init_anc a;
-            opaque post_sel a;
-        "
-            .to_string(),
-            None,
-        );
+        parser.parse_source::<String>(source, None);
+        parser.parse_source::<String>(prelude, None);
 
         let program = parser.done().to_errors().map_err(|e| e.to_string())?;
         program
@@ -212,6 +284,7 @@ impl Circuit {
 
         let mut writer = CircuitWriter {
             circuit: Circuit::new(0),
+            condition: None,
         };
         let mut linearize = openqasm::Linearize::new(&mut writer, usize::MAX);
         linearize
@@ -222,12 +295,23 @@ impl Circuit {
         Ok(writer.circuit)
     }
 
+    /// Reads a circuit from OpenQASM 2.0 source
+    ///
+    /// Gates defined with `gate` are expanded into the gates they are made of, and the
+    /// gates of "qelib1.inc" are always available. Other included files are looked up
+    /// relative to the working directory.
     pub fn from_qasm(source: &str) -> Result<Circuit, String> {
-        Circuit::from_qasm_parser(|parser| parser.parse_source::<String>(source.to_string(), None))
+        Circuit::from_qasm_source(source, None)
     }
 
+    /// Reads a circuit from an OpenQASM 2.0 file
+    ///
+    /// As [Circuit::from_qasm], but included files are looked up relative to the
+    /// directory of `name`.
     pub fn from_file(name: &str) -> Result<Circuit, String> {
-        Circuit::from_qasm_parser(|parser| parser.parse_file(name))
+        let path = Path::new(name);
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", name, e))?;
+        Circuit::from_qasm_source(&source, Some(path))
     }
 
     /// returns a copy of the circuit, decomposed into 1- and 2-qubit Clifford +
@@ -247,6 +331,25 @@ impl Circuit {
     }
 
     pub fn to_graph_with_options<G: GraphLike>(&self, postselect: bool) -> G {
+        self.to_graph_with_callback(postselect, |_, _, _| {})
+    }
+
+    /// Like [Circuit::to_graph_with_options], calling `f(i, graph, qs)` after adding
+    /// gate `i`
+    ///
+    /// Here `qs[q]` is the most recent vertex on qubit `q`, which lets the caller
+    /// find the spider a gate was turned into.
+    pub(crate) fn to_graph_with_callback<G, F>(&self, postselect: bool, mut f: F) -> G
+    where
+        G: GraphLike,
+        F: FnMut(usize, &G, &[Option<V>]),
+    {
+        if let Err(e) = self.terminal_measurements() {
+            panic!(
+                "{}, see Circuit::defer_measurements and Circuit::to_doubled_graph",
+                e
+            );
+        }
         let mut graph = G::new();
         let mut qs = Vec::with_capacity(self.nqubits);
         let mut inputs = Vec::with_capacity(self.nqubits);
@@ -262,12 +365,14 @@ impl Circuit {
             });
             qs.push(Some(v));
             inputs.push(v);
+            graph.set_input_qubit(v, i);
         }
 
         graph.set_inputs(inputs);
 
-        for g in &self.gates {
+        for (i, g) in self.gates.iter().enumerate() {
             g.add_to_graph(&mut graph, &mut qs, postselect);
+            f(i, &graph, &qs);
         }
 
         let last_row = qs
@@ -290,6 +395,7 @@ impl Circuit {
                 });
                 graph.add_edge(v0, v);
                 outputs.push(v);
+                graph.set_output_qubit(v, i);
             }
         }
 
@@ -301,6 +407,7 @@ impl Circuit {
         self.to_graph_with_options(false)
     }
 
+    /// Gate counts and depths, e.g. for benchmarks
     pub fn stats(&self) -> CircuitStats {
         CircuitStats::make(self)
     }
@@ -309,8 +416,15 @@ impl Circuit {
 impl fmt::Display for Circuit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "qreg q[{}];", self.num_qubits())?;
+        let nbits = self.num_bits();
+        if nbits > 0 {
+            writeln!(f, "creg c[{}];", nbits)?;
+        }
 
         for g in &self.gates {
+            if let Some(cond) = &g.condition {
+                write!(f, "{} ", cond.to_qasm(nbits))?;
+            }
             writeln!(f, "{};", g.to_qasm())?;
         }
 
@@ -385,40 +499,188 @@ impl RowOps for Circuit {
     }
 }
 
-struct CircuitWriter {
-    circuit: Circuit,
+/// The gates available to every QASM source, unless it declares a gate of the same
+/// name itself
+///
+/// The gates quizx supports natively are opaque, and the rest of "qelib1.inc" is
+/// defined in terms of them, up to global phase.
+pub(crate) const QASM_PRELUDE: &[(&str, &str)] = &[
+    ("rz", "opaque rz(phase) q;"),
+    ("rx", "opaque rx(phase) q;"),
+    ("x", "opaque x q;"),
+    ("z", "opaque z q;"),
+    ("s", "opaque s q;"),
+    ("t", "opaque t q;"),
+    ("sdg", "opaque sdg q;"),
+    ("tdg", "opaque tdg q;"),
+    ("h", "opaque h q;"),
+    ("cx", "opaque cx a, b;"),
+    ("cz", "opaque cz a, b;"),
+    ("ccx", "opaque ccx a, b, c;"),
+    ("ccz", "opaque ccz a, b, c;"),
+    ("swap", "opaque swap a, b;"),
+    ("xcx", "opaque xcx a, b;"),
+    ("init_anc", "opaque init_anc a;"),
+    ("post_sel", "opaque post_sel a;"),
+    ("id", "gate id q { }"),
+    ("u1", "gate u1(lambda) q { rz(lambda) q; }"),
+    ("p", "gate p(lambda) q { rz(lambda) q; }"),
+    ("y", "gate y q { z q; x q; }"),
+    ("ry", "gate ry(theta) q { sdg q; rx(theta) q; s q; }"),
+    (
+        "u3",
+        "gate u3(theta, phi, lambda) q { rz(lambda) q; ry(theta) q; rz(phi) q; }",
+    ),
+    ("u2", "gate u2(phi, lambda) q { u3(pi/2, phi, lambda) q; }"),
+    ("u", "gate u(theta, phi, lambda) q { u3(theta, phi, lambda) q; }"),
+    ("sx", "gate sx q { rx(pi/2) q; }"),
+    ("sxdg", "gate sxdg q { rx(-pi/2) q; }"),
+    ("cy", "gate cy a, b { sdg b; cx a, b; s b; }"),
+    (
+        "crz",
+        "gate crz(lambda) a, b { rz(lambda/2) b; cx a, b; rz(-lambda/2) b; cx a, b; }",
+    ),
+    (
+        "cu1",
+        "gate cu1(lambda) a, b { rz(lambda/2) a; cx a, b; rz(-lambda/2) b; cx a, b; rz(lambda/2) b; }",
+    ),
+    (
+        "cp",
+        "gate cp(lambda) a, b { rz(lambda/2) a; cx a, b; rz(-lambda/2) b; cx a, b; rz(lambda/2) b; }",
+    ),
+    ("rzz", "gate rzz(theta) a, b { cx a, b; rz(theta) b; cx a, b; }"),
+    ("cswap", "gate cswap a, b, c { cx c, b; ccx a, b, c; cx c, b; }"),
+];
+
+/// The position of the next `include` statement in a QASM source, skipping comments
+fn find_include(source: &str) -> Option<usize> {
+    let mut i = 0;
+    while i < source.len() {
+        let rest = &source[i..];
+        if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with("include")
+            && !source[..i].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+            && rest["include".len()..].starts_with(|c: char| c.is_whitespace() || c == '"')
+        {
+            return Some(i);
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    None
 }
 
-#[derive(Debug)]
-#[allow(clippy::enum_variant_names)]
-enum CircuitWriterError {
-    UnitaryNotSupported,
-    BarrierNotSupported,
-    ResetNotSupported,
-    MeasureNotSupported,
-    ConditionalNotSupported,
+/// Replaces each `include` statement by the contents of the included file
+///
+/// "qelib1.inc" is dropped, as its gates are in [QASM_PRELUDE]. Other files are
+/// looked up relative to `dir`, or to the working directory if it is `None`. The
+/// files currently being included are kept in `stack`, to catch circular includes.
+fn inline_includes(
+    source: &str,
+    dir: Option<&Path>,
+    stack: &mut Vec<PathBuf>,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = source;
+    while let Some(i) = find_include(rest) {
+        out.push_str(&rest[..i]);
+        let (name, tail) = rest[i + "include".len()..]
+            .trim_start()
+            .strip_prefix('"')
+            .and_then(|s| s.split_once('"'))
+            .ok_or("malformed include statement")?;
+        rest = tail
+            .trim_start()
+            .strip_prefix(';')
+            .ok_or("malformed include statement")?;
+        if name == "qelib1.inc" {
+            continue;
+        }
+
+        let path = dir.unwrap_or(Path::new("")).join(name);
+        if stack.contains(&path) {
+            return Err(format!("circular include of {}", path.display()));
+        }
+        let inc = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        // an included file may have a header of its own
+        let inc = match inc.trim_start().strip_prefix("OPENQASM") {
+            Some(s) => s.split_once(';').map_or("", |(_, s)| s),
+            None => &inc,
+        };
+        stack.push(path.clone());
+        out.push_str(&inline_includes(inc, path.parent(), stack)?);
+        stack.pop();
+    }
+    out.push_str(rest);
+    Ok(out)
 }
 
-impl std::fmt::Display for CircuitWriterError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            CircuitWriterError::UnitaryNotSupported => {
-                write!(f, "arbitrary unitaries are not supported")
-            }
-            CircuitWriterError::BarrierNotSupported => write!(f, "barriers are not supported"),
-            CircuitWriterError::ResetNotSupported => write!(f, "resets are not supported"),
-            CircuitWriterError::MeasureNotSupported => write!(f, "measurements are not supported"),
-            CircuitWriterError::ConditionalNotSupported => {
-                write!(f, "conditionals are not supported")
-            }
+/// The identifiers and numbers in a QASM source, skipping comments
+fn qasm_words(source: &str) -> impl Iterator<Item = &str> {
+    source
+        .lines()
+        .flat_map(|l| {
+            l.split("//")
+                .next()
+                .unwrap_or("")
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        })
+        .filter(|w| !w.is_empty())
+}
+
+/// The declarations from [QASM_PRELUDE] needed by a QASM source
+///
+/// These are the gates the source uses, directly or through other gates of the
+/// prelude, except for those it declares itself.
+fn qasm_prelude(source: &str) -> String {
+    let words: Vec<&str> = qasm_words(source).collect();
+    let declared: FxHashSet<&str> = words
+        .windows(2)
+        .filter(|w| w[0] == "gate" || w[0] == "opaque")
+        .map(|w| w[1])
+        .collect();
+    let mut used: FxHashSet<&str> = words.iter().copied().collect();
+
+    // gates in the prelude only use gates which come before them
+    let mut decls = vec![];
+    for &(name, decl) in QASM_PRELUDE.iter().rev() {
+        if used.contains(name) && !declared.contains(name) {
+            used.extend(qasm_words(decl));
+            decls.push(decl);
         }
     }
+    decls.reverse();
+    decls.join("\n")
+}
+
+/// The phase of a QASM parameter, in multiples of pi
+fn param_to_phase(value: Value) -> Phase {
+    if value.a.is_zero() {
+        Rational64::new(*value.b.numer(), *value.b.denom()).into()
+    } else {
+        let a = *value.a.numer() as f32 / *value.a.denom() as f32;
+        let mut r = Rational64::approximate_float(a / std::f32::consts::PI).unwrap_or(0.into());
+        r += Rational64::new(*value.b.numer(), *value.b.denom());
+        Phase::new(r)
+    }
+}
+
+struct CircuitWriter {
+    circuit: Circuit,
+    /// The condition of the `if` statement being written, if any
+    condition: Option<Condition>,
 }
 
-impl std::error::Error for CircuitWriterError {}
+impl CircuitWriter {
+    fn push(&mut self, mut g: Gate) {
+        g.condition = self.condition.clone();
+        self.circuit.push(g);
+    }
+}
 
 impl openqasm::GateWriter for &mut CircuitWriter {
-    type Error = CircuitWriterError;
+    type Error = std::convert::Infallible;
 
     fn initialize(&mut self, qubits: &[Symbol], _: &[Symbol]) -> Result<(), Self::Error> {
         self.circuit = Circuit::new(qubits.len());
@@ -426,7 +688,7 @@ impl openqasm::GateWriter for &mut CircuitWriter {
     }
 
     fn write_cx(&mut self, a: usize, b: usize) -> Result<(), Self::Error> {
-        self.circuit.push(Gate::new(GType::CNOT, vec![a, b]));
+        self.push(Gate::new(GType::CNOT, vec![a, b]));
         Ok(())
     }
 
@@ -436,51 +698,76 @@ impl openqasm::GateWriter for &mut CircuitWriter {
         params: &[Value],
         regs: &[usize],
     ) -> Result<(), Self::Error> {
-        fn param_to_phase(value: Value) -> Phase {
-            if value.a.is_zero() {
-                Rational64::new(*value.b.numer(), *value.b.denom()).into()
-            } else {
-                let a = *value.a.numer() as f32 / *value.a.denom() as f32;
-                let mut r =
-                    Rational64::approximate_float(a / std::f32::consts::PI).unwrap_or(0.into());
-                r += Rational64::new(*value.b.numer(), *value.b.denom());
-                Phase::new(r)
-            }
-        }
-
         let mut g = Gate::from_qasm_name(name.as_str());
         g.qs.extend_from_slice(regs);
         if !params.is_empty() {
             g.phase = param_to_phase(params[0]);
         }
 
-        self.circuit.push(g);
+        self.push(g);
 
         Ok(())
     }
 
-    fn write_u(&mut self, _: Value, _: Value, _: Value, _: usize) -> Result<(), Self::Error> {
-        Err(CircuitWriterError::UnitaryNotSupported)
+    fn write_u(
+        &mut self,
+        theta: Value,
+        phi: Value,
+        lambda: Value,
+        q: usize,
+    ) -> Result<(), Self::Error> {
+        // U(theta, phi, lambda) = Rz(phi) Ry(theta) Rz(lambda), and Ry(theta) = S Rx(theta) S^dag
+        self.push(Gate::new_with_phase(
+            GType::ZPhase,
+            vec![q],
+            param_to_phase(lambda),
+        ));
+        self.push(Gate::new(GType::Sdg, vec![q]));
+        self.push(Gate::new_with_phase(
+            GType::XPhase,
+            vec![q],
+            param_to_phase(theta),
+        ));
+        self.push(Gate::new(GType::S, vec![q]));
+        self.push(Gate::new_with_phase(
+            GType::ZPhase,
+            vec![q],
+            param_to_phase(phi),
+        ));
+        Ok(())
     }
 
-    fn write_barrier(&mut self, _: &[usize]) -> Result<(), Self::Error> {
-        Err(CircuitWriterError::BarrierNotSupported)
+    fn write_barrier(&mut self, qs: &[usize]) -> Result<(), Self::Error> {
+        self.push(Gate::new_barrier(qs.to_vec()));
+        Ok(())
     }
 
-    fn write_reset(&mut self, _: usize) -> Result<(), Self::Error> {
-        Err(CircuitWriterError::ResetNotSupported)
+    fn write_reset(&mut self, q: usize) -> Result<(), Self::Error> {
+        self.push(Gate::new(GType::Reset, vec![q]));
+        Ok(())
     }
 
-    fn write_measure(&mut self, _: usize, _: usize) -> Result<(), Self::Error> {
-        Err(CircuitWriterError::MeasureNotSupported)
+    fn write_measure(&mut self, q: usize, c: usize) -> Result<(), Self::Error> {
+        self.push(Gate::new_measure(q, c));
+        Ok(())
     }
 
-    fn start_conditional(&mut self, _: usize, _: usize, _: u64) -> Result<(), Self::Error> {
-        Err(CircuitWriterError::ConditionalNotSupported)
+    fn start_conditional(
+        &mut self,
+        reg: usize,
+        count: usize,
+        value: u64,
+    ) -> Result<(), Self::Error> {
+        self.condition = Some(Condition {
+            bits: (reg..reg + count).collect(),
+            value,
+        });
+        Ok(())
     }
 
     fn end_conditional(&mut self) -> Result<(), Self::Error> {
-        Err(CircuitWriterError::ConditionalNotSupported)
+        self.condition = None;
+        Ok(())
     }
 }
 
@@ -573,6 +860,141 @@ mod tests {
         assert_eq!(c1, Ok(c));
     }
 
+    #[test]
+    fn custom_gates() {
+        let mut c = Circuit::new(3);
+        c.add_gate("cx", vec![2, 1]);
+        c.add_gate("cx", vec![2, 0]);
+        c.add_gate("ccx", vec![0, 1, 2]);
+        c.add_gate("h", vec![1]);
+        c.add_gate("ccx", vec![2, 0, 1]);
+        c.add_gate("h", vec![1]);
+
+        let qasm = r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            // gates may be redefined, and use other user-defined gates
+            gate majority a, b, c { cx c, b; cx c, a; ccx a, b, c; }
+            gate ccz a, b, c { h c; ccx a, b, c; h c; }
+            qreg q[3];
+            majority q[0], q[1], q[2];
+            ccz q[2], q[0], q[1];
+        "#;
+
+        let c1 = Circuit::from_qasm(qasm);
+        assert_eq!(c1, Ok(c));
+    }
+
+    #[test]
+    fn qelib1_gates() {
+        let pairs = [
+            ("u2(0, pi) q[0];", "h q[0];"),
+            ("u3(pi, 0, pi) q[0];", "x q[0];"),
+            ("u1(pi/4) q[0]; p(pi/2) q[1];", "t q[0]; s q[1];"),
+            ("U(pi/2, 0, pi) q[1];", "h q[1];"),
+            ("y q[0]; ry(pi) q[1];", "z q[0]; x q[0]; x q[1]; z q[1];"),
+            ("sx q[0]; sx q[0];", "x q[0];"),
+            ("cy q[0], q[1];", "sdg q[1]; cx q[0], q[1]; s q[1];"),
+            (
+                "cu1(pi/2) q[0], q[1];",
+                "t q[0]; t q[1]; cx q[0], q[1]; tdg q[1]; cx q[0], q[1];",
+            ),
+            ("crz(pi) q[0], q[1];", "cz q[0], q[1]; sdg q[0];"),
+            ("rzz(pi/2) q[0], q[1];", "s q[0]; s q[1]; cz q[0], q[1];"),
+            (
+                "cswap q[0], q[1], q[2];",
+                "ccx q[0], q[2], q[1]; ccx q[0], q[1], q[2]; ccx q[0], q[2], q[1];",
+            ),
+            ("id q[0];", ""),
+        ];
+        for (qasm0, qasm1) in pairs {
+            let c0 = Circuit::from_qasm(&format!("qreg q[3]; {}", qasm0)).unwrap();
+            let c1 = Circuit::from_qasm(&format!("qreg q[3]; {}", qasm1)).unwrap();
+            assert!(Tensor4::scalar_compare(&c0, &c1), "{} != {}", qasm0, qasm1);
+        }
+    }
+
+    #[test]
+    fn param_expressions() {
+        let mut c = Circuit::new(1);
+        c.add_gate_with_phase("rz", vec![0], Rational64::new(3, 2));
+        c.add_gate_with_phase("rz", vec![0], Rational64::new(3, 4));
+        c.add_gate_with_phase("rz", vec![0], Rational64::new(1, 4));
+        c.add_gate_with_phase("rz", vec![0], Rational64::new(1, 2));
+
+        let qasm = r#"
+            qreg q[1];
+            gate twice(theta) a { rz(2*theta) a; }
+            rz(pi/2*3) q[0];
+            rz(-(pi/4) + pi) q[0];
+            rz((pi - pi/2) / 2) q[0];
+            twice(pi/4) q[0];
+        "#;
+
+        let c1 = Circuit::from_qasm(qasm);
+        assert_eq!(c1, Ok(c));
+    }
+
+    #[test]
+    fn include_files() {
+        let dir = std::env::temp_dir().join("quizx_qasm_include");
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(
+            dir.join("lib").join("defs.inc"),
+            "OPENQASM 2.0;\ninclude \"more.inc\";\ngate bell a, b { h a; cx a, b; }\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("lib").join("more.inc"),
+            "gate flip a { x a; } // include \"missing.inc\";\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("main.qasm"),
+            "OPENQASM 2.0;\ninclude \"qelib1.inc\";\ninclude \"lib/defs.inc\";\n\
+             qreg q[2];\nbell q[0], q[1];\nflip q[1];\n",
+        )
+        .unwrap();
+        fs::write(dir.join("loop.qasm"), "include \"loop.qasm\";\n").unwrap();
+
+        let mut c = Circuit::new(2);
+        c.add_gate("h", vec![0]);
+        c.add_gate("cx", vec![0, 1]);
+        c.add_gate("x", vec![1]);
+        let c1 = Circuit::from_file(dir.join("main.qasm").to_str().unwrap());
+        assert_eq!(c1, Ok(c));
+
+        assert!(Circuit::from_file(dir.join("loop.qasm").to_str().unwrap()).is_err());
+        assert!(Circuit::from_qasm("include \"no_such_file.inc\"; qreg q[1];").is_err());
+    }
+
+    #[test]
+    fn barriers() {
+        let c = Circuit::from_qasm(
+            "qreg q[3];
+            h q[0];
+            barrier q;
+            cx q[0], q[1];
+            barrier q[1], q[2];
+            t q[2];",
+        )
+        .unwrap();
+        assert_eq!(c.num_gates_of_type(Barrier), 2);
+        assert_eq!(c.gates[1], Gate::new_barrier(vec![0, 1, 2]));
+        assert_eq!(Circuit::from_qasm(&c.to_qasm()), Ok(c.clone()));
+
+        // barriers aren't counted as gates, but the T gate has to wait for the CNOT
+        let stats = c.stats();
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.depth, 3);
+
+        let mut c1 = c.clone();
+        c1.remove_barriers();
+        assert_eq!(c1.num_gates(), 3);
+        let g: Graph = c.to_graph();
+        assert!(Tensor4::scalar_compare(&g, &c1));
+    }
+
     #[test]
     fn tograph_cz() {
         let c = Circuit::from_qasm(
@@ -676,4 +1098,53 @@ mod tests {
         let g: Graph = c.to_graph_with_options(true);
         assert_eq!(c.to_tensor4(), g.to_tensor4());
     }
+
+    #[test]
+    fn clifford_phase_stats() {
+        // rotations by pi/2 are Clifford, like the S gates they are equal to
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[1];
+            rz(pi/2) q[0];
+            rx(-pi/2) q[0];
+            rz(pi/4) q[0];
+        "#,
+        )
+        .unwrap();
+        let s = c.stats();
+        assert_eq!(s.cliff, 2);
+        assert_eq!(s.non_cliff, 1);
+        let g: Graph = c.to_graph();
+        assert_eq!(g.tcount(), 1);
+    }
+
+    #[test]
+    fn circuit_stats() {
+        let c = Circuit::from_qasm(
+            "qreg q[3];
+            h q[0];
+            t q[0];
+            cx q[0], q[1];
+            t q[1];
+            tdg q[2];
+            cx q[1], q[2];
+            rz(pi/4) q[0];
+            s q[2];",
+        )
+        .unwrap();
+        let s = c.stats();
+        assert_eq!(s.total, 8);
+        assert_eq!(s.twoq, 2);
+        assert_eq!(s.tcount, 4);
+        assert_eq!(s.tdepth, 2);
+        assert_eq!(s.twoq_depth, 2);
+        assert_eq!(s.depth, 6);
+        assert_eq!(s.depth, c.depth());
+        assert_eq!(s.count(T), 2);
+        assert_eq!(s.count(CNOT), 2);
+        assert_eq!(s.count(CCZ), 0);
+        assert_eq!(s.gate_counts[0], (HAD, 1));
+        assert!(s.to_string().contains("T-count: 4"));
+        assert!(s.to_string().contains("h: 1, t: 2, cx: 2"));
+    }
 }
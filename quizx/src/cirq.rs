@@ -0,0 +1,445 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion to and from Cirq's json serialization of circuits
+//!
+//! A Cirq circuit is a list of moments, each of which is a list of operations on
+//! disjoint qubits. When reading, the moments are concatenated, and the qubits are
+//! numbered in Cirq's order: line qubits by `x`, grid qubits by row and column, and
+//! named qubits by name. When writing, each gate goes in the first moment after the
+//! last one using any of its qubits, and qubit `i` becomes `cirq.LineQubit(i)`.
+//!
+//! The gates `XPowGate`, `YPowGate`, `ZPowGate` and `CZPowGate` may have any
+//! exponent, which is rounded to a fraction with denominator at most 256, and
+//! `HPowGate`, `CXPowGate`, `SwapPowGate`, `CCXPowGate` and `CCZPowGate` must have
+//! exponent 1. `Rx`, `Ry` and `Rz`, identities and global phases are supported too.
+//! Global shifts are ignored, as circuits are only considered up to global phase,
+//! and barriers are dropped when writing.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::cirq::{decode_circuit, encode_circuit};
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; t q[1];").unwrap();
+//! let json = encode_circuit(&c).unwrap();
+//! assert_eq!(decode_circuit(&json).unwrap(), c);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::phase::Phase;
+use crate::qasm3::angle_to_phase;
+use num::{Rational64, Zero};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::path::Path;
+
+/// An error that can occur when converting to or from Cirq's json.
+#[derive(Debug, thiserror::Error)]
+pub enum CirqError {
+    /// The json does not describe a Cirq circuit.
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    /// A Cirq gate or operation with no counterpart in a [Circuit].
+    #[error("Cirq {0} is not supported")]
+    UnsupportedCirq(String),
+    /// A gate with no counterpart in Cirq.
+    #[error("Gate {0} cannot be written to Cirq")]
+    UnsupportedGate(String),
+    /// An operation acts on the wrong number of qubits for its gate.
+    #[error("Wrong number of qubits for {0}")]
+    InvalidOperation(String),
+    /// Error reading or writing a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CirqCircuit {
+    cirq_type: String,
+    moments: Vec<CirqMoment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CirqMoment {
+    cirq_type: String,
+    operations: Vec<CirqOperation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CirqOperation {
+    cirq_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gate: Option<CirqGate>,
+    #[serde(default)]
+    qubits: Vec<CirqQubit>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CirqGate {
+    cirq_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exponent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    global_shift: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rads: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(tag = "cirq_type")]
+#[allow(clippy::enum_variant_names)]
+enum CirqQubit {
+    LineQubit { x: i64 },
+    GridQubit { row: i64, col: i64 },
+    NamedQubit { name: String },
+}
+
+impl CirqGate {
+    fn pow(cirq_type: &str, exponent: Phase) -> Self {
+        CirqGate {
+            cirq_type: cirq_type.to_string(),
+            exponent: Some(exponent.to_f64()),
+            global_shift: Some(0.0),
+            rads: None,
+        }
+    }
+
+    /// The gates making up this gate, on the given qubits
+    fn to_gates(&self, qs: Vec<usize>) -> Result<Vec<Gate>, CirqError> {
+        let ty = self.cirq_type.as_str();
+        let unsupported = || {
+            CirqError::UnsupportedCirq(match self.exponent {
+                Some(e) => format!("{}**{}", ty, e),
+                None => ty.to_string(),
+            })
+        };
+        let phase = match (self.exponent, self.rads) {
+            (Some(e), _) => angle_to_phase(e * PI),
+            (None, Some(r)) => angle_to_phase(r),
+            (None, None) => Phase::new(1),
+        };
+        let pauli = phase == Phase::new(1);
+        let num_qubits = match ty {
+            "IdentityGate" | "GlobalPhaseGate" => return Ok(vec![]),
+            "XPowGate" | "YPowGate" | "ZPowGate" | "HPowGate" | "Rx" | "Ry" | "Rz" => 1,
+            "CXPowGate" | "CNotPowGate" | "CZPowGate" | "SwapPowGate" => 2,
+            "CCXPowGate" | "CCNotPowGate" | "CCZPowGate" => 3,
+            _ => return Err(unsupported()),
+        };
+        if qs.len() != num_qubits {
+            return Err(CirqError::InvalidOperation(ty.to_string()));
+        }
+
+        let gates = match ty {
+            "XPowGate" | "Rx" if pauli => vec![Gate::new(NOT, qs)],
+            "XPowGate" | "Rx" => vec![Gate::new_with_phase(XPhase, qs, phase)],
+            "YPowGate" | "Ry" if pauli => vec![Gate::new(Z, qs.clone()), Gate::new(NOT, qs)],
+            "YPowGate" | "Ry" => vec![
+                Gate::new(Sdg, qs.clone()),
+                Gate::new_with_phase(XPhase, qs.clone(), phase),
+                Gate::new(S, qs),
+            ],
+            "ZPowGate" | "Rz" => {
+                let r = phase.to_rational();
+                let t = if r == Rational64::new(1, 1) {
+                    Z
+                } else if r == Rational64::new(1, 2) {
+                    S
+                } else if r == Rational64::new(-1, 2) {
+                    Sdg
+                } else if r == Rational64::new(1, 4) {
+                    T
+                } else if r == Rational64::new(-1, 4) {
+                    Tdg
+                } else {
+                    ZPhase
+                };
+                vec![Gate::new_with_phase(
+                    t,
+                    qs,
+                    if t == ZPhase { phase } else { Phase::zero() },
+                )]
+            }
+            "CZPowGate" if pauli => vec![Gate::new(CZ, qs)],
+            "CZPowGate" => {
+                // a controlled phase is a phase polynomial on the two qubits
+                let half = phase / 2;
+                vec![
+                    Gate::new_with_phase(ZPhase, vec![qs[0]], half),
+                    Gate::new_with_phase(ZPhase, vec![qs[1]], half),
+                    Gate::new(CNOT, qs.clone()),
+                    Gate::new_with_phase(ZPhase, vec![qs[1]], -half),
+                    Gate::new(CNOT, qs),
+                ]
+            }
+            _ if !pauli => return Err(unsupported()),
+            "HPowGate" => vec![Gate::new(HAD, qs)],
+            "CXPowGate" | "CNotPowGate" => vec![Gate::new(CNOT, qs)],
+            "SwapPowGate" => vec![Gate::new(SWAP, qs)],
+            "CCXPowGate" | "CCNotPowGate" => vec![Gate::new(TOFF, qs)],
+            _ => vec![Gate::new(CCZ, qs)],
+        };
+        Ok(gates)
+    }
+}
+
+/// The Cirq gates making up a gate
+fn cirq_gates(g: &Gate) -> Result<Vec<(CirqGate, Vec<usize>)>, CirqError> {
+    let one = Phase::new(1);
+    let gate = match g.t {
+        NOT => CirqGate::pow("XPowGate", one),
+        XPhase => CirqGate::pow("XPowGate", g.phase),
+        Z => CirqGate::pow("ZPowGate", one),
+        S => CirqGate::pow("ZPowGate", Phase::from((1, 2))),
+        Sdg => CirqGate::pow("ZPowGate", Phase::from((-1, 2))),
+        T => CirqGate::pow("ZPowGate", Phase::from((1, 4))),
+        Tdg => CirqGate::pow("ZPowGate", Phase::from((-1, 4))),
+        ZPhase => CirqGate::pow("ZPowGate", g.phase),
+        HAD => CirqGate::pow("HPowGate", one),
+        CNOT => CirqGate::pow("CXPowGate", one),
+        CZ => CirqGate::pow("CZPowGate", one),
+        SWAP => CirqGate::pow("SwapPowGate", one),
+        TOFF => CirqGate::pow("CCXPowGate", one),
+        CCZ => CirqGate::pow("CCZPowGate", one),
+        XCX | ParityPhase => {
+            let mut c = Circuit::new(0);
+            if g.t == XCX {
+                c.push(Gate::new(HAD, vec![g.qs[0]]));
+                c.push(Gate::new(CNOT, g.qs.clone()));
+                c.push(Gate::new(HAD, vec![g.qs[0]]));
+            } else {
+                g.push_basic_gates(&mut c);
+            }
+            let mut gates = vec![];
+            for h in &c.gates {
+                gates.extend(cirq_gates(h)?);
+            }
+            return Ok(gates);
+        }
+        Barrier => return Ok(vec![]),
+        InitAncilla | PostSelect | Measure | Reset | UnknownGate => {
+            return Err(CirqError::UnsupportedGate(g.qasm_name().to_string()))
+        }
+    };
+    Ok(vec![(gate, g.qs.clone())])
+}
+
+/// Reads a circuit from Cirq's json serialization.
+///
+/// See the [module docs](self) for what is supported.
+pub fn decode_circuit(s: &str) -> Result<Circuit, CirqError> {
+    let cc: CirqCircuit = serde_json::from_str(s)?;
+    if cc.cirq_type != "Circuit" {
+        return Err(CirqError::UnsupportedCirq(cc.cirq_type));
+    }
+
+    let mut qubits: Vec<&CirqQubit> = cc
+        .moments
+        .iter()
+        .flat_map(|m| m.operations.iter().flat_map(|op| op.qubits.iter()))
+        .collect();
+    qubits.sort();
+    qubits.dedup();
+
+    let mut c = Circuit::new(qubits.len());
+    for op in cc.moments.iter().flat_map(|m| m.operations.iter()) {
+        let gate = match &op.gate {
+            Some(gate) if op.cirq_type == "GateOperation" => gate,
+            _ => return Err(CirqError::UnsupportedCirq(op.cirq_type.clone())),
+        };
+        let qs = op
+            .qubits
+            .iter()
+            .map(|q| qubits.binary_search(&q).unwrap())
+            .collect();
+        for g in gate.to_gates(qs)? {
+            c.push(g);
+        }
+    }
+    Ok(c)
+}
+
+/// Reads a circuit from a file containing Cirq's json serialization.
+pub fn read_circuit(filename: &Path) -> Result<Circuit, CirqError> {
+    let s = std::fs::read_to_string(filename)?;
+    decode_circuit(&s)
+}
+
+/// Returns Cirq's json serialization of a circuit.
+///
+/// Gates are packed into moments greedily. Fails on the pyzx-specific gates
+/// `init_anc` and `post_sel`.
+pub fn encode_circuit(c: &Circuit) -> Result<String, CirqError> {
+    let mut moments: Vec<CirqMoment> = vec![];
+    // the first moment which is free on each qubit
+    let mut free = vec![0; c.num_qubits()];
+    for g in &c.gates {
+        for (gate, qs) in cirq_gates(g)? {
+            let m = qs.iter().map(|&q| free[q]).max().unwrap_or(0);
+            if m == moments.len() {
+                moments.push(CirqMoment {
+                    cirq_type: "Moment".to_string(),
+                    operations: vec![],
+                });
+            }
+            for &q in &qs {
+                free[q] = m + 1;
+            }
+            moments[m].operations.push(CirqOperation {
+                cirq_type: "GateOperation".to_string(),
+                gate: Some(gate),
+                qubits: qs
+                    .iter()
+                    .map(|&q| CirqQubit::LineQubit { x: q as i64 })
+                    .collect(),
+            });
+        }
+    }
+
+    let cc = CirqCircuit {
+        cirq_type: "Circuit".to_string(),
+        moments,
+    };
+    Ok(serde_json::to_string(&cc)?)
+}
+
+/// Writes Cirq's json serialization of a circuit to a file.
+pub fn write_circuit(c: &Circuit, filename: &Path) -> Result<(), CirqError> {
+    std::fs::write(filename, encode_circuit(c)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    /// The gates acting on each qubit, in order
+    ///
+    /// Writing a circuit as moments can reorder gates on disjoint qubits, but not
+    /// the gates on any one qubit.
+    fn gates_per_qubit(c: &Circuit) -> Vec<Vec<Gate>> {
+        let mut gs = vec![vec![]; c.num_qubits()];
+        for g in &c.gates {
+            for &q in &g.qs {
+                gs[q].push(g.clone());
+            }
+        }
+        gs
+    }
+
+    #[test]
+    fn cirq_roundtrip() {
+        let mut c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(40)
+            .clifford_t(0.2)
+            .build();
+        c.push(Gate::new(CCZ, vec![0, 3, 1]));
+        c.push(Gate::new(TOFF, vec![2, 4, 0]));
+        c.push(Gate::new(SWAP, vec![1, 2]));
+        c.push(Gate::new_with_phase(
+            XPhase,
+            vec![1],
+            Rational64::new(-2, 3),
+        ));
+        c.push(Gate::new_with_phase(ZPhase, vec![1], Rational64::new(1, 8)));
+        let json = encode_circuit(&c).unwrap();
+        let c1 = decode_circuit(&json).unwrap();
+        assert_eq!(c1.num_gates(), c.num_gates());
+        assert_eq!(gates_per_qubit(&c1), gates_per_qubit(&c));
+
+        let mut c = Circuit::new(3);
+        c.push(Gate::new(XCX, vec![0, 1]));
+        c.push(Gate::new_with_phase(
+            ParityPhase,
+            vec![0, 1, 2],
+            Rational64::new(1, 4),
+        ));
+        let c1 = decode_circuit(&encode_circuit(&c).unwrap()).unwrap();
+        let g: Graph = c.to_graph();
+        let g1: Graph = c1.to_graph();
+        assert!(Tensor4::scalar_compare(&g, &g1));
+
+        let mut c = Circuit::new(1);
+        c.push(Gate::new(InitAncilla, vec![0]));
+        assert!(encode_circuit(&c).is_err());
+    }
+
+    #[test]
+    fn cirq_moments() {
+        let c = Circuit::from_qasm("qreg q[3]; h q[0]; h q[1]; cx q[0], q[2]; z q[1];").unwrap();
+        let cc: CirqCircuit = serde_json::from_str(&encode_circuit(&c).unwrap()).unwrap();
+        let sizes: Vec<usize> = cc.moments.iter().map(|m| m.operations.len()).collect();
+        assert_eq!(sizes, vec![2, 2]);
+    }
+
+    #[test]
+    fn cirq_decode() {
+        let json = r#"{
+          "cirq_type": "Circuit",
+          "moments": [
+            {
+              "cirq_type": "Moment",
+              "operations": [
+                {
+                  "cirq_type": "GateOperation",
+                  "gate": {"cirq_type": "YPowGate", "exponent": 0.5, "global_shift": 0.0},
+                  "qubits": [{"cirq_type": "GridQubit", "row": 1, "col": 0}]
+                },
+                {
+                  "cirq_type": "GateOperation",
+                  "gate": {"cirq_type": "Rz", "rads": 0.7853981633974483},
+                  "qubits": [{"cirq_type": "GridQubit", "row": 0, "col": 3}]
+                }
+              ]
+            },
+            {
+              "cirq_type": "Moment",
+              "operations": [
+                {
+                  "cirq_type": "GateOperation",
+                  "gate": {"cirq_type": "CZPowGate", "exponent": 0.5, "global_shift": 0.0},
+                  "qubits": [
+                    {"cirq_type": "GridQubit", "row": 1, "col": 0},
+                    {"cirq_type": "GridQubit", "row": 0, "col": 3}
+                  ]
+                }
+              ]
+            }
+          ],
+          "device": {"cirq_type": "_UnconstrainedDevice"}
+        }"#;
+        let c = decode_circuit(json).unwrap();
+        let c1 = Circuit::from_qasm(
+            "qreg q[2];
+             sdg q[1]; rx(pi/2) q[1]; s q[1];
+             t q[0];
+             t q[1]; t q[0]; cx q[1], q[0]; tdg q[0]; cx q[1], q[0];",
+        )
+        .unwrap();
+        assert_eq!(c.num_qubits(), 2);
+        assert!(Tensor4::scalar_compare(&c, &c1));
+
+        let json = json.replace("CZPowGate", "ISwapPowGate");
+        assert!(matches!(
+            decode_circuit(&json),
+            Err(CirqError::UnsupportedCirq(_))
+        ));
+    }
+}
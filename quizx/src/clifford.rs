@@ -0,0 +1,230 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Normal forms for Clifford diagrams
+//!
+//! Every Clifford diagram is equal to a graph state with a local Clifford on each
+//! qubit, the GSLC normal form. Here the inputs and outputs are treated alike,
+//! so a diagram with `n` inputs and `m` outputs gives a graph state on `n + m`
+//! qubits. [Gslc::from_graph] computes this form with [clifford_simp], and
+//! [Gslc::to_graph] turns it back into a diagram.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::clifford::Gslc;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; s q[1];").unwrap();
+//! let g: Graph = c.to_graph();
+//! let nf = Gslc::from_graph(&g).unwrap();
+//! assert_eq!(nf.num_qubits(), 4);
+//! let h: Graph = nf.to_graph();
+//! ```
+
+use crate::graph::*;
+use crate::linalg::Mat2;
+use crate::phase::Phase;
+use crate::scalar::*;
+use crate::simplify::{clifford_simp, hadamard_simp, scalar_simp};
+use rustc_hash::FxHashSet;
+
+/// The local Clifford on one qubit of a graph state
+///
+/// This is a Z phase, followed by a Hadamard if `hadamard` is set. In the diagram,
+/// these are the phase of the spider for the qubit and the type of the edge to its
+/// boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalClifford {
+    pub phase: Phase,
+    pub hadamard: bool,
+}
+
+/// A Clifford diagram in GSLC normal form, see the [module docs](self)
+///
+/// The qubits are numbered as the inputs of the diagram, followed by its
+/// outputs. Two diagrams with the same form are equal, but the converse need not
+/// hold: graph states related by local complementation can give different forms
+/// for the same diagram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gslc {
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+    /// The adjacency matrix of the graph state
+    pub adjacency: Mat2,
+    /// The local Clifford on each qubit
+    pub local: Vec<LocalClifford>,
+    pub scalar: ScalarN,
+}
+
+impl Gslc {
+    /// Compute the normal form of a diagram
+    ///
+    /// Returns None if the diagram is not Clifford, i.e. if it has a spider whose
+    /// phase is not a multiple of π/2, a symbolic phase, or an H-box which is not
+    /// a Hadamard edge.
+    pub fn from_graph(g: &impl GraphLike) -> Option<Gslc> {
+        let mut h = g.clone();
+        hadamard_simp(&mut h);
+        let clifford = h.vertices().all(|v| match h.vertex_type(v) {
+            VType::B => true,
+            VType::Z | VType::X => h.phase(v).is_clifford() && !h.has_symbolic_phase(v),
+            _ => false,
+        });
+        if !clifford {
+            return None;
+        }
+
+        clifford_simp(&mut h);
+        scalar_simp(&mut h);
+
+        // give every boundary a spider of its own
+        let bs: Vec<V> = h.inputs().iter().chain(h.outputs()).copied().collect();
+        let mut spiders: Vec<V> = Vec::with_capacity(bs.len());
+        let mut owned: FxHashSet<V> = FxHashSet::default();
+        for &b in &bs {
+            let mut w = h.neighbors(b).next()?;
+            let et = h.edge_type(b, w);
+            if h.vertex_type(w) == VType::B {
+                // a bare wire
+                let v = h.add_vertex(VType::Z);
+                h.remove_edge(b, w);
+                h.add_edge_with_type(b, v, et);
+                h.add_edge(v, w);
+                w = v;
+            } else if owned.contains(&w) {
+                let v = h.add_vertex(VType::Z);
+                h.remove_edge(b, w);
+                h.add_edge_with_type(w, v, EType::H);
+                h.add_edge_with_type(v, b, et.opposite());
+                w = v;
+            }
+            owned.insert(w);
+            spiders.push(w);
+        }
+
+        // any other spider is interior, which clifford_simp removes from a Clifford diagram
+        if h.num_vertices() != bs.len() + spiders.len() {
+            return None;
+        }
+
+        let adjacency = Mat2::build(spiders.len(), spiders.len(), |i, j| {
+            i != j && h.connected(spiders[i], spiders[j])
+        });
+        let local = bs
+            .iter()
+            .zip(&spiders)
+            .map(|(&b, &v)| LocalClifford {
+                phase: h.phase(v),
+                hadamard: h.edge_type(b, v) == EType::H,
+            })
+            .collect();
+
+        Some(Gslc {
+            num_inputs: h.inputs().len(),
+            num_outputs: h.outputs().len(),
+            adjacency,
+            local,
+            scalar: h.scalar().clone(),
+        })
+    }
+
+    /// The number of qubits of the graph state, i.e. the number of boundaries
+    pub fn num_qubits(&self) -> usize {
+        self.local.len()
+    }
+
+    /// The diagram of the normal form
+    ///
+    /// This is a graph-like diagram with one spider for each qubit.
+    pub fn to_graph<G: GraphLike>(&self) -> G {
+        let mut g = G::new();
+        let n = self.num_qubits();
+        let spiders: Vec<V> = self
+            .local
+            .iter()
+            .map(|lc| g.add_vertex_with_phase(VType::Z, lc.phase))
+            .collect();
+        let bs: Vec<V> = (0..n).map(|_| g.add_vertex(VType::B)).collect();
+        for i in 0..n {
+            let (qubit, row) = if i < self.num_inputs {
+                (i, 0)
+            } else {
+                (i - self.num_inputs, 2)
+            };
+            g.set_qubit(bs[i], qubit as i32);
+            g.set_row(bs[i], row);
+            g.set_qubit(spiders[i], qubit as i32);
+            g.set_row(spiders[i], 1);
+            let et = if self.local[i].hadamard {
+                EType::H
+            } else {
+                EType::N
+            };
+            g.add_edge_with_type(spiders[i], bs[i], et);
+            for j in 0..i {
+                if self.adjacency[(i, j)] == 1 {
+                    g.add_edge_with_type(spiders[i], spiders[j], EType::H);
+                }
+            }
+        }
+        g.set_inputs(bs[..self.num_inputs].to_vec());
+        g.set_outputs(bs[self.num_inputs..].to_vec());
+        *g.scalar_mut() = self.scalar.clone();
+        g
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+    use num::Zero;
+
+    #[test]
+    fn gslc_round_trip() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(30)
+                .clifford_t(0.0)
+                .build();
+            let g: Graph = c.to_graph();
+            let nf = Gslc::from_graph(&g).unwrap();
+            assert_eq!(nf.num_qubits(), 8);
+            assert_eq!(nf.adjacency, nf.adjacency.transpose());
+            let h: Graph = nf.to_graph();
+            assert!(h.is_graph_like());
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+        }
+    }
+
+    #[test]
+    fn gslc_wires() {
+        // a wire is a Bell state, i.e. an edge with a Hadamard on one side
+        let c = Circuit::from_qasm("qreg q[1]; s q[0]; sdg q[0];").unwrap();
+        let g: Graph = c.to_graph();
+        let nf = Gslc::from_graph(&g).unwrap();
+        assert_eq!(nf.adjacency, Mat2::new(vec![vec![0, 1], vec![1, 0]]));
+        assert!(nf.local.iter().all(|lc| lc.phase.is_zero()));
+        assert_eq!(nf.local[0].hadamard, !nf.local[1].hadamard);
+
+        let c = Circuit::from_qasm("qreg q[2]; t q[0];").unwrap();
+        let g: Graph = c.to_graph();
+        assert_eq!(Gslc::from_graph(&g), None);
+    }
+}
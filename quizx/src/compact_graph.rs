@@ -0,0 +1,458 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A vector-based graph backend with inline adjacency lists.
+//!
+//! This is laid out like [crate::vec_graph::Graph], except that each adjacency
+//! list is a [SmallVec] which keeps up to [NHD_INLINE] neighbours inside the
+//! adjacency table itself. Most spiders in circuit-derived diagrams have low
+//! degree, so a graph is mostly stored in two contiguous buffers, and cloning
+//! it is dominated by two memcpys rather than one allocation per vertex. This
+//! suits the clone-then-mutate-a-few-vertices pattern of the decomposer.
+//!
+//! See the `clone_bench` example for a comparison with the other backends.
+
+pub use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use num::rational::Rational64;
+use smallvec::SmallVec;
+use std::mem;
+
+/// The number of neighbours stored inline before an adjacency list spills to the heap
+pub const NHD_INLINE: usize = 4;
+
+/// The adjacency list of a single vertex
+pub type Nhd = SmallVec<[(V, EType); NHD_INLINE]>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+    vdata: Vec<Option<VData>>,
+    // deleted vertices have an empty adjacency list
+    edata: Vec<Nhd>,
+    holes: Vec<V>, // places where a vertex has been deleted
+    inputs: Vec<V>,
+    outputs: Vec<V>,
+    numv: usize,
+    nume: usize,
+    scalar: ScalarN,
+    stats: GraphStats,
+    bqubits: BoundaryQubits,
+}
+
+impl Graph {
+    fn nhd(&self, v: V) -> &Nhd {
+        if let Some(Some(_)) = self.vdata.get(v) {
+            &self.edata[v]
+        } else {
+            panic!("Vertex not found")
+        }
+    }
+
+    /// Removes vertex 't' from the adjacency list of 's'. This private method
+    /// is used by remove_edge and remove_vertex to make the latter slightly
+    /// more efficient.
+    fn remove_half_edge(&mut self, s: V, t: V) {
+        if let Some(nhd) = self.edata.get_mut(s) {
+            if let Some(i) = nhd.iter().position(|&(v, _)| v == t) {
+                nhd.swap_remove(i);
+                self.stats.change_degree(nhd.len() + 1, nhd.len());
+            }
+        }
+    }
+
+    /// Appends 't' to the adjacency list of 's', keeping the cached stats up to date.
+    fn add_half_edge(&mut self, s: V, t: V, ety: EType, err: &str) {
+        if !self.contains_vertex(s) {
+            panic!("{}", err);
+        }
+        let nhd = &mut self.edata[s];
+        nhd.push((t, ety));
+        self.stats.change_degree(nhd.len() - 1, nhd.len());
+    }
+
+    /// Sets the type of the edge to 't' in the adjacency list of 's'.
+    fn set_half_edge_type(&mut self, s: V, t: V, ety: EType) {
+        let nhd = self.edata.get_mut(s).expect("Vertex not found");
+        let i = nhd
+            .iter()
+            .position(|&(v, _)| v == t)
+            .expect("Edge not found");
+        nhd[i] = (t, ety);
+    }
+
+    /// Applies `f` to the data of vertex `v`, keeping the cached stats up to date.
+    fn update_vdata(&mut self, v: V, f: impl FnOnce(&mut VData)) {
+        if let Some(Some(d)) = self.vdata.get_mut(v) {
+            let old = *d;
+            f(d);
+            self.stats.update_vertex(&old, d);
+        } else {
+            panic!("Vertex not found");
+        }
+    }
+
+    fn vdata_mut(&mut self, v: V) -> &mut VData {
+        if let Some(Some(d)) = self.vdata.get_mut(v) {
+            d
+        } else {
+            panic!("Vertex not found")
+        }
+    }
+}
+
+impl GraphLike for Graph {
+    fn new() -> Graph {
+        Graph {
+            vdata: Vec::new(),
+            edata: Vec::new(),
+            holes: Vec::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            numv: 0,
+            nume: 0,
+            scalar: Scalar::one(),
+            stats: GraphStats::default(),
+            bqubits: BoundaryQubits::default(),
+        }
+    }
+
+    fn vindex(&self) -> V {
+        self.vdata.len()
+    }
+
+    fn num_vertices(&self) -> usize {
+        self.numv
+    }
+
+    fn num_edges(&self) -> usize {
+        self.nume
+    }
+
+    fn vertices(&self) -> VIter<'_> {
+        VIter::Vec(self.numv, self.vdata.iter().enumerate())
+    }
+
+    fn edges(&self) -> EIter<'_> {
+        EIter::Compact(self.nume, self.edata.iter().enumerate(), None)
+    }
+
+    fn inputs(&self) -> &Vec<V> {
+        &self.inputs
+    }
+    fn inputs_mut(&mut self) -> &mut Vec<V> {
+        &mut self.inputs
+    }
+    fn set_inputs(&mut self, inputs: Vec<V>) {
+        self.inputs = inputs;
+    }
+    fn outputs(&self) -> &Vec<V> {
+        &self.outputs
+    }
+    fn set_outputs(&mut self, outputs: Vec<V>) {
+        self.outputs = outputs;
+    }
+    fn outputs_mut(&mut self) -> &mut Vec<V> {
+        &mut self.outputs
+    }
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        self.add_vertex_with_data(VData {
+            ty,
+            phase: Rational64::new(0, 1).into(),
+            qubit: 0,
+            row: 0,
+        })
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        self.numv += 1;
+        self.stats.add_vertex(&d);
+        if let Some(v) = self.holes.pop() {
+            self.vdata[v] = Some(d);
+            v
+        } else {
+            self.vdata.push(Some(d));
+            self.edata.push(Nhd::new());
+            self.vdata.len() - 1
+        }
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        self.numv -= 1;
+        self.holes.push(v);
+
+        let d = mem::take(&mut self.vdata[v]).expect("No such vertex.");
+        let adj = mem::take(&mut self.edata[v]);
+        self.bqubits.remove(v);
+        self.stats.remove_vertex(&d, adj.len());
+
+        for (v1, _) in adj {
+            self.nume -= 1;
+            self.remove_half_edge(v1, v);
+        }
+    }
+
+    fn add_edge_with_type(&mut self, s: V, t: V, ety: EType) {
+        self.nume += 1;
+        self.add_half_edge(s, t, ety, "Source vertex not found");
+        self.add_half_edge(t, s, ety, "Target vertex not found");
+    }
+
+    fn remove_edge(&mut self, s: V, t: V) {
+        self.nume -= 1;
+        self.remove_half_edge(s, t);
+        self.remove_half_edge(t, s);
+    }
+
+    fn set_phase(&mut self, v: V, phase: impl Into<Phase>) {
+        let phase = phase.into();
+        self.update_vdata(v, |d| d.phase = phase);
+    }
+
+    fn phase(&self, v: V) -> Phase {
+        self.vdata[v].expect("Vertex not found").phase
+    }
+
+    fn add_to_phase(&mut self, v: V, phase: impl Into<Phase>) {
+        let phase = phase.into();
+        self.update_vdata(v, |d| d.phase = (d.phase + phase).normalize());
+    }
+
+    fn set_vertex_type(&mut self, v: V, ty: VType) {
+        self.update_vdata(v, |d| d.ty = ty);
+    }
+
+    fn vertex_data(&self, v: V) -> VData {
+        self.vdata[v].expect("Vertex not found")
+    }
+
+    fn vertex_type(&self, v: V) -> VType {
+        self.vdata[v].expect("Vertex not found").ty
+    }
+
+    fn set_edge_type(&mut self, s: V, t: V, ety: EType) {
+        self.set_half_edge_type(s, t, ety);
+        self.set_half_edge_type(t, s, ety);
+    }
+
+    fn edge_type_opt(&self, s: V, t: V) -> Option<EType> {
+        self.edata
+            .get(s)?
+            .iter()
+            .find(|&&(v, _)| v == t)
+            .map(|&(_, et)| et)
+    }
+
+    fn set_coord(&mut self, v: V, coord: impl Into<Coord>) {
+        let coord = coord.into();
+        let d = self.vdata_mut(v);
+        d.qubit = coord.x;
+        d.row = coord.y;
+    }
+
+    fn coord(&self, v: V) -> Coord {
+        let d = self.vdata[v].expect("Vertex not found");
+        Coord::new(d.qubit, d.row)
+    }
+
+    fn set_qubit(&mut self, v: V, qubit: i32) {
+        self.vdata_mut(v).qubit = qubit;
+    }
+
+    fn qubit(&self, v: V) -> i32 {
+        self.vdata[v].expect("Vertex not found").qubit
+    }
+
+    fn set_row(&mut self, v: V, row: i32) {
+        self.vdata_mut(v).row = row;
+    }
+
+    fn row(&self, v: V) -> i32 {
+        self.vdata[v].expect("Vertex not found").row
+    }
+
+    fn neighbors(&self, v: V) -> NeighborIter<'_> {
+        NeighborIter::Vec(self.nhd(v).iter())
+    }
+
+    fn incident_edges(&self, v: V) -> IncidentEdgeIter<'_> {
+        IncidentEdgeIter::Vec(self.nhd(v).iter())
+    }
+
+    fn degree(&self, v: V) -> usize {
+        self.nhd(v).len()
+    }
+
+    fn scalar(&self) -> &ScalarN {
+        &self.scalar
+    }
+    fn scalar_mut(&mut self) -> &mut ScalarN {
+        &mut self.scalar
+    }
+
+    fn stats(&self) -> &GraphStats {
+        &self.stats
+    }
+
+    fn boundary_qubits(&self) -> &BoundaryQubits {
+        &self.bqubits
+    }
+
+    fn boundary_qubits_mut(&mut self) -> &mut BoundaryQubits {
+        &mut self.bqubits
+    }
+
+    fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
+    where
+        F: Fn(V, V, EType) -> bool,
+    {
+        for (v0, nhd) in self.edata.iter().enumerate() {
+            for &(v1, et) in nhd.iter() {
+                if v0 <= v1 && f(v0, v1, et) {
+                    return Some((v0, v1, et));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_vertex<F>(&self, f: F) -> Option<V>
+    where
+        F: Fn(V) -> bool,
+    {
+        for (v, d) in self.vdata.iter().enumerate() {
+            if d.is_some() && f(v) {
+                return Some(v);
+            }
+        }
+
+        None
+    }
+
+    fn contains_vertex(&self, v: V) -> bool {
+        v < self.vdata.len() && self.vdata[v].is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::decompose::Decomposer;
+    use crate::simplify::full_simp;
+    use crate::tensor::ToTensor;
+
+    fn simple_graph() -> (Graph, Vec<V>) {
+        let mut g = Graph::new();
+        let vs = vec![
+            g.add_vertex(VType::B),
+            g.add_vertex(VType::B),
+            g.add_vertex(VType::Z),
+            g.add_vertex(VType::Z),
+            g.add_vertex(VType::X),
+            g.add_vertex(VType::X),
+            g.add_vertex(VType::B),
+            g.add_vertex(VType::B),
+        ];
+        g.add_edge(vs[0], vs[2]);
+        g.add_edge(vs[1], vs[3]);
+        g.add_edge(vs[2], vs[4]);
+        g.add_edge(vs[2], vs[5]);
+        g.add_edge(vs[3], vs[4]);
+        g.add_edge(vs[3], vs[5]);
+        g.add_edge(vs[4], vs[6]);
+        g.add_edge(vs[5], vs[7]);
+        (g, vs)
+    }
+
+    #[test]
+    fn edge_iterator() {
+        let (mut g, vs) = simple_graph();
+        g.set_edge_type(vs[1], vs[3], EType::H);
+
+        let mut edges = Vec::from_iter(g.edges());
+        let mut expected_edges = vec![
+            (vs[0], vs[2], EType::N),
+            (vs[1], vs[3], EType::H),
+            (vs[2], vs[4], EType::N),
+            (vs[2], vs[5], EType::N),
+            (vs[3], vs[4], EType::N),
+            (vs[3], vs[5], EType::N),
+            (vs[4], vs[6], EType::N),
+            (vs[5], vs[7], EType::N),
+        ];
+
+        edges.sort();
+        expected_edges.sort();
+        assert_eq!(expected_edges, edges);
+    }
+
+    #[test]
+    fn holes_and_spills() {
+        let (mut g, vs) = simple_graph();
+        g.remove_vertex(vs[2]);
+        assert_eq!(g.num_vertices(), 7);
+        assert_eq!(g.num_edges(), 5);
+        assert_eq!(g.edges().count(), 5);
+        assert!(!g.vertex_vec().contains(&vs[2]));
+
+        // reuse the hole, and give it more neighbours than fit inline
+        let v = g.add_vertex(VType::Z);
+        assert_eq!(v, vs[2]);
+        for &w in &vs {
+            if w != v {
+                g.add_edge(v, w);
+            }
+        }
+        assert_eq!(g.degree(v), 7);
+        assert!(g.edata[v].spilled());
+        assert_eq!(g.num_edges(), 12);
+        assert_eq!(g.edges().count(), 12);
+    }
+
+    #[test]
+    fn simp_and_decompose() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        let mut h: crate::vec_graph::Graph = c.to_graph();
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        full_simp(&mut g);
+        full_simp(&mut h);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        g.plug_inputs(&[BasisElem::Z0; 4]);
+        g.plug_outputs(&[BasisElem::Z0; 4]);
+        h.plug_inputs(&[BasisElem::Z0; 4]);
+        h.plug_outputs(&[BasisElem::Z0; 4]);
+
+        let mut d = Decomposer::new(&g);
+        d.with_full_simp();
+        d.decomp_all();
+        let mut e = Decomposer::new(&h);
+        e.with_full_simp();
+        e.decomp_all();
+        assert_eq!(d.scalar, e.scalar);
+    }
+}
@@ -0,0 +1,243 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Controlled versions of circuits
+//!
+//! [Circuit::controlled] adds a control qubit to every gate of a circuit. Gates with
+//! a controlled version in quizx become that gate, e.g. CNOT becomes a Toffoli, and
+//! the rest are written with the gates of quizx:
+//!
+//! - a phase `α` controlled on `c` is the phase polynomial
+//!   `α/2 (c + q - c⊕q) = α c·q`, so a controlled S uses three T gates, and a
+//!   controlled T uses phases of `π/8`,
+//! - X phases are controlled Z phases between Hadamards,
+//! - a controlled Hadamard uses two T gates, as in `qelib1.inc`,
+//! - a controlled Toffoli or CCZ is a CCCZ, which is the phase polynomial with
+//!   phases `±π/8` on all 15 parities of its qubits, and
+//! - a controlled swap is a Toffoli between two CNOTs.
+//!
+//! The result is equal to the controlled circuit up to a global phase.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::gate::*;
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; s q[1];").unwrap();
+//! let cc = c.controlled();
+//! assert_eq!(cc.num_qubits(), 3);
+//! assert_eq!(cc.num_gates_of_type(TOFF), 1);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::optimize_circuit::{rotation, rotation_gate};
+use crate::phase::Phase;
+
+/// Pushes the Z phase `phase` on `q` controlled on `c`
+pub(crate) fn push_controlled_phase(circ: &mut Circuit, c: usize, q: usize, phase: Phase) {
+    let half = phase / 2;
+    circ.gates.extend(rotation_gate(false, half, c));
+    circ.gates.extend(rotation_gate(false, half, q));
+    circ.push(Gate::new(CNOT, vec![c, q]));
+    circ.gates.extend(rotation_gate(false, -half, q));
+    circ.push(Gate::new(CNOT, vec![c, q]));
+}
+
+/// Pushes a Z on the last of `qs` controlled on all the others, as a phase
+/// polynomial on all the parities of `qs`
+fn push_multi_cz(circ: &mut Circuit, qs: &[usize]) {
+    let n = qs.len();
+    let denom = 1 << (n - 1);
+    for subset in 1..(1usize << n) {
+        let parity: Vec<usize> = (0..n)
+            .filter(|&i| subset & (1 << i) != 0)
+            .map(|i| qs[i])
+            .collect();
+        let sign = if parity.len() % 2 == 1 { 1 } else { -1 };
+        Gate::new_with_phase(ParityPhase, parity, Phase::from((sign, denom)))
+            .push_basic_gates(circ);
+    }
+}
+
+/// Pushes the gate `g` controlled on `c`
+pub(crate) fn push_controlled_gate(circ: &mut Circuit, c: usize, g: &Gate) {
+    assert!(
+        g.condition.is_none(),
+        "Cannot add a control to the classically controlled gate {}",
+        g.to_qasm()
+    );
+    let qs = &g.qs;
+    let h = |q: usize| Gate::new(HAD, vec![q]);
+    match g.t {
+        Barrier => circ.push(g.clone()),
+        NOT => circ.push(Gate::new(CNOT, vec![c, qs[0]])),
+        Z => circ.push(Gate::new(CZ, vec![c, qs[0]])),
+        CNOT => circ.push(Gate::new(TOFF, vec![c, qs[0], qs[1]])),
+        CZ => circ.push(Gate::new(CCZ, vec![c, qs[0], qs[1]])),
+        XCX => {
+            circ.push(h(qs[0]));
+            circ.push(h(qs[1]));
+            circ.push(Gate::new(CCZ, vec![c, qs[0], qs[1]]));
+            circ.push(h(qs[0]));
+            circ.push(h(qs[1]));
+        }
+        SWAP => {
+            circ.push(Gate::new(CNOT, vec![qs[1], qs[0]]));
+            circ.push(Gate::new(TOFF, vec![c, qs[0], qs[1]]));
+            circ.push(Gate::new(CNOT, vec![qs[1], qs[0]]));
+        }
+        HAD => {
+            let q = qs[0];
+            circ.push(h(q));
+            circ.push(Gate::new(Sdg, vec![q]));
+            circ.push(Gate::new(CNOT, vec![c, q]));
+            circ.push(h(q));
+            circ.push(Gate::new(T, vec![q]));
+            circ.push(Gate::new(CNOT, vec![c, q]));
+            circ.push(Gate::new(T, vec![q]));
+            circ.push(h(q));
+            circ.push(Gate::new(S, vec![q]));
+            circ.push(Gate::new(NOT, vec![q]));
+            circ.push(Gate::new(S, vec![c]));
+        }
+        CCZ => push_multi_cz(circ, &[c, qs[0], qs[1], qs[2]]),
+        TOFF => {
+            circ.push(h(qs[2]));
+            push_multi_cz(circ, &[c, qs[0], qs[1], qs[2]]);
+            circ.push(h(qs[2]));
+        }
+        ParityPhase if g.params.is_zero() => {
+            if let Some((&t, rest)) = qs.split_last() {
+                for &q in rest {
+                    circ.push(Gate::new(CNOT, vec![q, t]));
+                }
+                push_controlled_phase(circ, c, t, g.phase);
+                for &q in rest.iter().rev() {
+                    circ.push(Gate::new(CNOT, vec![q, t]));
+                }
+            }
+        }
+        _ => match rotation(g) {
+            Some((false, phase)) => push_controlled_phase(circ, c, qs[0], phase),
+            Some((true, phase)) => {
+                circ.push(h(qs[0]));
+                push_controlled_phase(circ, c, qs[0], phase);
+                circ.push(h(qs[0]));
+            }
+            None => panic!("Cannot add a control to the gate {}", g.to_qasm()),
+        },
+    }
+}
+
+impl Circuit {
+    /// The circuit with a control on every gate, up to a global phase
+    ///
+    /// The control is a new qubit, after the existing ones. See the
+    /// [module docs](crate::controlled). This panics if the circuit isn't unitary,
+    /// e.g. if it has post-selections or measurements, or if a gate has parameters.
+    pub fn controlled(&self) -> Circuit {
+        let c = self.num_qubits();
+        let mut circ = Circuit::new(c + 1);
+        for g in &self.gates {
+            push_controlled_gate(&mut circ, c, g);
+        }
+        circ
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    /// Checks that `c.controlled()` is `|0><0| ⊗ I + |1><1| ⊗ c`, up to a global phase
+    fn check_controlled(c: &Circuit) {
+        let n = c.num_qubits();
+        let cc = c.controlled();
+        let branch = |b: BasisElem| {
+            let mut g: Graph = cc.to_graph();
+            g.plug_output(n, b);
+            g.plug_input(n, b);
+            g.to_tensorf()
+        };
+        let t0 = branch(BasisElem::Z0);
+        let t1 = branch(BasisElem::Z1);
+        let id = Circuit::new(n).to_tensorf();
+        let u = c.to_basic_gates().to_tensorf();
+
+        let lambda = *t0.iter().next().unwrap();
+        assert!((lambda.norm() - 1.0).abs() < 1e-6);
+        for (x, y) in t0.iter().zip(id.iter()).chain(t1.iter().zip(u.iter())) {
+            assert!((*x - lambda * *y).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn controlled_gates() {
+        let mut c = Circuit::new(3);
+        c.push(Gate::new(HAD, vec![0]));
+        c.push(Gate::new(HAD, vec![1]));
+        c.push(Gate::new(T, vec![2]));
+        for t in [NOT, Z, S, Sdg, T, Tdg, HAD] {
+            c.push(Gate::new(t, vec![1]));
+        }
+        c.push(Gate::new_with_phase(ZPhase, vec![0], Phase::from((1, 3))));
+        c.push(Gate::new_with_phase(XPhase, vec![2], Phase::from((3, 4))));
+        c.push(Gate::new(CNOT, vec![2, 0]));
+        c.push(Gate::new(CZ, vec![1, 2]));
+        c.push(Gate::new(SWAP, vec![0, 1]));
+        c.push(Gate::new(TOFF, vec![0, 2, 1]));
+        c.push(Gate::new(CCZ, vec![1, 0, 2]));
+        c.push(Gate::new_with_phase(
+            ParityPhase,
+            vec![0, 1, 2],
+            Phase::from((1, 4)),
+        ));
+        check_controlled(&c);
+    }
+
+    #[test]
+    fn controlled_random() {
+        for seed in 0..3 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(3)
+                .depth(20)
+                .clifford_t(0.3)
+                .build();
+            check_controlled(&c);
+            check_controlled(&c.inverse());
+            check_controlled(&c.repeat(2));
+        }
+    }
+
+    #[test]
+    fn inverse_and_repeat() {
+        let c = Circuit::from_qasm("qreg q[2]; h q[0]; t q[0]; cx q[0], q[1];").unwrap();
+        assert_eq!(c.repeat(3).num_gates(), 9);
+        assert_eq!(c.repeat(0), Circuit::new(2));
+        assert!(Tensor4::scalar_compare(
+            &(c.inverse() + &c),
+            &Circuit::new(2)
+        ));
+
+        let mut s = Circuit::new(1);
+        s.push(Gate::new(T, vec![0]));
+        let z = Circuit::from_qasm("qreg q[1]; z q[0];").unwrap();
+        assert!(Tensor4::scalar_compare(&s.repeat(4), &z));
+    }
+}
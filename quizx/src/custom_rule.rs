@@ -0,0 +1,493 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rewrite rules given by a pair of diagrams
+//!
+//! A [CustomRule] replaces a copy of its left-hand side in a diagram by its
+//! right-hand side. Both sides are open diagrams with the same number of inputs
+//! and outputs, and the boundaries are paired up by position: the inputs of the
+//! two sides in order, followed by the outputs. [CustomRule::verify] checks that
+//! the two sides are equal by comparing their tensors.
+//!
+//! The left-hand side matches a set of spiders in a diagram if there is a
+//! bijection between them and the spiders of the left-hand side which preserves
+//! types, phases and the edges among them, and if every other edge at one of
+//! these spiders is matched to a boundary of the left-hand side, with the same
+//! edge type. Phases must be equal, so a rule only matches concrete phases.
+//!
+//! Rules can be used in a [crate::strategy::Strategy], see
+//! [crate::strategy::custom].
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::custom_rule::CustomRule;
+//! # use quizx::graph::*;
+//! # use quizx::vec_graph::Graph;
+//! // two T spiders in a row fuse to an S spider
+//! let lhs: Graph = Circuit::from_qasm("qreg q[1]; t q[0]; t q[0];").unwrap().to_graph();
+//! let rhs: Graph = Circuit::from_qasm("qreg q[1]; s q[0];").unwrap().to_graph();
+//! let rule = CustomRule::new("t_fusion", lhs, rhs).unwrap();
+//! assert!(rule.verify().is_ok());
+//!
+//! let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; t q[1]; t q[1];").unwrap();
+//! let mut g: Graph = c.to_graph();
+//! assert!(rule.simp(&mut g));
+//! assert_eq!(g.tcount(), 0);
+//! ```
+
+use crate::graph::*;
+use crate::strategy::Pass;
+use crate::tensor::ToTensor;
+use crate::vec_graph::Graph;
+use num::One;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// The largest number of boundaries for which [CustomRule::verify] computes
+/// tensors
+pub const MAX_VERIFY_BOUNDARIES: usize = 16;
+
+/// An error constructing or verifying a [CustomRule]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CustomRuleError {
+    #[error("Invalid rule name '{0}'")]
+    InvalidName(String),
+    #[error("The two sides have different numbers of inputs or outputs")]
+    BoundaryMismatch,
+    #[error("Every boundary on the {0} side must be an input or output with one edge")]
+    InvalidBoundary(&'static str),
+    #[error("The left side must contain a spider")]
+    EmptyLhs,
+    #[error("The left side must not contain a bare wire")]
+    LhsWire,
+    #[error("The left side must have scalar one")]
+    LhsScalar,
+    #[error("The rule has {0} boundaries, too many to verify")]
+    TooLarge(usize),
+    #[error("The two sides are not equal")]
+    NotEqual,
+}
+
+/// A rewrite rule given by a pair of diagrams, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomRule {
+    name: String,
+    lhs: Graph,
+    rhs: Graph,
+    /// The spiders of the left side, in the order they are matched
+    order: Vec<V>,
+    /// For each spider in `order`, an earlier one it is connected to
+    anchor: Vec<Option<usize>>,
+    /// For each boundary of the left side, the index in `order` of its spider and
+    /// the type of the edge to it
+    boundary: Vec<(usize, EType)>,
+}
+
+impl Eq for CustomRule {}
+
+/// A match of a [CustomRule]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CustomMatch {
+    /// The vertices matched to the spiders of the left side
+    pub vertices: Vec<V>,
+}
+
+fn boundaries(g: &Graph) -> Vec<V> {
+    g.inputs().iter().chain(g.outputs()).copied().collect()
+}
+
+impl CustomRule {
+    /// Make a rule which rewrites `lhs` to `rhs`
+    ///
+    /// The name is used to refer to the rule in a strategy, so it must be a word
+    /// which is not already the name of a pass or combinator. Any scalar factor
+    /// should go on the right side, since the left side must have scalar one. The
+    /// rule is not checked for correctness, see [CustomRule::verify].
+    pub fn new(name: &str, lhs: Graph, rhs: Graph) -> Result<CustomRule, CustomRuleError> {
+        if name.is_empty()
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            || Pass::from_name(name).is_some()
+            || ["seq", "repeat", "try", "limit"].contains(&name)
+        {
+            return Err(CustomRuleError::InvalidName(name.to_string()));
+        }
+        if lhs.inputs().len() != rhs.inputs().len() || lhs.outputs().len() != rhs.outputs().len() {
+            return Err(CustomRuleError::BoundaryMismatch);
+        }
+        for (side, h) in [("left", &lhs), ("right", &rhs)] {
+            let bs = boundaries(h);
+            let valid = bs
+                .iter()
+                .all(|&b| h.vertex_type(b) == VType::B && h.degree(b) == 1)
+                && h.vertices()
+                    .all(|v| h.vertex_type(v) != VType::B || bs.contains(&v));
+            if !valid {
+                return Err(CustomRuleError::InvalidBoundary(side));
+            }
+        }
+        if !lhs.scalar().is_one() {
+            return Err(CustomRuleError::LhsScalar);
+        }
+
+        // order the spiders so that each is connected to an earlier one, if it can be
+        let mut order = Vec::new();
+        let mut anchor = Vec::new();
+        let mut index: FxHashMap<V, usize> = FxHashMap::default();
+        for v in lhs.vertices() {
+            if lhs.vertex_type(v) == VType::B || index.contains_key(&v) {
+                continue;
+            }
+            index.insert(v, order.len());
+            order.push(v);
+            anchor.push(None);
+            let mut k = order.len() - 1;
+            while k < order.len() {
+                for w in lhs.neighbor_vec(order[k]) {
+                    if lhs.vertex_type(w) != VType::B && !index.contains_key(&w) {
+                        index.insert(w, order.len());
+                        order.push(w);
+                        anchor.push(Some(k));
+                    }
+                }
+                k += 1;
+            }
+        }
+        if order.is_empty() {
+            return Err(CustomRuleError::EmptyLhs);
+        }
+
+        let boundary = boundaries(&lhs)
+            .iter()
+            .map(|&b| {
+                let v = lhs.neighbors(b).next().unwrap();
+                index.get(&v).map(|&k| (k, lhs.edge_type(b, v)))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(CustomRuleError::LhsWire)?;
+
+        Ok(CustomRule {
+            name: name.to_string(),
+            lhs,
+            rhs,
+            order,
+            anchor,
+            boundary,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn lhs(&self) -> &Graph {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &Graph {
+        &self.rhs
+    }
+
+    /// Check that the two sides of the rule are equal, scalar included
+    ///
+    /// This compares the tensors of the two sides, so it only works for rules with
+    /// at most [MAX_VERIFY_BOUNDARIES] boundaries.
+    pub fn verify(&self) -> Result<(), CustomRuleError> {
+        let n = self.boundary.len();
+        if n > MAX_VERIFY_BOUNDARIES {
+            return Err(CustomRuleError::TooLarge(n));
+        }
+        if self.lhs.to_tensor4() == self.rhs.to_tensor4() {
+            Ok(())
+        } else {
+            Err(CustomRuleError::NotEqual)
+        }
+    }
+
+    /// Whether the `k`-th spider of the left side can be matched to `w`, given the
+    /// vertices `img` matched to the spiders before it
+    fn fits(&self, g: &impl GraphLike, k: usize, w: V, img: &[V]) -> bool {
+        let v = self.order[k];
+        g.vertex_type(w) == self.lhs.vertex_type(v)
+            && g.phase(w) == self.lhs.phase(v)
+            && !g.has_symbolic_phase(w)
+            && g.degree(w) == self.lhs.degree(v)
+            && !img.contains(&w)
+            && img
+                .iter()
+                .zip(&self.order)
+                .all(|(&w1, &v1)| g.edge_type_opt(w, w1) == self.lhs.edge_type_opt(v, v1))
+    }
+
+    /// Extend a partial match by backtracking
+    fn extend(&self, g: &impl GraphLike, img: &mut Vec<V>) -> bool {
+        let k = img.len();
+        if k == self.order.len() {
+            return self.boundary_neighbors(g, img).is_some();
+        }
+        let candidates = match self.anchor[k] {
+            Some(a) => g.neighbor_vec(img[a]),
+            None => g.vertex_vec(),
+        };
+        for w in candidates {
+            if self.fits(g, k, w, img) {
+                img.push(w);
+                if self.extend(g, img) {
+                    return true;
+                }
+                img.pop();
+            }
+        }
+        false
+    }
+
+    /// For each boundary of the left side, the neighbour outside of the match it
+    /// stands for
+    fn boundary_neighbors(&self, g: &impl GraphLike, img: &[V]) -> Option<Vec<V>> {
+        let mut used = FxHashSet::default();
+        let mut ws = Vec::with_capacity(self.boundary.len());
+        for &(k, et) in &self.boundary {
+            let v = img[k];
+            let (w, _) = g
+                .incident_edges(v)
+                .find(|&(w, et1)| et1 == et && !img.contains(&w) && !used.contains(&(v, w)))?;
+            used.insert((v, w));
+            ws.push(w);
+        }
+        Some(ws)
+    }
+
+    /// Find a match where the first spider of the left side is matched to `v`
+    pub fn match_at(&self, g: &impl GraphLike, v: V) -> Option<CustomMatch> {
+        let mut img = Vec::with_capacity(self.order.len());
+        if self.fits(g, 0, v, &img) {
+            img.push(v);
+            if self.extend(g, &mut img) {
+                return Some(CustomMatch { vertices: img });
+            }
+        }
+        None
+    }
+
+    /// Iterate lazily over matches, at most one for each vertex of `g`
+    ///
+    /// Every set of spiders where the rule matches is found from one of its
+    /// vertices, but the matches found from different vertices can overlap.
+    pub fn matches<'a, G: GraphLike>(&'a self, g: &'a G) -> impl Iterator<Item = CustomMatch> + 'a {
+        g.vertices().filter_map(move |v| self.match_at(g, v))
+    }
+
+    /// Find the first match
+    pub fn find_match(&self, g: &impl GraphLike) -> Option<CustomMatch> {
+        self.matches(g).next()
+    }
+
+    /// Find a match for each vertex where there is one, see [CustomRule::matches]
+    ///
+    /// Applying one of these matches can invalidate the others.
+    pub fn find_all_matches(&self, g: &impl GraphLike) -> Vec<CustomMatch> {
+        self.matches(g).collect()
+    }
+
+    /// Whether a match still applies
+    pub fn check(&self, g: &impl GraphLike, m: &CustomMatch) -> bool {
+        let img = &m.vertices;
+        img.len() == self.order.len()
+            && img.iter().all(|&v| g.contains_vertex(v))
+            && (0..img.len()).all(|k| self.fits(g, k, img[k], &img[..k]))
+            && self.boundary_neighbors(g, img).is_some()
+    }
+
+    /// Apply the rule at a match, if it still applies
+    ///
+    /// Returns false, leaving the graph unchanged, if the match went stale.
+    pub fn apply(&self, g: &mut impl GraphLike, m: &CustomMatch) -> bool {
+        if !self.check(g, m) {
+            return false;
+        }
+        let ws = self.boundary_neighbors(g, &m.vertices).unwrap();
+
+        g.begin_rewrite("custom_rule", &m.vertices);
+        for &v in &m.vertices {
+            g.remove_vertex(v);
+        }
+
+        let mut vmap = FxHashMap::default();
+        for v in self.rhs.vertices() {
+            if self.rhs.vertex_type(v) != VType::B {
+                vmap.insert(v, g.add_vertex_with_data(self.rhs.vertex_data(v)));
+            }
+        }
+        for (s, t, et) in self.rhs.edges() {
+            if let (Some(&s1), Some(&t1)) = (vmap.get(&s), vmap.get(&t)) {
+                g.add_edge_smart(s1, t1, et);
+            }
+        }
+
+        // plug the boundaries of the right side into the rest of the graph
+        let bs = boundaries(&self.rhs);
+        for (i, &b) in bs.iter().enumerate() {
+            let n = self.rhs.neighbors(b).next().unwrap();
+            let et = self.rhs.edge_type(b, n);
+            if let Some(&n1) = vmap.get(&n) {
+                g.add_edge_smart(n1, ws[i], et);
+            } else {
+                // a bare wire between two boundaries
+                let j = bs.iter().position(|&c| c == n).unwrap();
+                if i < j {
+                    g.add_edge_smart(ws[i], ws[j], et);
+                }
+            }
+        }
+        *g.scalar_mut() *= self.rhs.scalar();
+        g.end_rewrite();
+        true
+    }
+
+    /// Apply the rule once from each vertex of `g` where it matches
+    ///
+    /// Returns true if the rule was applied. Vertices added by the rule are not
+    /// tried as starting points, so this terminates even if the rule does not, and
+    /// a strategy can repeat it with `repeat` or `limit`.
+    pub fn simp(&self, g: &mut impl GraphLike) -> bool {
+        let mut got_match = false;
+        for v in g.vertex_vec() {
+            if !g.can_rewrite() {
+                break;
+            }
+            if !g.contains_vertex(v) {
+                continue;
+            }
+            if let Some(m) = self.match_at(g, v) {
+                if g.can_rewrite_at(&m.vertices) && self.apply(g, &m) {
+                    got_match = true;
+                }
+            }
+        }
+        got_match
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::phase::Phase;
+    use num::Zero;
+
+    fn wire() -> Graph {
+        let mut g = Graph::new();
+        let i = g.add_vertex(VType::B);
+        let o = g.add_vertex(VType::B);
+        g.add_edge(i, o);
+        g.set_inputs(vec![i]);
+        g.set_outputs(vec![o]);
+        g
+    }
+
+    fn spider(phase: Phase) -> Graph {
+        let mut g = wire();
+        let (i, o) = (g.inputs()[0], g.outputs()[0]);
+        g.remove_edge(i, o);
+        let v = g.add_vertex_with_phase(VType::Z, phase);
+        g.add_edge(i, v);
+        g.add_edge(v, o);
+        g
+    }
+
+    #[test]
+    fn custom_identity() {
+        let rule = CustomRule::new("my_id", spider(Phase::zero()), wire()).unwrap();
+        assert_eq!(rule.verify(), Ok(()));
+
+        let mut g = Graph::new();
+        let vs: Vec<V> = (0..5).map(|_| g.add_vertex(VType::Z)).collect();
+        let (i, o) = (g.add_vertex(VType::B), g.add_vertex(VType::B));
+        g.add_edge(i, vs[0]);
+        for k in 0..4 {
+            g.add_edge(vs[k], vs[k + 1]);
+        }
+        g.add_edge(vs[4], o);
+        g.set_phase(vs[2], Phase::from((1, 4)));
+        g.set_inputs(vec![i]);
+        g.set_outputs(vec![o]);
+
+        let h = g.clone();
+        assert_eq!(rule.find_all_matches(&g).len(), 4);
+        while rule.simp(&mut g) {}
+        assert_eq!(g.num_vertices(), 3);
+        assert_eq!(rule.find_match(&g), None);
+        assert_eq!(h.to_tensor4(), g.to_tensor4());
+    }
+
+    #[test]
+    fn custom_fusion() {
+        let lhs: Graph = Circuit::from_qasm("qreg q[1]; t q[0]; t q[0];")
+            .unwrap()
+            .to_graph();
+        let rule = CustomRule::new("t_fusion", lhs.clone(), spider(Phase::from((1, 2)))).unwrap();
+        assert_eq!(rule.verify(), Ok(()));
+        let bad = CustomRule::new("bad", lhs.clone(), spider(Phase::from((1, 4)))).unwrap();
+        assert_eq!(bad.verify(), Err(CustomRuleError::NotEqual));
+
+        let c = Circuit::from_qasm(
+            "qreg q[2]; t q[0]; t q[0]; cx q[0], q[1]; t q[1]; t q[1]; t q[1]; h q[1];",
+        )
+        .unwrap();
+        let g: Graph = c.to_graph();
+        let mut h = g.clone();
+        assert!(rule.simp(&mut h));
+        assert_eq!(h.tcount(), 1);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        let m = rule.find_match(&g).unwrap();
+        let mut h = g.clone();
+        assert!(rule.apply(&mut h, &m));
+        assert!(!rule.apply(&mut h, &m));
+    }
+
+    #[test]
+    fn custom_errors() {
+        let id = spider(Phase::zero());
+        assert_eq!(
+            CustomRule::new("full", id.clone(), wire()),
+            Err(CustomRuleError::InvalidName("full".into()))
+        );
+        assert_eq!(
+            CustomRule::new("wire", wire(), wire()),
+            Err(CustomRuleError::EmptyLhs)
+        );
+        let mut g = id.clone();
+        let (i, o) = (g.add_vertex(VType::B), g.add_vertex(VType::B));
+        g.add_edge(i, o);
+        g.inputs_mut().push(i);
+        g.outputs_mut().push(o);
+        assert_eq!(
+            CustomRule::new("wire", g.clone(), g),
+            Err(CustomRuleError::LhsWire)
+        );
+        let mut g = id.clone();
+        g.set_outputs(vec![]);
+        assert_eq!(
+            CustomRule::new("cap", id.clone(), g),
+            Err(CustomRuleError::BoundaryMismatch)
+        );
+        let mut g = id.clone();
+        g.scalar_mut().mul_sqrt2_pow(1);
+        assert_eq!(
+            CustomRule::new("scaled", g, wire()),
+            Err(CustomRuleError::LhsScalar)
+        );
+    }
+}
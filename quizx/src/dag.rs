@@ -0,0 +1,322 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A DAG view of a circuit
+//!
+//! The nodes of a [CircuitDag] are the gates of a circuit, numbered in circuit
+//! order, and there is an edge from each gate to the later gates which must come
+//! after it. For [CircuitDag::new], these are the next gates on each of its
+//! qubits. For [CircuitDag::with_commutation], a gate only has to come after the
+//! gates it doesn't commute with, as given by [commutes], so for instance the
+//! gates of a run of Z phases and CNOT controls on a qubit don't depend on each
+//! other.
+//!
+//! Any order of the gates which respects the edges gives the same circuit, so the
+//! DAG can be used to reorder gates, for instance to bring together rotations
+//! which are separated by gates they commute with.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::dag::CircuitDag;
+//! let c = Circuit::from_qasm("qreg q[2]; t q[0]; cx q[0], q[1]; t q[0];").unwrap();
+//! assert_eq!(CircuitDag::new(&c).depth(), 3);
+//! assert_eq!(CircuitDag::with_commutation(&c).depth(), 1);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use rustc_hash::FxHashMap;
+
+/// How a gate acts on one of its qubits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QubitAction {
+    /// The gate is diagonal in the Z basis of the qubit, e.g. a Z phase or the
+    /// control of a CNOT.
+    Z,
+    /// The gate is diagonal in the X basis of the qubit, e.g. an X phase or the
+    /// target of a CNOT.
+    X,
+    /// Anything else
+    Other,
+}
+
+/// How a gate acts on its `i`-th qubit
+///
+/// Classically controlled gates are treated as [QubitAction::Other].
+pub fn qubit_action(g: &Gate, i: usize) -> QubitAction {
+    if g.condition.is_some() {
+        return QubitAction::Other;
+    }
+    match g.t {
+        Z | S | Sdg | T | Tdg | ZPhase | CZ | CCZ | ParityPhase => QubitAction::Z,
+        NOT | XPhase | XCX => QubitAction::X,
+        CNOT if i == 0 => QubitAction::Z,
+        TOFF if i < 2 => QubitAction::Z,
+        CNOT | TOFF => QubitAction::X,
+        HAD | SWAP | InitAncilla | PostSelect | Measure | Reset | Barrier | UnknownGate => {
+            QubitAction::Other
+        }
+    }
+}
+
+/// Whether two gates commute
+///
+/// This is the case when they act the same way, as Z or as X, on each qubit they
+/// share, and they don't use the same classical bits. This is sufficient but not
+/// necessary, e.g. it misses that HAD commutes with itself.
+pub fn commutes(g: &Gate, h: &Gate) -> bool {
+    let shares_bits = g
+        .classical_bits()
+        .any(|b| h.classical_bits().any(|b1| b == b1));
+    !shares_bits
+        && g.qs.iter().enumerate().all(|(i, q)| {
+            h.qs.iter().position(|q1| q1 == q).map_or(true, |j| {
+                let a = qubit_action(g, i);
+                a != QubitAction::Other && a == qubit_action(h, j)
+            })
+        })
+}
+
+/// The gates of a circuit, with edges to the gates which must come after them
+///
+/// See the [module docs](self).
+#[derive(Clone, Debug)]
+pub struct CircuitDag {
+    nqubits: usize,
+    gates: Vec<Gate>,
+    preds: Vec<Vec<usize>>,
+    succs: Vec<Vec<usize>>,
+}
+
+impl CircuitDag {
+    fn empty(c: &Circuit) -> Self {
+        CircuitDag {
+            nqubits: c.num_qubits(),
+            gates: c.gates.iter().cloned().collect(),
+            preds: vec![vec![]; c.num_gates()],
+            succs: vec![vec![]; c.num_gates()],
+        }
+    }
+
+    /// Orders the gates which use the same classical bit, e.g. a measurement and
+    /// the gates conditioned on its outcome
+    fn add_classical_edges(&mut self) {
+        let mut last: FxHashMap<usize, usize> = FxHashMap::default();
+        for v in 0..self.gates.len() {
+            let bits: Vec<usize> = self.gates[v].classical_bits().collect();
+            for b in bits {
+                if let Some(u) = last.insert(b, v) {
+                    if u != v {
+                        self.add_edge(u, v);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize) {
+        if !self.succs[from].contains(&to) {
+            self.succs[from].push(to);
+            self.preds[to].push(from);
+        }
+    }
+
+    /// The DAG where each gate comes after the previous gate on each of its qubits
+    pub fn new(c: &Circuit) -> Self {
+        let mut dag = CircuitDag::empty(c);
+        let mut last: Vec<Option<usize>> = vec![None; c.num_qubits()];
+        for (v, g) in c.gates.iter().enumerate() {
+            for &q in &g.qs {
+                if let Some(u) = last[q] {
+                    dag.add_edge(u, v);
+                }
+                last[q] = Some(v);
+            }
+        }
+        dag.add_classical_edges();
+        dag
+    }
+
+    /// The DAG where each gate only comes after the gates it doesn't commute with
+    pub fn with_commutation(c: &Circuit) -> Self {
+        let mut dag = CircuitDag::empty(c);
+        // on each qubit, the gates since the last change of action, the gates
+        // before those, and the action they share
+        let mut block: Vec<Vec<usize>> = vec![vec![]; c.num_qubits()];
+        let mut prev_block: Vec<Vec<usize>> = vec![vec![]; c.num_qubits()];
+        let mut action: Vec<QubitAction> = vec![QubitAction::Other; c.num_qubits()];
+        for (v, g) in c.gates.iter().enumerate() {
+            for (i, &q) in g.qs.iter().enumerate() {
+                let a = qubit_action(g, i);
+                if a != QubitAction::Other && a == action[q] {
+                    for &u in &prev_block[q] {
+                        dag.add_edge(u, v);
+                    }
+                    block[q].push(v);
+                } else {
+                    for &u in &block[q] {
+                        dag.add_edge(u, v);
+                    }
+                    prev_block[q] = std::mem::replace(&mut block[q], vec![v]);
+                    action[q] = a;
+                }
+            }
+        }
+        dag.add_classical_edges();
+        dag
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.nqubits
+    }
+
+    pub fn num_gates(&self) -> usize {
+        self.gates.len()
+    }
+
+    /// The `v`-th gate of the circuit
+    pub fn gate(&self, v: usize) -> &Gate {
+        &self.gates[v]
+    }
+
+    /// The gates which must come right before gate `v`
+    pub fn predecessors(&self, v: usize) -> &[usize] {
+        &self.preds[v]
+    }
+
+    /// The gates which must come right after gate `v`
+    pub fn successors(&self, v: usize) -> &[usize] {
+        &self.succs[v]
+    }
+
+    /// The gates which can come first
+    pub fn front(&self) -> Vec<usize> {
+        (0..self.num_gates())
+            .filter(|&v| self.preds[v].is_empty())
+            .collect()
+    }
+
+    /// The layer of each gate, as early as possible
+    pub fn layer_of(&self) -> Vec<usize> {
+        let mut layer = vec![0; self.num_gates()];
+        // edges always go forward in circuit order, so this is a topological order
+        for (v, preds) in self.preds.iter().enumerate() {
+            layer[v] = preds.iter().map(|&u| layer[u] + 1).max().unwrap_or(0);
+        }
+        layer
+    }
+
+    /// The gates in each layer, as early as possible
+    ///
+    /// For a DAG with commutation, a layer may contain several gates on the same
+    /// qubit, which commute with each other.
+    pub fn layers(&self) -> Vec<Vec<usize>> {
+        let mut layers: Vec<Vec<usize>> = vec![];
+        for (v, l) in self.layer_of().into_iter().enumerate() {
+            if l >= layers.len() {
+                layers.resize(l + 1, vec![]);
+            }
+            layers[l].push(v);
+        }
+        layers
+    }
+
+    /// The number of layers, i.e. the length of the longest path in the DAG
+    pub fn depth(&self) -> usize {
+        self.layer_of().into_iter().max().map_or(0, |l| l + 1)
+    }
+
+    /// The circuit with the gates in the order of [CircuitDag::layers]
+    pub fn to_circuit(&self) -> Circuit {
+        let mut c = Circuit::new(self.nqubits);
+        for layer in self.layers() {
+            for v in layer {
+                c.push(self.gates[v].clone());
+            }
+        }
+        c
+    }
+}
+
+impl Circuit {
+    /// The depth of the circuit, where each gate takes one time step
+    pub fn depth(&self) -> usize {
+        CircuitDag::new(self).depth()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+
+    #[test]
+    fn commuting_gates() {
+        let g = |t: GType, qs: &[usize]| Gate::new(t, qs.to_vec());
+        assert!(commutes(&g(CNOT, &[0, 1]), &g(CNOT, &[0, 2])));
+        assert!(commutes(&g(CNOT, &[0, 1]), &g(CNOT, &[2, 1])));
+        assert!(!commutes(&g(CNOT, &[0, 1]), &g(CNOT, &[1, 2])));
+        assert!(commutes(&g(T, &[0]), &g(CZ, &[1, 0])));
+        assert!(commutes(&g(NOT, &[2]), &g(TOFF, &[0, 1, 2])));
+        assert!(!commutes(&g(NOT, &[1]), &g(TOFF, &[0, 1, 2])));
+        assert!(!commutes(&g(HAD, &[0]), &g(HAD, &[0])));
+        assert!(commutes(&g(HAD, &[0]), &g(HAD, &[1])));
+    }
+
+    #[test]
+    fn dag_layers() {
+        let c = Circuit::from_qasm(
+            "qreg q[3];
+            h q[0];
+            t q[1];
+            cx q[0], q[1];
+            cx q[0], q[2];
+            t q[0];
+            h q[2];",
+        )
+        .unwrap();
+        let dag = CircuitDag::new(&c);
+        assert_eq!(dag.front(), vec![0, 1]);
+        assert_eq!(dag.successors(2), &[3]);
+        assert_eq!(dag.predecessors(2), &[0, 1]);
+        assert_eq!(dag.layers(), vec![vec![0, 1], vec![2], vec![3], vec![4, 5]]);
+        assert_eq!(c.depth(), 4);
+        assert_eq!(dag.to_circuit(), c);
+
+        let dag = CircuitDag::with_commutation(&c);
+        assert_eq!(dag.predecessors(3), &[0]);
+        assert_eq!(dag.predecessors(4), &[0]);
+        assert_eq!(dag.layers(), vec![vec![0, 1], vec![2, 3, 4], vec![5]]);
+        assert_eq!(dag.depth(), 3);
+    }
+
+    #[test]
+    fn commutation_reordering() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(40)
+                .clifford_t(0.3)
+                .build();
+            let dag = CircuitDag::with_commutation(&c);
+            assert!(dag.depth() <= c.depth());
+            let c1 = dag.to_circuit();
+            assert_eq!(c1.num_gates(), c.num_gates());
+            assert!(Tensor4::scalar_compare(&c, &c1));
+        }
+    }
+}
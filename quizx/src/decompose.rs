@@ -24,6 +24,7 @@ use std::collections::VecDeque;
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum SimpFunc {
     FullSimp,
+    ParFullSimp,
     CliffordSimp,
     NoSimp,
 }
@@ -122,6 +123,10 @@ impl<G: GraphLike> Decomposer<G> {
     pub fn with_full_simp(&mut self) -> &mut Self {
         self.with_simp(FullSimp)
     }
+    /// Simplify with [crate::simplify::par_full_simp], for decomposing large graphs
+    pub fn with_par_full_simp(&mut self) -> &mut Self {
+        self.with_simp(ParFullSimp)
+    }
     pub fn with_clifford_simp(&mut self) -> &mut Self {
         self.with_simp(CliffordSimp)
     }
@@ -332,12 +337,17 @@ impl<G: GraphLike> Decomposer<G> {
                 FullSimp => {
                     crate::simplify::full_simp(&mut g);
                 }
+                ParFullSimp => {
+                    crate::simplify::par_full_simp(&mut g);
+                }
                 CliffordSimp => {
                     crate::simplify::clifford_simp(&mut g);
                 }
                 _ => {}
             }
 
+            g.debug_check();
+
             // let comps = g.component_vertices();
             // if comps.len() > 1 {
             //     println!("GOT {} COMPONENTS ({})", comps.len(), comps.iter().map(|c| c.len()).format(","));
@@ -892,5 +902,10 @@ mod tests {
         let mut d = Decomposer::new(&g);
         d.with_full_simp().save(true).decomp_all();
         assert_eq!(d.done.len(), 7 * 2 * 2);
+
+        let mut d1 = Decomposer::new(&g);
+        d1.with_par_full_simp().save(true).decomp_all();
+        assert_eq!(d1.done.len(), 7 * 2 * 2);
+        assert_eq!(d.scalar, d1.scalar);
     }
 }
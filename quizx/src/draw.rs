@@ -0,0 +1,282 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Plain text drawings of circuits, for a quick look in the terminal
+//!
+//! [Circuit::to_text] draws each qubit as a wire running left to right. Gates are
+//! placed in columns as early as possible, without overlapping the vertical extent
+//! of other gates in the same column. Single-qubit gates are drawn as boxes, and
+//! multi-qubit gates as symbols joined by a vertical line: `@` for a Z-type control
+//! or CZ, `X` for the target of a CNOT or Toffoli, and `x` for the ends of a SWAP.
+//! Barriers are drawn as `#`, and a classical condition is written after the gate.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; t q[1];").unwrap();
+//! assert_eq!(
+//!     c.to_text(),
+//!     "     +---+
+//! q0: -| H |-@-------
+//!      +---+ |
+//!            | +---+
+//! q1: -------X-| T |-
+//!              +---+
+//! "
+//! );
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::qasm3::fmt_angle;
+use num::Zero;
+
+/// How a gate is drawn on one of its qubits
+enum Symbol {
+    /// A label on the wire
+    Wire(String),
+    /// A label in a box
+    Boxed(String),
+}
+
+/// The angle of a phase gate, including its parameters
+fn angle(g: &Gate) -> String {
+    if g.params.is_zero() {
+        fmt_angle(g.phase)
+    } else if g.phase.is_zero() {
+        g.params.to_string()
+    } else {
+        format!("{} + {}", fmt_angle(g.phase), g.params)
+    }
+}
+
+/// The symbol of a gate on each of its qubits, and the character joining them
+fn symbols(g: &Gate, nbits: usize) -> (Vec<(usize, Symbol)>, char) {
+    let wire = |s: &str| Symbol::Wire(s.to_string());
+    let qs = &g.qs;
+    let (mut syms, link) = match g.t {
+        CNOT | TOFF => {
+            let (&t, cs) = qs.split_last().unwrap();
+            let mut syms: Vec<_> = cs.iter().map(|&c| (c, wire("@"))).collect();
+            syms.push((t, wire("X")));
+            (syms, '|')
+        }
+        CZ | CCZ => (qs.iter().map(|&q| (q, wire("@"))).collect(), '|'),
+        SWAP => (qs.iter().map(|&q| (q, wire("x"))).collect(), '|'),
+        Barrier => (qs.iter().map(|&q| (q, wire("#"))).collect(), ':'),
+        _ => {
+            let label = match g.t {
+                NOT => "X".to_string(),
+                ZPhase => format!("Rz({})", angle(g)),
+                XPhase => format!("Rx({})", angle(g)),
+                Z | S | T => g.qasm_name().to_uppercase(),
+                Sdg => "S+".to_string(),
+                Tdg => "T+".to_string(),
+                HAD => "H".to_string(),
+                ParityPhase => format!("pp({})", angle(g)),
+                Measure => format!("M->c{}", g.bits[0]),
+                Reset | InitAncilla => "|0>".to_string(),
+                PostSelect => "<0|".to_string(),
+                _ => g.qasm_name().to_string(),
+            };
+            let syms = qs.iter().map(|&q| (q, Symbol::Boxed(label.clone())));
+            (syms.collect(), '|')
+        }
+    };
+
+    if let Some(cond) = &g.condition {
+        if let Some((_, Symbol::Wire(s) | Symbol::Boxed(s))) = syms.last_mut() {
+            *s += " ";
+            *s += &cond.to_qasm(nbits);
+        }
+    }
+    (syms, link)
+}
+
+/// The gates in each column, as early as possible
+fn columns(c: &Circuit) -> Vec<Vec<usize>> {
+    let mut cols: Vec<Vec<usize>> = vec![];
+    // the first column in which each qubit and classical bit is free
+    let mut free = vec![0; c.num_qubits()];
+    let mut free_bits = vec![0; c.num_bits()];
+    for (i, g) in c.gates.iter().enumerate() {
+        let (Some(&lo), Some(&hi)) = (g.qs.iter().min(), g.qs.iter().max()) else {
+            continue;
+        };
+        let col = free[lo..=hi]
+            .iter()
+            .copied()
+            .chain(g.classical_bits().map(|b| free_bits[b]))
+            .max()
+            .unwrap_or(0);
+        if col == cols.len() {
+            cols.push(vec![]);
+        }
+        cols[col].push(i);
+        free[lo..=hi].fill(col + 1);
+        for b in g.classical_bits() {
+            free_bits[b] = col + 1;
+        }
+    }
+    cols
+}
+
+/// Writes `s` into `line`, centred on position `mid`
+fn put(line: &mut [char], mid: usize, s: &str) {
+    let start = mid - s.chars().count() / 2;
+    for (j, ch) in s.chars().enumerate() {
+        line[start + j] = ch;
+    }
+}
+
+impl Circuit {
+    /// A drawing of the circuit as plain text, see the [module docs](crate::draw)
+    pub fn to_text(&self) -> String {
+        let n = self.num_qubits();
+        let nbits = self.num_bits();
+        let width = format!("q{}: ", n.saturating_sub(1)).len();
+        // for each qubit, the line above the wire, the wire, and the line below it
+        let mut lines: Vec<[String; 3]> = (0..n)
+            .map(|q| {
+                [
+                    " ".repeat(width),
+                    format!("{:<width$}", format!("q{}:", q)),
+                    " ".repeat(width),
+                ]
+            })
+            .collect();
+
+        for col in columns(self) {
+            let gates: Vec<(&Gate, _)> = col
+                .iter()
+                .map(|&i| (&self.gates[i], symbols(&self.gates[i], nbits)))
+                .collect();
+            let w = gates
+                .iter()
+                .flat_map(|(_, (syms, _))| syms)
+                .map(|(_, s)| match s {
+                    Symbol::Wire(s) => s.chars().count(),
+                    Symbol::Boxed(s) => s.chars().count() + 4,
+                })
+                .max()
+                .unwrap_or(1);
+            let mid = w / 2;
+            let mut cells: Vec<[Vec<char>; 3]> = (0..n)
+                .map(|_| [vec![' '; w], vec!['-'; w], vec![' '; w]])
+                .collect();
+
+            for (g, (syms, link)) in &gates {
+                let lo = *g.qs.iter().min().unwrap();
+                let hi = *g.qs.iter().max().unwrap();
+                for (q, cell) in cells.iter_mut().enumerate().take(hi + 1).skip(lo) {
+                    if q > lo {
+                        cell[0][mid] = *link;
+                    }
+                    if q < hi {
+                        cell[2][mid] = *link;
+                    }
+                    if !g.qs.contains(&q) {
+                        cell[1][mid] = if *link == '|' { '+' } else { *link };
+                    }
+                }
+                for (q, s) in syms {
+                    let cell = &mut cells[*q];
+                    match s {
+                        Symbol::Wire(s) => put(&mut cell[1], mid, s),
+                        Symbol::Boxed(s) => {
+                            cell[1] = vec![' '; w];
+                            cell[1][0] = '|';
+                            cell[1][w - 1] = '|';
+                            put(&mut cell[1], mid, s);
+                            // the borders of the box, keeping any lines to other qubits
+                            for k in [0, 2] {
+                                let joined = cell[k][mid] != ' ';
+                                cell[k] = vec!['-'; w];
+                                cell[k][0] = '+';
+                                cell[k][w - 1] = '+';
+                                if joined {
+                                    cell[k][mid] = '+';
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            for (line, cell) in lines.iter_mut().zip(cells) {
+                for (k, (l, c)) in line.iter_mut().zip(cell).enumerate() {
+                    l.push(if k == 1 { '-' } else { ' ' });
+                    l.extend(c);
+                }
+            }
+        }
+
+        let mut s = String::new();
+        for line in &mut lines {
+            line[1].push('-');
+            for l in line.iter() {
+                let l = l.trim_end();
+                if !l.is_empty() {
+                    s += l;
+                    s.push('\n');
+                }
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_multi_qubit_gates() {
+        let c = Circuit::from_qasm(
+            "qreg q[3];
+            ccx q[0], q[2], q[1];
+            cz q[0], q[2];
+            swap q[1], q[2];
+            barrier q;",
+        )
+        .unwrap();
+        let expected = "\
+q0: -@-@---#-
+     | |   :
+     | |   :
+q1: -X-+-x-#-
+     | | | :
+     | | | :
+q2: -@-@-x-#-
+";
+        assert_eq!(c.to_text(), expected);
+    }
+
+    #[test]
+    fn draw_boxes() {
+        let mut c = Circuit::new(2);
+        c.push(Gate::new(HAD, vec![0]));
+        c.push(Gate::new_measure(0, 0));
+        c.push(Gate::new(NOT, vec![1]).with_condition(vec![0], 1));
+        let text = c.to_text();
+        assert!(text.contains("| M->c0 |"));
+        assert!(text.contains("| X if(c==1) |"));
+        assert_eq!(text.lines().count(), 6);
+
+        // the controlled gate comes after the measurement
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].find("M->c0") < lines[4].find("X if"));
+    }
+}
@@ -14,21 +14,102 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::architecture::Architecture;
 use crate::circuit::*;
 use crate::gate::*;
 use crate::graph::*;
 // use crate::tensor::*;
-use crate::basic_rules::{boundary_pivot, remove_id};
+use crate::basic_rules::{boundary_pivot, local_comp, remove_id};
+use crate::flow::Flow;
 use crate::linalg::*;
 use crate::phase::Phase;
+use crate::phase_poly::PhasePoly;
 use num::{Rational64, Zero};
-use rustc_hash::FxHashSet;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::fmt;
 
-/// Extraction couldn't finish. Returns a message, a
+/// Why extraction couldn't finish
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractErrorKind {
+    /// A spider is connected to two outputs
+    TwoOutputs(V),
+    /// A frontier spider has a neighbour which is not a Z spider
+    BadNeighbour(V),
+    /// An output is not connected to anything
+    BadOutput(V),
+    /// A phase gadget next to a frontier spider couldn't be removed by pivoting
+    Gadget { frontier: V, root: V },
+    /// None of the frontier spiders could be extracted, even after row operations,
+    /// so the rest of the diagram has no gflow with these spiders as outputs
+    Stuck { frontier: Vec<V> },
+}
+
+impl fmt::Display for ExtractErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractErrorKind::TwoOutputs(v) => {
+                write!(f, "Two outputs connected to a single vertex {}.", v)
+            }
+            ExtractErrorKind::BadNeighbour(v) => write!(f, "Bad neighbour: {}", v),
+            ExtractErrorKind::BadOutput(v) => write!(f, "Bad output vertex {}", v),
+            ExtractErrorKind::Gadget { frontier, root } => write!(
+                f,
+                "Could not remove gadget by pivoting: ({}, {})",
+                frontier, root
+            ),
+            ExtractErrorKind::Stuck { frontier } => {
+                write!(f, "No extractible vertex found. Frontier: {:?}", frontier)
+            }
+        }
+    }
+}
+
+/// Extraction couldn't finish. Returns what went wrong, a
 /// partially-extracted circuit, and the remainder of
 /// the graph.
-pub struct ExtractError<G: GraphLike>(pub String, pub Circuit, pub G);
+pub struct ExtractError<G: GraphLike>(pub ExtractErrorKind, pub Circuit, pub G);
+
+impl<G: GraphLike> ExtractError<G> {
+    /// The vertices of the remaining graph where extraction went wrong
+    pub fn vertices(&self) -> Vec<V> {
+        match &self.0 {
+            ExtractErrorKind::TwoOutputs(v)
+            | ExtractErrorKind::BadNeighbour(v)
+            | ExtractErrorKind::BadOutput(v) => vec![*v],
+            ExtractErrorKind::Gadget { frontier, root } => vec![*frontier, *root],
+            ExtractErrorKind::Stuck { frontier } => frontier.clone(),
+        }
+    }
+
+    /// The part of the remaining graph around [ExtractError::vertices]
+    ///
+    /// This is the subgraph induced by these vertices and their neighbours, see
+    /// [GraphLike::induced_subgraph].
+    pub fn subdiagram(&self) -> G {
+        let mut vs = self.vertices();
+        for v in self.vertices() {
+            for w in self.2.neighbors(v) {
+                if !vs.contains(&w) {
+                    vs.push(w);
+                }
+            }
+        }
+        self.2.induced_subgraph(&vs)
+    }
+
+    /// The remaining graph in graphviz format, with [ExtractError::vertices]
+    /// highlighted
+    pub fn to_dot(&self) -> String {
+        let options = DotOptions {
+            highlight: self.vertices().into_iter().collect(),
+            ..Default::default()
+        };
+        self.2.to_dot_with_options(&options)
+    }
+}
 
 impl<G: GraphLike> fmt::Display for ExtractError<G> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -44,6 +125,18 @@ impl<G: GraphLike> fmt::Debug for ExtractError<G> {
 
 impl<G: GraphLike> std::error::Error for ExtractError<G> {}
 
+/// How the extractor chooses between spiders, see [Extractor::with_tie_break]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// Prefer the spiders with the lowest index
+    #[default]
+    Index,
+    /// Prefer the spiders with the fewest neighbours, then the lowest index
+    Degree,
+    /// Put the spiders in a random order, from a generator with the given seed
+    Random(u64),
+}
+
 pub trait ToCircuit: GraphLike {
     fn to_circuit_mut(&mut self) -> Result<Circuit, ExtractError<Self>>;
     fn to_circuit(&self) -> Result<Circuit, ExtractError<Self>> {
@@ -60,6 +153,14 @@ pub struct Extractor<'a, G: GraphLike> {
     frontier: Vec<(usize, V)>,
     up_to_perm: bool,
     gaussf: fn(&mut Extractor<'a, G>, &mut Circuit),
+    depth: Option<FxHashMap<V, usize>>,
+    arch: Option<Architecture>,
+    graysynth: bool,
+    optimize: bool,
+    max_ancillas: usize,
+    ancillas: Vec<usize>,
+    tie_break: TieBreak,
+    rng: StdRng,
 }
 
 impl<'a, G: GraphLike> Extractor<'a, G> {
@@ -69,6 +170,14 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
             frontier: Vec::new(),
             up_to_perm: false,
             gaussf: Extractor::single_sln_set,
+            depth: None,
+            arch: None,
+            graysynth: false,
+            optimize: false,
+            max_ancillas: 0,
+            ancillas: Vec::new(),
+            tie_break: TieBreak::default(),
+            rng: StdRng::seed_from_u64(0),
         }
     }
 
@@ -82,6 +191,45 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         self
     }
 
+    /// Extract the phase gadgets on the frontier together, with GraySynth
+    ///
+    /// The phase gadgets which only act on frontier spiders make up a phase
+    /// polynomial on the frontier qubits. These are removed together and synthesised
+    /// with [PhasePoly::to_circuit], rather than pivoted into the graph one at a
+    /// time. This saves a lot of CNOTs for diagrams with many overlapping gadgets,
+    /// e.g. simplified CNOT+T circuits.
+    pub fn graysynth(&mut self) -> &mut Self {
+        self.graysynth = true;
+        self
+    }
+
+    /// Try to extract a circuit with fewer Hadamard gates
+    ///
+    /// Gaussian elimination is done with [Extractor::sparse_gauss], and the result
+    /// is simplified with [Circuit::optimize], which cancels pairs of Hadamards and
+    /// turns CZs between Hadamards into CNOTs.
+    pub fn min_hadamards(&mut self) -> &mut Self {
+        self.optimize = true;
+        self.with_gaussf(Extractor::sparse_gauss)
+    }
+
+    /// Allow up to `max_ancillas` ancilla qubits, to extract diagrams which would
+    /// otherwise get stuck
+    ///
+    /// When there is no other way to make progress, a spider next to the frontier
+    /// gets a new input and output of its own, which are plugged with `|+>` and
+    /// `<+|`. This leaves the diagram unchanged up to a scalar, and puts the spider
+    /// straight on to the frontier. The extra qubits come after the qubits of the
+    /// diagram. The circuit starts each of them with
+    /// [InitAncilla](GType::InitAncilla) and a Hadamard, and ends it with a Hadamard
+    /// and [PostSelect](GType::PostSelect). Since the ancillas have to end up on
+    /// their own qubits, the final permutation is always extracted, even with
+    /// [Extractor::up_to_perm].
+    pub fn with_ancillas(&mut self, max_ancillas: usize) -> &mut Self {
+        self.max_ancillas = max_ancillas;
+        self
+    }
+
     pub fn flow(&mut self) -> &mut Self {
         self.with_gaussf(Extractor::no_gauss)
     }
@@ -94,11 +242,81 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         self.with_gaussf(Extractor::simple_gauss)
     }
 
+    /// Extract vertices in the order of the layers of a flow
+    ///
+    /// When several vertices can be extracted next, one in the lowest layer of the
+    /// flow is taken first, see [Flow::layers]. The flow should be computed on the
+    /// graph being extracted, e.g. with [crate::flow::gflow].
+    pub fn with_flow(&mut self, flow: &Flow) -> &mut Self {
+        self.depth = Some(flow.depth.clone());
+        self
+    }
+
+    /// Only use two-qubit gates between qubits connected in the architecture
+    ///
+    /// Gaussian elimination on the frontier is done with [Extractor::steiner_gauss],
+    /// and any CNOT, CZ or SWAP between qubits which are not connected is routed
+    /// with [Architecture::push_routed]. Qubit `q` of the circuit is qubit `q` of
+    /// the architecture.
+    pub fn with_architecture(&mut self, arch: &Architecture) -> &mut Self {
+        assert!(
+            arch.num_qubits() >= self.g.outputs().len(),
+            "Architecture has too few qubits"
+        );
+        self.arch = Some(arch.clone());
+        self.with_gaussf(Extractor::steiner_gauss)
+    }
+
+    /// Choose how to break ties between spiders
+    ///
+    /// This sets the order of the neighbours of the frontier, which decides the
+    /// pivots of the row operations, and which gadget or Pauli spider is removed
+    /// first. Each choice is deterministic, so extraction is reproducible, and
+    /// the circuits from different choices can be compared. The default is
+    /// [TieBreak::Index].
+    pub fn with_tie_break(&mut self, tie_break: TieBreak) -> &mut Self {
+        self.tie_break = tie_break;
+        if let TieBreak::Random(seed) = tie_break {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        self
+    }
+
+    /// Put spiders in the order given by the tie-break
+    fn order(&mut self, vs: &mut [V]) {
+        vs.sort();
+        match self.tie_break {
+            TieBreak::Index => {}
+            TieBreak::Degree => vs.sort_by_key(|&v| self.g.degree(v)),
+            TieBreak::Random(_) => vs.shuffle(&mut self.rng),
+        }
+    }
+
+    /// The neighbours of a spider, in the order given by the tie-break
+    fn ordered_neighbors(&mut self, v: V) -> Vec<V> {
+        let mut ns = self.g.neighbor_vec(v);
+        self.order(&mut ns);
+        ns
+    }
+
+    /// Push a gate on to the front of `c`, routing it if there is an architecture
+    fn push_front(&self, c: &mut Circuit, g: Gate) {
+        if let Some(arch) = &self.arch {
+            let mut c1 = Circuit::new(c.num_qubits());
+            arch.push_routed(&mut c1, g);
+            for g in c1.gates.into_iter().rev() {
+                c.push_front(g);
+            }
+        } else {
+            c.push_front(g);
+        }
+    }
+
     /// Build a biadjacency matrix of frontier with its neighbors
     ///
     /// Frontier elements are rows and neighbors are columns. The computed
     /// vec of neighbors and the matrix are returned.
-    fn frontier_biadj(&self) -> (Vec<V>, Mat2) {
+    fn frontier_biadj(&mut self) -> (Vec<V>, Mat2) {
         let mut neighbor_set = FxHashSet::default();
         for &(_, v) in &self.frontier {
             for n in self.g.neighbors(v) {
@@ -108,7 +326,8 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
             }
         }
 
-        let neighbors: Vec<_> = neighbor_set.iter().copied().collect();
+        let mut neighbors: Vec<_> = neighbor_set.iter().copied().collect();
+        self.order(&mut neighbors);
 
         // Build an adjacency matrix between the frontier and its neighbors
         let m = Mat2::build(self.frontier.len(), neighbors.len(), |i, j| {
@@ -144,7 +363,7 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
             let mut gate = gate.clone();
             gate.qs[0] = self.frontier[gate.qs[0]].0;
             gate.qs[1] = self.frontier[gate.qs[1]].0;
-            c.push_front(gate);
+            self.push_front(c, gate);
         }
     }
 
@@ -165,10 +384,74 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         // elimination, but they are pushed on to the front of the circuit, so they
         // should end up in reverse order.
         let mut c1 = Circuit::new(c.num_qubits());
-        m.gauss_x(true, 3, &mut c1);
+        let blocksize = m.pmh_blocksize(true);
+        m.gauss_x(true, blocksize, &mut c1);
+
+        e.update_frontier_circuit(&c1, c);
+        e.update_frontier_biadj(&neighbors, m);
+    }
+
+    /// Perform gaussian elimination on the frontier along Steiner trees
+    ///
+    /// This uses [Architecture::gauss] with the architecture given to
+    /// [Extractor::with_architecture], so that the CNOTs are mostly between
+    /// connected qubits. Without an architecture, this is [Extractor::simple_gauss].
+    pub fn steiner_gauss(e: &mut Extractor<G>, c: &mut Circuit) {
+        if e.arch.is_none() {
+            Extractor::simple_gauss(e, c);
+            return;
+        }
+        let (neighbors, mut m) = e.frontier_biadj();
+        let qubits: Vec<usize> = e.frontier.iter().map(|&(q, _)| q).collect();
+        let mut c1 = Circuit::new(c.num_qubits());
+        if let Some(arch) = &e.arch {
+            arch.gauss(&mut m, &qubits, &mut c1);
+        }
+
+        e.update_frontier_circuit(&c1, c);
+        e.update_frontier_biadj(&neighbors, m);
+    }
+
+    /// Perform row operations which make the frontier biadjacency matrix sparser
+    ///
+    /// Each step adds the row to another which removes the most edges between the
+    /// frontier and its neighbours, until some frontier vertex has a single
+    /// neighbour. If no row operation removes edges before that, this finishes with
+    /// [Extractor::single_sln_set]. Every edge to the frontier is a Hadamard edge
+    /// which has to be extracted at some point, so fewer of these tend to give fewer
+    /// Hadamards and CZs in the circuit.
+    pub fn sparse_gauss(e: &mut Extractor<G>, c: &mut Circuit) {
+        let (neighbors, mut m) = e.frontier_biadj();
+        let mut c1 = Circuit::new(c.num_qubits());
+        let weight = |m: &Mat2, r: usize| (0..m.num_cols()).filter(|&j| m[(r, j)] == 1).count();
+        while m.unit_rows().is_empty() {
+            let mut best = None;
+            let mut best_gain = 0;
+            for r1 in 0..m.num_rows() {
+                let w1 = weight(&m, r1);
+                for r0 in (0..m.num_rows()).filter(|&r0| r0 != r1) {
+                    let w = (0..m.num_cols())
+                        .filter(|&j| m[(r0, j)] != m[(r1, j)])
+                        .count();
+                    if w1 > w + best_gain {
+                        best_gain = w1 - w;
+                        best = Some((r0, r1));
+                    }
+                }
+            }
+            let Some((r0, r1)) = best else {
+                break;
+            };
+            m.row_add(r0, r1);
+            c1.row_add(r0, r1);
+        }
 
+        let stuck = m.unit_rows().is_empty();
         e.update_frontier_circuit(&c1, c);
         e.update_frontier_biadj(&neighbors, m);
+        if stuck {
+            Extractor::single_sln_set(e, c);
+        }
     }
 
     /// Perform row operations to free a single vertex with the smallest solution set
@@ -177,15 +460,24 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         let mut row_ops = Mat2::id(m.num_rows());
         let mut m1 = m.clone();
         m1.gauss_x(true, 1, &mut row_ops);
-        let mut min_weight = row_ops.num_cols() as u8;
+        let mut min_weight = (usize::MAX, row_ops.num_cols() as u8);
         let mut extr_rows = Vec::new();
         let mut min_weight_row = 0;
 
-        // find the vertex with the smallest solution set
+        // find the vertex with the smallest solution set, in the lowest layer of the
+        // flow if there is one
         for i in 0..m1.num_rows() {
             if m1.row_weight(i) == 1 {
                 extr_rows.push(i);
-                let weight = row_ops.row_weight(i);
+                let layer = e
+                    .depth
+                    .as_ref()
+                    .and_then(|d| {
+                        let j = (0..m1.num_cols()).find(|&j| m1[(i, j)] == 1)?;
+                        d.get(&neighbors[j]).copied()
+                    })
+                    .unwrap_or(0);
+                let weight = (layer, row_ops.row_weight(i));
                 if weight <= min_weight {
                     min_weight_row = i;
                     min_weight = weight;
@@ -250,16 +542,17 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
     ///
     /// A permutation graph contains only inputs, outputs, and normal edges
     /// connecting inputs to outputs.
-    fn perm_to_cnots(&mut self, c: &mut Circuit, blocksize: usize) {
+    fn perm_to_cnots(&mut self, c: &mut Circuit) {
         let mut m = Mat2::build(self.g.outputs().len(), self.g.inputs().len(), |i, j| {
             self.g.connected(self.g.outputs()[i], self.g.inputs()[j])
         });
 
         // Extract CNOTs until adj. matrix is in reduced echelon form
         let mut c1 = Circuit::new(c.num_qubits());
+        let blocksize = m.pmh_blocksize(true);
         m.gauss_x(true, blocksize, &mut c1);
         for g in c1.gates {
-            c.push_front(g);
+            self.push_front(c, g);
         }
     }
 
@@ -305,7 +598,7 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
                         // for unitary circuits, an additional boundary must be an input
                         if !self.g.inputs().contains(&n) {
                             return Err(ExtractError(
-                                format!("Two outputs connected to a single vertex {}.", v),
+                                ExtractErrorKind::TwoOutputs(v),
                                 c.clone(),
                                 self.g.clone(),
                             ));
@@ -332,12 +625,12 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
                     } else if let Some(&(r, _)) = self.frontier.iter().find(|&&(_, n1)| n == n1) {
                         // TODO: CZ optimisation (maybe)
                         self.g.remove_edge(v, n);
-                        c.push_front(Gate::new(CZ, vec![q, r]));
+                        self.push_front(c, Gate::new(CZ, vec![q, r]));
 
                         // we should not encounter any non-Z vertices at this point
                     } else if self.g.vertex_type(n) != VType::Z {
                         return Err(ExtractError(
-                            format!("Bad neighbour: {}", n),
+                            ExtractErrorKind::BadNeighbour(n),
                             c.clone(),
                             self.g.clone(),
                         ));
@@ -347,7 +640,7 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
                 // this will happen if there is an output vertex not connected to anything, which
                 // is a mal-formed graph
                 return Err(ExtractError(
-                    format!("Bad output vertex {}", o),
+                    ExtractErrorKind::BadOutput(o),
                     c.clone(),
                     self.g.clone(),
                 ));
@@ -357,14 +650,81 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         Ok(())
     }
 
+    /// Extract the phase gadgets which only act on frontier spiders
+    ///
+    /// The root of such a gadget is a Z spider with phase 0 or π, connected by
+    /// Hadamard edges to some frontier spiders and to an arity-1 Z spider, the leaf.
+    /// Returns true if there were any, see [Extractor::graysynth].
+    fn extract_gadgets(&mut self, c: &mut Circuit, gadgets: &mut FxHashSet<V>) -> bool {
+        let mut p = PhasePoly::new(self.frontier.len());
+        let mut removed = vec![];
+        for &root in gadgets.iter() {
+            if !self.g.contains_vertex(root)
+                || self.g.vertex_type(root) != VType::Z
+                || !self.g.phase(root).is_pauli()
+            {
+                continue;
+            }
+            // the leaf of the gadget, and the frontier qubits the root is connected to
+            let mut leaf = None;
+            let mut qs = vec![];
+            let mut on_frontier = true;
+            for (n, et) in self.g.incident_edges(root) {
+                let i = self.frontier.iter().position(|&(_, v)| v == n);
+                if let (EType::H, Some(i)) = (et, i) {
+                    qs.push(i);
+                } else if leaf.is_none() && et == EType::H && self.g.degree(n) == 1 {
+                    leaf = Some(n);
+                } else {
+                    on_frontier = false;
+                }
+            }
+            let Some(leaf) = leaf else {
+                continue;
+            };
+            if !on_frontier
+                || self.g.vertex_type(leaf) != VType::Z
+                || self.g.has_symbolic_phase(root)
+                || self.g.has_symbolic_phase(leaf)
+            {
+                continue;
+            }
+
+            // a phase of pi on the root negates the phase of the gadget
+            let phase = if self.g.phase(root).is_zero() {
+                self.g.phase(leaf)
+            } else {
+                -self.g.phase(leaf)
+            };
+            p.add_term(&qs, phase);
+            removed.push((root, leaf));
+        }
+
+        for &(root, leaf) in &removed {
+            self.g.remove_vertex(leaf);
+            self.g.remove_vertex(root);
+            gadgets.remove(&root);
+        }
+        // the linear part is the identity, so the circuit is diagonal and commutes
+        // with the frontier spiders
+        for mut g in p.to_circuit().gates.into_iter().rev() {
+            for q in &mut g.qs {
+                *q = self.frontier[*q].0;
+            }
+            self.push_front(c, g);
+        }
+        !removed.is_empty()
+    }
+
     /// Pivot to remove gadgets adjacent to the frontier
     fn fix_gadgets(
         &mut self,
         c: &Circuit,
         gadgets: &mut FxHashSet<V>,
     ) -> Result<bool, ExtractError<G>> {
-        for &(_, v) in &self.frontier {
-            for n in self.g.neighbor_vec(v) {
+        for i in 0..self.frontier.len() {
+            let v = self.frontier[i].1;
+            for n in self.ordered_neighbors(v) {
                 if gadgets.contains(&n) {
                     // TODO: this can be probably be done with
                     // gen_pivot_unsafe
@@ -377,7 +737,10 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
                         return Ok(true);
                     } else {
                         return Err(ExtractError(
-                            format!("Could not remove gadget by pivoting: ({}, {})", v, n),
+                            ExtractErrorKind::Gadget {
+                                frontier: v,
+                                root: n,
+                            },
                             c.clone(),
                             self.g.clone(),
                         ));
@@ -388,6 +751,94 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         Ok(false)
     }
 
+    /// Remove a Pauli spider adjacent to the frontier
+    ///
+    /// Diagrams with a Pauli flow but no gflow can get stuck with a spider
+    /// next to the frontier that can't be extracted by row operations. If it is an
+    /// interior spider with phase 0 or π, we pivot it with a frontier spider it is
+    /// connected to. If its phase is ±π/2, we remove it by local complementation.
+    /// Either way, the graph loses a spider and the frontier has to be prepared
+    /// again. Returns false if there is no such spider.
+    ///
+    /// This is only tried once gflow extraction is stuck, so it doesn't change
+    /// the circuits extracted from diagrams with a gflow.
+    fn fix_pauli(&mut self) -> bool {
+        for i in 0..self.frontier.len() {
+            let v = self.frontier[i].1;
+            for n in self.ordered_neighbors(v) {
+                // skip spiders next to boundaries and the roots of phase gadgets
+                let interior = self.g.degree(n) > 1
+                    && self
+                        .g
+                        .neighbors(n)
+                        .all(|w| self.g.vertex_type(w) == VType::Z && self.g.degree(w) > 1);
+                if !interior || self.g.has_symbolic_phase(n) {
+                    continue;
+                }
+                if self.g.phase(n).is_pauli() && boundary_pivot(self.g, v, n) {
+                    return true;
+                }
+                if local_comp(self.g, n) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Give the spider next to the frontier with the most frontier neighbours its
+    /// own ancilla qubit, see [Extractor::with_ancillas]
+    ///
+    /// Returns false if there is no such spider.
+    fn add_ancilla(&mut self, c: &mut Circuit) -> bool {
+        let mut candidates = vec![];
+        for &(_, v) in &self.frontier {
+            for n in self.g.neighbors(v) {
+                let interior = self.g.degree(n) > 1
+                    && self
+                        .g
+                        .neighbors(n)
+                        .all(|w| self.g.vertex_type(w) != VType::B);
+                if interior && !candidates.contains(&n) {
+                    candidates.push(n);
+                }
+            }
+        }
+        // max_by_key gives the last of the best, so reverse to get the first one in
+        // tie-break order
+        self.order(&mut candidates);
+        let Some(w) = candidates.into_iter().rev().max_by_key(|&n| {
+            self.frontier
+                .iter()
+                .filter(|&&(_, v)| self.g.connected(v, n))
+                .count()
+        }) else {
+            return false;
+        };
+
+        let q = self.g.outputs().len();
+        let i = self.g.add_vertex(VType::B);
+        let o = self.g.add_vertex(VType::B);
+        self.g.set_qubit(i, q as i32);
+        self.g.set_qubit(o, q as i32);
+        self.g.add_edge(i, w);
+        self.g.add_edge(w, o);
+        self.g.inputs_mut().push(i);
+        self.g.outputs_mut().push(o);
+        self.g.set_input_qubit(i, q);
+        self.g.set_output_qubit(o, q);
+
+        // grow the circuit, and plug the new output with <0|H = <+|. Nothing
+        // extracted so far acts on the ancilla, so this can go at the end.
+        let mut c1 = Circuit::new(q + 1);
+        c1.gates = std::mem::take(&mut c.gates);
+        *c = c1;
+        c.push_back(Gate::new(HAD, vec![q]));
+        c.push_back(Gate::new(PostSelect, vec![q]));
+        self.ancillas.push(q);
+        true
+    }
+
     /// Extract vertices from the frontier
     ///
     /// Look for frontier elements that are phase-free and degree 2, and replace them
@@ -404,8 +855,45 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
     }
 
     pub fn extract(&mut self) -> Result<Circuit, ExtractError<G>> {
+        let mut c = self.extract_with(None)?;
+        if self.optimize {
+            c.optimize();
+        }
+        Ok(c)
+    }
+
+    /// Extract the circuit a step at a time, passing its gates to `emit`
+    ///
+    /// Extraction goes from the outputs to the inputs, so `emit` gets the gates in
+    /// reverse order, as soon as each step of extraction has produced them. Only
+    /// the gates of the current step are kept, so large diagrams can be extracted
+    /// without building the whole circuit. The final simplification of
+    /// [Extractor::min_hadamards] needs the whole circuit, so it is skipped.
+    ///
+    /// Returns the number of qubits of the circuit. If extraction fails, the
+    /// circuit in the error only has the gates which haven't been emitted yet.
+    pub fn extract_streaming(
+        &mut self,
+        mut emit: impl FnMut(Gate),
+    ) -> Result<usize, ExtractError<G>> {
+        Ok(self.extract_with(Some(&mut emit))?.num_qubits())
+    }
+
+    /// The main loop of extraction. With `emit`, the gates are drained from the
+    /// circuit after each step, see [Extractor::extract_streaming].
+    fn extract_with(
+        &mut self,
+        mut emit: Option<&mut dyn FnMut(Gate)>,
+    ) -> Result<Circuit, ExtractError<G>> {
         // let t = self.to_tensor4(); // DEBUG
         let mut c = Circuit::new(self.g.outputs().len());
+        let mut flush = |c: &mut Circuit| {
+            if let Some(emit) = emit.as_mut() {
+                for g in c.gates.drain(..).rev() {
+                    emit(g);
+                }
+            }
+        };
 
         // Pre-generate a set of all the phase gadgets. The extraction should
         // only ever eliminate phase gadgets, never create new ones.
@@ -421,6 +909,8 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         // println!("gadgets: {:?}", gadgets);
 
         loop {
+            flush(&mut c);
+
             // PREPROCESSING PHASE
             //
             // Remove any phases, Hadamards, or CZs from the output and generate
@@ -437,6 +927,14 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
             // assert!(Tensor4::scalar_eq(&t, &t1));
             // }}}
 
+            // PHASE POLYNOMIAL PHASE
+            //
+            // If we are using GraySynth, extract the gadgets which only act on the
+            // frontier all at once. Then the frontier needs to be re-generated.
+            if self.graysynth && self.extract_gadgets(&mut c, &mut gadgets) {
+                continue;
+            }
+
             // GADGET PHASE
             //
             // If any gadgets are adjacent to the frontier, do a generalised pivot to remove
@@ -460,10 +958,28 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
                 continue;
             }
 
+            // PAULI PHASE
+            //
+            // If the diagram only has a Pauli flow, a Pauli spider next to the frontier
+            // might be in the way. Remove it and start again with the new frontier.
+            if self.fix_pauli() {
+                continue;
+            }
+
+            // ANCILLA PHASE
+            //
+            // As a last resort, give a spider next to the frontier a qubit of its own,
+            // if we are allowed another ancilla.
+            if self.ancillas.len() < self.max_ancillas && self.add_ancilla(&mut c) {
+                continue;
+            }
+
             // If we didn't make progress, terminate with an error. This prevents infinite loops
             // in the case where a graph is not extractible.
             return Err(ExtractError(
-                "No extractible vertex found.".into(),
+                ExtractErrorKind::Stuck {
+                    frontier: self.frontier.iter().map(|&(_, v)| v).collect(),
+                },
                 c,
                 self.g.clone(),
             ));
@@ -472,10 +988,17 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         // FINAL PERMUTATION PHASE
         //
         // Generate CNOTs to turn the final permutation into the identity
-        if !self.up_to_perm {
-            self.perm_to_cnots(&mut c, 3);
+        if !self.up_to_perm || !self.ancillas.is_empty() {
+            self.perm_to_cnots(&mut c);
+        }
+
+        // plug the inputs of the ancillas with H|0> = |+>
+        for &q in &self.ancillas {
+            c.push_front(Gate::new(HAD, vec![q]));
+            c.push_front(Gate::new(InitAncilla, vec![q]));
         }
 
+        flush(&mut c);
         Ok(c)
     }
 }
@@ -509,7 +1032,7 @@ mod tests {
         let mut c = Circuit::new(4);
         let mut g1 = g.clone();
         let mut e = Extractor::new(&mut g1);
-        e.perm_to_cnots(&mut c, 3);
+        e.perm_to_cnots(&mut c);
         // c.adjoint();
         println!("{}", c);
         // panic!("foo");
@@ -533,7 +1056,7 @@ mod tests {
         let mut c = Circuit::new(4);
         let mut g1 = g.clone();
         let mut e = Extractor::new(&mut g1);
-        e.perm_to_cnots(&mut c, 3);
+        e.perm_to_cnots(&mut c);
         // c.adjoint();
         println!("{}", c);
         // panic!("foo");
@@ -661,6 +1184,193 @@ mod tests {
         assert!(Tensor4::scalar_compare(&c, &c1));
     }
 
+    #[test]
+    fn flow_layer_extract() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(40)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+
+        let mut g: Graph = c.to_graph();
+        full_simp_preserving_gflow(&mut g);
+        let f = crate::flow::focused_gflow(&g).unwrap();
+        let c1 = g
+            .extractor()
+            .with_flow(&f)
+            .extract()
+            .expect("Circuit should extract.");
+        assert!(Tensor4::scalar_compare(&c, &c1));
+    }
+
+    #[test]
+    fn pauli_flow_extract() {
+        use crate::flow::{gflow, pauli_flow};
+        // a triangle between the input spider, the output spider and a spider with
+        // phase 0, which only has a Pauli flow
+        let mut g = Graph::new();
+        let i = g.add_vertex(VType::B);
+        let o = g.add_vertex(VType::B);
+        let a = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        let b = g.add_vertex(VType::Z);
+        let c = g.add_vertex(VType::Z);
+        g.add_edge(i, a);
+        g.add_edge(c, o);
+        g.add_edge_with_type(a, b, EType::H);
+        g.add_edge_with_type(b, c, EType::H);
+        g.add_edge_with_type(a, c, EType::H);
+        g.set_inputs(vec![i]);
+        g.set_outputs(vec![o]);
+
+        assert!(gflow(&g).is_none());
+        assert!(pauli_flow(&g).is_some());
+        let c1 = g.to_circuit().expect("Circuit should extract.");
+        assert!(Tensor4::scalar_compare(&g, &c1));
+    }
+
+    #[test]
+    fn architecture_extract() {
+        use crate::architecture::Architecture;
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(6)
+            .depth(40)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        clifford_simp(&mut g);
+
+        for arch in [Architecture::line(6), Architecture::grid(2, 3)] {
+            let mut h = g.clone();
+            let c1 = h
+                .extractor()
+                .with_architecture(&arch)
+                .extract()
+                .expect("Circuit should extract.");
+            assert!(arch.supports(&c1));
+            assert!(Tensor4::scalar_compare(&c, &c1));
+        }
+    }
+
+    #[test]
+    fn graysynth_extract() {
+        // three wires with phases, and overlapping gadgets on the frontier
+        let mut g = Graph::new();
+        let (mut is, mut os, mut vs) = (vec![], vec![], vec![]);
+        for _ in 0..3 {
+            let i = g.add_vertex(VType::B);
+            let v = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+            let o = g.add_vertex(VType::B);
+            g.add_edge(i, v);
+            g.add_edge(v, o);
+            is.push(i);
+            vs.push(v);
+            os.push(o);
+        }
+        for (qs, root_phase, phase) in [
+            (vec![0, 1], 0, Rational64::new(1, 4)),
+            (vec![0, 1, 2], 1, Rational64::new(-1, 4)),
+            (vec![1, 2], 0, Rational64::new(1, 2)),
+        ] {
+            let root = g.add_vertex_with_phase(VType::Z, Rational64::from(root_phase));
+            let leaf = g.add_vertex_with_phase(VType::Z, phase);
+            g.add_edge_with_type(root, leaf, EType::H);
+            for q in qs {
+                g.add_edge_with_type(root, vs[q], EType::H);
+            }
+        }
+        g.set_inputs(is);
+        g.set_outputs(os);
+
+        let c1 = g
+            .clone()
+            .extractor()
+            .graysynth()
+            .extract()
+            .expect("Circuit should extract.");
+        assert!(Tensor4::scalar_compare(&g, &c1));
+
+        // a CNOT+T circuit, where only some of the gadgets are on the frontier
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(60)
+            .p_cnot(0.6)
+            .p_t(0.4)
+            .build();
+        let mut g: Graph = c.to_graph();
+        full_simp(&mut g);
+        let c1 = g
+            .extractor()
+            .graysynth()
+            .extract()
+            .expect("Circuit should extract.");
+        assert!(Tensor4::scalar_compare(&c, &c1));
+    }
+
+    #[test]
+    fn min_hadamards_extract() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(6)
+            .depth(60)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        clifford_simp(&mut g);
+
+        let c1 = g
+            .extractor()
+            .min_hadamards()
+            .extract()
+            .expect("Circuit should extract.");
+        assert!(Tensor4::scalar_compare(&c, &c1));
+        // no Hadamards are left to cancel
+        assert_eq!(c1.to_optimized(), c1);
+    }
+
+    #[test]
+    fn ancilla_extract() {
+        // two spiders with a T phase between the input and output spiders, which
+        // can't be extracted by row operations
+        let mut g = Graph::new();
+        let i = g.add_vertex(VType::B);
+        let o = g.add_vertex(VType::B);
+        let a = g.add_vertex(VType::Z);
+        let c = g.add_vertex(VType::Z);
+        g.add_edge(i, a);
+        g.add_edge(c, o);
+        for _ in 0..2 {
+            let b = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+            g.add_edge_with_type(a, b, EType::H);
+            g.add_edge_with_type(b, c, EType::H);
+        }
+        g.set_inputs(vec![i]);
+        g.set_outputs(vec![o]);
+
+        let e = g.to_circuit().unwrap_err();
+        assert_eq!(e.0, ExtractErrorKind::Stuck { frontier: vec![c] });
+        // c, its two neighbours b and the output, and boundaries for the edges to a
+        assert_eq!(e.subdiagram().num_vertices(), 6);
+        assert!(g.clone().extractor().with_ancillas(0).extract().is_err());
+        let c1 = g
+            .clone()
+            .extractor()
+            .with_ancillas(1)
+            .extract()
+            .expect("Circuit should extract.");
+        assert_eq!(c1.num_qubits(), 2);
+        assert_eq!(c1.num_gates_of_type(InitAncilla), 1);
+        assert_eq!(c1.num_gates_of_type(PostSelect), 1);
+        // circuit tensors don't support ancillas, so compare with its graph
+        let h: Graph = c1.to_graph();
+        assert!(Tensor4::scalar_compare(&g, &h));
+    }
+
     #[test]
     fn random_extract() {
         let c = Circuit::random()
@@ -676,6 +1386,57 @@ mod tests {
         let _c1 = g.to_circuit().expect("Circuit should extract.");
     }
 
+    #[test]
+    fn streaming_extract() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(6)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        full_simp(&mut g);
+        let c1 = g.to_circuit().expect("Circuit should extract.");
+
+        let mut gates = vec![];
+        let n = g
+            .clone()
+            .extractor()
+            .extract_streaming(|gate| gates.push(gate))
+            .expect("Circuit should extract.");
+        let mut c2 = Circuit::new(n);
+        for gate in gates.into_iter().rev() {
+            c2.push(gate);
+        }
+        assert_eq!(c1, c2);
+    }
+
+    #[test]
+    fn tie_break_extract() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(6)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        clifford_simp(&mut g);
+        for tie_break in [TieBreak::Index, TieBreak::Degree, TieBreak::Random(1)] {
+            let extract = || {
+                g.clone()
+                    .extractor()
+                    .with_tie_break(tie_break)
+                    .extract()
+                    .expect("Circuit should extract.")
+            };
+            let c1 = extract();
+            assert_eq!(c1, extract());
+            assert!(Tensor4::scalar_compare(&c, &c1));
+        }
+    }
+
     #[test]
     fn regression_extract_1() {
         // caused bug toward the end of extraction, when frontier was only
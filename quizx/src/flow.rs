@@ -0,0 +1,574 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Causal flow, gflow and Pauli flow
+//!
+//! A graph-like diagram is read as an open graph: its spiders are the vertices,
+//! the spiders connected to an input or output are the inputs and outputs of the
+//! open graph, and every spider which is not an output is measured. A flow gives
+//! each measured spider `v` a correction set `p(v)` of later spiders, and these
+//! exist exactly when the diagram can be run deterministically as a
+//! measurement-based computation, or equivalently, when a circuit can be extracted
+//! from it without ancillas.
+//!
+//! - [causal_flow] looks for correction sets with a single element.
+//! - [gflow] treats every spider as an XY-plane measurement, and [focused_gflow]
+//!   also makes its correction sets as small as the layers allow.
+//! - [pauli_flow] also uses that spiders with phase 0 or π are X measurements,
+//!   and spiders with phase ±π/2 are Y measurements.
+//! - [pauli_flow_with] takes the measurement of each spider as an argument.
+//!
+//! Each returns None if there is no flow of its kind. All but [causal_flow] use the
+//! layer-by-layer algorithm of Mhalla and Perdrix, as extended to Pauli flow by
+//! Simmons. For gflow, this finds a flow of minimal depth.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::flow::gflow;
+//! # use quizx::simplify::interior_clifford_simp;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::random().seed(1).qubits(4).depth(30).clifford_t(0.2).build();
+//! let mut g: Graph = c.to_graph();
+//! interior_clifford_simp(&mut g);
+//! let f = gflow(&g).unwrap();
+//! for (v, p) in &f.correction {
+//!     assert!(p.iter().all(|w| f.precedes(*v, *w)));
+//! }
+//! ```
+
+use crate::graph::*;
+use crate::linalg::Mat2;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// The measurement of a spider, for [pauli_flow_with]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Measurement {
+    XY,
+    XZ,
+    YZ,
+    X,
+    Y,
+    Z,
+}
+
+/// A flow on a graph-like diagram, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    /// The correction set of each measured spider
+    pub correction: FxHashMap<V, Vec<V>>,
+    /// The layer of each spider
+    ///
+    /// The outputs are in layer 0, and each measured spider is in a higher layer
+    /// than the spiders it must be measured before.
+    pub depth: FxHashMap<V, usize>,
+}
+
+impl Flow {
+    /// Whether `v` comes strictly before `w` in the partial order of the flow
+    pub fn precedes(&self, v: V, w: V) -> bool {
+        self.depth[&v] > self.depth[&w]
+    }
+
+    /// The number of layers, including the outputs
+    pub fn num_layers(&self) -> usize {
+        self.depth.values().max().map_or(0, |&d| d + 1)
+    }
+
+    /// The spiders in each layer, starting with the outputs
+    ///
+    /// The number of layers is the depth of the diagram as a measurement pattern.
+    pub fn layers(&self) -> Vec<Vec<V>> {
+        let mut layers = vec![vec![]; self.num_layers()];
+        for (&v, &d) in &self.depth {
+            layers[d].push(v);
+        }
+        for l in &mut layers {
+            l.sort();
+        }
+        layers
+    }
+
+    /// The measured spiders, in the order they can be extracted into a circuit
+    ///
+    /// Extraction starts from the outputs, so this lists the spiders by layer, from
+    /// the one closest to the outputs to the inputs.
+    pub fn extraction_order(&self) -> Vec<V> {
+        self.layers().into_iter().skip(1).flatten().collect()
+    }
+
+    /// Whether this is a focused gflow, i.e. the only measured spider in the odd
+    /// neighbourhood of each correction set `p(v)` is `v`
+    pub fn is_focused(&self, g: &impl GraphLike) -> bool {
+        self.correction.iter().all(|(&v, p)| {
+            odd_neighbourhood(g, p)
+                .iter()
+                .all(|w| *w == v || !self.correction.contains_key(w))
+        })
+    }
+
+    /// Turn a gflow into a focused gflow, see [Flow::is_focused]
+    ///
+    /// The layers stay the same. This is only meaningful for a gflow where every
+    /// spider is measured in the XY plane, e.g. as returned by [gflow].
+    pub fn focus(&mut self, g: &impl GraphLike) {
+        let mut vs: Vec<V> = self.correction.keys().copied().collect();
+        vs.sort_by_key(|v| (self.depth[v], *v));
+        // every measured w in Odd(p(v)) other than v is in a lower layer, so
+        // it is already focused, and adding p(w) to p(v) removes just w
+        for v in vs {
+            let odd = odd_neighbourhood(g, &self.correction[&v]);
+            let mut p: FxHashSet<V> = self.correction[&v].iter().copied().collect();
+            for w in odd {
+                if w != v && self.correction.contains_key(&w) {
+                    for &x in &self.correction[&w] {
+                        if !p.remove(&x) {
+                            p.insert(x);
+                        }
+                    }
+                }
+            }
+            let mut p: Vec<V> = p.into_iter().collect();
+            p.sort();
+            self.correction.insert(v, p);
+        }
+    }
+}
+
+/// The open graph of a graph-like diagram
+struct OpenGraph {
+    vs: Vec<V>,
+    nhd: Vec<Vec<usize>>,
+    inputs: Vec<bool>,
+    outputs: Vec<bool>,
+}
+
+impl OpenGraph {
+    fn new(g: &impl GraphLike) -> Option<OpenGraph> {
+        if !g.is_graph_like() {
+            return None;
+        }
+        let vs: Vec<V> = g
+            .vertices()
+            .filter(|&v| g.vertex_type(v) != VType::B)
+            .collect();
+        let index: FxHashMap<V, usize> = vs.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+        let nhd = vs
+            .iter()
+            .map(|&v| {
+                g.neighbors(v)
+                    .filter_map(|w| index.get(&w).copied())
+                    .collect()
+            })
+            .collect();
+        let mut inputs = vec![false; vs.len()];
+        let mut outputs = vec![false; vs.len()];
+        // a spider on two inputs or two outputs can't be one of the inputs or
+        // outputs of an open graph
+        for (bs, marks) in [(g.inputs(), &mut inputs), (g.outputs(), &mut outputs)] {
+            for &b in bs {
+                for w in g.neighbors(b) {
+                    if let Some(&i) = index.get(&w) {
+                        if marks[i] {
+                            return None;
+                        }
+                        marks[i] = true;
+                    }
+                }
+            }
+        }
+        Some(OpenGraph {
+            vs,
+            nhd,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Turn correction sets and layers given by index into a [Flow]
+    fn to_flow(&self, correction: Vec<Option<Vec<usize>>>, depth: Vec<usize>) -> Flow {
+        let correction = correction
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, p)| {
+                let mut p: Vec<V> = p?.into_iter().map(|j| self.vs[j]).collect();
+                p.sort();
+                Some((self.vs[i], p))
+            })
+            .collect();
+        let depth = depth
+            .into_iter()
+            .enumerate()
+            .map(|(i, d)| (self.vs[i], d))
+            .collect();
+        Flow { correction, depth }
+    }
+}
+
+/// Find a causal flow
+///
+/// This is the algorithm of Mhalla and Perdrix, which finds a causal flow of
+/// minimal depth if there is one.
+pub fn causal_flow(g: &impl GraphLike) -> Option<Flow> {
+    let og = OpenGraph::new(g)?;
+    let n = og.vs.len();
+    let mut correction: Vec<Option<Vec<usize>>> = vec![None; n];
+    let mut depth = vec![0; n];
+    let mut processed: Vec<bool> = og.outputs.clone();
+    let mut correctors: FxHashSet<usize> =
+        (0..n).filter(|&i| og.outputs[i] && !og.inputs[i]).collect();
+
+    let mut layer = 1;
+    while !correctors.is_empty() {
+        let mut next = FxHashSet::default();
+        let mut progress = false;
+        for &c in &correctors {
+            // a corrector with exactly one unprocessed neighbour corrects it
+            let mut unprocessed = og.nhd[c].iter().filter(|&&w| !processed[w]);
+            if let (Some(&u), None) = (unprocessed.next(), unprocessed.next()) {
+                if correction[u].is_none() {
+                    correction[u] = Some(vec![c]);
+                    depth[u] = layer;
+                    progress = true;
+                    if !og.inputs[u] {
+                        next.insert(u);
+                    }
+                }
+            }
+        }
+        if !progress {
+            break;
+        }
+        for u in 0..n {
+            if correction[u].is_some() {
+                processed[u] = true;
+            }
+        }
+        // the correctors which still have an unprocessed neighbour stay
+        correctors.retain(|&c| og.nhd[c].iter().any(|&w| !processed[w]));
+        correctors.extend(next);
+        layer += 1;
+    }
+
+    if processed.iter().all(|&p| p) {
+        Some(og.to_flow(correction, depth))
+    } else {
+        None
+    }
+}
+
+/// Find a gflow, treating every spider as an XY-plane measurement
+pub fn gflow(g: &impl GraphLike) -> Option<Flow> {
+    pauli_flow_with(g, |_| Measurement::XY)
+}
+
+/// Find a focused gflow, see [Flow::focus]
+pub fn focused_gflow(g: &impl GraphLike) -> Option<Flow> {
+    let mut f = gflow(g)?;
+    f.focus(g);
+    Some(f)
+}
+
+/// Find a Pauli flow, treating spiders with Pauli phases as X measurements, spiders
+/// with phase ±π/2 as Y measurements, and the rest as XY-plane measurements
+pub fn pauli_flow(g: &impl GraphLike) -> Option<Flow> {
+    pauli_flow_with(g, |v| {
+        let p = g.phase(v);
+        if p.is_pauli() {
+            Measurement::X
+        } else if p.is_proper_clifford() {
+            Measurement::Y
+        } else {
+            Measurement::XY
+        }
+    })
+}
+
+/// Solve a linear system over F2, given as an augmented matrix
+fn solve(mut m: Mat2) -> Option<Vec<bool>> {
+    let cols = m.num_cols() - 1;
+    m.gauss(true);
+    let mut x = vec![false; cols];
+    for i in 0..m.num_rows() {
+        match (0..=cols).find(|&j| m[(i, j)] != 0) {
+            Some(j) if j == cols => return None,
+            Some(j) => x[j] = m[(i, cols)] != 0,
+            None => {}
+        }
+    }
+    Some(x)
+}
+
+/// Find a Pauli flow, given the measurement of each spider which is not an output
+///
+/// With only planar measurements, i.e. [Measurement::XY], [Measurement::XZ] and
+/// [Measurement::YZ], this is an extended gflow.
+pub fn pauli_flow_with(g: &impl GraphLike, meas: impl Fn(V) -> Measurement) -> Option<Flow> {
+    use Measurement::*;
+    let og = OpenGraph::new(g)?;
+    let n = og.vs.len();
+    let m: Vec<Measurement> = og.vs.iter().map(|&v| meas(v)).collect();
+    let mut correction: Vec<Option<Vec<usize>>> = vec![None; n];
+    let mut depth = vec![0; n];
+    let mut processed: Vec<bool> = og.outputs.clone();
+
+    let adjacent = |w: usize, c: usize| og.nhd[w].contains(&c);
+
+    let mut layer = 1;
+    loop {
+        let unprocessed: Vec<usize> = (0..n).filter(|&i| !processed[i]).collect();
+        if unprocessed.is_empty() {
+            break;
+        }
+        let mut solved = vec![];
+        for &u in &unprocessed {
+            // the vertices allowed in p(u)
+            let cands: Vec<usize> = (0..n)
+                .filter(|&c| {
+                    !og.inputs[c]
+                        && if c == u {
+                            m[u] != XY
+                        } else {
+                            processed[c] || matches!(m[c], X | Y)
+                        }
+                })
+                .collect();
+            let col = |c: usize| cands.iter().position(|&d| d == c);
+
+            // each row is a parity of the candidates, followed by its value
+            let mut rows: Vec<Vec<u8>> = vec![];
+            let odd_row = |w: usize| -> Vec<u8> {
+                let mut r: Vec<u8> = cands.iter().map(|&c| u8::from(adjacent(w, c))).collect();
+                r.push(0);
+                r
+            };
+            for &w in &unprocessed {
+                if w == u {
+                    continue;
+                }
+                match m[w] {
+                    Z => {}
+                    Y => {
+                        let mut r = odd_row(w);
+                        if let Some(j) = col(w) {
+                            r[j] ^= 1;
+                        }
+                        rows.push(r);
+                    }
+                    _ => rows.push(odd_row(w)),
+                }
+            }
+            let in_p = |value: u8| -> Option<Vec<u8>> {
+                let j = col(u)?;
+                let mut r = vec![0; cands.len() + 1];
+                r[j] = 1;
+                r[cands.len()] = value;
+                Some(r)
+            };
+            let odd_u = |value: u8| {
+                let mut r = odd_row(u);
+                r[cands.len()] = value;
+                r
+            };
+            // if u is an input but must be in p(u), there is no flow
+            match m[u] {
+                XY | X => rows.push(odd_u(1)),
+                XZ => {
+                    rows.push(in_p(1)?);
+                    rows.push(odd_u(1));
+                }
+                YZ => {
+                    rows.push(in_p(1)?);
+                    rows.push(odd_u(0));
+                }
+                Z => rows.push(in_p(1)?),
+                Y => {
+                    let mut r = odd_u(1);
+                    if let Some(j) = col(u) {
+                        r[j] ^= 1;
+                    }
+                    rows.push(r);
+                }
+            }
+
+            if let Some(x) = solve(Mat2::new(rows)) {
+                let p = cands
+                    .iter()
+                    .zip(&x)
+                    .filter_map(|(&c, &b)| if b { Some(c) } else { None })
+                    .collect();
+                solved.push((u, p));
+            }
+        }
+
+        if solved.is_empty() {
+            return None;
+        }
+        for (u, p) in solved {
+            processed[u] = true;
+            correction[u] = Some(p);
+            depth[u] = layer;
+        }
+        layer += 1;
+    }
+
+    Some(og.to_flow(correction, depth))
+}
+
+/// The vertices with an odd number of neighbours in `p`
+pub fn odd_neighbourhood(g: &impl GraphLike, p: &[V]) -> Vec<V> {
+    let mut odd: FxHashMap<V, usize> = FxHashMap::default();
+    for &v in p {
+        for w in g.neighbors(v) {
+            if g.vertex_type(w) != VType::B {
+                *odd.entry(w).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut odd: Vec<V> = odd
+        .into_iter()
+        .filter_map(|(w, k)| if k % 2 == 1 { Some(w) } else { None })
+        .collect();
+    odd.sort();
+    odd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::*;
+    use crate::vec_graph::Graph;
+
+    /// Check the conditions of an XY-plane gflow
+    fn check_gflow(g: &Graph, f: &Flow) {
+        for (&v, p) in &f.correction {
+            let odd = odd_neighbourhood(g, p);
+            assert!(!p.contains(&v));
+            assert!(odd.contains(&v));
+            for &w in p.iter().chain(&odd) {
+                assert!(w == v || f.precedes(v, w));
+            }
+        }
+        let measured = g
+            .vertices()
+            .filter(|&v| {
+                g.vertex_type(v) != VType::B && !g.neighbors(v).any(|w| g.outputs().contains(&w))
+            })
+            .count();
+        assert_eq!(f.correction.len(), measured);
+    }
+
+    #[test]
+    fn flows_of_circuits() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(30)
+                .clifford_t(0.3)
+                .build();
+            let mut g: Graph = c.to_graph();
+            flow_simp(&mut g);
+            let f = causal_flow(&g).unwrap();
+            assert!(f.correction.values().all(|p| p.len() == 1));
+            check_gflow(&g, &f);
+            let f1 = gflow(&g).unwrap();
+            check_gflow(&g, &f1);
+            assert!(f1.num_layers() <= f.num_layers());
+
+            // full_simp_preserving_gflow introduces phase gadgets, which have an
+            // extended gflow once the leaves are removed and the roots are measured in
+            // the YZ plane
+            full_simp_preserving_gflow(&mut g);
+            let leaves: Vec<(V, V)> = g
+                .vertices()
+                .filter(|&v| g.degree(v) == 1 && g.vertex_type(v) == VType::Z)
+                .map(|v| (v, g.neighbors(v).next().unwrap()))
+                .collect();
+            let roots: Vec<V> = leaves.iter().map(|&(_, r)| r).collect();
+            for &(v, _) in &leaves {
+                g.remove_vertex(v);
+            }
+            let f = pauli_flow_with(&g, |v| match roots.contains(&v) {
+                true => Measurement::YZ,
+                false => Measurement::XY,
+            });
+            assert!(f.is_some());
+        }
+    }
+
+    #[test]
+    fn focused_layers() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .clifford_t(0.3)
+            .build();
+        let mut g: Graph = c.to_graph();
+        interior_clifford_simp(&mut g);
+        let f = gflow(&g).unwrap();
+        let mut f1 = f.clone();
+        f1.focus(&g);
+        assert!(f1.is_focused(&g));
+        assert_eq!(f1.depth, f.depth);
+        check_gflow(&g, &f1);
+        assert_eq!(focused_gflow(&g), Some(f1.clone()));
+
+        let layers = f1.layers();
+        assert_eq!(layers.len(), f1.num_layers());
+        assert!(layers[0].iter().all(|v| !f1.correction.contains_key(v)));
+        let order = f1.extraction_order();
+        assert_eq!(order.len(), f1.correction.len());
+        for (i, &v) in order.iter().enumerate() {
+            for &w in &order[i + 1..] {
+                assert!(!f1.precedes(v, w));
+            }
+        }
+    }
+
+    #[test]
+    fn no_flow() {
+        // two inputs and one output
+        let mut g = Graph::new();
+        let b: Vec<V> = (0..3).map(|_| g.add_vertex(VType::B)).collect();
+        let v: Vec<V> = (0..3).map(|_| g.add_vertex(VType::Z)).collect();
+        for i in 0..3 {
+            g.add_edge(b[i], v[i]);
+        }
+        g.add_edge_with_type(v[0], v[2], EType::H);
+        g.add_edge_with_type(v[1], v[2], EType::H);
+        g.set_inputs(vec![b[0], b[1]]);
+        g.set_outputs(vec![b[2]]);
+        assert!(causal_flow(&g).is_none());
+        assert!(gflow(&g).is_none());
+
+        // an interior spider in the XY plane has no gflow, but if it is X
+        // measured, it has a Pauli flow
+        let mut g = Graph::new();
+        let b: Vec<V> = (0..2).map(|_| g.add_vertex(VType::B)).collect();
+        let v: Vec<V> = (0..3).map(|_| g.add_vertex(VType::Z)).collect();
+        g.add_edge(b[0], v[0]);
+        g.add_edge(b[1], v[1]);
+        g.add_edge_with_type(v[0], v[1], EType::H);
+        g.add_edge_with_type(v[0], v[2], EType::H);
+        g.add_edge_with_type(v[1], v[2], EType::H);
+        g.set_inputs(vec![b[0]]);
+        g.set_outputs(vec![b[1]]);
+        assert!(gflow(&g).is_none());
+        assert!(pauli_flow(&g).is_some());
+    }
+}
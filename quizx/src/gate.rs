@@ -18,6 +18,7 @@ use crate::circuit::Circuit;
 use crate::graph::*;
 use crate::phase::Phase;
 use crate::scalar::*;
+use crate::symbolic::ParamSum;
 use num::{Rational64, Zero};
 use std::cmp::max;
 
@@ -41,6 +42,9 @@ pub enum GType {
     CCZ,
     InitAncilla,
     PostSelect,
+    Measure,
+    Reset,
+    Barrier,
     UnknownGate,
 }
 
@@ -69,6 +73,9 @@ impl GType {
             "xcx" => XCX,
             "init_anc" => InitAncilla,
             "post_sel" => PostSelect,
+            "measure" => Measure,
+            "reset" => Reset,
+            "barrier" => Barrier,
             _ => UnknownGate,
         }
     }
@@ -94,6 +101,9 @@ impl GType {
             XCX => "xcx",
             InitAncilla => "init_anc",
             PostSelect => "post_sel",
+            Measure => "measure",
+            Reset => "reset",
+            Barrier => "barrier",
             UnknownGate => "UNKNOWN",
         }
     }
@@ -106,17 +116,59 @@ impl GType {
         match self {
             CNOT | CZ | XCX | SWAP => Some(2),
             TOFF | CCZ => Some(3),
-            ParityPhase | UnknownGate => None,
+            ParityPhase | Barrier | UnknownGate => None,
             _ => Some(1),
         }
     }
 }
 
+/// A classical condition on a gate
+///
+/// The gate is only applied if the classical bits `bits` hold `value`, where
+/// `bits[i]` is bit `i` of `value`, as for `if(c==value)` in QASM.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Condition {
+    pub bits: Vec<usize>,
+    pub value: u64,
+}
+
+impl Condition {
+    /// The value the condition expects for its `i`-th bit
+    pub fn bit_value(&self, i: usize) -> bool {
+        i < 64 && (self.value >> i) & 1 == 1
+    }
+
+    /// QASM for the condition, when the classical register `c` has `nbits` bits
+    ///
+    /// If the condition is on the whole register, this is the QASM 2 form
+    /// `if(c==value)`, otherwise it compares single bits, as in QASM 3.
+    pub fn to_qasm(&self, nbits: usize) -> String {
+        if self.bits.iter().copied().eq(0..nbits) {
+            format!("if(c=={})", self.value)
+        } else {
+            let cs: Vec<String> = self
+                .bits
+                .iter()
+                .enumerate()
+                .map(|(i, b)| format!("c[{}]=={}", b, self.bit_value(i) as u8))
+                .collect();
+            format!("if({})", cs.join(" && "))
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Gate {
     pub t: GType,
     pub qs: Vec<usize>,
     pub phase: Phase,
+    /// Free parameters added to the phase of a [ZPhase] or [XPhase] gate, see
+    /// [Gate::new_with_params]
+    pub params: ParamSum,
+    /// The classical bits written by a [Measure] gate
+    pub bits: Vec<usize>,
+    /// A classical condition for applying the gate, see [Gate::with_condition]
+    pub condition: Option<Condition>,
 }
 
 impl Gate {
@@ -125,6 +177,9 @@ impl Gate {
             t: GType::from_qasm_name(s),
             qs: vec![],
             phase: Phase::zero(),
+            params: ParamSum::default(),
+            bits: vec![],
+            condition: None,
         }
     }
 
@@ -136,13 +191,25 @@ impl Gate {
         let mut s = String::from(self.qasm_name());
 
         if let ZPhase | XPhase = self.t {
-            s += &format!("({}*pi)", self.phase.to_f64());
+            if self.params.is_zero() {
+                s += &format!("({}*pi)", self.phase.to_f64());
+            } else if self.phase.is_zero() {
+                s += &format!("(({})*pi)", self.params);
+            } else {
+                s += &format!("(({} + {})*pi)", self.phase.to_f64(), self.params);
+            }
         }
 
         s += " ";
         let qs: Vec<String> = self.qs.iter().map(|i| format!("q[{}]", i)).collect();
         s += &qs.join(", ");
 
+        if self.t == Measure {
+            let bs: Vec<String> = self.bits.iter().map(|i| format!("c[{}]", i)).collect();
+            s += " -> ";
+            s += &bs.join(", ");
+        }
+
         s
     }
 
@@ -150,6 +217,7 @@ impl Gate {
         match self.t {
             ZPhase | XPhase | ParityPhase => {
                 self.phase *= -1;
+                self.params.negate();
             }
             S => self.t = Sdg,
             T => self.t = Tdg,
@@ -166,6 +234,9 @@ impl Gate {
             t,
             qs,
             phase: Phase::zero(),
+            params: ParamSum::default(),
+            bits: vec![],
+            condition: None,
         }
     }
 
@@ -174,9 +245,69 @@ impl Gate {
             t,
             qs,
             phase: phase.into(),
+            params: ParamSum::default(),
+            bits: vec![],
+            condition: None,
+        }
+    }
+
+    /// A [ZPhase] or [XPhase] gate whose angle is `phase` plus some parameters
+    ///
+    /// The parameters can be given values later with [Circuit::bind] or
+    /// [Circuit::substitute]. Code which only reads [Gate::phase], like tensor
+    /// contraction and most exporters, only sees the constant part.
+    pub fn new_with_params(
+        t: GType,
+        qs: Vec<usize>,
+        phase: impl Into<Phase>,
+        params: ParamSum,
+    ) -> Gate {
+        assert!(
+            matches!(t, ZPhase | XPhase),
+            "Only ZPhase and XPhase gates can have parameters"
+        );
+        Gate {
+            t,
+            qs,
+            phase: phase.into(),
+            params,
+            bits: vec![],
+            condition: None,
         }
     }
 
+    /// A barrier on the qubits `qs`
+    ///
+    /// A barrier does nothing, but gate-level passes such as [Circuit::optimize]
+    /// don't move or merge gates across it. It is ignored when the circuit is turned
+    /// into a graph, and can be removed with [Circuit::remove_barriers].
+    pub fn new_barrier(qs: Vec<usize>) -> Gate {
+        Gate::new(Barrier, qs)
+    }
+
+    /// A measurement of `q` in the Z basis, with the outcome written to the
+    /// classical bit `bit`
+    pub fn new_measure(q: usize, bit: usize) -> Gate {
+        Gate {
+            bits: vec![bit],
+            ..Gate::new(Measure, vec![q])
+        }
+    }
+
+    /// The gate, applied only if the classical bits `bits` hold `value`
+    ///
+    /// See [Condition].
+    pub fn with_condition(mut self, bits: Vec<usize>, value: u64) -> Gate {
+        self.condition = Some(Condition { bits, value });
+        self
+    }
+
+    /// The classical bits the gate writes to or depends on
+    pub fn classical_bits(&self) -> impl Iterator<Item = usize> + '_ {
+        let cond = self.condition.iter().flat_map(|c| c.bits.iter());
+        self.bits.iter().chain(cond).copied()
+    }
+
     fn push_ccz_decomp(circ: &mut Circuit, qs: &[usize]) {
         circ.push(Gate::new(CNOT, vec![qs[1], qs[2]]));
         circ.push(Gate::new(Tdg, vec![qs[2]]));
@@ -211,8 +342,10 @@ impl Gate {
 
     /// decompose as 1 and 2 qubit Clifford + phase gates and push on to given vec
     ///
-    /// If a gate is already basic, push a copy of itself.
+    /// If a gate is already basic, push a copy of itself. A classical condition on
+    /// the gate is copied to each of the basic gates.
     pub fn push_basic_gates(&self, circ: &mut Circuit) {
+        let start = circ.gates.len();
         match self.t {
             CCZ => {
                 Gate::push_ccz_decomp(circ, &self.qs);
@@ -236,6 +369,11 @@ impl Gate {
             }
             _ => circ.push(self.clone()),
         }
+        if self.condition.is_some() {
+            for g in circ.gates.iter_mut().skip(start) {
+                g.condition = self.condition.clone();
+            }
+        }
     }
 
     fn add_spider<G: GraphLike>(
@@ -332,6 +470,10 @@ impl Gate {
         qs: &mut Vec<Option<usize>>,
         postselect: bool,
     ) {
+        assert!(
+            self.condition.is_none(),
+            "Classically controlled gates can't be added to a graph, see Circuit::defer_measurements"
+        );
         match self.t {
             ZPhase => {
                 Gate::add_spider(graph, qs, self.qs[0], VType::Z, EType::N, self.phase);
@@ -506,7 +648,12 @@ impl Gate {
                     g.add_to_graph(graph, qs, postselect);
                 }
             }
-            UnknownGate => {}
+            Measure => {
+                // n.b. this is only correct for terminal measurements, which is checked
+                // by Circuit::to_graph
+            }
+            Reset => panic!("Resets can't be added to a graph, see Circuit::to_doubled_graph"),
+            Barrier | UnknownGate => {}
         };
     }
 }
@@ -17,8 +17,14 @@
 use std::mem;
 
 use crate::circuit::*;
+use crate::controlled::push_controlled_phase;
 use crate::gate::*;
-use num::Rational64;
+use crate::pauli_simp::{PauliForm, PauliRotation, PauliString};
+use crate::phase::Phase;
+use crate::symbolic::ParamSum;
+use crate::tableau::Tableau;
+use crate::toffoli::{push_mcz, ToffoliMethod};
+use num::{Rational64, Zero};
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 
@@ -31,6 +37,8 @@ pub struct RandomCircuitBuilder {
     pub p_h: f32,
     pub p_s: f32,
     pub p_t: f32,
+    /// Place exactly this many T gates, see [RandomCircuitBuilder::t_count]
+    pub t_count: Option<usize>,
 }
 
 pub struct RandomHiddenShiftCircuitBuilder {
@@ -40,6 +48,11 @@ pub struct RandomHiddenShiftCircuitBuilder {
     pub n_ccz: usize,
 }
 
+pub struct RandomCliffordBuilder {
+    pub rng: StdRng,
+    pub qubits: usize,
+}
+
 pub struct RandomPauliGadgetCircuitBuilder {
     pub rng: StdRng,
     pub qubits: usize,
@@ -49,6 +62,71 @@ pub struct RandomPauliGadgetCircuitBuilder {
     pub phase_denom: usize,
 }
 
+pub struct QaoaCircuitBuilder {
+    pub rng: StdRng,
+    pub qubits: usize,
+    pub layers: usize,
+    /// The edges of the problem graph, or `None` for a random graph
+    pub edges: Option<Vec<(usize, usize)>>,
+    /// The probability of each edge in a random graph
+    pub edge_prob: f64,
+    /// The angles `(gamma, beta)` of each layer, or `None` for free parameters
+    pub angles: Option<Vec<(Phase, Phase)>>,
+}
+
+pub struct RandomIqpCircuitBuilder {
+    pub rng: StdRng,
+    pub qubits: usize,
+    /// The probability of a CZ on each pair of qubits
+    pub p_cz: f64,
+    /// The probability of a controlled-S on each pair of qubits
+    pub p_cs: f64,
+    /// The probability of a T on each qubit
+    pub p_t: f64,
+}
+
+pub struct TrotterCircuitBuilder {
+    /// The Pauli operators of the Hamiltonian, with their coefficients
+    pub terms: Vec<(PauliString, f64)>,
+    pub time: f64,
+    pub steps: usize,
+    /// Use the symmetric second-order product formula
+    pub second_order: bool,
+    /// The largest denominator of the angles, which are rounded to fractions of pi
+    pub max_denom: i64,
+}
+
+pub struct DistillationCircuitBuilder {
+    /// The triorthogonal matrix of the protocol, with one row per qubit
+    pub matrix: Vec<Vec<bool>>,
+    /// The number of output qubits, which are the first rows of the matrix
+    pub outputs: usize,
+    /// The columns whose T gate is followed by a Z error
+    pub faults: Vec<usize>,
+}
+
+pub struct GroverCircuitBuilder {
+    /// The marked basis state, with one entry per qubit
+    pub marked: Vec<bool>,
+    /// The number of Grover iterations, or `None` for the optimal number
+    pub iterations: Option<usize>,
+    /// How to decompose the multi-controlled Z gates
+    pub method: ToffoliMethod,
+}
+
+/// Pushes the QFT on `qs`, with `qs[0]` as the most significant bit
+fn push_qft(c: &mut Circuit, qs: &[usize]) {
+    for (i, &q) in qs.iter().enumerate() {
+        c.push(Gate::new(HAD, vec![q]));
+        for (j, &r) in qs.iter().enumerate().skip(i + 1) {
+            push_controlled_phase(c, r, q, Phase::from((1, 1 << (j - i))));
+        }
+    }
+    for (&p, &q) in qs.iter().zip(qs.iter().rev()).take(qs.len() / 2) {
+        c.push(Gate::new(SWAP, vec![p, q]));
+    }
+}
+
 impl Circuit {
     pub fn random() -> RandomCircuitBuilder {
         RandomCircuitBuilder {
@@ -60,9 +138,19 @@ impl Circuit {
             p_h: 0.0,
             p_s: 0.0,
             p_t: 0.0,
+            t_count: None,
         }
     }
 
+    /// Random hidden shift circuits, as used to benchmark stabiliser rank
+    /// simulation in Bravyi and Gosset, "Improved classical simulation of quantum
+    /// circuits dominated by Clifford gates" (2016)
+    ///
+    /// The oracles are built from `n_ccz` random CCZ gates on the first half of the
+    /// qubits, each preceded by `clifford_depth` random Z and CZ gates, so the
+    /// circuit has `2 * n_ccz` CCZ gates. The circuit maps `|0...0>` to the hidden
+    /// shift, which is returned alongside it by
+    /// [build](RandomHiddenShiftCircuitBuilder::build).
     pub fn random_hidden_shift() -> RandomHiddenShiftCircuitBuilder {
         RandomHiddenShiftCircuitBuilder {
             rng: StdRng::from_entropy(),
@@ -72,6 +160,202 @@ impl Circuit {
         }
     }
 
+    /// Random IQP circuits, made of a diagonal layer between two layers of
+    /// Hadamards
+    ///
+    /// The diagonal layer has a CZ and a controlled-S on each pair of qubits, and a
+    /// T on each qubit, with the given probabilities. A controlled-S is written as
+    /// two T gates and a Tdg between CNOTs.
+    pub fn random_iqp() -> RandomIqpCircuitBuilder {
+        RandomIqpCircuitBuilder {
+            rng: StdRng::from_entropy(),
+            qubits: 10,
+            p_cz: 0.5,
+            p_cs: 0.5,
+            p_t: 0.5,
+        }
+    }
+
+    /// QAOA circuits for MaxCut, with `layers` rounds of cost and mixer unitaries
+    ///
+    /// The circuit starts with a Hadamard on every qubit. In layer `l`, each edge
+    /// `(u, v)` of the problem graph gets the cost unitary `exp(-i gamma_l Z_u Z_v
+    /// pi/2)`, as a CNOT, an `rz(gamma_l)` and a CNOT, then every qubit gets the
+    /// mixer `rx(beta_l)`. Angles are in half-turns, like [Phase]. Unless they are
+    /// given with [angles](QaoaCircuitBuilder::angles), they are free parameters
+    /// `gamma{l}` and `beta{l}`, which can be given values with [Circuit::bind] or
+    /// [Circuit::substitute].
+    pub fn qaoa() -> QaoaCircuitBuilder {
+        QaoaCircuitBuilder {
+            rng: StdRng::from_entropy(),
+            qubits: 10,
+            layers: 1,
+            edges: None,
+            edge_prob: 0.5,
+            angles: None,
+        }
+    }
+
+    /// Trotterised time evolution `exp(-i t H)` under a Hamiltonian `H`, given as a
+    /// sum of Pauli operators with real coefficients
+    ///
+    /// Each of the `steps` steps applies the rotation `exp(-i c P t / steps)` for
+    /// each term `c P`, in order. With the second-order formula, each step applies
+    /// the rotations for half the time, then again in reverse order. The rotations
+    /// are synthesised as phase gadgets by [PauliForm::to_circuit].
+    ///
+    /// ```
+    /// # use quizx::circuit::Circuit;
+    /// let c = Circuit::trotter()
+    ///     .term("ZZI", 1.0)
+    ///     .term("IZZ", 1.0)
+    ///     .term("XII", 0.5)
+    ///     .time(0.5)
+    ///     .steps(4)
+    ///     .build();
+    /// assert_eq!(c.num_qubits(), 3);
+    /// ```
+    pub fn trotter() -> TrotterCircuitBuilder {
+        TrotterCircuitBuilder {
+            terms: vec![],
+            time: 1.0,
+            steps: 1,
+            second_order: false,
+            max_denom: 1 << 16,
+        }
+    }
+
+    /// Uniformly random Clifford circuits
+    ///
+    /// The Clifford unitary is sampled uniformly, up to a global phase, with the
+    /// canonical form `F2 W F1` of Bravyi and Maslov, "Hadamard-free circuits expose
+    /// the structure of the Clifford group" (2021). Here `F1` and `F2` are
+    /// Hadamard-free Cliffords, and `W` is a layer of Hadamards followed by a
+    /// permutation of the qubits, which is sampled from the quantum Mallows
+    /// distribution. The circuit is followed by a random Pauli, and is returned with
+    /// its [Tableau].
+    pub fn random_clifford() -> RandomCliffordBuilder {
+        RandomCliffordBuilder {
+            rng: StdRng::from_entropy(),
+            qubits: 10,
+        }
+    }
+
+    /// Magic state distillation circuits, with the 15-to-1 protocol by default
+    ///
+    /// A protocol is given by a triorthogonal matrix, as in Bravyi and Haah, "Magic
+    /// state distillation with low overhead" (2012), with a qubit for each row. Every
+    /// qubit starts in `|+>`, then each column gets a Tdg gate on the parity of the
+    /// qubits in its support, as a phase gadget made of CNOTs. This consumes one
+    /// noisy T state per column. Finally, the check qubits are measured in the X
+    /// basis and post-selected onto `|+>` with [PostSelect] gates, leaving the
+    /// output qubits in the distilled state. For 15-to-1, this is `T|+>`, and a Z
+    /// error on any single column is detected.
+    ///
+    /// Other protocols, such as 116-to-12, can be given with
+    /// [triorthogonal](DistillationCircuitBuilder::triorthogonal).
+    ///
+    /// ```
+    /// # use quizx::circuit::Circuit;
+    /// # use quizx::gate::*;
+    /// let c = Circuit::distillation().build();
+    /// assert_eq!(c.num_qubits(), 5);
+    /// assert_eq!(c.num_gates_of_type(Tdg), 15);
+    /// assert_eq!(c.num_gates_of_type(PostSelect), 4);
+    /// ```
+    pub fn distillation() -> DistillationCircuitBuilder {
+        // the output row is all ones, and column v of the checks is v in binary
+        let mut matrix = vec![vec![true; 15]];
+        for i in 0..4 {
+            matrix.push((1..16).map(|v: usize| v & (1 << i) != 0).collect());
+        }
+        DistillationCircuitBuilder {
+            matrix,
+            outputs: 1,
+            faults: vec![],
+        }
+    }
+
+    /// The quantum Fourier transform on `n` qubits
+    ///
+    /// This maps `|x>` to the sum of `e^(2 pi i x y / 2^n) |y>` over all `y`, up to
+    /// normalisation, where qubit 0 is the most significant bit. The controlled
+    /// phases are decomposed into CNOTs and Z phases, and the qubits are reversed
+    /// with SWAPs at the end.
+    pub fn qft(n: usize) -> Circuit {
+        let mut c = Circuit::new(n);
+        push_qft(&mut c, &(0..n).collect::<Vec<_>>());
+        c
+    }
+
+    /// Draper's adder, which adds the register `a` to `b` modulo `2^n` in Fourier
+    /// space
+    ///
+    /// The registers are the qubits `0..n` and `n..2n`, each with its first qubit as
+    /// the most significant bit, and the circuit maps `|a>|b>` to `|a>|a + b>`. It is
+    /// a QFT on `b`, a controlled phase from each qubit of `a` to each qubit of
+    /// `b`, then the inverse QFT.
+    pub fn qft_adder(n: usize) -> Circuit {
+        let mut c = Circuit::new(2 * n);
+        let mut qft = Circuit::new(2 * n);
+        push_qft(&mut qft, &(n..2 * n).collect::<Vec<_>>());
+        c += &qft;
+        // a_m b_k contributes the phase 2 pi 2^(2n - 2 - m - k) / 2^n
+        for m in 0..n {
+            for k in n - 1 - m..n {
+                let phase = Phase::from((1, 1 << (k + m + 1 - n)));
+                push_controlled_phase(&mut c, m, n + k, phase);
+            }
+        }
+        c += &qft.to_adjoint();
+        c
+    }
+
+    /// The ripple-carry adder of Cuccaro et al., "A new quantum ripple-carry
+    /// addition circuit" (2004), which adds the register `a` to `b` modulo `2^n`
+    ///
+    /// The registers are laid out as for [Circuit::qft_adder], followed by an
+    /// ancilla for the carry, which should start in `|0>` and is returned to `|0>`.
+    /// The circuit uses only CNOT and Toffoli gates.
+    pub fn ripple_carry_adder(n: usize) -> Circuit {
+        let mut c = Circuit::new(2 * n + 1);
+        // the qubits of a and b with weight 2^i
+        let a = |i: usize| n - 1 - i;
+        let b = |i: usize| 2 * n - 1 - i;
+        let carry = |i: usize| if i == 0 { 2 * n } else { a(i - 1) };
+        let cnot = |x: usize, y: usize| Gate::new(CNOT, vec![x, y]);
+        let toff = |x: usize, y: usize, z: usize| Gate::new(TOFF, vec![x, y, z]);
+        for i in 0..n {
+            // majority of the carry, a_i and b_i into a_i
+            c.push(cnot(a(i), b(i)));
+            c.push(cnot(a(i), carry(i)));
+            c.push(toff(carry(i), b(i), a(i)));
+        }
+        for i in (0..n).rev() {
+            // un-majority and add, which restores a_i and writes the sum to b_i
+            c.push(toff(carry(i), b(i), a(i)));
+            c.push(cnot(a(i), carry(i)));
+            c.push(cnot(carry(i), b(i)));
+        }
+        c
+    }
+
+    /// Grover search for a single marked basis state
+    ///
+    /// The circuit prepares `|+...+>` then applies the Grover iteration, made of an
+    /// oracle which flips the sign of the marked state and the diffusion operator.
+    /// Both use a Z controlled on all the other qubits, which is decomposed into
+    /// Clifford+T with [push_mcz]. Any ancillas this needs come after the search
+    /// qubits. By default, there are `floor(pi/4 sqrt(2^n))` iterations, which
+    /// gives the marked state with high probability.
+    pub fn grover() -> GroverCircuitBuilder {
+        GroverCircuitBuilder {
+            marked: vec![true; 3],
+            iterations: None,
+            method: ToffoliMethod::AncillaFree,
+        }
+    }
+
     pub fn random_pauli_gadget() -> RandomPauliGadgetCircuitBuilder {
         RandomPauliGadgetCircuitBuilder {
             rng: StdRng::from_entropy(),
@@ -135,6 +419,17 @@ impl RandomCircuitBuilder {
         self.p_t(p_t).with_cliffords()
     }
 
+    /// Place exactly `t_count` T gates, at positions chosen uniformly at random
+    ///
+    /// The other `depth - t_count` gates are chosen with the probabilities of the
+    /// Clifford gates, scaled so they add up to 1, so the circuit has exactly `depth`
+    /// gates. [with_cliffords](Self::with_cliffords) is used if no Clifford gate has
+    /// been given a probability.
+    pub fn t_count(&mut self, t_count: usize) -> &mut Self {
+        self.t_count = Some(t_count);
+        self
+    }
+
     pub fn uniform(&mut self) -> &mut Self {
         self.p_cnot = 0.2;
         self.p_cz = 0.2;
@@ -147,15 +442,39 @@ impl RandomCircuitBuilder {
     pub fn build(&mut self) -> Circuit {
         let mut c = Circuit::new(self.qubits);
 
-        for _ in 0..self.depth {
+        let t_at = self.t_count.map(|k| {
+            assert!(
+                k <= self.depth,
+                "More T gates than the depth of the circuit"
+            );
+            if self.p_cnot + self.p_cz + self.p_h + self.p_s == 0.0 {
+                self.p_t = 0.0;
+                self.with_cliffords();
+            }
+            let mut t_at = vec![false; self.depth];
+            for i in rand::seq::index::sample(&mut self.rng, self.depth, k) {
+                t_at[i] = true;
+            }
+            t_at
+        });
+
+        for i in 0..self.depth {
             let mut p0 = 0.0;
-            let p: f32 = self.rng.gen();
+            let mut p: f32 = self.rng.gen();
             let q0 = self.rng.gen_range(0..self.qubits);
             let mut q1 = self.rng.gen_range(0..self.qubits - 1);
             if q1 >= q0 {
                 q1 += 1;
             }
 
+            if let Some(t_at) = &t_at {
+                if t_at[i] {
+                    c.push(Gate::new(T, vec![q0]));
+                    continue;
+                }
+                p *= self.p_cnot + self.p_cz + self.p_h + self.p_s;
+            }
+
             p0 += self.p_cnot;
             if p < p0 {
                 c.push(Gate::new(CNOT, vec![q0, q1]));
@@ -181,7 +500,7 @@ impl RandomCircuitBuilder {
             }
 
             p0 += self.p_t;
-            if p < p0 {
+            if p < p0 && t_at.is_none() {
                 c.push(Gate::new(T, vec![q0]));
                 continue;
             }
@@ -245,6 +564,7 @@ impl RandomHiddenShiftCircuitBuilder {
         c.push(Gate::new(CCZ, vec![q0, q1, q2]));
     }
 
+    /// The circuit and its hidden shift, with one bit per qubit
     pub fn build(&mut self) -> (Circuit, Vec<u8>) {
         if self.qubits < 6 || self.qubits % 2 != 0 {
             panic!("Random hidden shift circuits must have an even number of qubits >= 6.");
@@ -294,6 +614,440 @@ impl RandomHiddenShiftCircuitBuilder {
     }
 }
 
+impl RandomIqpCircuitBuilder {
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+    pub fn qubits(&mut self, qubits: usize) -> &mut Self {
+        self.qubits = qubits;
+        self
+    }
+    pub fn p_cz(&mut self, p_cz: f64) -> &mut Self {
+        self.p_cz = p_cz;
+        self
+    }
+    pub fn p_cs(&mut self, p_cs: f64) -> &mut Self {
+        self.p_cs = p_cs;
+        self
+    }
+    pub fn p_t(&mut self, p_t: f64) -> &mut Self {
+        self.p_t = p_t;
+        self
+    }
+    /// Use the same probability for every kind of gate
+    pub fn density(&mut self, p: f64) -> &mut Self {
+        self.p_t(p).p_cz(p).p_cs(p)
+    }
+
+    pub fn build(&mut self) -> Circuit {
+        let mut c = Circuit::new(self.qubits);
+        for q in 0..self.qubits {
+            c.push(Gate::new(HAD, vec![q]));
+        }
+        for q0 in 0..self.qubits {
+            for q1 in q0 + 1..self.qubits {
+                if self.rng.gen_bool(self.p_cz) {
+                    c.push(Gate::new(CZ, vec![q0, q1]));
+                }
+                if self.rng.gen_bool(self.p_cs) {
+                    c.push(Gate::new(T, vec![q0]));
+                    c.push(Gate::new(T, vec![q1]));
+                    c.push(Gate::new(CNOT, vec![q0, q1]));
+                    c.push(Gate::new(Tdg, vec![q1]));
+                    c.push(Gate::new(CNOT, vec![q0, q1]));
+                }
+            }
+        }
+        for q in 0..self.qubits {
+            if self.rng.gen_bool(self.p_t) {
+                c.push(Gate::new(T, vec![q]));
+            }
+        }
+        for q in 0..self.qubits {
+            c.push(Gate::new(HAD, vec![q]));
+        }
+        c
+    }
+}
+
+impl QaoaCircuitBuilder {
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+    pub fn qubits(&mut self, qubits: usize) -> &mut Self {
+        self.qubits = qubits;
+        self
+    }
+    pub fn layers(&mut self, layers: usize) -> &mut Self {
+        self.layers = layers;
+        self
+    }
+    /// Use the given problem graph instead of a random one
+    pub fn edges(&mut self, edges: Vec<(usize, usize)>) -> &mut Self {
+        self.edges = Some(edges);
+        self
+    }
+    pub fn edge_prob(&mut self, edge_prob: f64) -> &mut Self {
+        self.edge_prob = edge_prob;
+        self
+    }
+    /// Use fixed angles `(gamma, beta)`, one pair per layer
+    pub fn angles(&mut self, angles: Vec<(Phase, Phase)>) -> &mut Self {
+        self.layers = angles.len();
+        self.angles = Some(angles);
+        self
+    }
+
+    /// A rotation by the angle of layer `l`, or by the parameter `{name}{l}`
+    fn rotation(&self, t: GType, q: usize, l: usize, name: &str) -> Gate {
+        match &self.angles {
+            Some(angles) => {
+                let (gamma, beta) = angles[l];
+                Gate::new_with_phase(t, vec![q], if t == ZPhase { gamma } else { beta })
+            }
+            None => Gate::new_with_params(
+                t,
+                vec![q],
+                Phase::zero(),
+                ParamSum::param(&format!("{}{}", name, l)),
+            ),
+        }
+    }
+
+    /// The circuit and the edges of its problem graph
+    pub fn build(&mut self) -> (Circuit, Vec<(usize, usize)>) {
+        let edges = match &self.edges {
+            Some(edges) => edges.clone(),
+            None => {
+                let mut edges = vec![];
+                for u in 0..self.qubits {
+                    for v in u + 1..self.qubits {
+                        if self.rng.gen_bool(self.edge_prob) {
+                            edges.push((u, v));
+                        }
+                    }
+                }
+                edges
+            }
+        };
+
+        let mut c = Circuit::new(self.qubits);
+        for q in 0..self.qubits {
+            c.push(Gate::new(HAD, vec![q]));
+        }
+        for l in 0..self.layers {
+            for &(u, v) in &edges {
+                c.push(Gate::new(CNOT, vec![u, v]));
+                c.push(self.rotation(ZPhase, v, l, "gamma"));
+                c.push(Gate::new(CNOT, vec![u, v]));
+            }
+            for q in 0..self.qubits {
+                c.push(self.rotation(XPhase, q, l, "beta"));
+            }
+        }
+        (c, edges)
+    }
+}
+
+impl TrotterCircuitBuilder {
+    /// Add the term `coeff * P`, where the Pauli operator `P` is written with the
+    /// letters `I`, `X`, `Y` and `Z`, see [PauliString::from_letters]
+    pub fn term(&mut self, pauli: &str, coeff: f64) -> &mut Self {
+        let p = PauliString::from_letters(pauli)
+            .unwrap_or_else(|| panic!("Invalid Pauli string: {}", pauli));
+        self.pauli_term(p, coeff)
+    }
+    pub fn pauli_term(&mut self, pauli: PauliString, coeff: f64) -> &mut Self {
+        if let Some((p, _)) = self.terms.first() {
+            assert_eq!(
+                p.num_qubits(),
+                pauli.num_qubits(),
+                "Pauli strings must all have the same length"
+            );
+        }
+        self.terms.push((pauli, coeff));
+        self
+    }
+    pub fn time(&mut self, time: f64) -> &mut Self {
+        self.time = time;
+        self
+    }
+    pub fn steps(&mut self, steps: usize) -> &mut Self {
+        self.steps = steps;
+        self
+    }
+    pub fn second_order(&mut self, second_order: bool) -> &mut Self {
+        self.second_order = second_order;
+        self
+    }
+    pub fn max_denom(&mut self, max_denom: i64) -> &mut Self {
+        self.max_denom = max_denom;
+        self
+    }
+
+    /// The rotation `exp(-i c P dt)`, as a multiple of `exp(-i pi P / 2)`
+    fn rotation(&self, pauli: &PauliString, c: f64, dt: f64) -> PauliRotation {
+        let phase = Phase::from_f64(2.0 * c * dt / std::f64::consts::PI);
+        PauliRotation {
+            pauli: pauli.clone(),
+            phase: phase.limit_denominator(self.max_denom),
+        }
+    }
+
+    /// The Pauli rotations of the circuit, in order
+    ///
+    /// Neighbouring rotations about the same operator are merged.
+    pub fn rotations(&self) -> Vec<PauliRotation> {
+        let dt = self.time / self.steps as f64;
+        let mut step = vec![];
+        if self.second_order {
+            for (p, c) in &self.terms {
+                step.push(self.rotation(p, *c, dt / 2.0));
+            }
+            for (p, c) in self.terms.iter().rev() {
+                step.push(self.rotation(p, *c, dt / 2.0));
+            }
+        } else {
+            for (p, c) in &self.terms {
+                step.push(self.rotation(p, *c, dt));
+            }
+        }
+
+        let mut rotations: Vec<PauliRotation> = vec![];
+        for r in (0..self.steps).flat_map(|_| step.iter()) {
+            match rotations.last_mut() {
+                Some(last) if last.pauli == r.pauli => last.phase += r.phase,
+                _ => rotations.push(r.clone()),
+            }
+        }
+        rotations.retain(|r| !r.phase.is_zero());
+        rotations
+    }
+
+    pub fn build(&self) -> Circuit {
+        let n = self.terms.first().map_or(0, |(p, _)| p.num_qubits());
+        PauliForm {
+            num_qubits: n,
+            rotations: self.rotations(),
+            clifford: Circuit::new(n),
+        }
+        .to_circuit()
+    }
+}
+
+impl DistillationCircuitBuilder {
+    /// Use the protocol of a triorthogonal matrix, whose first `outputs` rows are
+    /// the output qubits
+    pub fn triorthogonal(&mut self, matrix: Vec<Vec<bool>>, outputs: usize) -> &mut Self {
+        assert!(outputs <= matrix.len());
+        self.matrix = matrix;
+        self.outputs = outputs;
+        self
+    }
+    /// Add a Z error after the T gate of the given column
+    pub fn fault(&mut self, column: usize) -> &mut Self {
+        self.faults.push(column);
+        self
+    }
+
+    pub fn build(&self) -> Circuit {
+        let n = self.matrix.len();
+        let cols = self.matrix.first().map_or(0, |row| row.len());
+        let mut c = Circuit::new(n);
+        for q in 0..n {
+            c.push(Gate::new(InitAncilla, vec![q]));
+            c.push(Gate::new(HAD, vec![q]));
+        }
+
+        for j in 0..cols {
+            let support: Vec<usize> = (0..n).filter(|&q| self.matrix[q][j]).collect();
+            let Some((&t, rest)) = support.split_first() else {
+                continue;
+            };
+            for &q in rest {
+                c.push(Gate::new(CNOT, vec![q, t]));
+            }
+            c.push(Gate::new(Tdg, vec![t]));
+            if self.faults.contains(&j) {
+                c.push(Gate::new(Z, vec![t]));
+            }
+            for &q in rest.iter().rev() {
+                c.push(Gate::new(CNOT, vec![q, t]));
+            }
+        }
+
+        for q in self.outputs..n {
+            c.push(Gate::new(HAD, vec![q]));
+            c.push(Gate::new(PostSelect, vec![q]));
+        }
+        c
+    }
+}
+
+impl GroverCircuitBuilder {
+    pub fn marked(&mut self, marked: Vec<bool>) -> &mut Self {
+        self.marked = marked;
+        self
+    }
+    pub fn iterations(&mut self, iterations: usize) -> &mut Self {
+        self.iterations = Some(iterations);
+        self
+    }
+    pub fn method(&mut self, method: ToffoliMethod) -> &mut Self {
+        self.method = method;
+        self
+    }
+
+    pub fn build(&self) -> Circuit {
+        let n = self.marked.len();
+        assert!(n > 0, "Grover search needs at least one qubit");
+        let iterations = self.iterations.unwrap_or_else(|| {
+            (std::f64::consts::FRAC_PI_4 * (n as f64 / 2.0).exp2()).floor() as usize
+        });
+        // ancillas for LogicalAnd are used up, so each gate gets fresh ones
+        let per_gate = self.method.num_ancillas(n - 1);
+        let num_ancillas = match self.method {
+            ToffoliMethod::AncillaFree => per_gate,
+            ToffoliMethod::LogicalAnd => 2 * iterations * per_gate,
+        };
+        let mut c = Circuit::new(n + num_ancillas);
+        let mut next_anc = n;
+        let mut mcz = |c: &mut Circuit| {
+            let ancillas: Vec<usize> = (next_anc..next_anc + per_gate).collect();
+            let controls: Vec<usize> = (0..n - 1).collect();
+            push_mcz(c, &controls, n - 1, &ancillas, self.method);
+            if self.method == ToffoliMethod::LogicalAnd {
+                next_anc += per_gate;
+            }
+        };
+        let layer = |c: &mut Circuit, t: GType, qs: &mut dyn Iterator<Item = usize>| {
+            for q in qs {
+                c.push(Gate::new(t, vec![q]));
+            }
+        };
+
+        layer(&mut c, HAD, &mut (0..n));
+        for _ in 0..iterations {
+            let unmarked = || (0..n).filter(|&q| !self.marked[q]);
+            layer(&mut c, NOT, &mut unmarked());
+            mcz(&mut c);
+            layer(&mut c, NOT, &mut unmarked());
+
+            layer(&mut c, HAD, &mut (0..n));
+            layer(&mut c, NOT, &mut (0..n));
+            mcz(&mut c);
+            layer(&mut c, NOT, &mut (0..n));
+            layer(&mut c, HAD, &mut (0..n));
+        }
+        c
+    }
+}
+
+impl RandomCliffordBuilder {
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+    pub fn qubits(&mut self, qubits: usize) -> &mut Self {
+        self.qubits = qubits;
+        self
+    }
+
+    /// The qubits with a Hadamard and the permutation of `W`, from the quantum
+    /// Mallows distribution
+    fn sample_mallows(&mut self) -> (Vec<bool>, Vec<usize>) {
+        let n = self.qubits;
+        let mut had = Vec::with_capacity(n);
+        let mut perm = Vec::with_capacity(n);
+        let mut rest: Vec<usize> = (0..n).collect();
+        while !rest.is_empty() {
+            let m = rest.len();
+            let eps = 4f64.powi(-(m as i32));
+            let r: f64 = self.rng.gen();
+            let index = (-(r + (1.0 - r) * eps).log2().ceil()) as usize;
+            let index = index.min(2 * m - 1);
+            had.push(index < m);
+            let k = if index < m { index } else { 2 * m - index - 1 };
+            perm.push(rest.remove(k));
+        }
+        (had, perm)
+    }
+
+    /// A random Hadamard-free Clifford, which maps X Paulis to X Paulis
+    ///
+    /// This is a layer of S and CZ gates between two layers of Hadamards, followed
+    /// by CNOTs which map each `X_i` to `X_i` times some of the `X_j` with `j < i`.
+    fn hadamard_free(&mut self, c: &mut Circuit) {
+        let n = self.qubits;
+        for q in 0..n {
+            c.push(Gate::new(HAD, vec![q]));
+        }
+        for i in 0..n {
+            if self.rng.gen_bool(0.5) {
+                c.push(Gate::new(S, vec![i]));
+            }
+            for j in 0..i {
+                if self.rng.gen_bool(0.5) {
+                    c.push(Gate::new(CZ, vec![j, i]));
+                }
+            }
+        }
+        for q in 0..n {
+            c.push(Gate::new(HAD, vec![q]));
+        }
+        // the X_j are only changed by CNOTs with later controls
+        for i in 0..n {
+            for j in 0..i {
+                if self.rng.gen_bool(0.5) {
+                    c.push(Gate::new(CNOT, vec![i, j]));
+                }
+            }
+        }
+    }
+
+    pub fn build(&mut self) -> (Circuit, Tableau) {
+        let n = self.qubits;
+        let mut c = Circuit::new(n);
+        let (had, perm) = self.sample_mallows();
+        self.hadamard_free(&mut c);
+
+        for (q, &h) in had.iter().enumerate() {
+            if h {
+                c.push(Gate::new(HAD, vec![q]));
+            }
+        }
+        // move the state of each qubit i to perm[i], where pos[i] is its current
+        // position and at[q] the qubit whose state is at q
+        let mut pos: Vec<usize> = (0..n).collect();
+        let mut at: Vec<usize> = (0..n).collect();
+        for (i, &b) in perm.iter().enumerate() {
+            let a = pos[i];
+            if a != b {
+                c.push(Gate::new(SWAP, vec![a, b]));
+                let j = at[b];
+                at.swap(a, b);
+                pos[i] = b;
+                pos[j] = a;
+            }
+        }
+
+        self.hadamard_free(&mut c);
+        for q in 0..n {
+            if self.rng.gen_bool(0.5) {
+                c.push(Gate::new(NOT, vec![q]));
+            }
+            if self.rng.gen_bool(0.5) {
+                c.push(Gate::new(Z, vec![q]));
+            }
+        }
+
+        let t = Tableau::from_circuit(&c).expect("Random Clifford circuit is not Clifford");
+        (c, t)
+    }
+}
+
 impl RandomPauliGadgetCircuitBuilder {
     pub fn seed(&mut self, seed: u64) -> &mut Self {
         self.rng = StdRng::seed_from_u64(seed);
@@ -475,6 +1229,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn random_exact_t_count() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(6)
+                .depth(100)
+                .t_count(17)
+                .build();
+            assert_eq!(c.num_gates(), 100);
+            assert_eq!(c.num_gates_of_type(T), 17);
+            assert_eq!(c.num_gates_of_type(CZ), 0);
+            assert_ne!(c.num_gates_of_type(CNOT), 0);
+
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(6)
+                .depth(50)
+                .p_t(0.5)
+                .p_cz(1.0)
+                .t_count(3)
+                .build();
+            assert_eq!(c.num_gates_of_type(T), 3);
+            assert_eq!(c.num_gates_of_type(CZ), 47);
+        }
+    }
+
     #[test]
     fn random_hidden_shift() {
         // this could fail with some (small) probablity, so try some fixed seeds
@@ -494,6 +1275,209 @@ mod tests {
         }
     }
 
+    #[test]
+    fn hidden_shift_output() {
+        for seed in 0..3 {
+            let (c, shift) = Circuit::random_hidden_shift()
+                .seed(seed)
+                .qubits(6)
+                .clifford_depth(10)
+                .n_ccz(2)
+                .build();
+            let x: Vec<bool> = shift.iter().map(|&b| b == 1).collect();
+            let a = c.amplitude(&x).complex_value();
+            assert!((a.norm() - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn random_iqp() {
+        let c = Circuit::random_iqp()
+            .seed(1337)
+            .qubits(8)
+            .density(0.3)
+            .build();
+        assert_eq!(c.num_gates_of_type(HAD), 16);
+        assert_ne!(c.num_gates_of_type(CZ), 0);
+        assert_eq!(c.num_gates_of_type(CNOT), 2 * c.num_gates_of_type(Tdg));
+
+        let c = Circuit::random_iqp().qubits(5).density(1.0).build();
+        assert_eq!(c.num_gates_of_type(CZ), 10);
+        assert_eq!(c.num_gates_of_type(T), 25);
+    }
+
+    #[test]
+    fn qaoa() {
+        let (c, edges) = Circuit::qaoa().seed(1337).qubits(6).layers(2).build();
+        assert!(!edges.is_empty());
+        assert_eq!(c.num_gates_of_type(CNOT), 4 * edges.len());
+        assert_eq!(c.num_gates_of_type(XPhase), 12);
+        assert_eq!(c.param_names(), ["beta0", "beta1", "gamma0", "gamma1"]);
+
+        let angles = vec![
+            (Phase::from((1, 3)), Phase::from((1, 5))),
+            (Phase::from((1, 7)), Phase::from((1, 9))),
+        ];
+        let (c1, _) = Circuit::qaoa()
+            .edges(edges)
+            .angles(angles)
+            .qubits(6)
+            .build();
+        let c = c.substitute(|p| match p {
+            "gamma0" => Phase::from((1, 3)),
+            "beta0" => Phase::from((1, 5)),
+            "gamma1" => Phase::from((1, 7)),
+            _ => Phase::from((1, 9)),
+        });
+        assert_eq!(c, c1);
+    }
+
+    #[test]
+    fn trotter() {
+        use crate::tensor::*;
+        let pi = std::f64::consts::PI;
+        let c = Circuit::trotter()
+            .term("ZZ", pi / 8.0)
+            .term("XI", -pi / 8.0)
+            .build();
+        let c1 = Circuit::from_qasm(
+            "qreg q[2]; cx q[0], q[1]; t q[1]; cx q[0], q[1]; h q[0]; tdg q[0]; h q[0];",
+        )
+        .unwrap();
+        assert!(Tensor4::scalar_compare(&c, &c1));
+
+        let mut b = Circuit::trotter();
+        b.term("ZZI", 1.0).term("IYY", 0.5).time(2.0).steps(5);
+        assert_eq!(b.rotations().len(), 10);
+        assert_eq!(b.build().num_gates_of_type(ZPhase), 10);
+        // the middle rotations of each step merge, as do the ends of consecutive steps
+        b.second_order(true);
+        assert_eq!(b.rotations().len(), 11);
+        let r = b.rotations();
+        assert_eq!(r[0].phase * 2, r[2].phase);
+    }
+
+    #[test]
+    fn distillation() {
+        use crate::graph::GraphLike;
+        use crate::tensor::*;
+        use crate::vec_graph::Graph;
+        // the state T|+>, or Z T|+> with a logical error
+        let t_state = |logical_error: bool| {
+            let mut c = Circuit::new(1);
+            c.push(Gate::new(InitAncilla, vec![0]));
+            c.push(Gate::new(HAD, vec![0]));
+            c.push(Gate::new(T, vec![0]));
+            if logical_error {
+                c.push(Gate::new(Z, vec![0]));
+            }
+            c.to_graph::<Graph>()
+        };
+
+        let g: Graph = Circuit::distillation().build().to_graph();
+        assert_eq!((g.inputs().len(), g.outputs().len()), (0, 1));
+        assert!(Tensor4::scalar_compare(&g, &t_state(false)));
+
+        // one or two faulty T states are always detected
+        for faults in [vec![0], vec![6], vec![14], vec![2, 5]] {
+            let mut b = Circuit::distillation();
+            for j in faults {
+                b.fault(j);
+            }
+            let g: Graph = b.build().to_graph();
+            assert!(g.to_tensor4().iter().all(|s| s.is_zero()));
+        }
+
+        // columns 1, 2 and 3 sum to zero, so these faults give a logical error
+        let g: Graph = Circuit::distillation()
+            .fault(0)
+            .fault(1)
+            .fault(2)
+            .build()
+            .to_graph();
+        assert!(Tensor4::scalar_compare(&g, &t_state(true)));
+    }
+
+    #[test]
+    fn qft() {
+        use crate::tensor::*;
+        let c =
+            Circuit::from_qasm("qreg q[2]; h q[0]; cp(pi/2) q[1], q[0]; h q[1]; swap q[0], q[1];")
+                .unwrap();
+        assert!(Tensor4::compare(&Circuit::qft(2), &c));
+
+        // the QFT has order 4
+        let t = Circuit::qft(3).repeat(4).to_tensorf();
+        let id = Circuit::new(3).to_tensorf();
+        assert!(t.iter().zip(id.iter()).all(|(x, y)| (x - y).norm() < 1e-6));
+    }
+
+    #[test]
+    fn adders() {
+        use crate::graph::GraphLike;
+        use crate::tensor::*;
+        use crate::vec_graph::Graph;
+        let n = 3;
+        let c = Circuit::ripple_carry_adder(n);
+        assert_eq!(c.num_qubits(), 2 * n + 1);
+        assert_eq!(c.num_gates_of_type(TOFF), 2 * n);
+
+        // both adders are the same permutation, once the carry is plugged
+        let mut c1 = Circuit::new(2 * n + 1);
+        c1.push(Gate::new(InitAncilla, vec![2 * n]));
+        c1 += &c;
+        c1.push(Gate::new(PostSelect, vec![2 * n]));
+        let g: Graph = c1.to_graph();
+        assert_eq!(g.inputs().len(), 2 * n);
+        let t0 = g.to_tensorf();
+        let t1 = Circuit::qft_adder(n).to_tensorf();
+        assert!(t0.iter().zip(t1.iter()).all(|(x, y)| (x - y).norm() < 1e-6));
+    }
+
+    #[test]
+    fn grover() {
+        let marked = vec![true, false, true];
+        let c = Circuit::grover().marked(marked.clone()).build();
+        assert_eq!(c.num_qubits(), 3);
+        // two iterations, with Hadamards only in the diffusion operator
+        assert_eq!(c.num_gates_of_type(HAD), 3 + 2 * 6);
+        let a = c.amplitude(&marked).complex_value();
+        assert!(a.norm_sqr() > 0.9);
+
+        let c = Circuit::grover()
+            .marked(vec![false; 5])
+            .iterations(2)
+            .method(ToffoliMethod::LogicalAnd)
+            .build();
+        assert_eq!(c.num_qubits(), 5 + 4 * 3);
+        assert_eq!(c.num_gates_of_type(PostSelect), 4 * 3);
+    }
+
+    #[test]
+    fn random_clifford() {
+        // each of the 24 single-qubit Cliffords should come up about 100 times
+        let mut b = Circuit::random_clifford();
+        b.seed(1337).qubits(1);
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..2400 {
+            *counts.entry(b.build().1).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 24);
+        assert!(counts.values().all(|&k| k > 50 && k < 150));
+
+        for seed in 0..5 {
+            let (c, t) = Circuit::random_clifford().seed(seed).qubits(5).build();
+            assert_eq!(t.num_qubits(), 5);
+            for (q0, x) in t.x.iter().enumerate() {
+                for (q1, z) in t.z.iter().enumerate() {
+                    assert_eq!(x.commutes(z), q0 != q1);
+                    assert!(x.commutes(&t.x[q1]) && z.commutes(&t.z[q0]));
+                }
+            }
+            assert_eq!(c.num_gates_of_type(T), 0);
+        }
+    }
+
     #[test]
     fn random_pauli_gadget() {
         for &seed in &[1337, 800, 40104] {
@@ -0,0 +1,530 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dense tensor contraction on the GPU, enabled by the `gpu` feature
+//!
+//! This runs the same contraction as [ToTensor::to_tensorf], but once the tensor
+//! becomes dense, every step is done by a compute shader with [wgpu], which can
+//! use Vulkan, Metal, DirectX 12 or OpenGL. This is meant for checking diagrams
+//! with 20 or more qubits, where contracting on the CPU is very slow.
+//!
+//! Entries are stored on the GPU as pairs of `f32`, since most GPUs don't support
+//! `f64`, so results are only accurate to about 1e-6 relative to the largest
+//! entry. Compare them with a tolerance.
+//!
+//! If there is no suitable GPU, or a tensor does not fit into a single buffer on
+//! it, the contraction carries on on the CPU instead. Clifford diagrams and
+//! diagrams with H-boxes are always done on the CPU, see [ToTensor].
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::gpu_tensor::ToTensorGpu;
+//! # use quizx::tensor::ToTensor;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::random().seed(1).qubits(4).depth(20).p_t(0.3).build();
+//! let g: Graph = c.to_graph();
+//! let t = g.to_tensor_gpu();
+//! assert!(t.iter().zip(g.to_tensorf().iter()).all(|(x, y)| (x - y).norm() < 1e-5));
+//! ```
+
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use crate::tensor::*;
+use num::{Complex, One, Zero};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Params {
+    n: u32,
+    i: u32,
+    j: u32,
+    len: u32,
+    c: vec2<f32>,
+    pad: vec2<f32>,
+    perm: array<vec4<u32>, 8>,
+}
+
+@group(0) @binding(0) var<storage, read> src: array<vec2<f32>>;
+@group(0) @binding(1) var<storage, read_write> dst: array<vec2<f32>>;
+@group(0) @binding(2) var<uniform> p: Params;
+
+fn index(gid: vec3<u32>, nwg: vec3<u32>) -> u32 {
+    return gid.x + gid.y * nwg.x * 256u;
+}
+
+fn cmul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+// the bit of entry k for the given axis, where axis 0 is the most significant
+fn bit(k: u32, axis: u32) -> u32 {
+    return (k >> (p.n - 1u - axis)) & 1u;
+}
+
+@compute @workgroup_size(256)
+fn push_spider(@builtin(global_invocation_id) gid: vec3<u32>,
+               @builtin(num_workgroups) nwg: vec3<u32>) {
+    let k = index(gid, nwg);
+    if (k >= p.len) { return; }
+    let half = p.len / 2u;
+    if (k < half) {
+        dst[k] = src[k];
+    } else {
+        dst[k] = cmul(src[k - half], p.c);
+    }
+}
+
+@compute @workgroup_size(256)
+fn delta_at(@builtin(global_invocation_id) gid: vec3<u32>,
+            @builtin(num_workgroups) nwg: vec3<u32>) {
+    let k = index(gid, nwg);
+    if (k >= p.len) { return; }
+    if (bit(k, p.i) == bit(k, p.j)) {
+        dst[k] = src[k];
+    } else {
+        dst[k] = vec2<f32>(0.0, 0.0);
+    }
+}
+
+@compute @workgroup_size(256)
+fn hadamard_edge_at(@builtin(global_invocation_id) gid: vec3<u32>,
+                    @builtin(num_workgroups) nwg: vec3<u32>) {
+    let k = index(gid, nwg);
+    if (k >= p.len) { return; }
+    if (bit(k, p.i) == 1u && bit(k, p.j) == 1u) {
+        dst[k] = -p.c.x * src[k];
+    } else {
+        dst[k] = p.c.x * src[k];
+    }
+}
+
+@compute @workgroup_size(256)
+fn sum_axis(@builtin(global_invocation_id) gid: vec3<u32>,
+            @builtin(num_workgroups) nwg: vec3<u32>) {
+    let k = index(gid, nwg);
+    if (k >= p.len) { return; }
+    let b = p.n - 1u - p.i;
+    let low = k & ((1u << b) - 1u);
+    let k0 = ((k >> b) << (b + 1u)) | low;
+    dst[k] = src[k0] + src[k0 | (1u << b)];
+}
+
+@compute @workgroup_size(256)
+fn permute(@builtin(global_invocation_id) gid: vec3<u32>,
+           @builtin(num_workgroups) nwg: vec3<u32>) {
+    let k = index(gid, nwg);
+    if (k >= p.len) { return; }
+    var s = 0u;
+    for (var a = 0u; a < p.n; a++) {
+        let b = p.perm[a / 4u][a % 4u];
+        s = s | (bit(k, a) << (p.n - 1u - b));
+    }
+    dst[k] = src[s];
+}
+"#;
+
+/// Invocations per workgroup, as in the shader
+const WORKGROUP_SIZE: usize = 256;
+
+/// Maximum number of indices which can be permuted, as in the shader
+const MAX_NDIM: usize = 32;
+
+/// Number of bytes per tensor entry
+const ENTRY_SIZE: u64 = 8;
+
+/// A device and the compiled shaders
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    layout: wgpu::BindGroupLayout,
+    push_spider: wgpu::ComputePipeline,
+    delta_at: wgpu::ComputePipeline,
+    hadamard_edge_at: wgpu::ComputePipeline,
+    sum_axis: wgpu::ComputePipeline,
+    permute: wgpu::ComputePipeline,
+    max_ndim: usize,
+}
+
+impl Gpu {
+    fn new() -> Option<Gpu> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))?;
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return None;
+        }
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("quizx"),
+                required_features: wgpu::Features::empty(),
+                required_limits: limits.clone(),
+            },
+            None,
+        ))
+        .ok()?;
+
+        // the largest tensor which fits in a buffer, and can be indexed in the
+        // shader with a workgroup grid of at most 65535 x 65535
+        let max_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        let max_len = (max_bytes / ENTRY_SIZE).min(1 << 31);
+        let max_ndim = (63 - max_len.max(1).leading_zeros()) as usize;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("quizx tensor"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+        });
+        let buffer = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                buffer(0, wgpu::BufferBindingType::Storage { read_only: true }),
+                buffer(1, wgpu::BufferBindingType::Storage { read_only: false }),
+                buffer(2, wgpu::BufferBindingType::Uniform),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point,
+            })
+        };
+
+        Some(Gpu {
+            push_spider: pipeline("push_spider"),
+            delta_at: pipeline("delta_at"),
+            hadamard_edge_at: pipeline("hadamard_edge_at"),
+            sum_axis: pipeline("sum_axis"),
+            permute: pipeline("permute"),
+            device,
+            queue,
+            layout,
+            max_ndim,
+        })
+    }
+
+    /// The GPU, if there is a suitable one. This is only set up once.
+    fn get() -> Option<&'static Gpu> {
+        static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+        GPU.get_or_init(Gpu::new).as_ref()
+    }
+
+    /// Run one of the shaders on `t`, returning a new buffer with `len` entries
+    ///
+    /// The other arguments are passed to the shader as `Params`.
+    fn run(
+        &self,
+        pipeline: &wgpu::ComputePipeline,
+        t: &GpuTensor,
+        len: usize,
+        ij: (usize, usize),
+        c: Complex<f32>,
+        perm: &[usize],
+    ) -> wgpu::Buffer {
+        let mut params = [0u32; 8 + 4 * 8];
+        params[0] = t.ndim as u32;
+        params[1] = ij.0 as u32;
+        params[2] = ij.1 as u32;
+        params[3] = len as u32;
+        params[4] = c.re.to_bits();
+        params[5] = c.im.to_bits();
+        for (i, &a) in perm.iter().enumerate() {
+            params[8 + i] = a as u32;
+        }
+        let params = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let dst = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: len as u64 * ENTRY_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: t.buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: dst.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        });
+
+        let groups = len.div_ceil(WORKGROUP_SIZE);
+        let x = groups.min(65535);
+        let y = groups.div_ceil(x);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(x as u32, y as u32, 1);
+        }
+        self.queue.submit(Some(encoder.finish()));
+        dst
+    }
+
+    fn upload(&self, t: &Tensorf) -> GpuTensor {
+        let data: Vec<[f32; 2]> = t.iter().map(|c| [c.re as f32, c.im as f32]).collect();
+        let buf = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(&data),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            });
+        GpuTensor {
+            ndim: t.ndim(),
+            buf,
+        }
+    }
+
+    fn download(&self, t: &GpuTensor) -> Tensorf {
+        let size = t.buf.size();
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(&t.buf, 0, &staging, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |r| {
+            r.expect("Could not read GPU buffer")
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        let data: Vec<Complex<f64>> =
+            bytemuck::cast_slice::<u8, [f32; 2]>(&slice.get_mapped_range())
+                .iter()
+                .map(|&[re, im]| Complex::new(re as f64, im as f64))
+                .collect();
+        staging.unmap();
+        Tensorf::from_shape_vec(vec![2; t.ndim], data).expect("Wrong size of GPU buffer")
+    }
+}
+
+/// A dense tensor on the GPU, stored like a [Tensor] in standard layout
+struct GpuTensor {
+    ndim: usize,
+    buf: wgpu::Buffer,
+}
+
+/// A tensor in the middle of contraction, which moves to the GPU once it is
+/// dense, and back to the CPU if it gets too big
+enum GpuPartial {
+    Cpu(Partial<Complex<f64>>),
+    Gpu(&'static Gpu, GpuTensor),
+}
+
+impl Contraction for GpuPartial {
+    fn push_spider(&mut self, p: Phase, densify: bool) {
+        match self {
+            GpuPartial::Cpu(a) => {
+                a.push_spider(p, densify);
+                let Some(gpu) = Gpu::get() else { return };
+                if let Partial::Dense(t) = a {
+                    // leave room for the next spider
+                    if t.ndim() < gpu.max_ndim.min(MAX_NDIM) {
+                        let t = gpu.upload(t);
+                        *self = GpuPartial::Gpu(gpu, t);
+                    }
+                }
+            }
+            GpuPartial::Gpu(gpu, t) => {
+                if t.ndim + 1 > gpu.max_ndim.min(MAX_NDIM) {
+                    let a = gpu.download(t);
+                    *self = GpuPartial::Cpu(Partial::Dense(a));
+                    self.push_spider(p, densify);
+                    return;
+                }
+                let c = Complex::<f64>::from_phase(p);
+                let c = Complex::new(c.re as f32, c.im as f32);
+                let len = 2usize << t.ndim;
+                t.buf = gpu.run(&gpu.push_spider, t, len, (0, 0), c, &[]);
+                t.ndim += 1;
+            }
+        }
+    }
+
+    fn delta_at(&mut self, i: usize, j: usize) {
+        match self {
+            GpuPartial::Cpu(a) => a.delta_at(i, j),
+            GpuPartial::Gpu(gpu, t) => {
+                let (len, c) = (1 << t.ndim, Complex::zero());
+                t.buf = gpu.run(&gpu.delta_at, t, len, (i, j), c, &[]);
+            }
+        }
+    }
+
+    fn hadamard_edge_at(&mut self, i: usize, j: usize) {
+        match self {
+            GpuPartial::Cpu(a) => a.hadamard_edge_at(i, j),
+            GpuPartial::Gpu(gpu, t) => {
+                let (len, c) = (
+                    1 << t.ndim,
+                    Complex::new(std::f32::consts::FRAC_1_SQRT_2, 0.0),
+                );
+                t.buf = gpu.run(&gpu.hadamard_edge_at, t, len, (i, j), c, &[]);
+            }
+        }
+    }
+
+    fn sum_axis(&mut self, i: usize) {
+        match self {
+            GpuPartial::Cpu(a) => a.sum_axis(i),
+            GpuPartial::Gpu(gpu, t) => {
+                let (len, c) = (1 << (t.ndim - 1), Complex::zero());
+                t.buf = gpu.run(&gpu.sum_axis, t, len, (i, 0), c, &[]);
+                t.ndim -= 1;
+            }
+        }
+    }
+
+    fn permute(&mut self, perm: &[usize]) {
+        match self {
+            GpuPartial::Cpu(a) => a.permute(perm),
+            GpuPartial::Gpu(gpu, t) => {
+                let (len, c) = (1 << t.ndim, Complex::zero());
+                t.buf = gpu.run(&gpu.permute, t, len, (0, 0), c, perm);
+            }
+        }
+    }
+}
+
+impl GpuPartial {
+    fn into_dense(self) -> Tensorf {
+        match self {
+            GpuPartial::Cpu(a) => a.into_dense(),
+            GpuPartial::Gpu(gpu, t) => gpu.download(&t),
+        }
+    }
+}
+
+/// Returns true if there is a GPU which [ToTensorGpu] can use
+pub fn gpu_available() -> bool {
+    Gpu::get().is_some()
+}
+
+/// Contract diagrams on the GPU, see the [module documentation](self)
+pub trait ToTensorGpu {
+    /// Like [ToTensor::to_tensorf], but using the GPU for dense contraction
+    fn to_tensor_gpu(&self) -> Tensorf;
+}
+
+impl<G: GraphLike + Clone> ToTensorGpu for G {
+    fn to_tensor_gpu(&self) -> Tensorf {
+        let mut g = self.clone();
+        g.x_to_z();
+        if Gpu::get().is_none()
+            || g.vertices().all(|v| g.phase(v).is_clifford())
+            || g.vertices().any(|v| g.vertex_type(v) == VType::H)
+        {
+            return self.to_tensorf();
+        }
+
+        let mut a = GpuPartial::Cpu(Partial::Sparse(SparseTensor::scalar(Complex::one())));
+        contract_into(&g, &mut a, true);
+        a.into_dense() * Complex::<f64>::from_scalar(g.scalar())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::vec_graph::Graph;
+
+    fn close(t0: &Tensorf, t1: &Tensorf, eps: f64) -> bool {
+        t0.iter().zip(t1.iter()).all(|(x, y)| (x - y).norm() < eps)
+    }
+
+    #[test]
+    fn gpu_tensors() {
+        // big enough that the tensor becomes dense and moves to the GPU
+        for seed in 0..3 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(8)
+                .depth(60)
+                .p_t(0.2)
+                .with_cliffords()
+                .build();
+            let g: Graph = c.to_graph();
+            let t = g.to_tensor_gpu();
+            assert_eq!(t.shape(), &[2; 16]);
+            assert!(close(&t, &g.to_tensorf(), 1e-4));
+        }
+
+        // Clifford diagrams and H-boxes go to the CPU
+        let c = Circuit::random().seed(1).qubits(3).depth(20).build();
+        let g: Graph = c.to_graph();
+        assert!(close(&g.to_tensor_gpu(), &g.to_tensorf(), 1e-10));
+        let mut g = Graph::new();
+        let h = g.add_vertex_with_phase(VType::H, (1, 2));
+        let b = g.add_vertex(VType::B);
+        g.add_edge(h, b);
+        g.set_outputs(vec![b]);
+        assert!(close(&g.to_tensor_gpu(), &g.to_tensorf(), 1e-10));
+    }
+}
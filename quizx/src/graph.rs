@@ -26,7 +26,9 @@ pub type V = usize;
 /// The type of a vertex in a graph.
 ///
 /// The serialized names may differ.
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
 pub enum VType {
     B, // Boundary
     #[default]
@@ -42,6 +44,9 @@ pub enum VType {
     ZBox,
 }
 
+/// The number of variants of [VType]
+pub const NUM_VTYPES: usize = 7;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct VData {
     pub ty: VType,
@@ -50,7 +55,187 @@ pub struct VData {
     pub row: i32,
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+impl VData {
+    /// Returns true if this is a Z or X spider with a non-Clifford phase
+    pub fn is_t_like(&self) -> bool {
+        (self.ty == VType::Z || self.ty == VType::X) && !self.phase.is_clifford()
+    }
+}
+
+/// Vertex statistics, maintained incrementally by graph backends
+///
+/// This stores the T-count, the number of vertices of each type, and a histogram
+/// of vertex degrees. Backends call the `pub(crate)` update methods whenever vertex
+/// data or adjacency changes, so the values are always consistent with the graph
+/// and reading them never requires a scan.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GraphStats {
+    tcount: usize,
+    type_counts: [usize; NUM_VTYPES],
+    degree_hist: Vec<usize>,
+}
+
+impl GraphStats {
+    /// Number of Z or X spiders with non-Clifford phase
+    pub fn tcount(&self) -> usize {
+        self.tcount
+    }
+
+    /// Number of vertices of the given type
+    pub fn num_vertices_of_type(&self, ty: VType) -> usize {
+        self.type_counts[ty as usize]
+    }
+
+    /// Number of vertices with the given degree
+    pub fn num_vertices_of_degree(&self, d: usize) -> usize {
+        self.degree_hist.get(d).copied().unwrap_or(0)
+    }
+
+    /// Histogram of vertex degrees
+    ///
+    /// The `d`-th entry is the number of vertices with degree `d`. The last
+    /// entry, if there is one, is always non-zero.
+    pub fn degree_histogram(&self) -> &[usize] {
+        &self.degree_hist
+    }
+
+    /// Largest degree of a vertex, or 0 for the empty graph
+    pub fn max_degree(&self) -> usize {
+        self.degree_hist.len().saturating_sub(1)
+    }
+
+    fn inc_degree(&mut self, d: usize) {
+        if self.degree_hist.len() <= d {
+            self.degree_hist.resize(d + 1, 0);
+        }
+        self.degree_hist[d] += 1;
+    }
+
+    fn dec_degree(&mut self, d: usize) {
+        self.degree_hist[d] -= 1;
+        while self.degree_hist.last() == Some(&0) {
+            self.degree_hist.pop();
+        }
+    }
+
+    /// Record a new vertex of degree 0
+    pub(crate) fn add_vertex(&mut self, d: &VData) {
+        self.type_counts[d.ty as usize] += 1;
+        if d.is_t_like() {
+            self.tcount += 1;
+        }
+        self.inc_degree(0);
+    }
+
+    /// Record the removal of a vertex which had the given degree
+    ///
+    /// This does not update the degrees of the neighbours.
+    pub(crate) fn remove_vertex(&mut self, d: &VData, degree: usize) {
+        self.type_counts[d.ty as usize] -= 1;
+        if d.is_t_like() {
+            self.tcount -= 1;
+        }
+        self.dec_degree(degree);
+    }
+
+    /// Record a change in the type or phase of a vertex
+    pub(crate) fn update_vertex(&mut self, old: &VData, new: &VData) {
+        self.type_counts[old.ty as usize] -= 1;
+        self.type_counts[new.ty as usize] += 1;
+        if old.is_t_like() {
+            self.tcount -= 1;
+        }
+        if new.is_t_like() {
+            self.tcount += 1;
+        }
+    }
+
+    /// Record a change in the degree of a vertex
+    pub(crate) fn change_degree(&mut self, old: usize, new: usize) {
+        if old != new {
+            self.inc_degree(new);
+            self.dec_degree(old);
+        }
+    }
+}
+
+/// Qubit indices of the input and output boundaries of a graph
+///
+/// These are stored separately from the drawing coordinates in [VData], so moving
+/// a boundary vertex (e.g. with [GraphLike::set_qubit]) does not change which
+/// qubit it carries. Backends drop the index of a vertex when it is removed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BoundaryQubits {
+    qubit: FxHashMap<V, usize>,
+    inputs: FxHashMap<usize, V>,
+    outputs: FxHashMap<usize, V>,
+}
+
+impl BoundaryQubits {
+    /// The qubit carried by the boundary `v`, if it has one
+    pub fn qubit(&self, v: V) -> Option<usize> {
+        self.qubit.get(&v).copied()
+    }
+
+    /// The input carrying qubit `q`, if there is one
+    pub fn input(&self, q: usize) -> Option<V> {
+        self.inputs.get(&q).copied()
+    }
+
+    /// The output carrying qubit `q`, if there is one
+    pub fn output(&self, q: usize) -> Option<V> {
+        self.outputs.get(&q).copied()
+    }
+
+    /// Record that the input `v` carries qubit `q`
+    ///
+    /// Any other input with the same qubit, and any previous index of `v`, are dropped.
+    pub fn set_input(&mut self, v: V, q: usize) {
+        self.remove(v);
+        if let Some(old) = self.inputs.insert(q, v) {
+            self.qubit.remove(&old);
+        }
+        self.qubit.insert(v, q);
+    }
+
+    /// Record that the output `v` carries qubit `q`
+    ///
+    /// Any other output with the same qubit, and any previous index of `v`, are dropped.
+    pub fn set_output(&mut self, v: V, q: usize) {
+        self.remove(v);
+        if let Some(old) = self.outputs.insert(q, v) {
+            self.qubit.remove(&old);
+        }
+        self.qubit.insert(v, q);
+    }
+
+    /// Forget the qubit index of `v`, if it has one
+    pub fn remove(&mut self, v: V) {
+        if let Some(q) = self.qubit.remove(&v) {
+            if self.inputs.get(&q) == Some(&v) {
+                self.inputs.remove(&q);
+            } else {
+                self.outputs.remove(&q);
+            }
+        }
+    }
+
+    /// Forget all qubit indices
+    pub fn clear(&mut self) {
+        self.qubit.clear();
+        self.inputs.clear();
+        self.outputs.clear();
+    }
+
+    /// Exchange the roles of inputs and outputs
+    pub fn swap_sides(&mut self) {
+        std::mem::swap(&mut self.inputs, &mut self.outputs);
+    }
+}
+
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
 pub enum EType {
     /// Normal edge.
     #[default]
@@ -173,7 +358,8 @@ pub enum VIter<'a> {
         usize,
         std::iter::Enumerate<std::slice::Iter<'a, Option<VData>>>,
     ),
-    Hash(std::collections::hash_map::Keys<'a, V, VData>),
+    Hash(indexmap::map::Keys<'a, V, VData>),
+    Persistent(im::ordmap::Keys<'a, V, VData>),
 }
 
 impl<'a> Iterator for VIter<'a> {
@@ -195,6 +381,7 @@ impl<'a> Iterator for VIter<'a> {
                 }
             }
             VIter::Hash(inner) => inner.next().copied(),
+            VIter::Persistent(inner) => inner.next().copied(),
         }
     }
 
@@ -202,6 +389,7 @@ impl<'a> Iterator for VIter<'a> {
         let len = match self {
             VIter::Vec(sz, _) => *sz,
             VIter::Hash(inner) => inner.len(),
+            VIter::Persistent(inner) => inner.len(),
         };
         (len, Some(len))
     }
@@ -218,8 +406,18 @@ pub enum EIter<'a> {
     ),
     Hash(
         usize,
-        std::collections::hash_map::Iter<'a, V, rustc_hash::FxHashMap<V, EType>>,
-        Option<(V, std::collections::hash_map::Iter<'a, V, EType>)>,
+        indexmap::map::Iter<'a, V, crate::hash_graph::VTab<EType>>,
+        Option<(V, indexmap::map::Iter<'a, V, EType>)>,
+    ),
+    Persistent(
+        usize,
+        im::ordmap::Iter<'a, V, std::sync::Arc<Vec<(V, EType)>>>,
+        Option<(V, std::slice::Iter<'a, (V, EType)>)>,
+    ),
+    Compact(
+        usize,
+        std::iter::Enumerate<std::slice::Iter<'a, crate::compact_graph::Nhd>>,
+        Option<(V, std::slice::Iter<'a, (V, EType)>)>,
     ),
 }
 
@@ -289,6 +487,31 @@ impl<'a> Iterator for EIter<'a> {
                     None => None,
                 },
             },
+            EIter::Persistent(_, outer, inner) => loop {
+                if let Some((v, iter)) = inner {
+                    // skip over edges with target id < source id to avoid double-counting
+                    if let Some(&(v1, et)) = iter.find(|&&(v1, _)| *v <= v1) {
+                        return Some((*v, v1, et));
+                    }
+                }
+                match outer.next() {
+                    Some((v, tab)) => *inner = Some((*v, tab.iter())),
+                    None => return None,
+                }
+            },
+            EIter::Compact(_, outer, inner) => loop {
+                if let Some((v, iter)) = inner {
+                    // skip over edges with target id < source id to avoid double-counting
+                    if let Some(&(v1, et)) = iter.find(|&&(v1, _)| *v <= v1) {
+                        return Some((*v, v1, et));
+                    }
+                }
+                // deleted vertices have empty adjacency lists, so need no special treatment
+                match outer.next() {
+                    Some((v, tab)) => *inner = Some((v, tab.iter())),
+                    None => return None,
+                }
+            },
         }
     }
 
@@ -296,6 +519,8 @@ impl<'a> Iterator for EIter<'a> {
         let len = match self {
             EIter::Vec(sz, ..) => *sz,
             EIter::Hash(sz, ..) => *sz,
+            EIter::Persistent(sz, ..) => *sz,
+            EIter::Compact(sz, ..) => *sz,
         };
         (len, Some(len))
     }
@@ -305,7 +530,7 @@ impl<'a> ExactSizeIterator for EIter<'a> {}
 
 pub enum NeighborIter<'a> {
     Vec(std::slice::Iter<'a, (V, EType)>),
-    Hash(std::collections::hash_map::Keys<'a, V, EType>),
+    Hash(indexmap::map::Keys<'a, V, EType>),
 }
 
 impl<'a> Iterator for NeighborIter<'a> {
@@ -330,7 +555,7 @@ impl<'a> ExactSizeIterator for NeighborIter<'a> {}
 
 pub enum IncidentEdgeIter<'a> {
     Vec(std::slice::Iter<'a, (V, EType)>),
-    Hash(std::collections::hash_map::Iter<'a, V, EType>),
+    Hash(indexmap::map::Iter<'a, V, EType>),
 }
 
 impl<'a> Iterator for IncidentEdgeIter<'a> {
@@ -353,6 +578,235 @@ impl<'a> Iterator for IncidentEdgeIter<'a> {
 
 impl<'a> ExactSizeIterator for IncidentEdgeIter<'a> {}
 
+/// A violated invariant, as reported by [GraphLike::check]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CheckError {
+    /// A vertex is adjacent to a vertex which does not exist.
+    #[error("Vertex {s} is adjacent to missing vertex {t}")]
+    DanglingEdge { s: V, t: V },
+    /// The edge s-t is not recorded, or has a different type, at t.
+    #[error("Edge {s}-{t} is not symmetric")]
+    AsymmetricEdge { s: V, t: V },
+    /// A vertex appears twice in the same neighbourhood.
+    #[error("Parallel edges between {s} and {t}")]
+    ParallelEdge { s: V, t: V },
+    /// An input or output does not exist.
+    #[error("Boundary {0} is not a vertex")]
+    DanglingBoundary(V),
+    /// An input or output is not a boundary vertex.
+    #[error("Boundary {0} has type {1:?}")]
+    BoundaryNotB(V, VType),
+    /// A boundary vertex is connected to itself.
+    #[error("Boundary {0} has a self-loop")]
+    BoundarySelfLoop(V),
+    /// A phase is not in the range (-1, 1].
+    #[error("Vertex {0} has unnormalized phase {1}")]
+    UnnormalizedPhase(V, Phase),
+    /// The cached number of vertices or edges is wrong.
+    #[error("Expected {expected} {what}, found {found}")]
+    WrongCount {
+        what: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// The cached statistics do not match the graph.
+    #[error("Cached graph statistics are out of date")]
+    StaleStats,
+}
+
+/// Options for [GraphLike::to_dot_with_options]
+///
+/// The defaults reproduce the output of [GraphLike::to_dot].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotOptions {
+    /// Include non-zero phases in vertex labels
+    pub show_phases: bool,
+    /// Color vertices by type, otherwise all vertices are black
+    pub color_by_type: bool,
+    /// Draw Hadamard edges dashed as well as blue
+    pub dashed_h_edges: bool,
+    /// Vertices to draw filled and with a thick border
+    pub highlight: FxHashSet<V>,
+    /// Lay out left to right, with inputs in the first rank and outputs in the last
+    pub rank_boundaries: bool,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        DotOptions {
+            show_phases: true,
+            color_by_type: true,
+            dashed_h_edges: false,
+            highlight: FxHashSet::default(),
+            rank_boundaries: false,
+        }
+    }
+}
+
+/// Implement the required methods of [GraphLike], other than `new` and those
+/// which add or remove vertices, by delegating to the graph in the given field
+///
+/// This is for wrappers which follow what happens to a graph through the hooks
+/// [GraphLike::fuse_phases], [GraphLike::begin_rewrite], etc.
+macro_rules! delegate_graph_like {
+    ( $g:ident ) => {
+        fn vindex(&self) -> V {
+            self.$g.vindex()
+        }
+
+        fn num_vertices(&self) -> usize {
+            self.$g.num_vertices()
+        }
+
+        fn num_edges(&self) -> usize {
+            self.$g.num_edges()
+        }
+
+        fn vertices(&self) -> VIter<'_> {
+            self.$g.vertices()
+        }
+
+        fn edges(&self) -> EIter<'_> {
+            self.$g.edges()
+        }
+
+        fn inputs(&self) -> &Vec<V> {
+            self.$g.inputs()
+        }
+
+        fn inputs_mut(&mut self) -> &mut Vec<V> {
+            self.$g.inputs_mut()
+        }
+
+        fn set_inputs(&mut self, inputs: Vec<V>) {
+            self.$g.set_inputs(inputs)
+        }
+
+        fn outputs(&self) -> &Vec<V> {
+            self.$g.outputs()
+        }
+
+        fn outputs_mut(&mut self) -> &mut Vec<V> {
+            self.$g.outputs_mut()
+        }
+
+        fn set_outputs(&mut self, outputs: Vec<V>) {
+            self.$g.set_outputs(outputs)
+        }
+
+        fn add_edge_with_type(&mut self, s: V, t: V, ety: EType) {
+            self.$g.add_edge_with_type(s, t, ety)
+        }
+
+        fn remove_edge(&mut self, s: V, t: V) {
+            self.$g.remove_edge(s, t)
+        }
+
+        fn set_phase(&mut self, v: V, phase: impl Into<Phase>) {
+            self.$g.set_phase(v, phase)
+        }
+
+        fn phase(&self, v: V) -> Phase {
+            self.$g.phase(v)
+        }
+
+        fn set_vertex_type(&mut self, v: V, ty: VType) {
+            self.$g.set_vertex_type(v, ty)
+        }
+
+        fn vertex_type(&self, v: V) -> VType {
+            self.$g.vertex_type(v)
+        }
+
+        fn vertex_data(&self, v: V) -> VData {
+            self.$g.vertex_data(v)
+        }
+
+        fn set_edge_type(&mut self, s: V, t: V, ety: EType) {
+            self.$g.set_edge_type(s, t, ety)
+        }
+
+        fn edge_type_opt(&self, s: V, t: V) -> Option<EType> {
+            self.$g.edge_type_opt(s, t)
+        }
+
+        fn set_coord(&mut self, v: V, coord: impl Into<Coord>) {
+            self.$g.set_coord(v, coord)
+        }
+
+        fn coord(&self, v: V) -> Coord {
+            self.$g.coord(v)
+        }
+
+        fn set_qubit(&mut self, v: V, qubit: i32) {
+            self.$g.set_qubit(v, qubit)
+        }
+
+        fn qubit(&self, v: V) -> i32 {
+            self.$g.qubit(v)
+        }
+
+        fn set_row(&mut self, v: V, row: i32) {
+            self.$g.set_row(v, row)
+        }
+
+        fn row(&self, v: V) -> i32 {
+            self.$g.row(v)
+        }
+
+        fn neighbors(&self, v: V) -> NeighborIter<'_> {
+            self.$g.neighbors(v)
+        }
+
+        fn incident_edges(&self, v: V) -> IncidentEdgeIter<'_> {
+            self.$g.incident_edges(v)
+        }
+
+        fn degree(&self, v: V) -> usize {
+            self.$g.degree(v)
+        }
+
+        fn scalar(&self) -> &ScalarN {
+            self.$g.scalar()
+        }
+
+        fn scalar_mut(&mut self) -> &mut ScalarN {
+            self.$g.scalar_mut()
+        }
+
+        fn stats(&self) -> &GraphStats {
+            self.$g.stats()
+        }
+
+        fn boundary_qubits(&self) -> &BoundaryQubits {
+            self.$g.boundary_qubits()
+        }
+
+        fn boundary_qubits_mut(&mut self) -> &mut BoundaryQubits {
+            self.$g.boundary_qubits_mut()
+        }
+
+        fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
+        where
+            F: Fn(V, V, EType) -> bool,
+        {
+            self.$g.find_edge(f)
+        }
+
+        fn find_vertex<F>(&self, f: F) -> Option<V>
+        where
+            F: Fn(V) -> bool,
+        {
+            self.$g.find_vertex(f)
+        }
+
+        fn contains_vertex(&self, v: V) -> bool {
+            self.$g.contains_vertex(v)
+        }
+    };
+}
+pub(crate) use delegate_graph_like;
+
 pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     /// Initialise a new empty graph
     fn new() -> Self;
@@ -440,6 +894,17 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     fn degree(&self, v: V) -> usize;
     fn scalar(&self) -> &ScalarN;
     fn scalar_mut(&mut self) -> &mut ScalarN;
+
+    /// Cached vertex statistics (T-count, type counts, degree histogram)
+    ///
+    /// These are updated as the graph changes, so this is cheap to call.
+    fn stats(&self) -> &GraphStats;
+
+    /// Qubit indices of the boundaries
+    ///
+    /// See [GraphLike::input_of_qubit] and [GraphLike::set_input_qubit].
+    fn boundary_qubits(&self) -> &BoundaryQubits;
+    fn boundary_qubits_mut(&mut self) -> &mut BoundaryQubits;
     fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
     where
         F: Fn(V, V, EType) -> bool;
@@ -448,6 +913,62 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         F: Fn(V) -> bool;
     fn contains_vertex(&self, v: V) -> bool;
 
+    /// Hook called by rewrite rules just before the phase of `v1` is moved onto `v0`
+    ///
+    /// Afterwards, `v0` carries the sum of both phases, and `v1` is removed or left
+    /// with a zero phase. This does nothing for the graph backends, but wrappers can
+    /// use it to follow non-Clifford phases through a simplification, as in
+    /// [crate::teleport::PhaseTracker].
+    fn fuse_phases(&mut self, _v0: V, _v1: V) {}
+
+    /// Hook called by rewrite rules when the phase of `v` is negated
+    ///
+    /// See [GraphLike::fuse_phases].
+    fn phase_negate(&mut self, _v: V) {}
+
+    /// Hook consulted by rewrite rules which depend on the value of a phase
+    ///
+    /// Returns true if the phase of `v` has a symbolic part, which is not included
+    /// in [GraphLike::phase]. Such spiders are never treated as Pauli or Clifford,
+    /// and their phases are only moved around with [GraphLike::fuse_phases] and
+    /// [GraphLike::phase_negate]. The graph backends have no symbolic phases, see
+    /// [crate::symbolic::SymbolicGraph].
+    fn has_symbolic_phase(&self, _v: V) -> bool {
+        false
+    }
+
+    /// Hook consulted by the simplifiers before they apply a rule at the given vertices
+    ///
+    /// If this returns false, the match is skipped, and the simplifier carries on
+    /// with the others. The graph backends allow rewriting anywhere, but wrappers
+    /// can use this to confine a simplification to part of the graph, as in
+    /// [crate::region::Region].
+    fn can_rewrite_at(&self, _vertices: &[V]) -> bool {
+        true
+    }
+
+    /// Hook called by rewrite rules before they change the graph
+    ///
+    /// `rule` names the rule and `vertices` are the vertices it was applied to. Every
+    /// call is followed by a call to [GraphLike::end_rewrite] once the rule is done.
+    /// Rules built from other rules cause nested calls. Like [GraphLike::fuse_phases],
+    /// this does nothing for the graph backends, see [crate::trace::Tracer].
+    fn begin_rewrite(&mut self, _rule: &'static str, _vertices: &[V]) {}
+
+    /// Hook called by rewrite rules after they change the graph
+    ///
+    /// See [GraphLike::begin_rewrite].
+    fn end_rewrite(&mut self) {}
+
+    /// Hook consulted by the simplifiers before they apply a rule
+    ///
+    /// If this returns false, the simplifiers stop rewriting and return. The graph
+    /// backends always return true, but wrappers can use this to limit the number
+    /// of rewrites, as in [crate::trace::Profiler].
+    fn can_rewrite(&self) -> bool {
+        true
+    }
+
     fn add_edge(&mut self, s: V, t: V) {
         self.add_edge_with_type(s, t, EType::N);
     }
@@ -480,11 +1001,13 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     /// Convert all X spiders to Z with the colour-change rule
     fn x_to_z(&mut self) {
         for v in Vec::from_iter(self.vertices()) {
-            if self.vertex_type(v) == VType::X {
+            if self.vertex_type(v) == VType::X && self.can_rewrite() && self.can_rewrite_at(&[v]) {
+                self.begin_rewrite("color_change", &[v]);
                 self.set_vertex_type(v, VType::Z);
                 for w in Vec::from_iter(self.neighbors(v)) {
                     self.toggle_edge_type(v, w);
                 }
+                self.end_rewrite();
             }
         }
     }
@@ -563,11 +1086,76 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         }
     }
 
+    /// The input boundary carrying the given qubit index, if there is one
+    ///
+    /// Qubit indices are kept in [GraphLike::boundary_qubits], independently of
+    /// the drawing coordinates and of the order of [GraphLike::inputs], so this
+    /// stays correct when other inputs are plugged or the input list is reordered.
+    fn input_of_qubit(&self, q: usize) -> Option<V> {
+        self.boundary_qubits().input(q)
+    }
+
+    /// The output boundary carrying the given qubit index, if there is one
+    ///
+    /// See [GraphLike::input_of_qubit].
+    fn output_of_qubit(&self, q: usize) -> Option<V> {
+        self.boundary_qubits().output(q)
+    }
+
+    /// The qubit index carried by the boundary `v`, if it has one
+    fn boundary_qubit(&self, v: V) -> Option<usize> {
+        self.boundary_qubits().qubit(v)
+    }
+
+    /// Record that the input `v` carries qubit `q`
+    fn set_input_qubit(&mut self, v: V, q: usize) {
+        self.boundary_qubits_mut().set_input(v, q);
+    }
+
+    /// Record that the output `v` carries qubit `q`
+    fn set_output_qubit(&mut self, v: V, q: usize) {
+        self.boundary_qubits_mut().set_output(v, q);
+    }
+
+    /// Copy the qubit indices of the inputs/outputs of `other` along the renaming `vmap`
+    ///
+    /// Boundaries which are not in `vmap` are skipped.
+    fn copy_boundary_qubits(&mut self, other: &impl GraphLike, vmap: &FxHashMap<V, V>) {
+        for v in other.inputs() {
+            if let (Some(&v1), Some(q)) = (vmap.get(v), other.boundary_qubit(*v)) {
+                self.set_input_qubit(v1, q);
+            }
+        }
+        for v in other.outputs() {
+            if let (Some(&v1), Some(q)) = (vmap.get(v), other.boundary_qubit(*v)) {
+                self.set_output_qubit(v1, q);
+            }
+        }
+    }
+
+    /// Set the qubit index of every boundary to its position in the inputs/outputs
+    ///
+    /// Graphs built by [crate::circuit::Circuit::to_graph] are already numbered
+    /// this way. This is useful for graphs built by hand or read from a format
+    /// which does not record qubit indices. Any previous indices are dropped.
+    fn number_boundaries(&mut self) {
+        let (inputs, outputs) = (self.inputs().clone(), self.outputs().clone());
+        let bq = self.boundary_qubits_mut();
+        bq.clear();
+        for (q, v) in inputs.into_iter().enumerate() {
+            bq.set_input(v, q);
+        }
+        for (q, v) in outputs.into_iter().enumerate() {
+            bq.set_output(v, q);
+        }
+    }
+
     /// Replace a boundary vertex with the given basis element
     ///
     /// Note this does not replace the vertex from the input/output list or do
-    /// normalisation.
+    /// normalisation. The qubit index of `v`, if any, is dropped.
     fn plug_vertex(&mut self, v: V, b: BasisElem) {
+        self.boundary_qubits_mut().remove(v);
         self.set_vertex_type(v, VType::Z);
         self.set_phase(v, b.phase());
 
@@ -671,6 +1259,11 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
 
         let outp = other.outputs().iter().map(|o| vmap[o]).collect();
         self.set_outputs(outp);
+        for o in other.outputs() {
+            if let Some(q) = other.boundary_qubit(*o) {
+                self.set_output_qubit(vmap[o], q);
+            }
+        }
     }
 
     /// Checks if the given graph only consists of wires from the inputs to outputs (in order)
@@ -684,19 +1277,109 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
 
     /// Return number of Z or X spiders with non-Clifford phase
     fn tcount(&self) -> usize {
-        let mut n = 0;
+        self.stats().tcount()
+    }
+
+    /// Return the number of vertices of the given type
+    fn num_vertices_of_type(&self, ty: VType) -> usize {
+        self.stats().num_vertices_of_type(ty)
+    }
+
+    /// Check the internal consistency of the graph
+    ///
+    /// This checks that adjacency is symmetric with no parallel edges, that inputs
+    /// and outputs exist and are boundary vertices without self-loops, that phases
+    /// are normalized, and that the cached counts and [GraphStats] are correct.
+    /// It takes time linear in the size of the graph.
+    fn check(&self) -> Result<(), CheckError> {
+        let mut numv = 0;
+        let mut nume2 = 0;
+        let mut stats = GraphStats::default();
         for v in self.vertices() {
-            let t = self.vertex_type(v);
-            if (t == VType::Z || t == VType::X) && !self.phase(v).is_clifford() {
-                n += 1;
+            numv += 1;
+            let d = self.vertex_data(v);
+            if d.phase.normalize() != d.phase {
+                return Err(CheckError::UnnormalizedPhase(v, d.phase));
+            }
+            stats.add_vertex(&d);
+
+            let mut seen = FxHashSet::default();
+            for (w, et) in self.incident_edges(v) {
+                if !self.contains_vertex(w) {
+                    return Err(CheckError::DanglingEdge { s: v, t: w });
+                }
+                // backends differ in how they store self-loops, so only
+                // boundaries are checked for those, below
+                if v != w && !seen.insert(w) {
+                    return Err(CheckError::ParallelEdge { s: v, t: w });
+                }
+                if self.edge_type_opt(w, v) != Some(et) {
+                    return Err(CheckError::AsymmetricEdge { s: v, t: w });
+                }
+                nume2 += 1;
+            }
+            stats.change_degree(0, self.degree(v));
+        }
+
+        if numv != self.num_vertices() {
+            return Err(CheckError::WrongCount {
+                what: "vertices",
+                expected: self.num_vertices(),
+                found: numv,
+            });
+        }
+        if nume2 / 2 != self.num_edges() {
+            return Err(CheckError::WrongCount {
+                what: "edges",
+                expected: self.num_edges(),
+                found: nume2 / 2,
+            });
+        }
+        if &stats != self.stats() {
+            return Err(CheckError::StaleStats);
+        }
+
+        for &b in self.inputs().iter().chain(self.outputs()) {
+            if !self.contains_vertex(b) {
+                return Err(CheckError::DanglingBoundary(b));
+            }
+            let ty = self.vertex_type(b);
+            if ty != VType::B {
+                return Err(CheckError::BoundaryNotB(b, ty));
+            }
+            if self.connected(b, b) {
+                return Err(CheckError::BoundarySelfLoop(b));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// In debug builds, panic if [GraphLike::check] fails
+    ///
+    /// This is a no-op in release builds, so it can be sprinkled liberally
+    /// through rewrite and decomposition code.
+    fn debug_check(&self) {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check() {
+                panic!("Graph invariant violated: {}", e);
             }
         }
-        n
     }
 
     /// Return a graphviz-friendly string representation of the graph
     fn to_dot(&self) -> String {
+        self.to_dot_with_options(&DotOptions::default())
+    }
+
+    /// Return a graphviz-friendly string representation of the graph
+    ///
+    /// See [DotOptions] for the available options.
+    fn to_dot_with_options(&self, options: &DotOptions) -> String {
         let mut dot = String::from("graph {\n");
+        if options.rank_boundaries {
+            dot += "  rankdir=LR\n";
+        }
         for v in self.vertices() {
             let t = self.vertex_type(v);
             let p = self.phase(v);
@@ -704,6 +1387,7 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
                 "  {} [color={}, label=\"{}\"",
                 v,
                 match t {
+                    _ if !options.color_by_type => "black",
                     VType::B => "black",
                     VType::Z => "green",
                     VType::X => "red",
@@ -716,7 +1400,7 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
                     format!("{}:i", v)
                 } else if self.outputs().contains(&v) {
                     format!("{}:o", v)
-                } else if !p.is_zero() {
+                } else if options.show_phases && !p.is_zero() {
                     format!("{}:{}", v, p)
                 } else {
                     format!("{}", v)
@@ -727,15 +1411,31 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
             if q != 0 || r != 0 {
                 dot += &format!(", pos=\"{},{}!\"", q, r);
             }
+            if options.highlight.contains(&v) {
+                dot += ", style=filled, fillcolor=orange, penwidth=3";
+            }
             dot += "]\n";
         }
 
+        if options.rank_boundaries {
+            for (rank, bs) in [("source", self.inputs()), ("sink", self.outputs())] {
+                if !bs.is_empty() {
+                    let names: Vec<String> = bs.iter().map(|b| b.to_string()).collect();
+                    dot += &format!("  {{rank={}; {}}}\n", rank, names.join("; "));
+                }
+            }
+        }
+
         dot += "\n";
 
         for (s, t, ty) in self.edges() {
             dot += &format!("  {} -- {}", s, t);
             if ty == EType::H {
-                dot += " [color=blue]";
+                if options.dashed_h_edges {
+                    dot += " [color=blue, style=dashed]";
+                } else {
+                    dot += " [color=blue]";
+                }
             }
             dot += "\n";
         }
@@ -745,6 +1445,91 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         dot
     }
 
+    /// Return a tikzit-compatible representation of the graph
+    ///
+    /// Style names match the defaults used by `pyzx.tikz.to_tikz`, so the output
+    /// can be used with the zx.tikzstyles file distributed with pyzx. Vertices are
+    /// placed according to their row and qubit. If no vertex has coordinates, they
+    /// are laid out in a square grid instead.
+    fn to_tikz(&self) -> String {
+        let has_coords = self
+            .vertices()
+            .any(|v| self.row(v) != 0 || self.qubit(v) != 0);
+        let width = (self.num_vertices() as f64).sqrt().ceil().max(1.0) as usize;
+
+        let mut tikz = String::from("\\begin{tikzpicture}\n");
+        tikz += "    \\begin{pgfonlayer}{nodelayer}\n";
+        for (i, v) in self.vertices().enumerate() {
+            let t = self.vertex_type(v);
+            let p = self.phase(v);
+            let style = match t {
+                VType::B => "none",
+                VType::Z if p.is_zero() => "Z dot",
+                VType::Z => "Z phase dot",
+                VType::X if p.is_zero() => "X dot",
+                VType::X => "X phase dot",
+                VType::H => "hadamard",
+                VType::WInput => "W input",
+                VType::WOutput => "W triangle",
+                VType::ZBox => "Z box",
+            };
+
+            let r = p.to_rational();
+            let label = if t == VType::B || (t == VType::H && p.is_one()) || p.is_zero() {
+                String::new()
+            } else {
+                let sign = if *r.numer() < 0 { "-" } else { "" };
+                let numer = match r.numer().abs() {
+                    1 => String::new(),
+                    n => n.to_string(),
+                };
+                match *r.denom() {
+                    1 => format!("${}{}\\pi$", sign, numer),
+                    d => format!("${}\\frac{{{}\\pi}}{{{}}}$", sign, numer, d),
+                }
+            };
+
+            let (x, y) = if has_coords {
+                (self.row(v) as f64, -self.qubit(v) as f64)
+            } else {
+                ((i % width) as f64, -((i / width) as i32) as f64)
+            };
+            tikz += &format!(
+                "        \\node [style={}] ({}) at ({:.2}, {:.2}) {{{}}};\n",
+                style, v, x, y, label
+            );
+        }
+        tikz += "    \\end{pgfonlayer}\n";
+
+        tikz += "    \\begin{pgfonlayer}{edgelayer}\n";
+        for (s, t, ty) in self.edges() {
+            tikz += &match ty {
+                EType::N => format!("        \\draw ({}) to ({});\n", s, t),
+                EType::H => format!("        \\draw [style=hadamard edge] ({}) to ({});\n", s, t),
+                EType::Wio => format!("        \\draw [style=W io edge] ({}) to ({});\n", s, t),
+            };
+        }
+        tikz += "    \\end{pgfonlayer}\n";
+        tikz += "\\end{tikzpicture}\n";
+
+        tikz
+    }
+
+    /// Render the graph as SVG, see [crate::svg] for details
+    fn to_svg(&self) -> String {
+        crate::svg::to_svg(self)
+    }
+
+    /// Encode the graph in the json format used by `pyzx.Graph.to_json`
+    fn to_pyzx_json(&self) -> Result<String, crate::json::JsonError> {
+        crate::json::encode_pyzx_graph(self)
+    }
+
+    /// Decode a graph from the json format used by `pyzx.Graph.to_json`
+    fn from_pyzx_json(s: &str) -> Result<Self, crate::json::JsonError> {
+        crate::json::decode_pyzx_graph(s)
+    }
+
     /// Exchange inputs and outputs and reverse all phases
     fn adjoint(&mut self) {
         for v in self.vertex_vec() {
@@ -755,6 +1540,7 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         let inp = self.inputs().clone();
         self.set_inputs(self.outputs().clone());
         self.set_outputs(inp);
+        self.boundary_qubits_mut().swap_sides();
         let s = self.scalar().conj();
         *(self.scalar_mut()) = s;
     }
@@ -766,6 +1552,183 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         g
     }
 
+    /// Returns the subgraph induced by the given vertices
+    ///
+    /// Every edge from a vertex in `vs` to a vertex outside of it is replaced by an
+    /// edge of the same type to a fresh boundary, and these boundaries are appended
+    /// to the outputs. Inputs and outputs of `self` which are in `vs` stay inputs and
+    /// outputs, in their original order, ahead of the fresh ones. The scalar is one.
+    ///
+    /// Vertices are added in the order given, so for backends which allocate names
+    /// sequentially, `vs[i]` becomes vertex `i`.
+    fn induced_subgraph(&self, vs: &[V]) -> Self {
+        let mut g = Self::new();
+        let mut vmap = FxHashMap::default();
+        for &v in vs {
+            vmap.insert(v, g.add_vertex_with_data(self.vertex_data(v)));
+        }
+
+        let mut fresh = vec![];
+        for &v in vs {
+            for (w, et) in self.incident_edges(v) {
+                if let Some(&w1) = vmap.get(&w) {
+                    if v < w || (v == w && !g.connected(w1, w1)) {
+                        g.add_edge_with_type(vmap[&v], w1, et);
+                    }
+                } else {
+                    let b = g.add_vertex_with_data(VData {
+                        ty: VType::B,
+                        phase: Phase::zero(),
+                        qubit: self.qubit(v),
+                        row: self.row(w),
+                    });
+                    g.add_edge_with_type(vmap[&v], b, et);
+                    fresh.push(b);
+                }
+            }
+        }
+
+        let inputs = self.inputs().iter().filter_map(|v| vmap.get(v).copied());
+        g.set_inputs(inputs.collect());
+        let outputs = self.outputs().iter().filter_map(|v| vmap.get(v).copied());
+        g.set_outputs(outputs.chain(fresh).collect());
+        g.copy_boundary_qubits(self, &vmap);
+        g
+    }
+
+    /// Returns a copy of the graph with every vertex `v` renamed to `f(v)`
+    ///
+    /// The map `f` must be a bijection from the vertices of `self` onto `0..n`, where
+    /// `n` is the number of vertices, and the vertices are added to a new graph in
+    /// that order. Inputs, outputs, and the scalar are carried over.
+    ///
+    /// Panics if `f` is not a bijection onto `0..n`, or if the backend does not name
+    /// the vertices of a new graph `0, 1, 2, ...`, as all of the backends in this
+    /// crate do.
+    fn map_vertices<F>(&self, f: F) -> Self
+    where
+        F: Fn(V) -> V,
+    {
+        let mut order: Vec<(V, V)> = self.vertices().map(|v| (f(v), v)).collect();
+        order.sort_unstable();
+        assert!(
+            order.iter().enumerate().all(|(i, &(w, _))| i == w),
+            "Vertex map is not a bijection onto 0..n"
+        );
+
+        let mut g = Self::new();
+        let mut vmap: FxHashMap<V, V> = FxHashMap::default();
+        for &(w, v) in &order {
+            let w1 = g.add_vertex_with_data(self.vertex_data(v));
+            assert_eq!(w, w1, "Backend does not name new vertices 0, 1, 2, ...");
+            vmap.insert(v, w1);
+        }
+
+        for (s, t, et) in self.edges() {
+            g.add_edge_with_type(vmap[&s], vmap[&t], et);
+        }
+        g.set_inputs(self.inputs().iter().map(|v| vmap[v]).collect());
+        g.set_outputs(self.outputs().iter().map(|v| vmap[v]).collect());
+        g.copy_boundary_qubits(self, &vmap);
+        *g.scalar_mut() = self.scalar().clone();
+        g
+    }
+
+    /// Try to find a renaming of the vertices of `self` which makes it equal to `other`
+    ///
+    /// Matching starts from the inputs and outputs, which are paired up by position,
+    /// and spreads to neighbours which can be told apart by their type, phase and the
+    /// type of the connecting edge. Returns `None` if the graphs differ, or if some
+    /// vertices could not be matched unambiguously this way. Otherwise, the returned
+    /// map `m` sends every vertex of `self` to the corresponding vertex of `other`, so
+    /// that the two graphs have the same vertex data, edges, inputs and outputs under
+    /// `m`, up to qubit and row positions. If the vertices of `other` are `0..n`, then
+    /// `self.map_vertices(|v| m[&v])` also agrees with `other` on vertex names.
+    fn align_vertices(&self, other: &impl GraphLike) -> Option<FxHashMap<V, V>> {
+        if self.num_vertices() != other.num_vertices()
+            || self.num_edges() != other.num_edges()
+            || self.inputs().len() != other.inputs().len()
+            || self.outputs().len() != other.outputs().len()
+        {
+            return None;
+        }
+
+        let mut m: FxHashMap<V, V> = FxHashMap::default();
+        let mut used: FxHashSet<V> = FxHashSet::default();
+        let mut stack = vec![];
+        let bs = self.inputs().iter().chain(self.outputs());
+        let obs = other.inputs().iter().chain(other.outputs());
+        for (&v, &w) in bs.zip(obs) {
+            if let Some(&w1) = m.get(&v) {
+                if w1 != w {
+                    return None;
+                }
+            } else if used.insert(w) {
+                m.insert(v, w);
+                stack.push((v, w));
+            } else {
+                return None;
+            }
+        }
+
+        // pair up unmatched neighbours which are unique up to type, phase, and edge type
+        let key = |d: VData, et: EType| (d.ty, d.phase.to_rational(), et);
+        while let Some((v, w)) = stack.pop() {
+            let mut nhd: FxHashMap<_, Vec<V>> = FxHashMap::default();
+            for (n, et) in self.incident_edges(v) {
+                if !m.contains_key(&n) {
+                    nhd.entry(key(self.vertex_data(n), et)).or_default().push(n);
+                }
+            }
+            let mut onhd: FxHashMap<_, Vec<V>> = FxHashMap::default();
+            for (n, et) in other.incident_edges(w) {
+                if !used.contains(&n) {
+                    onhd.entry(key(other.vertex_data(n), et))
+                        .or_default()
+                        .push(n);
+                }
+            }
+            if nhd.len() != onhd.len() {
+                return None;
+            }
+            for (k, ns) in nhd {
+                let ons = onhd.get(&k)?;
+                if ns.len() != ons.len() {
+                    return None;
+                }
+                if let ([n], [on]) = (&ns[..], &ons[..]) {
+                    m.insert(*n, *on);
+                    used.insert(*on);
+                    stack.push((*n, *on));
+                }
+            }
+        }
+
+        let aligned = m.len() == self.num_vertices()
+            && m.iter().all(|(&v, &w)| {
+                key(self.vertex_data(v), EType::N) == key(other.vertex_data(w), EType::N)
+            })
+            && self
+                .edges()
+                .all(|(s, t, et)| other.edge_type_opt(m[&s], m[&t]) == Some(et));
+        aligned.then_some(m)
+    }
+
+    /// Checks if the graph is in graph-like form
+    ///
+    /// A graph is graph-like if every vertex is a Z spider or a boundary, every edge
+    /// between spiders is a Hadamard edge, there are no self-loops, and every
+    /// boundary has exactly one neighbour.
+    fn is_graph_like(&self) -> bool {
+        self.vertices().all(|v| match self.vertex_type(v) {
+            VType::Z => self
+                .incident_edges(v)
+                .all(|(w, et)| w != v && (et == EType::H || self.vertex_type(w) == VType::B)),
+            VType::B => self.degree(v) == 1,
+            _ => false,
+        })
+    }
+
     /// Returns vertices in the components of g
     fn component_vertices(&self) -> Vec<FxHashSet<V>> {
         // vec of vecs storing components
@@ -798,6 +1761,31 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
 
         comps
     }
+
+    /// Split the graph into its connected components
+    ///
+    /// Each component keeps the inputs and outputs it contains, in their original
+    /// order. The first component carries the scalar of `self` and the rest have
+    /// scalar one, so the product of the components is equal to `self`. Components
+    /// are ordered by their smallest vertex.
+    fn split_components(&self) -> Vec<Self> {
+        let mut comps: Vec<Vec<V>> = self
+            .component_vertices()
+            .into_iter()
+            .map(|c| {
+                let mut c: Vec<V> = c.into_iter().collect();
+                c.sort_unstable();
+                c
+            })
+            .collect();
+        comps.sort_unstable_by_key(|c| c[0]);
+
+        let mut gs: Vec<Self> = comps.iter().map(|c| self.induced_subgraph(c)).collect();
+        if let Some(g) = gs.first_mut() {
+            *g.scalar_mut() = self.scalar().clone();
+        }
+        gs
+    }
 }
 
 #[cfg(test)]
@@ -890,4 +1878,371 @@ mod tests {
 
         assert_eq!(g.component_vertices().first().unwrap().len(), 4)
     }
+
+    fn check_stats(g: &impl GraphLike) {
+        let tcount = g
+            .vertices()
+            .filter(|&v| g.vertex_data(v).is_t_like())
+            .count();
+        assert_eq!(g.tcount(), tcount);
+
+        for ty in [VType::B, VType::Z, VType::X, VType::H] {
+            let n = g.vertices().filter(|&v| g.vertex_type(v) == ty).count();
+            assert_eq!(g.num_vertices_of_type(ty), n);
+        }
+
+        let mut hist = vec![];
+        for v in g.vertices() {
+            let d = g.degree(v);
+            if hist.len() <= d {
+                hist.resize(d + 1, 0);
+            }
+            hist[d] += 1;
+        }
+        assert_eq!(g.stats().degree_histogram(), &hist[..]);
+    }
+
+    #[test]
+    fn cached_stats() {
+        use crate::circuit::Circuit;
+        use crate::simplify::full_simp;
+
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(50)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+
+        let mut g: Graph = c.to_graph();
+        check_stats(&g);
+        assert_eq!(g.tcount(), c.num_gates_of_type(crate::gate::T));
+        full_simp(&mut g);
+        check_stats(&g);
+
+        let mut h: crate::hash_graph::Graph = c.to_graph();
+        check_stats(&h);
+        full_simp(&mut h);
+        check_stats(&h);
+    }
+
+    #[test]
+    fn tikz() {
+        let mut g = Graph::new();
+        let b = g.add_vertex_with_data(VData {
+            ty: VType::B,
+            phase: Phase::zero(),
+            qubit: 0,
+            row: 0,
+        });
+        let z = g.add_vertex_with_data(VData {
+            ty: VType::Z,
+            phase: Phase::new(Rational64::new(-1, 4)),
+            qubit: 0,
+            row: 1,
+        });
+        let x = g.add_vertex_with_data(VData {
+            ty: VType::X,
+            phase: Phase::one(),
+            qubit: 1,
+            row: 2,
+        });
+        g.add_vertex_with_data(VData {
+            ty: VType::Z,
+            phase: Phase::new(Rational64::new(-3, 4)),
+            qubit: 1,
+            row: 3,
+        });
+        g.add_edge(b, z);
+        g.add_edge_with_type(z, x, EType::H);
+
+        let tikz = g.to_tikz();
+        assert!(tikz.contains("\\node [style=none] (0) at (0.00, 0.00) {};"));
+        assert!(
+            tikz.contains("\\node [style=Z phase dot] (1) at (1.00, 0.00) {$-\\frac{\\pi}{4}$};")
+        );
+        assert!(tikz.contains("\\node [style=X phase dot] (2) at (2.00, -1.00) {$\\pi$};"));
+        assert!(
+            tikz.contains("\\node [style=Z phase dot] (3) at (3.00, -1.00) {$-\\frac{3\\pi}{4}$};")
+        );
+        assert!(tikz.contains("\\draw (0) to (1);"));
+        assert!(tikz.contains("\\draw [style=hadamard edge] (1) to (2);"));
+    }
+
+    #[test]
+    fn dot_options() {
+        let mut g = Graph::new();
+        let b0 = g.add_vertex(VType::B);
+        let z = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(b0, z);
+        g.add_edge_with_type(z, b1, EType::H);
+        g.set_inputs(vec![b0]);
+        g.set_outputs(vec![b1]);
+
+        let dot = g.to_dot();
+        assert_eq!(dot, g.to_dot_with_options(&DotOptions::default()));
+        assert!(dot.contains("1 [color=green, label=\"1:1/4\"]"));
+        assert!(dot.contains("1 -- 2 [color=blue]\n"));
+
+        let options = DotOptions {
+            show_phases: false,
+            color_by_type: false,
+            dashed_h_edges: true,
+            highlight: [z].into_iter().collect(),
+            rank_boundaries: true,
+        };
+        let dot = g.to_dot_with_options(&options);
+        assert!(dot
+            .contains("1 [color=black, label=\"1\", style=filled, fillcolor=orange, penwidth=3]"));
+        assert!(dot.contains("1 -- 2 [color=blue, style=dashed]"));
+        assert!(dot.contains("rankdir=LR"));
+        assert!(dot.contains("{rank=source; 0}"));
+        assert!(dot.contains("{rank=sink; 2}"));
+    }
+
+    fn check_simplified<G: GraphLike>() {
+        use crate::circuit::Circuit;
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(50)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: G = c.to_graph();
+        assert_eq!(g.check(), Ok(()));
+        crate::simplify::full_simp(&mut g);
+        assert_eq!(g.check(), Ok(()));
+    }
+
+    #[test]
+    fn check_valid() {
+        check_simplified::<Graph>();
+        check_simplified::<crate::hash_graph::Graph>();
+        check_simplified::<crate::compact_graph::Graph>();
+        check_simplified::<crate::persistent_graph::Graph>();
+    }
+
+    #[test]
+    fn check_invalid() {
+        let mut g = Graph::new();
+        let b0 = g.add_vertex(VType::B);
+        let z = g.add_vertex(VType::Z);
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(b0, z);
+        g.add_edge(z, b1);
+        g.set_inputs(vec![b0]);
+        g.set_outputs(vec![b1]);
+        assert_eq!(g.check(), Ok(()));
+
+        let mut h = g.clone();
+        h.set_inputs(vec![z]);
+        assert_eq!(h.check(), Err(CheckError::BoundaryNotB(z, VType::Z)));
+
+        let mut h = g.clone();
+        h.remove_vertex(b1);
+        assert_eq!(h.check(), Err(CheckError::DanglingBoundary(b1)));
+
+        let mut h = g.clone();
+        h.add_edge(b0, b0);
+        assert_eq!(h.check(), Err(CheckError::BoundarySelfLoop(b0)));
+    }
+
+    #[test]
+    fn boundary_qubits() {
+        use crate::circuit::Circuit;
+        use crate::extract::ToCircuit;
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        crate::simplify::full_simp(&mut g);
+        for q in 0..4 {
+            assert_eq!(g.input_of_qubit(q), Some(g.inputs()[q]));
+            assert_eq!(g.output_of_qubit(q), Some(g.outputs()[q]));
+        }
+
+        // moving the boundaries around does not change the qubits they carry
+        let (is, os) = (g.inputs().clone(), g.outputs().clone());
+        for (k, (&i, &o)) in is.iter().zip(&os).enumerate() {
+            g.set_qubit(i, 7 - k as i32);
+            g.set_coord(o, (-1, 0));
+        }
+        for q in 0..4 {
+            assert_eq!(g.input_of_qubit(q), Some(is[q]));
+            assert_eq!(g.output_of_qubit(q), Some(os[q]));
+            assert_eq!(g.boundary_qubit(is[q]), Some(q));
+        }
+        let h = g.to_adjoint();
+        assert_eq!(h.input_of_qubit(2), Some(os[2]));
+        assert_eq!(h.output_of_qubit(2), Some(is[2]));
+
+        let mut h = g.clone();
+        let i1 = h.inputs()[1];
+        h.plug_input(0, BasisElem::Z0);
+        assert_eq!(h.input_of_qubit(0), None);
+        assert_eq!(h.input_of_qubit(1), Some(i1));
+        assert_eq!(h.inputs()[0], i1);
+
+        let (is, os) = (g.inputs().clone(), g.outputs().clone());
+        assert!(g.to_circuit_mut().is_ok());
+        for (q, (&i, &o)) in is.iter().zip(&os).enumerate() {
+            assert_eq!(g.input_of_qubit(q), Some(i));
+            assert_eq!(g.output_of_qubit(q), Some(o));
+        }
+
+        let mut g = Graph::new();
+        let b0 = g.add_vertex(VType::B);
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(b0, b1);
+        g.set_inputs(vec![b0]);
+        g.set_outputs(vec![b1]);
+        g.set_qubit(b1, 3);
+        assert_eq!(g.output_of_qubit(0), None);
+        g.set_output_qubit(b1, 3);
+        assert_eq!(g.output_of_qubit(3), Some(b1));
+        g.number_boundaries();
+        assert_eq!(g.output_of_qubit(0), Some(b1));
+        assert_eq!(g.output_of_qubit(3), None);
+        g.remove_vertex(b1);
+        assert_eq!(g.output_of_qubit(0), None);
+    }
+
+    #[test]
+    fn induced_subgraph() {
+        let mut g = Graph::new();
+        let b0 = g.add_vertex(VType::B);
+        let z0 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        let x0 = g.add_vertex(VType::X);
+        let z1 = g.add_vertex(VType::Z);
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(b0, z0);
+        g.add_edge_with_type(z0, x0, EType::H);
+        g.add_edge(x0, z1);
+        g.add_edge(z0, z1);
+        g.add_edge(z1, b1);
+        g.set_inputs(vec![b0]);
+        g.set_outputs(vec![b1]);
+        g.scalar_mut().mul_sqrt2_pow(3);
+
+        let h = g.induced_subgraph(&[b0, z0, x0]);
+        assert_eq!(h.num_vertices(), 5);
+        assert_eq!(h.num_edges(), 4);
+        assert_eq!(h.inputs(), &vec![0]);
+        assert_eq!(h.outputs(), &vec![3, 4]);
+        assert_eq!(h.phase(1), Rational64::new(1, 4).into());
+        assert_eq!(h.edge_type(1, 2), EType::H);
+        assert_eq!(h.edge_type(1, 3), EType::N);
+        assert_eq!(h.edge_type(2, 4), EType::N);
+        assert_eq!(h.vertex_type(3), VType::B);
+        assert_eq!(h.scalar(), &ScalarN::one());
+        assert_eq!(h.check(), Ok(()));
+
+        let all = g.vertex_vec();
+        let h = g.induced_subgraph(&all);
+        assert_eq!(h.num_vertices(), g.num_vertices());
+        assert_eq!(h.num_edges(), g.num_edges());
+        assert_eq!(h.outputs(), &vec![4]);
+    }
+
+    #[test]
+    fn split_components() {
+        use crate::circuit::Circuit;
+        let mut g = Graph::new();
+        for seed in [1, 2] {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(2)
+                .depth(8)
+                .p_t(0.3)
+                .with_cliffords()
+                .build();
+            let mut h: Graph = c.to_graph();
+            h.plug_inputs(&[BasisElem::Z0, BasisElem::X1]);
+            h.plug_outputs(&[BasisElem::Z0, BasisElem::Z1]);
+            g.append_graph(&h);
+        }
+        g.scalar_mut().mul_sqrt2_pow(1);
+
+        let gs = g.split_components();
+        assert!(gs.len() >= 2);
+        assert_eq!(
+            gs.iter().map(|h| h.num_vertices()).sum::<usize>(),
+            g.num_vertices()
+        );
+
+        let mut s = Scalar4::one();
+        for h in &gs {
+            assert_eq!(h.check(), Ok(()));
+            assert!(h.inputs().is_empty() && h.outputs().is_empty());
+            s *= h.to_tensor4().first().unwrap();
+        }
+        assert_eq!(&s, g.to_tensor4().first().unwrap());
+    }
+
+    #[test]
+    fn map_and_align_vertices() {
+        use crate::circuit::Circuit;
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(3)
+            .depth(20)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        crate::simplify::full_simp(&mut g);
+
+        // a bijection onto 0..n is followed exactly
+        let n = g.num_vertices();
+        let mut vs = g.vertex_vec();
+        vs.sort();
+        let rank: FxHashMap<V, V> = vs.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+        let h = g.map_vertices(|v| n - 1 - rank[&v]);
+        assert_eq!(h.check(), Ok(()));
+        assert_eq!(h.num_vertices(), g.num_vertices());
+        assert_eq!(h.num_edges(), g.num_edges());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        for v in g.vertices() {
+            assert_eq!(g.vertex_data(v), h.vertex_data(n - 1 - rank[&v]));
+        }
+
+        let mut g = Graph::new();
+        let b0 = g.add_vertex(VType::B);
+        let z0 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        let z1 = g.add_vertex(VType::Z);
+        let x0 = g.add_vertex_with_phase(VType::X, Rational64::new(1, 2));
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(b0, z0);
+        g.add_edge_with_type(z0, x0, EType::H);
+        g.add_edge(z0, z1);
+        g.add_edge_with_type(z1, x0, EType::H);
+        g.add_edge(x0, b1);
+        g.set_inputs(vec![b0]);
+        g.set_outputs(vec![b1]);
+        let h = g.map_vertices(|v| [4, 2, 0, 3, 1][v]);
+
+        let m = g.align_vertices(&h).unwrap();
+        assert_eq!(m.len(), 5);
+        assert!(m.iter().all(|(&v, &w)| w == [4, 2, 0, 3, 1][v]));
+
+        let mut h1 = h.clone();
+        h1.add_to_phase(0, Rational64::new(1, 2));
+        assert_eq!(g.align_vertices(&h1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Vertex map is not a bijection onto 0..n")]
+    fn map_vertices_not_onto() {
+        let mut g = Graph::new();
+        let vs: Vec<V> = (0..3).map(|_| g.add_vertex(VType::Z)).collect();
+        g.add_edge(vs[0], vs[2]);
+        g.map_vertices(|v| 2 * v + 5);
+    }
 }
@@ -0,0 +1,469 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GraphML import and export, for inspecting diagrams in tools like Gephi or yEd.
+//!
+//! Vertex and edge data are stored as GraphML attributes with the following names:
+//!
+//! | attribute | domain | type   | meaning                                                |
+//! |-----------|--------|--------|--------------------------------------------------------|
+//! | `scalar`  | graph  | string | the global scalar, in the pyzx json format             |
+//! | `type`    | node   | string | the vertex type, as in the .qgraph format (e.g. `Z`)    |
+//! | `phase`   | node   | string | the phase, as a fraction of pi (e.g. `1/4`)             |
+//! | `qubit`   | node   | int    | the qubit index                                        |
+//! | `row`     | node   | int    | the row index                                          |
+//! | `input`   | node   | int    | position in the list of inputs, or -1                  |
+//! | `output`  | node   | int    | position in the list of outputs, or -1                 |
+//! | `etype`   | edge   | string | the edge type, one of `N`, `H` or `Wio`                |
+//!
+//! When reading, attributes are matched by `attr.name`, so files produced by other
+//! tools are accepted as long as they use these names. Missing attributes fall back
+//! to the declared `<default>`, or to a plain Z spider and a normal edge. An H-box
+//! without a phase gets phase π, as in pyzx.
+//!
+//! ```
+//! # use quizx::graph::*;
+//! # use quizx::vec_graph::Graph;
+//! # use quizx::tensor::ToTensor;
+//! let mut g = Graph::new();
+//! let v0 = g.add_vertex(VType::B);
+//! let v1 = g.add_vertex_with_phase(VType::Z, (1, 4));
+//! let v2 = g.add_vertex(VType::B);
+//! g.add_edge(v0, v1);
+//! g.add_edge_with_type(v1, v2, EType::H);
+//! g.set_inputs(vec![v0]);
+//! g.set_outputs(vec![v2]);
+//!
+//! let xml = quizx::graphml::encode_graph(&g);
+//! let h: Graph = quizx::graphml::decode_graph(&xml).unwrap();
+//! assert_eq!(g.to_tensor4(), h.to_tensor4());
+//! ```
+
+use crate::graph::*;
+use crate::json::{JsonError, JsonScalar};
+use crate::phase::Phase;
+use num::{One, Rational64, Zero};
+use rustc_hash::FxHashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// An error that can occur when decoding a GraphML document.
+#[derive(Debug, thiserror::Error)]
+pub enum GraphMLError {
+    /// The document is not well-formed xml.
+    #[error(transparent)]
+    XmlError(#[from] roxmltree::Error),
+    /// The document does not contain a `<graph>` element.
+    #[error("No graph found in document")]
+    MissingGraph,
+    /// A node or edge is missing a required xml attribute.
+    #[error("Missing xml attribute {0}")]
+    MissingAttribute(&'static str),
+    /// A GraphML attribute has a value that could not be parsed.
+    #[error("Got an invalid value {value} for attribute {name}")]
+    InvalidAttribute { name: String, value: String },
+    /// An edge refers to a node that does not exist.
+    #[error("Reference to unknown node {0}")]
+    UnknownNode(String),
+    /// Error decoding the scalar.
+    #[error(transparent)]
+    JsonError(#[from] JsonError),
+    /// Error reading or writing a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+fn vtype_name(ty: VType) -> &'static str {
+    match ty {
+        VType::B => "B",
+        VType::Z => "Z",
+        VType::X => "X",
+        VType::H => "hadamard",
+        VType::WInput => "W_input",
+        VType::WOutput => "W_output",
+        VType::ZBox => "Z_box",
+    }
+}
+
+fn vtype_from_name(s: &str) -> Option<VType> {
+    match s {
+        "B" => Some(VType::B),
+        "Z" => Some(VType::Z),
+        "X" => Some(VType::X),
+        "hadamard" => Some(VType::H),
+        "W_input" => Some(VType::WInput),
+        "W_output" => Some(VType::WOutput),
+        "Z_box" => Some(VType::ZBox),
+        _ => None,
+    }
+}
+
+fn etype_name(ty: EType) -> &'static str {
+    match ty {
+        EType::N => "N",
+        EType::H => "H",
+        EType::Wio => "Wio",
+    }
+}
+
+fn etype_from_name(s: &str) -> Option<EType> {
+    match s {
+        "N" => Some(EType::N),
+        "H" => Some(EType::H),
+        "Wio" => Some(EType::Wio),
+        _ => None,
+    }
+}
+
+/// Escape a string for use in xml text or attribute values
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The `<key>` declarations written at the top of every document.
+const KEYS: &str = r#"  <key id="scalar" for="graph" attr.name="scalar" attr.type="string"/>
+  <key id="type" for="node" attr.name="type" attr.type="string"><default>Z</default></key>
+  <key id="phase" for="node" attr.name="phase" attr.type="string"><default>0</default></key>
+  <key id="qubit" for="node" attr.name="qubit" attr.type="int"><default>0</default></key>
+  <key id="row" for="node" attr.name="row" attr.type="int"><default>0</default></key>
+  <key id="input" for="node" attr.name="input" attr.type="int"><default>-1</default></key>
+  <key id="output" for="node" attr.name="output" attr.type="int"><default>-1</default></key>
+  <key id="etype" for="edge" attr.name="etype" attr.type="string"><default>N</default></key>
+"#;
+
+/// Returns the GraphML representation of a graph.
+pub fn encode_graph(graph: &impl GraphLike) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n";
+    xml += KEYS;
+    xml += "  <graph id=\"G\" edgedefault=\"undirected\">\n";
+
+    // this can only fail on serde errors, which JsonScalar never produces
+    let scalar = serde_json::to_string(&JsonScalar::from_scalar(graph.scalar())).unwrap();
+    writeln!(xml, "    <data key=\"scalar\">{}</data>", escape(&scalar)).unwrap();
+
+    let input_pos: FxHashMap<V, usize> = graph
+        .inputs()
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| (v, i))
+        .collect();
+    let output_pos: FxHashMap<V, usize> = graph
+        .outputs()
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| (v, i))
+        .collect();
+
+    for v in graph.vertices() {
+        let d = graph.vertex_data(v);
+        writeln!(xml, "    <node id=\"n{}\">", v).unwrap();
+        writeln!(xml, "      <data key=\"type\">{}</data>", vtype_name(d.ty)).unwrap();
+        writeln!(xml, "      <data key=\"phase\">{}</data>", d.phase).unwrap();
+        writeln!(xml, "      <data key=\"qubit\">{}</data>", d.qubit).unwrap();
+        writeln!(xml, "      <data key=\"row\">{}</data>", d.row).unwrap();
+        if let Some(i) = input_pos.get(&v) {
+            writeln!(xml, "      <data key=\"input\">{}</data>", i).unwrap();
+        }
+        if let Some(i) = output_pos.get(&v) {
+            writeln!(xml, "      <data key=\"output\">{}</data>", i).unwrap();
+        }
+        xml += "    </node>\n";
+    }
+
+    for (s, t, et) in graph.edges() {
+        writeln!(
+            xml,
+            "    <edge source=\"n{}\" target=\"n{}\"><data key=\"etype\">{}</data></edge>",
+            s,
+            t,
+            etype_name(et)
+        )
+        .unwrap();
+    }
+
+    xml += "  </graph>\n</graphml>\n";
+    xml
+}
+
+/// Writes the GraphML representation of a graph to a file.
+pub fn write_graph(graph: &impl GraphLike, filename: &Path) -> Result<(), GraphMLError> {
+    std::fs::write(filename, encode_graph(graph))?;
+    Ok(())
+}
+
+/// A declared GraphML attribute
+#[derive(Clone, Copy)]
+struct Key<'a> {
+    name: &'a str,
+    default: Option<&'a str>,
+}
+
+/// Collects the `<data>` values of an element, keyed by attribute name
+fn data_values<'a>(
+    node: roxmltree::Node<'a, '_>,
+    keys: &FxHashMap<&str, Key<'a>>,
+) -> FxHashMap<&'a str, &'a str> {
+    let mut values = FxHashMap::default();
+    for key in keys.values() {
+        if let Some(d) = key.default {
+            values.insert(key.name, d);
+        }
+    }
+    for data in node.children().filter(|n| n.has_tag_name("data")) {
+        if let Some(key) = data.attribute("key").and_then(|k| keys.get(k)) {
+            values.insert(key.name, data.text().unwrap_or("").trim());
+        }
+    }
+    values
+}
+
+fn parse_attr<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, GraphMLError> {
+    value.parse().map_err(|_| GraphMLError::InvalidAttribute {
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Reads a graph from its GraphML representation.
+///
+/// Node ids are not preserved, but the order of inputs and outputs is.
+pub fn decode_graph<G: GraphLike>(s: &str) -> Result<G, GraphMLError> {
+    let doc = roxmltree::Document::parse(s)?;
+    let root = doc.root_element();
+
+    let mut graph_keys: FxHashMap<&str, Key> = FxHashMap::default();
+    let mut node_keys: FxHashMap<&str, Key> = FxHashMap::default();
+    let mut edge_keys: FxHashMap<&str, Key> = FxHashMap::default();
+    for key in root.children().filter(|n| n.has_tag_name("key")) {
+        let id = key
+            .attribute("id")
+            .ok_or(GraphMLError::MissingAttribute("id"))?;
+        let name = key.attribute("attr.name").unwrap_or(id);
+        let default = key
+            .children()
+            .find(|n| n.has_tag_name("default"))
+            .and_then(|n| n.text())
+            .map(str::trim);
+        let k = Key { name, default };
+        match key.attribute("for").unwrap_or("all") {
+            "graph" => {
+                graph_keys.insert(id, k);
+            }
+            "node" => {
+                node_keys.insert(id, k);
+            }
+            "edge" => {
+                edge_keys.insert(id, k);
+            }
+            "all" => {
+                graph_keys.insert(id, k);
+                node_keys.insert(id, k);
+                edge_keys.insert(id, k);
+            }
+            _ => {}
+        }
+    }
+
+    let gnode = root
+        .children()
+        .find(|n| n.has_tag_name("graph"))
+        .ok_or(GraphMLError::MissingGraph)?;
+
+    let mut graph = G::new();
+    let mut ids: FxHashMap<&str, V> = FxHashMap::default();
+    let mut inputs: Vec<(i64, V)> = vec![];
+    let mut outputs: Vec<(i64, V)> = vec![];
+
+    for node in gnode.children().filter(|n| n.has_tag_name("node")) {
+        let id = node
+            .attribute("id")
+            .ok_or(GraphMLError::MissingAttribute("id"))?;
+        let values = data_values(node, &node_keys);
+
+        let ty = match values.get("type") {
+            Some(&t) => vtype_from_name(t).ok_or_else(|| GraphMLError::InvalidAttribute {
+                name: "type".to_string(),
+                value: t.to_string(),
+            })?,
+            None => VType::Z,
+        };
+        let phase = match values.get("phase") {
+            Some(&p) if !p.is_empty() => Phase::new(parse_attr::<Rational64>("phase", p)?),
+            // H-boxes default to phase pi, i.e. an ordinary Hadamard, as in pyzx
+            _ if ty == VType::H => Phase::one(),
+            _ => Phase::zero(),
+        };
+        let qubit = values
+            .get("qubit")
+            .map_or(Ok(0), |q| parse_attr("qubit", q))?;
+        let row = values.get("row").map_or(Ok(0), |r| parse_attr("row", r))?;
+
+        let v = graph.add_vertex_with_data(VData {
+            ty,
+            phase,
+            qubit,
+            row,
+        });
+        ids.insert(id, v);
+
+        let input: i64 = values
+            .get("input")
+            .map_or(Ok(-1), |i| parse_attr("input", i))?;
+        if input >= 0 {
+            inputs.push((input, v));
+        }
+        let output: i64 = values
+            .get("output")
+            .map_or(Ok(-1), |o| parse_attr("output", o))?;
+        if output >= 0 {
+            outputs.push((output, v));
+        }
+    }
+
+    let lookup = |id: &str| {
+        ids.get(id)
+            .copied()
+            .ok_or_else(|| GraphMLError::UnknownNode(id.to_string()))
+    };
+
+    for edge in gnode.children().filter(|n| n.has_tag_name("edge")) {
+        let s = edge
+            .attribute("source")
+            .ok_or(GraphMLError::MissingAttribute("source"))?;
+        let t = edge
+            .attribute("target")
+            .ok_or(GraphMLError::MissingAttribute("target"))?;
+        let values = data_values(edge, &edge_keys);
+        let et = match values.get("etype") {
+            Some(&e) => etype_from_name(e).ok_or_else(|| GraphMLError::InvalidAttribute {
+                name: "etype".to_string(),
+                value: e.to_string(),
+            })?,
+            None => EType::N,
+        };
+        graph.add_edge_smart(lookup(s)?, lookup(t)?, et);
+    }
+
+    inputs.sort();
+    outputs.sort();
+    graph.set_inputs(inputs.into_iter().map(|(_, v)| v).collect());
+    graph.set_outputs(outputs.into_iter().map(|(_, v)| v).collect());
+    graph.number_boundaries();
+
+    let values = data_values(gnode, &graph_keys);
+    if let Some(&s) = values.get("scalar") {
+        let scalar: JsonScalar = serde_json::from_str(s).map_err(JsonError::from)?;
+        *graph.scalar_mut() = scalar.to_scalar()?;
+    }
+
+    Ok(graph)
+}
+
+/// Reads a graph from a GraphML file.
+pub fn read_graph<G: GraphLike>(filename: &Path) -> Result<G, GraphMLError> {
+    let s = std::fs::read_to_string(filename)?;
+    decode_graph(&s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn graphml_roundtrip() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        crate::simplify::clifford_simp(&mut g);
+
+        let xml = encode_graph(&g);
+        let h: Graph = decode_graph(&xml).unwrap();
+
+        assert_eq!(g.num_vertices(), h.num_vertices());
+        assert_eq!(g.num_edges(), h.num_edges());
+        assert_eq!(g.tcount(), h.tcount());
+        for (v, w) in g.vertices().zip(h.vertices()) {
+            assert_eq!(g.vertex_data(v), h.vertex_data(w));
+        }
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn graphml_foreign() {
+        // a file from another tool, using its own key ids and no phases or types
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+              <key id="d0" for="node" attr.name="output" attr.type="int"/>
+              <key id="d1" for="node" attr.name="type" attr.type="string"/>
+              <key id="d2" for="edge" attr.name="etype" attr.type="string"/>
+              <graph edgedefault="undirected">
+                <node id="a"><data key="d1">B</data></node>
+                <node id="b"/>
+                <node id="c"><data key="d1">B</data><data key="d0">0</data></node>
+                <node id="d"><data key="d1">hadamard</data></node>
+                <edge source="a" target="b"/>
+                <edge source="b" target="c"><data key="d2">H</data></edge>
+              </graph>
+            </graphml>"#;
+        let g: Graph = decode_graph(xml).unwrap();
+        assert_eq!(g.num_vertices(), 4);
+        assert_eq!(g.num_edges(), 2);
+        assert_eq!(g.inputs().len(), 0);
+        assert_eq!(g.outputs().len(), 1);
+        assert_eq!(g.vertex_type(1), VType::Z);
+        assert_eq!(g.edge_type(1, 2), EType::H);
+        assert_eq!(g.phase(1), Phase::zero());
+        assert_eq!(g.vertex_type(3), VType::H);
+        assert_eq!(g.phase(3), Phase::one());
+    }
+
+    #[test]
+    fn graphml_errors() {
+        assert!(matches!(
+            decode_graph::<Graph>("<graphml"),
+            Err(GraphMLError::XmlError(_))
+        ));
+        assert!(matches!(
+            decode_graph::<Graph>("<graphml/>"),
+            Err(GraphMLError::MissingGraph)
+        ));
+        assert!(matches!(
+            decode_graph::<Graph>(
+                "<graphml><graph><node id=\"a\"/><edge source=\"a\" target=\"b\"/></graph></graphml>"
+            ),
+            Err(GraphMLError::UnknownNode(_))
+        ));
+    }
+}
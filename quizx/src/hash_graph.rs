@@ -18,13 +18,21 @@ pub use crate::graph::*;
 use crate::json::JsonGraph;
 use crate::phase::Phase;
 use crate::scalar::*;
+use indexmap::IndexMap;
 use num::rational::Rational64;
-use rustc_hash::FxHashMap;
+use rustc_hash::FxHasher;
 use serde::de::Error as _;
 use serde::ser::Error as _;
+use std::hash::BuildHasherDefault;
 use std::iter::FromIterator;
 
-pub type VTab<T> = FxHashMap<V, T>;
+/// A hash table which remembers the order of its entries
+///
+/// This makes iteration over vertices and neighbourhoods deterministic. Entries
+/// are iterated in insertion order, and removing an entry keeps the order of the
+/// others. Removal shifts the later entries down, so it takes time linear in the
+/// size of the table.
+pub type VTab<T> = IndexMap<V, T, BuildHasherDefault<FxHasher>>;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Graph {
@@ -36,11 +44,13 @@ pub struct Graph {
     nume: usize,
     freshv: V,
     scalar: ScalarN,
+    stats: GraphStats,
+    bqubits: BoundaryQubits,
 }
 
 pub struct EdgeIter<'a> {
-    outer: std::collections::hash_map::Iter<'a, V, VTab<EType>>,
-    inner: Option<(V, std::collections::hash_map::Iter<'a, V, EType>)>,
+    outer: indexmap::map::Iter<'a, V, VTab<EType>>,
+    inner: Option<(V, indexmap::map::Iter<'a, V, EType>)>,
 }
 
 impl<'a> Iterator for EdgeIter<'a> {
@@ -77,21 +87,43 @@ impl Graph {
     /// is used by remove_edge and remove_vertex to make the latter slightly
     /// more efficient.
     fn remove_half_edge(&mut self, s: V, t: V) {
-        self.edata.get_mut(&s).map(|nhd| nhd.remove(&t));
+        if let Some(nhd) = self.edata.get_mut(&s) {
+            if nhd.shift_remove(&t).is_some() {
+                self.stats.change_degree(nhd.len() + 1, nhd.len());
+            }
+        }
+    }
+
+    /// Inserts 't' into the adjacency map of 's', keeping the cached stats up to date.
+    fn add_half_edge(&mut self, s: V, t: V, ety: EType, err: &str) {
+        let nhd = self.edata.get_mut(&s).expect(err);
+        if nhd.insert(t, ety).is_none() {
+            self.stats.change_degree(nhd.len() - 1, nhd.len());
+        }
+    }
+
+    /// Applies `f` to the data of vertex `v`, keeping the cached stats up to date.
+    fn update_vdata(&mut self, v: V, f: impl FnOnce(&mut VData)) {
+        let d = self.vdata.get_mut(&v).expect("Vertex not found");
+        let old = *d;
+        f(d);
+        self.stats.update_vertex(&old, d);
     }
 }
 
 impl GraphLike for Graph {
     fn new() -> Graph {
         Graph {
-            vdata: FxHashMap::default(),
-            edata: FxHashMap::default(),
+            vdata: VTab::default(),
+            edata: VTab::default(),
             inputs: Vec::new(),
             outputs: Vec::new(),
             numv: 0,
             nume: 0,
             freshv: 0,
             scalar: Scalar::one(),
+            stats: GraphStats::default(),
+            bqubits: BoundaryQubits::default(),
         }
     }
 
@@ -147,8 +179,9 @@ impl GraphLike for Graph {
         let v = self.freshv;
         self.freshv += 1;
         self.numv += 1;
+        self.stats.add_vertex(&d);
         self.vdata.insert(v, d);
-        self.edata.insert(v, FxHashMap::default());
+        self.edata.insert(v, VTab::default());
         v
     }
 
@@ -160,21 +193,17 @@ impl GraphLike for Graph {
             self.remove_half_edge(v1, v);
         }
 
-        self.vdata.remove(&v);
-        self.edata.remove(&v);
+        let d = self.vdata.shift_remove(&v).expect("Vertex not found");
+        let nhd = self.edata.shift_remove(&v).expect("Vertex not found");
+        self.bqubits.remove(v);
+        self.stats.remove_vertex(&d, nhd.len());
     }
 
     fn add_edge_with_type(&mut self, s: V, t: V, ety: EType) {
         self.nume += 1;
 
-        self.edata
-            .get_mut(&s)
-            .expect("Source vertex not found")
-            .insert(t, ety);
-        self.edata
-            .get_mut(&t)
-            .expect("Target vertex not found")
-            .insert(s, ety);
+        self.add_half_edge(s, t, ety, "Source vertex not found");
+        self.add_half_edge(t, s, ety, "Target vertex not found");
     }
 
     fn remove_edge(&mut self, s: V, t: V) {
@@ -184,7 +213,8 @@ impl GraphLike for Graph {
     }
 
     fn set_phase(&mut self, v: V, phase: impl Into<Phase>) {
-        self.vdata.get_mut(&v).expect("Vertex not found").phase = phase.into();
+        let phase = phase.into();
+        self.update_vdata(v, |d| d.phase = phase);
     }
 
     fn phase(&self, v: V) -> Phase {
@@ -192,15 +222,12 @@ impl GraphLike for Graph {
     }
 
     fn add_to_phase(&mut self, v: V, phase: impl Into<Phase>) {
-        if let Some(d) = self.vdata.get_mut(&v) {
-            d.phase = (d.phase + phase.into()).normalize();
-        } else {
-            panic!("Vertex not found");
-        }
+        let phase = phase.into();
+        self.update_vdata(v, |d| d.phase = (d.phase + phase).normalize());
     }
 
     fn set_vertex_type(&mut self, v: V, ty: VType) {
-        self.vdata.get_mut(&v).expect("Vertex not found").ty = ty;
+        self.update_vdata(v, |d| d.ty = ty);
     }
 
     fn vertex_data(&self, v: V) -> VData {
@@ -281,6 +308,18 @@ impl GraphLike for Graph {
         &mut self.scalar
     }
 
+    fn stats(&self) -> &GraphStats {
+        &self.stats
+    }
+
+    fn boundary_qubits(&self) -> &BoundaryQubits {
+        &self.bqubits
+    }
+
+    fn boundary_qubits_mut(&mut self) -> &mut BoundaryQubits {
+        &mut self.bqubits
+    }
+
     fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
     where
         F: Fn(V, V, EType) -> bool,
@@ -436,4 +475,41 @@ mod tests {
         //     Vec::from_iter(h.edges()));
         assert_eq!(h.edge_type(vs[1], vs[2]), EType::H);
     }
+
+    #[test]
+    fn deterministic_iteration() {
+        let (g, vs) = simple_graph();
+        assert_eq!(g.vertex_vec(), vs);
+        assert_eq!(g.neighbor_vec(vs[2]), vec![vs[0], vs[4], vs[5]]);
+
+        // neighbours come in insertion order, not sorted by id
+        let mut g = Graph::new();
+        let vs: Vec<V> = (0..6).map(|_| g.add_vertex(VType::Z)).collect();
+        for i in [5, 1, 2, 4] {
+            g.add_edge(vs[0], vs[i]);
+        }
+        assert_eq!(g.neighbor_vec(vs[0]), vec![vs[5], vs[1], vs[2], vs[4]]);
+
+        // removing keeps the order of the other entries, and new entries go at the end
+        g.remove_vertex(vs[1]);
+        let v6 = g.add_vertex(VType::X);
+        g.add_edge(vs[0], v6);
+        assert_eq!(g.vertex_vec(), vec![vs[0], vs[2], vs[3], vs[4], vs[5], v6]);
+        assert_eq!(g.neighbor_vec(vs[0]), vec![vs[5], vs[2], vs[4], v6]);
+        assert_eq!(g.neighbor_vec(vs[4]), vec![vs[0]]);
+
+        let c = crate::circuit::Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(50)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        let mut h: Graph = c.to_graph();
+        crate::simplify::full_simp(&mut g);
+        crate::simplify::full_simp(&mut h);
+        assert_eq!(g.vertex_vec(), h.vertex_vec());
+        assert_eq!(g.edge_vec(), h.edge_vec());
+    }
 }
@@ -0,0 +1,236 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A journal of applied rewrites, with undo
+//!
+//! [History] wraps a graph and records every rewrite applied through it: the
+//! name of the rule, the vertices it was applied to, and the scalar before and
+//! after. Any number of steps can then be rolled back, which is handy for
+//! backtracking search and for finding the step where an unsound rewrite
+//! changed the diagram.
+//!
+//! Undo works by keeping a copy of the graph from before each step. With
+//! [crate::persistent_graph::Graph] these copies share almost all of their
+//! structure, so a long history is cheap. For the other backends, the number
+//! of steps kept can be bounded with [History::with_limit].
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::graph::*;
+//! # use quizx::history::History;
+//! # use quizx::simplify::find_spider_fusion;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[1]; t q[0]; s q[0]; t q[0];").unwrap();
+//! let g: Graph = c.to_graph();
+//! let mut h = History::new(g.clone());
+//! while let Some(m) = find_spider_fusion(h.graph()) {
+//!     h.apply(m);
+//! }
+//! assert_eq!(h.len(), 2);
+//! assert_eq!(h.entries()[0].rule, "spider_fusion");
+//! h.undo(2);
+//! assert_eq!(h.graph(), &g);
+//! ```
+
+use crate::graph::*;
+use crate::scalar::*;
+use crate::simplify::RuleMatch;
+use std::collections::VecDeque;
+
+/// A record of a single rewrite
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The name of the rule
+    pub rule: &'static str,
+    /// The vertices the rule was applied to
+    pub vertices: Vec<V>,
+    /// The scalar of the graph before the rewrite
+    pub scalar_before: ScalarN,
+    /// The scalar of the graph after the rewrite
+    pub scalar_after: ScalarN,
+}
+
+/// A graph together with a journal of the rewrites applied to it
+#[derive(Debug, Clone)]
+pub struct History<G: GraphLike> {
+    g: G,
+    steps: VecDeque<(Entry, G)>,
+    limit: Option<usize>,
+}
+
+impl<G: GraphLike> History<G> {
+    /// Start recording rewrites on `g`, keeping every step
+    pub fn new(g: G) -> Self {
+        History {
+            g,
+            steps: VecDeque::new(),
+            limit: None,
+        }
+    }
+
+    /// Start recording rewrites on `g`, keeping only the last `limit` steps
+    pub fn with_limit(g: G, limit: usize) -> Self {
+        History {
+            g,
+            steps: VecDeque::new(),
+            limit: Some(limit),
+        }
+    }
+
+    /// The current graph
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    /// Stop recording and return the current graph
+    pub fn into_graph(self) -> G {
+        self.g
+    }
+
+    /// The recorded steps which can still be undone, oldest first
+    pub fn entries(&self) -> Vec<&Entry> {
+        self.steps.iter().map(|(e, _)| e).collect()
+    }
+
+    /// The number of steps which can be undone
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Returns true if there is nothing to undo
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Apply a rule at a match, and record it if it applied
+    pub fn apply<M: RuleMatch>(&mut self, m: M) -> bool {
+        self.record(M::NAME, &m.vertices(), |g| m.apply(g))
+    }
+
+    /// Run an arbitrary rewrite, and record it under the given name
+    ///
+    /// The closure should return false if it did not change the graph, in which
+    /// case nothing is recorded.
+    pub fn record<F>(&mut self, rule: &'static str, vertices: &[V], f: F) -> bool
+    where
+        F: FnOnce(&mut G) -> bool,
+    {
+        let before = self.g.clone();
+        if !f(&mut self.g) {
+            return false;
+        }
+
+        let entry = Entry {
+            rule,
+            vertices: vertices.to_vec(),
+            scalar_before: before.scalar().clone(),
+            scalar_after: self.g.scalar().clone(),
+        };
+        self.steps.push_back((entry, before));
+        if let Some(limit) = self.limit {
+            while self.steps.len() > limit {
+                self.steps.pop_front();
+            }
+        }
+        true
+    }
+
+    /// Roll back the last `n` steps, or as many as there are
+    ///
+    /// Returns the number of steps actually undone.
+    pub fn undo(&mut self, n: usize) -> usize {
+        let n = n.min(self.steps.len());
+        if n > 0 {
+            self.steps.truncate(self.steps.len() - n + 1);
+            let (_, g) = self.steps.pop_back().unwrap();
+            self.g = g;
+        }
+        n
+    }
+
+    /// Forget all recorded steps, keeping the current graph
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::*;
+    use crate::tensor::ToTensor;
+
+    fn history<G: GraphLike + PartialEq>() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: G = c.to_graph();
+        g.x_to_z();
+        let mut h = History::new(g.clone());
+
+        let mut snapshots = vec![g.clone()];
+        while let Some(m) = find_spider_fusion(h.graph()) {
+            assert!(h.apply(m));
+            snapshots.push(h.graph().clone());
+        }
+        while let Some(m) = find_remove_id(h.graph()) {
+            assert!(h.apply(m));
+            snapshots.push(h.graph().clone());
+        }
+        let n = h.len();
+        assert!(n > 2);
+        assert_eq!(n + 1, snapshots.len());
+        assert_eq!(h.entries()[0].rule, "spider_fusion");
+        assert_eq!(h.entries()[n - 1].rule, "remove_id");
+        assert_eq!(g.to_tensor4(), h.graph().to_tensor4());
+
+        assert_eq!(h.undo(2), 2);
+        assert_eq!(h.graph(), &snapshots[n - 2]);
+        assert_eq!(h.len(), n - 2);
+        assert_eq!(h.undo(n), n - 2);
+        assert_eq!(h.graph(), &g);
+        assert!(h.is_empty());
+    }
+
+    #[test]
+    fn history_vec_graph() {
+        history::<crate::vec_graph::Graph>();
+    }
+
+    #[test]
+    fn history_persistent_graph() {
+        history::<crate::persistent_graph::Graph>();
+    }
+
+    #[test]
+    fn history_limit() {
+        let c = Circuit::from_qasm("qreg q[1]; t q[0]; s q[0]; t q[0]; z q[0];").unwrap();
+        let g: crate::vec_graph::Graph = c.to_graph();
+        let mut h = History::with_limit(g, 2);
+        assert!(!h.record("nothing", &[], |_| false));
+        while let Some(m) = find_spider_fusion(h.graph()) {
+            h.apply(m);
+        }
+        assert_eq!(h.len(), 2);
+        assert_eq!(h.undo(5), 2);
+        assert_eq!(h.graph().num_vertices(), 5);
+    }
+}
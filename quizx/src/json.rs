@@ -16,6 +16,8 @@
 
 //! Json encoding for interoperability with pyzx and Quantomatic using the .qgraph format.
 //!
+//! Circuits can also be encoded, using the schema described in [encode_circuit].
+//!
 //! # Examples
 //!
 //! ```rust
@@ -45,12 +47,16 @@
 //! assert_eq!(g.to_tensor4(), g2.to_tensor4());
 //! ```
 
+mod circuit;
 mod graph;
 mod phase;
+mod pyzx;
 mod scalar;
 
 pub use phase::PhaseOptions;
+pub use pyzx::PyzxGraph;
 
+use crate::circuit::Circuit;
 use crate::graph::VType;
 use crate::hash_graph::{EType, GraphLike};
 
@@ -88,6 +94,66 @@ pub fn read_graph<G: GraphLike>(filename: &Path) -> Result<G, JsonError> {
     jg.to_graph()
 }
 
+/// Returns the representation of a graph in the json format used by `pyzx.Graph.to_json`.
+pub fn encode_pyzx_graph(graph: &impl crate::graph::GraphLike) -> Result<String, JsonError> {
+    let pg = PyzxGraph::from_graph(graph);
+    let s = serde_json::to_string(&pg)?;
+    Ok(s)
+}
+
+/// Writes a graph to a file in the json format used by `pyzx.Graph.to_json`.
+pub fn write_pyzx_graph(
+    graph: &impl crate::graph::GraphLike,
+    filename: &Path,
+) -> Result<(), JsonError> {
+    let pg = PyzxGraph::from_graph(graph);
+    let file = std::fs::File::create(filename).unwrap();
+    let writer = std::io::BufWriter::new(file);
+    serde_json::to_writer(writer, &pg)?;
+    Ok(())
+}
+
+/// Reads a graph from the json format used by `pyzx.Graph.to_json`.
+pub fn decode_pyzx_graph<G: GraphLike>(s: &str) -> Result<G, JsonError> {
+    let pg: PyzxGraph = serde_json::from_str(s)?;
+    pg.to_graph()
+}
+
+/// Reads a graph from a file in the json format used by `pyzx.Graph.to_json`.
+pub fn read_pyzx_graph<G: GraphLike>(filename: &Path) -> Result<G, JsonError> {
+    let file = std::fs::File::open(filename).unwrap();
+    let reader = std::io::BufReader::new(file);
+    let pg: PyzxGraph = serde_json::from_reader(reader)?;
+    pg.to_graph()
+}
+
+/// Returns the json-encoded representation of a circuit.
+///
+/// [Circuit] and [Gate](crate::gate::Gate) implement [Serialize] and
+/// [Deserialize] with a stable schema, so they can also be embedded in other
+/// serde formats. A circuit is encoded as its number of qubits and its gates:
+///
+/// ```json
+/// {"qubits": 2, "gates": [
+///     {"name": "h", "qubits": [0]},
+///     {"name": "rz", "qubits": [1], "phase": [1, 4], "params": {"a": 1}},
+///     {"name": "measure", "qubits": [0], "bits": [0]},
+///     {"name": "x", "qubits": [1], "condition": {"bits": [0], "value": 1}}
+/// ]}
+/// ```
+///
+/// Gates are named as in QASM. The phase is a fraction `[numer, denom]` of pi,
+/// and `params` maps each free parameter to its coefficient. The `phase`,
+/// `params`, `bits` and `condition` fields are omitted when they are empty.
+pub fn encode_circuit(c: &Circuit) -> Result<String, JsonError> {
+    Ok(serde_json::to_string(c)?)
+}
+
+/// Reads a circuit from its json-encoded representation, see [encode_circuit].
+pub fn decode_circuit(s: &str) -> Result<Circuit, JsonError> {
+    Ok(serde_json::from_str(s)?)
+}
+
 /// Identifier for an encoded vertex.
 type VertexName = String;
 /// Identifier for an encoded edge.
@@ -339,6 +405,34 @@ pub enum JsonError {
     /// Found an invalid phase value in a node definition.
     #[error("Got an invalid phase value {phase} for node {name}")]
     InvalidNodePhase { name: String, phase: String },
+    /// Found an unknown vertex type.
+    #[error("Got an invalid vertex type {typ} for node {id}")]
+    InvalidVertexType { id: usize, typ: u8 },
+    /// Found an unknown edge type.
+    #[error("Got an invalid edge type {typ}")]
+    InvalidEdgeType { typ: u8 },
+    /// An edge or boundary refers to a vertex that does not exist.
+    #[error("Reference to unknown vertex {id}")]
+    UnknownVertex { id: usize },
+    /// Found a gate name which quizx does not know.
+    #[error("Got an unknown gate {name}")]
+    UnknownGate { name: String },
+    /// A gate has the wrong number of qubits or classical bits for its type.
+    #[error("Gate {name} cannot act on {qubits} qubits and {bits} bits")]
+    InvalidGateArity {
+        name: String,
+        qubits: usize,
+        bits: usize,
+    },
+    /// A gate refers to a qubit outside of the circuit.
+    #[error("Reference to unknown qubit {q}")]
+    UnknownQubit { q: usize },
+    /// The graph uses symbolic phases, which are not supported.
+    #[error("Variables are not currently supported")]
+    UnsupportedVariables,
+    /// The graph has a grounded vertex, which is not supported.
+    #[error("Got a grounded node {id}, which is not supported")]
+    GroundedVertex { id: usize },
     /// Some other serde error.
     #[error(transparent)]
     SerdeError(#[from] serde_json::Error),
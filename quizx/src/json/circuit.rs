@@ -0,0 +1,221 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serde implementations for circuits and gates, see [super::encode_circuit] for
+//! the schema.
+
+use super::JsonError;
+use crate::circuit::Circuit;
+use crate::gate::{Condition, GType, Gate, Measure, UnknownGate};
+use crate::phase::Phase;
+use crate::symbolic::ParamSum;
+
+use num::Zero;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// The json encoding of a circuit.
+#[derive(Serialize, Deserialize)]
+struct JsonCircuit {
+    qubits: usize,
+    gates: Vec<Gate>,
+}
+
+/// The json encoding of a gate.
+#[derive(Serialize, Deserialize)]
+struct JsonGate {
+    /// The QASM name of the gate type.
+    name: String,
+    qubits: Vec<usize>,
+    /// The phase as a fraction of pi, omitted when it is zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    phase: Option<[i64; 2]>,
+    /// The coefficient of each free parameter in the phase.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<String, i64>,
+    /// The classical bits written by a measurement.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bits: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    condition: Option<JsonCondition>,
+}
+
+/// The json encoding of a classical condition.
+#[derive(Serialize, Deserialize)]
+struct JsonCondition {
+    bits: Vec<usize>,
+    value: u64,
+}
+
+impl JsonGate {
+    fn from_gate(g: &Gate) -> Self {
+        let phase = (!g.phase.is_zero()).then(|| {
+            let r = g.phase.to_rational();
+            [*r.numer(), *r.denom()]
+        });
+        JsonGate {
+            name: g.qasm_name().to_string(),
+            qubits: g.qs.clone(),
+            phase,
+            params: g.params.iter().map(|(p, c)| (p.to_string(), c)).collect(),
+            bits: g.bits.clone(),
+            condition: g.condition.as_ref().map(|c| JsonCondition {
+                bits: c.bits.clone(),
+                value: c.value,
+            }),
+        }
+    }
+
+    fn into_gate(self) -> Result<Gate, JsonError> {
+        let t = GType::from_qasm_name(&self.name);
+        if t == UnknownGate && self.name != UnknownGate.qasm_name() {
+            return Err(JsonError::UnknownGate { name: self.name });
+        }
+        let arity_ok = t.num_qubits().map_or(true, |n| n == self.qubits.len());
+        let bits_ok = if t == Measure {
+            self.bits.len() == self.qubits.len()
+        } else {
+            self.bits.is_empty()
+        };
+        if !arity_ok || !bits_ok {
+            return Err(JsonError::InvalidGateArity {
+                name: self.name,
+                qubits: self.qubits.len(),
+                bits: self.bits.len(),
+            });
+        }
+
+        let phase = match self.phase {
+            None => Phase::zero(),
+            Some([n, 0]) => {
+                return Err(JsonError::InvalidPhase {
+                    phase: format!("{}/0", n),
+                })
+            }
+            Some([n, d]) => Phase::from((n, d)),
+        };
+        let mut params = ParamSum::default();
+        for (p, c) in &self.params {
+            params.add(p, *c);
+        }
+
+        Ok(Gate {
+            t,
+            qs: self.qubits,
+            phase,
+            params,
+            bits: self.bits,
+            condition: self.condition.map(|c| Condition {
+                bits: c.bits,
+                value: c.value,
+            }),
+        })
+    }
+}
+
+impl Serialize for Gate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        JsonGate::from_gate(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Gate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JsonGate::deserialize(deserializer)?
+            .into_gate()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Circuit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        JsonCircuit {
+            qubits: self.num_qubits(),
+            gates: self.gates.iter().cloned().collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Circuit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let jc = JsonCircuit::deserialize(deserializer)?;
+        let mut c = Circuit::new(jc.qubits);
+        for g in jc.gates {
+            if let Some(&q) = g.qs.iter().find(|&&q| q >= jc.qubits) {
+                return Err(de::Error::custom(JsonError::UnknownQubit { q }));
+            }
+            c.push(g);
+        }
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{decode_circuit, encode_circuit};
+    use super::*;
+    use crate::gate::*;
+    use num::Rational64;
+
+    #[test]
+    fn circuit_roundtrip() {
+        let mut c = Circuit::from_qasm(
+            "qreg q[3];
+            creg c[2];
+            h q[0];
+            rz(3*pi/4) q[1];
+            ccx q[0], q[1], q[2];
+            barrier q[0], q[2];
+            measure q[0] -> c[1];
+            if(c==2) x q[2];",
+        )
+        .unwrap();
+        let mut g = Gate::new(ZPhase, vec![2]);
+        g.params.add("a", 1);
+        g.params.add("b", -1);
+        c.push(g);
+
+        let s = encode_circuit(&c).unwrap();
+        assert_eq!(decode_circuit(&s).unwrap(), c);
+    }
+
+    #[test]
+    fn schema() {
+        let mut c = Circuit::new(2);
+        c.push(Gate::new_with_phase(ZPhase, vec![1], Rational64::new(1, 4)));
+        c.push(Gate::new_measure(1, 0));
+        c.push(Gate::new(CNOT, vec![1, 0]).with_condition(vec![0], 1));
+        let s = encode_circuit(&c).unwrap();
+        assert_eq!(
+            s,
+            r#"{"qubits":2,"gates":[{"name":"rz","qubits":[1],"phase":[1,4]},{"name":"measure","qubits":[1],"bits":[0]},{"name":"cx","qubits":[1,0],"condition":{"bits":[0],"value":1}}]}"#
+        );
+    }
+
+    #[test]
+    fn invalid_circuits() {
+        for s in [
+            r#"{"qubits":1,"gates":[{"name":"foo","qubits":[0]}]}"#,
+            r#"{"qubits":1,"gates":[{"name":"h","qubits":[1]}]}"#,
+            r#"{"qubits":2,"gates":[{"name":"cx","qubits":[0]}]}"#,
+            r#"{"qubits":1,"gates":[{"name":"measure","qubits":[0]}]}"#,
+            r#"{"qubits":1,"gates":[{"name":"rz","qubits":[0],"phase":[1,0]}]}"#,
+        ] {
+            assert!(decode_circuit(s).is_err(), "{}", s);
+        }
+    }
+}
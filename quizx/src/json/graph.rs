@@ -231,6 +231,7 @@ impl JsonGraph {
         }
         graph.set_inputs(inputs.into_values().map(|name| names[name]).collect());
         graph.set_outputs(outputs.into_values().map(|name| names[name]).collect());
+        graph.number_boundaries();
 
         // Insert the edges.
         for attrs in self.undir_edges.values() {
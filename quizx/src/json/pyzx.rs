@@ -0,0 +1,318 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The graph format produced by `pyzx.Graph.to_json` and read by `pyzx.Graph.from_json`.
+//!
+//! Unlike the .qgraph format, this encodes vertices and edges as lists, with vertex
+//! and edge types given by pyzx's integer constants, and keeps the ordering of inputs
+//! and outputs. Encoding writes out the vertex ids of the graph, but decoding adds the
+//! vertices to a new graph, so they get fresh ids.
+//!
+//! Symbolic phases and grounded vertices are not supported, and decoding a graph which
+//! uses them fails.
+
+use num::{One, Zero};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::phase::PhaseOptions;
+use super::{is_default, JsonError, JsonPhase, JsonScalar};
+use crate::graph::{EType, GraphLike, VData, VType, V};
+use crate::phase::Phase;
+
+/// The json format version written by pyzx 0.8.
+const PYZX_VERSION: u32 = 2;
+
+/// The json-encoded format used by `pyzx.Graph.to_json`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct PyzxGraph {
+    /// Format version.
+    #[serde(default)]
+    version: u32,
+    /// The name of the pyzx backend which produced the graph.
+    #[serde(default)]
+    backend: String,
+    /// Types of the variables in the graph.
+    ///
+    /// Variables are not supported by quizx, so decoding fails if there are any.
+    #[serde(default)]
+    variable_types: HashMap<String, String>,
+    /// The graph scalar.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scalar: Option<JsonScalar>,
+    /// Ids of the input vertices, in order.
+    #[serde(default)]
+    inputs: Vec<usize>,
+    /// Ids of the output vertices, in order.
+    #[serde(default)]
+    outputs: Vec<usize>,
+    /// The vertices of the graph.
+    #[serde(default)]
+    vertices: Vec<PyzxVertex>,
+    /// The edges of the graph, as triples (source, target, type).
+    #[serde(default)]
+    edges: Vec<(usize, usize, u8)>,
+}
+
+/// A vertex in the pyzx json format.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+struct PyzxVertex {
+    /// The vertex id.
+    id: usize,
+    /// The vertex type, as a pyzx `VertexType` constant.
+    t: u8,
+    /// Position of the vertex, as (row, qubit).
+    #[serde(default)]
+    pos: (f64, f64),
+    /// The vertex phase.
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    phase: JsonPhase,
+    /// A flag marking grounded nodes.
+    ///
+    /// Grounds are not supported by quizx, so this is always false when encoding,
+    /// and decoding fails if it is set.
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    ground: bool,
+}
+
+/// Returns the pyzx `VertexType` constant for a vertex type.
+fn vtype_to_pyzx(ty: VType) -> u8 {
+    match ty {
+        VType::B => 0,
+        VType::Z => 1,
+        VType::X => 2,
+        VType::H => 3,
+        VType::WInput => 4,
+        VType::WOutput => 5,
+        VType::ZBox => 6,
+    }
+}
+
+/// Returns the vertex type for a pyzx `VertexType` constant.
+fn vtype_from_pyzx(t: u8) -> Option<VType> {
+    match t {
+        0 => Some(VType::B),
+        1 => Some(VType::Z),
+        2 => Some(VType::X),
+        3 => Some(VType::H),
+        4 => Some(VType::WInput),
+        5 => Some(VType::WOutput),
+        6 => Some(VType::ZBox),
+        _ => None,
+    }
+}
+
+/// Returns the pyzx `EdgeType` constant for an edge type.
+fn etype_to_pyzx(ty: EType) -> u8 {
+    match ty {
+        EType::N => 1,
+        EType::H => 2,
+        EType::Wio => 3,
+    }
+}
+
+/// Returns the edge type for a pyzx `EdgeType` constant.
+fn etype_from_pyzx(t: u8) -> Option<EType> {
+    match t {
+        1 => Some(EType::N),
+        2 => Some(EType::H),
+        3 => Some(EType::Wio),
+        _ => None,
+    }
+}
+
+impl PyzxGraph {
+    /// Encode a graph using the pyzx json representation.
+    pub fn from_graph(graph: &impl GraphLike) -> Self {
+        let vertices = graph
+            .vertices()
+            .map(|v| {
+                let ty = graph.vertex_type(v);
+                // pyzx omits phases equal to the default for the vertex type.
+                let phase_options = PhaseOptions {
+                    ignore_value: Some(match ty == VType::H {
+                        true => Phase::one(),
+                        false => Phase::zero(),
+                    }),
+                    ..Default::default()
+                };
+                PyzxVertex {
+                    id: v,
+                    t: vtype_to_pyzx(ty),
+                    pos: (graph.row(v) as f64, graph.qubit(v) as f64),
+                    phase: JsonPhase::from_phase(graph.phase(v), phase_options),
+                    ground: false,
+                }
+            })
+            .collect();
+
+        let edges = graph
+            .edges()
+            .map(|(s, t, ty)| (s, t, etype_to_pyzx(ty)))
+            .collect();
+
+        Self {
+            version: PYZX_VERSION,
+            backend: "simple".to_string(),
+            variable_types: Default::default(),
+            scalar: Some(JsonScalar::from_scalar(graph.scalar())),
+            inputs: graph.inputs().clone(),
+            outputs: graph.outputs().clone(),
+            vertices,
+            edges,
+        }
+    }
+
+    /// Decode a graph from the pyzx json representation.
+    ///
+    /// Vertex ids are not preserved, but the order of inputs and outputs is.
+    ///
+    /// Returns an error if the graph has variables or grounded vertices.
+    pub fn to_graph<G: GraphLike>(&self) -> Result<G, JsonError> {
+        let mut graph = G::new();
+
+        if !self.variable_types.is_empty() {
+            return Err(JsonError::UnsupportedVariables);
+        }
+
+        let mut ids: FxHashMap<usize, V> = FxHashMap::default();
+        for vert in &self.vertices {
+            if vert.ground {
+                return Err(JsonError::GroundedVertex { id: vert.id });
+            }
+            let ty = vtype_from_pyzx(vert.t).ok_or(JsonError::InvalidVertexType {
+                id: vert.id,
+                typ: vert.t,
+            })?;
+            let phase = vert
+                .phase
+                .to_phase()
+                .map_err(|_| JsonError::InvalidNodePhase {
+                    name: vert.id.to_string(),
+                    phase: vert.phase.0.clone(),
+                })?;
+            let phase = match (phase, ty) {
+                (Some(p), _) => p,
+                (None, VType::H) => Phase::one(),
+                (None, _) => Phase::zero(),
+            };
+            let v = graph.add_vertex_with_data(VData {
+                ty,
+                phase,
+                qubit: vert.pos.1.round() as i32,
+                row: vert.pos.0.round() as i32,
+            });
+            ids.insert(vert.id, v);
+        }
+
+        let lookup = |id: usize| ids.get(&id).copied().ok_or(JsonError::UnknownVertex { id });
+
+        for &(s, t, ty) in &self.edges {
+            let ety = etype_from_pyzx(ty).ok_or(JsonError::InvalidEdgeType { typ: ty })?;
+            graph.add_edge_smart(lookup(s)?, lookup(t)?, ety);
+        }
+
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|&i| lookup(i))
+            .collect::<Result<_, _>>()?;
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|&o| lookup(o))
+            .collect::<Result<_, _>>()?;
+        graph.set_inputs(inputs);
+        graph.set_outputs(outputs);
+        graph.number_boundaries();
+
+        if let Some(scalar) = &self.scalar {
+            *graph.scalar_mut() = scalar.to_scalar()?;
+        }
+
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::json::{decode_pyzx_graph, encode_pyzx_graph};
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn pyzx_roundtrip() -> Result<(), JsonError> {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        crate::simplify::clifford_simp(&mut g);
+
+        let s = encode_pyzx_graph(&g)?;
+        let h: Graph = decode_pyzx_graph(&s)?;
+
+        assert_eq!(g.num_vertices(), h.num_vertices());
+        assert_eq!(g.num_edges(), h.num_edges());
+        assert_eq!(g.inputs().len(), h.inputs().len());
+        assert_eq!(g.outputs().len(), h.outputs().len());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        Ok(())
+    }
+
+    #[test]
+    fn pyzx_decode() -> Result<(), JsonError> {
+        // A CZ gate, as written by pyzx 0.8
+        let json = r#"{
+            "version": 2, "backend": "simple", "variable_types": {},
+            "scalar": {"power2": 1, "phase": "0"},
+            "inputs": [0, 1], "outputs": [4, 5],
+            "vertices": [
+                {"id": 0, "t": 0, "pos": [0, 0]},
+                {"id": 1, "t": 0, "pos": [0, 1]},
+                {"id": 2, "t": 1, "pos": [1, 0]},
+                {"id": 3, "t": 1, "pos": [1, 1], "phase": "0"},
+                {"id": 4, "t": 0, "pos": [2, 0]},
+                {"id": 5, "t": 0, "pos": [2, 1]}
+            ],
+            "edges": [[0, 2, 1], [1, 3, 1], [2, 3, 2], [2, 4, 1], [3, 5, 1]]
+        }"#;
+        let g: Graph = decode_pyzx_graph(json)?;
+        let c = Circuit::from_qasm("qreg q[2]; cz q[0], q[1];").unwrap();
+        assert_eq!(g.to_tensor4(), c.to_tensor4());
+        Ok(())
+    }
+
+    #[test]
+    fn pyzx_unsupported() {
+        let json = r#"{"variable_types": {"a": "continuous"}, "vertices": []}"#;
+        let g: Result<Graph, _> = decode_pyzx_graph(json);
+        assert!(matches!(g, Err(JsonError::UnsupportedVariables)));
+
+        let json = r#"{"vertices": [{"id": 3, "t": 1, "ground": true}]}"#;
+        let g: Result<Graph, _> = decode_pyzx_graph(json);
+        assert!(matches!(g, Err(JsonError::GroundedVertex { id: 3 })));
+    }
+}
@@ -22,29 +22,32 @@ impl JsonScalar {
             ignore_pi: true,
             ..Default::default()
         };
-        match scalar {
-            Scalar::Float(complex) => {
-                let (r, theta) = complex.to_polar();
-                // Encoding `theta` as a `Phase` here converts it to a fractional value,
-                // which may cause a loss of precision.
-                let phase = JsonPhase::from_phase(theta / PI, phase_options);
-                JsonScalar {
-                    phase,
-                    floatfactor: r,
-                    is_zero: scalar.is_zero(),
+        if scalar.is_exact() && !scalar.is_zero() {
+            // Pyzx stores exact scalars as a power of sqrt(2) times a phase. Scalars
+            // which do not have this form fall back to the floating point encoding.
+            let power2 = (2.0 * scalar.complex_value().norm().log2()).round() as i32;
+            let phase = scalar.phase().limit_denominator(1 << 16);
+            let mut s = Scalar::from_phase(phase);
+            s.mul_sqrt2_pow(power2);
+            if s == *scalar {
+                return JsonScalar {
+                    power2,
+                    phase: JsonPhase::from_phase(phase, phase_options),
                     ..Default::default()
-                }
-            }
-            Scalar::Exact(pow, _) => {
-                let phase = JsonPhase::from_phase(scalar.phase(), phase_options);
-                JsonScalar {
-                    power2: *pow,
-                    phase,
-                    is_zero: scalar.is_zero(),
-                    ..Default::default()
-                }
+                };
             }
         }
+
+        let (r, theta) = scalar.complex_value().to_polar();
+        // Encoding `theta` as a `Phase` here converts it to a fractional value,
+        // which may cause a loss of precision.
+        let phase = JsonPhase::from_phase(theta / PI, phase_options);
+        JsonScalar {
+            phase,
+            floatfactor: r,
+            is_zero: scalar.is_zero(),
+            ..Default::default()
+        }
     }
 
     /// Returns an scalar marked as "unknown".
@@ -90,7 +93,7 @@ impl JsonScalar {
 mod test {
     use rstest::rstest;
 
-    use crate::scalar::ScalarN;
+    use crate::scalar::{ScalarN, Sqrt2};
 
     use super::*;
 
@@ -100,12 +103,18 @@ mod test {
     #[case(ScalarN::from_phase(1))]
     #[case(ScalarN::from_phase((1,2)))]
     #[case(ScalarN::from_phase((-1,2)))]
+    #[case(ScalarN::sqrt2_pow(3))]
+    #[case(ScalarN::sqrt2_pow(-2) * ScalarN::from_phase((1,4)))]
+    #[case(ScalarN::one() + ScalarN::sqrt2_pow(1))]
     #[case(ScalarN::real(2.0))]
     #[case(ScalarN::complex(1.0, 1.0))]
     fn scalar_roundtrip(#[case] scalar: ScalarN) -> Result<(), JsonError> {
         let json_scalar = JsonScalar::from_scalar(&scalar);
         let decoded: ScalarN = json_scalar.to_scalar()?;
         assert!(decoded.approx_eq(&scalar, 1e-6));
+        if scalar.is_exact() && json_scalar.floatfactor.is_zero() {
+            assert_eq!(decoded, scalar);
+        }
 
         Ok(())
     }
@@ -14,21 +14,69 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod affine;
 pub mod annealer;
+pub mod architecture;
 pub mod basic_rules;
+pub mod beam;
+pub mod binary;
+pub mod ccz;
 pub mod circuit;
+pub mod cirq;
+pub mod clifford;
+pub mod compact_graph;
+pub mod controlled;
+pub mod custom_rule;
+pub mod dag;
 pub mod decompose;
+pub mod draw;
 pub mod extract;
+pub mod flow;
 pub mod gate;
 pub mod generate;
+#[cfg(feature = "gpu")]
+pub mod gpu_tensor;
 pub mod graph;
+pub mod graphml;
 pub mod hash_graph;
+pub mod history;
 pub mod json;
 pub mod linalg;
+pub mod mbqc;
+pub mod measurement;
+pub mod mixed;
+pub mod moments;
 pub mod optimize_circuit;
+pub mod pauli_simp;
+pub mod persistent_graph;
+pub mod petgraph_conv;
 pub mod phase;
+pub mod phase_poly;
+pub mod provenance;
+pub mod qasm3;
+pub mod qasm_export;
+pub mod qc;
+pub mod qec;
+#[cfg(feature = "qir")]
+pub mod qir;
+pub mod quil;
+pub mod quipper;
 pub mod random_graph;
+pub mod rebase;
+pub mod region;
 pub mod scalar;
 pub mod simplify;
+pub mod simulate;
+pub mod spider_nest;
+pub mod strategy;
+pub mod svg;
+pub mod symbolic;
+pub mod tableau;
+pub mod tbound;
+pub mod teleport;
 pub mod tensor;
+pub mod tket;
+pub mod toffoli;
+pub mod trace;
 pub mod vec_graph;
+pub mod verify;
@@ -16,8 +16,9 @@
 
 //! Matrices and linear algebra over F2
 
+use crate::circuit::Circuit;
 use rustc_hash::FxHashMap;
-use std::cmp::min;
+use std::cmp::{max, min};
 use std::fmt;
 
 /// A type for matrices over F2
@@ -46,6 +47,19 @@ impl RowOps for () {
     fn row_swap(&mut self, _: usize, _: usize) {}
 }
 
+/// Counts row operations, to compare eliminations without recording them
+#[derive(Default)]
+struct CountOps(usize);
+
+impl RowOps for CountOps {
+    fn row_add(&mut self, _: usize, _: usize) {
+        self.0 += 1;
+    }
+    fn row_swap(&mut self, _: usize, _: usize) {
+        self.0 += 1;
+    }
+}
+
 impl Mat2 {
     pub fn new(d: Vec<Vec<u8>>) -> Mat2 {
         Mat2 { d }
@@ -239,6 +253,23 @@ impl Mat2 {
         self.gauss_helper(full_reduce, blocksize, x, &mut vec![])
     }
 
+    /// The blocksize for [Mat2::gauss_x] which gives the fewest row operations
+    ///
+    /// Patel/Markov/Hayes show that blocks of about log2(n)/2 columns need
+    /// O(n^2/log(n)) row operations for an n x n matrix, compared to O(n^2) for
+    /// plain Gaussian elimination. For small matrices the best blocksize varies, so
+    /// this tries each one from 1 up to the larger of 3 and log2(n)/2.
+    pub fn pmh_blocksize(&self, full_reduce: bool) -> usize {
+        let log_n = (usize::BITS - self.num_cols().leading_zeros()) as usize;
+        (1..=max(3, log_n / 2))
+            .min_by_key(|&blocksize| {
+                let mut ops = CountOps::default();
+                self.clone().gauss_x(full_reduce, blocksize, &mut ops);
+                ops.0
+            })
+            .unwrap()
+    }
+
     pub fn rank(&self) -> usize {
         let mut m = self.clone();
         m.gauss(false)
@@ -390,6 +421,26 @@ impl std::ops::Mul<Mat2> for Mat2 {
     }
 }
 
+/// Synthesise a circuit of CNOTs from an invertible parity matrix
+///
+/// The circuit `c` satisfies `c|b> = |m * b>` for all bitstrings `b`. It is built
+/// from the row operations of [Mat2::gauss_x] on the transpose of `m`, using the
+/// blocksize from [Mat2::pmh_blocksize]. Returns None if `m` is not invertible.
+pub fn linear_synth(m: &Mat2) -> Option<Circuit> {
+    if m.num_rows() != m.num_cols() {
+        return None;
+    }
+    // the row operation adding r0 to r1 is recorded as a CNOT from r1 to r0, whose
+    // parity matrix is the transpose, so the circuit computes (m^T)^T = m
+    let mut mt = m.transpose();
+    let mut c = Circuit::new(m.num_rows());
+    let blocksize = mt.pmh_blocksize(true);
+    if mt.gauss_x(true, blocksize, &mut c) < m.num_rows() {
+        return None;
+    }
+    Some(c)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -503,4 +554,40 @@ mod tests {
         let vi_exp = Mat2::new(vec![vec![1, 1, 0], vec![0, 1, 1], vec![0, 0, 1]]);
         assert_eq!(vi_exp, vi);
     }
+
+    #[test]
+    fn linear_synth_cnots() {
+        use crate::gate::CNOT;
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for n in [1, 4, 12, 40] {
+            let mut m = Mat2::id(n);
+            for _ in 0..n * n {
+                let (r0, r1) = (rng.gen_range(0..n), rng.gen_range(0..n));
+                if r0 != r1 {
+                    m.row_add(r0, r1);
+                }
+            }
+
+            // a CNOT from q0 to q1 adds bit q0 to bit q1
+            let c = linear_synth(&m).expect("m should be invertible");
+            let mut p = Mat2::id(n);
+            for g in &c.gates {
+                assert_eq!(g.t, CNOT);
+                p.row_add(g.qs[0], g.qs[1]);
+            }
+            assert_eq!(p, m);
+
+            let mut ops = CountOps::default();
+            m.transpose().gauss_x(true, 3, &mut ops);
+            assert!(c.num_gates() <= ops.0);
+        }
+
+        let v = Mat2::new(vec![vec![1, 0, 1, 0], vec![1, 1, 1, 1], vec![0, 1, 0, 1]]);
+        assert_eq!(linear_synth(&v), None);
+        let v = Mat2::new(vec![vec![1, 1, 0], vec![0, 1, 1], vec![1, 0, 1]]);
+        assert_eq!(linear_synth(&v), None);
+    }
 }
@@ -0,0 +1,386 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measurement patterns, as an alternative to extracting a circuit
+//!
+//! A graph-like diagram with a flow can be run as a measurement-based computation:
+//! prepare a qubit for each spider, entangle them along the Hadamard edges,
+//! then measure every spider which is not an output, correcting the later qubits
+//! after each measurement according to the flow. [Pattern::from_graph] writes this
+//! down as a pattern in the measurement calculus of Danos, Kashefi and Panangaden,
+//! "The measurement calculus" (2007).
+//!
+//! Most spiders are measured in the XY plane. A phase gadget, that is, a spider
+//! with phase 0 or π connected to a single leaf spider, becomes one YZ-plane
+//! measurement, so diagrams simplified with [full_simp] usually have a pattern
+//! too.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::mbqc::Pattern;
+//! # use quizx::simplify::full_simp_preserving_gflow;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::random().seed(1).qubits(3).depth(20).clifford_t(0.2).build();
+//! let mut g: Graph = c.to_graph();
+//! full_simp_preserving_gflow(&mut g);
+//! let p = Pattern::from_graph(&g).unwrap();
+//! assert_eq!(p.inputs, vec![0, 1, 2]);
+//! println!("{}", p);
+//! ```
+//!
+//! [full_simp]: crate::simplify::full_simp
+
+use crate::circuit::Circuit;
+use crate::flow::{odd_neighbourhood, pauli_flow_with, Measurement};
+use crate::gate::*;
+use crate::graph::*;
+use crate::phase::Phase;
+use num::Zero;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::cmp::Reverse;
+use std::fmt;
+
+/// The plane of the Bloch sphere a qubit is measured in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Plane {
+    XY,
+    XZ,
+    YZ,
+}
+
+/// A command of a measurement pattern
+///
+/// Outcome 0 of a measurement at angle `a` projects onto `|0> + e^(i a)|1>` in the
+/// XY plane, `cos(a/2)|0> + sin(a/2)|1>` in the XZ plane, and
+/// `cos(a/2)|0> + i sin(a/2)|1>` in the YZ plane, where `a` is a [Phase] and so a
+/// multiple of pi.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Prepare a qubit in the state `|+>`
+    N(usize),
+    /// Entangle two qubits with a CZ
+    E(usize, usize),
+    /// Measure a qubit in a plane at an angle
+    M(usize, Plane, Phase),
+    /// Apply X to the first qubit if measuring the second one gave outcome 1
+    X(usize, usize),
+    /// Apply Z to the first qubit if measuring the second one gave outcome 1
+    Z(usize, usize),
+}
+
+/// A measurement pattern, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub num_qubits: usize,
+    /// The qubit of each input. These are the first qubits, and are not prepared.
+    pub inputs: Vec<usize>,
+    /// The qubit of each output. These are not measured.
+    pub outputs: Vec<usize>,
+    pub commands: Vec<Command>,
+}
+
+impl Pattern {
+    /// The pattern of a graph-like diagram with an extended gflow
+    ///
+    /// The qubits are the spiders: first the inputs, then the other measured
+    /// spiders, then the other outputs. A spider with a phase on an output, or a
+    /// Hadamard edge to a boundary, first gets an identity spider put on its
+    /// boundary edge. Returns None if the diagram is not graph-like, has symbolic
+    /// phases, or has no flow.
+    pub fn from_graph<G: GraphLike>(g: &G) -> Option<Pattern> {
+        if !g.is_graph_like() || g.vertices().any(|v| g.has_symbolic_phase(v)) {
+            return None;
+        }
+        let mut g = g.clone();
+        open_graph_form(&mut g);
+
+        // replace each phase gadget by a YZ measurement of its root
+        let mut roots: FxHashMap<V, Phase> = FxHashMap::default();
+        for l in g.vertex_vec() {
+            if g.vertex_type(l) != VType::Z || g.degree(l) != 1 || roots.contains_key(&l) {
+                continue;
+            }
+            let r = g.neighbors(l).next().unwrap();
+            let ok = g.vertex_type(r) == VType::Z
+                && g.degree(r) > 1
+                && g.phase(r).is_pauli()
+                && !roots.contains_key(&r)
+                && g.neighbors(r).all(|w| g.vertex_type(w) != VType::B);
+            if ok {
+                let phase = g.phase(l);
+                roots.insert(r, if g.phase(r).is_zero() { phase } else { -phase });
+                g.remove_vertex(l);
+            }
+        }
+        let flow = pauli_flow_with(&g, |v| {
+            if roots.contains_key(&v) {
+                Measurement::YZ
+            } else {
+                Measurement::XY
+            }
+        })?;
+
+        let spider = |b: V| g.neighbors(b).next().unwrap();
+        let mut measured: Vec<V> = flow.correction.keys().copied().collect();
+        measured.sort();
+        let num_inputs = g.inputs().len();
+        let mut index: FxHashMap<V, usize> = FxHashMap::default();
+        let spiders = g
+            .inputs()
+            .iter()
+            .map(|&b| spider(b))
+            .chain(measured.iter().copied())
+            .chain(g.outputs().iter().map(|&b| spider(b)));
+        for v in spiders {
+            if !index.contains_key(&v) {
+                index.insert(v, index.len());
+            }
+        }
+
+        let mut commands: Vec<Command> = (num_inputs..index.len()).map(Command::N).collect();
+        let mut edges: Vec<(usize, usize)> = g
+            .edges()
+            .filter_map(|(s, t, _)| {
+                let (s, t) = (*index.get(&s)?, *index.get(&t)?);
+                Some((s.min(t), s.max(t)))
+            })
+            .collect();
+        edges.sort();
+        commands.extend(edges.into_iter().map(|(s, t)| Command::E(s, t)));
+
+        // measure the spiders in the order of the flow
+        measured.sort_by_key(|v| (Reverse(flow.depth[v]), index[v]));
+        for v in measured {
+            let q = index[&v];
+            commands.push(match roots.get(&v) {
+                Some(&angle) => Command::M(q, Plane::YZ, angle),
+                None => Command::M(q, Plane::XY, -g.phase(v)),
+            });
+            // outcome 1 is corrected by the stabiliser of the graph state given by p(v)
+            let p = &flow.correction[&v];
+            commands.extend(
+                p.iter()
+                    .filter(|&&w| w != v)
+                    .map(|w| Command::X(index[w], q)),
+            );
+            let odd = odd_neighbourhood(&g, p);
+            commands.extend(
+                odd.iter()
+                    .filter(|&&w| w != v)
+                    .map(|w| Command::Z(index[w], q)),
+            );
+        }
+
+        Some(Pattern {
+            num_qubits: index.len(),
+            inputs: (0..num_inputs).collect(),
+            outputs: g.outputs().iter().map(|&b| index[&spider(b)]).collect(),
+            commands,
+        })
+    }
+
+    /// A circuit running the pattern
+    ///
+    /// Each qubit is a wire, and the outcome of measuring qubit `q` goes in bit
+    /// `q`. The corrections are gates conditioned on a single bit, so
+    /// [Circuit::defer_measurements] can turn this into a unitary circuit with
+    /// measurements at the end.
+    pub fn to_circuit(&self) -> Circuit {
+        let mut c = Circuit::new(self.num_qubits);
+        for &cmd in &self.commands {
+            match cmd {
+                Command::N(q) => {
+                    c.push(Gate::new(InitAncilla, vec![q]));
+                    c.push(Gate::new(HAD, vec![q]));
+                }
+                Command::E(s, t) => c.push(Gate::new(CZ, vec![s, t])),
+                Command::M(q, plane, angle) => {
+                    // rotate the basis state for outcome 0 to |0>
+                    match plane {
+                        Plane::XY => {
+                            c.push(Gate::new_with_phase(ZPhase, vec![q], -angle));
+                            c.push(Gate::new(HAD, vec![q]));
+                        }
+                        Plane::XZ => {
+                            c.push(Gate::new(Sdg, vec![q]));
+                            c.push(Gate::new_with_phase(XPhase, vec![q], -angle));
+                            c.push(Gate::new(S, vec![q]));
+                        }
+                        Plane::YZ => c.push(Gate::new_with_phase(XPhase, vec![q], angle)),
+                    }
+                    c.push(Gate::new_measure(q, q));
+                }
+                Command::X(q, s) => c.push(Gate::new(NOT, vec![q]).with_condition(vec![s], 1)),
+                Command::Z(q, s) => c.push(Gate::new(Z, vec![q]).with_condition(vec![s], 1)),
+            }
+        }
+        c
+    }
+}
+
+/// Put identity spiders on boundary edges until each boundary is connected by a
+/// plain edge to its own spider, and each output spider has phase 0
+fn open_graph_form(g: &mut impl GraphLike) {
+    let boundaries: Vec<(V, bool)> = g
+        .inputs()
+        .iter()
+        .map(|&b| (b, true))
+        .chain(g.outputs().iter().map(|&b| (b, false)))
+        .collect();
+    // the spiders which already have an input or output
+    let mut claimed: FxHashSet<(V, bool)> = FxHashSet::default();
+    for (b, is_input) in boundaries {
+        let Some((v, et)) = g.incident_edges(b).next() else {
+            continue;
+        };
+        let plain_spider = et == EType::N && g.vertex_type(v) == VType::Z;
+        if plain_spider && (is_input || g.phase(v).is_zero()) && claimed.insert((v, is_input)) {
+            continue;
+        }
+        g.remove_edge(b, v);
+        let z = g.add_vertex(VType::Z);
+        g.add_edge(b, z);
+        if plain_spider {
+            // two Hadamard edges cancel out
+            let z1 = g.add_vertex(VType::Z);
+            g.add_edge_with_type(z, z1, EType::H);
+            g.add_edge_with_type(z1, v, EType::H);
+        } else {
+            g.add_edge_with_type(z, v, et);
+        }
+        claimed.insert((z, is_input));
+    }
+}
+
+impl fmt::Display for Plane {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::N(q) => write!(f, "N({})", q),
+            Command::E(s, t) => write!(f, "E({},{})", s, t),
+            Command::M(q, plane, angle) => write!(f, "M({},{},{})", q, plane, angle),
+            Command::X(q, s) => write!(f, "X({},s{})", q, s),
+            Command::Z(q, s) => write!(f, "Z({},s{})", q, s),
+        }
+    }
+}
+
+/// The commands in order, one per line
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for cmd in &self.commands {
+            writeln!(f, "{}", cmd)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simplify::full_simp_preserving_gflow;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    /// Check that every branch of the pattern, with all outcomes 0 or all outcomes
+    /// 1, implements the diagram up to a scalar. Circuit tensors don't support
+    /// post-selection, so this goes via the graph of the circuit.
+    fn check_branches(g: &Graph, p: &Pattern) {
+        // the outputs of the circuit come in the order of their qubits
+        let mut order: Vec<usize> = (0..p.outputs.len()).collect();
+        order.sort_by_key(|&k| p.outputs[k]);
+        let mut h = g.clone();
+        h.set_outputs(order.iter().map(|&k| g.outputs()[k]).collect());
+
+        let c = p.to_circuit().defer_measurements().unwrap();
+        for outcome in [false, true] {
+            let mut c1 = Circuit::new(c.num_qubits());
+            for gate in &c.gates {
+                if gate.t == Measure {
+                    if outcome {
+                        c1.push(Gate::new(NOT, gate.qs.clone()));
+                    }
+                    c1.push(Gate::new(PostSelect, gate.qs.clone()));
+                } else {
+                    c1.push(gate.clone());
+                }
+            }
+            let h1: Graph = c1.to_graph();
+            assert!(Tensor4::scalar_compare(&h, &h1));
+        }
+    }
+
+    #[test]
+    fn pattern_from_circuit() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(2)
+            .depth(12)
+            .clifford_t(0.3)
+            .build();
+        let mut g: Graph = c.to_graph();
+        full_simp_preserving_gflow(&mut g);
+        let p = Pattern::from_graph(&g).unwrap();
+
+        let count = |f: fn(&Command) -> bool| p.commands.iter().filter(|c| f(c)).count();
+        assert_eq!(count(|c| matches!(c, Command::N(_))), p.num_qubits - 2);
+        assert_eq!(
+            count(|c| matches!(c, Command::M(..))),
+            p.num_qubits - p.outputs.len()
+        );
+        check_branches(&g, &p);
+    }
+
+    #[test]
+    fn pattern_with_gadgets() {
+        // three wires with a T spider on each, and phase gadgets on some of them
+        let mut g = Graph::new();
+        let mut vs = vec![];
+        for q in 0..3 {
+            let i = g.add_vertex(VType::B);
+            let v = g.add_vertex_with_phase(VType::Z, Phase::from((1, 4)));
+            let o = g.add_vertex(VType::B);
+            g.add_edge(i, v);
+            g.add_edge(v, o);
+            g.inputs_mut().push(i);
+            g.outputs_mut().push(o);
+            g.set_qubit(v, q);
+            vs.push(v);
+        }
+        for (targets, root, leaf) in [(&[0, 1][..], 0, (1, 4)), (&[0, 1, 2][..], 1, (3, 4))] {
+            let r = g.add_vertex_with_phase(VType::Z, Phase::from((root, 1)));
+            let l = g.add_vertex_with_phase(VType::Z, Phase::from(leaf));
+            g.add_edge_with_type(r, l, EType::H);
+            for &q in targets {
+                g.add_edge_with_type(r, vs[q], EType::H);
+            }
+        }
+
+        let p = Pattern::from_graph(&g).unwrap();
+        let yz = p
+            .commands
+            .iter()
+            .filter(|c| matches!(c, Command::M(_, Plane::YZ, _)))
+            .count();
+        assert_eq!(yz, 2);
+        check_branches(&g, &p);
+    }
+}
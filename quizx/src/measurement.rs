@@ -0,0 +1,299 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Circuits with measurements and classical control
+//!
+//! A circuit can measure qubits into classical bits with [Measure] gates, reset
+//! qubits with [Reset] gates, and apply gates only if some classical bits have a
+//! given value, see [Condition]. These all come from QASM statements such as
+//! `measure q[0] -> c[0];`, `reset q[0];` and `if(c==1) x q[1];`.
+//!
+//! Measurements at the end of a circuit don't change its diagram, so
+//! [Circuit::to_graph] allows them, and the qubits they measure are the outputs
+//! of the graph as usual. Other measurements, resets and classically controlled
+//! gates can't be turned into a graph, and [Circuit::to_graph] panics on them.
+//! Instead, [Circuit::defer_measurements] can first move the measurements to the
+//! end, turning gates conditioned on an outcome into gates controlled on the
+//! measured qubit. Circuits with resets can be simulated as mixed states, see
+//! [crate::mixed].
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::gate::*;
+//! let c = Circuit::from_qasm(
+//!     "qreg q[2]; creg c[2];
+//!     h q[0];
+//!     measure q[0] -> c[0];
+//!     if(c==1) x q[1];
+//!     measure q[1] -> c[1];",
+//! )
+//! .unwrap();
+//! assert!(c.terminal_measurements().is_err());
+//! let c1 = c.defer_measurements().unwrap();
+//! assert_eq!(c1.num_gates_of_type(CNOT), 1);
+//! assert_eq!(c1.terminal_measurements().unwrap(), vec![(0, 0), (1, 1)]);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::controlled::push_controlled_gate;
+use crate::gate::*;
+use rustc_hash::FxHashMap;
+
+/// An error for measurements or classical control which can't be handled
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum MeasurementError {
+    /// A gate acts on a qubit which has already been measured.
+    #[error("Gate {0} acts on a qubit after it is measured")]
+    MidCircuit(usize),
+    /// A gate is classically controlled.
+    #[error("Gate {0} is classically controlled")]
+    Conditional(usize),
+    /// A gate depends on more than one measurement outcome.
+    #[error("Gate {0} depends on more than one measurement outcome")]
+    MultipleOutcomes(usize),
+    /// A gate is a reset.
+    #[error("Gate {0} is a reset")]
+    Reset(usize),
+}
+
+impl Circuit {
+    /// The measurements of the circuit, if they are all at the end
+    ///
+    /// This returns the pairs of a qubit and the classical bit it is measured into,
+    /// in circuit order. It is an error if a measured qubit is used again, or if
+    /// the circuit has resets or classically controlled gates.
+    pub fn terminal_measurements(&self) -> Result<Vec<(usize, usize)>, MeasurementError> {
+        let mut measured = vec![false; self.num_qubits()];
+        let mut ms = vec![];
+        for (i, g) in self.gates.iter().enumerate() {
+            if g.condition.is_some() {
+                return Err(MeasurementError::Conditional(i));
+            }
+            match g.t {
+                Reset => return Err(MeasurementError::Reset(i)),
+                Measure => {
+                    for (&q, &b) in g.qs.iter().zip(&g.bits) {
+                        measured[q] = true;
+                        ms.push((q, b));
+                    }
+                }
+                Barrier => {}
+                _ => {
+                    if g.qs.iter().any(|&q| measured[q]) {
+                        return Err(MeasurementError::MidCircuit(i));
+                    }
+                }
+            }
+        }
+        Ok(ms)
+    }
+
+    /// The circuit with all its measurements at the end
+    ///
+    /// This is the principle of deferred measurement: a gate conditioned on the
+    /// outcome of measuring a qubit is the same as the gate controlled on that
+    /// qubit, followed by the measurement. The controlled gates are written as in
+    /// [Circuit::controlled], up to a global phase. Bits which haven't been written
+    /// by a measurement yet are taken to be 0.
+    ///
+    /// This is an error if a measured qubit is used again other than as a control,
+    /// if a gate depends on more than one outcome, or if there are resets.
+    pub fn defer_measurements(&self) -> Result<Circuit, MeasurementError> {
+        let mut c = Circuit::new(self.num_qubits());
+        let mut measured = vec![false; self.num_qubits()];
+        // the qubit most recently measured into each classical bit
+        let mut source: FxHashMap<usize, usize> = FxHashMap::default();
+        let mut ms = vec![];
+        for (i, g) in self.gates.iter().enumerate() {
+            match g.t {
+                Reset => return Err(MeasurementError::Reset(i)),
+                Measure if g.condition.is_some() => {
+                    return Err(MeasurementError::Conditional(i));
+                }
+                Measure => {
+                    for (&q, &b) in g.qs.iter().zip(&g.bits) {
+                        measured[q] = true;
+                        source.insert(b, q);
+                        ms.push(Gate::new_measure(q, b));
+                    }
+                    continue;
+                }
+                Barrier => {
+                    c.push(g.clone());
+                    continue;
+                }
+                _ => {}
+            }
+            if g.qs.iter().any(|&q| measured[q]) {
+                return Err(MeasurementError::MidCircuit(i));
+            }
+            let Some(cond) = &g.condition else {
+                c.push(g.clone());
+                continue;
+            };
+
+            // the measured qubits the gate depends on, and the outcomes it needs
+            let mut controls = vec![];
+            let mut never = cond.bits.len() < 64 && cond.value >> cond.bits.len() != 0;
+            for (j, b) in cond.bits.iter().enumerate() {
+                match source.get(b) {
+                    Some(&q) => controls.push((q, cond.bit_value(j))),
+                    None => never |= cond.bit_value(j),
+                }
+            }
+            if never {
+                continue;
+            }
+
+            let mut h = g.clone();
+            h.condition = None;
+            match controls[..] {
+                [] => c.push(h),
+                [(q, one)] => {
+                    if !one {
+                        c.push(Gate::new(NOT, vec![q]));
+                    }
+                    push_controlled_gate(&mut c, q, &h);
+                    if !one {
+                        c.push(Gate::new(NOT, vec![q]));
+                    }
+                }
+                _ => return Err(MeasurementError::MultipleOutcomes(i)),
+            }
+        }
+        c.gates.extend(ms);
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn qasm_measurements() {
+        let qasm = "qreg q[2];
+            creg c[2];
+            h q[0];
+            reset q[1];
+            measure q[0] -> c[1];
+            if(c==2) x q[1];
+            measure q[1] -> c[0];";
+        let c = Circuit::from_qasm(qasm).unwrap();
+        assert_eq!(c.num_gates(), 5);
+        assert_eq!(c.num_bits(), 2);
+        assert_eq!(c.gates[2], Gate::new_measure(0, 1));
+        assert_eq!(
+            c.gates[3],
+            Gate::new(NOT, vec![1]).with_condition(vec![0, 1], 2)
+        );
+        assert_eq!(c.terminal_measurements(), Err(MeasurementError::Reset(1)));
+
+        // the written QASM reads back as the same circuit
+        let c1 = Circuit::from_qasm(&c.to_qasm()).unwrap();
+        assert_eq!(c, c1);
+        assert!(c
+            .to_string()
+            .contains("creg c[2];\nh q[0];\nreset q[1];\nmeasure q[0] -> c[1];\nif(c==2) x q[1];"));
+    }
+
+    #[test]
+    fn terminal_measurements() {
+        let mut c = Circuit::from_qasm("qreg q[3]; h q[0]; cx q[0], q[1]; t q[2];").unwrap();
+        let c0 = c.clone();
+        c.push(Gate::new_measure(1, 0));
+        c.push(Gate::new_measure(0, 2));
+        assert_eq!(c.terminal_measurements(), Ok(vec![(1, 0), (0, 2)]));
+        let g: Graph = c.to_graph();
+        assert!(Tensor4::scalar_compare(&g, &c0));
+
+        c.push(Gate::new(HAD, vec![2]));
+        assert!(c.terminal_measurements().is_ok());
+        c.push(Gate::new(HAD, vec![1]));
+        assert_eq!(
+            c.terminal_measurements(),
+            Err(MeasurementError::MidCircuit(6))
+        );
+
+        let mut c = c0.clone();
+        c.push(Gate::new(Z, vec![0]).with_condition(vec![0], 1));
+        assert_eq!(
+            c.terminal_measurements(),
+            Err(MeasurementError::Conditional(3))
+        );
+    }
+
+    #[test]
+    fn defer_measurements() {
+        // teleport T H |0> from q[0] to q[2], correcting with classically
+        // controlled gates
+        let mut c = Circuit::from_qasm(
+            "qreg q[3];
+            h q[0];
+            t q[0];
+            h q[1];
+            cx q[1], q[2];
+            cx q[0], q[1];
+            h q[0];",
+        )
+        .unwrap();
+        c.push(Gate::new_measure(0, 0));
+        c.push(Gate::new_measure(1, 1));
+        c.push(Gate::new(NOT, vec![2]).with_condition(vec![1], 1));
+        c.push(Gate::new(Z, vec![2]).with_condition(vec![0], 1));
+        let d = c.defer_measurements().unwrap();
+        assert_eq!(d.terminal_measurements(), Ok(vec![(0, 0), (1, 1)]));
+
+        // for each outcome, q[2] is left in the state T H |0>, with amplitude 1/2
+        let mut e: Graph = Circuit::from_qasm("qreg q[1]; h q[0]; t q[0];")
+            .unwrap()
+            .to_graph();
+        e.plug_input(0, BasisElem::Z0);
+        let e: Vec<_> = e.to_tensorf().iter().copied().collect();
+        for b0 in [BasisElem::Z0, BasisElem::Z1] {
+            for b1 in [BasisElem::Z0, BasisElem::Z1] {
+                let mut g: Graph = d.to_graph();
+                g.plug_inputs(&[BasisElem::Z0; 3]);
+                g.plug_outputs(&[b0, b1]);
+                let t: Vec<_> = g.to_tensorf().iter().copied().collect();
+                let lambda = t[0] / e[0];
+                assert!((lambda.norm() - 0.5).abs() < 1e-6);
+                assert!((t[1] - lambda * e[1]).norm() < 1e-6);
+            }
+        }
+
+        // a condition on both outcomes can't be deferred
+        let mut c1 = c.clone();
+        c1.push(Gate::new(Z, vec![2]).with_condition(vec![0, 1], 3));
+        assert_eq!(
+            c1.defer_measurements().unwrap_err(),
+            MeasurementError::MultipleOutcomes(10)
+        );
+
+        // a condition on a 0 outcome, and on a bit which is never written
+        let mut c = Circuit::new(2);
+        c.push(Gate::new_measure(0, 0));
+        c.push(Gate::new(NOT, vec![1]).with_condition(vec![0], 0));
+        c.push(Gate::new(Z, vec![1]).with_condition(vec![1], 1));
+        let d = c.defer_measurements().unwrap();
+        assert_eq!(d.num_gates_of_type(CNOT), 1);
+        assert_eq!(d.num_gates_of_type(NOT), 2);
+        assert_eq!(d.num_gates_of_type(Z), 0);
+    }
+}
@@ -0,0 +1,406 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mixed-state simulation of circuits with resets and measurements
+//!
+//! A circuit with mid-circuit resets isn't a linear map on states, but it is one on
+//! density matrices. [Circuit::to_doubled_graph] gives this as a "doubled"
+//! ZX-diagram, with a copy of the circuit on qubits `0..n` and its complex
+//! conjugate on qubits `n..2n`. In this diagram:
+//!
+//! - a unitary gate is the gate on the first copy and its conjugate on the second,
+//! - discarding a qubit joins its wire in the first copy to its wire in the second,
+//!   which takes the partial trace,
+//! - a [Reset] discards the qubit and starts a fresh `|0>` on both copies, and
+//! - a [Measure] whose outcome isn't used joins both wires with a Z spider, which
+//!   removes the off-diagonal terms of the qubit.
+//!
+//! Classically controlled gates should first be removed with
+//! [Circuit::defer_measurements]. [Circuit::probability] plugs the doubled diagram
+//! into `|0...0>` and some measurement outcomes, traces out the other qubits and
+//! computes the resulting scalar with a stabiliser decomposition, as for
+//! [Circuit::amplitude]. For small channels, [choi_matrix] and [superoperator]
+//! give the whole channel as a matrix, e.g. to compare with a density-matrix
+//! simulator.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! // a Bell pair, where one half is reset and replaced by a copy of the other
+//! let c = Circuit::from_qasm(
+//!     "qreg q[2];
+//!     h q[0];
+//!     cx q[0], q[1];
+//!     reset q[0];
+//!     cx q[1], q[0];",
+//! )
+//! .unwrap();
+//! assert!((c.probability(&[(0, true)]) - 0.5).abs() < 1e-6);
+//! assert!(c.probability(&[(0, true), (1, false)]).abs() < 1e-6);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::simulate::evaluate;
+use crate::tensor::{Tensor, TensorElem, ToTensor};
+use crate::vec_graph::Graph;
+use num::Zero;
+
+/// Adds an identity spider on each copy of qubit `q`, and returns them
+fn split_wires(graph: &mut impl GraphLike, qs: &mut Vec<Option<V>>, q: usize) -> Option<(V, V)> {
+    let n = qs.len() / 2;
+    Gate::new_with_phase(ZPhase, vec![q], Phase::zero()).add_to_graph(graph, qs, false);
+    Gate::new_with_phase(ZPhase, vec![q + n], Phase::zero()).add_to_graph(graph, qs, false);
+    qs[q].zip(qs[q + n])
+}
+
+impl Circuit {
+    /// The circuit as a doubled ZX-diagram, see the [module docs](crate::mixed)
+    ///
+    /// The inputs and outputs are those of the circuit, followed by those of its
+    /// conjugate. This panics on classically controlled gates.
+    pub fn to_doubled_graph<G: GraphLike>(&self) -> G {
+        let n = self.num_qubits();
+        let mut graph = G::new();
+        let mut qs = Vec::with_capacity(2 * n);
+        let mut inputs = Vec::with_capacity(2 * n);
+        for i in 0..2 * n {
+            let v = graph.add_vertex_with_data(VData {
+                ty: VType::B,
+                phase: Phase::zero(),
+                qubit: i as i32,
+                row: 1,
+            });
+            qs.push(Some(v));
+            inputs.push(v);
+        }
+        graph.set_inputs(inputs);
+
+        for g in &self.gates {
+            assert!(
+                g.condition.is_none(),
+                "Classically controlled gates can't be added to a graph, see Circuit::defer_measurements"
+            );
+            match g.t {
+                Measure => {
+                    for &q in &g.qs {
+                        if let Some((v1, v2)) = split_wires(&mut graph, &mut qs, q) {
+                            graph.add_edge(v1, v2);
+                        }
+                    }
+                }
+                Reset => {
+                    for &q in &g.qs {
+                        let Some((v1, v2)) = split_wires(&mut graph, &mut qs, q) else {
+                            continue;
+                        };
+                        graph.add_edge(v1, v2);
+                        for (q1, v) in [(q, v1), (q + n, v2)] {
+                            let w = graph.add_vertex_with_data(VData {
+                                ty: VType::X,
+                                phase: Phase::zero(),
+                                qubit: q1 as i32,
+                                row: graph.row(v) + 1,
+                            });
+                            graph.scalar_mut().mul_sqrt2_pow(-1);
+                            qs[q1] = Some(w);
+                        }
+                    }
+                }
+                _ => {
+                    g.add_to_graph(&mut graph, &mut qs, false);
+                    let mut h = g.clone();
+                    h.adjoint();
+                    for q in &mut h.qs {
+                        *q += n;
+                    }
+                    h.add_to_graph(&mut graph, &mut qs, false);
+                }
+            }
+        }
+
+        let last_row = qs
+            .iter()
+            .flatten()
+            .map(|&v| graph.row(v))
+            .max()
+            .unwrap_or(0);
+        let mut outputs = Vec::with_capacity(2 * n);
+        for (i, &q) in qs.iter().enumerate() {
+            if let Some(v0) = q {
+                let v = graph.add_vertex_with_data(VData {
+                    ty: VType::B,
+                    phase: Phase::zero(),
+                    qubit: i as i32,
+                    row: last_row + 1,
+                });
+                graph.add_edge(v0, v);
+                outputs.push(v);
+            }
+        }
+        graph.set_outputs(outputs);
+        graph
+    }
+
+    /// The probability of the given measurement outcomes at the end of the circuit
+    ///
+    /// The circuit starts in `|0...0>`, and each outcome is a qubit and its value.
+    /// The qubits without an outcome are traced out. The circuit may contain resets
+    /// and measurements, and its phases should be multiples of `π/4`. See the
+    /// [module docs](crate::mixed).
+    ///
+    /// If the circuit is unitary and every qubit has an outcome, this is the squared
+    /// norm of an [amplitude](Circuit::amplitude), which avoids doubling the
+    /// circuit.
+    pub fn probability(&self, outcomes: &[(usize, bool)]) -> f64 {
+        let x: Option<Vec<bool>> = (0..self.num_qubits())
+            .map(|q| outcomes.iter().find(|&&(q1, _)| q1 == q).map(|&(_, b)| b))
+            .collect();
+        if let Some(x) = x {
+            if self.terminal_measurements().is_ok() {
+                return self.amplitude(&x).complex_value().norm_sqr();
+            }
+        }
+
+        let mut g: Graph = self.to_doubled_graph();
+        g.plug_inputs(&vec![BasisElem::Z0; g.inputs().len()]);
+
+        // the outputs of the circuit are followed by the matching outputs of its
+        // conjugate
+        let outputs = g.outputs().clone();
+        let (outs1, outs2) = outputs.split_at(outputs.len() / 2);
+        for (&o1, &o2) in outs1.iter().zip(outs2) {
+            let q = g.qubit(o1) as usize;
+            match outcomes.iter().find(|&&(q1, _)| q1 == q) {
+                Some(&(_, b)) => {
+                    let b = if b { BasisElem::Z1 } else { BasisElem::Z0 };
+                    g.plug_vertex(o1, b);
+                    g.plug_vertex(o2, b);
+                    g.scalar_mut().mul_sqrt2_pow(-2);
+                }
+                None => {
+                    g.set_vertex_type(o1, VType::Z);
+                    g.set_vertex_type(o2, VType::Z);
+                    g.add_edge(o1, o2);
+                }
+            }
+        }
+        g.set_outputs(vec![]);
+        evaluate(g).complex_value().re
+    }
+}
+
+/// The tensor of a doubled diagram with the indices of the given copies of the
+/// inputs and outputs in order, reshaped to a matrix
+///
+/// Index 0 and 1 of `rows` and `cols` stand for the inputs of the diagram and of
+/// its conjugate, and 2 and 3 for the outputs.
+fn doubled_matrix<A: TensorElem>(
+    g: &impl GraphLike,
+    rows: [usize; 2],
+    cols: [usize; 2],
+) -> Tensor<A> {
+    let (ni, no) = (g.inputs().len(), g.outputs().len());
+    assert!(
+        ni % 2 == 0 && no % 2 == 0,
+        "A doubled diagram should have an even number of inputs and outputs"
+    );
+    let (a, b) = (ni / 2, no / 2);
+    let ranges = [0..a, a..ni, ni..ni + b, ni + b..ni + no];
+    let perm: Vec<usize> = rows
+        .iter()
+        .chain(&cols)
+        .flat_map(|&i| ranges[i].clone())
+        .collect();
+    let size = |ix: [usize; 2]| -> usize { ix.iter().map(|&i| 1 << ranges[i].len()).product() };
+    g.to_tensor::<A>()
+        .permuted_axes(perm)
+        .as_standard_layout()
+        .into_owned()
+        .into_shape(vec![size(rows), size(cols)])
+        .unwrap()
+}
+
+/// The Choi matrix of the channel given by a doubled diagram, see
+/// [Circuit::to_doubled_graph]
+///
+/// For a channel `E` from `a` to `b` qubits, this is the `2^(a+b)` by `2^(a+b)`
+/// matrix `sum_ij |i><j| ⊗ E(|i><j|)`, so its trace is `2^a` if `E` is trace
+/// preserving. The qubits are numbered from the left, as for
+/// [ToTensor::to_tensor].
+pub fn choi_matrix<A: TensorElem>(g: &impl GraphLike) -> Tensor<A> {
+    doubled_matrix(g, [0, 2], [1, 3])
+}
+
+/// The superoperator of the channel given by a doubled diagram, see
+/// [Circuit::to_doubled_graph]
+///
+/// For a channel `E` from `a` to `b` qubits, this is the `2^(2b)` by `2^(2a)`
+/// matrix sending a density matrix `ρ`, flattened row by row, to `E(ρ)`. For a
+/// unitary `U`, this is `U ⊗ conj(U)`.
+pub fn superoperator<A: TensorElem>(g: &impl GraphLike) -> Tensor<A> {
+    doubled_matrix(g, [2, 3], [0, 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::*;
+    use crate::tensor::*;
+
+    /// The probabilities of the outcomes of qubit 0 and 1 for a circuit without
+    /// resets, computed from its state vector
+    fn pure_probabilities(c: &Circuit) -> [[f64; 2]; 2] {
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&vec![BasisElem::Z0; c.num_qubits()]);
+        let mut p = [[0.0; 2]; 2];
+        for (i, a) in g.to_tensorf().indexed_iter() {
+            p[i[0]][i[1]] += a.norm_sqr();
+        }
+        p
+    }
+
+    #[test]
+    fn pure_circuits() {
+        for seed in 0..3 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(3)
+                .depth(20)
+                .clifford_t(0.3)
+                .build();
+            let p = pure_probabilities(&c);
+            for b0 in [false, true] {
+                let p0 = p[b0 as usize][0] + p[b0 as usize][1];
+                assert!((c.probability(&[(0, b0)]) - p0).abs() < 1e-6);
+                for b1 in [false, true] {
+                    let p01 = c.probability(&[(0, b0), (1, b1)]);
+                    assert!((p01 - p[b0 as usize][b1 as usize]).abs() < 1e-6);
+                }
+            }
+            assert!((c.probability(&[]) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn resets() {
+        let c = Circuit::from_qasm("qreg q[1]; h q[0]; t q[0]; reset q[0];").unwrap();
+        assert!((c.probability(&[(0, false)]) - 1.0).abs() < 1e-6);
+
+        // the reset qubit is reused, and the other qubit is left mixed
+        let c = Circuit::from_qasm(
+            "qreg q[2];
+            h q[0];
+            cx q[0], q[1];
+            reset q[0];
+            x q[0];
+            t q[1];
+            h q[1];",
+        )
+        .unwrap();
+        assert!((c.probability(&[(0, true)]) - 1.0).abs() < 1e-6);
+        assert!((c.probability(&[(0, true), (1, true)]) - 0.5).abs() < 1e-6);
+
+        // a reset before any gates only changes the scalar
+        let mut c1 = Circuit::new(2);
+        c1.push(Gate::new(Reset, vec![1]));
+        c1.push(Gate::new(NOT, vec![1]));
+        assert!((c1.probability(&[(1, true)]) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn dephasing_measurements() {
+        let mut c = Circuit::from_qasm("qreg q[1]; h q[0];").unwrap();
+        let c0 = c.clone() + Circuit::from_qasm("qreg q[1]; h q[0];").unwrap();
+        c.push(Gate::new_measure(0, 0));
+        c.push(Gate::new(HAD, vec![0]));
+        assert!((c.probability(&[(0, false)]) - 0.5).abs() < 1e-6);
+        assert!((c0.probability(&[(0, false)]) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn doubled_graph() {
+        // without resets, the doubled graph is the circuit and its conjugate
+        let c = Circuit::from_qasm("qreg q[2]; h q[0]; t q[0]; cx q[0], q[1];").unwrap();
+        let c2 = Circuit::from_qasm(
+            "qreg q[4];
+            h q[0]; t q[0]; cx q[0], q[1];
+            h q[2]; tdg q[2]; cx q[2], q[3];",
+        )
+        .unwrap();
+        let g: Graph = c.to_doubled_graph();
+        assert_eq!(g.inputs().len(), 4);
+        assert!(Tensor4::scalar_compare(&g, &c2));
+    }
+
+    #[test]
+    fn unitary_superoperator() {
+        let c = Circuit::from_qasm("qreg q[2]; h q[0]; t q[0]; cx q[0], q[1]; s q[1];").unwrap();
+        let u = c
+            .to_tensor_with_convention::<Scalar4>(TensorConvention::PYZX)
+            .into_shape((4, 4))
+            .unwrap();
+        let g: Graph = c.to_doubled_graph();
+        let s: Tensor4 = superoperator(&g);
+        assert_eq!(s.shape(), &[16, 16]);
+        for ((r, c), a) in s
+            .into_dimensionality::<ndarray::Ix2>()
+            .unwrap()
+            .indexed_iter()
+        {
+            assert_eq!(*a, u[[r / 4, c / 4]] * u[[r % 4, c % 4]].conj());
+        }
+        let j: Tensor4 = choi_matrix(&g);
+        assert_eq!(j.shape(), &[16, 16]);
+    }
+
+    #[test]
+    fn reset_choi_matrix() {
+        // resetting a qubit sends every state to |0><0|, so its Choi matrix is
+        // I ⊗ |0><0|
+        let mut c = Circuit::new(1);
+        c.push(Gate::new(Reset, vec![0]));
+        let g: Graph = c.to_doubled_graph();
+        let j: Tensorf = choi_matrix(&g);
+        for ((r, c), a) in j
+            .into_dimensionality::<ndarray::Ix2>()
+            .unwrap()
+            .indexed_iter()
+        {
+            let expected = if r == c && r % 2 == 0 { 1.0 } else { 0.0 };
+            assert!((*a - expected).norm() < 1e-6);
+        }
+
+        // a measurement removes the off-diagonal entries of the density matrix
+        let mut c = Circuit::new(1);
+        c.push(Gate::new_measure(0, 0));
+        let g: Graph = c.to_doubled_graph();
+        let s: Tensorf = superoperator(&g);
+        for ((r, c), a) in s
+            .into_dimensionality::<ndarray::Ix2>()
+            .unwrap()
+            .indexed_iter()
+        {
+            let expected = if r == c && (r == 0 || r == 3) {
+                1.0
+            } else {
+                0.0
+            };
+            assert!((*a - expected).norm() < 1e-6);
+        }
+    }
+}
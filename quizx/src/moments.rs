@@ -0,0 +1,200 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Circuits as a sequence of moments
+//!
+//! A moment is a set of gates on disjoint qubits, which can be applied at the same
+//! time. [Moments::new] schedules each gate as soon as possible, i.e. in the moment
+//! after the last one which uses any of its qubits or classical bits, so the number
+//! of moments is the depth of the circuit. The moments can then be edited, e.g. to insert idle gates
+//! or remove a layer, and turned back into a circuit.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! let c = Circuit::from_qasm("qreg q[3]; h q[0]; h q[1]; cx q[0], q[1]; t q[2];").unwrap();
+//! let m = c.moments();
+//! assert_eq!(m.depth(), 2);
+//! assert_eq!(m.moments[0].len(), 3);
+//! assert_eq!(m.to_circuit().num_gates(), 4);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::Gate;
+
+/// The gates of a circuit, grouped into moments, see the [module docs](self)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Moments {
+    nqubits: usize,
+    /// The gates of each moment, which should act on disjoint qubits
+    pub moments: Vec<Vec<Gate>>,
+}
+
+impl Moments {
+    /// No moments on `nqubits` qubits
+    pub fn empty(nqubits: usize) -> Self {
+        Moments {
+            nqubits,
+            moments: vec![],
+        }
+    }
+
+    /// The moments of a circuit, with each gate as early as possible
+    pub fn new(c: &Circuit) -> Self {
+        let mut m = Moments::empty(c.num_qubits());
+        // the first moment in which each qubit and classical bit is free
+        let mut free = vec![0; c.num_qubits()];
+        let mut free_bits = vec![0; c.num_bits()];
+        for g in &c.gates {
+            let i =
+                g.qs.iter()
+                    .map(|&q| free[q])
+                    .chain(g.classical_bits().map(|b| free_bits[b]))
+                    .max()
+                    .unwrap_or(0);
+            if i == m.moments.len() {
+                m.moments.push(vec![]);
+            }
+            m.moments[i].push(g.clone());
+            for &q in &g.qs {
+                free[q] = i + 1;
+            }
+            for b in g.classical_bits() {
+                free_bits[b] = i + 1;
+            }
+        }
+        m
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.nqubits
+    }
+
+    /// The number of moments
+    pub fn depth(&self) -> usize {
+        self.moments.len()
+    }
+
+    /// Adds a gate at the end of the circuit, as early as possible
+    ///
+    /// This is the moment after the last one which uses any of its qubits or
+    /// classical bits.
+    pub fn push(&mut self, g: Gate) {
+        let overlaps = |h: &Gate| {
+            h.qs.iter().any(|q| g.qs.contains(q))
+                || h.classical_bits()
+                    .any(|b| g.classical_bits().any(|b1| b == b1))
+        };
+        let i = self
+            .moments
+            .iter()
+            .rposition(|m| m.iter().any(overlaps))
+            .map_or(0, |i| i + 1);
+        if i == self.moments.len() {
+            self.moments.push(vec![]);
+        }
+        self.moments[i].push(g);
+    }
+
+    /// Whether the gates in each moment act on disjoint qubits
+    pub fn is_valid(&self) -> bool {
+        self.moments.iter().all(|m| {
+            let mut used = vec![false; self.nqubits];
+            m.iter()
+                .flat_map(|g| g.qs.iter())
+                .all(|&q| q < self.nqubits && !std::mem::replace(&mut used[q], true))
+        })
+    }
+
+    /// Removes the moments without any gates
+    pub fn remove_empty(&mut self) {
+        self.moments.retain(|m| !m.is_empty());
+    }
+
+    /// The circuit with the gates of each moment in turn
+    pub fn to_circuit(&self) -> Circuit {
+        let mut c = Circuit::new(self.nqubits);
+        for m in &self.moments {
+            c.gates.extend(m.iter().cloned());
+        }
+        c
+    }
+}
+
+impl Circuit {
+    /// The gates of the circuit grouped into moments, see [Moments::new]
+    pub fn moments(&self) -> Moments {
+        Moments::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dag::CircuitDag;
+    use crate::gate::*;
+
+    #[test]
+    fn asap_moments() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(5)
+                .depth(40)
+                .clifford_t(0.3)
+                .build();
+            let m = c.moments();
+            assert!(m.is_valid());
+            assert_eq!(m.depth(), c.depth());
+            assert_eq!(m.to_circuit(), CircuitDag::new(&c).to_circuit());
+
+            let mut m1 = Moments::empty(5);
+            for g in &c.gates {
+                m1.push(g.clone());
+            }
+            assert_eq!(m1, m);
+        }
+    }
+
+    #[test]
+    fn edit_moments() {
+        let c = Circuit::from_qasm(
+            "qreg q[2];
+            h q[0];
+            cx q[0], q[1];
+            t q[1];
+            s q[0];",
+        )
+        .unwrap();
+        let mut m = c.moments();
+        assert_eq!(m.depth(), 3);
+        assert_eq!(m.moments[2].len(), 2);
+
+        // remove the Hadamards, and the moments this leaves empty
+        for moment in &mut m.moments {
+            moment.retain(|g| g.t != HAD);
+        }
+        m.remove_empty();
+        assert_eq!(m.depth(), 2);
+
+        m.moments[0].push(Gate::new(T, vec![1]));
+        assert!(!m.is_valid());
+        m.moments[0].pop();
+        m.push(Gate::new(Z, vec![0]));
+        assert_eq!(m.depth(), 3);
+        assert!(m.is_valid());
+        assert_eq!(m.to_circuit().num_gates(), 4);
+    }
+}
@@ -1,8 +1,376 @@
 use crate::circuit::*;
+use crate::dag::{qubit_action, QubitAction};
+use crate::gate::*;
+use crate::phase::Phase;
+use num::Zero;
+
+/// The axis and angle of a single-qubit phase gate without parameters
+pub(crate) fn rotation(g: &Gate) -> Option<(bool, Phase)> {
+    if !g.params.is_zero() {
+        return None;
+    }
+    let phase = match g.t {
+        Z | NOT => Phase::new(1),
+        S => Phase::from((1, 2)),
+        Sdg => Phase::from((-1, 2)),
+        T => Phase::from((1, 4)),
+        Tdg => Phase::from((-1, 4)),
+        ZPhase | XPhase => g.phase,
+        _ => return None,
+    };
+    Some((matches!(g.t, NOT | XPhase), phase))
+}
+
+/// The simplest gate for a rotation about Z (or X if `x_axis`) on qubit `q`
+pub(crate) fn rotation_gate(x_axis: bool, phase: Phase, q: usize) -> Option<Gate> {
+    let t = if phase.is_zero() {
+        return None;
+    } else if x_axis {
+        if phase == Phase::new(1) {
+            NOT
+        } else {
+            XPhase
+        }
+    } else if phase == Phase::new(1) {
+        Z
+    } else if phase == Phase::from((1, 2)) {
+        S
+    } else if phase == Phase::from((-1, 2)) {
+        Sdg
+    } else if phase == Phase::from((1, 4)) {
+        T
+    } else if phase == Phase::from((-1, 4)) {
+        Tdg
+    } else {
+        ZPhase
+    };
+    let phase = if t == ZPhase || t == XPhase {
+        phase
+    } else {
+        Phase::zero()
+    };
+    Some(Gate::new_with_phase(t, vec![q], phase))
+}
+
+/// Combines two adjacent gates on the same qubits into at most one gate
+///
+/// Returns `None` if they can't be combined, and `Some(None)` if they cancel.
+fn combine(g: &Gate, h: &Gate) -> Option<Option<Gate>> {
+    if g.condition.is_some() || h.condition.is_some() {
+        return None;
+    }
+    if let (Some((xg, pg)), Some((xh, ph))) = (rotation(g), rotation(h)) {
+        return (xg == xh).then(|| rotation_gate(xg, pg + ph, g.qs[0]));
+    }
+    let cancels = match (g.t, h.t) {
+        (HAD, HAD) | (CZ, CZ) | (SWAP, SWAP) | (XCX, XCX) | (CCZ, CCZ) => true,
+        (CNOT, CNOT) => g.qs == h.qs,
+        (TOFF, TOFF) => g.qs[2] == h.qs[2],
+        (ParityPhase, ParityPhase) => {
+            let phase = g.phase + h.phase;
+            return Some(
+                (!phase.is_zero()).then(|| Gate::new_with_phase(ParityPhase, g.qs.clone(), phase)),
+            );
+        }
+        _ => false,
+    };
+    cancels.then_some(None)
+}
+
+/// The gate sandwiched between two Hadamards on the qubit `q`
+///
+/// Returns `None` if there is no simpler form, and `Some(None)` for the identity.
+fn conjugate_by_had(g: &Gate, q: usize) -> Option<Option<Gate>> {
+    if g.condition.is_some() {
+        return None;
+    }
+    if let Some((x_axis, phase)) = rotation(g) {
+        return Some(rotation_gate(!x_axis, phase, q));
+    }
+    match g.t {
+        CNOT if g.qs[1] == q => Some(Some(Gate::new(CZ, g.qs.clone()))),
+        CZ => {
+            let c = if g.qs[0] == q { g.qs[1] } else { g.qs[0] };
+            Some(Some(Gate::new(CNOT, vec![c, q])))
+        }
+        _ => None,
+    }
+}
+
+/// One pass of peephole optimisation, returning whether anything changed
+///
+/// Gates which change in a pass aren't matched again until the next pass, so the
+/// links between gates stay valid.
+fn peephole_pass(c: &mut Circuit) -> bool {
+    let gates: Vec<Gate> = c.gates.drain(..).collect();
+    let n = gates.len();
+    // for each gate, the next gate on each of its qubits
+    let mut next: Vec<Vec<Option<usize>>> = gates.iter().map(|g| vec![None; g.qs.len()]).collect();
+    let mut last: Vec<Option<(usize, usize)>> = vec![None; c.num_qubits()];
+    for (i, g) in gates.iter().enumerate() {
+        for &q in &g.qs {
+            if let Some((j, k)) = last[q] {
+                next[j][k] = Some(i);
+            }
+        }
+        for (k, &q) in g.qs.iter().enumerate() {
+            last[q] = Some((i, k));
+        }
+    }
+
+    let mut result: Vec<Option<Gate>> = gates.into_iter().map(Some).collect();
+    let mut dirty = vec![false; n];
+    let mut changed = false;
+    for i in 0..n {
+        if dirty[i] {
+            continue;
+        }
+        let Some(g) = result[i].clone() else { continue };
+
+        // two gates on the same qubits which cancel or merge
+        if let Some(j) = next[i][0].filter(|&j| !dirty[j]) {
+            let h = result[j].as_ref().unwrap();
+            if h.qs.len() == g.qs.len() && next[i].iter().all(|&k| k == Some(j)) {
+                if let Some(merged) = combine(&g, h) {
+                    result[i] = None;
+                    result[j] = merged;
+                    dirty[i] = true;
+                    dirty[j] = true;
+                    changed = true;
+                    continue;
+                }
+            }
+        }
+
+        // a gate between two Hadamards, such as H S H = X(π/2) or H CNOT H = CZ
+        if g.t == HAD && g.condition.is_none() {
+            let q = g.qs[0];
+            let Some(j) = next[i][0].filter(|&j| !dirty[j]) else {
+                continue;
+            };
+            let h = result[j].as_ref().unwrap();
+            let slot = h.qs.iter().position(|&q1| q1 == q).unwrap();
+            let Some(k) = next[j][slot].filter(|&k| !dirty[k]) else {
+                continue;
+            };
+            let k_gate = result[k].as_ref().unwrap();
+            if k_gate.t != HAD || k_gate.condition.is_some() {
+                continue;
+            }
+            if let Some(h1) = conjugate_by_had(h, q) {
+                result[i] = None;
+                result[k] = None;
+                result[j] = h1;
+                dirty[i] = true;
+                dirty[j] = true;
+                dirty[k] = true;
+                changed = true;
+            }
+        }
+    }
+
+    c.gates = result.into_iter().flatten().collect();
+    changed
+}
 
 impl Circuit {
-    /// Implements (roughly) `basic_optimization` from pyzx
+    /// Removes redundant gates with local rewrites, in the spirit of
+    /// `basic_optimization` from pyzx
+    ///
+    /// This repeatedly cancels or merges adjacent gates, such as inverse pairs and
+    /// rotations about the same axis, and simplifies gates between two Hadamards,
+    /// e.g. `H S H = X(π/2)` and `H CNOT H = CZ` when the Hadamards are on the
+    /// target. Doing the latter on both qubits of a CNOT reverses it. This is cheap,
+    /// so it can be run before or after ZX simplification, e.g. to clean up
+    /// extracted circuits.
     pub fn optimize(&mut self) {
-        panic!("Not implemented.");
+        while peephole_pass(self) {}
+    }
+
+    /// Returns an optimized copy of the circuit, see [Circuit::optimize]
+    pub fn to_optimized(&self) -> Circuit {
+        let mut c = self.clone();
+        c.optimize();
+        c
+    }
+
+    /// Merges rotations about the same axis which are separated by gates they
+    /// commute with
+    ///
+    /// A Z rotation commutes with any gate which acts on its qubit as Z, such as a
+    /// CZ or the control of a CNOT, and likewise for X rotations, see
+    /// [crate::dag::qubit_action]. All the rotations in such a run of commuting
+    /// gates on a qubit are added up into the first one, which reduces the number
+    /// of T gates e.g. in `T; CNOT; T` on the control. Unlike [Circuit::optimize],
+    /// this finds rotations which aren't adjacent, and unlike simplifying the
+    /// ZX-diagram, it leaves the rest of the circuit alone.
+    pub fn merge_rotations(&mut self) {
+        let mut result: Vec<Option<Gate>> = self.gates.drain(..).map(Some).collect();
+        // on each qubit, the action of the current run of commuting gates, and the
+        // first rotation in it
+        let mut run: Vec<(QubitAction, Option<usize>)> =
+            vec![(QubitAction::Other, None); self.num_qubits()];
+        // the total angle of each first rotation, and whether others were merged in
+        let mut total: Vec<Option<(Phase, bool)>> = vec![None; result.len()];
+        for i in 0..result.len() {
+            let g = result[i].as_ref().unwrap();
+            let rot = rotation(g).filter(|_| g.condition.is_none());
+            if let Some((x_axis, phase)) = rot {
+                let q = g.qs[0];
+                let a = if x_axis {
+                    QubitAction::X
+                } else {
+                    QubitAction::Z
+                };
+                match run[q] {
+                    (a1, Some(j)) if a1 == a => {
+                        if let Some((p, changed)) = &mut total[j] {
+                            *p += phase;
+                            *changed = true;
+                        }
+                        result[i] = None;
+                    }
+                    _ => {
+                        run[q] = (a, Some(i));
+                        total[i] = Some((phase, false));
+                    }
+                }
+                continue;
+            }
+            for (k, &q) in g.qs.iter().enumerate() {
+                let a = qubit_action(g, k);
+                if a == QubitAction::Other || a != run[q].0 {
+                    run[q] = (a, None);
+                }
+            }
+        }
+
+        for (j, t) in total.into_iter().enumerate() {
+            if let Some((phase, true)) = t {
+                let g = result[j].as_ref().unwrap();
+                let x_axis = matches!(g.t, NOT | XPhase);
+                result[j] = rotation_gate(x_axis, phase, g.qs[0]);
+            }
+        }
+        self.gates = result.into_iter().flatten().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+
+    #[test]
+    fn cancel_and_merge() {
+        let c = Circuit::from_qasm(
+            "qreg q[3];
+            h q[0]; h q[0];
+            cx q[0], q[1]; t q[2]; cx q[0], q[1];
+            t q[1]; t q[1];
+            s q[2]; sdg q[2];
+            cz q[1], q[2]; cz q[2], q[1];
+            rz(pi/4) q[0]; rz(-pi/4) q[0];
+            ccx q[0], q[1], q[2]; ccx q[1], q[0], q[2];",
+        )
+        .unwrap();
+        let c1 = c.to_optimized();
+        assert_eq!(c1.num_gates(), 2);
+        assert_eq!(c1.num_gates_of_type(T), 1);
+        assert_eq!(c1.num_gates_of_type(S), 1);
+        assert!(Tensor4::scalar_compare(&c, &c1));
+    }
+
+    #[test]
+    fn hadamard_conjugation() {
+        let c = Circuit::from_qasm(
+            "qreg q[2];
+            h q[0]; s q[0]; h q[0];
+            h q[1]; cx q[0], q[1]; h q[1];
+            h q[0]; h q[1]; cx q[0], q[1]; h q[0]; h q[1];",
+        )
+        .unwrap();
+        let c1 = c.to_optimized();
+        assert_eq!(c1.num_gates(), 3);
+        assert_eq!(c1.num_gates_of_type(HAD), 0);
+        assert_eq!(c1.gates[2], Gate::new(CNOT, vec![1, 0]));
+        assert!(Tensor4::scalar_compare(&c, &c1));
+    }
+
+    #[test]
+    fn merge_rotations() {
+        let mut c = Circuit::from_qasm(
+            "qreg q[3];
+            t q[0];
+            cx q[0], q[1];
+            cz q[2], q[0];
+            t q[0];
+            x q[1];
+            cx q[2], q[1];
+            x q[1];
+            t q[2];
+            h q[2];
+            t q[2];",
+        )
+        .unwrap();
+        let c0 = c.clone();
+        c.merge_rotations();
+        assert_eq!(c.num_gates(), 7);
+        assert_eq!(c.gates[0], Gate::new(S, vec![0]));
+        assert_eq!(c.num_gates_of_type(NOT), 0);
+        assert_eq!(c.num_gates_of_type(T), 2);
+        assert!(Tensor4::scalar_compare(&c, &c0));
+    }
+
+    #[test]
+    fn merge_rotations_random() {
+        for seed in 0..10 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(60)
+                .clifford_t(0.3)
+                .build();
+            let mut c1 = c.clone();
+            c1.merge_rotations();
+            assert!(c1.stats().tcount <= c.stats().tcount);
+            assert!(Tensor4::scalar_compare(&c, &c1));
+        }
+    }
+
+    #[test]
+    fn barriers() {
+        let mut c = Circuit::from_qasm(
+            "qreg q[2];
+            t q[0];
+            barrier q[0], q[1];
+            t q[0];
+            h q[1];
+            barrier q[1];
+            h q[1];",
+        )
+        .unwrap();
+        assert_eq!(c.to_optimized(), c);
+        c.merge_rotations();
+        assert_eq!(c.num_gates_of_type(T), 2);
+
+        c.remove_barriers();
+        c.optimize();
+        assert_eq!(c.gates, [Gate::new(S, vec![0])]);
+    }
+
+    #[test]
+    fn optimize_random() {
+        for seed in 0..10 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(60)
+                .clifford_t(0.3)
+                .build();
+            let c1 = c.to_optimized();
+            assert!(c1.num_gates() <= c.num_gates());
+            assert!(Tensor4::scalar_compare(&c, &c1));
+        }
     }
 }
@@ -0,0 +1,581 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resynthesis of circuits as Pauli rotations
+//!
+//! Pushing the Clifford gates of a circuit to the end turns each non-Clifford
+//! phase gate into a rotation about a Pauli operator, so the circuit becomes a
+//! sequence of Pauli rotations followed by a Clifford circuit, a [PauliForm].
+//! Rotations which commute can be reordered, which puts them into groups of
+//! mutually commuting rotations. Rotations about the same operator in a group
+//! merge into one, and the rotations of a group can be synthesised in an order
+//! where the basis changes and CNOT ladders of neighbouring rotations cancel. This
+//! is the idea of the PauliSimp pass of TKET.
+//!
+//! [pauli_simp] does all of this, e.g. before turning a circuit into a diagram.
+//! The result is equal to the original circuit up to a global phase.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::pauli_simp::pauli_simp;
+//! // the CNOTs swap the qubits, so the two T gates merge into an S gate
+//! let c = Circuit::from_qasm(
+//!     "qreg q[2]; t q[0]; cx q[0], q[1]; cx q[1], q[0]; cx q[0], q[1]; t q[1];",
+//! )
+//! .unwrap();
+//! let c1 = pauli_simp(&c).unwrap();
+//! assert_eq!(c1.stats().non_cliff, 0);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::phase::Phase;
+use num::Zero;
+use std::fmt;
+
+/// A Pauli operator `i^k X^x Z^z` on some number of qubits
+///
+/// `X^x` is the product of `X` on the qubits where `x` is set, and likewise for
+/// `Z^z`, so e.g. `Y = i X Z`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PauliString {
+    pub x: Vec<bool>,
+    pub z: Vec<bool>,
+    /// The power of `i` in front, mod 4
+    pub k: u8,
+}
+
+impl PauliString {
+    pub fn identity(n: usize) -> Self {
+        PauliString {
+            x: vec![false; n],
+            z: vec![false; n],
+            k: 0,
+        }
+    }
+
+    /// The operator `i^k X^x Z^z` where `x` and `z` are given by lists of qubits
+    pub fn new(n: usize, xs: &[usize], zs: &[usize], k: u8) -> Self {
+        let mut p = PauliString::identity(n);
+        for &q in xs {
+            p.x[q] = true;
+        }
+        for &q in zs {
+            p.z[q] = true;
+        }
+        p.k = k % 4;
+        p
+    }
+
+    /// The tensor product of the Paulis written as letters `I`, `X`, `Y` and `Z`,
+    /// e.g. `"XIZ"`, or None if there are other characters
+    pub fn from_letters(s: &str) -> Option<Self> {
+        let mut p = PauliString::identity(s.chars().count());
+        for (q, ch) in s.chars().enumerate() {
+            match ch {
+                'I' => {}
+                'X' => p.x[q] = true,
+                'Z' => p.z[q] = true,
+                'Y' => {
+                    p.x[q] = true;
+                    p.z[q] = true;
+                    p.k = (p.k + 1) % 4;
+                }
+                _ => return None,
+            }
+        }
+        Some(p)
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.x.len()
+    }
+
+    /// The product `self * other`
+    pub fn mul(&self, other: &PauliString) -> PauliString {
+        // X^x1 Z^z1 X^x2 Z^z2 = (-1)^(z1.x2) X^(x1 + x2) Z^(z1 + z2)
+        let n = self.num_qubits();
+        let swaps = (0..n).filter(|&q| self.z[q] && other.x[q]).count();
+        PauliString {
+            x: (0..n).map(|q| self.x[q] ^ other.x[q]).collect(),
+            z: (0..n).map(|q| self.z[q] ^ other.z[q]).collect(),
+            k: ((self.k as usize + other.k as usize + 2 * swaps) % 4) as u8,
+        }
+    }
+
+    pub fn commutes(&self, other: &PauliString) -> bool {
+        (0..self.num_qubits())
+            .filter(|&q| (self.x[q] && other.z[q]) != (self.z[q] && other.x[q]))
+            .count()
+            % 2
+            == 0
+    }
+
+    /// The power of `i` in front of the tensor product of `I`, `X`, `Y` and `Z`
+    fn letter_phase(&self) -> usize {
+        let ys = (0..self.num_qubits())
+            .filter(|&q| self.x[q] && self.z[q])
+            .count();
+        (self.k as usize + 4 - ys % 4) % 4
+    }
+
+    /// Whether the operator is Hermitian, i.e. plus or minus a tensor product of
+    /// `I`, `X`, `Y` and `Z`
+    pub fn is_hermitian(&self) -> bool {
+        self.letter_phase() % 2 == 0
+    }
+
+    /// Whether the operator is minus a tensor product of `I`, `X`, `Y` and `Z`
+    pub fn is_negative(&self) -> bool {
+        self.letter_phase() == 2
+    }
+
+    pub fn negate(&mut self) {
+        self.k = (self.k + 2) % 4;
+    }
+
+    /// The Pauli on qubit `q`, as one of `'I'`, `'X'`, `'Y'` or `'Z'`
+    pub fn letter(&self, q: usize) -> char {
+        match (self.x[q], self.z[q]) {
+            (false, false) => 'I',
+            (true, false) => 'X',
+            (false, true) => 'Z',
+            (true, true) => 'Y',
+        }
+    }
+
+    /// The qubits where the operator is not the identity
+    pub fn support(&self) -> Vec<usize> {
+        (0..self.num_qubits())
+            .filter(|&q| self.x[q] || self.z[q])
+            .collect()
+    }
+}
+
+impl fmt::Display for PauliString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = ["+", "+i", "-", "-i"][self.letter_phase()];
+        write!(f, "{}", sign)?;
+        for q in 0..self.num_qubits() {
+            write!(f, "{}", self.letter(q))?;
+        }
+        Ok(())
+    }
+}
+
+/// The rotation `exp(-iπ phase P / 2)` about a Hermitian Pauli operator `P`
+///
+/// For `P = Z` this is a Z phase gate with the given phase, up to a global phase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PauliRotation {
+    pub pauli: PauliString,
+    pub phase: Phase,
+}
+
+impl PauliRotation {
+    /// The same rotation, about a positive Pauli operator
+    fn normalized(mut self) -> Self {
+        if self.pauli.is_negative() {
+            self.pauli.negate();
+            self.phase = -self.phase;
+        }
+        self
+    }
+}
+
+/// The images `C† X_q C` and `C† Z_q C` of the single-qubit Paulis under a
+/// Clifford circuit `C`
+struct Frame {
+    x: Vec<PauliString>,
+    z: Vec<PauliString>,
+}
+
+impl Frame {
+    fn new(n: usize) -> Self {
+        Frame {
+            x: (0..n).map(|q| PauliString::new(n, &[q], &[], 0)).collect(),
+            z: (0..n).map(|q| PauliString::new(n, &[], &[q], 0)).collect(),
+        }
+    }
+
+    /// The image `C† P C` of a Pauli operator
+    fn image(&self, p: &PauliString) -> PauliString {
+        let mut r = PauliString::identity(self.x.len());
+        r.k = p.k;
+        for (q, img) in self.x.iter().enumerate() {
+            if p.x[q] {
+                r = r.mul(img);
+            }
+        }
+        for (q, img) in self.z.iter().enumerate() {
+            if p.z[q] {
+                r = r.mul(img);
+            }
+        }
+        r
+    }
+
+    /// Append a Clifford gate `G` to `C`, given `G† X_q G` and `G† Z_q G` for each
+    /// qubit `q` it acts on
+    fn update(&mut self, conj: &[(usize, PauliString, PauliString)]) {
+        let imgs: Vec<_> = conj
+            .iter()
+            .map(|(q, px, pz)| (*q, self.image(px), self.image(pz)))
+            .collect();
+        for (q, px, pz) in imgs {
+            self.x[q] = px;
+            self.z[q] = pz;
+        }
+    }
+
+    /// Append a basic Clifford gate. Returns false if the gate is not one.
+    fn apply(&mut self, t: GType, qs: &[usize]) -> bool {
+        let n = self.x.len();
+        let p = |xs: &[usize], zs: &[usize], k: u8| PauliString::new(n, xs, zs, k);
+        let conj = match (t, qs) {
+            (HAD, &[q]) => vec![(q, p(&[], &[q], 0), p(&[q], &[], 0))],
+            (S, &[q]) => vec![(q, p(&[q], &[q], 3), p(&[], &[q], 0))],
+            (Sdg, &[q]) => vec![(q, p(&[q], &[q], 1), p(&[], &[q], 0))],
+            (Z, &[q]) => vec![(q, p(&[q], &[], 2), p(&[], &[q], 0))],
+            (NOT, &[q]) => vec![(q, p(&[q], &[], 0), p(&[], &[q], 2))],
+            (CNOT, &[c, t]) => vec![
+                (c, p(&[c, t], &[], 0), p(&[], &[c], 0)),
+                (t, p(&[t], &[], 0), p(&[], &[c, t], 0)),
+            ],
+            (CZ, &[a, b]) => vec![
+                (a, p(&[a], &[b], 0), p(&[], &[a], 0)),
+                (b, p(&[b], &[a], 0), p(&[], &[b], 0)),
+            ],
+            (SWAP, &[a, b]) => vec![
+                (a, p(&[b], &[], 0), p(&[], &[b], 0)),
+                (b, p(&[a], &[], 0), p(&[], &[a], 0)),
+            ],
+            _ => return false,
+        };
+        self.update(&conj);
+        true
+    }
+}
+
+/// The named gate for a Clifford Z phase, if there is one
+fn clifford_phase_gate(phase: Phase) -> Option<GType> {
+    let r = phase.to_rational();
+    if r.is_zero() {
+        None
+    } else if r == (1, 2).into() {
+        Some(S)
+    } else if r == (-1, 2).into() {
+        Some(Sdg)
+    } else {
+        Some(Z)
+    }
+}
+
+/// A circuit as a sequence of Pauli rotations followed by a Clifford circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PauliForm {
+    pub num_qubits: usize,
+    /// The rotations, in the order they are applied
+    pub rotations: Vec<PauliRotation>,
+    pub clifford: Circuit,
+}
+
+impl PauliForm {
+    /// Push the Clifford gates of a circuit past its non-Clifford phase gates
+    ///
+    /// The circuit is first decomposed with [Circuit::to_basic_gates]. Returns None
+    /// if it contains gates which are not unitary, or not known.
+    pub fn from_circuit(c: &Circuit) -> Option<PauliForm> {
+        let n = c.num_qubits();
+        let mut frame = Frame::new(n);
+        let mut rotations = Vec::new();
+        let mut clifford = Circuit::new(n);
+
+        for g in c.to_basic_gates().gates {
+            if g.t == Barrier {
+                continue;
+            }
+            let q = g.qs.first().copied();
+            let rotation = match g.t {
+                T => Some((PauliString::new(n, &[], &[q?], 0), Phase::from((1, 4)))),
+                Tdg => Some((PauliString::new(n, &[], &[q?], 0), Phase::from((-1, 4)))),
+                ZPhase if !g.phase.is_clifford() => {
+                    Some((PauliString::new(n, &[], &[q?], 0), g.phase))
+                }
+                XPhase if !g.phase.is_clifford() => {
+                    Some((PauliString::new(n, &[q?], &[], 0), g.phase))
+                }
+                _ => None,
+            };
+            if let Some((pauli, phase)) = rotation {
+                rotations.push(PauliRotation {
+                    pauli: frame.image(&pauli),
+                    phase,
+                });
+                continue;
+            }
+
+            let ok = match g.t {
+                ZPhase => clifford_phase_gate(g.phase).map_or(true, |t| frame.apply(t, &g.qs)),
+                XPhase => {
+                    frame.apply(HAD, &g.qs)
+                        && clifford_phase_gate(g.phase).map_or(true, |t| frame.apply(t, &g.qs))
+                        && frame.apply(HAD, &g.qs)
+                }
+                XCX => {
+                    frame.apply(HAD, &g.qs[..1])
+                        && frame.apply(CNOT, &g.qs)
+                        && frame.apply(HAD, &g.qs[..1])
+                }
+                t => frame.apply(t, &g.qs),
+            };
+            if !ok {
+                return None;
+            }
+            clifford.push(g);
+        }
+
+        Some(PauliForm {
+            num_qubits: n,
+            rotations,
+            clifford,
+        })
+    }
+
+    /// Group the rotations into sets of mutually commuting rotations
+    ///
+    /// Each rotation is moved to the earliest group it can reach by commuting past
+    /// the later groups, and merged with a rotation about the same operator there.
+    /// Rotations whose phases cancel are dropped.
+    pub fn groups(&self) -> Vec<Vec<PauliRotation>> {
+        let mut groups: Vec<Vec<PauliRotation>> = Vec::new();
+        for r in &self.rotations {
+            let r = r.clone().normalized();
+            let mut k = groups.len();
+            while k > 0 && groups[k - 1].iter().all(|s| s.pauli.commutes(&r.pauli)) {
+                k -= 1;
+            }
+            if k == groups.len() {
+                groups.push(vec![r]);
+            } else if let Some(i) = groups[k].iter().position(|s| s.pauli == r.pauli) {
+                groups[k][i].phase += r.phase;
+                if groups[k][i].phase.is_zero() {
+                    groups[k].remove(i);
+                }
+            } else {
+                groups[k].push(r);
+            }
+        }
+        groups.retain(|gr| !gr.is_empty());
+        groups
+    }
+
+    /// Replace the rotations by their [PauliForm::groups], each sorted by operator
+    pub fn simplify(&mut self) {
+        self.rotations = self
+            .groups()
+            .into_iter()
+            .flat_map(|mut gr| {
+                gr.sort_by_key(|r| r.pauli.to_string());
+                gr
+            })
+            .collect();
+    }
+
+    /// Synthesise the rotations one by one, followed by the Clifford circuit
+    ///
+    /// Each rotation is a basis change, a ladder of CNOTs, a Z phase gate and the
+    /// same in reverse. Gates which cancel with the previous gate on their qubits are
+    /// dropped.
+    pub fn to_circuit(&self) -> Circuit {
+        let mut e = Emitter::new(self.num_qubits);
+        for r in &self.rotations {
+            let r = r.clone().normalized();
+            let support = r.pauli.support();
+            let Some(&t) = support.last() else {
+                // a global phase
+                continue;
+            };
+            for &q in &support {
+                match r.pauli.letter(q) {
+                    'X' => e.push(Gate::new(HAD, vec![q])),
+                    'Y' => {
+                        e.push(Gate::new(Sdg, vec![q]));
+                        e.push(Gate::new(HAD, vec![q]));
+                    }
+                    _ => {}
+                }
+            }
+            for &q in &support[..support.len() - 1] {
+                e.push(Gate::new(CNOT, vec![q, t]));
+            }
+            let r1 = r.phase.to_rational();
+            if r1 == (1, 4).into() {
+                e.push(Gate::new(T, vec![t]));
+            } else if r1 == (-1, 4).into() {
+                e.push(Gate::new(Tdg, vec![t]));
+            } else if r.phase.is_clifford() {
+                if let Some(gt) = clifford_phase_gate(r.phase) {
+                    e.push(Gate::new(gt, vec![t]));
+                }
+            } else {
+                e.push(Gate::new_with_phase(ZPhase, vec![t], r.phase));
+            }
+            for &q in support[..support.len() - 1].iter().rev() {
+                e.push(Gate::new(CNOT, vec![q, t]));
+            }
+            for &q in support.iter().rev() {
+                match r.pauli.letter(q) {
+                    'X' => e.push(Gate::new(HAD, vec![q])),
+                    'Y' => {
+                        e.push(Gate::new(HAD, vec![q]));
+                        e.push(Gate::new(S, vec![q]));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for g in &self.clifford.gates {
+            e.push(g.clone());
+        }
+        e.finish()
+    }
+}
+
+/// A circuit under construction, where each gate cancels with the previous gate on
+/// its qubits if the two are inverse to each other
+struct Emitter {
+    num_qubits: usize,
+    gates: Vec<Option<Gate>>,
+    /// For each qubit, the indices of the gates on it which are still there
+    last: Vec<Vec<usize>>,
+}
+
+impl Emitter {
+    fn new(n: usize) -> Self {
+        Emitter {
+            num_qubits: n,
+            gates: Vec::new(),
+            last: vec![Vec::new(); n],
+        }
+    }
+
+    fn push(&mut self, g: Gate) {
+        if let Some(&i) = g.qs.first().and_then(|&q| self.last[q].last()) {
+            let prev = self.gates[i].as_ref().unwrap();
+            let mut inv = prev.clone();
+            inv.adjoint();
+            if inv == g && g.qs.iter().all(|&q| self.last[q].last() == Some(&i)) {
+                for &q in &g.qs {
+                    self.last[q].pop();
+                }
+                self.gates[i] = None;
+                return;
+            }
+        }
+        for &q in &g.qs {
+            self.last[q].push(self.gates.len());
+        }
+        self.gates.push(Some(g));
+    }
+
+    fn finish(self) -> Circuit {
+        let mut c = Circuit::new(self.num_qubits);
+        for g in self.gates.into_iter().flatten() {
+            c.push(g);
+        }
+        c
+    }
+}
+
+/// Resynthesise a circuit as groups of commuting Pauli rotations
+///
+/// See the [module docs](self). Returns None if the circuit contains gates which
+/// are not unitary, or not known.
+pub fn pauli_simp(c: &Circuit) -> Option<Circuit> {
+    let mut f = PauliForm::from_circuit(c)?;
+    f.simplify();
+    Some(f.to_circuit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+
+    #[test]
+    fn pauli_strings() {
+        let x = PauliString::new(2, &[0], &[], 0);
+        let z = PauliString::new(2, &[], &[0], 0);
+        let y = PauliString::new(2, &[0], &[0], 1);
+        assert_eq!(x.to_string(), "+XI");
+        assert_eq!(y.to_string(), "+YI");
+        assert_eq!(z.mul(&x).to_string(), "+iYI");
+        assert_eq!(x.mul(&z).to_string(), "-iYI");
+        assert_eq!(x.mul(&z).mul(&y), PauliString::new(2, &[], &[], 3));
+        assert!(!x.commutes(&z));
+        assert!(x.commutes(&x));
+        let xx = PauliString::new(2, &[0, 1], &[], 0);
+        let zz = PauliString::new(2, &[], &[0, 1], 0);
+        assert!(xx.commutes(&zz));
+        assert!(y.is_hermitian() && !y.is_negative());
+        assert!(!z.mul(&x).is_hermitian());
+        assert_eq!(PauliString::from_letters("YI"), Some(y));
+        assert_eq!(PauliString::from_letters("YY").unwrap().to_string(), "+YY");
+        assert_eq!(PauliString::from_letters("XA"), None);
+    }
+
+    #[test]
+    fn pauli_form() {
+        let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; t q[1]; s q[1];").unwrap();
+        let f = PauliForm::from_circuit(&c).unwrap();
+        assert_eq!(f.rotations.len(), 1);
+        assert_eq!(f.rotations[0].pauli.to_string(), "+XZ");
+        assert_eq!(f.clifford.num_gates(), 3);
+        assert!(Tensor4::scalar_compare(&c, &f.to_circuit()));
+
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(40)
+                .p_t(0.2)
+                .with_cliffords()
+                .build();
+            let f = PauliForm::from_circuit(&c).unwrap();
+            assert_eq!(f.rotations.len(), c.stats().non_cliff);
+            assert!(Tensor4::scalar_compare(&c, &f.to_circuit()));
+        }
+    }
+
+    #[test]
+    fn pauli_simp_random() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(60)
+                .clifford_t(0.3)
+                .build();
+            let c1 = pauli_simp(&c).unwrap();
+            assert!(c1.stats().non_cliff <= c.stats().non_cliff);
+            assert!(Tensor4::scalar_compare(&c, &c1));
+        }
+
+        let c = Circuit::from_qasm("qreg q[1]; t q[0]; h q[0]; h q[0]; tdg q[0];").unwrap();
+        assert_eq!(pauli_simp(&c).unwrap().num_gates(), 0);
+    }
+}
@@ -0,0 +1,448 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A graph backend built on persistent data structures.
+//!
+//! Vertex data and adjacency lists are stored in persistent B-trees, and each
+//! adjacency list sits behind an [Arc]. Cloning a graph therefore only bumps a
+//! few reference counts, and a subsequent mutation copies just the tree nodes
+//! and neighbourhoods it touches. This suits the decomposer, which clones a
+//! graph for every branch and then changes a handful of vertices.
+//!
+//! Vertices are iterated in increasing order, so iteration is deterministic.
+
+pub use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use num::rational::Rational64;
+use std::sync::Arc;
+
+pub type VTab<T> = im::OrdMap<V, T>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+    vdata: VTab<VData>,
+    edata: VTab<Arc<Vec<(V, EType)>>>,
+    inputs: Vec<V>,
+    outputs: Vec<V>,
+    numv: usize,
+    nume: usize,
+    freshv: V,
+    scalar: ScalarN,
+    stats: GraphStats,
+    bqubits: BoundaryQubits,
+}
+
+impl Graph {
+    /// Returns true if the two graphs share all of their vertex and edge data
+    ///
+    /// This is the case for a freshly cloned graph, and can be used to check that
+    /// no copying took place.
+    pub fn ptr_eq(&self, other: &Graph) -> bool {
+        self.vdata.ptr_eq(&other.vdata) && self.edata.ptr_eq(&other.edata)
+    }
+
+    /// Returns a mutable reference to the adjacency list of `v`, copying it
+    /// first if it is shared with another graph.
+    fn nhd_mut(&mut self, v: V) -> Option<&mut Vec<(V, EType)>> {
+        self.edata.get_mut(&v).map(Arc::make_mut)
+    }
+
+    /// Removes vertex 't' from the adjacency list of 's'. This private method
+    /// is used by remove_edge and remove_vertex to make the latter slightly
+    /// more efficient.
+    fn remove_half_edge(&mut self, s: V, t: V) {
+        if let Some(nhd) = self.nhd_mut(s) {
+            if let Some(i) = nhd.iter().position(|&(v, _)| v == t) {
+                nhd.swap_remove(i);
+                let d = nhd.len();
+                self.stats.change_degree(d + 1, d);
+            }
+        }
+    }
+
+    /// Appends 't' to the adjacency list of 's', keeping the cached stats up to date.
+    fn add_half_edge(&mut self, s: V, t: V, ety: EType, err: &str) {
+        let nhd = self.nhd_mut(s).expect(err);
+        nhd.push((t, ety));
+        let d = nhd.len();
+        self.stats.change_degree(d - 1, d);
+    }
+
+    /// Sets the type of the edge to 't' in the adjacency list of 's'.
+    fn set_half_edge_type(&mut self, s: V, t: V, ety: EType, err: &str) {
+        let nhd = self.nhd_mut(s).expect(err);
+        let i = nhd
+            .iter()
+            .position(|&(v, _)| v == t)
+            .expect("Edge not found");
+        nhd[i] = (t, ety);
+    }
+
+    /// Applies `f` to the data of vertex `v`, keeping the cached stats up to date.
+    fn update_vdata(&mut self, v: V, f: impl FnOnce(&mut VData)) {
+        let d = self.vdata.get_mut(&v).expect("Vertex not found");
+        let old = *d;
+        f(d);
+        self.stats.update_vertex(&old, d);
+    }
+
+    fn nhd(&self, v: V) -> &[(V, EType)] {
+        self.edata.get(&v).expect("Vertex not found")
+    }
+}
+
+impl GraphLike for Graph {
+    fn new() -> Graph {
+        Graph {
+            vdata: VTab::new(),
+            edata: VTab::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            numv: 0,
+            nume: 0,
+            freshv: 0,
+            scalar: Scalar::one(),
+            stats: GraphStats::default(),
+            bqubits: BoundaryQubits::default(),
+        }
+    }
+
+    fn vindex(&self) -> V {
+        self.freshv
+    }
+
+    fn num_vertices(&self) -> usize {
+        self.numv
+    }
+
+    fn num_edges(&self) -> usize {
+        self.nume
+    }
+
+    fn vertices(&self) -> VIter<'_> {
+        VIter::Persistent(self.vdata.keys())
+    }
+
+    fn edges(&self) -> EIter<'_> {
+        EIter::Persistent(self.nume, self.edata.iter(), None)
+    }
+
+    fn inputs(&self) -> &Vec<V> {
+        &self.inputs
+    }
+    fn inputs_mut(&mut self) -> &mut Vec<V> {
+        &mut self.inputs
+    }
+    fn set_inputs(&mut self, inputs: Vec<V>) {
+        self.inputs = inputs;
+    }
+    fn outputs(&self) -> &Vec<V> {
+        &self.outputs
+    }
+    fn set_outputs(&mut self, outputs: Vec<V>) {
+        self.outputs = outputs;
+    }
+    fn outputs_mut(&mut self) -> &mut Vec<V> {
+        &mut self.outputs
+    }
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        self.add_vertex_with_data(VData {
+            ty,
+            phase: Rational64::new(0, 1).into(),
+            qubit: 0,
+            row: 0,
+        })
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        let v = self.freshv;
+        self.freshv += 1;
+        self.numv += 1;
+        self.stats.add_vertex(&d);
+        self.vdata.insert(v, d);
+        self.edata.insert(v, Arc::new(Vec::new()));
+        v
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        self.numv -= 1;
+
+        let d = self.vdata.remove(&v).expect("Vertex not found");
+        let adj = self.edata.remove(&v).expect("Vertex not found");
+        self.bqubits.remove(v);
+        self.stats.remove_vertex(&d, adj.len());
+
+        for &(v1, _) in adj.iter() {
+            self.nume -= 1;
+            self.remove_half_edge(v1, v);
+        }
+    }
+
+    fn add_edge_with_type(&mut self, s: V, t: V, ety: EType) {
+        self.nume += 1;
+
+        self.add_half_edge(s, t, ety, "Source vertex not found");
+        self.add_half_edge(t, s, ety, "Target vertex not found");
+    }
+
+    fn remove_edge(&mut self, s: V, t: V) {
+        self.nume -= 1;
+        self.remove_half_edge(s, t);
+        self.remove_half_edge(t, s);
+    }
+
+    fn set_phase(&mut self, v: V, phase: impl Into<Phase>) {
+        let phase = phase.into();
+        self.update_vdata(v, |d| d.phase = phase);
+    }
+
+    fn phase(&self, v: V) -> Phase {
+        self.vdata.get(&v).expect("Vertex not found").phase
+    }
+
+    fn add_to_phase(&mut self, v: V, phase: impl Into<Phase>) {
+        let phase = phase.into();
+        self.update_vdata(v, |d| d.phase = (d.phase + phase).normalize());
+    }
+
+    fn set_vertex_type(&mut self, v: V, ty: VType) {
+        self.update_vdata(v, |d| d.ty = ty);
+    }
+
+    fn vertex_data(&self, v: V) -> VData {
+        *self.vdata.get(&v).expect("Vertex not found")
+    }
+
+    fn vertex_type(&self, v: V) -> VType {
+        self.vdata.get(&v).expect("Vertex not found").ty
+    }
+
+    fn set_edge_type(&mut self, s: V, t: V, ety: EType) {
+        self.set_half_edge_type(s, t, ety, "Source vertex not found");
+        self.set_half_edge_type(t, s, ety, "Target vertex not found");
+    }
+
+    fn edge_type_opt(&self, s: V, t: V) -> Option<EType> {
+        self.edata
+            .get(&s)
+            .and_then(|nhd| nhd.iter().find(|&&(v, _)| v == t))
+            .map(|&(_, et)| et)
+    }
+
+    fn set_coord(&mut self, v: V, coord: impl Into<Coord>) {
+        let coord = coord.into();
+        let d = self.vdata.get_mut(&v).expect("Vertex not found");
+        d.qubit = coord.x;
+        d.row = coord.y;
+    }
+
+    fn coord(&self, v: V) -> Coord {
+        let d = self.vdata.get(&v).expect("Vertex not found");
+        Coord::new(d.qubit, d.row)
+    }
+
+    fn set_qubit(&mut self, v: V, qubit: i32) {
+        self.vdata.get_mut(&v).expect("Vertex not found").qubit = qubit;
+    }
+
+    fn qubit(&self, v: V) -> i32 {
+        self.vdata.get(&v).expect("Vertex not found").qubit
+    }
+
+    fn set_row(&mut self, v: V, row: i32) {
+        self.vdata.get_mut(&v).expect("Vertex not found").row = row;
+    }
+
+    fn row(&self, v: V) -> i32 {
+        self.vdata.get(&v).expect("Vertex not found").row
+    }
+
+    fn neighbors(&self, v: V) -> NeighborIter<'_> {
+        NeighborIter::Vec(self.nhd(v).iter())
+    }
+
+    fn incident_edges(&self, v: V) -> IncidentEdgeIter<'_> {
+        IncidentEdgeIter::Vec(self.nhd(v).iter())
+    }
+
+    fn degree(&self, v: V) -> usize {
+        self.nhd(v).len()
+    }
+
+    fn scalar(&self) -> &ScalarN {
+        &self.scalar
+    }
+    fn scalar_mut(&mut self) -> &mut ScalarN {
+        &mut self.scalar
+    }
+
+    fn stats(&self) -> &GraphStats {
+        &self.stats
+    }
+
+    fn boundary_qubits(&self) -> &BoundaryQubits {
+        &self.bqubits
+    }
+
+    fn boundary_qubits_mut(&mut self) -> &mut BoundaryQubits {
+        &mut self.bqubits
+    }
+
+    fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
+    where
+        F: Fn(V, V, EType) -> bool,
+    {
+        for (&v0, nhd) in self.edata.iter() {
+            for &(v1, et) in nhd.iter() {
+                if v0 <= v1 && f(v0, v1, et) {
+                    return Some((v0, v1, et));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_vertex<F>(&self, f: F) -> Option<V>
+    where
+        F: Fn(V) -> bool,
+    {
+        self.vdata.keys().find(|&&v| f(v)).copied()
+    }
+
+    fn contains_vertex(&self, v: V) -> bool {
+        self.vdata.contains_key(&v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::decompose::Decomposer;
+    use crate::simplify::full_simp;
+    use crate::tensor::ToTensor;
+
+    fn simple_graph() -> (Graph, Vec<V>) {
+        let mut g = Graph::new();
+        let vs = vec![
+            g.add_vertex(VType::B),
+            g.add_vertex(VType::B),
+            g.add_vertex(VType::Z),
+            g.add_vertex(VType::Z),
+            g.add_vertex(VType::X),
+            g.add_vertex(VType::X),
+            g.add_vertex(VType::B),
+            g.add_vertex(VType::B),
+        ];
+        g.add_edge(vs[0], vs[2]);
+        g.add_edge(vs[1], vs[3]);
+        g.add_edge(vs[2], vs[4]);
+        g.add_edge(vs[2], vs[5]);
+        g.add_edge(vs[3], vs[4]);
+        g.add_edge(vs[3], vs[5]);
+        g.add_edge(vs[4], vs[6]);
+        g.add_edge(vs[5], vs[7]);
+        (g, vs)
+    }
+
+    #[test]
+    fn create_simple_graph() {
+        let (g, vs) = simple_graph();
+        assert_eq!(g.num_vertices(), 8);
+        assert_eq!(g.num_edges(), 8);
+        assert_eq!(g.vertex_vec(), vs);
+        assert_eq!(g.edges().count(), 8);
+        assert_eq!(g.degree(vs[2]), 3);
+    }
+
+    #[test]
+    fn edge_iterator() {
+        let (mut g, vs) = simple_graph();
+        g.set_edge_type(vs[1], vs[3], EType::H);
+
+        let mut edges = Vec::from_iter(g.edges());
+        let mut expected_edges = vec![
+            (vs[0], vs[2], EType::N),
+            (vs[1], vs[3], EType::H),
+            (vs[2], vs[4], EType::N),
+            (vs[2], vs[5], EType::N),
+            (vs[3], vs[4], EType::N),
+            (vs[3], vs[5], EType::N),
+            (vs[4], vs[6], EType::N),
+            (vs[5], vs[7], EType::N),
+        ];
+
+        edges.sort();
+        expected_edges.sort();
+        assert_eq!(expected_edges, edges);
+    }
+
+    #[test]
+    fn clone_shares_structure() {
+        let (g, vs) = simple_graph();
+        let mut h = g.clone();
+        assert!(g.ptr_eq(&h));
+
+        h.remove_vertex(vs[2]);
+        h.set_phase(vs[3], Rational64::new(1, 4));
+        assert!(!g.ptr_eq(&h));
+
+        // the original is unaffected by changes to the clone
+        assert_eq!(g.num_vertices(), 8);
+        assert_eq!(g.num_edges(), 8);
+        assert_eq!(g.degree(vs[4]), 3);
+        assert_eq!(g.phase(vs[3]), Phase::zero());
+        assert_eq!(h.num_vertices(), 7);
+        assert_eq!(h.num_edges(), 5);
+        assert_eq!(h.degree(vs[4]), 2);
+        assert_eq!(h.tcount(), 1);
+        assert_eq!(g.tcount(), 0);
+    }
+
+    #[test]
+    fn simp_and_decompose() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        let mut h: crate::vec_graph::Graph = c.to_graph();
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        full_simp(&mut g);
+        full_simp(&mut h);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        g.plug_inputs(&[BasisElem::Z0; 4]);
+        g.plug_outputs(&[BasisElem::Z0; 4]);
+        h.plug_inputs(&[BasisElem::Z0; 4]);
+        h.plug_outputs(&[BasisElem::Z0; 4]);
+
+        let mut d = Decomposer::new(&g);
+        d.with_full_simp();
+        d.decomp_all();
+        let mut e = Decomposer::new(&h);
+        e.with_full_simp();
+        e.decomp_all();
+        assert_eq!(d.scalar, e.scalar);
+    }
+}
@@ -0,0 +1,146 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversions between quizx graphs and [petgraph] graphs.
+//!
+//! Vertices become nodes weighted by their [VData] (type, phase, qubit and row)
+//! and edges are weighted by their [EType]. This makes it possible to run the
+//! standard petgraph algorithms on a ZX-diagram.
+//!
+//! Note that petgraph has no notion of inputs, outputs, or global scalars, so
+//! these are not carried over by the `From` conversions. Use [to_petgraph] and
+//! [from_petgraph] to get the vertex maps needed to restore them.
+//!
+//! ```
+//! # use quizx::graph::*;
+//! # use quizx::vec_graph::Graph;
+//! # use quizx::petgraph_conv::PetGraph;
+//! let mut g = Graph::new();
+//! let v0 = g.add_vertex(VType::Z);
+//! let v1 = g.add_vertex(VType::X);
+//! let v2 = g.add_vertex(VType::Z);
+//! g.add_edge(v0, v1);
+//!
+//! let pg = PetGraph::from(&g);
+//! assert_eq!(petgraph::algo::connected_components(&pg), 2);
+//!
+//! let h = Graph::from(&pg);
+//! assert_eq!(h.num_vertices(), 3);
+//! assert_eq!(h.num_edges(), 1);
+//! ```
+
+use crate::graph::*;
+use crate::{hash_graph, vec_graph};
+use petgraph::graph::{NodeIndex, UnGraph};
+use rustc_hash::FxHashMap;
+
+/// An undirected petgraph graph carrying quizx vertex and edge data
+pub type PetGraph = UnGraph<VData, EType>;
+
+/// Convert a graph to a [PetGraph]
+///
+/// Returns the new graph, along with a map from vertices of `g` to node indices.
+pub fn to_petgraph(g: &impl GraphLike) -> (PetGraph, FxHashMap<V, NodeIndex>) {
+    let mut pg = PetGraph::with_capacity(g.num_vertices(), g.num_edges());
+    let mut vmap = FxHashMap::default();
+
+    for v in g.vertices() {
+        vmap.insert(v, pg.add_node(g.vertex_data(v)));
+    }
+
+    for (s, t, et) in g.edges() {
+        pg.add_edge(vmap[&s], vmap[&t], et);
+    }
+
+    (pg, vmap)
+}
+
+/// Convert a [PetGraph] to a quizx graph
+///
+/// Returns the new graph, along with a vec mapping node indices to vertices.
+/// Parallel edges are merged with [GraphLike::add_edge_smart], so they should
+/// only occur between Z and X spiders.
+pub fn from_petgraph<G: GraphLike>(pg: &PetGraph) -> (G, Vec<V>) {
+    let mut g = G::new();
+    let vmap: Vec<V> = pg
+        .node_indices()
+        .map(|n| g.add_vertex_with_data(pg[n]))
+        .collect();
+
+    for e in pg.edge_indices() {
+        let (s, t) = pg.edge_endpoints(e).unwrap();
+        g.add_edge_smart(vmap[s.index()], vmap[t.index()], pg[e]);
+    }
+
+    (g, vmap)
+}
+
+impl From<&vec_graph::Graph> for PetGraph {
+    fn from(g: &vec_graph::Graph) -> Self {
+        to_petgraph(g).0
+    }
+}
+
+impl From<&hash_graph::Graph> for PetGraph {
+    fn from(g: &hash_graph::Graph) -> Self {
+        to_petgraph(g).0
+    }
+}
+
+impl From<&PetGraph> for vec_graph::Graph {
+    fn from(pg: &PetGraph) -> Self {
+        from_petgraph(pg).0
+    }
+}
+
+impl From<&PetGraph> for hash_graph::Graph {
+    fn from(pg: &PetGraph) -> Self {
+        from_petgraph(pg).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn petgraph_roundtrip() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+
+        let (pg, vmap) = to_petgraph(&g);
+        assert_eq!(pg.node_count(), g.num_vertices());
+        assert_eq!(pg.edge_count(), g.num_edges());
+        for v in g.vertices() {
+            assert_eq!(pg[vmap[&v]], g.vertex_data(v));
+        }
+
+        let (mut h, hmap): (Graph, _) = from_petgraph(&pg);
+        h.set_inputs(g.inputs().iter().map(|v| hmap[vmap[v].index()]).collect());
+        h.set_outputs(g.outputs().iter().map(|v| hmap[vmap[v].index()]).collect());
+        *h.scalar_mut() = g.scalar().clone();
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+}
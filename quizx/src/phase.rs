@@ -12,7 +12,7 @@ use utils::limit_denominator;
 /// A phase, expressed in half-turns and encoded as a rational number.
 ///
 /// The phase is always normalized to be in the range (-1,1].
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Phase {
     r: Rational64,
 }
@@ -59,7 +59,7 @@ impl Phase {
 
     /// Returns `true` if the phase is a multiple of 1/2.
     pub fn is_clifford(&self) -> bool {
-        self.r.denom().abs() < 2
+        self.r.denom().abs() <= 2
     }
 
     /// Returns `true` if the phase is either -1/2 or 1/2.
@@ -241,3 +241,20 @@ impl DivAssign<i64> for Phase {
         *self = *self / other;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clifford_phases() {
+        assert!(Phase::zero().is_clifford());
+        assert!(Phase::one().is_clifford());
+        assert!(Phase::new(Rational64::new(1, 2)).is_clifford());
+        assert!(Phase::new(Rational64::new(-1, 2)).is_clifford());
+        assert!(Phase::new(Rational64::new(3, 2)).is_clifford());
+        assert!(!Phase::new(Rational64::new(1, 4)).is_clifford());
+        assert!(!Phase::new(Rational64::new(-3, 4)).is_clifford());
+        assert!(!Phase::new(Rational64::new(1, 3)).is_clifford());
+    }
+}
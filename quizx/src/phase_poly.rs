@@ -0,0 +1,308 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Phase polynomials, and their synthesis as CNOT+phase circuits
+//!
+//! A circuit of CNOTs and Z phases on `n` qubits sends a basis state `|x>` to
+//! `e^(i pi f(x)) |A x>`, where `A` is an invertible parity matrix and the phase
+//! polynomial `f` is a sum of phases on parities of the bits of `x`. A [PhasePoly]
+//! stores `f` and `A`.
+//!
+//! [PhasePoly::to_circuit] uses the GraySynth algorithm of Amy, Azimzadeh and
+//! Mosca, "On the CNOT-complexity of CNOT-phase circuits" (2018). The parities are
+//! split up recursively on the bit they most often agree on, and each group is
+//! reached by CNOTs onto a single target, so overlapping parities share their
+//! CNOTs. This usually needs a lot fewer CNOTs than a ladder for each parity, as
+//! for [ParityPhase] gates.
+//!
+//! ```
+//! # use quizx::phase_poly::PhasePoly;
+//! # use quizx::phase::Phase;
+//! let mut p = PhasePoly::new(3);
+//! p.add_term(&[0, 1], Phase::from((1, 4)));
+//! p.add_term(&[0, 1, 2], Phase::from((-1, 4)));
+//! p.add_term(&[1, 2], Phase::from((1, 4)));
+//! let c = p.to_circuit();
+//! assert_eq!(PhasePoly::from_circuit(&c), Some(p));
+//! ```
+//!
+//! [ParityPhase]: crate::gate::GType::ParityPhase
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::linalg::{linear_synth, Mat2, RowOps};
+use crate::optimize_circuit::{rotation, rotation_gate};
+use crate::phase::Phase;
+use num::Zero;
+use std::cmp::max;
+use std::collections::BTreeMap;
+
+/// A phase polynomial and a parity matrix, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhasePoly {
+    /// The phase on each parity of the inputs, given by the bits in the parity
+    pub terms: BTreeMap<Vec<bool>, Phase>,
+    /// The linear part, where output qubit `i` carries the parity in row `i`
+    pub linear: Mat2,
+}
+
+impl PhasePoly {
+    /// The identity on `n` qubits
+    pub fn new(n: usize) -> Self {
+        PhasePoly {
+            terms: BTreeMap::new(),
+            linear: Mat2::id(n),
+        }
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.linear.num_rows()
+    }
+
+    /// Add a phase on the parity of the given input qubits
+    ///
+    /// A phase on the empty parity is a global phase, so it is dropped.
+    pub fn add_term(&mut self, qubits: &[usize], phase: impl Into<Phase>) {
+        let mut parity = vec![false; self.num_qubits()];
+        for &q in qubits {
+            parity[q] ^= true;
+        }
+        self.add_parity(parity, phase.into());
+    }
+
+    fn add_parity(&mut self, parity: Vec<bool>, phase: Phase) {
+        if !parity.contains(&true) {
+            return;
+        }
+        let p = self.terms.entry(parity.clone()).or_insert(Phase::zero());
+        *p += phase;
+        if p.is_zero() {
+            self.terms.remove(&parity);
+        }
+    }
+
+    /// The phase polynomial of a circuit
+    ///
+    /// Returns None unless every gate is a CNOT, SWAP, a phase gate about Z, or a
+    /// [ParityPhase](GType::ParityPhase), without parameters or conditions.
+    /// Barriers are ignored.
+    pub fn from_circuit(c: &Circuit) -> Option<PhasePoly> {
+        let n = c.num_qubits();
+        let mut p = PhasePoly::new(n);
+        // row q is the parity of the inputs on qubit q
+        let mut wires = Mat2::id(n);
+        for g in &c.gates {
+            if g.condition.is_some() {
+                return None;
+            }
+            match (g.t, &g.qs[..]) {
+                (CNOT, &[ctrl, tgt]) => wires.row_add(ctrl, tgt),
+                (SWAP, &[a, b]) => wires.row_swap(a, b),
+                (Barrier, _) => {}
+                (ParityPhase, qs) if g.params.is_zero() => {
+                    let parity = (0..n)
+                        .map(|j| qs.iter().filter(|&&q| wires[(q, j)] == 1).count() % 2 == 1)
+                        .collect();
+                    p.add_parity(parity, g.phase);
+                }
+                (_, &[q]) => match rotation(g) {
+                    Some((false, phase)) => {
+                        p.add_parity((0..n).map(|j| wires[(q, j)] == 1).collect(), phase)
+                    }
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+        p.linear = wires;
+        Some(p)
+    }
+
+    /// Synthesise a circuit of CNOTs and Z phases with GraySynth
+    ///
+    /// Panics if the linear part is not invertible.
+    pub fn to_circuit(&self) -> Circuit {
+        let n = self.num_qubits();
+        let (x, phases): (Vec<Vec<bool>>, Vec<Phase>) = self
+            .terms
+            .iter()
+            .filter(|(_, phase)| !phase.is_zero())
+            .map(|(parity, &phase)| (parity.clone(), phase))
+            .unzip();
+        let mut gs = GraySynth {
+            done: vec![false; x.len()],
+            x,
+            phases,
+            wires: Mat2::id(n),
+            c: Circuit::new(n),
+        };
+        for t in 0..gs.x.len() {
+            gs.place(t);
+        }
+
+        // each entry is a set of terms, the bits they have not been split on yet,
+        // and the wire they are being gathered on, if any
+        let mut stack = vec![(
+            (0..gs.x.len()).collect::<Vec<_>>(),
+            (0..n).collect::<Vec<_>>(),
+            None,
+        )];
+        while let Some((mut s, free, target)) = stack.pop() {
+            s.retain(|&t| !gs.done[t]);
+            if let Some(i) = target {
+                // all the terms include wire i, so add any other wire they share to it
+                while !s.is_empty() {
+                    let shared = (0..n).find(|&j| j != i && s.iter().all(|&t| gs.x[t][j]));
+                    let Some(j) = shared else {
+                        break;
+                    };
+                    gs.cnot(j, i);
+                    s.retain(|&t| !gs.done[t]);
+                }
+            }
+            if s.is_empty() || free.is_empty() {
+                continue;
+            }
+
+            // split on the bit with the most terms agreeing on it
+            let j = *free
+                .iter()
+                .max_by_key(|&&j| {
+                    let ones = s.iter().filter(|&&t| gs.x[t][j]).count();
+                    max(ones, s.len() - ones)
+                })
+                .unwrap();
+            let free: Vec<usize> = free.into_iter().filter(|&k| k != j).collect();
+            let (s1, s0): (Vec<usize>, Vec<usize>) = s.into_iter().partition(|&t| gs.x[t][j]);
+            stack.push((s0, free.clone(), target));
+            stack.push((s1, free, target.or(Some(j))));
+        }
+
+        // the wires now carry the parities in gs.wires, so finish with the linear map
+        // taking these to the linear part
+        let rest = &self.linear * &gs.wires.inverse().unwrap();
+        let mut c = gs.c;
+        c += &linear_synth(&rest).expect("Linear part should be invertible");
+        c
+    }
+}
+
+/// The state of [PhasePoly::to_circuit]
+struct GraySynth {
+    /// Each term as a parity of the current values of the wires
+    x: Vec<Vec<bool>>,
+    phases: Vec<Phase>,
+    /// Whether the phase of each term has been added to the circuit
+    done: Vec<bool>,
+    /// The parity of the inputs on each wire
+    wires: Mat2,
+    c: Circuit,
+}
+
+impl GraySynth {
+    /// Add the phase of term `t` if its parity is on a single wire
+    fn place(&mut self, t: usize) {
+        if self.done[t] {
+            return;
+        }
+        let mut ones = (0..self.x[t].len()).filter(|&q| self.x[t][q]);
+        if let (Some(q), None) = (ones.next(), ones.next()) {
+            if let Some(g) = rotation_gate(false, self.phases[t], q) {
+                self.c.push(g);
+            }
+            self.done[t] = true;
+        }
+    }
+
+    /// Add a CNOT, and the phase of any term this puts on the target wire
+    fn cnot(&mut self, ctrl: usize, tgt: usize) {
+        self.c.push(Gate::new(CNOT, vec![ctrl, tgt]));
+        self.wires.row_add(ctrl, tgt);
+        // the new value of tgt is the sum of the old values, so a term which
+        // included the old value of tgt now includes ctrl iff it didn't before
+        for t in 0..self.x.len() {
+            if !self.done[t] && self.x[t][tgt] {
+                self.x[t][ctrl] ^= true;
+                self.place(t);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn graysynth() {
+        let mut rng = StdRng::seed_from_u64(1337);
+        let n = 5;
+        let mut p = PhasePoly::new(n);
+        for _ in 0..20 {
+            let qs: Vec<usize> = (0..n).filter(|_| rng.gen_bool(0.6)).collect();
+            p.add_term(&qs, Phase::from((rng.gen_range(1..8), 4)));
+        }
+        for _ in 0..10 {
+            let (a, b) = (rng.gen_range(0..n), rng.gen_range(0..n));
+            if a != b {
+                p.linear.row_add(a, b);
+            }
+        }
+
+        // the same map with a CNOT ladder for each term
+        let mut naive = Circuit::new(n);
+        for (parity, &phase) in &p.terms {
+            let qs = (0..n).filter(|&q| parity[q]).collect();
+            Gate::new_with_phase(ParityPhase, qs, phase).push_basic_gates(&mut naive);
+        }
+        naive += &linear_synth(&p.linear).unwrap();
+        assert_eq!(PhasePoly::from_circuit(&naive).as_ref(), Some(&p));
+
+        let c = p.to_circuit();
+        assert_eq!(PhasePoly::from_circuit(&c).as_ref(), Some(&p));
+        assert!(Tensor4::compare(&c, &naive));
+        assert!(c.num_gates_of_type(CNOT) < naive.num_gates_of_type(CNOT));
+    }
+
+    #[test]
+    fn from_circuit() {
+        let c = Circuit::from_qasm(
+            "qreg q[3];
+            cx q[0], q[1];
+            t q[1];
+            swap q[1], q[2];
+            s q[2];
+            rz(pi/4) q[0];
+            cx q[2], q[0];
+            tdg q[0];",
+        )
+        .unwrap();
+        let p = PhasePoly::from_circuit(&c).unwrap();
+        let mut p1 = PhasePoly::new(3);
+        p1.add_term(&[0, 1], Phase::from((3, 4)));
+        p1.add_term(&[0], Phase::from((1, 4)));
+        p1.add_term(&[1], Phase::from((-1, 4)));
+        p1.linear = Mat2::new(vec![vec![0, 1, 0], vec![0, 0, 1], vec![1, 1, 0]]);
+        assert_eq!(p, p1);
+        assert!(Tensor4::compare(&c, &p.to_circuit()));
+
+        let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; h q[1];").unwrap();
+        assert_eq!(PhasePoly::from_circuit(&c), None);
+    }
+}
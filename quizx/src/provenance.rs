@@ -0,0 +1,240 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Which gates the vertices of a graph came from
+//!
+//! [Circuit::to_graph_with_provenance] tags each spider of the graph of a circuit
+//! with the index of the gate it was made for. The result is a [Provenance]
+//! wrapper, which keeps the tags up to date, as well as it can, while the graph is
+//! simplified:
+//!
+//! - when a rule moves the phase of one spider onto another, reported with
+//!   [GraphLike::fuse_phases], the spider receiving the phase also gets the gates
+//!   of the other,
+//! - a spider added by a rule gets the gates of the vertices the rule was applied
+//!   to, as reported with [GraphLike::begin_rewrite], and
+//! - the tags of removed vertices are dropped.
+//!
+//! This answers questions like "which gates does this T spider come from?". Only
+//! vertices are tagged, since almost every rule rewires the edges; the gates of an
+//! edge can be found from its endpoints. The boundaries have no gates.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::graph::*;
+//! # use quizx::simplify::spider_simp;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[2]; t q[0]; cx q[0], q[1]; t q[0];").unwrap();
+//! let mut g = c.to_graph_with_provenance::<Graph>();
+//! spider_simp(&mut g);
+//! let v = g.vertices().find(|&v| g.phase(v) == (1, 2).into()).unwrap();
+//! assert_eq!(g.gates(v), &[0, 1, 2]);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use rustc_hash::FxHashMap;
+
+/// A graph which records which gates each vertex came from, see the
+/// [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Provenance<G: GraphLike> {
+    g: G,
+    origins: FxHashMap<V, Vec<usize>>,
+    depth: usize,
+    /// During a rewrite, the gates of the vertices it was applied to, and the
+    /// vertices it added
+    pending: Option<(Vec<usize>, Vec<V>)>,
+}
+
+/// Adds the gates `gs` to the sorted list `to`
+fn merge(to: &mut Vec<usize>, gs: &[usize]) {
+    to.extend_from_slice(gs);
+    to.sort_unstable();
+    to.dedup();
+}
+
+impl<G: GraphLike> Provenance<G> {
+    /// Start following the gates of `g`, where no vertex has any gates yet
+    pub fn new(g: G) -> Self {
+        Provenance {
+            g,
+            origins: FxHashMap::default(),
+            depth: 0,
+            pending: None,
+        }
+    }
+
+    /// The underlying graph
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    pub fn into_graph(self) -> G {
+        self.g
+    }
+
+    /// The indices of the gates `v` came from, in increasing order
+    pub fn gates(&self, v: V) -> &[usize] {
+        self.origins.get(&v).map_or(&[], |gs| gs.as_slice())
+    }
+
+    /// Adds the gate with index `gate` to the gates of `v`
+    pub fn add_gate(&mut self, v: V, gate: usize) {
+        merge(self.origins.entry(v).or_default(), &[gate]);
+    }
+}
+
+impl<G: GraphLike> GraphLike for Provenance<G> {
+    fn new() -> Self {
+        Provenance::new(G::new())
+    }
+
+    delegate_graph_like!(g);
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        let v = self.g.add_vertex(ty);
+        if let Some((_, added)) = &mut self.pending {
+            added.push(v);
+        }
+        v
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        let v = self.g.add_vertex_with_data(d);
+        if let Some((_, added)) = &mut self.pending {
+            added.push(v);
+        }
+        v
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        self.origins.remove(&v);
+        self.g.remove_vertex(v)
+    }
+
+    fn fuse_phases(&mut self, v0: V, v1: V) {
+        if let Some(gs) = self.origins.get(&v1).cloned() {
+            merge(self.origins.entry(v0).or_default(), &gs);
+        }
+        self.g.fuse_phases(v0, v1)
+    }
+
+    fn phase_negate(&mut self, v: V) {
+        self.g.phase_negate(v)
+    }
+
+    fn begin_rewrite(&mut self, rule: &'static str, vertices: &[V]) {
+        self.depth += 1;
+        if self.depth == 1 {
+            let mut gs = vec![];
+            for v in vertices {
+                merge(&mut gs, self.gates(*v));
+            }
+            self.pending = Some((gs, vec![]));
+        }
+        self.g.begin_rewrite(rule, vertices)
+    }
+
+    fn end_rewrite(&mut self) {
+        self.depth -= 1;
+        if self.depth == 0 {
+            let (gs, added) = self.pending.take().expect("end_rewrite without begin");
+            for v in added {
+                if self.g.contains_vertex(v) && !gs.is_empty() {
+                    merge(self.origins.entry(v).or_default(), &gs);
+                }
+            }
+        }
+        self.g.end_rewrite()
+    }
+}
+
+impl Circuit {
+    /// The graph of the circuit, where each spider is tagged with the gate it came
+    /// from
+    ///
+    /// See the [module docs](crate::provenance).
+    pub fn to_graph_with_provenance<G: GraphLike>(&self) -> Provenance<G> {
+        // the boundaries of a fresh graph are added first, as vertices 0..n
+        let mut next = self.num_qubits();
+        let mut tags = vec![];
+        let g: G = self.to_graph_with_callback(false, |i, g: &G, _| {
+            for v in next..g.vindex() {
+                if g.contains_vertex(v) {
+                    tags.push((v, i));
+                }
+            }
+            next = g.vindex();
+        });
+        let mut p = Provenance::new(g);
+        for (v, i) in tags {
+            p.add_gate(v, i);
+        }
+        p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::*;
+    use crate::simplify::*;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn gates_of_spiders() {
+        let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; t q[1];").unwrap();
+        let g = c.to_graph_with_provenance::<Graph>();
+        let tagged: Vec<(V, &[usize])> = g
+            .vertices()
+            .filter(|&v| g.vertex_type(v) != VType::B)
+            .map(|v| (v, g.gates(v)))
+            .collect();
+        // one spider for the Hadamard, two for the CNOT and one for the T gate
+        assert_eq!(tagged.len(), 4);
+        assert!(tagged.iter().all(|(_, gs)| gs.len() == 1));
+        let t = tagged.iter().find(|(v, _)| g.phase(*v).is_t()).unwrap();
+        assert_eq!(t.1, &[2]);
+        for &b in g.inputs().iter().chain(g.outputs()) {
+            assert!(g.gates(b).is_empty());
+        }
+    }
+
+    #[test]
+    fn provenance_through_simp() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(30)
+                .clifford_t(0.3)
+                .build();
+            let mut g = c.to_graph_with_provenance::<Graph>();
+            full_simp(&mut g);
+
+            // every non-Clifford phase comes from some T gate
+            for v in g.vertices() {
+                if !g.phase(v).is_clifford() {
+                    let gs = g.gates(v);
+                    assert!(gs.iter().any(|&i| matches!(c.gates[i].t, T | Tdg)));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1146 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OpenQASM 3 import and export
+//!
+//! [decode_circuit] reads the part of OpenQASM 3 which describes a unitary circuit:
+//!
+//! - qubit declarations, `qubit[n] q;`, as well as the older `qreg q[n];`, or
+//!   physical qubits `$0`, `$1`, ...
+//! - aliases, `let a = q[1:3] ++ r[0];`, and operands given by an index, a range or
+//!   a set of indices
+//! - the gates of "stdgates.inc" and "qelib1.inc", and gates defined with `gate`
+//! - the modifiers `inv @` and `pow(k) @` for integer `k`, and `ctrl @` where the
+//!   controlled gate is a CNOT, CZ, Toffoli or CCZ
+//! - barriers, which become a single [Barrier] on all their qubits
+//!
+//! Registers given whole as operands are broadcast, so `h q;` applies `h` to every
+//! qubit of `q`. Bit declarations are skipped, and `gphase` is dropped, as circuits
+//! are only considered up to global phase. Other classical statements, like
+//! measurements and control flow, give [Qasm3Error::Unsupported]. Angles may be any
+//! arithmetic expression, and are rounded to a multiple of `π` with a denominator of
+//! at most 256.
+//!
+//! [encode_circuit] writes a circuit on a single register `q`, using the gates of
+//! "stdgates.inc".
+//!
+//! ```
+//! # use quizx::qasm3::{decode_circuit, encode_circuit};
+//! let c = decode_circuit(
+//!     r#"
+//!     OPENQASM 3.0;
+//!     include "stdgates.inc";
+//!     qubit[3] q;
+//!     let pair = q[0:1];
+//!     h pair;
+//!     ctrl @ x pair[0], q[2];
+//!     rz(3*pi/4) q[2];
+//! "#,
+//! )
+//! .unwrap();
+//! assert_eq!(c.num_gates(), 4);
+//! assert_eq!(decode_circuit(&encode_circuit(&c)).unwrap(), c);
+//! ```
+
+use crate::circuit::{Circuit, QASM_PRELUDE};
+use crate::gate::*;
+use crate::phase::Phase;
+use num::{Rational64, Zero};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::f64::consts::{E, PI, TAU};
+use std::fmt::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+/// An error that can occur when reading OpenQASM 3.
+#[derive(Debug, thiserror::Error)]
+pub enum Qasm3Error {
+    /// The source is not valid OpenQASM 3.
+    #[error("line {0}: {1}")]
+    SyntaxError(usize, String),
+    /// A gate, register or parameter is used without being declared.
+    #[error("line {0}: undeclared identifier {1}")]
+    Undeclared(usize, String),
+    /// The operands or parameters of a gate do not fit it.
+    #[error("line {0}: {1}")]
+    InvalidOperands(usize, String),
+    /// A feature of OpenQASM 3 with no counterpart in a [Circuit].
+    #[error("line {0}: {1} is not supported")]
+    Unsupported(usize, String),
+    /// Error reading or writing a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// The largest denominator of an angle, as a multiple of `π`
+const MAX_DENOM: i64 = 256;
+
+/// How deeply gate definitions may refer to each other
+const MAX_DEPTH: usize = 256;
+
+/// Gates of "stdgates.inc" which are not in "qelib1.inc"
+const STDGATES: &[&str] = &[
+    "gate phase(lambda) q { rz(lambda) q; }",
+    "gate cphase(lambda) a, b { cp(lambda) a, b; }",
+    "gate crx(theta) a, b { h b; crz(theta) a, b; h b; }",
+    "gate cry(theta) a, b { sdg b; crx(theta) a, b; s b; }",
+    "gate ch a, b { h b; sdg b; cx a, b; h b; t b; cx a, b; t b; h b; s b; x b; s a; }",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Ident(String),
+    Num(f64),
+    Str(String),
+    Sym(&'static str),
+}
+
+/// Symbols, with the longer ones first
+const SYMBOLS: &[&str] = &[
+    "++", "**", ";", ",", "(", ")", "[", "]", "{", "}", "=", ":", "@", "+", "-", "*", "/",
+];
+
+/// The length of the number at the start of `s`
+fn number_len(s: &str) -> usize {
+    let b = s.as_bytes();
+    let mut i = 0;
+    while i < b.len() && (b[i].is_ascii_digit() || b[i] == b'.') {
+        i += 1;
+    }
+    if i < b.len() && (b[i] == b'e' || b[i] == b'E') {
+        let mut j = i + 1;
+        if j < b.len() && (b[j] == b'+' || b[j] == b'-') {
+            j += 1;
+        }
+        if j < b.len() && b[j].is_ascii_digit() {
+            i = j;
+            while i < b.len() && b[i].is_ascii_digit() {
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+/// Splits a source into tokens, each with its line number
+fn lex(source: &str) -> Result<Vec<(Tok, usize)>, Qasm3Error> {
+    let mut toks = vec![];
+    let mut line = 1;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' {
+            line += 1;
+            rest = &rest[1..];
+        } else if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("//") {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+        } else if rest.starts_with("/*") {
+            let end = rest
+                .find("*/")
+                .ok_or_else(|| Qasm3Error::SyntaxError(line, "unterminated comment".into()))?;
+            line += rest[..end].matches('\n').count();
+            rest = &rest[end + 2..];
+        } else if c == '"' {
+            let end = rest[1..]
+                .find('"')
+                .ok_or_else(|| Qasm3Error::SyntaxError(line, "unterminated string".into()))?;
+            toks.push((Tok::Str(rest[1..end + 1].to_string()), line));
+            rest = &rest[end + 2..];
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|d: char| d.is_ascii_digit()))
+        {
+            let len = number_len(rest);
+            let n = rest[..len].parse().map_err(|_| {
+                Qasm3Error::SyntaxError(line, format!("invalid number {}", &rest[..len]))
+            })?;
+            toks.push((Tok::Num(n), line));
+            rest = &rest[len..];
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let len = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(_, d)| !(d.is_alphanumeric() || d == '_'))
+                .map_or(rest.len(), |(i, _)| i);
+            toks.push((Tok::Ident(rest[..len].to_string()), line));
+            rest = &rest[len..];
+        } else if let Some(&sym) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+            toks.push((Tok::Sym(sym), line));
+            rest = &rest[sym.len()..];
+        } else {
+            return Err(Qasm3Error::SyntaxError(
+                line,
+                format!("unexpected character {}", c),
+            ));
+        }
+    }
+    Ok(toks)
+}
+
+/// A position in a list of tokens
+struct Cursor<'a> {
+    toks: &'a [(Tok, usize)],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(toks: &'a [(Tok, usize)]) -> Self {
+        Cursor { toks, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.toks.len()
+    }
+
+    fn line(&self) -> usize {
+        self.toks
+            .get(self.pos)
+            .or(self.toks.last())
+            .map_or(0, |t| t.1)
+    }
+
+    fn peek(&self) -> Option<&'a Tok> {
+        self.toks.get(self.pos).map(|t| &t.0)
+    }
+
+    fn bump(&mut self) -> Option<&'a Tok> {
+        let t = self.peek();
+        self.pos += 1;
+        t
+    }
+
+    fn is(&self, sym: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Sym(s)) if *s == sym)
+    }
+
+    fn eat(&mut self, sym: &str) -> bool {
+        let found = self.is(sym);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn error(&self, msg: impl Into<String>) -> Qasm3Error {
+        Qasm3Error::SyntaxError(self.line(), msg.into())
+    }
+
+    fn expect(&mut self, sym: &str) -> Result<(), Qasm3Error> {
+        if self.eat(sym) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {}", sym)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str, Qasm3Error> {
+        match self.peek() {
+            Some(Tok::Ident(s)) => {
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(self.error("expected an identifier")),
+        }
+    }
+
+    /// Identifiers separated by commas, up to and including `end`
+    fn idents(&mut self, end: &str) -> Result<Vec<String>, Qasm3Error> {
+        let mut names = vec![];
+        while !self.eat(end) {
+            names.push(self.ident()?.to_string());
+            if !self.eat(",") {
+                self.expect(end)?;
+                break;
+            }
+        }
+        Ok(names)
+    }
+
+    /// Skips to just after the next `;`
+    fn skip_statement(&mut self) -> Result<(), Qasm3Error> {
+        while !self.eat(";") {
+            if self.bump().is_none() {
+                return Err(self.error("expected ;"));
+            }
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, scope: &Scope) -> Result<f64, Qasm3Error> {
+        let mut x = self.term(scope)?;
+        loop {
+            if self.eat("+") {
+                x += self.term(scope)?;
+            } else if self.eat("-") {
+                x -= self.term(scope)?;
+            } else {
+                return Ok(x);
+            }
+        }
+    }
+
+    fn term(&mut self, scope: &Scope) -> Result<f64, Qasm3Error> {
+        let mut x = self.unary(scope)?;
+        loop {
+            if self.eat("*") {
+                x *= self.unary(scope)?;
+            } else if self.eat("/") {
+                x /= self.unary(scope)?;
+            } else {
+                return Ok(x);
+            }
+        }
+    }
+
+    fn unary(&mut self, scope: &Scope) -> Result<f64, Qasm3Error> {
+        if self.eat("-") {
+            Ok(-self.unary(scope)?)
+        } else if self.eat("+") {
+            self.unary(scope)
+        } else {
+            let x = self.atom(scope)?;
+            if self.eat("**") {
+                Ok(x.powf(self.unary(scope)?))
+            } else {
+                Ok(x)
+            }
+        }
+    }
+
+    fn atom(&mut self, scope: &Scope) -> Result<f64, Qasm3Error> {
+        let line = self.line();
+        match self.bump() {
+            Some(Tok::Num(n)) => Ok(*n),
+            Some(Tok::Sym("(")) => {
+                let x = self.expr(scope)?;
+                self.expect(")")?;
+                Ok(x)
+            }
+            Some(Tok::Ident(s)) => {
+                let f: fn(f64) -> f64 = match s.as_str() {
+                    "pi" | "π" => return Ok(PI),
+                    "tau" | "τ" => return Ok(TAU),
+                    "euler" | "ℇ" => return Ok(E),
+                    "sin" => f64::sin,
+                    "cos" => f64::cos,
+                    "tan" => f64::tan,
+                    "arcsin" => f64::asin,
+                    "arccos" => f64::acos,
+                    "arctan" => f64::atan,
+                    "exp" => f64::exp,
+                    "ln" => f64::ln,
+                    "sqrt" => f64::sqrt,
+                    _ => {
+                        return scope
+                            .params
+                            .get(s)
+                            .copied()
+                            .ok_or_else(|| Qasm3Error::Undeclared(line, s.clone()))
+                    }
+                };
+                self.expect("(")?;
+                let x = self.expr(scope)?;
+                self.expect(")")?;
+                Ok(f(x))
+            }
+            _ => Err(Qasm3Error::SyntaxError(
+                line,
+                "expected an expression".into(),
+            )),
+        }
+    }
+
+    fn integer(&mut self, scope: &Scope) -> Result<i64, Qasm3Error> {
+        let x = self.expr(scope)?;
+        if x.fract() == 0.0 {
+            Ok(x as i64)
+        } else {
+            Err(self.error("expected an integer"))
+        }
+    }
+}
+
+/// The angle `x`, in radians, as a phase
+pub(crate) fn angle_to_phase(x: f64) -> Phase {
+    Rational64::approximate_float(x / PI).map_or(Phase::zero(), |r| {
+        Phase::new(r).limit_denominator(MAX_DENOM)
+    })
+}
+
+/// The names bound in the body of a gate definition
+#[derive(Default)]
+struct Scope {
+    params: FxHashMap<String, f64>,
+    qubits: FxHashMap<String, usize>,
+}
+
+struct GateDef {
+    params: Vec<String>,
+    qubits: Vec<String>,
+    body: Vec<(Tok, usize)>,
+}
+
+enum Modifier {
+    Inv,
+    Pow(i64),
+    Ctrl(usize),
+}
+
+/// The gate which is `name` with `n` more controls, if it is one of ours
+fn controlled(name: &str, n: usize) -> Option<GType> {
+    match (name, n) {
+        ("x", 1) => Some(CNOT),
+        ("x", 2) | ("cx", 1) | ("CX", 1) => Some(TOFF),
+        ("z", 1) => Some(CZ),
+        ("z", 2) | ("cz", 1) => Some(CCZ),
+        _ => None,
+    }
+}
+
+struct Reader {
+    registers: FxHashMap<String, Vec<usize>>,
+    /// registers which are single qubits, declared without a size
+    scalars: FxHashSet<String>,
+    bits: FxHashSet<String>,
+    num_qubits: usize,
+    physical: bool,
+    defs: FxHashMap<String, Rc<GateDef>>,
+    gates: Vec<Gate>,
+}
+
+impl Reader {
+    fn new() -> Self {
+        let mut reader = Reader {
+            registers: FxHashMap::default(),
+            scalars: FxHashSet::default(),
+            bits: FxHashSet::default(),
+            num_qubits: 0,
+            physical: false,
+            defs: FxHashMap::default(),
+            gates: vec![],
+        };
+        let decls = QASM_PRELUDE
+            .iter()
+            .map(|&(_, decl)| decl)
+            .filter(|decl| decl.starts_with("gate"))
+            .chain(STDGATES.iter().copied());
+        for decl in decls {
+            let toks = lex(decl).expect("Invalid standard gate");
+            reader
+                .statement(&mut Cursor::new(&toks))
+                .expect("Invalid standard gate");
+        }
+        reader
+    }
+
+    fn declare(&mut self, name: &str, size: Option<i64>, line: usize) -> Result<(), Qasm3Error> {
+        if self.physical {
+            return Err(Qasm3Error::Unsupported(
+                line,
+                "mixing physical and virtual qubits".into(),
+            ));
+        }
+        if size.is_some_and(|n| n < 0) || self.registers.contains_key(name) {
+            return Err(Qasm3Error::SyntaxError(
+                line,
+                format!("invalid declaration of {}", name),
+            ));
+        }
+        let n = match size {
+            Some(n) => n as usize,
+            None => {
+                self.scalars.insert(name.to_string());
+                1
+            }
+        };
+        self.registers.insert(
+            name.to_string(),
+            (self.num_qubits..self.num_qubits + n).collect(),
+        );
+        self.num_qubits += n;
+        Ok(())
+    }
+
+    fn statement(&mut self, cur: &mut Cursor) -> Result<(), Qasm3Error> {
+        let line = cur.line();
+        let kw = match cur.peek() {
+            Some(Tok::Ident(kw)) => kw.as_str(),
+            _ => return Err(cur.error("expected a statement")),
+        };
+        match kw {
+            "OPENQASM" => cur.skip_statement(),
+            "include" => {
+                cur.pos += 1;
+                match cur.bump() {
+                    Some(Tok::Str(f)) if f == "stdgates.inc" || f == "qelib1.inc" => {
+                        cur.expect(";")
+                    }
+                    Some(Tok::Str(f)) => {
+                        Err(Qasm3Error::Unsupported(line, format!("including {}", f)))
+                    }
+                    _ => Err(cur.error("expected a file name")),
+                }
+            }
+            "qubit" | "bit" => {
+                cur.pos += 1;
+                let size = if cur.eat("[") {
+                    let n = cur.integer(&Scope::default())?;
+                    cur.expect("]")?;
+                    Some(n)
+                } else {
+                    None
+                };
+                let name = cur.ident()?;
+                if cur.eat("=") {
+                    return Err(Qasm3Error::Unsupported(line, "initialised bits".into()));
+                }
+                cur.expect(";")?;
+                if kw == "qubit" {
+                    self.declare(name, size, line)
+                } else {
+                    self.bits.insert(name.to_string());
+                    Ok(())
+                }
+            }
+            "qreg" | "creg" => {
+                cur.pos += 1;
+                let name = cur.ident()?;
+                let size = if cur.eat("[") {
+                    let n = cur.integer(&Scope::default())?;
+                    cur.expect("]")?;
+                    Some(n)
+                } else {
+                    None
+                };
+                cur.expect(";")?;
+                if kw == "qreg" {
+                    self.declare(name, size, line)
+                } else {
+                    self.bits.insert(name.to_string());
+                    Ok(())
+                }
+            }
+            "let" => {
+                cur.pos += 1;
+                let name = cur.ident()?;
+                cur.expect("=")?;
+                let mut qs = vec![];
+                loop {
+                    qs.extend(self.operand(cur, &Scope::default())?.0);
+                    if !cur.eat("++") {
+                        break;
+                    }
+                }
+                cur.expect(";")?;
+                self.registers.insert(name.to_string(), qs);
+                Ok(())
+            }
+            "gate" => {
+                cur.pos += 1;
+                let name = cur.ident()?.to_string();
+                let params = if cur.eat("(") {
+                    cur.idents(")")?
+                } else {
+                    vec![]
+                };
+                let qubits = cur.idents("{")?;
+                let start = cur.pos;
+                let mut depth = 1;
+                while depth > 0 {
+                    match cur.bump() {
+                        Some(Tok::Sym("{")) => depth += 1,
+                        Some(Tok::Sym("}")) => depth -= 1,
+                        Some(_) => {}
+                        None => return Err(cur.error("unterminated gate definition")),
+                    }
+                }
+                let body = cur.toks[start..cur.pos - 1].to_vec();
+                self.defs.insert(
+                    name,
+                    Rc::new(GateDef {
+                        params,
+                        qubits,
+                        body,
+                    }),
+                );
+                Ok(())
+            }
+            _ if self.bits.contains(kw) => {
+                Err(Qasm3Error::Unsupported(line, "assignment to bits".into()))
+            }
+            "barrier" => {
+                cur.pos += 1;
+                let mut qs = vec![];
+                while !cur.eat(";") {
+                    for q in self.operand(cur, &Scope::default())?.0 {
+                        if !qs.contains(&q) {
+                            qs.push(q);
+                        }
+                    }
+                    if !cur.eat(",") {
+                        cur.expect(";")?;
+                        break;
+                    }
+                }
+                // a barrier without operands is on every qubit
+                if qs.is_empty() {
+                    qs = (0..self.num_qubits).collect();
+                }
+                self.gates.push(Gate::new_barrier(qs));
+                Ok(())
+            }
+            "measure" | "reset" | "if" | "for" | "while" | "def" | "defcal" | "cal"
+            | "defcalgrammar" | "box" | "delay" | "input" | "output" | "const" | "int" | "uint"
+            | "float" | "angle" | "bool" | "complex" | "duration" | "stretch" | "array"
+            | "extern" | "opaque" | "return" | "break" | "continue" => {
+                Err(Qasm3Error::Unsupported(line, format!("'{}'", kw)))
+            }
+            _ => {
+                let gates = self.call(cur, &Scope::default(), 0)?;
+                self.gates.extend(gates);
+                Ok(())
+            }
+        }
+    }
+
+    /// An operand of a gate, and whether it is a single qubit rather than a
+    /// register
+    fn operand(
+        &mut self,
+        cur: &mut Cursor,
+        scope: &Scope,
+    ) -> Result<(Vec<usize>, bool), Qasm3Error> {
+        let line = cur.line();
+        let name = cur.ident()?;
+        // the qubits of a gate body are its arguments, and nothing else
+        if !scope.qubits.is_empty() {
+            return match scope.qubits.get(name) {
+                Some(&q) => Ok((vec![q], true)),
+                None => Err(Qasm3Error::Undeclared(line, name.to_string())),
+            };
+        }
+        if let Some(q) = name.strip_prefix('$').and_then(|n| n.parse::<usize>().ok()) {
+            if !self.registers.is_empty() {
+                return Err(Qasm3Error::Unsupported(
+                    line,
+                    "mixing physical and virtual qubits".into(),
+                ));
+            }
+            self.physical = true;
+            self.num_qubits = self.num_qubits.max(q + 1);
+            return Ok((vec![q], true));
+        }
+
+        let reg = self
+            .registers
+            .get(name)
+            .ok_or_else(|| Qasm3Error::Undeclared(line, name.to_string()))?;
+        if !cur.eat("[") {
+            return Ok((reg.clone(), self.scalars.contains(name)));
+        }
+        let lookup = |i: i64| {
+            let j = if i < 0 { i + reg.len() as i64 } else { i };
+            if 0 <= j && (j as usize) < reg.len() {
+                Ok(reg[j as usize])
+            } else {
+                Err(Qasm3Error::InvalidOperands(
+                    line,
+                    format!("index {} out of range for {}", i, name),
+                ))
+            }
+        };
+
+        let operand = if cur.eat("{") {
+            let mut qs = vec![];
+            while !cur.eat("}") {
+                qs.push(lookup(cur.integer(scope)?)?);
+                if !cur.eat(",") {
+                    cur.expect("}")?;
+                    break;
+                }
+            }
+            (qs, false)
+        } else {
+            let start = if cur.is(":") { 0 } else { cur.integer(scope)? };
+            if cur.eat(":") {
+                let last = reg.len() as i64 - 1;
+                let mut step = 1;
+                let mut end = if cur.is("]") || cur.is(":") {
+                    last
+                } else {
+                    cur.integer(scope)?
+                };
+                if cur.eat(":") {
+                    step = end;
+                    end = if cur.is("]") {
+                        last
+                    } else {
+                        cur.integer(scope)?
+                    };
+                }
+                if step == 0 {
+                    return Err(Qasm3Error::InvalidOperands(
+                        line,
+                        "range with step 0".into(),
+                    ));
+                }
+                let mut qs = vec![];
+                let mut i = start;
+                while (step > 0 && i <= end) || (step < 0 && i >= end) {
+                    qs.push(lookup(i)?);
+                    i += step;
+                }
+                (qs, false)
+            } else {
+                (vec![lookup(start)?], true)
+            }
+        };
+        cur.expect("]")?;
+        Ok(operand)
+    }
+
+    /// Reads a gate call, and returns the gates it stands for
+    fn call(
+        &mut self,
+        cur: &mut Cursor,
+        scope: &Scope,
+        depth: usize,
+    ) -> Result<Vec<Gate>, Qasm3Error> {
+        let line = cur.line();
+        let mut mods = vec![];
+        loop {
+            match cur.peek() {
+                Some(Tok::Ident(m)) if m == "inv" => {
+                    cur.pos += 1;
+                    mods.push(Modifier::Inv);
+                }
+                Some(Tok::Ident(m)) if m == "pow" => {
+                    cur.pos += 1;
+                    cur.expect("(")?;
+                    let k = cur.expr(scope)?;
+                    cur.expect(")")?;
+                    if k.fract() != 0.0 {
+                        return Err(Qasm3Error::Unsupported(line, "non-integer powers".into()));
+                    }
+                    mods.push(Modifier::Pow(k as i64));
+                }
+                Some(Tok::Ident(m)) if m == "ctrl" => {
+                    cur.pos += 1;
+                    let n = if cur.eat("(") {
+                        let n = cur.integer(scope)?;
+                        cur.expect(")")?;
+                        n.max(0) as usize
+                    } else {
+                        1
+                    };
+                    mods.push(Modifier::Ctrl(n));
+                }
+                Some(Tok::Ident(m)) if m == "negctrl" => {
+                    return Err(Qasm3Error::Unsupported(line, "negctrl".into()));
+                }
+                _ => break,
+            }
+            cur.expect("@")?;
+        }
+
+        let name = cur.ident()?;
+        let mut params = vec![];
+        if cur.eat("(") {
+            while !cur.eat(")") {
+                params.push(cur.expr(scope)?);
+                if !cur.eat(",") {
+                    cur.expect(")")?;
+                    break;
+                }
+            }
+        }
+        let mut operands = vec![];
+        while !cur.eat(";") {
+            operands.push(self.operand(cur, scope)?);
+            if !cur.eat(",") {
+                cur.expect(";")?;
+                break;
+            }
+        }
+        if name == "gphase" {
+            return if mods.iter().any(|m| matches!(m, Modifier::Ctrl(_))) {
+                Err(Qasm3Error::Unsupported(line, "controlled gphase".into()))
+            } else {
+                Ok(vec![])
+            };
+        }
+
+        // registers are broadcast, and single qubits are repeated
+        let mut size = None;
+        for (qs, single) in &operands {
+            if !single {
+                if size.is_some_and(|n| n != qs.len()) {
+                    return Err(Qasm3Error::InvalidOperands(
+                        line,
+                        "registers of different sizes".into(),
+                    ));
+                }
+                size = Some(qs.len());
+            }
+        }
+        let mut gates = vec![];
+        for i in 0..size.unwrap_or(1) {
+            let qs: Vec<usize> = operands
+                .iter()
+                .map(|(qs, single)| if *single { qs[0] } else { qs[i] })
+                .collect();
+            if (1..qs.len()).any(|j| qs[..j].contains(&qs[j])) {
+                return Err(Qasm3Error::InvalidOperands(
+                    line,
+                    format!("repeated qubit in {}", name),
+                ));
+            }
+            gates.extend(self.apply(name, &params, &qs, &mods, line, depth)?);
+        }
+        Ok(gates)
+    }
+
+    /// The gates for a single application of a gate, with modifiers
+    fn apply(
+        &mut self,
+        name: &str,
+        params: &[f64],
+        qs: &[usize],
+        mods: &[Modifier],
+        line: usize,
+        depth: usize,
+    ) -> Result<Vec<Gate>, Qasm3Error> {
+        let ctrls: usize = mods
+            .iter()
+            .map(|m| if let Modifier::Ctrl(n) = m { *n } else { 0 })
+            .sum();
+        let mut gates = if ctrls > 0 {
+            let t = controlled(name, ctrls)
+                .filter(|_| params.is_empty() && !self.defs.contains_key(name))
+                .ok_or_else(|| Qasm3Error::Unsupported(line, format!("controlled {}", name)))?;
+            if t.num_qubits() != Some(qs.len()) {
+                return Err(Qasm3Error::InvalidOperands(
+                    line,
+                    format!("wrong number of qubits for controlled {}", name),
+                ));
+            }
+            vec![Gate::new(t, qs.to_vec())]
+        } else {
+            self.expand(name, params, qs, line, depth)?
+        };
+
+        // the modifier closest to the gate applies first
+        for m in mods.iter().rev() {
+            let k = match *m {
+                Modifier::Inv => -1,
+                Modifier::Pow(k) => k,
+                Modifier::Ctrl(_) => continue,
+            };
+            if k < 0 {
+                gates.reverse();
+                for g in &mut gates {
+                    g.adjoint();
+                }
+            }
+            let n = gates.len() * k.unsigned_abs() as usize;
+            gates = gates.iter().cycle().take(n).cloned().collect();
+        }
+        Ok(gates)
+    }
+
+    /// The gates for a single application of a gate, expanding definitions
+    fn expand(
+        &mut self,
+        name: &str,
+        params: &[f64],
+        qs: &[usize],
+        line: usize,
+        depth: usize,
+    ) -> Result<Vec<Gate>, Qasm3Error> {
+        if depth > MAX_DEPTH {
+            return Err(Qasm3Error::SyntaxError(
+                line,
+                format!("gate definitions nested too deeply in {}", name),
+            ));
+        }
+        let wrong_arity = || {
+            Qasm3Error::InvalidOperands(
+                line,
+                format!("wrong number of parameters or qubits for {}", name),
+            )
+        };
+
+        if let Some(def) = self.defs.get(name).cloned() {
+            if params.len() != def.params.len() || qs.len() != def.qubits.len() {
+                return Err(wrong_arity());
+            }
+            let scope = Scope {
+                params: def
+                    .params
+                    .iter()
+                    .cloned()
+                    .zip(params.iter().copied())
+                    .collect(),
+                qubits: def.qubits.iter().cloned().zip(qs.iter().copied()).collect(),
+            };
+            let mut cur = Cursor::new(&def.body);
+            let mut gates = vec![];
+            while !cur.at_end() {
+                gates.extend(self.call(&mut cur, &scope, depth + 1)?);
+            }
+            return Ok(gates);
+        }
+
+        if name == "U" {
+            // U(theta, phi, lambda) = Rz(phi) Ry(theta) Rz(lambda), up to global phase
+            if params.len() != 3 || qs.len() != 1 {
+                return Err(wrong_arity());
+            }
+            return Ok(vec![
+                Gate::new_with_phase(ZPhase, qs.to_vec(), angle_to_phase(params[2])),
+                Gate::new(Sdg, qs.to_vec()),
+                Gate::new_with_phase(XPhase, qs.to_vec(), angle_to_phase(params[0])),
+                Gate::new(S, qs.to_vec()),
+                Gate::new_with_phase(ZPhase, qs.to_vec(), angle_to_phase(params[1])),
+            ]);
+        }
+
+        let t = GType::from_qasm_name(name);
+        if t == UnknownGate {
+            return Err(Qasm3Error::Undeclared(line, name.to_string()));
+        }
+        let num_params = match t {
+            ZPhase | XPhase | ParityPhase => 1,
+            _ => 0,
+        };
+        if params.len() != num_params || t.num_qubits().is_some_and(|n| n != qs.len()) {
+            return Err(wrong_arity());
+        }
+        let phase = params.first().map_or(Phase::zero(), |&x| angle_to_phase(x));
+        Ok(vec![Gate::new_with_phase(t, qs.to_vec(), phase)])
+    }
+}
+
+/// Reads a circuit from OpenQASM 3.
+///
+/// See the [module docs](self) for what is supported.
+pub fn decode_circuit(s: &str) -> Result<Circuit, Qasm3Error> {
+    let toks = lex(s)?;
+    let mut reader = Reader::new();
+    let mut cur = Cursor::new(&toks);
+    while !cur.at_end() {
+        reader.statement(&mut cur)?;
+    }
+    let mut c = Circuit::new(reader.num_qubits);
+    c.gates.extend(reader.gates);
+    Ok(c)
+}
+
+/// Reads a circuit from an OpenQASM 3 file.
+pub fn read_circuit(filename: &Path) -> Result<Circuit, Qasm3Error> {
+    let s = std::fs::read_to_string(filename)?;
+    decode_circuit(&s)
+}
+
+/// A phase as an angle, e.g. `3*pi/4`
+pub(crate) fn fmt_angle(phase: Phase) -> String {
+    let r = phase.to_rational();
+    let (n, d) = (*r.numer(), *r.denom());
+    let mut s = match n {
+        0 => return "0".to_string(),
+        1 => "pi".to_string(),
+        -1 => "-pi".to_string(),
+        _ => format!("{}*pi", n),
+    };
+    if d != 1 {
+        write!(s, "/{}", d).unwrap();
+    }
+    s
+}
+
+fn write_gate(s: &mut String, g: &Gate) {
+    let qs: Vec<String> = g.qs.iter().map(|q| format!("q[{}]", q)).collect();
+    let qs = qs.join(", ");
+    match g.t {
+        ZPhase | XPhase => writeln!(s, "{}({}) {};", g.qasm_name(), fmt_angle(g.phase), qs),
+        CCZ => writeln!(s, "ctrl @ cz {};", qs),
+        XCX => writeln!(
+            s,
+            "h q[{0}];\ncx q[{0}], q[{1}];\nh q[{0}];",
+            g.qs[0], g.qs[1]
+        ),
+        ParityPhase => {
+            let mut c = Circuit::new(0);
+            g.push_basic_gates(&mut c);
+            for h in &c.gates {
+                write_gate(s, h);
+            }
+            Ok(())
+        }
+        _ => writeln!(s, "{} {};", g.qasm_name(), qs),
+    }
+    .unwrap();
+}
+
+/// Returns the OpenQASM 3 representation of a circuit.
+///
+/// Everything but the pyzx-specific gates `init_anc` and `post_sel` is written
+/// with the gates of "stdgates.inc". Phases are written exactly, as fractions of
+/// `π`.
+pub fn encode_circuit(c: &Circuit) -> String {
+    let mut s = String::from("OPENQASM 3.0;\ninclude \"stdgates.inc\";\n");
+    writeln!(s, "qubit[{}] q;", c.num_qubits()).unwrap();
+    for g in &c.gates {
+        write_gate(&mut s, g);
+    }
+    s
+}
+
+/// Writes the OpenQASM 3 representation of a circuit to a file.
+pub fn write_circuit(c: &Circuit, filename: &Path) -> Result<(), Qasm3Error> {
+    std::fs::write(filename, encode_circuit(c))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn qasm3_roundtrip() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(40)
+            .clifford_t(0.2)
+            .build();
+        let mut c1 = c.clone();
+        c1.push(Gate::new(CCZ, vec![0, 3, 1]));
+        c1.push(Gate::new(TOFF, vec![2, 4, 0]));
+        c1.push(Gate::new_with_phase(
+            XPhase,
+            vec![1],
+            Rational64::new(-2, 3),
+        ));
+        c1.push(Gate::new(SWAP, vec![1, 2]));
+        let c2 = decode_circuit(&encode_circuit(&c1)).unwrap();
+        assert_eq!(c1, c2);
+
+        let mut c3 = Circuit::new(3);
+        c3.push(Gate::new(XCX, vec![0, 1]));
+        c3.push(Gate::new_with_phase(
+            ParityPhase,
+            vec![0, 1, 2],
+            Rational64::new(1, 4),
+        ));
+        let c4 = decode_circuit(&encode_circuit(&c3)).unwrap();
+        let g3: Graph = c3.to_graph();
+        let g4: Graph = c4.to_graph();
+        assert!(Tensor4::scalar_compare(&g3, &g4));
+    }
+
+    #[test]
+    fn qasm3_operands() {
+        let c = decode_circuit(
+            r#"
+            OPENQASM 3;
+            include "stdgates.inc";
+            qubit[4] q;
+            qubit r;
+            bit[4] c;
+            let evens = q[0:2:3];
+            let odds = q[{1, 3}];
+            let all = evens ++ odds ++ r;
+            cx evens, odds;
+            x all[-1];
+            h q[1:];
+            cz r, q;
+        "#,
+        )
+        .unwrap();
+
+        let mut c1 = Circuit::new(5);
+        c1.add_gate("cx", vec![0, 1]);
+        c1.add_gate("cx", vec![2, 3]);
+        c1.add_gate("x", vec![4]);
+        for q in 1..4 {
+            c1.add_gate("h", vec![q]);
+        }
+        for q in 0..4 {
+            c1.add_gate("cz", vec![4, q]);
+        }
+        assert_eq!(c, c1);
+
+        let c = decode_circuit("h $2; cx $0, $2;").unwrap();
+        assert_eq!(c.num_qubits(), 3);
+        assert_eq!(c.num_gates(), 2);
+    }
+
+    #[test]
+    fn qasm3_gates() {
+        let pairs = [
+            ("ctrl @ x q[0], q[1];", "cx q[0], q[1];"),
+            ("ctrl(2) @ x q[0], q[1], q[2];", "ccx q[0], q[1], q[2];"),
+            ("ctrl @ ctrl @ z q[2], q[0], q[1];", "ccz q[0], q[1], q[2];"),
+            ("inv @ t q[0]; inv @ s q[1];", "tdg q[0]; sdg q[1];"),
+            ("pow(2) @ t q[0]; pow(-3) @ s q[1];", "s q[0]; s q[1];"),
+            ("inv @ pow(3) @ u1(pi/4) q[0];", "z q[0]; t q[0];"),
+            (
+                "ch q[0], q[1];",
+                "ry(-pi/4) q[1]; cz q[0], q[1]; ry(pi/4) q[1];",
+            ),
+            (
+                "crx(pi) q[0], q[2]; cry(pi) q[1], q[2];",
+                "cx q[0], q[2]; sdg q[0]; cz q[1], q[2]; cx q[1], q[2];",
+            ),
+            (
+                "phase(pi/4) q[0]; cphase(pi) q[1], q[2];",
+                "t q[0]; cz q[1], q[2];",
+            ),
+            ("gphase(pi/3); U(pi, 0, pi) q[0];", "x q[0];"),
+            (
+                "gate g(a) x, y { cx x, y; rz(a/2) y; inv @ cx x, y; } g(pi) q[1], q[0];",
+                "cx q[1], q[0]; s q[0]; cx q[1], q[0];",
+            ),
+        ];
+        for (qasm0, qasm1) in pairs {
+            let c0 = decode_circuit(&format!("qubit[3] q; {}", qasm0)).unwrap();
+            let c1 = decode_circuit(&format!("qubit[3] q; {}", qasm1)).unwrap();
+            assert!(Tensor4::scalar_compare(&c0, &c1), "{} != {}", qasm0, qasm1);
+        }
+    }
+
+    #[test]
+    fn qasm3_errors() {
+        for qasm in [
+            "qubit[2] q; h q[2];",
+            "qubit[2] q; foo q[0];",
+            "qubit[2] q; bit[2] c; c[0] = measure q[0];",
+            "qubit[2] q; measure q;",
+            "qubit[2] q; cx q[0], q[0];",
+            "qubit[2] q; cx q[0];",
+            "qubit[2] q; ctrl @ h q[0], q[1];",
+            "qubit[2] q; rz(pi/2) q[0]",
+            "qubit[2] q; gate g a { g a; } g q[0];",
+            "include \"other.inc\";",
+            "qubit[2] q; h $0;",
+        ] {
+            assert!(decode_circuit(qasm).is_err(), "{}", qasm);
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Writing QASM for a given dialect and gate set
+//!
+//! [Circuit::to_qasm] writes every gate as it is, including the pyzx-specific gates
+//! `ccz`, `pp`, `xcx`, `init_anc` and `post_sel`, which other tools don't know.
+//! [Circuit::to_qasm_with_options] instead writes either OpenQASM 2 or 3, using only
+//! the gates of a [QasmBasis], and decomposes any other gates on the fly. Phases are
+//! written exactly, as fractions of `π`.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::qasm_export::{QasmBasis, QasmOptions, QasmVersion};
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cz q[0], q[1]; t q[1];").unwrap();
+//! let opts = QasmOptions::new()
+//!     .version(QasmVersion::V3)
+//!     .basis(QasmBasis::RzSxCx);
+//! let qasm = c.to_qasm_with_options(&opts).unwrap();
+//! assert!(qasm.starts_with("OPENQASM 3.0;"));
+//! assert!(!qasm.contains("cz"));
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::phase::Phase;
+use crate::qasm3::fmt_angle;
+
+/// The version of OpenQASM to write
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QasmVersion {
+    /// OpenQASM 2.0, with the gates of `qelib1.inc`.
+    #[default]
+    V2,
+    /// OpenQASM 3.0, with the gates of `stdgates.inc`.
+    V3,
+}
+
+/// The gates which may appear in the QASM output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QasmBasis {
+    /// The standard gates `x`, `z`, `s`, `sdg`, `t`, `tdg`, `h`, `rz`, `rx`, `cx`,
+    /// `cz`, `swap` and `ccx`. With OpenQASM 3, CCZ gates are written as
+    /// `ctrl @ cz`.
+    #[default]
+    Standard,
+    /// Only `u3` and `cx`.
+    U3Cx,
+    /// Only `rz`, `sx`, `x` and `cx`, as on IBM hardware.
+    RzSxCx,
+    /// Only `h`, `x`, `z`, `s`, `sdg`, `t`, `tdg` and `cx`. Fails on phases which
+    /// aren't multiples of `π/4`.
+    CliffordT,
+}
+
+/// Options for [Circuit::to_qasm_with_options]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QasmOptions {
+    pub version: QasmVersion,
+    pub basis: QasmBasis,
+}
+
+impl QasmOptions {
+    pub fn new() -> Self {
+        QasmOptions::default()
+    }
+
+    pub fn version(mut self, version: QasmVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn basis(mut self, basis: QasmBasis) -> Self {
+        self.basis = basis;
+        self
+    }
+}
+
+/// A decomposition of a gate into simpler gates, or `None` if there isn't one
+fn decompose(g: &Gate) -> Option<Circuit> {
+    let mut c = Circuit::new(0);
+    match g.t {
+        XPhase => {
+            c.push(Gate::new(HAD, g.qs.clone()));
+            c.push(Gate::new_with_phase(ZPhase, g.qs.clone(), g.phase));
+            c.push(Gate::new(HAD, g.qs.clone()));
+        }
+        ZPhase => {
+            // phases which are multiples of π/4
+            let r = g.phase.to_rational() * 4;
+            if !r.is_integer() {
+                return None;
+            }
+            let ts: &[GType] = match r.to_integer().rem_euclid(8) {
+                0 => &[],
+                1 => &[T],
+                2 => &[S],
+                3 => &[S, T],
+                4 => &[Z],
+                5 => &[Z, T],
+                6 => &[Sdg],
+                _ => &[Tdg],
+            };
+            for &t in ts {
+                c.push(Gate::new(t, g.qs.clone()));
+            }
+        }
+        CZ => {
+            c.push(Gate::new(HAD, vec![g.qs[1]]));
+            c.push(Gate::new(CNOT, g.qs.clone()));
+            c.push(Gate::new(HAD, vec![g.qs[1]]));
+        }
+        XCX => {
+            c.push(Gate::new(HAD, vec![g.qs[0]]));
+            c.push(Gate::new(CNOT, g.qs.clone()));
+            c.push(Gate::new(HAD, vec![g.qs[0]]));
+        }
+        SWAP => {
+            c.push(Gate::new(CNOT, vec![g.qs[0], g.qs[1]]));
+            c.push(Gate::new(CNOT, vec![g.qs[1], g.qs[0]]));
+            c.push(Gate::new(CNOT, vec![g.qs[0], g.qs[1]]));
+        }
+        CCZ => {
+            c.push(Gate::new(HAD, vec![g.qs[2]]));
+            c.push(Gate::new(TOFF, g.qs.clone()));
+            c.push(Gate::new(HAD, vec![g.qs[2]]));
+        }
+        TOFF | ParityPhase => g.push_basic_gates(&mut c),
+        _ => return None,
+    }
+    Some(c)
+}
+
+/// The phase of a single-qubit Z rotation
+fn z_phase(g: &Gate) -> Option<Phase> {
+    match g.t {
+        Z => Some(Phase::new(1)),
+        S => Some(Phase::from((1, 2))),
+        Sdg => Some(Phase::from((-1, 2))),
+        T => Some(Phase::from((1, 4))),
+        Tdg => Some(Phase::from((-1, 4))),
+        ZPhase => Some(g.phase),
+        _ => None,
+    }
+}
+
+/// The name and parameters of a gate if it is in the basis
+fn basis_gate(g: &Gate, opts: &QasmOptions) -> Option<String> {
+    let half = Phase::from((1, 2));
+    let name = match (opts.basis, g.t) {
+        (_, Measure) => {
+            let (q, b) = (g.qs[0], g.bits[0]);
+            return Some(match opts.version {
+                QasmVersion::V2 => format!("measure q[{}] -> c[{}];\n", q, b),
+                QasmVersion::V3 => format!("c[{}] = measure q[{}];\n", b, q),
+            });
+        }
+        (_, Reset) => "reset".to_string(),
+        (_, Barrier) => "barrier".to_string(),
+        (_, CNOT) => "cx".to_string(),
+        (QasmBasis::U3Cx, t) => {
+            // u3(θ, φ, λ) = Rz(φ) Ry(θ) Rz(λ)
+            let (theta, phi, lambda) = match t {
+                NOT => (Phase::new(1), Phase::new(0), Phase::new(1)),
+                HAD => (half, Phase::new(0), Phase::new(1)),
+                XPhase => (g.phase, -half, half),
+                _ => (Phase::new(0), Phase::new(0), z_phase(g)?),
+            };
+            format!(
+                "u3({}, {}, {})",
+                fmt_angle(theta),
+                fmt_angle(phi),
+                fmt_angle(lambda)
+            )
+        }
+        (QasmBasis::RzSxCx, NOT) => "x".to_string(),
+        (QasmBasis::RzSxCx, XPhase) if g.phase == half => "sx".to_string(),
+        (QasmBasis::RzSxCx, XPhase) if g.phase == Phase::new(1) => "x".to_string(),
+        (QasmBasis::RzSxCx, HAD) => {
+            let q = g.qs[0];
+            return Some(format!(
+                "rz(pi/2) q[{0}];\nsx q[{0}];\nrz(pi/2) q[{0}];\n",
+                q
+            ));
+        }
+        (QasmBasis::RzSxCx, _) => format!("rz({})", fmt_angle(z_phase(g)?)),
+        (QasmBasis::CliffordT, ZPhase | XPhase) => return None,
+        (QasmBasis::CliffordT, NOT | Z | S | Sdg | T | Tdg | HAD) => g.qasm_name().to_string(),
+        (QasmBasis::Standard, ZPhase | XPhase) => {
+            format!("{}({})", g.qasm_name(), fmt_angle(g.phase))
+        }
+        (QasmBasis::Standard, CCZ) if opts.version == QasmVersion::V3 => "ctrl @ cz".to_string(),
+        (QasmBasis::Standard, NOT | Z | S | Sdg | T | Tdg | HAD | CZ | SWAP | TOFF) => {
+            g.qasm_name().to_string()
+        }
+        _ => return None,
+    };
+    let qs: Vec<String> = g.qs.iter().map(|q| format!("q[{}]", q)).collect();
+    Some(format!("{} {};\n", name, qs.join(", ")))
+}
+
+fn write_gate(s: &mut String, g: &Gate, opts: &QasmOptions) -> Result<(), String> {
+    if let Some(line) = basis_gate(g, opts) {
+        *s += &line;
+        return Ok(());
+    }
+    let c = decompose(g).ok_or_else(|| {
+        format!(
+            "Gate {} cannot be written in the basis {:?}",
+            g.qasm_name(),
+            opts.basis
+        )
+    })?;
+    for h in &c.gates {
+        write_gate(s, h, opts)?;
+    }
+    Ok(())
+}
+
+impl Circuit {
+    /// Returns the QASM representation of a circuit, for the given options.
+    ///
+    /// See the [module docs](crate::qasm_export). Fails on the pyzx-specific gates
+    /// `init_anc` and `post_sel`, and on gates which cannot be written in the basis.
+    /// Measurements, resets and barriers are kept, and a classically controlled gate
+    /// which is decomposed has the condition on each of its parts.
+    pub fn to_qasm_with_options(&self, opts: &QasmOptions) -> Result<String, String> {
+        let mut s = match opts.version {
+            QasmVersion::V2 => format!(
+                "OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[{}];\n",
+                self.num_qubits()
+            ),
+            QasmVersion::V3 => format!(
+                "OPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[{}] q;\n",
+                self.num_qubits()
+            ),
+        };
+        let nbits = self.num_bits();
+        if nbits > 0 {
+            s += &match opts.version {
+                QasmVersion::V2 => format!("creg c[{}];\n", nbits),
+                QasmVersion::V3 => format!("bit[{}] c;\n", nbits),
+            };
+        }
+        for g in &self.gates {
+            match &g.condition {
+                Some(cond) => {
+                    let mut lines = String::new();
+                    write_gate(&mut lines, g, opts)?;
+                    for l in lines.lines() {
+                        s += &format!("{} {}\n", cond.to_qasm(nbits), l);
+                    }
+                }
+                None => write_gate(&mut s, g, opts)?,
+            }
+        }
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qasm3;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    /// Check two circuits are equal up to a global phase
+    ///
+    /// The tensors are computed from the graphs of the circuits, as not every gate
+    /// has a direct tensor implementation, and compared numerically, as the phases
+    /// need not be multiples of π/4.
+    fn approx_scalar_compare(c0: &Circuit, c1: &Circuit) -> bool {
+        let t0 = c0.to_graph::<Graph>().to_tensorf();
+        let t1 = c1.to_graph::<Graph>().to_tensorf();
+        let (i, x) = t0
+            .indexed_iter()
+            .max_by(|a, b| a.1.norm().total_cmp(&b.1.norm()))
+            .unwrap();
+        let r = t1[&i] / x;
+        (r.norm() - 1.0).abs() < 1e-6
+            && t0
+                .iter()
+                .zip(t1.iter())
+                .all(|(x, y)| (x * r - y).norm() < 1e-6)
+    }
+
+    #[test]
+    fn qasm_bases() {
+        let mut c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .clifford_t(0.2)
+            .build();
+        c.push(Gate::new(CCZ, vec![0, 3, 1]));
+        c.push(Gate::new(TOFF, vec![2, 1, 0]));
+        c.push(Gate::new(SWAP, vec![1, 2]));
+        c.push(Gate::new(XCX, vec![2, 3]));
+        c.push(Gate::new_with_phase(
+            ParityPhase,
+            vec![0, 1, 3],
+            Phase::from((1, 4)),
+        ));
+        c.push(Gate::new_with_phase(XPhase, vec![1], Phase::from((-1, 2))));
+        let mut c_arb = c.clone();
+        c_arb.push(Gate::new_with_phase(XPhase, vec![0], Phase::from((2, 3))));
+        c_arb.push(Gate::new_with_phase(ZPhase, vec![3], Phase::from((1, 8))));
+
+        let allowed: [(QasmBasis, &[&str]); 4] = [
+            (
+                QasmBasis::Standard,
+                &[
+                    "x", "z", "s", "sdg", "t", "tdg", "h", "rz", "rx", "cx", "cz", "swap", "ccx",
+                    "ctrl",
+                ],
+            ),
+            (QasmBasis::U3Cx, &["u3", "cx"]),
+            (QasmBasis::RzSxCx, &["rz", "sx", "x", "cx"]),
+            (
+                QasmBasis::CliffordT,
+                &["h", "x", "z", "s", "sdg", "t", "tdg", "cx"],
+            ),
+        ];
+        for (basis, names) in allowed {
+            for version in [QasmVersion::V2, QasmVersion::V3] {
+                let circ = if basis == QasmBasis::CliffordT {
+                    &c
+                } else {
+                    &c_arb
+                };
+                let opts = QasmOptions::new().version(version).basis(basis);
+                let qasm = circ.to_qasm_with_options(&opts).unwrap();
+                for line in qasm.lines().skip(3) {
+                    let name = line.split([' ', '(']).next().unwrap();
+                    assert!(names.contains(&name), "{:?}: {}", basis, line);
+                }
+
+                let c1 = match version {
+                    QasmVersion::V2 => Circuit::from_qasm(&qasm).unwrap(),
+                    QasmVersion::V3 => qasm3::decode_circuit(&qasm).unwrap(),
+                };
+                assert!(approx_scalar_compare(circ, &c1), "{:?}", opts);
+            }
+        }
+    }
+
+    #[test]
+    fn qasm_measurements() {
+        let mut c = Circuit::new(3);
+        c.push(Gate::new(HAD, vec![0]));
+        c.push(Gate::new_measure(0, 0));
+        c.push(Gate::new(CCZ, vec![0, 1, 2]).with_condition(vec![0], 1));
+        c.push(Gate::new(Reset, vec![0]));
+        let opts = QasmOptions::new().basis(QasmBasis::CliffordT);
+        let qasm = c.to_qasm_with_options(&opts).unwrap();
+        let lines: Vec<&str> = qasm.lines().skip(3).collect();
+        assert_eq!(lines[0], "creg c[1];");
+        assert_eq!(lines[2], "measure q[0] -> c[0];");
+        assert_eq!(lines.last(), Some(&"reset q[0];"));
+        assert!(lines[3..lines.len() - 1]
+            .iter()
+            .all(|l| l.starts_with("if(c==1) ")));
+        let c1 = Circuit::from_qasm(&qasm).unwrap();
+        let cond = Some(Condition {
+            bits: vec![0],
+            value: 1,
+        });
+        let nconds = c1.gates.iter().filter(|g| g.condition == cond).count();
+        assert_eq!(nconds, lines.len() - 4);
+
+        let opts = QasmOptions::new().version(QasmVersion::V3);
+        let qasm = c.to_qasm_with_options(&opts).unwrap();
+        assert!(qasm.contains("bit[1] c;\nh q[0];\nc[0] = measure q[0];\n"));
+    }
+
+    #[test]
+    fn qasm_basis_errors() {
+        let mut c = Circuit::new(1);
+        c.push(Gate::new_with_phase(ZPhase, vec![0], Phase::from((1, 3))));
+        let opts = QasmOptions::new().basis(QasmBasis::CliffordT);
+        assert!(c.to_qasm_with_options(&opts).is_err());
+        assert!(c.to_qasm_with_options(&QasmOptions::new()).is_ok());
+
+        let mut c = Circuit::new(1);
+        c.push(Gate::new(InitAncilla, vec![0]));
+        assert!(c.to_qasm_with_options(&QasmOptions::new()).is_err());
+    }
+}
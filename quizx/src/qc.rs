@@ -0,0 +1,459 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `.qc` and `.tfc` formats of reversible circuit benchmarks
+//!
+//! Both formats declare their variables with `.v`, followed by a list of gates
+//! between `BEGIN` and `END`, one per line. Each variable becomes a qubit, in the
+//! order they are declared. The `.i`, `.o` and `.c` declarations of inputs, outputs
+//! and constants are ignored, so a circuit is read as a unitary on all its
+//! variables.
+//!
+//! In `.qc` files, `tof` (or `X`, `cnot`) with 1 to 3 arguments is a NOT, CNOT or
+//! Toffoli, where the last argument is the target, and `Z` with 1 to 3 arguments
+//! is a Z, CZ or CCZ. The other gates are `H`, `S`, `S*`, `P`, `P*`, `T`, `T*`, `Y`
+//! and `swap`. In `.tfc` files, `t1` to `t3` are Toffoli gates with 0 to 2 controls,
+//! `f2` and `f3` are Fredkin gates with 0 or 1 controls, and a control `a'` is a
+//! control on `|0>`.
+//!
+//! Toffoli gates are read as given by [ToffoliDecomposition].
+//!
+//! ```
+//! # use quizx::qc::{decode_qc, ToffoliDecomposition};
+//! let qc = "
+//! .v a b c
+//! BEGIN
+//! tof a b c
+//! T* a
+//! END
+//! ";
+//! let c = decode_qc(qc, ToffoliDecomposition::CliffordT).unwrap();
+//! assert_eq!(c.num_qubits(), 3);
+//! assert_eq!(c.stats().non_cliff, 8);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::phase::Phase;
+use std::fmt::Write;
+use std::path::Path;
+
+/// An error that can occur when reading or writing `.qc` and `.tfc` files.
+#[derive(Debug, thiserror::Error)]
+pub enum QcError {
+    /// A line could not be parsed.
+    #[error("line {0}: {1}")]
+    SyntaxError(usize, String),
+    /// A gate with no counterpart in a [Circuit].
+    #[error("line {0}: {1} is not supported")]
+    UnsupportedLine(usize, String),
+    /// A gate which cannot be written in the format.
+    #[error("Gate {0} cannot be written in this format")]
+    UnsupportedGate(String),
+    /// The file extension is neither `.qc` nor `.tfc`.
+    #[error("Unknown file extension for {0}")]
+    UnknownExtension(String),
+    /// Error reading or writing a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// How to read Toffoli gates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToffoliDecomposition {
+    /// Keep them as Toffoli gates.
+    #[default]
+    Keep,
+    /// A CCZ gate, with Hadamards on the target.
+    Ccz,
+    /// The usual Clifford+T circuit with 7 T gates, as in [Gate::push_basic_gates].
+    CliffordT,
+}
+
+impl ToffoliDecomposition {
+    fn push_toffoli(&self, c: &mut Circuit, qs: Vec<usize>) {
+        let g = Gate::new(TOFF, qs);
+        match self {
+            ToffoliDecomposition::Keep => c.push(g),
+            ToffoliDecomposition::Ccz => {
+                c.push(Gate::new(HAD, vec![g.qs[2]]));
+                c.push(Gate::new(CCZ, g.qs.clone()));
+                c.push(Gate::new(HAD, vec![g.qs[2]]));
+            }
+            ToffoliDecomposition::CliffordT => g.push_basic_gates(c),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Qc,
+    Tfc,
+}
+
+/// The qubits of the arguments of a gate, and whether each is negated with `'`
+fn arguments(
+    args: &str,
+    vars: &mut Vec<String>,
+    line_no: usize,
+) -> Result<Vec<(usize, bool)>, QcError> {
+    let mut qs: Vec<(usize, bool)> = vec![];
+    for a in args.split([' ', '\t', ',']).filter(|a| !a.is_empty()) {
+        let (name, negated) = match a.strip_suffix('\'') {
+            Some(name) => (name, true),
+            None => (a, false),
+        };
+        let q = match vars.iter().position(|v| v == name) {
+            Some(q) => q,
+            None => {
+                vars.push(name.to_string());
+                vars.len() - 1
+            }
+        };
+        if qs.iter().any(|&(q1, _)| q1 == q) {
+            return Err(QcError::SyntaxError(
+                line_no,
+                format!("repeated argument {}", name),
+            ));
+        }
+        qs.push((q, negated));
+    }
+    Ok(qs)
+}
+
+fn decode(s: &str, format: Format, tof: ToffoliDecomposition) -> Result<Circuit, QcError> {
+    let mut vars: Vec<String> = vec![];
+    let mut gates = Circuit::new(0);
+
+    for (i, line) in s.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (name, args) = line.split_once([' ', '\t']).unwrap_or((line, ""));
+        if name == ".v" {
+            arguments(args, &mut vars, line_no)?;
+            continue;
+        }
+        if name.starts_with('.') || name.eq_ignore_ascii_case("END") {
+            continue;
+        }
+        if name.eq_ignore_ascii_case("BEGIN") {
+            if !args.trim().is_empty() {
+                return Err(QcError::UnsupportedLine(line_no, "subcircuits".into()));
+            }
+            continue;
+        }
+
+        let args = arguments(args, &mut vars, line_no)?;
+        let unsupported = || QcError::UnsupportedLine(line_no, line.to_string());
+        let qs: Vec<usize> = args.iter().map(|&(q, _)| q).collect();
+        let negated: Vec<usize> = args.iter().filter(|a| a.1).map(|a| a.0).collect();
+        if !negated.is_empty() && format == Format::Qc {
+            return Err(QcError::SyntaxError(line_no, line.to_string()));
+        }
+        for &q in &negated {
+            gates.push(Gate::new(NOT, vec![q]));
+        }
+
+        let single = |t: GType| match args[..] {
+            [(q, _)] => Ok(Gate::new(t, vec![q])),
+            _ => Err(unsupported()),
+        };
+        match (format, name) {
+            (Format::Qc, "tof" | "Tof" | "X" | "x" | "cnot" | "CNOT" | "not" | "NOT")
+            | (Format::Tfc, "t1" | "t2" | "t3") => match qs.len() {
+                1 => gates.push(Gate::new(NOT, qs)),
+                2 => gates.push(Gate::new(CNOT, qs)),
+                3 => tof.push_toffoli(&mut gates, qs),
+                _ => return Err(unsupported()),
+            },
+            (Format::Qc, "Z" | "z" | "Zd") => match qs.len() {
+                1 => gates.push(Gate::new(Z, qs)),
+                2 => gates.push(Gate::new(CZ, qs)),
+                3 => gates.push(Gate::new(CCZ, qs)),
+                _ => return Err(unsupported()),
+            },
+            (Format::Qc, "swap" | "SWAP") | (Format::Tfc, "f2" | "f3") => match qs.len() {
+                2 => gates.push(Gate::new(SWAP, qs)),
+                3 => {
+                    // a controlled swap is a Toffoli between two CNOTs
+                    gates.push(Gate::new(CNOT, vec![qs[2], qs[1]]));
+                    tof.push_toffoli(&mut gates, qs.clone());
+                    gates.push(Gate::new(CNOT, vec![qs[2], qs[1]]));
+                }
+                _ => return Err(unsupported()),
+            },
+            (Format::Qc, "H" | "h") => gates.push(single(HAD)?),
+            (Format::Qc, "S" | "s" | "P" | "p") => gates.push(single(S)?),
+            (Format::Qc, "S*" | "s*" | "P*" | "p*") => gates.push(single(Sdg)?),
+            (Format::Qc, "T" | "t") => gates.push(single(T)?),
+            (Format::Qc, "T*" | "t*") => gates.push(single(Tdg)?),
+            (Format::Qc, "Y" | "y") => {
+                gates.push(single(Z)?);
+                gates.push(single(NOT)?);
+            }
+            _ => return Err(unsupported()),
+        }
+
+        for &q in &negated {
+            gates.push(Gate::new(NOT, vec![q]));
+        }
+    }
+
+    let mut c = Circuit::new(vars.len());
+    c.gates = gates.gates;
+    Ok(c)
+}
+
+/// Reads a circuit in the `.qc` format.
+///
+/// See the [module docs](self) for what is supported.
+pub fn decode_qc(s: &str, tof: ToffoliDecomposition) -> Result<Circuit, QcError> {
+    decode(s, Format::Qc, tof)
+}
+
+/// Reads a circuit in the `.tfc` format.
+///
+/// See the [module docs](self) for what is supported.
+pub fn decode_tfc(s: &str, tof: ToffoliDecomposition) -> Result<Circuit, QcError> {
+    decode(s, Format::Tfc, tof)
+}
+
+fn format_of(filename: &Path) -> Result<Format, QcError> {
+    match filename.extension().and_then(|e| e.to_str()) {
+        Some("qc") => Ok(Format::Qc),
+        Some("tfc") => Ok(Format::Tfc),
+        _ => Err(QcError::UnknownExtension(filename.display().to_string())),
+    }
+}
+
+/// Reads a circuit from a `.qc` or `.tfc` file, depending on its extension.
+pub fn read_circuit(filename: &Path, tof: ToffoliDecomposition) -> Result<Circuit, QcError> {
+    let format = format_of(filename)?;
+    let s = std::fs::read_to_string(filename)?;
+    decode(&s, format, tof)
+}
+
+/// The `.qc` gates for a Z phase which is a multiple of `π/4`
+fn qc_phase_gates(phase: Phase) -> Option<&'static [&'static str]> {
+    let r = phase.to_rational() * 4;
+    if !r.is_integer() {
+        return None;
+    }
+    let gates: &[&str] = match r.to_integer().rem_euclid(8) {
+        0 => &[],
+        1 => &["T"],
+        2 => &["S"],
+        3 => &["S", "T"],
+        4 => &["Z"],
+        5 => &["Z", "T"],
+        6 => &["S*"],
+        _ => &["T*"],
+    };
+    Some(gates)
+}
+
+fn encode_gate(s: &mut String, g: &Gate, format: Format) -> Result<(), QcError> {
+    let unsupported = || QcError::UnsupportedGate(g.qasm_name().to_string());
+    let args: Vec<String> = g.qs.iter().map(|q| format!("q{}", q)).collect();
+    let sep = if format == Format::Qc { " " } else { "," };
+    let args = args.join(sep);
+
+    let name = match (format, g.t) {
+        (Format::Qc, NOT | CNOT | TOFF) => "tof".to_string(),
+        (Format::Tfc, NOT | CNOT | TOFF) => format!("t{}", g.qs.len()),
+        (Format::Qc, SWAP) => "swap".to_string(),
+        (Format::Tfc, SWAP) => "f2".to_string(),
+        (Format::Qc, Z | CZ | CCZ) => "Z".to_string(),
+        (Format::Qc, HAD) => "H".to_string(),
+        (Format::Qc, S) => "S".to_string(),
+        (Format::Qc, Sdg) => "S*".to_string(),
+        (Format::Qc, T) => "T".to_string(),
+        (Format::Qc, Tdg) => "T*".to_string(),
+        (Format::Qc, ZPhase) => {
+            for name in qc_phase_gates(g.phase).ok_or_else(unsupported)? {
+                writeln!(s, "{} {}", name, args).unwrap();
+            }
+            return Ok(());
+        }
+        (Format::Qc, XPhase | XCX | ParityPhase) => {
+            let mut c = Circuit::new(0);
+            match g.t {
+                XPhase => {
+                    c.push(Gate::new(HAD, g.qs.clone()));
+                    c.push(Gate::new_with_phase(ZPhase, g.qs.clone(), g.phase));
+                    c.push(Gate::new(HAD, g.qs.clone()));
+                }
+                XCX => {
+                    c.push(Gate::new(HAD, vec![g.qs[0]]));
+                    c.push(Gate::new(CNOT, g.qs.clone()));
+                    c.push(Gate::new(HAD, vec![g.qs[0]]));
+                }
+                _ => g.push_basic_gates(&mut c),
+            }
+            for h in &c.gates {
+                encode_gate(s, h, format)?;
+            }
+            return Ok(());
+        }
+        _ => return Err(unsupported()),
+    };
+    writeln!(s, "{} {}", name, args).unwrap();
+    Ok(())
+}
+
+fn encode(c: &Circuit, format: Format) -> Result<String, QcError> {
+    let vars: Vec<String> = (0..c.num_qubits()).map(|q| format!("q{}", q)).collect();
+    let sep = if format == Format::Qc { " " } else { "," };
+    let vars = vars.join(sep);
+    let mut s = String::new();
+    for decl in [".v", ".i", ".o"] {
+        writeln!(s, "{} {}", decl, vars).unwrap();
+    }
+    s += "BEGIN\n";
+    for g in &c.gates {
+        encode_gate(&mut s, g, format)?;
+    }
+    s += "END\n";
+    Ok(s)
+}
+
+/// Returns a circuit in the `.qc` format.
+///
+/// Phase gates must be multiples of `π/4`, and the pyzx-specific gates `init_anc`
+/// and `post_sel` are not supported.
+pub fn encode_qc(c: &Circuit) -> Result<String, QcError> {
+    encode(c, Format::Qc)
+}
+
+/// Returns a circuit in the `.tfc` format.
+///
+/// Only NOT, CNOT, Toffoli and swap gates are supported.
+pub fn encode_tfc(c: &Circuit) -> Result<String, QcError> {
+    encode(c, Format::Tfc)
+}
+
+/// Writes a circuit to a `.qc` or `.tfc` file, depending on its extension.
+pub fn write_circuit(c: &Circuit, filename: &Path) -> Result<(), QcError> {
+    let s = encode(c, format_of(filename)?)?;
+    std::fs::write(filename, s)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn qc_roundtrip() {
+        let mut c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(40)
+            .clifford_t(0.2)
+            .build();
+        c.push(Gate::new(CCZ, vec![0, 3, 1]));
+        c.push(Gate::new(TOFF, vec![2, 4, 0]));
+        c.push(Gate::new(SWAP, vec![1, 2]));
+        c.push(Gate::new(Tdg, vec![1]));
+        c.push(Gate::new(Sdg, vec![2]));
+        let qc = encode_qc(&c).unwrap();
+        let c1 = decode_qc(&qc, ToffoliDecomposition::Keep).unwrap();
+        assert_eq!(c, c1);
+
+        let mut c = Circuit::new(2);
+        c.push(Gate::new_with_phase(ZPhase, vec![0], Phase::from((3, 4))));
+        c.push(Gate::new_with_phase(XPhase, vec![1], Phase::from((-1, 2))));
+        c.push(Gate::new(XCX, vec![0, 1]));
+        let c1 = decode_qc(&encode_qc(&c).unwrap(), ToffoliDecomposition::Keep).unwrap();
+        let g: Graph = c.to_graph();
+        let g1: Graph = c1.to_graph();
+        assert!(Tensor4::scalar_compare(&g, &g1));
+
+        c.push(Gate::new_with_phase(ZPhase, vec![0], Phase::from((1, 3))));
+        assert!(encode_qc(&c).is_err());
+    }
+
+    #[test]
+    fn tfc_roundtrip() {
+        let mut c = Circuit::new(4);
+        c.push(Gate::new(NOT, vec![1]));
+        c.push(Gate::new(CNOT, vec![0, 1]));
+        c.push(Gate::new(TOFF, vec![3, 1, 2]));
+        c.push(Gate::new(SWAP, vec![0, 3]));
+        let tfc = encode_tfc(&c).unwrap();
+        assert!(tfc.contains("t3 q3,q1,q2"));
+        let c1 = decode_tfc(&tfc, ToffoliDecomposition::Keep).unwrap();
+        assert_eq!(c, c1);
+
+        c.push(Gate::new(HAD, vec![0]));
+        assert!(encode_tfc(&c).is_err());
+    }
+
+    #[test]
+    fn toffoli_decompositions() {
+        let tfc = "
+            .v a,b,c,d
+            .i a,b,c
+            .c 0
+            BEGIN
+            t3 a,b',d
+            f3 c,a,b
+            t1 c
+            END
+        ";
+        let c = decode_tfc(tfc, ToffoliDecomposition::Keep).unwrap();
+        assert_eq!(c.num_qubits(), 4);
+        assert_eq!(c.num_gates_of_type(TOFF), 2);
+        assert_eq!(c.num_gates_of_type(NOT), 3);
+
+        let c1 = decode_tfc(tfc, ToffoliDecomposition::Ccz).unwrap();
+        assert_eq!(c1.num_gates_of_type(CCZ), 2);
+        let c2 = decode_tfc(tfc, ToffoliDecomposition::CliffordT).unwrap();
+        assert_eq!(c2.stats().non_cliff, 14);
+        assert!(Tensor4::scalar_compare(&c, &c1));
+        assert!(Tensor4::scalar_compare(&c, &c2));
+
+        // the Fredkin gate swaps b and a when c is set
+        let mut c3 = Circuit::new(4);
+        c3.push(Gate::new(NOT, vec![1]));
+        c3.push(Gate::new(TOFF, vec![0, 1, 3]));
+        c3.push(Gate::new(NOT, vec![1]));
+        c3.push(Gate::new(CNOT, vec![1, 0]));
+        c3.push(Gate::new(TOFF, vec![2, 0, 1]));
+        c3.push(Gate::new(CNOT, vec![1, 0]));
+        c3.push(Gate::new(NOT, vec![2]));
+        assert_eq!(c, c3);
+    }
+
+    #[test]
+    fn qc_errors() {
+        for qc in [
+            "BEGIN\ntof a b c d\nEND",
+            "BEGIN\ntof a a\nEND",
+            "BEGIN\nfoo a\nEND",
+            "BEGIN\nH a b\nEND",
+            "BEGIN\ntof a' b\nEND",
+            "BEGIN f(a, b)\nEND",
+        ] {
+            assert!(decode_qc(qc, ToffoliDecomposition::Keep).is_err(), "{}", qc);
+        }
+    }
+}
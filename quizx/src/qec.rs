@@ -0,0 +1,595 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Syndrome extraction circuits for quantum error-correcting codes
+//!
+//! A [CssCode] is given by the supports of its X and Z stabilisers on some data
+//! qubits. [CssCode::memory] builds a memory experiment for it: the data qubits are
+//! prepared in the `|0...0>` or `|+...+>` state, the stabilisers are measured for
+//! some number of rounds, and finally the data qubits are measured. Each
+//! stabiliser is measured with an ancilla, using CNOTs from the data qubits for a Z
+//! stabiliser, or CNOTs onto them between Hadamards for an X stabiliser. All the Z
+//! stabilisers are measured first, then all the X stabilisers, so any CSS code can
+//! be used without worrying about the order of the CNOTs.
+//!
+//! The experiment can go through several codes on the same data qubits, which is
+//! how lattice surgery is done: [CssCode::rotated_surface_pair] gives two patches
+//! of the surface code and the single patch they merge into.
+//!
+//! ```
+//! # use quizx::qec::*;
+//! let code = CssCode::rotated_surface(3);
+//! assert_eq!(code.data_qubits, 9);
+//! let c = code.memory().rounds(2).build();
+//! // 8 stabilisers measured twice, then the 9 data qubits
+//! assert_eq!(c.num_bits(), 25);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+
+/// The basis of a memory experiment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Basis {
+    /// Prepare `|0...0>` and measure the data qubits in the Z basis
+    Z,
+    /// Prepare `|+...+>` and measure the data qubits in the X basis
+    X,
+}
+
+/// A CSS code, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CssCode {
+    pub data_qubits: usize,
+    /// The supports of the X stabilisers
+    pub x_stabilizers: Vec<Vec<usize>>,
+    /// The supports of the Z stabilisers
+    pub z_stabilizers: Vec<Vec<usize>>,
+    /// The supports of the logical X operators, one for each logical qubit
+    pub logical_x: Vec<Vec<usize>>,
+    /// The supports of the logical Z operators, one for each logical qubit
+    pub logical_z: Vec<Vec<usize>>,
+}
+
+/// The X and Z stabilisers of a patch of the rotated surface code
+///
+/// The patch has `rows` rows and `cols` columns of data qubits, where the qubit in
+/// row `r` and column `c` is `r * width + c0 + c`. Interior plaquettes alternate
+/// between Z and X in a checkerboard, and the weight-2 plaquettes on the top and
+/// bottom boundaries are X, and on the left and right boundaries Z.
+fn rotated_patch(
+    rows: usize,
+    cols: usize,
+    width: usize,
+    c0: usize,
+) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let (rows, cols) = (rows as isize, cols as isize);
+    let mut xs = vec![];
+    let mut zs = vec![];
+    for r in -1..rows {
+        for c in -1..cols {
+            let support: Vec<usize> = [(0, 0), (0, 1), (1, 0), (1, 1)]
+                .iter()
+                .map(|(dr, dc)| (r + dr, c + dc))
+                .filter(|&(r1, c1)| 0 <= r1 && r1 < rows && 0 <= c1 && c1 < cols)
+                .map(|(r1, c1)| r1 as usize * width + c0 + c1 as usize)
+                .collect();
+            let z_type = (r + c).rem_euclid(2) == 0;
+            let top_bottom = r == -1 || r == rows - 1;
+            let left_right = c == -1 || c == cols - 1;
+            let keep = support.len() == 4
+                || (support.len() == 2 && (if z_type { left_right } else { top_bottom }));
+            if keep {
+                if z_type {
+                    zs.push(support);
+                } else {
+                    xs.push(support);
+                }
+            }
+        }
+    }
+    (xs, zs)
+}
+
+impl CssCode {
+    /// The rotated surface code with distance `d`, see [CssCode::rotated_surface_rect]
+    pub fn rotated_surface(d: usize) -> CssCode {
+        CssCode::rotated_surface_rect(d, d)
+    }
+
+    /// The rotated surface code on a grid of `rows` by `cols` data qubits
+    ///
+    /// The qubit in row `r` and column `c` is `r * cols + c`. The top and bottom
+    /// boundaries have weight-2 X stabilisers, and the left and right boundaries
+    /// weight-2 Z stabilisers, so the logical X is a column of X, with distance
+    /// `rows`, and the logical Z is a row of Z, with distance `cols`. Use
+    /// [CssCode::dual] for the other choice of boundaries.
+    pub fn rotated_surface_rect(rows: usize, cols: usize) -> CssCode {
+        let (x_stabilizers, z_stabilizers) = rotated_patch(rows, cols, cols, 0);
+        CssCode {
+            data_qubits: rows * cols,
+            x_stabilizers,
+            z_stabilizers,
+            logical_x: vec![(0..rows).map(|r| r * cols).collect()],
+            logical_z: vec![(0..cols).collect()],
+        }
+    }
+
+    /// Two patches of the rotated surface code with odd distance `d`, and the
+    /// patch they merge into by lattice surgery
+    ///
+    /// The data qubits form a grid of `d` rows and `2d + 1` columns, as for
+    /// [CssCode::rotated_surface_rect]. The patches are on the first and last `d`
+    /// columns, and the merged patch covers the whole grid. Merging measures the
+    /// product of the two logical X operators. Going from the patches to the merged
+    /// patch in [MemoryCircuitBuilder::then] prepares the middle column in `|0>`,
+    /// and going back measures it.
+    pub fn rotated_surface_pair(d: usize) -> (CssCode, CssCode) {
+        assert!(d % 2 == 1, "Lattice surgery needs an odd distance");
+        let width = 2 * d + 1;
+        let (mut xs, mut zs) = rotated_patch(d, d, width, 0);
+        let (xs1, zs1) = rotated_patch(d, d, width, d + 1);
+        xs.extend(xs1);
+        zs.extend(zs1);
+        let pair = CssCode {
+            data_qubits: d * width,
+            x_stabilizers: xs,
+            z_stabilizers: zs,
+            logical_x: vec![
+                (0..d).map(|r| r * width).collect(),
+                (0..d).map(|r| r * width + d + 1).collect(),
+            ],
+            logical_z: vec![(0..d).collect(), (d + 1..width).collect()],
+        };
+        (pair, CssCode::rotated_surface_rect(d, width))
+    }
+
+    /// The unrotated surface code with distance `d`
+    ///
+    /// The data qubits are the points `(r, c)` of a `2d - 1` by `2d - 1` grid with
+    /// `r + c` even, numbered row by row. The other points are the stabilisers, on
+    /// their neighbours: X stabilisers in the even rows and Z stabilisers in the odd
+    /// rows. The logical Z is the top row and the logical X the left column.
+    pub fn unrotated_surface(d: usize) -> CssCode {
+        let w = 2 * d - 1;
+        let index = |r: usize, c: usize| (r * w + c) / 2;
+        let mut x_stabilizers = vec![];
+        let mut z_stabilizers = vec![];
+        for r in 0..w {
+            for c in 0..w {
+                if (r + c) % 2 == 0 {
+                    continue;
+                }
+                let mut support = vec![];
+                if r > 0 {
+                    support.push(index(r - 1, c));
+                }
+                if c > 0 {
+                    support.push(index(r, c - 1));
+                }
+                if c + 1 < w {
+                    support.push(index(r, c + 1));
+                }
+                if r + 1 < w {
+                    support.push(index(r + 1, c));
+                }
+                if r % 2 == 0 {
+                    x_stabilizers.push(support);
+                } else {
+                    z_stabilizers.push(support);
+                }
+            }
+        }
+        CssCode {
+            data_qubits: (w * w).div_ceil(2),
+            x_stabilizers,
+            z_stabilizers,
+            logical_x: vec![(0..w).step_by(2).map(|r| index(r, 0)).collect()],
+            logical_z: vec![(0..w).step_by(2).map(|c| index(0, c)).collect()],
+        }
+    }
+
+    /// The repetition code with distance `d`, which corrects bit flips
+    ///
+    /// The stabilisers are `Z_i Z_{i+1}`, the logical Z is `Z_0` and the logical X
+    /// is X on every qubit.
+    pub fn repetition(d: usize) -> CssCode {
+        CssCode {
+            data_qubits: d,
+            x_stabilizers: vec![],
+            z_stabilizers: (1..d).map(|i| vec![i - 1, i]).collect(),
+            logical_x: vec![(0..d).collect()],
+            logical_z: vec![vec![0]],
+        }
+    }
+
+    /// The triangular 6.6.6 color code with odd distance `d`
+    ///
+    /// The code has `(3d^2 + 1) / 4` data qubits on the vertices of a hexagonal
+    /// lattice cut into a triangle, and an X and a Z stabiliser on each face, which
+    /// are hexagons inside the triangle and squares along its sides. The logical X
+    /// and Z are both on the qubits along one side. For `d = 3` this is the Steane
+    /// code.
+    pub fn color_code(d: usize) -> CssCode {
+        assert!(d % 2 == 1, "The color code needs an odd distance");
+        // the points (a, b) of a triangular lattice with a + b <= size, where the
+        // points with a - b = 1 mod 3 are the centres of the faces
+        let size = 3 * (d - 1) / 2;
+        let is_face = |a: usize, b: usize| (a + 2 * b) % 3 == 1;
+        let mut index = std::collections::HashMap::new();
+        for a in 0..=size {
+            for b in 0..=size - a {
+                if !is_face(a, b) {
+                    index.insert((a, b), index.len());
+                }
+            }
+        }
+
+        let mut faces = vec![];
+        for a in 0..=size {
+            for b in 0..=size - a {
+                if is_face(a, b) {
+                    let (a, b) = (a as isize, b as isize);
+                    let neighbours = [(1, 0), (0, 1), (-1, 1), (-1, 0), (0, -1), (1, -1)];
+                    let face: Vec<usize> = neighbours
+                        .iter()
+                        .filter(|&&(da, db)| a + da >= 0 && b + db >= 0)
+                        .filter_map(|(da, db)| index.get(&((a + da) as usize, (b + db) as usize)))
+                        .copied()
+                        .collect();
+                    faces.push(face);
+                }
+            }
+        }
+
+        let side: Vec<usize> = (0..=size)
+            .filter_map(|a| index.get(&(a, 0)))
+            .copied()
+            .collect();
+        CssCode {
+            data_qubits: index.len(),
+            x_stabilizers: faces.clone(),
+            z_stabilizers: faces,
+            logical_x: vec![side.clone()],
+            logical_z: vec![side],
+        }
+    }
+
+    /// The same code with the roles of X and Z swapped
+    pub fn dual(&self) -> CssCode {
+        CssCode {
+            data_qubits: self.data_qubits,
+            x_stabilizers: self.z_stabilizers.clone(),
+            z_stabilizers: self.x_stabilizers.clone(),
+            logical_x: self.logical_z.clone(),
+            logical_z: self.logical_x.clone(),
+        }
+    }
+
+    pub fn num_stabilizers(&self) -> usize {
+        self.x_stabilizers.len() + self.z_stabilizers.len()
+    }
+
+    /// Which data qubits are in the support of some stabiliser or logical operator
+    fn support(&self) -> Vec<bool> {
+        let mut s = vec![false; self.data_qubits];
+        let ops = self.x_stabilizers.iter().chain(&self.z_stabilizers);
+        for &q in ops.chain(&self.logical_x).chain(&self.logical_z).flatten() {
+            s[q] = true;
+        }
+        s
+    }
+
+    /// A memory experiment for the code, with one round of stabiliser measurements
+    pub fn memory(&self) -> MemoryCircuitBuilder {
+        MemoryCircuitBuilder {
+            stages: vec![(self.clone(), 1)],
+            basis: Basis::Z,
+            fresh_ancillas: false,
+            errors: vec![],
+        }
+    }
+}
+
+/// A memory experiment, see [CssCode::memory]
+pub struct MemoryCircuitBuilder {
+    /// The codes whose stabilisers are measured, each for some number of rounds
+    pub stages: Vec<(CssCode, usize)>,
+    pub basis: Basis,
+    /// Use a new ancilla for each measurement instead of resetting them
+    pub fresh_ancillas: bool,
+    /// Pauli errors, as the round before which they happen, the data qubit, and
+    /// the gate [NOT] or [Z]
+    pub errors: Vec<(usize, usize, GType)>,
+}
+
+impl MemoryCircuitBuilder {
+    /// Set the number of rounds of the last code
+    pub fn rounds(&mut self, rounds: usize) -> &mut Self {
+        self.stages.last_mut().unwrap().1 = rounds;
+        self
+    }
+
+    /// Go on to measure the stabilisers of another code on the same data qubits
+    ///
+    /// Data qubits which the new code starts to use are reset to `|0>`, and those it
+    /// stops using are measured in the Z basis.
+    pub fn then(&mut self, code: CssCode, rounds: usize) -> &mut Self {
+        assert_eq!(
+            code.data_qubits, self.stages[0].0.data_qubits,
+            "Codes must have the same data qubits"
+        );
+        self.stages.push((code, rounds));
+        self
+    }
+
+    pub fn basis(&mut self, basis: Basis) -> &mut Self {
+        self.basis = basis;
+        self
+    }
+
+    /// Apply the Pauli `t`, which is [NOT] or [Z], to a data qubit just before the
+    /// given round
+    ///
+    /// Rounds are counted from 0 over all the codes. An error before the round after
+    /// the last one happens just before the data qubits are measured.
+    pub fn error(&mut self, round: usize, qubit: usize, t: GType) -> &mut Self {
+        assert!(t == NOT || t == Z, "Errors must be X or Z");
+        self.errors.push((round, qubit, t));
+        self
+    }
+
+    /// Measure every stabiliser with a new ancilla, so that all measurements are at
+    /// the end of their qubits and the circuit can be turned into a graph
+    pub fn fresh_ancillas(&mut self, fresh_ancillas: bool) -> &mut Self {
+        self.fresh_ancillas = fresh_ancillas;
+        self
+    }
+
+    /// The circuit of the experiment
+    ///
+    /// The data qubits come first, followed by the ancillas. The measurement
+    /// outcomes are written to consecutive classical bits: for each round, the Z
+    /// stabilisers and then the X stabilisers, in order. The data qubits which stop
+    /// being used by a code are measured before the first round of the next code,
+    /// and all remaining data qubits are measured at the end.
+    pub fn build(&self) -> Circuit {
+        let n = self.stages[0].0.data_qubits;
+        let num_ancillas = if self.fresh_ancillas {
+            self.stages
+                .iter()
+                .map(|(code, rounds)| code.num_stabilizers() * rounds)
+                .sum()
+        } else {
+            self.stages
+                .iter()
+                .map(|(code, _)| code.num_stabilizers())
+                .max()
+                .unwrap_or(0)
+        };
+        let mut c = Circuit::new(n + num_ancillas);
+        let mut bit = 0;
+        let mut measure = |c: &mut Circuit, q: usize| {
+            c.push(Gate::new_measure(q, bit));
+            bit += 1;
+        };
+
+        let inject = |c: &mut Circuit, round: usize| {
+            for &(r, q, t) in &self.errors {
+                if r == round {
+                    c.push(Gate::new(t, vec![q]));
+                }
+            }
+        };
+
+        let mut round = 0;
+        let mut used = vec![false; n + num_ancillas];
+        let mut active = vec![false; n];
+        let mut next_ancilla = n;
+        for (i, (code, rounds)) in self.stages.iter().enumerate() {
+            let support = code.support();
+            for q in 0..n {
+                if active[q] && !support[q] {
+                    measure(&mut c, q);
+                } else if !active[q] && support[q] {
+                    if used[q] {
+                        c.push(Gate::new(Reset, vec![q]));
+                    }
+                    if i == 0 && self.basis == Basis::X {
+                        c.push(Gate::new(HAD, vec![q]));
+                    }
+                    used[q] = true;
+                }
+            }
+            active = support;
+
+            for _ in 0..*rounds {
+                inject(&mut c, round);
+                round += 1;
+                let zs = code.z_stabilizers.iter().map(|s| (false, s));
+                let xs = code.x_stabilizers.iter().map(|s| (true, s));
+                for (k, (x_type, stab)) in zs.chain(xs).enumerate() {
+                    let a = if self.fresh_ancillas {
+                        next_ancilla += 1;
+                        next_ancilla - 1
+                    } else {
+                        n + k
+                    };
+                    if used[a] {
+                        c.push(Gate::new(Reset, vec![a]));
+                    }
+                    used[a] = true;
+                    if x_type {
+                        c.push(Gate::new(HAD, vec![a]));
+                        for &q in stab {
+                            c.push(Gate::new(CNOT, vec![a, q]));
+                        }
+                        c.push(Gate::new(HAD, vec![a]));
+                    } else {
+                        for &q in stab {
+                            c.push(Gate::new(CNOT, vec![q, a]));
+                        }
+                    }
+                    measure(&mut c, a);
+                }
+            }
+        }
+
+        inject(&mut c, round);
+        for (q, &a) in active.iter().enumerate() {
+            if a {
+                if self.basis == Basis::X {
+                    c.push(Gate::new(HAD, vec![q]));
+                }
+                measure(&mut c, q);
+            }
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlap(a: &[usize], b: &[usize]) -> usize {
+        a.iter().filter(|q| b.contains(q)).count()
+    }
+
+    /// Check the stabilisers commute, and the logical operators commute with them
+    /// and anticommute in pairs
+    fn check_code(code: &CssCode) {
+        for x in code.x_stabilizers.iter().chain(&code.logical_x) {
+            for z in &code.z_stabilizers {
+                assert_eq!(overlap(x, z) % 2, 0);
+            }
+        }
+        for x in &code.x_stabilizers {
+            for z in &code.logical_z {
+                assert_eq!(overlap(x, z) % 2, 0);
+            }
+        }
+        for (i, x) in code.logical_x.iter().enumerate() {
+            for (j, z) in code.logical_z.iter().enumerate() {
+                assert_eq!(overlap(x, z) % 2, (i == j) as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn surface_codes() {
+        for d in [2, 3, 5] {
+            let code = CssCode::rotated_surface(d);
+            check_code(&code);
+            check_code(&code.dual());
+            assert_eq!(code.num_stabilizers(), d * d - 1);
+            assert_eq!(code.logical_x[0].len(), d);
+
+            let code = CssCode::unrotated_surface(d);
+            check_code(&code);
+            assert_eq!(code.data_qubits, d * d + (d - 1) * (d - 1));
+            assert_eq!(code.num_stabilizers(), code.data_qubits - 1);
+        }
+
+        let code = CssCode::rotated_surface_rect(3, 5);
+        check_code(&code);
+        assert_eq!(code.logical_x[0].len(), 3);
+        assert_eq!(code.logical_z[0].len(), 5);
+    }
+
+    #[test]
+    fn surface_memory() {
+        let code = CssCode::rotated_surface(3);
+        let c = code.memory().fresh_ancillas(true).build();
+        assert_eq!(c.num_qubits(), 17);
+        // the Z stabilisers are deterministic on |0...0>, and the X ones are not
+        assert!((c.probability(&[(9, false)]) - 1.0).abs() < 1e-6);
+        assert!((c.probability(&[(13, false)]) - 0.5).abs() < 1e-6);
+
+        let c = code.memory().rounds(3).basis(Basis::X).build();
+        assert_eq!(c.num_qubits(), 17);
+        assert_eq!(c.num_gates_of_type(Measure), 3 * 8 + 9);
+        assert_eq!(c.num_gates_of_type(Reset), 2 * 8);
+    }
+
+    #[test]
+    fn repetition_code() {
+        let code = CssCode::repetition(3);
+        check_code(&code);
+
+        // a bit flip on the middle qubit before the second round flips both
+        // stabilisers in that round
+        let c = code
+            .memory()
+            .rounds(2)
+            .fresh_ancillas(true)
+            .error(1, 1, NOT)
+            .build();
+        let p = c.probability(&[(3, false), (4, false), (5, true), (6, true)]);
+        assert!((p - 1.0).abs() < 1e-6);
+
+        // with the ancillas reset between rounds, they end up with the outcomes of
+        // the last round
+        let c = code.memory().rounds(2).error(1, 0, NOT).build();
+        assert_eq!(c.num_gates_of_type(Reset), 2);
+        let p = c.probability(&[(0, true), (1, false), (3, true), (4, false)]);
+        assert!((p - 1.0).abs() < 1e-6);
+
+        // phase flips are not detected
+        let c = code.memory().error(0, 2, Z).build();
+        assert!((c.probability(&[(3, false), (4, false)]) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn color_codes() {
+        for d in [3, 5, 7] {
+            let code = CssCode::color_code(d);
+            check_code(&code);
+            assert_eq!(code.data_qubits, (3 * d * d + 1) / 4);
+            assert_eq!(code.x_stabilizers.len(), (code.data_qubits - 1) / 2);
+            assert!(code
+                .x_stabilizers
+                .iter()
+                .all(|f| f.len() == 4 || f.len() == 6));
+            assert_eq!(code.logical_x[0].len(), d);
+        }
+
+        // the Z stabilisers of the Steane code are deterministic on |0...0>
+        let code = CssCode::color_code(3);
+        let c = code.memory().fresh_ancillas(true).build();
+        for q in 7..10 {
+            assert!((c.probability(&[(q, false)]) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn lattice_surgery() {
+        let (pair, merged) = CssCode::rotated_surface_pair(3);
+        check_code(&pair);
+        check_code(&merged);
+        assert_eq!(merged.num_stabilizers(), 20);
+        // the stabilisers of the patches away from the middle are unchanged
+        for s in pair.x_stabilizers.iter().chain(&pair.z_stabilizers) {
+            if s.iter().all(|q| q % 7 != 2 && q % 7 != 4) {
+                assert!(merged.x_stabilizers.contains(s) || merged.z_stabilizers.contains(s));
+            }
+        }
+
+        let c = pair.memory().then(merged, 1).then(pair.clone(), 1).build();
+        // 16 + 20 + 16 stabilisers, the middle column and the other data qubits
+        assert_eq!(c.num_gates_of_type(Measure), 16 + 20 + 16 + 3 + 18);
+    }
+}
@@ -0,0 +1,378 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Import of base profile QIR
+//!
+//! This module is only available with the `qir` feature. It reads the textual form
+//! of an LLVM module (a `.ll` file) following the base profile of QIR, where the
+//! entry point is a straight-line sequence of calls to quantum intrinsics on
+//! statically allocated qubits, given as `null` or `inttoptr (i64 n to ...)`. Both
+//! typed (`%Qubit*`) and opaque (`ptr`) pointers are supported.
+//!
+//! The intrinsics `h`, `x`, `y`, `z`, `s`, `t`, `rx`, `ry`, `rz`, `cnot` (or `cx`),
+//! `cz`, `ccx` and `swap` become gates, along with the adjoints of `s` and `t`.
+//! Rotation angles are rounded to a fraction of `π` with denominator at most 256.
+//! Measurements are ignored, as the base profile doesn't allow any gates on a qubit
+//! after it is measured, and so are calls to the runtime, such as output recording.
+//! The number of qubits is taken from the `required_num_qubits` attribute of the
+//! entry point if it is there.
+//!
+//! ```
+//! # use quizx::qir::decode_circuit;
+//! let qir = r#"
+//! %Qubit = type opaque
+//!
+//! define void @main() #0 {
+//! entry:
+//!   call void @__quantum__qis__h__body(%Qubit* null)
+//!   call void @__quantum__qis__cnot__body(%Qubit* null, %Qubit* inttoptr (i64 1 to %Qubit*))
+//!   ret void
+//! }
+//!
+//! attributes #0 = { "entry_point" "qir_profiles"="base_profile" "required_num_qubits"="2" }
+//! "#;
+//! let c = decode_circuit(qir).unwrap();
+//! assert_eq!(c.num_qubits(), 2);
+//! assert_eq!(c.num_gates(), 2);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::qasm3::angle_to_phase;
+use rustc_hash::FxHashMap;
+use std::path::Path;
+
+/// An error that can occur when reading QIR.
+#[derive(Debug, thiserror::Error)]
+pub enum QirError {
+    /// A line could not be parsed.
+    #[error("line {0}: {1}")]
+    SyntaxError(usize, String),
+    /// An instruction outside the base profile, or an unknown intrinsic.
+    #[error("line {0}: {1} is not supported")]
+    Unsupported(usize, String),
+    /// The module has no entry point.
+    #[error("No entry point found")]
+    NoEntryPoint,
+    /// Error reading a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// A function definition: the line of its header, and its body
+struct Define<'a> {
+    line_no: usize,
+    header: &'a str,
+    body: Vec<(usize, &'a str)>,
+}
+
+/// Splits the arguments of a call at the commas which are not in parentheses
+fn split_args(args: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !args[start..].trim().is_empty() {
+        result.push(args[start..].trim());
+    }
+    result
+}
+
+/// Parses a double constant, either in decimal or as the hex of its bits
+fn parse_double(s: &str) -> Option<f64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(f64::from_bits),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parses a static pointer, `null` or `inttoptr (i64 n to ...)`, as its address
+fn parse_pointer(s: &str) -> Option<usize> {
+    if s.ends_with("null") {
+        return Some(0);
+    }
+    let (_, rest) = s.split_once("inttoptr")?;
+    let rest = rest.trim().strip_prefix('(')?.trim().strip_prefix("i64")?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+/// The gates of an intrinsic, or `None` if it is unknown or has the wrong arguments
+fn intrinsic_gates(name: &str, angles: &[f64], qs: &[usize]) -> Option<Vec<Gate>> {
+    let gates = match (name, angles, qs) {
+        ("h", [], &[q]) => vec![Gate::new(HAD, vec![q])],
+        ("x", [], &[q]) => vec![Gate::new(NOT, vec![q])],
+        ("y", [], &[q]) => vec![Gate::new(Z, vec![q]), Gate::new(NOT, vec![q])],
+        ("z", [], &[q]) => vec![Gate::new(Z, vec![q])],
+        ("s", [], &[q]) => vec![Gate::new(S, vec![q])],
+        ("t", [], &[q]) => vec![Gate::new(T, vec![q])],
+        ("rx", &[a], &[q]) => vec![Gate::new_with_phase(XPhase, vec![q], angle_to_phase(a))],
+        ("ry", &[a], &[q]) => vec![
+            Gate::new(Sdg, vec![q]),
+            Gate::new_with_phase(XPhase, vec![q], angle_to_phase(a)),
+            Gate::new(S, vec![q]),
+        ],
+        ("rz", &[a], &[q]) => vec![Gate::new_with_phase(ZPhase, vec![q], angle_to_phase(a))],
+        ("cnot" | "cx", [], &[_, _]) => vec![Gate::new(CNOT, qs.to_vec())],
+        ("cz", [], &[_, _]) => vec![Gate::new(CZ, qs.to_vec())],
+        ("ccx" | "ccnot", [], &[_, _, _]) => vec![Gate::new(TOFF, qs.to_vec())],
+        ("swap", [], &[_, _]) => vec![Gate::new(SWAP, qs.to_vec())],
+        _ => return None,
+    };
+    Some(gates)
+}
+
+/// Finds the entry point of a module, and the number of qubits it requires
+fn entry_point<'a>(
+    defines: &'a [Define<'a>],
+    attributes: &FxHashMap<&str, &str>,
+) -> Result<(&'a Define<'a>, Option<usize>), QirError> {
+    // the attributes of a function are given inline or as a group #n
+    let attrs = |d: &Define| {
+        let after = d.header.rsplit_once(')').map_or("", |(_, a)| a);
+        let mut s = after.to_string();
+        for word in after.split_whitespace() {
+            if let Some(group) = attributes.get(word) {
+                s += group;
+            }
+        }
+        s
+    };
+    let entry = defines
+        .iter()
+        .find(|d| attrs(d).contains("\"entry_point\""))
+        .or(match defines {
+            [d] => Some(d),
+            _ => None,
+        })
+        .ok_or(QirError::NoEntryPoint)?;
+
+    let a = attrs(entry);
+    let mut num_qubits = None;
+    for key in ["\"required_num_qubits\"=\"", "\"num_required_qubits\"=\""] {
+        if let Some((_, rest)) = a.split_once(key) {
+            let n = rest.split('"').next().unwrap_or("");
+            num_qubits = Some(n.parse().map_err(|_| {
+                QirError::SyntaxError(entry.line_no, format!("number of qubits {}", n))
+            })?);
+        }
+    }
+    Ok((entry, num_qubits))
+}
+
+/// Reads a circuit from the textual form of a base profile QIR module.
+///
+/// See the [module docs](self) for what is supported.
+pub fn decode_circuit(s: &str) -> Result<Circuit, QirError> {
+    let mut defines: Vec<Define> = vec![];
+    let mut attributes: FxHashMap<&str, &str> = FxHashMap::default();
+    let mut current: Option<Define> = None;
+    for (i, line) in s.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split(';').next().unwrap_or("").trim();
+        if let Some(d) = current.as_mut() {
+            if line == "}" {
+                defines.extend(current.take());
+            } else if !line.is_empty() {
+                d.body.push((line_no, line));
+            }
+        } else if line.starts_with("define") {
+            if !line.ends_with('{') {
+                return Err(QirError::SyntaxError(line_no, line.to_string()));
+            }
+            current = Some(Define {
+                line_no,
+                header: line.trim_end_matches('{'),
+                body: vec![],
+            });
+        } else if let Some(rest) = line.strip_prefix("attributes") {
+            if let Some((group, attrs)) = rest.split_once('=') {
+                attributes.insert(group.trim(), attrs);
+            }
+        }
+    }
+    if current.is_some() {
+        return Err(QirError::SyntaxError(s.lines().count(), "missing }".into()));
+    }
+
+    let (entry, num_qubits) = entry_point(&defines, &attributes)?;
+    let mut gates: Vec<Gate> = vec![];
+    let mut measured: Vec<usize> = vec![];
+    for &(line_no, line) in &entry.body {
+        let syntax_error = || QirError::SyntaxError(line_no, line.to_string());
+        let unsupported = |what: &str| QirError::Unsupported(line_no, what.to_string());
+        if line.ends_with(':') || line.starts_with("ret") || line.starts_with("br label") {
+            continue;
+        }
+        let call = line
+            .strip_prefix("call void @")
+            .or_else(|| line.strip_prefix("tail call void @"))
+            .ok_or_else(|| unsupported(line.split_whitespace().next().unwrap_or("")))?;
+        let (name, args) = call.split_once('(').ok_or_else(syntax_error)?;
+        let args = args
+            .rsplit_once(')')
+            .map(|(args, _)| args)
+            .ok_or_else(syntax_error)?;
+        if name.starts_with("__quantum__rt__") {
+            continue;
+        }
+        let name = name
+            .strip_prefix("__quantum__qis__")
+            .ok_or_else(|| unsupported(name))?;
+
+        let mut angles = vec![];
+        let mut qs = vec![];
+        for arg in split_args(args) {
+            if let Some(x) = arg.strip_prefix("double") {
+                angles.push(parse_double(x.trim()).ok_or_else(syntax_error)?);
+            } else if arg.starts_with("ptr") || arg.contains('*') {
+                qs.push(parse_pointer(arg).ok_or_else(|| unsupported(arg))?);
+            } else {
+                return Err(syntax_error());
+            }
+        }
+
+        let (base, suffix) = name.rsplit_once("__").ok_or_else(|| unsupported(name))?;
+        match (base, suffix) {
+            ("mz" | "m" | "measure", "body") => {
+                measured.extend(qs.first());
+                continue;
+            }
+            ("barrier", "body") => continue,
+            (_, "body" | "adj") => {}
+            _ => return Err(unsupported(name)),
+        }
+        if qs.iter().any(|q| measured.contains(q)) {
+            return Err(unsupported("a gate after measurement"));
+        }
+        let mut gs = intrinsic_gates(base, &angles, &qs).ok_or_else(|| unsupported(name))?;
+        if suffix == "adj" {
+            gs.reverse();
+            gs.iter_mut().for_each(Gate::adjoint);
+        }
+        gates.extend(gs);
+    }
+
+    let max_qubit = gates.iter().flat_map(|g| g.qs.iter()).max();
+    let num_qubits = num_qubits.unwrap_or(max_qubit.map_or(0, |&q| q + 1));
+    if max_qubit.is_some_and(|&q| q >= num_qubits) {
+        return Err(QirError::SyntaxError(
+            entry.line_no,
+            "more qubits than required_num_qubits".into(),
+        ));
+    }
+    let mut c = Circuit::new(num_qubits);
+    for g in gates {
+        c.push(g);
+    }
+    Ok(c)
+}
+
+/// Reads a circuit from a `.ll` file containing a base profile QIR module.
+pub fn read_circuit(filename: &Path) -> Result<Circuit, QirError> {
+    let s = std::fs::read_to_string(filename)?;
+    decode_circuit(&s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+
+    #[test]
+    fn qir_gates() {
+        let qir = r#"
+            ; ModuleID = 'bell'
+            source_filename = "bell"
+
+            define void @helper() {
+              ret void
+            }
+
+            define void @ENTRYPOINT__main() #0 {
+            block_0:
+              call void @__quantum__rt__initialize(ptr null)
+              br label %block_1
+            block_1:
+              call void @__quantum__qis__h__body(ptr null)
+              call void @__quantum__qis__rz__body(double 0x3FE921FB54442D18, ptr inttoptr (i64 2 to ptr))
+              call void @__quantum__qis__cx__body(ptr null, ptr inttoptr (i64 2 to ptr))
+              call void @__quantum__qis__t__adj(ptr inttoptr (i64 1 to ptr))
+              call void @__quantum__qis__rx__body(double -1.570796e+00, ptr null)
+              call void @__quantum__qis__ccx__body(ptr null, ptr inttoptr (i64 1 to ptr), ptr inttoptr (i64 2 to ptr))
+              call void @__quantum__qis__mz__body(ptr null, ptr writeonly null)
+              call void @__quantum__qis__y__body(ptr inttoptr (i64 1 to ptr))
+              call void @__quantum__rt__result_record_output(ptr null, ptr null)
+              ret void
+            }
+
+            declare void @__quantum__qis__h__body(ptr)
+
+            attributes #0 = { "entry_point" "output_labeling_schema" "qir_profiles"="base_profile" "required_num_qubits"="4" "required_num_results"="1" }
+        "#;
+        let c = decode_circuit(qir).unwrap();
+        assert_eq!(c.num_qubits(), 4);
+
+        let qasm = "qreg q[4];
+            h q[0];
+            rz(pi/4) q[2];
+            cx q[0], q[2];
+            tdg q[1];
+            rx(-pi/2) q[0];
+            ccx q[0], q[1], q[2];
+            y q[1];";
+        let c1 = Circuit::from_qasm(qasm).unwrap();
+        assert_eq!(c.num_gates_of_type(Tdg), 1);
+        assert!(Tensor4::scalar_compare(&c, &c1));
+    }
+
+    #[test]
+    fn qir_errors() {
+        let entry = |body: &str| {
+            format!(
+                "define void @main() #0 {{\n{}\nret void\n}}\nattributes #0 = {{ \"entry_point\" }}",
+                body
+            )
+        };
+        for body in [
+            "call void @__quantum__qis__h__body(%Qubit* null, %Qubit* null)",
+            "call void @__quantum__qis__u3__body(%Qubit* null)",
+            "call void @__quantum__qis__reset__body(%Qubit* null)",
+            "%q = call %Qubit* @__quantum__rt__qubit_allocate()",
+            "call void @__quantum__qis__h__body(%Qubit* %q)",
+            "call void @__quantum__qis__mz__body(%Qubit* null, %Result* null)\n\
+             call void @__quantum__qis__h__body(%Qubit* null)",
+            "br i1 %0, label %then, label %else",
+        ] {
+            assert!(decode_circuit(&entry(body)).is_err(), "{}", body);
+        }
+
+        let two_entries = "define void @a() {\nret void\n}\ndefine void @b() {\nret void\n}";
+        assert!(matches!(
+            decode_circuit(two_entries),
+            Err(QirError::NoEntryPoint)
+        ));
+    }
+}
@@ -0,0 +1,657 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading and writing Quil programs
+//!
+//! A Quil program is read as a list of gates on the qubits it mentions, where qubit
+//! `n` of the program is qubit `n` of the circuit. The standard gates `I`, `X`, `Y`,
+//! `Z`, `H`, `S`, `T`, `RX`, `RY`, `RZ`, `PHASE`, `CNOT`, `CZ`, `CCNOT`, `SWAP` and
+//! `CPHASE` are supported, along with the modifier `DAGGER`, and `CONTROLLED` on
+//! `X`, `Z` and their controlled versions. Parameters are expressions in `pi`,
+//! which are rounded to a fraction of `π` with denominator at most 256. `PRAGMA`,
+//! `NOP` and `HALT` are ignored, and [Classical] says what to do with `DECLARE` and
+//! `MEASURE`. Gate definitions and control flow are not supported.
+//!
+//! Circuits are written either with the standard gates, or with Rigetti's native
+//! gates `RZ`, `RX(±pi/2)`, `RX(pi)` and `CZ`. Barriers are dropped.
+//!
+//! ```
+//! # use quizx::quil::{decode_circuit, encode_circuit, Classical};
+//! let quil = "
+//! DECLARE ro BIT[2]
+//! H 0
+//! CNOT 0 1
+//! RZ(-pi/4) 1
+//! MEASURE 0 ro[0]
+//! MEASURE 1 ro[1]
+//! ";
+//! let c = decode_circuit(quil, Classical::Terminal).unwrap();
+//! assert_eq!(c.num_gates(), 3);
+//! let native = encode_circuit(&c, true).unwrap();
+//! assert!(native.contains("CZ 0 1"));
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::phase::Phase;
+use crate::qasm3::{angle_to_phase, fmt_angle};
+use std::f64::consts::PI;
+use std::fmt::Write;
+use std::path::Path;
+
+/// An error that can occur when reading or writing Quil.
+#[derive(Debug, thiserror::Error)]
+pub enum QuilError {
+    /// A line could not be parsed.
+    #[error("line {0}: {1}")]
+    SyntaxError(usize, String),
+    /// An instruction with no counterpart in a [Circuit].
+    #[error("line {0}: {1} is not supported")]
+    Unsupported(usize, String),
+    /// A gate which cannot be written in Quil.
+    #[error("Gate {0} cannot be written in Quil")]
+    UnsupportedGate(String),
+    /// Error reading or writing a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// What to do with the classical instructions `DECLARE` and `MEASURE`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Classical {
+    /// Ignore them.
+    Skip,
+    /// Ignore them, but fail if a measured qubit is used again, as the circuit
+    /// would then differ from the program.
+    #[default]
+    Terminal,
+    /// Fail on any classical instruction.
+    Reject,
+}
+
+/// Evaluation of parameter expressions, with `pi`, `+`, `-`, `*`, `/`, `^` and
+/// the functions `sin`, `cos`, `sqrt` and `exp`
+struct Expr<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Expr<'a> {
+    fn eval(s: &'a str) -> Option<f64> {
+        let mut e = Expr {
+            s: s.as_bytes(),
+            pos: 0,
+        };
+        let x = e.sum()?;
+        e.skip_space();
+        (e.pos == e.s.len()).then_some(x)
+    }
+
+    fn skip_space(&mut self) {
+        while self
+            .s
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_space();
+        if self.s.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Option<f64> {
+        let mut x = self.product()?;
+        loop {
+            if self.eat(b'+') {
+                x += self.product()?;
+            } else if self.eat(b'-') {
+                x -= self.product()?;
+            } else {
+                return Some(x);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Option<f64> {
+        let mut x = self.power()?;
+        loop {
+            if self.eat(b'*') {
+                x *= self.power()?;
+            } else if self.eat(b'/') {
+                x /= self.power()?;
+            } else {
+                return Some(x);
+            }
+        }
+    }
+
+    fn power(&mut self) -> Option<f64> {
+        let x = self.unary()?;
+        if self.eat(b'^') {
+            Some(x.powf(self.power()?))
+        } else {
+            Some(x)
+        }
+    }
+
+    fn unary(&mut self) -> Option<f64> {
+        if self.eat(b'-') {
+            Some(-self.unary()?)
+        } else if self.eat(b'+') {
+            self.unary()
+        } else {
+            self.atom()
+        }
+    }
+
+    fn atom(&mut self) -> Option<f64> {
+        if self.eat(b'(') {
+            let x = self.sum()?;
+            return self.eat(b')').then_some(x);
+        }
+        let start = self.pos;
+        let c = *self.s.get(self.pos)?;
+        if c.is_ascii_alphabetic() {
+            while self
+                .s
+                .get(self.pos)
+                .is_some_and(|c| c.is_ascii_alphanumeric())
+            {
+                self.pos += 1;
+            }
+            let name = std::str::from_utf8(&self.s[start..self.pos]).ok()?;
+            if name == "pi" {
+                return Some(PI);
+            }
+            let f: fn(f64) -> f64 = match name {
+                "sin" => f64::sin,
+                "cos" => f64::cos,
+                "sqrt" => f64::sqrt,
+                "exp" => f64::exp,
+                _ => return None,
+            };
+            if !self.eat(b'(') {
+                return None;
+            }
+            let x = self.sum()?;
+            return self.eat(b')').then_some(f(x));
+        }
+        while let Some(&c) = self.s.get(self.pos) {
+            let exponent_sign =
+                (c == b'+' || c == b'-') && matches!(self.s[self.pos - 1], b'e' | b'E');
+            if c.is_ascii_digit() || c == b'.' || c == b'e' || c == b'E' || exponent_sign {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        std::str::from_utf8(&self.s[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+}
+
+/// Splits a list of parameters at the commas which are not in parentheses
+fn split_params(params: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(&params[start..]);
+    result
+}
+
+/// The gates of a Quil gate with the given number of extra controls, or `None` if
+/// it is unknown or has the wrong number of parameters or qubits
+fn quil_gates(name: &str, controls: usize, ps: &[Phase], qs: &[usize]) -> Option<Vec<Gate>> {
+    // X and Z gates with any number of controls, up to two
+    let (x_controls, z_controls) = match name {
+        "X" => (Some(controls), None),
+        "CNOT" => (Some(controls + 1), None),
+        "CCNOT" => (Some(controls + 2), None),
+        "Z" => (None, Some(controls)),
+        "CZ" => (None, Some(controls + 1)),
+        _ => (None, None),
+    };
+    let t = match (x_controls, z_controls) {
+        (Some(0), _) => Some(NOT),
+        (Some(1), _) => Some(CNOT),
+        (Some(2), _) => Some(TOFF),
+        (_, Some(0)) => Some(Z),
+        (_, Some(1)) => Some(CZ),
+        (_, Some(2)) => Some(CCZ),
+        _ => None,
+    };
+    if let Some(t) = t {
+        return (ps.is_empty() && Some(qs.len()) == t.num_qubits())
+            .then(|| vec![Gate::new(t, qs.to_vec())]);
+    }
+
+    // PHASE with one control is CPHASE
+    let name = match (name, controls) {
+        ("PHASE", 1) => "CPHASE",
+        (_, 0) => name,
+        _ => return None,
+    };
+    let gates = match (name, ps, qs) {
+        ("I", [], &[_]) => vec![],
+        ("Y", [], &[q]) => vec![Gate::new(Z, vec![q]), Gate::new(NOT, vec![q])],
+        ("H", [], &[q]) => vec![Gate::new(HAD, vec![q])],
+        ("S", [], &[q]) => vec![Gate::new(S, vec![q])],
+        ("T", [], &[q]) => vec![Gate::new(T, vec![q])],
+        ("RX", &[p], &[q]) => vec![Gate::new_with_phase(XPhase, vec![q], p)],
+        ("RY", &[p], &[q]) => vec![
+            Gate::new(Sdg, vec![q]),
+            Gate::new_with_phase(XPhase, vec![q], p),
+            Gate::new(S, vec![q]),
+        ],
+        ("RZ" | "PHASE", &[p], &[q]) => vec![Gate::new_with_phase(ZPhase, vec![q], p)],
+        ("SWAP", [], &[_, _]) => vec![Gate::new(SWAP, qs.to_vec())],
+        ("CPHASE", &[p], &[c, t]) => {
+            // a controlled phase is a phase polynomial on the two qubits
+            vec![
+                Gate::new_with_phase(ZPhase, vec![c], p / 2),
+                Gate::new_with_phase(ZPhase, vec![t], p / 2),
+                Gate::new(CNOT, vec![c, t]),
+                Gate::new_with_phase(ZPhase, vec![t], -p / 2),
+                Gate::new(CNOT, vec![c, t]),
+            ]
+        }
+        _ => return None,
+    };
+    Some(gates)
+}
+
+/// Reads a circuit from a Quil program.
+///
+/// See the [module docs](self) for what is supported.
+pub fn decode_circuit(s: &str, classical: Classical) -> Result<Circuit, QuilError> {
+    let mut gates: Vec<Gate> = vec![];
+    let mut measured: Vec<usize> = vec![];
+    for (i, line) in s.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let syntax_error = || QuilError::SyntaxError(line_no, line.to_string());
+        let unsupported = |what: &str| QuilError::Unsupported(line_no, what.to_string());
+        let keyword = line.split_whitespace().next().unwrap_or("");
+        match keyword {
+            "" | "PRAGMA" | "NOP" | "HALT" => continue,
+            "DECLARE" | "MEASURE" if classical == Classical::Reject => {
+                return Err(unsupported(keyword))
+            }
+            "DECLARE" => continue,
+            "MEASURE" => {
+                let q = line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|q| q.parse().ok())
+                    .ok_or_else(syntax_error)?;
+                measured.push(q);
+                continue;
+            }
+            _ => {}
+        }
+
+        // modifiers, then the name of the gate and its parameters
+        let mut rest = line;
+        let mut dagger = false;
+        let mut controls = 0;
+        let name = loop {
+            let (word, r) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            rest = r.trim_start();
+            match word {
+                "DAGGER" => dagger = !dagger,
+                "CONTROLLED" => controls += 1,
+                _ => break word,
+            }
+        };
+        let (name, params) = match name.split_once('(') {
+            Some((name, p)) => {
+                // the parameters may contain spaces, so find the closing bracket
+                let p = format!("{} {}", p, rest);
+                let (params, r) = p.rsplit_once(')').ok_or_else(syntax_error)?;
+                rest = &line[line.len() - r.len()..];
+                (name, Some(params.to_string()))
+            }
+            None => (name, None),
+        };
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || name.is_empty() {
+            return Err(syntax_error());
+        }
+
+        let mut ps = vec![];
+        for p in params.iter().flat_map(|p| split_params(p)) {
+            let x = Expr::eval(p).ok_or_else(|| unsupported(p.trim()))?;
+            ps.push(angle_to_phase(x));
+        }
+        let mut qs = vec![];
+        for q in rest.split_whitespace() {
+            let q: usize = q.parse().map_err(|_| unsupported(q))?;
+            if qs.contains(&q) {
+                return Err(syntax_error());
+            }
+            if classical == Classical::Terminal && measured.contains(&q) {
+                return Err(unsupported("a gate after MEASURE"));
+            }
+            qs.push(q);
+        }
+
+        let mut gs = quil_gates(name, controls, &ps, &qs).ok_or_else(|| unsupported(name))?;
+        if dagger {
+            gs.reverse();
+            gs.iter_mut().for_each(Gate::adjoint);
+        }
+        gates.extend(gs);
+    }
+
+    let num_qubits = gates
+        .iter()
+        .flat_map(|g| g.qs.iter())
+        .max()
+        .map_or(0, |&q| q + 1);
+    let mut c = Circuit::new(num_qubits);
+    for g in gates {
+        c.push(g);
+    }
+    Ok(c)
+}
+
+/// Reads a circuit from a file containing a Quil program.
+pub fn read_circuit(filename: &Path, classical: Classical) -> Result<Circuit, QuilError> {
+    let s = std::fs::read_to_string(filename)?;
+    decode_circuit(&s, classical)
+}
+
+/// Pushes the native gates RZ, RX(±π/2), RX(π) and CZ making up a gate
+fn push_native_gates(g: &Gate, gates: &mut Vec<Gate>) -> Result<(), QuilError> {
+    let z = |q: usize, p: Phase| Gate::new_with_phase(ZPhase, vec![q], p);
+    let x = |q: usize, p: Phase| Gate::new_with_phase(XPhase, vec![q], p);
+    let half = Phase::from((1, 2));
+    let mut c = Circuit::new(0);
+    match g.t {
+        ZPhase | XPhase if g.phase == Phase::new(0) => {}
+        ZPhase => gates.push(g.clone()),
+        Z => gates.push(z(g.qs[0], Phase::new(1))),
+        S => gates.push(z(g.qs[0], half)),
+        Sdg => gates.push(z(g.qs[0], -half)),
+        T => gates.push(z(g.qs[0], Phase::from((1, 4)))),
+        Tdg => gates.push(z(g.qs[0], Phase::from((-1, 4)))),
+        NOT => gates.push(x(g.qs[0], Phase::new(1))),
+        XPhase if g.phase == half || g.phase == -half || g.phase == Phase::new(1) => {
+            gates.push(g.clone())
+        }
+        HAD => {
+            gates.push(z(g.qs[0], half));
+            gates.push(x(g.qs[0], half));
+            gates.push(z(g.qs[0], half));
+        }
+        CZ => gates.push(g.clone()),
+        XPhase => {
+            c.push(Gate::new(HAD, g.qs.clone()));
+            c.push(z(g.qs[0], g.phase));
+            c.push(Gate::new(HAD, g.qs.clone()));
+        }
+        CNOT => {
+            c.push(Gate::new(HAD, vec![g.qs[1]]));
+            c.push(Gate::new(CZ, g.qs.clone()));
+            c.push(Gate::new(HAD, vec![g.qs[1]]));
+        }
+        XCX => {
+            c.push(Gate::new(HAD, vec![g.qs[0]]));
+            c.push(Gate::new(CNOT, g.qs.clone()));
+            c.push(Gate::new(HAD, vec![g.qs[0]]));
+        }
+        SWAP => {
+            c.push(Gate::new(CNOT, vec![g.qs[0], g.qs[1]]));
+            c.push(Gate::new(CNOT, vec![g.qs[1], g.qs[0]]));
+            c.push(Gate::new(CNOT, vec![g.qs[0], g.qs[1]]));
+        }
+        TOFF | CCZ | ParityPhase => g.push_basic_gates(&mut c),
+        Barrier => {}
+        InitAncilla | PostSelect | Measure | Reset | UnknownGate => {
+            return Err(QuilError::UnsupportedGate(g.qasm_name().to_string()))
+        }
+    }
+    for h in &c.gates {
+        push_native_gates(h, gates)?;
+    }
+    Ok(())
+}
+
+fn write_gate(s: &mut String, g: &Gate) -> Result<(), QuilError> {
+    let qs: Vec<String> = g.qs.iter().map(|q| q.to_string()).collect();
+    let qs = qs.join(" ");
+    let name = match g.t {
+        NOT => "X",
+        Z => "Z",
+        S => "S",
+        Sdg => "DAGGER S",
+        T => "T",
+        Tdg => "DAGGER T",
+        HAD => "H",
+        CNOT => "CNOT",
+        CZ => "CZ",
+        SWAP => "SWAP",
+        TOFF => "CCNOT",
+        CCZ => "CONTROLLED CZ",
+        ZPhase | XPhase => {
+            let name = if g.t == ZPhase { "RZ" } else { "RX" };
+            writeln!(s, "{}({}) {}", name, fmt_angle(g.phase), qs).unwrap();
+            return Ok(());
+        }
+        XCX | ParityPhase => {
+            let mut c = Circuit::new(0);
+            if g.t == XCX {
+                c.push(Gate::new(HAD, vec![g.qs[0]]));
+                c.push(Gate::new(CNOT, g.qs.clone()));
+                c.push(Gate::new(HAD, vec![g.qs[0]]));
+            } else {
+                g.push_basic_gates(&mut c);
+            }
+            for h in &c.gates {
+                write_gate(s, h)?;
+            }
+            return Ok(());
+        }
+        Barrier => return Ok(()),
+        InitAncilla | PostSelect | Measure | Reset | UnknownGate => {
+            return Err(QuilError::UnsupportedGate(g.qasm_name().to_string()))
+        }
+    };
+    writeln!(s, "{} {}", name, qs).unwrap();
+    Ok(())
+}
+
+/// Returns a circuit as a Quil program.
+///
+/// If `native` is true, only the native gates `RZ`, `RX(±pi/2)`, `RX(pi)` and `CZ`
+/// are used. Fails on the pyzx-specific gates `init_anc` and `post_sel`.
+pub fn encode_circuit(c: &Circuit, native: bool) -> Result<String, QuilError> {
+    let mut s = String::new();
+    for g in &c.gates {
+        if native {
+            let mut gates = vec![];
+            push_native_gates(g, &mut gates)?;
+            for h in &gates {
+                write_gate(&mut s, h)?;
+            }
+        } else {
+            write_gate(&mut s, g)?;
+        }
+    }
+    Ok(s)
+}
+
+/// Writes a circuit as a Quil program to a file.
+pub fn write_circuit(c: &Circuit, filename: &Path, native: bool) -> Result<(), QuilError> {
+    std::fs::write(filename, encode_circuit(c, native)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+
+    /// Check two circuits are equal up to a global phase
+    ///
+    /// Quil's phases need not be multiples of π/4, so this compares the tensors
+    /// numerically.
+    fn approx_scalar_compare(c0: &Circuit, c1: &Circuit) -> bool {
+        let t0 = c0.to_tensorf();
+        let t1 = c1.to_tensorf();
+        let (i, x) = t0
+            .indexed_iter()
+            .max_by(|a, b| a.1.norm().total_cmp(&b.1.norm()))
+            .unwrap();
+        let r = t1[&i] / x;
+        (r.norm() - 1.0).abs() < 1e-6
+            && t0
+                .iter()
+                .zip(t1.iter())
+                .all(|(x, y)| (x * r - y).norm() < 1e-6)
+    }
+
+    #[test]
+    fn quil_roundtrip() {
+        let mut c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(40)
+            .clifford_t(0.2)
+            .build();
+        c.push(Gate::new(CCZ, vec![0, 3, 1]));
+        c.push(Gate::new(TOFF, vec![2, 4, 0]));
+        c.push(Gate::new(SWAP, vec![1, 2]));
+        c.push(Gate::new(Sdg, vec![3]));
+        c.push(Gate::new(Tdg, vec![4]));
+        c.push(Gate::new_with_phase(XPhase, vec![1], Phase::from((-2, 3))));
+        c.push(Gate::new_with_phase(ZPhase, vec![1], Phase::from((1, 8))));
+        let quil = encode_circuit(&c, false).unwrap();
+        assert_eq!(decode_circuit(&quil, Classical::Reject).unwrap(), c);
+
+        let native = encode_circuit(&c, true).unwrap();
+        for line in native.lines() {
+            assert!(
+                line.starts_with("RZ(")
+                    || line.starts_with("CZ ")
+                    || ["RX(pi/2) ", "RX(-pi/2) ", "RX(pi) "]
+                        .iter()
+                        .any(|p| line.starts_with(p)),
+                "{}",
+                line
+            );
+        }
+        let c1 = decode_circuit(&native, Classical::Reject).unwrap();
+        assert!(approx_scalar_compare(&c, &c1));
+
+        let mut c = Circuit::new(1);
+        c.push(Gate::new(PostSelect, vec![0]));
+        assert!(encode_circuit(&c, false).is_err());
+    }
+
+    #[test]
+    fn quil_gates() {
+        let quil = "
+            # a comment
+            PRAGMA INITIAL_REWIRING \"PARTIAL\"
+            RX( pi / 2 ) 0
+            RY(-0.5*pi) 1
+            PHASE(2*pi/3) 2   # another comment
+            DAGGER CPHASE(pi/4) 0 2
+            CONTROLLED CONTROLLED X 0 1 2
+            CONTROLLED PHASE(pi/2) 1 2
+            DAGGER DAGGER S 0
+            Y 1
+            I 2
+        ";
+        let c = decode_circuit(quil, Classical::Reject).unwrap();
+        assert_eq!(c.num_qubits(), 3);
+        assert_eq!(c.num_gates_of_type(TOFF), 1);
+        assert_eq!(c.num_gates_of_type(S), 2);
+
+        let qasm = "qreg q[3];
+            rx(pi/2) q[0];
+            ry(-pi/2) q[1];
+            u1(2*pi/3) q[2];
+            cu1(-pi/4) q[0], q[2];
+            ccx q[0], q[1], q[2];
+            cu1(pi/2) q[1], q[2];
+            s q[0];
+            y q[1];";
+        let c1 = Circuit::from_qasm(qasm).unwrap();
+        assert!(approx_scalar_compare(&c, &c1));
+    }
+
+    #[test]
+    fn quil_classical() {
+        let quil = "
+            DECLARE ro BIT[2]
+            H 0
+            MEASURE 0 ro[0]
+            CNOT 1 2
+            MEASURE 1
+        ";
+        let c = decode_circuit(quil, Classical::Terminal).unwrap();
+        assert_eq!(c.num_gates(), 2);
+        assert_eq!(decode_circuit(quil, Classical::Skip).unwrap(), c);
+        assert!(decode_circuit(quil, Classical::Reject).is_err());
+
+        let quil = "MEASURE 0 ro[0]\nX 0";
+        assert!(decode_circuit(quil, Classical::Terminal).is_err());
+        assert_eq!(
+            decode_circuit(quil, Classical::Skip).unwrap().num_gates(),
+            1
+        );
+    }
+
+    #[test]
+    fn quil_errors() {
+        for quil in [
+            "DEFGATE FOO:\n    1, 0\n    0, 1",
+            "LABEL @start",
+            "RX(theta) 0",
+            "RX(pi/2 0",
+            "RX 0",
+            "H 0 1",
+            "CNOT 0 0",
+            "H q",
+            "CONTROLLED H 0 1",
+            "RESET",
+        ] {
+            assert!(decode_circuit(quil, Classical::Skip).is_err(), "{}", quil);
+        }
+    }
+}
@@ -0,0 +1,394 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Quipper ASCII import
+//!
+//! Reads circuits printed by Quipper's `print_generic ASCII`, as in the benchmarks
+//! of Amy, Maslov and Mosca. Each line is a gate, like
+//!
+//! ```text
+//! QGate["not"](2) with controls=[+0,-1] with nocontrol
+//! QGate["T"]*(0) with nocontrol
+//! QRot["exp(-i%Z)",0.39269908169872414](1) with nocontrol
+//! ```
+//!
+//! where `*` marks an inverse and `-` a control on `|0>`. The gates `not`, `X`,
+//! `Z`, `H`, `S`, `T`, `Y`, `V`, `swap` and `omega` are supported, with as many
+//! controls as there are gates in a [Circuit] for, e.g. up to two for `not` and `Z`.
+//! The rotations `exp(-i%Z)` and `R(2pi/%)` become phase gates. `QInit0` and `QTerm0`
+//! become `init_anc` and `post_sel`, and a wire which is initialised again after
+//! being terminated gets a fresh qubit. Only global phases are dropped.
+//!
+//! ```
+//! # use quizx::quipper::decode_circuit;
+//! let c = decode_circuit(
+//!     r#"
+//! Inputs: 0:Qbit, 1:Qbit, 2:Qbit
+//! QGate["H"](2) with nocontrol
+//! QGate["not"](2) with controls=[+0,+1] with nocontrol
+//! QGate["T"]*(1) with nocontrol
+//! Outputs: 0:Qbit, 1:Qbit, 2:Qbit
+//! "#,
+//! )
+//! .unwrap();
+//! assert_eq!(c.num_qubits(), 3);
+//! assert_eq!(c.num_gates(), 3);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::qasm3::angle_to_phase;
+use num::Rational64;
+use rustc_hash::FxHashMap;
+use std::f64::consts::PI;
+use std::path::Path;
+
+/// An error that can occur when reading a Quipper circuit.
+#[derive(Debug, thiserror::Error)]
+pub enum QuipperError {
+    /// A line could not be parsed.
+    #[error("line {0}: {1}")]
+    SyntaxError(usize, String),
+    /// A gate or feature with no counterpart in a [Circuit].
+    #[error("line {0}: {1} is not supported")]
+    Unsupported(usize, String),
+    /// Error reading a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// One gate line, before mapping wires to qubits
+struct QGate<'a> {
+    name: &'a str,
+    angle: Option<f64>,
+    inverse: bool,
+    targets: Vec<usize>,
+    /// the control wires, and whether each is a positive control
+    controls: Vec<(usize, bool)>,
+}
+
+fn parse_wires(s: &str) -> Option<Vec<usize>> {
+    if s.trim().is_empty() {
+        return Some(vec![]);
+    }
+    s.split(',').map(|w| w.trim().parse().ok()).collect()
+}
+
+/// Parses a line of the form `Kind["name",angle]*(targets) with controls=[...] ...`
+/// where the name, angle, inverse marker and controls are all optional
+fn parse_gate(line: &str) -> Option<QGate<'_>> {
+    // the name may contain parentheses, so look for the targets after it
+    let name_end = match line.find('[') {
+        Some(i) => i + line[i..].find(']')?,
+        None => 0,
+    };
+    let open = name_end + line[name_end..].find('(')?;
+    let (head, rest) = (&line[..open], &line[open + 1..]);
+    let (targets, rest) = rest.split_once(')')?;
+    let (kind_and_name, inverse) = match head.strip_suffix('*') {
+        Some(h) => (h, true),
+        None => (head, false),
+    };
+    let (name, angle) = match kind_and_name.split_once('[') {
+        Some((_, args)) => {
+            let args = args.strip_suffix(']')?;
+            let (name, angle) = match args.rsplit_once(',') {
+                Some((name, angle)) if !angle.ends_with('"') => {
+                    (name, Some(angle.trim().parse().ok()?))
+                }
+                _ => (args, None),
+            };
+            (name.trim().strip_prefix('"')?.strip_suffix('"')?, angle)
+        }
+        None => ("", None),
+    };
+
+    let controls = match rest.split_once("controls=[") {
+        Some((_, cs)) => {
+            let (cs, _) = cs.split_once(']')?;
+            cs.split(',')
+                .filter(|c| !c.trim().is_empty())
+                .map(|c| {
+                    let c = c.trim();
+                    let positive = !c.starts_with('-');
+                    let w = c.trim_start_matches(['+', '-']).parse().ok()?;
+                    Some((w, positive))
+                })
+                .collect::<Option<Vec<_>>>()?
+        }
+        None => vec![],
+    };
+
+    Some(QGate {
+        name,
+        angle,
+        inverse,
+        targets: parse_wires(targets)?,
+        controls,
+    })
+}
+
+/// The gate types of a Quipper gate, depending on its number of controls
+fn gate_type(name: &str, inverse: bool, ncontrols: usize) -> Option<GType> {
+    match (name, ncontrols) {
+        ("not" | "X", 0) => Some(NOT),
+        ("not" | "X", 1) => Some(CNOT),
+        ("not" | "X", 2) => Some(TOFF),
+        ("Z", 0) => Some(Z),
+        ("Z", 1) => Some(CZ),
+        ("Z", 2) => Some(CCZ),
+        ("H", 0) => Some(HAD),
+        ("S", 0) => Some(if inverse { Sdg } else { S }),
+        ("T", 0) => Some(if inverse { Tdg } else { T }),
+        ("swap", 0) => Some(SWAP),
+        _ => None,
+    }
+}
+
+/// Keeps track of which qubit each live wire is on
+#[derive(Default)]
+struct Wires {
+    live: FxHashMap<usize, usize>,
+    num_qubits: usize,
+}
+
+impl Wires {
+    fn qubit(&mut self, w: usize) -> usize {
+        let n = &mut self.num_qubits;
+        *self.live.entry(w).or_insert_with(|| {
+            *n += 1;
+            *n - 1
+        })
+    }
+
+    fn fresh(&mut self, w: usize) -> usize {
+        self.live.remove(&w);
+        self.qubit(w)
+    }
+}
+
+/// Reads a circuit from Quipper's ASCII format.
+///
+/// See the [module docs](self) for what is supported.
+pub fn decode_circuit(s: &str) -> Result<Circuit, QuipperError> {
+    let mut wires = Wires::default();
+    let mut gates = vec![];
+
+    for (i, line) in s.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        let unsupported = |what: &str| QuipperError::Unsupported(line_no, what.to_string());
+        let syntax = || QuipperError::SyntaxError(line_no, line.to_string());
+
+        if line.is_empty() || line.starts_with("Comment") {
+            continue;
+        }
+        if let Some(ws) = line.strip_prefix("Inputs:") {
+            for w in ws.split(',').filter(|w| !w.trim().is_empty()) {
+                let (w, ty) = w.split_once(':').ok_or_else(syntax)?;
+                if ty.trim() != "Qbit" {
+                    return Err(unsupported("classical wires"));
+                }
+                wires.qubit(w.trim().parse().map_err(|_| syntax())?);
+            }
+            continue;
+        }
+        if line.starts_with("Outputs:") {
+            continue;
+        }
+
+        let g = parse_gate(line).ok_or_else(syntax)?;
+        if line.starts_with("QInit") || line.starts_with("QTerm") {
+            let [w] = g.targets[..] else {
+                return Err(syntax());
+            };
+            let one = line[5..].starts_with('1');
+            if !g.controls.is_empty() || !(one || line[5..].starts_with('0')) {
+                return Err(unsupported(line));
+            }
+            if line.starts_with("QInit") {
+                let q = wires.fresh(w);
+                gates.push(Gate::new(InitAncilla, vec![q]));
+                if one {
+                    gates.push(Gate::new(NOT, vec![q]));
+                }
+            } else {
+                let q = wires.qubit(w);
+                if one {
+                    gates.push(Gate::new(NOT, vec![q]));
+                }
+                gates.push(Gate::new(PostSelect, vec![q]));
+                wires.live.remove(&w);
+            }
+            continue;
+        }
+        if !line.starts_with("QGate") && !line.starts_with("QRot") {
+            return Err(unsupported(line.split(['[', '(']).next().unwrap_or(line)));
+        }
+
+        let ts: Vec<usize> = g.targets.iter().map(|&w| wires.qubit(w)).collect();
+        let cs: Vec<(usize, bool)> = g
+            .controls
+            .iter()
+            .map(|&(w, positive)| (wires.qubit(w), positive))
+            .collect();
+        let mut qs: Vec<usize> = cs.iter().map(|&(q, _)| q).collect();
+        qs.extend_from_slice(&ts);
+        if (1..qs.len()).any(|j| qs[..j].contains(&qs[j])) {
+            return Err(syntax());
+        }
+
+        let sign = if g.inverse { -1 } else { 1 };
+        let body: Vec<Gate> = match (line.starts_with("QRot"), g.name, &ts[..], &cs[..]) {
+            (true, "exp(-i%Z)", &[t], []) => {
+                let angle = g.angle.ok_or_else(syntax)?;
+                vec![Gate::new_with_phase(
+                    ZPhase,
+                    vec![t],
+                    angle_to_phase(2.0 * angle * sign as f64),
+                )]
+            }
+            (true, "R(2pi/%)", &[t], []) => {
+                let n = g.angle.ok_or_else(syntax)?;
+                vec![Gate::new_with_phase(
+                    ZPhase,
+                    vec![t],
+                    angle_to_phase(2.0 * PI / n * sign as f64),
+                )]
+            }
+            (true, name, _, _) => return Err(unsupported(&format!("rotation {}", name))),
+            (false, "omega", _, []) => vec![],
+            (false, "omega", _, &[(c, _)]) => {
+                vec![Gate::new(if g.inverse { Tdg } else { T }, vec![c])]
+            }
+            (false, "Y", &[t], []) => vec![Gate::new(Z, vec![t]), Gate::new(NOT, vec![t])],
+            (false, "V", &[t], []) => vec![Gate::new_with_phase(
+                XPhase,
+                vec![t],
+                Rational64::new(sign, 2),
+            )],
+            (false, name, _, _) => {
+                let t = gate_type(name, g.inverse, cs.len())
+                    .filter(|t| t.num_qubits() == Some(qs.len()))
+                    .ok_or_else(|| {
+                        unsupported(&format!("gate {} with {} controls", name, cs.len()))
+                    })?;
+                vec![Gate::new(t, qs.clone())]
+            }
+        };
+
+        // controls on |0> are conjugated by NOT
+        let negated: Vec<Gate> = cs
+            .iter()
+            .filter(|&&(_, positive)| !positive)
+            .map(|&(q, _)| Gate::new(NOT, vec![q]))
+            .collect();
+        gates.extend(negated.iter().cloned());
+        gates.extend(body);
+        gates.extend(negated);
+    }
+
+    let mut c = Circuit::new(wires.num_qubits);
+    c.gates.extend(gates);
+    Ok(c)
+}
+
+/// Reads a circuit from a file in Quipper's ASCII format.
+pub fn read_circuit(filename: &Path) -> Result<Circuit, QuipperError> {
+    let s = std::fs::read_to_string(filename)?;
+    decode_circuit(&s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+
+    #[test]
+    fn quipper_gates() {
+        let c = decode_circuit(
+            r#"
+Inputs: 0:Qbit, 1:Qbit, 2:Qbit
+Comment["ENTER: toffoli"](0:"x", 1:"y", 2:"z")
+QGate["H"](2) with nocontrol
+QGate["not"](2) with controls=[+0,-1] with nocontrol
+QGate["Z"](1) with controls=[+2] with nocontrol
+QGate["T"]*(0) with nocontrol
+QGate["S"](1) with nocontrol
+QRot["exp(-i%Z)",0.39269908169872414](1) with nocontrol
+QGate["omega"](2) with nocontrol
+QGate["swap"](0,2) with nocontrol
+Outputs: 0:Qbit, 1:Qbit, 2:Qbit
+"#,
+        )
+        .unwrap();
+
+        let mut c1 = Circuit::new(3);
+        c1.add_gate("h", vec![2]);
+        c1.add_gate("x", vec![1]);
+        c1.add_gate("ccx", vec![0, 1, 2]);
+        c1.add_gate("x", vec![1]);
+        c1.add_gate("cz", vec![2, 1]);
+        c1.add_gate("tdg", vec![0]);
+        c1.add_gate("s", vec![1]);
+        c1.add_gate_with_phase("rz", vec![1], Rational64::new(1, 4));
+        c1.add_gate("swap", vec![0, 2]);
+        assert_eq!(c, c1);
+    }
+
+    #[test]
+    fn quipper_ancillas() {
+        let c = decode_circuit(
+            r#"
+Inputs: 0:Qbit, 1:Qbit
+QInit0(5) with nocontrol
+QGate["not"](5) with controls=[+0,+1] with nocontrol
+QGate["Z"](5) with nocontrol
+QGate["not"](5) with controls=[+0,+1] with nocontrol
+QTerm0(5) with nocontrol
+QInit1(5) with nocontrol
+QTerm1(5) with nocontrol
+Outputs: 0:Qbit, 1:Qbit
+"#,
+        )
+        .unwrap();
+        assert_eq!(c.num_qubits(), 4);
+        assert_eq!(c.num_gates_of_type(InitAncilla), 2);
+        assert_eq!(c.num_gates_of_type(PostSelect), 2);
+
+        // the ancilla computes a CZ
+        let mut cz = Circuit::new(2);
+        cz.add_gate("cz", vec![0, 1]);
+        let mut c1 = Circuit::new(3);
+        c1.gates.extend(c.gates.iter().take(5).cloned());
+        let g: crate::vec_graph::Graph = c1.to_graph();
+        assert!(Tensor4::scalar_compare(&g, &cz));
+    }
+
+    #[test]
+    fn quipper_errors() {
+        for line in [
+            "Inputs: 0:Cbit",
+            "QGate[\"H\"](0) with controls=[+1]",
+            "QGate[\"not\"](0) with controls=[+0]",
+            "QMeas(0) with nocontrol",
+            "QGate[\"foo\"](0) with nocontrol",
+            "QGate[\"not\"](0",
+        ] {
+            assert!(decode_circuit(line).is_err(), "{}", line);
+        }
+    }
+}
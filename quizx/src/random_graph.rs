@@ -1,3 +1,5 @@
+use crate::circuit::Circuit;
+use crate::gate::*;
 use crate::graph::*;
 use num::Rational64;
 use rand::rngs::StdRng;
@@ -8,6 +10,23 @@ pub struct EquatorialStabilizerStateBuilder {
     pub qubits: usize,
 }
 
+/// Graph states, with one qubit for each vertex of a graph
+///
+/// The graph is either given with [edges](GraphStateBuilder::edges), a 2D cluster
+/// from [cluster](GraphStateBuilder::cluster), or a random `G(n, p)` graph, where
+/// each edge is present with probability `edge_prob`. The state can be built
+/// directly as a ZX diagram with [build](GraphStateBuilder::build), or as a circuit
+/// with [build_circuit](GraphStateBuilder::build_circuit). A random graph is
+/// sampled again on each call, so reset the seed to get the same state twice.
+pub struct GraphStateBuilder {
+    pub rng: StdRng,
+    pub qubits: usize,
+    /// The probability of each edge in a random graph
+    pub edge_prob: f64,
+    /// The edges of the graph, or `None` for a random graph
+    pub edges: Option<Vec<(usize, usize)>>,
+}
+
 impl Default for EquatorialStabilizerStateBuilder {
     fn default() -> Self {
         Self::new()
@@ -54,3 +73,149 @@ impl EquatorialStabilizerStateBuilder {
         g
     }
 }
+
+impl Default for GraphStateBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphStateBuilder {
+    pub fn new() -> GraphStateBuilder {
+        GraphStateBuilder {
+            rng: StdRng::from_entropy(),
+            qubits: 1,
+            edge_prob: 0.5,
+            edges: None,
+        }
+    }
+
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+    pub fn qubits(&mut self, qubits: usize) -> &mut Self {
+        self.qubits = qubits;
+        self
+    }
+    pub fn edge_prob(&mut self, edge_prob: f64) -> &mut Self {
+        self.edge_prob = edge_prob;
+        self
+    }
+    pub fn edges(&mut self, edges: Vec<(usize, usize)>) -> &mut Self {
+        self.edges = Some(edges);
+        self
+    }
+
+    /// Use a `rows` by `cols` grid, where the qubit in row `r` and column `c` is
+    /// `r * cols + c`
+    pub fn cluster(&mut self, rows: usize, cols: usize) -> &mut Self {
+        let mut edges = vec![];
+        for r in 0..rows {
+            for c in 0..cols {
+                let q = r * cols + c;
+                if c + 1 < cols {
+                    edges.push((q, q + 1));
+                }
+                if r + 1 < rows {
+                    edges.push((q, q + cols));
+                }
+            }
+        }
+        self.qubits = rows * cols;
+        self.edges(edges)
+    }
+
+    /// The edges of the graph, sampling a random graph if none were given
+    pub fn sample_edges(&mut self) -> Vec<(usize, usize)> {
+        if let Some(edges) = &self.edges {
+            return edges.clone();
+        }
+        let mut edges = vec![];
+        for i in 0..self.qubits {
+            for j in i + 1..self.qubits {
+                if self.rng.gen_bool(self.edge_prob) {
+                    edges.push((i, j));
+                }
+            }
+        }
+        edges
+    }
+
+    /// The graph state as a ZX diagram, with a Z spider on each output and
+    /// Hadamard edges between them
+    pub fn build<G: GraphLike>(&mut self) -> G {
+        let edges = self.sample_edges();
+        let mut g = G::new();
+        let outputs: Vec<_> = (0..self.qubits).map(|_| g.add_vertex(VType::B)).collect();
+        let spiders: Vec<_> = (0..self.qubits).map(|_| g.add_vertex(VType::Z)).collect();
+        for (&s, &o) in spiders.iter().zip(&outputs) {
+            g.add_edge(s, o);
+        }
+        for &(i, j) in &edges {
+            g.add_edge_with_type(spiders[i], spiders[j], EType::H);
+        }
+
+        g.set_outputs(outputs);
+        g.scalar_mut()
+            .mul_sqrt2_pow(edges.len() as i32 - self.qubits as i32);
+        g
+    }
+
+    /// The graph state as a circuit, which prepares each qubit in `|+>` and
+    /// applies a CZ for each edge
+    pub fn build_circuit(&mut self) -> Circuit {
+        let edges = self.sample_edges();
+        let mut c = Circuit::new(self.qubits);
+        for q in 0..self.qubits {
+            c.push(Gate::new(InitAncilla, vec![q]));
+            c.push(Gate::new(HAD, vec![q]));
+        }
+        for (i, j) in edges {
+            c.push(Gate::new(CZ, vec![i, j]));
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn graph_states() {
+        let mut b = GraphStateBuilder::new();
+        b.qubits(6).edge_prob(0.4);
+        for seed in 0..5 {
+            let g: Graph = b.seed(seed).build();
+            let c = b.seed(seed).build_circuit();
+            assert_eq!(g.num_edges() - 6, c.num_gates_of_type(CZ));
+            assert!(Tensor4::compare(&g, &c.to_graph::<Graph>()));
+        }
+
+        let g: Graph = GraphStateBuilder::new().edge_prob(1.0).qubits(5).build();
+        assert_eq!(g.num_edges(), 15);
+    }
+
+    #[test]
+    fn cluster_states() {
+        let mut b = GraphStateBuilder::new();
+        b.cluster(3, 4);
+        assert_eq!(b.qubits, 12);
+        assert_eq!(b.sample_edges().len(), 17);
+        let g: Graph = b.build();
+        // the degree of the spider on each qubit, including the edge to its output
+        let degree = |q: usize| g.degree(g.neighbors(g.outputs()[q]).next().unwrap());
+        assert_eq!(degree(0), 3);
+        assert_eq!(degree(1), 4);
+        assert_eq!(degree(5), 5);
+
+        let c = GraphStateBuilder::new().cluster(2, 2).build_circuit();
+        assert!(Tensor4::compare(
+            &c.to_graph::<Graph>(),
+            &b.cluster(2, 2).build::<Graph>()
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rewriting circuits into a given gate set
+//!
+//! [Circuit::rebase] replaces each gate which isn't in a [GateSet] by a sequence of
+//! gates which are, using exact identities such as `H = Rz(π/2) Rx(π/2) Rz(π/2)`,
+//! `CNOT = (I ⊗ H) CZ (I ⊗ H)` or `S = T T`, and the decompositions of
+//! [Gate::push_basic_gates]. The identities are applied recursively, up to a small
+//! depth, and if there is no exact way to write a gate, e.g. a rotation by `π/3`
+//! with Clifford+T, rebasing fails. All identities hold up to a global phase.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::gate::*;
+//! # use quizx::rebase::GateSet;
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; s q[1];").unwrap();
+//! let c1 = c.rebase(&GateSet::cz_rz_h()).unwrap();
+//! assert_eq!(c1.num_gates_of_type(CZ), 1);
+//! assert_eq!(c1.num_gates_of_type(CNOT), 0);
+//! assert!(c.rebase(&GateSet::clifford_t()).is_ok());
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::optimize_circuit::{rotation, rotation_gate};
+use crate::phase::Phase;
+use num::Rational64;
+use rustc_hash::FxHashMap;
+
+/// How many identities may be applied in a row to rebase a gate
+const MAX_DEPTH: usize = 5;
+
+/// An error that can occur when rebasing a circuit.
+#[derive(Debug, thiserror::Error)]
+pub enum RebaseError {
+    /// A gate has no exact decomposition into the gate set.
+    #[error("Gate {0} cannot be written exactly in the gate set")]
+    Inexact(String),
+}
+
+/// A set of gates to rebase circuits into
+///
+/// Each gate type in the set either allows any phase, or only a given phase, so
+/// e.g. `SX` is [XPhase] with phase `1/2`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GateSet {
+    gates: Vec<(GType, Option<Phase>)>,
+}
+
+impl GateSet {
+    /// The empty gate set
+    pub fn new() -> Self {
+        GateSet::default()
+    }
+
+    /// Adds a gate type with any phase.
+    pub fn with(mut self, t: GType) -> Self {
+        self.gates.push((t, None));
+        self
+    }
+
+    /// Adds a gate type with only the given phase.
+    pub fn with_phase(mut self, t: GType, phase: impl Into<Phase>) -> Self {
+        self.gates.push((t, Some(phase.into())));
+        self
+    }
+
+    /// The gates CZ, Rz and H
+    pub fn cz_rz_h() -> Self {
+        GateSet::new().with(CZ).with(ZPhase).with(HAD)
+    }
+
+    /// The gates CNOT, Rz and SX, as on IBM hardware
+    pub fn cx_rz_sx() -> Self {
+        GateSet::new()
+            .with(CNOT)
+            .with(ZPhase)
+            .with_phase(XPhase, Rational64::new(1, 2))
+    }
+
+    /// The Clifford+T gates H, X, Z, S, S†, T, T† and CNOT
+    pub fn clifford_t() -> Self {
+        GateSet::new()
+            .with(HAD)
+            .with(NOT)
+            .with(Z)
+            .with(S)
+            .with(Sdg)
+            .with(T)
+            .with(Tdg)
+            .with(CNOT)
+    }
+
+    /// Whether a gate is in the set
+    pub fn contains(&self, g: &Gate) -> bool {
+        let has_phase = matches!(g.t, ZPhase | XPhase | ParityPhase);
+        self.gates
+            .iter()
+            .any(|&(t, p)| t == g.t && (!has_phase || p.map_or(true, |p| p == g.phase)))
+    }
+}
+
+/// Exact decompositions of a gate into other gates
+fn candidates(g: &Gate) -> Vec<Vec<Gate>> {
+    let mut cs: Vec<Vec<Gate>> = vec![];
+    let h = |q: usize| Gate::new(HAD, vec![q]);
+    let zp = |q: usize, p: Phase| Gate::new_with_phase(ZPhase, vec![q], p);
+    let xp = |q: usize, p: Phase| Gate::new_with_phase(XPhase, vec![q], p);
+    let half = Phase::from((1, 2));
+
+    if let Some((x_axis, phase)) = rotation(g) {
+        let q = g.qs[0];
+        let named = rotation_gate(x_axis, phase, q);
+        if named.is_none() {
+            // a rotation by 0 is the identity
+            return vec![vec![]];
+        }
+        cs.extend(named.map(|n| vec![n]));
+        if x_axis {
+            cs.push(vec![xp(q, phase)]);
+            cs.push(vec![h(q), zp(q, phase), h(q)]);
+            let r = phase.to_rational() * 2;
+            if r.is_integer() {
+                // powers of SX
+                let k = r.to_integer().rem_euclid(4) as usize;
+                cs.push(vec![xp(q, half); k]);
+            }
+        } else {
+            cs.push(vec![zp(q, phase)]);
+            let r = phase.to_rational() * 4;
+            if r.is_integer() {
+                // a product of Clifford phases and T gates, or of T gates only
+                let k = r.to_integer().rem_euclid(8);
+                let ts: Vec<GType> = match k {
+                    3 => vec![S, T],
+                    5 => vec![Z, T],
+                    _ => vec![T; k as usize],
+                };
+                cs.push(ts.into_iter().map(|t| Gate::new(t, vec![q])).collect());
+                cs.push(vec![Gate::new(Tdg, vec![q]); (8 - k) as usize % 8]);
+            }
+            cs.push(vec![h(q), xp(q, phase), h(q)]);
+        }
+        cs.retain(|c| c.len() != 1 || c[0] != *g);
+        return cs;
+    }
+
+    let qs = &g.qs;
+    match g.t {
+        HAD => {
+            let q = qs[0];
+            cs.push(vec![zp(q, half), xp(q, half), zp(q, half)]);
+            cs.push(vec![xp(q, half), zp(q, half), xp(q, half)]);
+        }
+        CNOT => {
+            cs.push(vec![h(qs[1]), Gate::new(CZ, qs.clone()), h(qs[1])]);
+            cs.push(vec![h(qs[0]), Gate::new(XCX, qs.clone()), h(qs[0])]);
+        }
+        CZ => cs.push(vec![h(qs[1]), Gate::new(CNOT, qs.clone()), h(qs[1])]),
+        XCX => cs.push(vec![h(qs[0]), Gate::new(CNOT, qs.clone()), h(qs[0])]),
+        SWAP => cs.push(vec![
+            Gate::new(CNOT, vec![qs[0], qs[1]]),
+            Gate::new(CNOT, vec![qs[1], qs[0]]),
+            Gate::new(CNOT, vec![qs[0], qs[1]]),
+        ]),
+        TOFF | CCZ | ParityPhase => {
+            let mut c = Circuit::new(0);
+            g.push_basic_gates(&mut c);
+            cs.push(c.gates.into_iter().collect());
+            let other = if g.t == TOFF { CCZ } else { TOFF };
+            if g.t != ParityPhase {
+                cs.push(vec![h(qs[2]), Gate::new(other, qs.clone()), h(qs[2])]);
+            }
+        }
+        _ => {}
+    }
+    cs
+}
+
+/// A search for decompositions, remembering the results for each gate
+struct Rebaser<'a> {
+    gs: &'a GateSet,
+    memo: FxHashMap<(usize, Rational64, usize, usize), Option<Vec<Gate>>>,
+}
+
+impl Rebaser<'_> {
+    /// Decomposes a gate into the gate set with at most `depth` identities
+    fn rebase_gate(&mut self, g: &Gate, depth: usize) -> Option<Vec<Gate>> {
+        if self.gs.contains(g) || g.t == Barrier {
+            return Some(vec![g.clone()]);
+        }
+        if depth == 0 {
+            return None;
+        }
+
+        // decompose the gate on qubits 0..n, and then move it to its qubits
+        let key = (g.t as usize, g.phase.to_rational(), g.qs.len(), depth);
+        if !self.memo.contains_key(&key) {
+            let local = Gate::new_with_phase(g.t, (0..g.qs.len()).collect(), g.phase);
+            let result = candidates(&local).into_iter().find_map(|c| {
+                let mut gates = vec![];
+                for h in &c {
+                    gates.extend(self.rebase_gate(h, depth - 1)?);
+                }
+                Some(gates)
+            });
+            self.memo.insert(key, result);
+        }
+        let mut gates = self.memo[&key].clone()?;
+        for h in &mut gates {
+            for q in &mut h.qs {
+                *q = g.qs[*q];
+            }
+        }
+        Some(gates)
+    }
+}
+
+impl Circuit {
+    /// Rewrites the circuit into the given gate set, exactly
+    ///
+    /// See the [module docs](crate::rebase).
+    pub fn rebase(&self, gs: &GateSet) -> Result<Circuit, RebaseError> {
+        let mut r = Rebaser {
+            gs,
+            memo: FxHashMap::default(),
+        };
+        let mut c = Circuit::new(self.num_qubits());
+        for g in &self.gates {
+            let gates = r
+                .rebase_gate(g, MAX_DEPTH)
+                .ok_or_else(|| RebaseError::Inexact(g.to_qasm()))?;
+            for h in gates {
+                c.push(h);
+            }
+        }
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    /// Check two circuits are equal up to a global phase
+    fn approx_scalar_compare(c0: &Circuit, c1: &Circuit) -> bool {
+        let t0 = c0.to_graph::<Graph>().to_tensorf();
+        let t1 = c1.to_graph::<Graph>().to_tensorf();
+        let (i, x) = t0
+            .indexed_iter()
+            .max_by(|a, b| a.1.norm().total_cmp(&b.1.norm()))
+            .unwrap();
+        let r = t1[&i] / x;
+        (r.norm() - 1.0).abs() < 1e-6
+            && t0
+                .iter()
+                .zip(t1.iter())
+                .all(|(x, y)| (x * r - y).norm() < 1e-6)
+    }
+
+    #[test]
+    fn rebase_gate_sets() {
+        let mut c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .clifford_t(0.2)
+            .build();
+        c.push(Gate::new(CCZ, vec![0, 3, 1]));
+        c.push(Gate::new(TOFF, vec![2, 1, 0]));
+        c.push(Gate::new(SWAP, vec![1, 2]));
+        c.push(Gate::new(XCX, vec![2, 3]));
+        c.push(Gate::new(NOT, vec![3]));
+        c.push(Gate::new_with_phase(
+            ParityPhase,
+            vec![0, 1, 3],
+            Rational64::new(3, 4),
+        ));
+        c.push(Gate::new_with_phase(
+            XPhase,
+            vec![1],
+            Rational64::new(-1, 2),
+        ));
+        c.push(Gate::new_with_phase(ZPhase, vec![0], Rational64::new(5, 4)));
+        let mut c_arb = c.clone();
+        c_arb.push(Gate::new_with_phase(XPhase, vec![0], Rational64::new(2, 3)));
+        c_arb.push(Gate::new_with_phase(ZPhase, vec![3], Rational64::new(1, 8)));
+
+        for (gs, circ) in [
+            (GateSet::cz_rz_h(), &c_arb),
+            (GateSet::cx_rz_sx(), &c_arb),
+            (GateSet::clifford_t(), &c),
+            (GateSet::new().with(HAD).with(T).with(CNOT), &c),
+        ] {
+            let c1 = circ.rebase(&gs).unwrap();
+            assert!(c1.gates.iter().all(|g| gs.contains(g)), "{:?}", gs);
+            assert!(approx_scalar_compare(circ, &c1), "{:?}", gs);
+        }
+    }
+
+    #[test]
+    fn rebase_inexact() {
+        let mut c = Circuit::new(1);
+        c.push(Gate::new_with_phase(ZPhase, vec![0], Rational64::new(1, 3)));
+        assert!(c.rebase(&GateSet::clifford_t()).is_err());
+        assert!(c.rebase(&GateSet::cz_rz_h()).is_ok());
+
+        let mut c = Circuit::new(1);
+        c.push(Gate::new(InitAncilla, vec![0]));
+        assert!(c.rebase(&GateSet::cz_rz_h()).is_err());
+        assert!(c.rebase(&GateSet::cz_rz_h().with(InitAncilla)).is_ok());
+    }
+}
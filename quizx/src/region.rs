@@ -0,0 +1,207 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simplifying part of a graph
+//!
+//! A [Region] restricts the simplifiers to a set of vertices: a rewrite is only
+//! applied if every vertex it changes, i.e. the vertices it matched and their
+//! neighbours, is in the region. Everything outside the region is left as it
+//! was. This is useful to clean up locally after changing a small part of a
+//! large graph, e.g. after replacing a few spiders in a decomposition, without
+//! running a simplification on the whole graph again.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::graph::*;
+//! # use quizx::region::*;
+//! # use quizx::simplify::clifford_simp;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::random().seed(1).qubits(4).depth(40).with_cliffords().build();
+//! let mut g: Graph = c.to_graph();
+//! let seed = g.vertices().find(|&v| g.vertex_type(v) != VType::B).unwrap();
+//! simp_near(&mut g, [seed], 3, clifford_simp);
+//! ```
+
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use rustc_hash::FxHashSet;
+
+/// A graph whose simplification is confined to a set of vertices
+///
+/// Vertices added by rewrites in the region become part of it.
+#[derive(Debug, Clone)]
+pub struct Region<G: GraphLike> {
+    g: G,
+    region: FxHashSet<V>,
+}
+
+impl<G: GraphLike> Region<G> {
+    pub fn new(g: G, region: impl IntoIterator<Item = V>) -> Self {
+        Region {
+            g,
+            region: region.into_iter().collect(),
+        }
+    }
+
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    pub fn into_graph(self) -> G {
+        self.g
+    }
+
+    /// The vertices in the region which are still in the graph
+    pub fn region(&self) -> &FxHashSet<V> {
+        &self.region
+    }
+}
+
+impl<G: GraphLike> GraphLike for Region<G> {
+    fn new() -> Self {
+        Region::new(G::new(), [])
+    }
+
+    delegate_graph_like!(g);
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        let v = self.g.add_vertex(ty);
+        self.region.insert(v);
+        v
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        let v = self.g.add_vertex_with_data(d);
+        self.region.insert(v);
+        v
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        self.region.remove(&v);
+        self.g.remove_vertex(v)
+    }
+
+    fn can_rewrite_at(&self, vertices: &[V]) -> bool {
+        vertices.iter().all(|&v| {
+            self.region.contains(&v) && self.g.neighbors(v).all(|n| self.region.contains(&n))
+        })
+    }
+}
+
+/// The vertices at distance at most `k` from one of the seeds
+pub fn neighborhood(
+    g: &impl GraphLike,
+    seeds: impl IntoIterator<Item = V>,
+    k: usize,
+) -> FxHashSet<V> {
+    let mut nhd: FxHashSet<V> = seeds.into_iter().collect();
+    let mut frontier: Vec<V> = nhd.iter().copied().collect();
+    for _ in 0..k {
+        let mut next = vec![];
+        for v in frontier {
+            for n in g.neighbors(v) {
+                if nhd.insert(n) {
+                    next.push(n);
+                }
+            }
+        }
+        frontier = next;
+    }
+    nhd
+}
+
+/// Run a simplification on part of a graph, see [Region]
+///
+/// For example, `simp_in_region(&mut g, vs, full_simp)`. Returns true if any
+/// rewrite was applied.
+pub fn simp_in_region<G: GraphLike>(
+    g: &mut G,
+    region: impl IntoIterator<Item = V>,
+    simp: fn(&mut Region<G>) -> bool,
+) -> bool {
+    let mut r = Region::new(std::mem::replace(g, G::new()), region);
+    let m = simp(&mut r);
+    *g = r.into_graph();
+    m
+}
+
+/// Run a simplification on the vertices within distance `k` of the seeds
+///
+/// See [neighborhood] and [simp_in_region].
+pub fn simp_near<G: GraphLike>(
+    g: &mut G,
+    seeds: impl IntoIterator<Item = V>,
+    k: usize,
+    simp: fn(&mut Region<G>) -> bool,
+) -> bool {
+    let region = neighborhood(g, seeds, k);
+    simp_in_region(g, region, simp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::*;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn region_simp() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let h: Graph = c.to_graph();
+
+        let mut g = h.clone();
+        let seeds: Vec<V> = g
+            .vertices()
+            .filter(|&v| g.vertex_type(v) != VType::B)
+            .take(3)
+            .collect();
+        let region = neighborhood(&g, seeds.iter().copied(), 4);
+        assert!(region.len() < g.num_vertices());
+        assert!(simp_in_region(
+            &mut g,
+            region.iter().copied(),
+            clifford_simp
+        ));
+        assert!(g.num_vertices() < h.num_vertices());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        // nothing outside the region changed
+        for v in h.vertices().filter(|v| !region.contains(v)) {
+            assert_eq!(g.vertex_data(v), h.vertex_data(v));
+            let mut e0 = g.incident_edge_vec(v);
+            let mut e1 = h.incident_edge_vec(v);
+            e0.sort();
+            e1.sort();
+            assert_eq!(e0, e1);
+        }
+
+        // the whole graph as a region is the same as no region
+        let mut g0 = h.clone();
+        let mut g1 = h.clone();
+        clifford_simp(&mut g0);
+        simp_in_region(&mut g1, h.vertices(), clifford_simp);
+        assert_eq!(g0, g1);
+    }
+}
@@ -17,7 +17,7 @@
 use approx::AbsDiffEq;
 use num::complex::Complex;
 pub use num::traits::identities::{One, Zero};
-use num::{integer, Integer};
+use num::{integer, BigInt, Integer, ToPrimitive};
 use std::cmp::min;
 use std::f64::consts::PI;
 use std::fmt;
@@ -39,7 +39,8 @@ use std::ops::{Add, AddAssign, Mul};
 /// The type of the coefficient list is given as a type parameter
 /// implementing a trait [Coeffs].  This is to allow fixed N (with an
 /// array) or variable N (with a [Vec]).  Only the former is allowed
-/// to implement the [Copy] trait, needed for tensor/matrix elements.
+/// to implement the [Copy] trait, and is used for tensor/matrix
+/// elements. For exact tensors with any N, see [BigScalar].
 ///
 /// The [Float] representation of a scalar is given as a 64-bit
 /// floating point [Complex] number.
@@ -74,9 +75,8 @@ pub trait Sqrt2: Sized {
 
 /// A list of coefficients. We give this as a parameter to allow
 /// either fixed-size lists (e.g. `[i32;4]`) or dynamic ones (e.g.
-/// [Vec]\<i32\>). Only the former can be used in tensors and
-/// matrices, because they have to implement Copy (the size must be
-/// known at compile time).
+/// [Vec]\<i32\>). Only the former implement Copy (the size must be
+/// known at compile time), and are used in tensors and matrices.
 pub trait Coeffs: Clone + std::ops::IndexMut<usize, Output = isize> {
     /// Returns a coefficient list representing the number 0.
     fn zero() -> Self;
@@ -699,6 +699,248 @@ impl Coeffs for Vec<isize> {
 
 pub type ScalarN = Scalar<Vec<isize>>;
 
+/// An exact scalar with arbitrary-precision coefficients
+///
+/// Like the [Exact] variant of [Scalar], this is an element of D\[omega\], stored
+/// as a power of 2 and a list of N coefficients, where omega = e^(i pi/N). Here the
+/// coefficients are [BigInt]s and N grows to fit the phases that are used, so
+/// arithmetic never overflows or falls back to floating point. This is slower than
+/// [Scalar4], but tensors over [BigScalar] are exact for any phases, e.g. for
+/// checking circuits with small-angle rotations.
+///
+/// As for [ScalarN], equality is exact when N is a power of 2. Otherwise, the
+/// powers of omega are not linearly independent, so equal numbers can have
+/// different coefficients.
+#[derive(Debug, Clone)]
+pub struct BigScalar {
+    pow: i32,
+    coeffs: Vec<BigInt>,
+}
+
+impl BigScalar {
+    /// Returns the complex number representation of the scalar.
+    pub fn complex_value(&self) -> Complex<f64> {
+        let omega = Complex::new(-1f64, 0f64).powf(1f64 / (self.coeffs.len() as f64));
+        let pow2 = 2f64.powi(self.pow);
+        let mut num = Complex::new(0f64, 0f64);
+        for (i, c) in self.coeffs.iter().enumerate() {
+            num += pow2 * c.to_f64().unwrap_or(f64::NAN) * omega.powu(i as u32);
+        }
+        num
+    }
+
+    /// Compute the reduced form of the scalar value, as for [Scalar]
+    fn reduce(mut self) -> BigScalar {
+        match self.coeffs.iter().filter_map(|c| c.trailing_zeros()).min() {
+            Some(shift) => {
+                for c in &mut self.coeffs {
+                    *c >>= shift;
+                }
+                self.pow += shift as i32;
+            }
+            None => self.pow = 0,
+        }
+        self
+    }
+
+    /// The coefficients with respect to the 2n-th root of unity, where n must be
+    /// a multiple of the number of coefficients
+    fn padded(&self, n: usize) -> Vec<BigInt> {
+        let pad = n / self.coeffs.len();
+        let mut coeffs = vec![BigInt::zero(); n];
+        for (i, c) in self.coeffs.iter().enumerate() {
+            coeffs[i * pad] = c.clone();
+        }
+        coeffs
+    }
+}
+
+impl Zero for BigScalar {
+    fn zero() -> BigScalar {
+        BigScalar {
+            pow: 0,
+            coeffs: vec![BigInt::zero()],
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.coeffs.iter().all(|c| c.is_zero())
+    }
+}
+
+impl One for BigScalar {
+    fn one() -> BigScalar {
+        BigScalar {
+            pow: 0,
+            coeffs: vec![BigInt::one()],
+        }
+    }
+}
+
+impl Sqrt2 for BigScalar {
+    fn sqrt2_pow(p: i32) -> BigScalar {
+        if p % 2 == 0 {
+            BigScalar {
+                pow: p / 2,
+                coeffs: vec![BigInt::one()],
+            }
+        } else {
+            // as for Scalar, omega - omega^3 = sqrt(2) when omega = e^(i pi/4)
+            BigScalar {
+                pow: (p - 1) / 2,
+                coeffs: vec![0.into(), 1.into(), 0.into(), (-1).into()],
+            }
+        }
+    }
+}
+
+impl FromPhase for BigScalar {
+    fn from_phase(p: impl Into<Phase>) -> BigScalar {
+        let p = p.into().to_rational();
+        let denom = *p.denom();
+        let mut numer = p.numer().rem_euclid(2 * denom);
+        let mut coeffs = vec![BigInt::zero(); denom as usize];
+        if numer >= denom {
+            numer -= denom;
+            coeffs[numer as usize] = BigInt::from(-1);
+        } else {
+            coeffs[numer as usize] = BigInt::one();
+        }
+        BigScalar { pow: 0, coeffs }
+    }
+
+    fn minus_one() -> BigScalar {
+        BigScalar::from_phase(Phase::one())
+    }
+}
+
+impl fmt::Display for BigScalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        if self.pow != 0 {
+            write!(f, "2^{} * (", self.pow)?;
+        }
+        let mut fst = true;
+        for (i, c) in self.coeffs.iter().enumerate() {
+            if !c.is_zero() {
+                if !fst {
+                    write!(f, " + ")?;
+                }
+                fst = false;
+                write!(f, "{}", c)?;
+                if i != 0 {
+                    write!(f, " * om^{}", i)?;
+                }
+            }
+        }
+        if self.pow != 0 {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl Mul<&BigScalar> for &BigScalar {
+    type Output = BigScalar;
+
+    fn mul(self, rhs: &BigScalar) -> Self::Output {
+        let (lcm, pad0, pad1) = lcm_with_padding(self.coeffs.len(), rhs.coeffs.len());
+        let mut coeffs = vec![BigInt::zero(); lcm];
+        for (i, c0) in self.coeffs.iter().enumerate() {
+            if c0.is_zero() {
+                continue;
+            }
+            for (j, c1) in rhs.coeffs.iter().enumerate() {
+                // omega^lcm = -1, so wrapping around negates the coefficient
+                let pos = i * pad0 + j * pad1;
+                if pos < lcm {
+                    coeffs[pos] += c0 * c1;
+                } else {
+                    coeffs[pos - lcm] -= c0 * c1;
+                }
+            }
+        }
+        BigScalar {
+            pow: self.pow + rhs.pow,
+            coeffs,
+        }
+        .reduce()
+    }
+}
+
+impl Mul for BigScalar {
+    type Output = BigScalar;
+    fn mul(self, rhs: BigScalar) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl std::ops::MulAssign for BigScalar {
+    fn mul_assign(&mut self, rhs: BigScalar) {
+        *self = &*self * &rhs;
+    }
+}
+
+impl Add<&BigScalar> for &BigScalar {
+    type Output = BigScalar;
+
+    fn add(self, rhs: &BigScalar) -> Self::Output {
+        if rhs.is_zero() {
+            return self.clone();
+        }
+        if self.is_zero() {
+            return rhs.clone();
+        }
+        let lcm = integer::lcm(self.coeffs.len(), rhs.coeffs.len());
+        let minpow = min(self.pow, rhs.pow);
+        let mut coeffs = self.padded(lcm);
+        for c in &mut coeffs {
+            *c <<= (self.pow - minpow) as usize;
+        }
+        for (c, c1) in coeffs.iter_mut().zip(rhs.padded(lcm)) {
+            *c += c1 << (rhs.pow - minpow) as usize;
+        }
+        BigScalar {
+            pow: minpow,
+            coeffs,
+        }
+        .reduce()
+    }
+}
+
+impl Add for BigScalar {
+    type Output = BigScalar;
+    fn add(self, rhs: BigScalar) -> Self::Output {
+        &self + &rhs
+    }
+}
+
+impl PartialEq for BigScalar {
+    fn eq(&self, other: &Self) -> bool {
+        // both sides are reduced, so equal scalars have the same power of 2
+        let lcm = integer::lcm(self.coeffs.len(), other.coeffs.len());
+        self.pow == other.pow && self.padded(lcm) == other.padded(lcm)
+    }
+}
+
+impl<T: Coeffs> FromScalar<Scalar<T>> for BigScalar {
+    /// Panics if the scalar is not [Exact]
+    fn from_scalar(s: &Scalar<T>) -> BigScalar {
+        match s {
+            Exact(pow, coeffs) => BigScalar {
+                pow: *pow,
+                coeffs: coeffs.iter_coeffs().map(BigInt::from).collect(),
+            }
+            .reduce(),
+            Float(_) => panic!("Cannot convert a floating-point scalar to a BigScalar"),
+        }
+    }
+}
+
+impl ndarray::ScalarOperand for BigScalar {}
+
 /// tests {{{
 #[cfg(test)]
 mod tests {
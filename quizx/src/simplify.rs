@@ -18,14 +18,15 @@ use crate::basic_rules::*;
 use crate::graph::*;
 use crate::phase::Phase;
 use num::{One, Zero};
-use rustc_hash::FxHashMap;
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 /// Repeatedly apply the given rule at any vertex
 /// that matches the check function
 ///
-/// We assume the rule will at most delete the current
-/// vertex, and leave other vertices in place (although
-/// edges might change).
+/// The rule can delete the current vertex and its
+/// neighbours. Deleted vertices are skipped for the rest
+/// of the pass.
 pub fn vertex_simp<G: GraphLike>(
     g: &mut G,
     check: fn(&G, V) -> bool,
@@ -39,7 +40,10 @@ pub fn vertex_simp<G: GraphLike>(
         numv = g.num_vertices();
         new_matches = false;
         for v in g.vertex_vec() {
-            if check(g, v) {
+            if !g.can_rewrite() {
+                return got_match;
+            }
+            if g.contains_vertex(v) && g.can_rewrite_at(&[v]) && check(g, v) {
                 rule(g, v);
                 new_matches = true;
                 got_match = true;
@@ -66,7 +70,14 @@ pub fn edge_simp<G: GraphLike>(
         numv = g.num_vertices();
         new_matches = false;
         for (s, t, _) in g.edge_vec() {
-            if !g.contains_vertex(s) || !g.contains_vertex(t) || !check(g, s, t) {
+            if !g.can_rewrite() {
+                return got_match;
+            }
+            if !g.contains_vertex(s)
+                || !g.contains_vertex(t)
+                || !g.can_rewrite_at(&[s, t])
+                || !check(g, s, t)
+            {
                 continue;
             }
             rule(g, s, t);
@@ -81,10 +92,466 @@ pub fn edge_simp<G: GraphLike>(
     got_match
 }
 
+/// Claim the closed neighbourhood of the given vertices, unless part of it is
+/// already claimed
+fn claim_nhd(g: &impl GraphLike, claimed: &mut FxHashSet<V>, vs: &[V]) -> bool {
+    let mut nhd: Vec<V> = vs.to_vec();
+    for &v in vs {
+        nhd.extend(g.neighbors(v));
+    }
+    if nhd.iter().any(|v| claimed.contains(v)) {
+        false
+    } else {
+        claimed.extend(nhd);
+        true
+    }
+}
+
+/// Like [vertex_simp], but finds matches in parallel
+///
+/// Each round checks every vertex in parallel, then picks matches whose closed
+/// neighbourhoods do not overlap, and applies them. Since the rules only change
+/// a vertex and its neighbours, the picked matches do not interfere with each
+/// other, and only the scan for matches, which dominates the cost on a large
+/// graph, needs to run in parallel. Rounds are repeated until there are no
+/// matches left.
+pub fn par_vertex_simp<G: GraphLike>(
+    g: &mut G,
+    check: fn(&G, V) -> bool,
+    rule: fn(&mut G, V) -> (),
+) -> bool {
+    let mut got_match = false;
+    loop {
+        let gr: &G = g;
+        let matches: Vec<V> = gr
+            .vertex_vec()
+            .into_par_iter()
+            .filter(|&v| gr.can_rewrite_at(&[v]) && check(gr, v))
+            .collect();
+        if matches.is_empty() {
+            break;
+        }
+
+        let mut claimed = FxHashSet::default();
+        let matches: Vec<V> = matches
+            .into_iter()
+            .filter(|&v| claim_nhd(gr, &mut claimed, &[v]))
+            .collect();
+        for v in matches {
+            if !g.can_rewrite() {
+                return got_match;
+            }
+            if g.contains_vertex(v) && check(g, v) {
+                rule(g, v);
+                got_match = true;
+            }
+        }
+    }
+
+    got_match
+}
+
+/// Like [edge_simp], but finds matches in parallel
+///
+/// See [par_vertex_simp]. Here, a match claims the neighbourhoods of both ends of
+/// the edge.
+pub fn par_edge_simp<G: GraphLike>(
+    g: &mut G,
+    check: fn(&G, V, V) -> bool,
+    rule: fn(&mut G, V, V) -> (),
+) -> bool {
+    let mut got_match = false;
+    loop {
+        let gr: &G = g;
+        let matches: Vec<(V, V)> = gr
+            .edge_vec()
+            .into_par_iter()
+            .filter_map(|(s, t, _)| {
+                if !gr.can_rewrite_at(&[s, t]) {
+                    None
+                } else if check(gr, s, t) {
+                    Some((s, t))
+                } else if check(gr, t, s) {
+                    Some((t, s))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if matches.is_empty() {
+            break;
+        }
+
+        let mut claimed = FxHashSet::default();
+        let matches: Vec<(V, V)> = matches
+            .into_iter()
+            .filter(|&(s, t)| claim_nhd(gr, &mut claimed, &[s, t]))
+            .collect();
+        for (s, t) in matches {
+            if !g.can_rewrite() {
+                return got_match;
+            }
+            if check(g, s, t) {
+                rule(g, s, t);
+                got_match = true;
+            }
+        }
+    }
+
+    got_match
+}
+
+/// A match of a rewrite rule, as returned by e.g. [find_spider_fusion]
+///
+/// Each rule has three ways to find matches: `matches_*` iterates lazily over
+/// them, `find_*` returns the first and `find_all_*` collects them all. The lazy
+/// iterator only checks as many vertices or edges as it is asked for, so it is
+/// the cheapest way to look for the first match with some extra property, e.g.
+/// `matches_pivot(&g).find(|m| ...)`, or to sample a match at random with
+/// [rand::seq::IteratorRandom::choose].
+pub trait RuleMatch: Copy + std::fmt::Debug {
+    /// A short name for the rule, e.g. `"spider_fusion"`
+    const NAME: &'static str;
+
+    /// The vertices the rule is applied to, in order
+    fn vertices(&self) -> Vec<V>;
+
+    /// Apply the rule, if it still applies. Returns false if the match went stale.
+    fn apply(&self, g: &mut impl GraphLike) -> bool;
+}
+
+/// Define a match type for a rule on 1 vertex, with functions to find and apply it
+macro_rules! vertex_match {
+    ( $match:ident, $check:ident, $unchecked:ident, $matches:ident, $find:ident, $find_all:ident, $apply:ident, $name:literal ) => {
+        #[doc = concat!("A vertex where [", stringify!($unchecked), "] applies")]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $match {
+            pub v: V,
+        }
+
+        #[doc = concat!("Iterate lazily over the vertices where [", stringify!($check), "] holds")]
+        ///
+        /// Each match is only checked when the iterator reaches it. See [RuleMatch].
+        pub fn $matches(g: &impl GraphLike) -> impl Iterator<Item = $match> + '_ {
+            g.vertices()
+                .filter(move |&v| $check(g, v))
+                .map(|v| $match { v })
+        }
+
+        #[doc = concat!("Find the first vertex where [", stringify!($check), "] holds")]
+        pub fn $find(g: &impl GraphLike) -> Option<$match> {
+            $matches(g).next()
+        }
+
+        #[doc = concat!("Find every vertex where [", stringify!($check), "] holds")]
+        ///
+        /// Applying one of these matches can invalidate the others.
+        pub fn $find_all(g: &impl GraphLike) -> Vec<$match> {
+            $matches(g).collect()
+        }
+
+        /// Apply the rule at a match, if it still applies
+        ///
+        /// Returns false, leaving the graph unchanged, if the match went stale.
+        pub fn $apply(g: &mut impl GraphLike, m: $match) -> bool {
+            if g.contains_vertex(m.v) && $check(g, m.v) {
+                $unchecked(g, m.v);
+                true
+            } else {
+                false
+            }
+        }
+
+        impl RuleMatch for $match {
+            const NAME: &'static str = $name;
+
+            fn vertices(&self) -> Vec<V> {
+                vec![self.v]
+            }
+
+            fn apply(&self, g: &mut impl GraphLike) -> bool {
+                $apply(g, *self)
+            }
+        }
+    };
+}
+
+/// Define a match type for a rule on an edge, with functions to find and apply it
+///
+/// Both orientations of each edge are tried, so this also works for rules which
+/// are not symmetric in their two vertices.
+macro_rules! edge_match {
+    ( $match:ident, $check:ident, $unchecked:ident, $matches:ident, $find:ident, $find_all:ident, $apply:ident, $name:literal ) => {
+        #[doc = concat!("A pair of vertices where [", stringify!($unchecked), "] applies")]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $match {
+            pub v0: V,
+            pub v1: V,
+        }
+
+        #[doc = concat!("Iterate lazily over the edges where [", stringify!($check), "] holds")]
+        ///
+        /// Each edge occurs at most once, and is only checked when the iterator
+        /// reaches it. See [RuleMatch].
+        pub fn $matches(g: &impl GraphLike) -> impl Iterator<Item = $match> + '_ {
+            g.edges().filter_map(move |(s, t, _)| {
+                if $check(g, s, t) {
+                    Some($match { v0: s, v1: t })
+                } else if $check(g, t, s) {
+                    Some($match { v0: t, v1: s })
+                } else {
+                    None
+                }
+            })
+        }
+
+        #[doc = concat!("Find the first edge where [", stringify!($check), "] holds")]
+        pub fn $find(g: &impl GraphLike) -> Option<$match> {
+            $matches(g).next()
+        }
+
+        #[doc = concat!("Find every edge where [", stringify!($check), "] holds")]
+        ///
+        /// Each edge occurs at most once. Applying one of these matches can invalidate
+        /// the others.
+        pub fn $find_all(g: &impl GraphLike) -> Vec<$match> {
+            $matches(g).collect()
+        }
+
+        /// Apply the rule at a match, if it still applies
+        ///
+        /// Returns false, leaving the graph unchanged, if the match went stale.
+        pub fn $apply(g: &mut impl GraphLike, m: $match) -> bool {
+            if g.contains_vertex(m.v0) && g.contains_vertex(m.v1) && $check(g, m.v0, m.v1) {
+                $unchecked(g, m.v0, m.v1);
+                true
+            } else {
+                false
+            }
+        }
+
+        impl RuleMatch for $match {
+            const NAME: &'static str = $name;
+
+            fn vertices(&self) -> Vec<V> {
+                vec![self.v0, self.v1]
+            }
+
+            fn apply(&self, g: &mut impl GraphLike) -> bool {
+                $apply(g, *self)
+            }
+        }
+    };
+}
+
+vertex_match!(
+    RemoveIdMatch,
+    check_remove_id,
+    remove_id_unchecked,
+    matches_remove_id,
+    find_remove_id,
+    find_all_remove_id,
+    apply_remove_id,
+    "remove_id"
+);
+vertex_match!(
+    LocalCompMatch,
+    check_local_comp,
+    local_comp_unchecked,
+    matches_local_comp,
+    find_local_comp,
+    find_all_local_comp,
+    apply_local_comp,
+    "local_comp"
+);
+vertex_match!(
+    PiCopyMatch,
+    check_pi_copy,
+    pi_copy_unchecked,
+    matches_pi_copy,
+    find_pi_copy,
+    find_all_pi_copy,
+    apply_pi_copy,
+    "pi_copy"
+);
+vertex_match!(
+    RemoveSingleMatch,
+    check_remove_single,
+    remove_single_unchecked,
+    matches_remove_single,
+    find_remove_single,
+    find_all_remove_single,
+    apply_remove_single,
+    "remove_single"
+);
+vertex_match!(
+    CopyMatch,
+    check_copy,
+    copy_unchecked,
+    matches_copy,
+    find_copy,
+    find_all_copy,
+    apply_copy,
+    "copy"
+);
+edge_match!(
+    SpiderFusionMatch,
+    check_spider_fusion,
+    spider_fusion_unchecked,
+    matches_spider_fusion,
+    find_spider_fusion,
+    find_all_spider_fusion,
+    apply_spider_fusion,
+    "spider_fusion"
+);
+edge_match!(
+    PivotMatch,
+    check_pivot,
+    pivot_unchecked,
+    matches_pivot,
+    find_pivot,
+    find_all_pivot,
+    apply_pivot,
+    "pivot"
+);
+edge_match!(
+    GenPivotMatch,
+    check_gen_pivot_reduce,
+    gen_pivot_unchecked,
+    matches_gen_pivot,
+    find_gen_pivot,
+    find_all_gen_pivot,
+    apply_gen_pivot,
+    "gen_pivot"
+);
+edge_match!(
+    BoundaryPivotMatch,
+    check_boundary_pivot,
+    gen_pivot_unchecked,
+    matches_boundary_pivot,
+    find_boundary_pivot,
+    find_all_boundary_pivot,
+    apply_boundary_pivot,
+    "boundary_pivot"
+);
+edge_match!(
+    PivotGadgetMatch,
+    check_pivot_gadget,
+    gen_pivot_unchecked,
+    matches_pivot_gadget,
+    find_pivot_gadget,
+    find_all_pivot_gadget,
+    apply_pivot_gadget,
+    "pivot_gadget"
+);
+edge_match!(
+    PivotBoundaryMatch,
+    check_pivot_boundary,
+    gen_pivot_unchecked,
+    matches_pivot_boundary,
+    find_pivot_boundary,
+    find_all_pivot_boundary,
+    apply_pivot_boundary,
+    "pivot_boundary"
+);
+edge_match!(
+    RemovePairMatch,
+    check_remove_pair,
+    remove_pair_unchecked,
+    matches_remove_pair,
+    find_remove_pair,
+    find_all_remove_pair,
+    apply_remove_pair,
+    "remove_pair"
+);
+
+/// Remove all phase-free arity-2 Z and X spiders
+///
+/// See [remove_id_unchecked]. This is cheap, and can be run between other
+/// transformations to tidy up a diagram.
 pub fn id_simp(g: &mut impl GraphLike) -> bool {
     vertex_simp(g, check_remove_id, remove_id_unchecked, false)
 }
 
+/// Replace all arity-2 H-boxes by Hadamard edges, cancelling pairs of Hadamards
+///
+/// See [hbox_to_edge_unchecked]. Parallel Hadamard edges between spiders are also
+/// cancelled as this happens. Like [id_simp], this is cheap.
+pub fn hadamard_simp(g: &mut impl GraphLike) -> bool {
+    vertex_simp(g, check_hbox_to_edge, hbox_to_edge_unchecked, false)
+}
+
+/// Multiply together H-boxes with the same neighbours, see [hbox_multiply_unchecked]
+pub fn hbox_multiply_simp(g: &mut impl GraphLike) -> bool {
+    let mut got_match = false;
+    for v in g.vertex_vec() {
+        if !g.can_rewrite() {
+            break;
+        }
+        if !g.contains_vertex(v) || g.vertex_type(v) != VType::H {
+            continue;
+        }
+        // any other H-box with the same neighbours is a neighbour of the first one
+        let w = g.neighbors(v).next().and_then(|n| {
+            g.neighbors(n)
+                .find(|&w| check_hbox_multiply(g, v, w) && g.can_rewrite_at(&[v, w]))
+        });
+        if let Some(w) = w {
+            hbox_multiply_unchecked(g, v, w);
+            got_match = true;
+        }
+    }
+    got_match
+}
+
+/// Simplify a diagram containing H-boxes with the rules of the ZH-calculus
+///
+/// This fuses spiders and H-boxes, multiplies H-boxes on the same legs, absorbs
+/// Z-basis states, removes H-boxes which are constantly 1 (see
+/// [hbox_absorb_unchecked] and [hbox_ortho_unchecked]), and replaces arity-2
+/// H-boxes by Hadamard edges, until none of these apply. Unlike [full_simp], it
+/// works on H-boxes directly, so multi-controlled gates like Toffolis do not
+/// need to be decomposed into Clifford+T first.
+///
+/// Returns true if any rewrite was applied.
+pub fn zh_simp(g: &mut impl GraphLike) -> bool {
+    // spider fusion and identity removal can create parallel edges, which are
+    // only supported between spiders
+    fn is_spider(g: &impl GraphLike, v: V) -> bool {
+        matches!(g.vertex_type(v), VType::Z | VType::X)
+    }
+    fn check_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
+        check_spider_fusion(g, v0, v1)
+            && !g
+                .neighbors(v1)
+                .any(|n| !is_spider(g, n) && g.connected(v0, n))
+    }
+    fn check_id(g: &impl GraphLike, v: V) -> bool {
+        if !check_remove_id(g, v) {
+            return false;
+        }
+        let nhd = g.neighbor_vec(v);
+        !g.connected(nhd[0], nhd[1]) || (is_spider(g, nhd[0]) && is_spider(g, nhd[1]))
+    }
+
+    let mut got_match = false;
+    let mut m = true;
+    while m {
+        m = edge_simp(g, check_fusion, spider_fusion_unchecked, false);
+        m = vertex_simp(g, check_id, remove_id_unchecked, false) || m;
+        m = hadamard_simp(g) || m;
+        m = edge_simp(g, check_hbox_fusion, hbox_fusion_unchecked, false) || m;
+        m = hbox_multiply_simp(g) || m;
+        m = vertex_simp(g, check_hbox_absorb, hbox_absorb_unchecked, false) || m;
+        m = vertex_simp(g, check_hbox_ortho, hbox_ortho_unchecked, false) || m;
+        if m {
+            got_match = true;
+        }
+    }
+
+    got_match
+}
+
 pub fn local_comp_simp(g: &mut impl GraphLike) -> bool {
     vertex_simp(g, check_local_comp, local_comp_unchecked, false)
 }
@@ -97,10 +564,45 @@ pub fn pivot_simp(g: &mut impl GraphLike) -> bool {
     edge_simp(g, check_pivot, pivot_unchecked, false)
 }
 
+/// Remove interior Pauli spiders by pivoting
+///
+/// Unlike [pivot_simp], the other spider can have any phase and can be connected to
+/// boundaries, so this subsumes [pivot_gadget_simp] and [pivot_boundary_simp].
 pub fn gen_pivot_simp(g: &mut impl GraphLike) -> bool {
     edge_simp(g, check_gen_pivot_reduce, gen_pivot_unchecked, false)
 }
 
+/// Remove interior Pauli spiders next to non-Pauli spiders, which become gadgets
+///
+/// See [check_pivot_gadget]. Together with [pivot_simp] and [pivot_boundary_simp],
+/// this covers the cases of [gen_pivot_simp] which come up in practice, split up in
+/// the same way as in PyZX.
+pub fn pivot_gadget_simp(g: &mut impl GraphLike) -> bool {
+    // gen_pivot is symmetric, so we can match the Pauli spider on either end
+    fn check(g: &impl GraphLike, v0: V, v1: V) -> bool {
+        check_pivot_gadget(g, v0, v1) || check_pivot_gadget(g, v1, v0)
+    }
+    edge_simp(g, check, gen_pivot_unchecked, false)
+}
+
+/// Remove interior Pauli spiders next to boundary spiders
+///
+/// See [check_pivot_boundary] and [pivot_gadget_simp].
+pub fn pivot_boundary_simp(g: &mut impl GraphLike) -> bool {
+    fn check(g: &impl GraphLike, v0: V, v1: V) -> bool {
+        check_pivot_boundary(g, v0, v1) || check_pivot_boundary(g, v1, v0)
+    }
+    edge_simp(g, check, gen_pivot_unchecked, false)
+}
+
+/// Copy Pauli states through spiders of the opposite color
+///
+/// See [copy_unchecked]. This is most useful on plugged diagrams, where it removes
+/// the basis states introduced by [GraphLike::plug_inputs] and the decomposer.
+pub fn copy_simp(g: &mut impl GraphLike) -> bool {
+    vertex_simp(g, check_copy, copy_unchecked, false)
+}
+
 pub fn scalar_simp(g: &mut impl GraphLike) -> bool {
     let mut m = vertex_simp(g, check_remove_single, remove_single_unchecked, false);
     m = edge_simp(g, check_remove_pair, remove_pair_unchecked, false) || m;
@@ -124,14 +626,111 @@ pub fn flow_simp(g: &mut impl GraphLike) -> bool {
     got_match
 }
 
+/// Simplify the interior of a diagram with Clifford rewrites
+///
+/// This turns X spiders into Z spiders, then fuses spiders and removes identities,
+/// and applies local complementation and pivoting to interior Clifford spiders until
+/// none of these rules apply. Afterwards:
+///
+/// - the diagram is graph-like (see [GraphLike::is_graph_like]),
+/// - no interior spider has a proper Clifford phase (±π/2), and
+/// - no two adjacent interior spiders both have a Pauli phase (0 or π).
+///
+/// Boundary spiders are never removed, so if the input has gflow, so does the
+/// output, and a circuit can still be extracted from it.
+///
+/// Returns true if any rewrite was applied.
 pub fn interior_clifford_simp(g: &mut impl GraphLike) -> bool {
-    spider_simp(g);
+    interior_clifford_simp_impl(g, false)
+}
+
+/// Check that `v0` and `v1` together are, or are connected to, at most one input
+/// and at most one output, so merging them leaves every boundary on its own spider
+fn boundaries_apart(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    let count = |bs: &Vec<V>| {
+        [v0, v1]
+            .iter()
+            .flat_map(|&v| {
+                if g.vertex_type(v) == VType::B {
+                    vec![v]
+                } else {
+                    g.neighbors(v).collect()
+                }
+            })
+            .filter(|n| g.vertex_type(*n) == VType::B && bs.contains(n))
+            .count()
+    };
+    count(g.inputs()) <= 1 && count(g.outputs()) <= 1
+}
+
+/// Check spider fusion applies, and the fused spider is connected to at most one
+/// input and at most one output
+fn check_spider_fusion_gflow(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    check_spider_fusion(g, v0, v1) && boundaries_apart(g, v0, v1)
+}
+
+/// Check spider fusion applies, but would connect the fused spider to two inputs
+/// or two outputs
+fn check_split_edge_gflow(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    check_spider_fusion(g, v0, v1) && !boundaries_apart(g, v0, v1)
+}
+
+/// Replace the normal edge between `v0` and `v1` by a phase-free Z spider with a
+/// Hadamard edge to each of them
+///
+/// This makes the diagram graph-like without fusing `v0` and `v1`.
+fn split_edge_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    g.begin_rewrite("split_edge", &[v0, v1]);
+    g.remove_edge(v0, v1);
+    let w = g.add_vertex(VType::Z);
+    g.set_qubit(w, g.qubit(v1));
+    g.set_row(w, g.row(v1));
+    g.add_edge_with_type(v0, w, EType::H);
+    g.add_edge_with_type(w, v1, EType::H);
+    g.end_rewrite();
+}
+
+/// Check an identity can be removed without connecting a spider to two inputs or
+/// two outputs, either directly or by leaving a normal edge to a spider which
+/// would then be fused with it
+fn check_remove_id_gflow(g: &impl GraphLike, v: V) -> bool {
+    if !check_remove_id(g, v) {
+        return false;
+    }
+    let nhd: Vec<(V, EType)> = g.incident_edges(v).collect();
+    let (v0, v1) = (nhd[0].0, nhd[1].0);
+    let spiders = g.vertex_type(v0) != VType::B && g.vertex_type(v1) != VType::B;
+    (spiders && nhd[0].1 != nhd[1].1) || boundaries_apart(g, v0, v1)
+}
+
+fn remove_ids(g: &mut impl GraphLike, preserve_gflow: bool) -> bool {
+    if preserve_gflow {
+        vertex_simp(g, check_remove_id_gflow, remove_id_unchecked, false)
+    } else {
+        id_simp(g)
+    }
+}
+
+/// Fuse spiders. To preserve gflow, spiders are not fused if the result would be
+/// connected to two inputs or two outputs, and the edge between them is split
+/// with [split_edge_unchecked] instead.
+fn fuse_spiders(g: &mut impl GraphLike, preserve_gflow: bool) -> bool {
+    if preserve_gflow {
+        let m = edge_simp(g, check_spider_fusion_gflow, spider_fusion_unchecked, false);
+        edge_simp(g, check_split_edge_gflow, split_edge_unchecked, false) || m
+    } else {
+        spider_simp(g)
+    }
+}
+
+fn interior_clifford_simp_impl(g: &mut impl GraphLike, preserve_gflow: bool) -> bool {
+    fuse_spiders(g, preserve_gflow);
     g.x_to_z();
     let mut got_match = false;
     let mut m = true;
     while m {
-        m = id_simp(g);
-        m = spider_simp(g) || m;
+        m = remove_ids(g, preserve_gflow);
+        m = fuse_spiders(g, preserve_gflow) || m;
         m = pivot_simp(g) || m;
         m = local_comp_simp(g) || m;
         m = scalar_simp(g) || m;
@@ -143,13 +742,27 @@ pub fn interior_clifford_simp(g: &mut impl GraphLike) -> bool {
     got_match
 }
 
+/// Simplify a diagram with Clifford rewrites, including at the boundary
+///
+/// In addition to [interior_clifford_simp], this pivots Pauli spiders against their
+/// neighbours, unfusing phases and boundary wires as needed, until there are no more
+/// interior Pauli spiders to remove. The guarantees of [interior_clifford_simp] still
+/// hold afterwards, and in particular gflow is preserved, so this is the simplifier
+/// to use before [crate::extract]. For a Clifford circuit, the result is in the
+/// graph-state-with-local-Cliffords form with no interior spiders.
+///
+/// Returns true if any rewrite was applied.
 pub fn clifford_simp(g: &mut impl GraphLike) -> bool {
+    clifford_simp_impl(g, false)
+}
+
+fn clifford_simp_impl(g: &mut impl GraphLike, preserve_gflow: bool) -> bool {
     let mut got_match = false;
     let mut m = true;
     while m {
         // let numv = g.num_vertices();
         // println!("v: {}", numv);
-        m = interior_clifford_simp(g);
+        m = interior_clifford_simp_impl(g, preserve_gflow);
         m = gen_pivot_simp(g) || m;
         if m {
             got_match = true;
@@ -160,24 +773,37 @@ pub fn clifford_simp(g: &mut impl GraphLike) -> bool {
     got_match
 }
 
+/// Fuse phase gadgets which act on the same set of spiders
+///
+/// A phase gadget is a phase-free Z spider, the hub, with a Z spider of degree 1,
+/// the leaf, among its neighbours. Only hubs whose neighbours are all Z spiders
+/// connected by Hadamard edges count as gadgets, since otherwise the hub is not a
+/// phase gadget on its other neighbours. Gadgets with the same neighbours are
+/// replaced by a single gadget carrying the sum of their phases.
+///
+/// Returns true if any gadgets were fused.
 pub fn fuse_gadgets(g: &mut impl GraphLike) -> bool {
     let mut gadgets: FxHashMap<Vec<V>, Vec<(V, V)>> = FxHashMap::default();
 
     for v in g.vertices() {
         if g.degree(v) == 1 && g.vertex_type(v) == VType::Z {
             let w = g.neighbors(v).next().unwrap();
-            if g.vertex_type(w) != VType::Z || !g.phase(w).is_zero() {
+            if g.vertex_type(w) != VType::Z
+                || !g.phase(w).is_zero()
+                || g.has_symbolic_phase(w)
+                || !g.can_rewrite_at(&[w, v])
+            {
                 continue;
             }
-            let mut nhd = Vec::new();
-            for (n, et) in g.incident_edges(w) {
-                if g.vertex_type(n) != VType::Z || et != EType::H {
-                    continue;
-                }
-                if n != v {
-                    nhd.push(n);
-                }
+            // the hub should only be connected to Z spiders by H-edges, otherwise it
+            // is not a phase gadget on its neighbours
+            if !g
+                .incident_edges(w)
+                .all(|(n, et)| g.vertex_type(n) == VType::Z && et == EType::H)
+            {
+                continue;
             }
+            let mut nhd: Vec<V> = g.neighbors(w).filter(|&n| n != v).collect();
             nhd.sort();
 
             if let Some(gs) = gadgets.get_mut(&nhd) {
@@ -192,12 +818,15 @@ pub fn fuse_gadgets(g: &mut impl GraphLike) -> bool {
 
     let mut fused = false;
     for (vs, gs) in gadgets.iter() {
-        if gs.len() > 1 {
+        if gs.len() > 1 && g.can_rewrite() {
             let num = gs.len() as i32;
             let degree = vs.len() as i32;
             fused = true;
+            let gvs: Vec<V> = gs.iter().flat_map(|&(u, v)| [u, v]).collect();
+            g.begin_rewrite("gadget_fusion", &gvs);
             let mut ph = Phase::zero();
             for (u, v) in gs.iter().skip(1).copied() {
+                g.fuse_phases(gs[0].1, v);
                 ph += g.phase(v);
                 g.remove_vertex(u);
                 g.remove_vertex(v);
@@ -205,6 +834,7 @@ pub fn fuse_gadgets(g: &mut impl GraphLike) -> bool {
 
             g.add_to_phase(gs[0].1, ph);
             g.scalar_mut().mul_sqrt2_pow(-(num - 1) * (degree - 1));
+            g.end_rewrite();
         }
     }
 
@@ -219,34 +849,214 @@ fn remove_gadget_pi(g: &mut impl GraphLike) -> bool {
         // Look for the outsides of phase gadgets
         .filter(|&v| g.degree(v) == 1 && g.vertex_type(v) == VType::Z)
         .map(|v| (g.neighbors(v).next().unwrap(), v))
-        // Check that the middle is a pi-phase
+        // Check that the middle is a pi-phase, and the outside is not symbolic,
+        // since pi-copy moves its phase into the scalar
         .filter(|&(n, v)| {
-            g.edge_type(v, n) == EType::H && g.vertex_type(n) == VType::Z && g.phase(n).is_one()
+            g.edge_type(v, n) == EType::H
+                && g.vertex_type(n) == VType::Z
+                && g.phase(n).is_one()
+                && !g.has_symbolic_phase(n)
+                && !g.has_symbolic_phase(v)
+                && g.can_rewrite_at(&[v])
         })
         // Collect them in a hash-map keyed by the central vertex
         // so that multiple phases hanging off a single gadget
         // are only mapped to one phase to flip
         .collect::<FxHashMap<_, _>>();
 
-    // If this isn't empty, we matched at least one
-    let matched = !gadgets.is_empty();
-
+    let mut matched = false;
     for &v in gadgets.values() {
+        if !g.can_rewrite() {
+            break;
+        }
         // Use a pi-copy to remove all the pi phases.
         // We can use unchecked because we verified that
         // this vertex has the phase-gadget structure:
         // Z-spider connected to a single Z-spider with a H edge
         pi_copy_unchecked(g, v);
+        matched = true;
     }
 
     matched
 }
 
+/// Remove phase gadgets which act trivially
+///
+/// A gadget is a phase-free Z spider (the hub) connected by H-edges to a degree-1
+/// Z spider carrying the phase, and to one or more Z spiders (the legs). A gadget
+/// whose phase is zero is the identity up to a scalar, so it is removed. A gadget
+/// with a single leg is a phase gate on that leg, so its phase is moved onto the leg.
+pub fn remove_trivial_gadgets(g: &mut impl GraphLike) -> bool {
+    let mut removed = false;
+    for v in g.vertex_vec() {
+        if !g.can_rewrite() {
+            break;
+        }
+        if !g.contains_vertex(v) || g.degree(v) != 1 || g.vertex_type(v) != VType::Z {
+            continue;
+        }
+        let (w, et) = g.incident_edges(v).next().unwrap();
+        if et != EType::H
+            || g.vertex_type(w) != VType::Z
+            || !g.phase(w).is_zero()
+            || g.has_symbolic_phase(w)
+            || g.degree(w) < 2
+            || !g.can_rewrite_at(&[v, w])
+        {
+            continue;
+        }
+        let legs: Vec<(V, EType)> = g.incident_edges(w).filter(|&(n, _)| n != v).collect();
+        if !legs
+            .iter()
+            .all(|&(n, et)| et == EType::H && g.vertex_type(n) == VType::Z && g.degree(n) > 1)
+        {
+            continue;
+        }
+
+        let p = g.phase(v);
+        if legs.len() > 1 && (!p.is_zero() || g.has_symbolic_phase(v)) {
+            continue;
+        }
+
+        g.begin_rewrite("remove_trivial_gadget", &[v, w]);
+        if legs.len() == 1 {
+            g.fuse_phases(legs[0].0, v);
+            g.add_to_phase(legs[0].0, p);
+        } else {
+            g.scalar_mut().mul_sqrt2_pow(1 - legs.len() as i32);
+        }
+        g.remove_vertex(v);
+        g.remove_vertex(w);
+        g.end_rewrite();
+        removed = true;
+    }
+    removed
+}
+
+/// Simplify the phase gadgets of a graph-like diagram
+///
+/// This moves pi phases off of gadget hubs, fuses gadgets acting on the same set
+/// of spiders, and removes trivial gadgets, until none of these apply. The graph
+/// should be graph-like, e.g. the output of [clifford_simp].
+pub fn gadget_simp(g: &mut impl GraphLike) -> bool {
+    let mut got_match = false;
+    let mut m = true;
+    while m {
+        m = remove_gadget_pi(g);
+        m = fuse_gadgets(g) || m;
+        m = remove_trivial_gadgets(g) || m;
+        if m {
+            got_match = true;
+        }
+    }
+
+    got_match
+}
+
 pub fn full_simp(g: &mut impl GraphLike) -> bool {
+    full_simp_impl(g, false)
+}
+
+/// Like [full_simp], but only applies rewrites which preserve gflow
+///
+/// If the input has gflow, e.g. because it came from a circuit, so does the output,
+/// and a circuit can be extracted from it with [crate::extract]. Pivoting and local
+/// complementation of interior spiders, the unfusing done by [gen_pivot_simp], and
+/// the fusion of phase gadgets all preserve gflow (see Duncan et al., "Graph-theoretic
+/// Simplification of Quantum Circuits with the ZX-calculus", Quantum 4, 279 (2020)).
+/// The only difference from [full_simp] is that no spider is connected to two inputs
+/// or two outputs, which the extractor does not handle and which leaves no open graph
+/// to have gflow. Spiders which would be are not fused, and the normal edge between
+/// them is split by a phase-free spider instead, and identities are not removed if
+/// that would connect a boundary to such a spider. For a unitary diagram this never
+/// happens, so this only differs from [full_simp] on e.g. diagrams with some inputs
+/// plugged.
+pub fn full_simp_preserving_gflow(g: &mut impl GraphLike) -> bool {
+    full_simp_impl(g, true)
+}
+
+fn full_simp_impl(g: &mut impl GraphLike, preserve_gflow: bool) -> bool {
     let mut got_match = false;
     let mut m = true;
     while m {
-        m = clifford_simp(g);
+        m = clifford_simp_impl(g, preserve_gflow);
+        m = fuse_gadgets(g) || m;
+        m = remove_gadget_pi(g) || m;
+        if m {
+            got_match = true;
+        }
+    }
+
+    got_match
+}
+
+/// Like [id_simp], but finds matches in parallel, see [par_vertex_simp]
+pub fn par_id_simp(g: &mut impl GraphLike) -> bool {
+    par_vertex_simp(g, check_remove_id, remove_id_unchecked)
+}
+
+/// Like [spider_simp], but finds matches in parallel, see [par_edge_simp]
+pub fn par_spider_simp(g: &mut impl GraphLike) -> bool {
+    par_edge_simp(g, check_spider_fusion, spider_fusion_unchecked)
+}
+
+/// Like [pivot_simp], but finds matches in parallel, see [par_edge_simp]
+pub fn par_pivot_simp(g: &mut impl GraphLike) -> bool {
+    par_edge_simp(g, check_pivot, pivot_unchecked)
+}
+
+/// Like [local_comp_simp], but finds matches in parallel, see [par_vertex_simp]
+pub fn par_local_comp_simp(g: &mut impl GraphLike) -> bool {
+    par_vertex_simp(g, check_local_comp, local_comp_unchecked)
+}
+
+/// Like [interior_clifford_simp], but finds matches in parallel
+///
+/// The result satisfies the same guarantees, but is not necessarily the same
+/// graph, since the rules are applied in a different order.
+pub fn par_interior_clifford_simp(g: &mut impl GraphLike) -> bool {
+    par_spider_simp(g);
+    g.x_to_z();
+    let mut got_match = false;
+    let mut m = true;
+    while m {
+        m = par_id_simp(g);
+        m = par_spider_simp(g) || m;
+        m = par_pivot_simp(g) || m;
+        m = par_local_comp_simp(g) || m;
+        m = scalar_simp(g) || m;
+        if m {
+            got_match = true;
+        }
+    }
+
+    got_match
+}
+
+/// Like [clifford_simp], but uses [par_interior_clifford_simp]
+pub fn par_clifford_simp(g: &mut impl GraphLike) -> bool {
+    let mut got_match = false;
+    let mut m = true;
+    while m {
+        m = par_interior_clifford_simp(g);
+        m = gen_pivot_simp(g) || m;
+        if m {
+            got_match = true;
+        }
+    }
+
+    got_match
+}
+
+/// Like [full_simp], but uses [par_clifford_simp]
+///
+/// This is faster than [full_simp] on large graphs, with thousands of vertices.
+/// On small graphs, the overhead of the parallel matching outweighs the gain.
+pub fn par_full_simp(g: &mut impl GraphLike) -> bool {
+    let mut got_match = false;
+    let mut m = true;
+    while m {
+        m = par_clifford_simp(g);
         m = fuse_gadgets(g) || m;
         m = remove_gadget_pi(g) || m;
         if m {
@@ -261,8 +1071,10 @@ pub fn full_simp(g: &mut impl GraphLike) -> bool {
 mod tests {
     use super::*;
     use crate::circuit::*;
-    use crate::tensor::ToTensor;
+    use crate::extract::ToCircuit;
+    use crate::tensor::{QubitOps, Tensor4, ToTensor};
     use crate::vec_graph::Graph;
+    use num::Rational64;
 
     #[test]
     fn simp_cnot() {
@@ -405,4 +1217,364 @@ mod tests {
         println!("{}", g.to_dot());
         assert_eq!(g.to_tensor4(), h.to_tensor4());
     }
+
+    #[test]
+    fn match_and_apply() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+        let mut h = g.clone();
+
+        let ms = find_all_spider_fusion(&h);
+        assert!(!ms.is_empty());
+        assert_eq!(find_spider_fusion(&h), Some(ms[0]));
+        assert!(apply_spider_fusion(&mut h, ms[0]));
+        // the second vertex is gone, so the match is stale
+        assert!(!apply_spider_fusion(&mut h, ms[0]));
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        while let Some(m) = find_spider_fusion(&h) {
+            assert!(apply_spider_fusion(&mut h, m));
+        }
+        h.x_to_z();
+        while let Some(m) = find_remove_id(&h) {
+            assert!(apply_remove_id(&mut h, m));
+        }
+        while let Some(m) = find_spider_fusion(&h) {
+            assert!(apply_spider_fusion(&mut h, m));
+        }
+        assert!(find_all_remove_id(&h).is_empty());
+        assert_eq!(matches_remove_id(&h).next(), None);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        let mut found = false;
+        loop {
+            if let Some(m) = find_pivot(&h) {
+                assert!(apply_pivot(&mut h, m));
+            } else if let Some(m) = find_local_comp(&h) {
+                assert!(apply_local_comp(&mut h, m));
+            } else {
+                break;
+            }
+            found = true;
+        }
+        assert!(found);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn lazy_matches() {
+        use rand::rngs::StdRng;
+        use rand::seq::IteratorRandom;
+        use rand::SeedableRng;
+
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+
+        let all = find_all_spider_fusion(&g);
+        assert_eq!(matches_spider_fusion(&g).collect::<Vec<_>>(), all);
+        assert_eq!(matches_spider_fusion(&g).next(), find_spider_fusion(&g));
+        assert_eq!(matches_spider_fusion(&g).nth(2), Some(all[2]));
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut h = g.clone();
+        while let Some(m) = matches_spider_fusion(&h).choose(&mut rng) {
+            assert!(m.apply(&mut h));
+        }
+        assert!(find_spider_fusion(&h).is_none());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn trivial_gadgets() {
+        let mut g = Graph::new();
+        let legs: Vec<V> = (0..3)
+            .map(|_| g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4)))
+            .collect();
+        let bs: Vec<V> = (0..3).map(|_| g.add_vertex(VType::B)).collect();
+        for (&l, &b) in legs.iter().zip(&bs) {
+            g.add_edge(l, b);
+        }
+        g.set_outputs(bs);
+
+        // a phase-free gadget on all three legs
+        let hub = g.add_vertex(VType::Z);
+        let leaf = g.add_vertex(VType::Z);
+        g.add_edge_with_type(hub, leaf, EType::H);
+        for &l in &legs {
+            g.add_edge_with_type(hub, l, EType::H);
+        }
+
+        // a gadget on a single leg
+        let hub = g.add_vertex(VType::Z);
+        let leaf = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 2));
+        g.add_edge_with_type(hub, leaf, EType::H);
+        g.add_edge_with_type(hub, legs[1], EType::H);
+
+        let h = g.clone();
+        assert!(remove_trivial_gadgets(&mut g));
+        assert_eq!(g.num_vertices(), 6);
+        assert_eq!(g.phase(legs[1]), Rational64::new(3, 4).into());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert!(!remove_trivial_gadgets(&mut g));
+    }
+
+    #[test]
+    fn gadget_simp_circuit() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            cx q[0], q[1];
+            t q[1];
+            cx q[0], q[1];
+            h q[2];
+            cx q[1], q[0];
+            tdg q[0];
+            cx q[1], q[0];
+            cx q[0], q[1];
+            t q[1];
+            cx q[0], q[1];
+            cx q[1], q[2];
+            z q[2];
+            cx q[1], q[2];
+        "#,
+        )
+        .unwrap();
+        let g: Graph = c.to_graph();
+        let mut h = g.clone();
+        clifford_simp(&mut h);
+        let t = h.tcount();
+        gadget_simp(&mut h);
+        assert!(h.tcount() <= t);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn clifford_simp_guarantees() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(60)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+        assert!(!g.is_graph_like());
+
+        let is_interior = |h: &Graph, v: V| h.neighbors(v).all(|n| h.vertex_type(n) != VType::B);
+        let simps: [fn(&mut Graph) -> bool; 4] = [
+            interior_clifford_simp,
+            clifford_simp,
+            par_interior_clifford_simp,
+            par_clifford_simp,
+        ];
+        for simp in simps {
+            let mut h = g.clone();
+            assert!(simp(&mut h));
+            assert!(h.is_graph_like());
+            for v in h.vertices() {
+                if h.vertex_type(v) != VType::Z || !is_interior(&h, v) {
+                    continue;
+                }
+                assert!(!h.phase(v).is_proper_clifford());
+                if h.phase(v).is_pauli() {
+                    assert!(h
+                        .neighbors(v)
+                        .all(|n| !is_interior(&h, n) || !h.phase(n).is_pauli()));
+                }
+            }
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+            assert!(h.to_circuit().is_ok());
+        }
+    }
+
+    #[test]
+    fn copy_plugged() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(30)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&[BasisElem::Z0, BasisElem::Z1, BasisElem::X0, BasisElem::X1]);
+        let h = g.clone();
+        spider_simp(&mut g);
+        assert!(copy_simp(&mut g));
+        assert!(find_copy(&g).is_none());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn par_simp() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(6)
+            .depth(80)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+
+        let mut h = g.clone();
+        assert!(par_spider_simp(&mut h));
+        assert!(find_spider_fusion(&h).is_none());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        let mut h = g.clone();
+        assert!(par_full_simp(&mut h));
+        assert!(h.is_graph_like());
+        assert!(find_pivot(&h).is_none());
+        assert!(find_local_comp(&h).is_none());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert!(h.to_circuit().is_ok());
+    }
+
+    #[test]
+    fn pivot_gadget_boundary_simp() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(60)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+        let mut h = g.clone();
+        interior_clifford_simp(&mut h);
+        let t = h.tcount();
+
+        let mut m = true;
+        while m {
+            m = pivot_gadget_simp(&mut h);
+            m = pivot_boundary_simp(&mut h) || m;
+            m = interior_clifford_simp(&mut h) || m;
+        }
+        assert!(find_pivot_gadget(&h).is_none());
+        assert!(find_pivot_boundary(&h).is_none());
+        assert_eq!(h.tcount(), t);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert!(h.to_circuit().is_ok());
+    }
+
+    #[test]
+    fn full_simp_gflow() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(5)
+                .depth(40)
+                .p_t(0.2)
+                .with_cliffords()
+                .build();
+            let g: Graph = c.to_graph();
+            let mut h = g.clone();
+            full_simp_preserving_gflow(&mut h);
+            assert!(h.is_graph_like());
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+            assert!(h.to_circuit().is_ok());
+        }
+
+        // spiders are not fused if the result would be on two outputs
+        let mut g = Graph::new();
+        let vs: Vec<V> = (0..7).map(|_| g.add_vertex(VType::Z)).collect();
+        for &b in &[0, 1, 4, 5] {
+            g.set_vertex_type(vs[b], VType::B);
+        }
+        for &(s, t) in &[(0, 2), (2, 3), (3, 4), (1, 6), (6, 5)] {
+            g.add_edge(vs[s], vs[t]);
+        }
+        g.set_inputs(vec![vs[0], vs[1]]);
+        g.set_outputs(vec![vs[4], vs[5]]);
+        assert!(check_spider_fusion_gflow(&g, vs[2], vs[3]));
+        g.add_edge(vs[2], vs[5]);
+        assert!(check_spider_fusion(&g, vs[2], vs[3]));
+        assert!(!check_spider_fusion_gflow(&g, vs[2], vs[3]));
+
+        // copying onto a |0> ancilla: full_simp puts both outputs on one spider, so
+        // the result has no gflow, whereas the new strategy keeps them apart
+        let mut c = Circuit::new(2);
+        c.add_gate("t", vec![0]);
+        c.add_gate("cx", vec![0, 1]);
+        c.add_gate("t", vec![0]);
+        c.add_gate("t", vec![1]);
+        let mut g: Graph = c.to_graph();
+        g.plug_input(1, BasisElem::Z0);
+        let mut h = g.clone();
+        full_simp(&mut h);
+        assert!(crate::flow::gflow(&h).is_none());
+        let mut h = g.clone();
+        full_simp_preserving_gflow(&mut h);
+        assert!(h.is_graph_like());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert!(crate::flow::gflow(&h).is_some());
+    }
+
+    #[test]
+    fn fuse_gadgets_boundary_hub() {
+        // the hub 3 has a normal edge to 8, which leads to the output, so 3 and 4
+        // are not a phase gadget on 1, 2 like 5 and 6 are
+        let mut g = Graph::new();
+        let vs: Vec<V> = (0..10).map(|_| g.add_vertex(VType::Z)).collect();
+        g.set_vertex_type(vs[0], VType::B);
+        g.set_vertex_type(vs[9], VType::B);
+        g.set_phase(vs[4], Rational64::new(1, 4));
+        g.set_phase(vs[6], Rational64::new(1, 4));
+        g.add_edge(vs[0], vs[1]);
+        g.add_edge_with_type(vs[1], vs[2], EType::H);
+        for &(s, t) in &[(1, 3), (2, 3), (3, 4), (1, 5), (2, 5), (5, 6), (2, 7)] {
+            g.add_edge_with_type(vs[s], vs[t], EType::H);
+        }
+        g.add_edge(vs[3], vs[8]);
+        g.add_edge(vs[8], vs[9]);
+        g.set_inputs(vec![vs[0]]);
+        g.set_outputs(vec![vs[9]]);
+        let h = g.clone();
+        assert!(!fuse_gadgets(&mut g));
+        assert_eq!(g, h);
+    }
+
+    #[test]
+    fn zh_simp_ccz() {
+        // two CCZ gates, each written as an H-box on three Z spiders, cancel
+        let mut g = Graph::new();
+        let h0 = g.add_vertex_with_phase(VType::H, 1);
+        let h1 = g.add_vertex_with_phase(VType::H, 1);
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        for _ in 0..3 {
+            let i = g.add_vertex(VType::B);
+            let z0 = g.add_vertex(VType::Z);
+            let z1 = g.add_vertex(VType::Z);
+            let o = g.add_vertex(VType::B);
+            g.add_edge(i, z0);
+            g.add_edge(z0, z1);
+            g.add_edge(z1, o);
+            g.add_edge(z0, h0);
+            g.add_edge(z1, h1);
+            inputs.push(i);
+            outputs.push(o);
+        }
+        g.set_inputs(inputs);
+        g.set_outputs(outputs);
+        let t = g.to_tensor4();
+
+        assert!(zh_simp(&mut g));
+        assert_eq!(g.num_vertices(), 6);
+        assert_eq!(g.num_vertices_of_type(VType::H), 0);
+        assert_eq!(t, g.to_tensor4());
+        assert_eq!(t, Tensor4::ident(3));
+    }
 }
@@ -0,0 +1,116 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Amplitudes of circuits
+//!
+//! [Circuit::amplitude] computes a single amplitude `<x|C|0...0>` of a circuit by
+//! plugging basis states into the inputs and outputs of its graph, simplifying it
+//! with [full_simp] and summing the remaining stabiliser terms with a
+//! [Decomposer]. The cost grows exponentially with the number of non-Clifford
+//! phases left after simplification, but not with the number of qubits.
+//! Probabilities, including marginals and circuits with resets, are computed by
+//! [Circuit::probability].
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; t q[1];").unwrap();
+//! let a = c.amplitude(&[true, true]).complex_value();
+//! assert!((a.norm_sqr() - 0.5).abs() < 1e-6);
+//! assert!(c.amplitude(&[true, false]).complex_value().norm() < 1e-6);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::decompose::Decomposer;
+use crate::graph::*;
+use crate::scalar::*;
+use crate::simplify::full_simp;
+use crate::vec_graph::Graph;
+
+/// The scalar of a graph without boundaries
+///
+/// The graph is simplified, then decomposed into stabiliser terms.
+pub(crate) fn evaluate(mut g: Graph) -> ScalarN {
+    full_simp(&mut g);
+    let mut d = Decomposer::new(&g);
+    d.with_full_simp();
+    d.decomp_all();
+    d.scalar
+}
+
+/// The basis element for a bit
+fn basis_elem(b: bool) -> BasisElem {
+    if b {
+        BasisElem::Z1
+    } else {
+        BasisElem::Z0
+    }
+}
+
+impl Circuit {
+    /// The amplitude `<x|C|0...0>`, where `x` gives the value of each qubit
+    ///
+    /// The circuit may end with measurements, but should otherwise be unitary, and
+    /// its phases should be multiples of `π/4`. See the [module docs](crate::simulate).
+    pub fn amplitude(&self, x: &[bool]) -> ScalarN {
+        assert_eq!(
+            x.len(),
+            self.num_qubits(),
+            "Expected a value for each qubit"
+        );
+        let mut g: Graph = self.to_graph();
+        g.plug_inputs(&vec![BasisElem::Z0; self.num_qubits()]);
+        g.plug_outputs(&x.iter().map(|&b| basis_elem(b)).collect::<Vec<_>>());
+        evaluate(g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+
+    #[test]
+    fn amplitudes() {
+        for seed in 0..3 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(3)
+                .depth(25)
+                .clifford_t(0.3)
+                .build();
+            let mut g: Graph = c.to_graph();
+            g.plug_inputs(&[BasisElem::Z0; 3]);
+            let t = g.to_tensorf();
+            for (i, a) in t.indexed_iter() {
+                let x: Vec<bool> = (0..3).map(|q| i[q] == 1).collect();
+                let a1 = c.amplitude(&x).complex_value();
+                assert!((a1 - a).norm() < 1e-6);
+                assert!(
+                    (c.probability(&[(0, x[0]), (1, x[1]), (2, x[2])]) - a.norm_sqr()).abs() < 1e-6
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn clifford_amplitudes() {
+        let c =
+            Circuit::from_qasm("qreg q[3]; h q[0]; cx q[0], q[1]; cx q[1], q[2]; s q[2];").unwrap();
+        let a = c.amplitude(&[true, true, true]).complex_value();
+        assert!((a.im - 0.5f64.sqrt()).abs() < 1e-6);
+        assert!(c.amplitude(&[false, true, false]).complex_value().norm() < 1e-6);
+    }
+}
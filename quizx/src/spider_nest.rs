@@ -0,0 +1,303 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spider-nest identities
+//!
+//! In a graph-like diagram, a Z spider `v` with phase `α` contributes a factor
+//! `e^(iπα x_v)`, where `x_v` is the value of the spider, and a phase gadget with
+//! phase `α` on the spiders `S` contributes `e^(iπα p)`, where `p` is the parity
+//! of the `x_v` for `v` in `S`. So the phases of the diagram form a phase
+//! polynomial, with a term for each parity.
+//!
+//! For any four parities `p1, ..., p4`, the sum over the non-empty subsets `T` of
+//! `{1, ..., 4}` of `(-1)^(|T| - 1)` times the parity of the `p_i` for `i` in `T`
+//! is `8 p1 p2 p3 p4`. So adding `±π/4` to each of the 15 parities spanned by the
+//! `p_i`, with these signs, does not change the diagram. This is a spider nest
+//! identity. Each term whose phase was an odd multiple of `π/4` becomes Clifford,
+//! and each other term picks up a T, so if at least 8 of the 15 terms were T terms,
+//! this lowers the T-count.
+//!
+//! [spider_nest_simp] looks for such sets of terms and applies the identity until
+//! there are none left. It works best on a diagram which has been simplified with
+//! e.g. [crate::simplify::full_simp], so the phases are on phase gadgets, and the
+//! Clifford gadgets it leaves are removed by simplifying again afterwards.
+
+use crate::affine::{xor, Parity};
+use crate::graph::*;
+use crate::phase::Phase;
+use num::Zero;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// If `v` is the phase-carrying spider of a phase gadget, the spiders the gadget
+/// acts on, sorted
+fn gadget_parity(g: &impl GraphLike, v: V) -> Option<Parity> {
+    if g.degree(v) != 1 {
+        return None;
+    }
+    let w = g.neighbors(v).next().unwrap();
+    if g.vertex_type(w) != VType::Z
+        || !g.phase(w).is_zero()
+        || g.has_symbolic_phase(w)
+        || !g
+            .incident_edges(w)
+            .all(|(n, et)| g.vertex_type(n) == VType::Z && et == EType::H)
+    {
+        return None;
+    }
+    let mut p: Parity = g.neighbors(w).filter(|&n| n != v).collect();
+    p.sort();
+    Some(p)
+}
+
+/// The terms of the phase polynomial of a diagram
+///
+/// Each parity is mapped to its total phase and a spider whose phase adds to it.
+pub(crate) fn phase_terms(g: &impl GraphLike) -> FxHashMap<Parity, (Phase, V)> {
+    let mut terms: FxHashMap<Parity, (Phase, V)> = FxHashMap::default();
+    for v in g.vertices() {
+        if g.vertex_type(v) != VType::Z || g.has_symbolic_phase(v) {
+            continue;
+        }
+        let p = match gadget_parity(g, v) {
+            Some(p) => p,
+            None if g.phase(v).is_zero() => continue,
+            None => vec![v],
+        };
+        if p.is_empty() {
+            continue;
+        }
+        let phase = g.phase(v);
+        terms
+            .entry(p)
+            .and_modify(|t| t.0 += phase)
+            .or_insert((phase, v));
+    }
+    terms
+}
+
+/// The non-empty sums of the given parities, each with the number of summands
+pub(crate) fn span(gens: &[&Parity]) -> Vec<(Parity, u32)> {
+    (1..1usize << gens.len())
+        .map(|mask| {
+            let mut p = Parity::new();
+            for (i, q) in gens.iter().enumerate() {
+                if (mask >> i) & 1 == 1 {
+                    p = xor(&p, q);
+                }
+            }
+            (p, mask.count_ones())
+        })
+        .collect()
+}
+
+/// The spiders changed by applying the identity to a span
+pub(crate) fn touched(terms: &FxHashMap<Parity, (Phase, V)>, sp: &[(Parity, u32)]) -> Vec<V> {
+    let mut vs: FxHashSet<V> = FxHashSet::default();
+    for (p, _) in sp {
+        vs.extend(p);
+        if let Some(&(_, v)) = terms.get(p) {
+            vs.insert(v);
+        }
+    }
+    let mut vs: Vec<V> = vs.into_iter().collect();
+    vs.sort();
+    vs
+}
+
+pub(crate) fn add_gadget(g: &mut impl GraphLike, targets: &[V], phase: Phase) {
+    let hub = g.add_vertex(VType::Z);
+    let leaf = g.add_vertex_with_phase(VType::Z, phase);
+    g.add_edge_with_type(hub, leaf, EType::H);
+    for &v in targets {
+        g.add_edge_with_type(v, hub, EType::H);
+    }
+    g.scalar_mut().mul_sqrt2_pow(targets.len() as i32 - 1);
+}
+
+/// Find four parities such that at least 8 of the 15 terms they span have a phase
+/// which is an odd multiple of `π/4`
+///
+/// Each parity is given as a sorted list of spiders. Applying the spider nest
+/// identity to these, with [apply_spider_nest], lowers the number of such terms.
+pub fn find_spider_nest(g: &impl GraphLike) -> Option<Vec<Parity>> {
+    let terms = phase_terms(g);
+    let mut ts: Vec<&Parity> = terms
+        .iter()
+        .filter(|(_, (phase, _))| phase.is_t())
+        .map(|(p, _)| p)
+        .collect();
+    ts.sort();
+    let count = |sp: &[(Parity, u32)]| {
+        sp.iter()
+            .filter(|(p, _)| terms.get(p).is_some_and(|t| t.0.is_t()))
+            .count()
+    };
+
+    // A 4-dimensional span with 8 T terms has a 3-dimensional subspace with at least
+    // 4 of them, which is spanned by three of those.
+    for (i, &p0) in ts.iter().enumerate() {
+        for (j, &p1) in ts.iter().enumerate().skip(i + 1) {
+            for &p2 in &ts[j + 1..] {
+                let sp3 = span(&[p0, p1, p2]);
+                if sp3.iter().any(|(p, _)| p.is_empty()) || count(&sp3) < 4 {
+                    continue;
+                }
+                for &p3 in &ts {
+                    if sp3.iter().any(|(p, _)| p == p3) {
+                        continue;
+                    }
+                    let gens = [p0, p1, p2, p3];
+                    let sp4 = span(&gens);
+                    if count(&sp4) >= 8 && g.can_rewrite_at(&touched(&terms, &sp4)) {
+                        return Some(gens.iter().map(|&p| p.clone()).collect());
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Add `±π/4` to every term spanned by the given parities, as in the spider nest
+/// identity
+///
+/// The parities should be linearly independent. Terms which are not in the diagram
+/// yet are added as new phase gadgets, or as phases on single spiders.
+pub fn apply_spider_nest(g: &mut impl GraphLike, gens: &[Parity]) {
+    let terms = phase_terms(g);
+    let sp = span(&gens.iter().collect::<Vec<_>>());
+    g.begin_rewrite("spider_nest", &touched(&terms, &sp));
+    for (p, size) in sp {
+        let phase = if size % 2 == 1 {
+            Phase::from((1, 4))
+        } else {
+            Phase::from((-1, 4))
+        };
+        match terms.get(&p) {
+            Some(&(_, v)) => g.add_to_phase(v, phase),
+            None if p.len() == 1 => g.add_to_phase(p[0], phase),
+            None => add_gadget(g, &p, phase),
+        }
+    }
+    g.end_rewrite();
+}
+
+/// Apply spider nest identities until none of them lowers the T-count
+///
+/// See the [module docs](self). Each step searches over triples of T terms, so
+/// this is slow on diagrams with many of them.
+pub fn spider_nest_simp(g: &mut impl GraphLike) -> bool {
+    let mut got_match = false;
+    while g.can_rewrite() {
+        match find_spider_nest(g) {
+            Some(gens) => {
+                apply_spider_nest(g, &gens);
+                got_match = true;
+            }
+            None => break,
+        }
+    }
+    got_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::full_simp;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    /// Four wires with the terms of the spider nest identity for the given subsets
+    fn nest(masks: impl Iterator<Item = usize>) -> Graph {
+        let mut g = Graph::new();
+        let mut vs = vec![];
+        let (mut ins, mut outs) = (vec![], vec![]);
+        for _ in 0..4 {
+            let i = g.add_vertex(VType::B);
+            let v = g.add_vertex(VType::Z);
+            let o = g.add_vertex(VType::B);
+            g.add_edge(i, v);
+            g.add_edge(v, o);
+            ins.push(i);
+            vs.push(v);
+            outs.push(o);
+        }
+        g.set_inputs(ins);
+        g.set_outputs(outs);
+        for mask in masks {
+            let p: Vec<V> = (0..4)
+                .filter(|i| (mask >> i) & 1 == 1)
+                .map(|i| vs[i])
+                .collect();
+            let phase = if p.len() % 2 == 1 {
+                Phase::from((1, 4))
+            } else {
+                Phase::from((-1, 4))
+            };
+            if p.len() == 1 {
+                g.add_to_phase(p[0], phase);
+            } else {
+                add_gadget(&mut g, &p, phase);
+            }
+        }
+        g
+    }
+
+    #[test]
+    fn full_nest() {
+        let mut g = nest(1..16);
+        let id = nest(0..0);
+        assert_eq!(g.tcount(), 15);
+        assert_eq!(g.to_tensor4(), id.to_tensor4());
+        assert!(find_spider_nest(&g).is_some());
+        assert!(spider_nest_simp(&mut g));
+        assert_eq!(g.tcount(), 0);
+        assert_eq!(g.to_tensor4(), id.to_tensor4());
+    }
+
+    #[test]
+    fn partial_nest() {
+        let mut g = nest(1..10);
+        let h = g.clone();
+        assert!(spider_nest_simp(&mut g));
+        assert_eq!(g.tcount(), 6);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        let mut g = nest(1..8);
+        assert_eq!(find_spider_nest(&g), None);
+        assert!(!spider_nest_simp(&mut g));
+    }
+
+    #[test]
+    fn nest_random() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(60)
+                .clifford_t(0.3)
+                .build();
+            let mut g: Graph = c.to_graph();
+            full_simp(&mut g);
+            let h = g.clone();
+            spider_nest_simp(&mut g);
+            full_simp(&mut g);
+            assert!(g.tcount() <= h.tcount());
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+        }
+    }
+}
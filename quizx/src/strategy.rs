@@ -0,0 +1,549 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simplification strategies built from named passes
+//!
+//! A [Strategy] combines the passes in [crate::simplify] into a pipeline. Running
+//! a strategy returns whether it made progress, which the combinators use as
+//! follows:
+//!
+//! | Combinator                 | Syntax           | Behaviour                                     |
+//! |----------------------------|------------------|-----------------------------------------------|
+//! | [pass]                     | `name`           | runs the named pass once                      |
+//! | [seq]                      | `seq(a, b, ...)` | runs each in turn, progress if any progressed |
+//! | [repeat_until_fixpoint]    | `repeat(a)`      | runs `a` until it makes no progress           |
+//! | [try_]                     | `try(a)`         | runs `a`, but never reports progress          |
+//! | [with_limit]               | `limit(a, n)`    | like `repeat`, but at most `n` times          |
+//!
+//! Strategies can be written as strings in the syntax above, e.g. for use from a
+//! command line or from Python. See [Pass] for the passes and their names. A
+//! [CustomRule] can be used as a pass with [custom], or by name with
+//! [Strategy::parse_with_rules].
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::graph::*;
+//! # use quizx::strategy::*;
+//! # use quizx::vec_graph::Graph;
+//! let s: Strategy = "repeat(seq(interior_clifford, gadget, gen_pivot))".parse().unwrap();
+//! assert_eq!(
+//!     s,
+//!     repeat_until_fixpoint(seq([
+//!         pass(Pass::InteriorClifford),
+//!         pass(Pass::Gadget),
+//!         pass(Pass::GenPivot),
+//!     ]))
+//! );
+//!
+//! let c = Circuit::random().seed(1337).qubits(4).depth(30).p_t(0.2).with_cliffords().build();
+//! let mut g: Graph = c.to_graph();
+//! assert!(s.run(&mut g));
+//! ```
+
+use crate::custom_rule::CustomRule;
+use crate::graph::{GraphLike, VType};
+use crate::simplify::*;
+use crate::spider_nest::spider_nest_simp;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A pass from [crate::simplify] which can be used in a strategy
+///
+/// In the string syntax, a pass is referred to by its [Pass::name].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pass {
+    Id,
+    Spider,
+    Pivot,
+    GenPivot,
+    PivotGadget,
+    PivotBoundary,
+    LocalComp,
+    Scalar,
+    Copy,
+    Hadamard,
+    Zh,
+    FuseGadgets,
+    Gadget,
+    SpiderNest,
+    XToZ,
+    Flow,
+    InteriorClifford,
+    Clifford,
+    Full,
+    FullPreservingGflow,
+}
+
+impl Pass {
+    /// All of the passes
+    pub const ALL: &'static [Pass] = &[
+        Pass::Id,
+        Pass::Spider,
+        Pass::Pivot,
+        Pass::GenPivot,
+        Pass::PivotGadget,
+        Pass::PivotBoundary,
+        Pass::LocalComp,
+        Pass::Scalar,
+        Pass::Copy,
+        Pass::Hadamard,
+        Pass::Zh,
+        Pass::FuseGadgets,
+        Pass::Gadget,
+        Pass::SpiderNest,
+        Pass::XToZ,
+        Pass::Flow,
+        Pass::InteriorClifford,
+        Pass::Clifford,
+        Pass::Full,
+        Pass::FullPreservingGflow,
+    ];
+
+    /// The name of the pass in a strategy string
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Id => "id",
+            Pass::Spider => "spider",
+            Pass::Pivot => "pivot",
+            Pass::GenPivot => "gen_pivot",
+            Pass::PivotGadget => "pivot_gadget",
+            Pass::PivotBoundary => "pivot_boundary",
+            Pass::LocalComp => "local_comp",
+            Pass::Scalar => "scalar",
+            Pass::Copy => "copy",
+            Pass::Hadamard => "hadamard",
+            Pass::Zh => "zh",
+            Pass::FuseGadgets => "fuse_gadgets",
+            Pass::Gadget => "gadget",
+            Pass::SpiderNest => "spider_nest",
+            Pass::XToZ => "x_to_z",
+            Pass::Flow => "flow",
+            Pass::InteriorClifford => "interior_clifford",
+            Pass::Clifford => "clifford",
+            Pass::Full => "full",
+            Pass::FullPreservingGflow => "full_preserving_gflow",
+        }
+    }
+
+    /// The pass with the given name, if there is one
+    pub fn from_name(name: &str) -> Option<Pass> {
+        Pass::ALL.iter().copied().find(|p| p.name() == name)
+    }
+
+    /// Run the pass on a graph, returning true if it made progress
+    pub fn run(self, g: &mut impl GraphLike) -> bool {
+        match self {
+            Pass::Id => id_simp(g),
+            Pass::Spider => spider_simp(g),
+            Pass::Pivot => pivot_simp(g),
+            Pass::GenPivot => gen_pivot_simp(g),
+            Pass::PivotGadget => pivot_gadget_simp(g),
+            Pass::PivotBoundary => pivot_boundary_simp(g),
+            Pass::LocalComp => local_comp_simp(g),
+            Pass::Scalar => scalar_simp(g),
+            Pass::Copy => copy_simp(g),
+            Pass::Hadamard => hadamard_simp(g),
+            Pass::Zh => zh_simp(g),
+            Pass::FuseGadgets => fuse_gadgets(g),
+            Pass::Gadget => gadget_simp(g),
+            Pass::SpiderNest => spider_nest_simp(g),
+            Pass::XToZ => {
+                let num_x = g.stats().num_vertices_of_type(VType::X);
+                g.x_to_z();
+                g.stats().num_vertices_of_type(VType::X) < num_x
+            }
+            Pass::Flow => flow_simp(g),
+            Pass::InteriorClifford => interior_clifford_simp(g),
+            Pass::Clifford => clifford_simp(g),
+            Pass::Full => full_simp(g),
+            Pass::FullPreservingGflow => full_simp_preserving_gflow(g),
+        }
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A simplification strategy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Strategy {
+    /// A pass from [crate::simplify]
+    Pass(Pass),
+    /// Run each strategy in turn
+    Seq(Vec<Strategy>),
+    /// Run a strategy until it makes no progress
+    Repeat(Box<Strategy>),
+    /// Run a strategy, ignoring whether it made progress
+    Try(Box<Strategy>),
+    /// Run a strategy until it makes no progress, at most the given number of times
+    Limit(Box<Strategy>, usize),
+    /// A user-defined rule, see [CustomRule::simp]
+    Custom(Arc<CustomRule>),
+}
+
+/// A pass from [crate::simplify]
+pub fn pass(p: Pass) -> Strategy {
+    Strategy::Pass(p)
+}
+
+/// A user-defined rule, applied once from each vertex where it matches
+///
+/// Unlike the passes in [Pass], this does not run to a fixpoint, so it is
+/// usually wrapped in [repeat_until_fixpoint].
+pub fn custom(rule: impl Into<Arc<CustomRule>>) -> Strategy {
+    Strategy::Custom(rule.into())
+}
+
+/// Run each strategy in turn
+pub fn seq(ss: impl IntoIterator<Item = Strategy>) -> Strategy {
+    Strategy::Seq(ss.into_iter().collect())
+}
+
+/// Run a strategy until it makes no progress
+pub fn repeat_until_fixpoint(s: Strategy) -> Strategy {
+    Strategy::Repeat(Box::new(s))
+}
+
+/// Run a strategy, ignoring whether it made progress
+pub fn try_(s: Strategy) -> Strategy {
+    Strategy::Try(Box::new(s))
+}
+
+/// Run a strategy until it makes no progress, at most `n` times
+pub fn with_limit(s: Strategy, n: usize) -> Strategy {
+    Strategy::Limit(Box::new(s), n)
+}
+
+impl Strategy {
+    /// Run the strategy on a graph, returning true if it made progress
+    pub fn run(&self, g: &mut impl GraphLike) -> bool {
+        match self {
+            Strategy::Pass(p) => p.run(g),
+            Strategy::Seq(ss) => {
+                // run every strategy, even after one has made progress
+                let mut got_match = false;
+                for s in ss {
+                    got_match |= s.run(g);
+                }
+                got_match
+            }
+            Strategy::Repeat(s) => {
+                let mut got_match = false;
+                while s.run(g) {
+                    got_match = true;
+                }
+                got_match
+            }
+            Strategy::Try(s) => {
+                s.run(g);
+                false
+            }
+            Strategy::Limit(s, n) => {
+                let mut got_match = false;
+                for _ in 0..*n {
+                    if !s.run(g) {
+                        break;
+                    }
+                    got_match = true;
+                }
+                got_match
+            }
+            Strategy::Custom(rule) => rule.simp(g),
+        }
+    }
+
+    /// Parse a strategy which can refer to the given rules by name
+    pub fn parse_with_rules(s: &str, rules: &[Arc<CustomRule>]) -> Result<Strategy, StrategyError> {
+        let mut p = Parser { s, pos: 0, rules };
+        let strategy = p.strategy()?;
+        p.skip_ws();
+        if p.pos < s.len() {
+            return Err(StrategyError::Expected {
+                expected: "end of input",
+                pos: p.pos,
+            });
+        }
+        Ok(strategy)
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Pass(p) => write!(f, "{}", p),
+            Strategy::Seq(ss) => {
+                write!(f, "seq(")?;
+                for (i, s) in ss.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", s)?;
+                }
+                write!(f, ")")
+            }
+            Strategy::Repeat(s) => write!(f, "repeat({})", s),
+            Strategy::Try(s) => write!(f, "try({})", s),
+            Strategy::Limit(s, n) => write!(f, "limit({}, {})", s, n),
+            Strategy::Custom(rule) => write!(f, "{}", rule.name()),
+        }
+    }
+}
+
+/// An error parsing a [Strategy]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StrategyError {
+    #[error("Unknown pass '{0}'")]
+    UnknownPass(String),
+    #[error("Expected {expected} at position {pos}")]
+    Expected { expected: &'static str, pos: usize },
+    #[error("'{0}' takes {1} argument(s)")]
+    WrongArity(String, usize),
+    #[error("Invalid limit '{0}'")]
+    InvalidLimit(String),
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+    rules: &'a [Arc<CustomRule>],
+}
+
+impl<'a> Parser<'a> {
+    fn skip_ws(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.s[self.pos..].starts_with(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), StrategyError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(StrategyError::Expected {
+                expected,
+                pos: self.pos,
+            })
+        }
+    }
+
+    fn word(&mut self) -> &'a str {
+        self.skip_ws();
+        let rest = &self.s[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn strategy(&mut self) -> Result<Strategy, StrategyError> {
+        let name = self.word();
+        if name.is_empty() {
+            return Err(StrategyError::Expected {
+                expected: "a strategy",
+                pos: self.pos,
+            });
+        }
+
+        if !self.eat('(') {
+            return if let Some(p) = Pass::from_name(name) {
+                Ok(Strategy::Pass(p))
+            } else if let Some(rule) = self.rules.iter().find(|r| r.name() == name) {
+                Ok(Strategy::Custom(rule.clone()))
+            } else {
+                Err(StrategyError::UnknownPass(name.to_string()))
+            };
+        }
+
+        let s = match name {
+            "seq" => {
+                let mut ss = vec![self.strategy()?];
+                while self.eat(',') {
+                    ss.push(self.strategy()?);
+                }
+                Strategy::Seq(ss)
+            }
+            "repeat" => repeat_until_fixpoint(self.strategy()?),
+            "try" => try_(self.strategy()?),
+            "limit" => {
+                let s = self.strategy()?;
+                self.expect(',', "','")?;
+                let n = self.word();
+                let n = n
+                    .parse()
+                    .map_err(|_| StrategyError::InvalidLimit(n.to_string()))?;
+                with_limit(s, n)
+            }
+            _ => return Err(StrategyError::UnknownPass(name.to_string())),
+        };
+
+        if !self.eat(')') {
+            let arity = if name == "limit" { 2 } else { 1 };
+            return if name != "seq" && self.eat(',') {
+                Err(StrategyError::WrongArity(name.to_string(), arity))
+            } else {
+                Err(StrategyError::Expected {
+                    expected: "')'",
+                    pos: self.pos,
+                })
+            };
+        }
+        Ok(s)
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = StrategyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strategy::parse_with_rules(s, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn parse_and_print() {
+        let s = with_limit(
+            seq([
+                pass(Pass::InteriorClifford),
+                try_(pass(Pass::Gadget)),
+                repeat_until_fixpoint(pass(Pass::Pivot)),
+            ]),
+            10,
+        );
+        let text = "limit(seq(interior_clifford, try(gadget), repeat(pivot)), 10)";
+        assert_eq!(s.to_string(), text);
+        assert_eq!(text.parse::<Strategy>(), Ok(s.clone()));
+        assert_eq!(
+            " limit ( seq(interior_clifford,try( gadget ),repeat(pivot)) ,10 ) ".parse(),
+            Ok(s)
+        );
+
+        for &p in Pass::ALL {
+            assert_eq!(p.name().parse(), Ok(pass(p)));
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "foo".parse::<Strategy>(),
+            Err(StrategyError::UnknownPass("foo".into()))
+        );
+        assert_eq!(
+            "repeat(full, id)".parse::<Strategy>(),
+            Err(StrategyError::WrongArity("repeat".into(), 1))
+        );
+        assert_eq!(
+            "limit(full, x)".parse::<Strategy>(),
+            Err(StrategyError::InvalidLimit("x".into()))
+        );
+        assert!(matches!(
+            "seq(full".parse::<Strategy>(),
+            Err(StrategyError::Expected { .. })
+        ));
+        assert!(matches!(
+            "full)".parse::<Strategy>(),
+            Err(StrategyError::Expected { .. })
+        ));
+        assert!(matches!(
+            "".parse::<Strategy>(),
+            Err(StrategyError::Expected { .. })
+        ));
+    }
+
+    #[test]
+    fn run_strategies() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(50)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+
+        let mut h0 = g.clone();
+        full_simp(&mut h0);
+        let mut h1 = g.clone();
+        let s: Strategy = "repeat(seq(clifford, fuse_gadgets))".parse().unwrap();
+        assert!(s.run(&mut h1));
+        assert_eq!(h1.tcount(), h0.tcount());
+        assert_eq!(g.to_tensor4(), h1.to_tensor4());
+
+        // everything is already simplified
+        assert!(!s.run(&mut h1));
+        assert!(!try_(pass(Pass::Full)).run(&mut g.clone()));
+
+        let mut h2 = g.clone();
+        assert!(with_limit(pass(Pass::Spider), 1).run(&mut h2));
+        assert_eq!(g.to_tensor4(), h2.to_tensor4());
+
+        // x_to_z makes progress only if there are X spiders to change
+        let mut h3 = g.clone();
+        assert!(pass(Pass::XToZ).run(&mut h3));
+        assert!(!pass(Pass::XToZ).run(&mut h3));
+        assert_eq!(g.to_tensor4(), h3.to_tensor4());
+    }
+
+    #[test]
+    fn custom_strategies() {
+        let lhs: Graph = Circuit::from_qasm("qreg q[1]; s q[0]; s q[0];")
+            .unwrap()
+            .to_graph();
+        let rhs: Graph = Circuit::from_qasm("qreg q[1]; z q[0];").unwrap().to_graph();
+        let rule = Arc::new(CustomRule::new("s_fusion", lhs, rhs).unwrap());
+        assert_eq!(rule.verify(), Ok(()));
+
+        let text = "repeat(seq(s_fusion, id))";
+        let s = Strategy::parse_with_rules(text, std::slice::from_ref(&rule)).unwrap();
+        assert_eq!(
+            s,
+            repeat_until_fixpoint(seq([custom(rule.clone()), pass(Pass::Id)]))
+        );
+        assert_eq!(s.to_string(), text);
+        assert_eq!(
+            text.parse::<Strategy>(),
+            Err(StrategyError::UnknownPass("s_fusion".into()))
+        );
+
+        let c = Circuit::from_qasm("qreg q[1]; s q[0]; s q[0]; s q[0]; s q[0];").unwrap();
+        let g: Graph = c.to_graph();
+        let mut h = g.clone();
+        assert!(s.run(&mut h));
+        assert_eq!(h.num_vertices(), 4);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+}
@@ -0,0 +1,358 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SVG rendering of graphs, for quick visual inspection.
+//!
+//! Graphs coming from circuits carry row and qubit metadata, which gives a layered
+//! layout with qubits running left to right. Graphs without any metadata are laid
+//! out with a simple force-directed algorithm instead. Colours follow pyzx: green Z
+//! spiders, red X spiders, yellow H-boxes and dashed blue Hadamard edges.
+//!
+//! ```
+//! # use quizx::graph::*;
+//! # use quizx::vec_graph::Graph;
+//! let mut g = Graph::new();
+//! let v0 = g.add_vertex(VType::Z);
+//! let v1 = g.add_vertex_with_phase(VType::X, (1, 2));
+//! g.add_edge_with_type(v0, v1, EType::H);
+//!
+//! let svg = quizx::svg::to_svg(&g);
+//! assert!(svg.starts_with("<svg"));
+//! ```
+
+use crate::graph::*;
+use crate::phase::Phase;
+use num::{One, Zero};
+use rustc_hash::FxHashMap;
+use std::fmt::Write;
+
+/// How to position the vertices of a graph.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Use row and qubit metadata if any vertex has it, otherwise force-directed.
+    #[default]
+    Auto,
+    /// Place each vertex at (row, qubit).
+    Layered,
+    /// Ignore the metadata and compute a force-directed layout.
+    ForceDirected,
+}
+
+/// Options for [to_svg_with_options].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvgOptions {
+    /// The layout algorithm.
+    pub layout: Layout,
+    /// Distance in pixels between adjacent rows or qubits.
+    pub scale: f64,
+    /// Draw phase labels next to spiders.
+    pub show_phases: bool,
+    /// Draw vertex indices next to vertices.
+    pub show_indices: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            layout: Layout::Auto,
+            scale: 40.0,
+            show_phases: true,
+            show_indices: false,
+        }
+    }
+}
+
+/// Position vertices at (row, qubit)
+fn layered_layout(g: &impl GraphLike) -> FxHashMap<V, (f64, f64)> {
+    g.vertices()
+        .map(|v| (v, (g.row(v) as f64, g.qubit(v) as f64)))
+        .collect()
+}
+
+/// Position vertices using the Fruchterman-Reingold algorithm
+///
+/// This is deterministic: vertices start out evenly spaced on a circle.
+/// Positions are in units of the ideal edge length.
+fn force_directed_layout(g: &impl GraphLike) -> FxHashMap<V, (f64, f64)> {
+    const ITERATIONS: usize = 200;
+    let vs = g.vertex_vec();
+    let n = vs.len();
+    if n == 0 {
+        return FxHashMap::default();
+    }
+    let index: FxHashMap<V, usize> = vs.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+    let radius = (n as f64).sqrt();
+    let mut pos: Vec<(f64, f64)> = (0..n)
+        .map(|i| {
+            let a = 2.0 * std::f64::consts::PI * (i as f64) / (n as f64);
+            (radius * a.cos(), radius * a.sin())
+        })
+        .collect();
+    let edges: Vec<(usize, usize)> = g.edges().map(|(s, t, _)| (index[&s], index[&t])).collect();
+
+    let mut temp = radius;
+    for _ in 0..ITERATIONS {
+        let mut disp = vec![(0.0, 0.0); n];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let (dx, dy) = (pos[i].0 - pos[j].0, pos[i].1 - pos[j].1);
+                let d2 = (dx * dx + dy * dy).max(1e-4);
+                // repulsive force 1/d, in the direction of (dx, dy)
+                let (fx, fy) = (dx / d2, dy / d2);
+                disp[i].0 += fx;
+                disp[i].1 += fy;
+                disp[j].0 -= fx;
+                disp[j].1 -= fy;
+            }
+        }
+        for &(i, j) in &edges {
+            let (dx, dy) = (pos[i].0 - pos[j].0, pos[i].1 - pos[j].1);
+            let d = (dx * dx + dy * dy).sqrt();
+            // attractive force d^2, in the direction of -(dx, dy)
+            let (fx, fy) = (dx * d, dy * d);
+            disp[i].0 -= fx;
+            disp[i].1 -= fy;
+            disp[j].0 += fx;
+            disp[j].1 += fy;
+        }
+        for (p, &(dx, dy)) in pos.iter_mut().zip(&disp) {
+            let d = (dx * dx + dy * dy).sqrt();
+            if d > 0.0 {
+                let step = d.min(temp);
+                p.0 += dx / d * step;
+                p.1 += dy / d * step;
+            }
+        }
+        temp *= 0.97;
+    }
+
+    vs.into_iter().zip(pos).collect()
+}
+
+/// Compute the position of each vertex, in units of `SvgOptions::scale`
+pub fn layout(g: &impl GraphLike, layout: Layout) -> FxHashMap<V, (f64, f64)> {
+    let layered = match layout {
+        Layout::Auto => g.vertices().any(|v| g.row(v) != 0 || g.qubit(v) != 0),
+        Layout::Layered => true,
+        Layout::ForceDirected => false,
+    };
+    if layered {
+        layered_layout(g)
+    } else {
+        force_directed_layout(g)
+    }
+}
+
+/// Format a phase as a multiple of π, e.g. "3π/4"
+fn phase_label(p: Phase) -> String {
+    let r = p.to_rational();
+    let numer = match *r.numer() {
+        1 => String::new(),
+        -1 => "-".to_string(),
+        n => n.to_string(),
+    };
+    match *r.denom() {
+        1 => format!("{}π", numer),
+        d => format!("{}π/{}", numer, d),
+    }
+}
+
+/// Render a graph as SVG with the default options
+pub fn to_svg(g: &impl GraphLike) -> String {
+    to_svg_with_options(g, &SvgOptions::default())
+}
+
+/// Render a graph as SVG
+pub fn to_svg_with_options(g: &impl GraphLike, options: &SvgOptions) -> String {
+    let pos = layout(g, options.layout);
+    let margin = options.scale;
+    let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+    let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &(x, y) in pos.values() {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    if pos.is_empty() {
+        (min_x, min_y, max_x, max_y) = (0.0, 0.0, 0.0, 0.0);
+    }
+    let coord = |v: V| {
+        let (x, y) = pos[&v];
+        (
+            (x - min_x) * options.scale + margin,
+            (y - min_y) * options.scale + margin,
+        )
+    };
+    let width = (max_x - min_x) * options.scale + 2.0 * margin;
+    let height = (max_y - min_y) * options.scale + 2.0 * margin;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.0} {:.0}\">",
+        width, height, width, height
+    )
+    .unwrap();
+
+    for (s, t, et) in g.edges() {
+        let ((x0, y0), (x1, y1)) = (coord(s), coord(t));
+        let style = match et {
+            EType::N => "stroke=\"black\"",
+            EType::H => "stroke=\"#0088ff\" stroke-dasharray=\"5,3\"",
+            EType::Wio => "stroke=\"gray\"",
+        };
+        writeln!(
+            svg,
+            "  <line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" {} stroke-width=\"1.5\"/>",
+            x0, y0, x1, y1, style
+        )
+        .unwrap();
+    }
+
+    let r = options.scale / 5.0;
+    for v in g.vertices() {
+        let (x, y) = coord(v);
+        let d = g.vertex_data(v);
+        match d.ty {
+            VType::B => writeln!(
+                svg,
+                "  <circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"black\"/>",
+                x,
+                y,
+                r / 3.0
+            ),
+            VType::Z | VType::X => writeln!(
+                svg,
+                "  <circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\" stroke=\"black\"/>",
+                x,
+                y,
+                r,
+                if d.ty == VType::Z {
+                    "#ccffcc"
+                } else {
+                    "#ff8888"
+                }
+            ),
+            VType::H | VType::ZBox => writeln!(
+                svg,
+                "  <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" stroke=\"black\"/>",
+                x - r * 0.8,
+                y - r * 0.8,
+                r * 1.6,
+                r * 1.6,
+                if d.ty == VType::H {
+                    "yellow"
+                } else {
+                    "#ccffcc"
+                }
+            ),
+            VType::WInput | VType::WOutput => writeln!(
+                svg,
+                "  <polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"black\"/>",
+                x - r,
+                y - r,
+                x - r,
+                y + r,
+                x + r,
+                y
+            ),
+        }
+        .unwrap();
+
+        let default_phase = (d.ty == VType::H && d.phase.is_one()) || d.phase.is_zero();
+        if options.show_phases && d.ty != VType::B && !default_phase {
+            writeln!(
+                svg,
+                "  <text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-size=\"{:.0}\" fill=\"blue\" text-anchor=\"middle\">{}</text>",
+                x,
+                y - r * 1.4,
+                options.scale / 3.5,
+                phase_label(d.phase)
+            )
+            .unwrap();
+        }
+
+        if options.show_indices {
+            writeln!(
+                svg,
+                "  <text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-size=\"{:.0}\" fill=\"gray\" text-anchor=\"middle\">{}</text>",
+                x,
+                y + r * 2.2,
+                options.scale / 4.0,
+                v
+            )
+            .unwrap();
+        }
+    }
+
+    svg += "</svg>\n";
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn svg_circuit() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[2];
+            h q[0];
+            cx q[0], q[1];
+            t q[1];
+            "#,
+        )
+        .unwrap();
+        let g: Graph = c.to_graph();
+        let svg = to_svg(&g);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<line").count(), g.num_edges());
+        assert_eq!(svg.matches("<circle").count(), g.num_vertices());
+        assert!(svg.contains("stroke-dasharray"));
+        assert!(svg.contains(">π/4<"));
+
+        // layered layout respects the metadata
+        let pos = layout(&g, Layout::Auto);
+        for v in g.vertices() {
+            assert_eq!(pos[&v], (g.row(v) as f64, g.qubit(v) as f64));
+        }
+    }
+
+    #[test]
+    fn force_directed() {
+        let mut g = Graph::new();
+        let vs: Vec<_> = (0..6).map(|_| g.add_vertex(VType::Z)).collect();
+        for i in 0..6 {
+            g.add_edge(vs[i], vs[(i + 1) % 6]);
+        }
+        let pos = layout(&g, Layout::Auto);
+        assert_eq!(pos.len(), 6);
+        for &v in &vs {
+            for &w in &vs {
+                if v != w {
+                    let (dx, dy) = (pos[&v].0 - pos[&w].0, pos[&v].1 - pos[&w].1);
+                    assert!(dx * dx + dy * dy > 0.01);
+                }
+            }
+        }
+        assert!(to_svg(&Graph::new()).starts_with("<svg"));
+    }
+}
@@ -0,0 +1,496 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Phases with free parameters
+//!
+//! A [SymbolicGraph] attaches a sum of named parameters to the phases of some
+//! spiders, so the phase of such a spider is its ordinary [GraphLike::phase] plus
+//! the parameters. The simplifiers can be run on it as usual: rules which only
+//! move phases around, like spider fusion, gadget fusion and pivoting, keep the
+//! parameters with their phases, and rules which depend on the value of a phase,
+//! like removing identities or local complementation, skip symbolic spiders. See
+//! [GraphLike::has_symbolic_phase].
+//!
+//! ```
+//! # use quizx::graph::*;
+//! # use quizx::phase::Phase;
+//! # use quizx::simplify::full_simp;
+//! # use quizx::symbolic::*;
+//! # use quizx::vec_graph::Graph;
+//! let mut g = Graph::new();
+//! let vs: Vec<V> = (0..4).map(|_| g.add_vertex(VType::Z)).collect();
+//! g.set_vertex_type(vs[0], VType::B);
+//! g.set_vertex_type(vs[3], VType::B);
+//! g.add_edge(vs[0], vs[1]);
+//! g.add_edge(vs[1], vs[2]);
+//! g.add_edge(vs[2], vs[3]);
+//! g.set_inputs(vec![vs[0]]);
+//! g.set_outputs(vec![vs[3]]);
+//!
+//! let mut s = SymbolicGraph::new(g);
+//! s.add_param(vs[1], "a", 1);
+//! s.add_param(vs[2], "b", 1);
+//! full_simp(&mut s);
+//! let v = s.graph().vertices().find(|&v| s.has_symbolic_phase(v)).unwrap();
+//! assert_eq!(s.phase_string(v), "a + b");
+//!
+//! let h = s.substitute(|_| Phase::from((1, 4)));
+//! assert_eq!(h.phase(v), Phase::from((1, 2)));
+//! ```
+//!
+//! Circuits can also have parameters, in the angles of
+//! [ZPhase](crate::gate::GType::ZPhase) and [XPhase](crate::gate::GType::XPhase)
+//! gates made with [Gate::new_with_params](crate::gate::Gate::new_with_params). [Circuit::to_symbolic_graph] turns these into
+//! the parameters of the corresponding spiders, so a parameterised circuit can be
+//! simplified once and then given values with [SymbolicGraph::substitute], or the
+//! circuit itself can be given values with [Circuit::bind] and
+//! [Circuit::substitute].
+
+use crate::circuit::Circuit;
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use num::Zero;
+use rustc_hash::FxHashMap;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A sum of named parameters with integer coefficients
+///
+/// Parameters are angles in half-turns, like [Phase]. Rewriting only ever adds or
+/// negates phases, so the coefficients stay integers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParamSum {
+    terms: BTreeMap<String, i64>,
+}
+
+impl ParamSum {
+    /// The sum with a single parameter
+    pub fn param(name: &str) -> Self {
+        let mut s = ParamSum::default();
+        s.add(name, 1);
+        s
+    }
+
+    /// Add `coeff` times the parameter `name`
+    pub fn add(&mut self, name: &str, coeff: i64) {
+        let c = self.terms.entry(name.to_string()).or_insert(0);
+        *c += coeff;
+        if *c == 0 {
+            self.terms.remove(name);
+        }
+    }
+
+    /// Add another sum of parameters
+    pub fn add_sum(&mut self, other: &ParamSum) {
+        for (name, &coeff) in &other.terms {
+            self.add(name, coeff);
+        }
+    }
+
+    pub fn negate(&mut self) {
+        for c in self.terms.values_mut() {
+            *c = -*c;
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The coefficient of the parameter `name`
+    pub fn coeff(&self, name: &str) -> i64 {
+        self.terms.get(name).copied().unwrap_or(0)
+    }
+
+    /// Iterate over the parameters with non-zero coefficients, in order of name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.terms.iter().map(|(name, &c)| (name.as_str(), c))
+    }
+
+    /// The value of the sum, given a value for each parameter
+    pub fn eval(&self, value: impl Fn(&str) -> Phase) -> Phase {
+        self.iter()
+            .fold(Phase::zero(), |p, (name, c)| p + value(name) * c)
+    }
+}
+
+impl fmt::Display for ParamSum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        for (i, (name, c)) in self.iter().enumerate() {
+            let sign = if c < 0 { "-" } else { "+" };
+            if i > 0 {
+                write!(f, " {} ", sign)?;
+            } else if c < 0 {
+                write!(f, "-")?;
+            }
+            if c.abs() != 1 {
+                write!(f, "{}*", c.abs())?;
+            }
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// A graph whose spiders can have parameters in their phases, see the
+/// [module docs](self)
+///
+/// Scalars do not support parameters, so symbolic spiders are never removed into
+/// the scalar. Other code which reads phases, like extraction and tensor
+/// contraction, only sees the constant part, so parameters should be substituted
+/// with [SymbolicGraph::substitute] first.
+///
+/// Removing a spider which still has parameters panics, as they would be lost.
+/// Rules which remove spiders first move their phases with [GraphLike::fuse_phases],
+/// which takes the parameters along.
+#[derive(Debug, Clone)]
+pub struct SymbolicGraph<G: GraphLike> {
+    g: G,
+    params: FxHashMap<V, ParamSum>,
+}
+
+impl<G: GraphLike> SymbolicGraph<G> {
+    /// Wrap a graph, initially with no parameters
+    pub fn new(g: G) -> Self {
+        SymbolicGraph {
+            g,
+            params: FxHashMap::default(),
+        }
+    }
+
+    /// The underlying graph, whose phases are the constant parts
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    /// The underlying graph, and the parameters of each symbolic spider
+    pub fn into_parts(self) -> (G, FxHashMap<V, ParamSum>) {
+        (self.g, self.params)
+    }
+
+    /// Add `coeff` times the parameter `name` to the phase of `v`
+    pub fn add_param(&mut self, v: V, name: &str, coeff: i64) {
+        let p = self.params.entry(v).or_default();
+        p.add(name, coeff);
+        if p.is_zero() {
+            self.params.remove(&v);
+        }
+    }
+
+    /// The parameters in the phase of `v`, if it has any
+    pub fn params(&self, v: V) -> Option<&ParamSum> {
+        self.params.get(&v)
+    }
+
+    /// The full phase of `v`, e.g. `1/4 + a - b`
+    pub fn phase_string(&self, v: V) -> String {
+        let p = self.g.phase(v);
+        match self.params.get(&v) {
+            None => p.to_string(),
+            Some(s) if p.is_zero() => s.to_string(),
+            Some(s) => {
+                let s = s.to_string();
+                match s.strip_prefix('-') {
+                    Some(s) => format!("{} - {}", p, s),
+                    None => format!("{} + {}", p, s),
+                }
+            }
+        }
+    }
+
+    /// A copy of the underlying graph, with the given value for each parameter
+    pub fn substitute(&self, value: impl Fn(&str) -> Phase) -> G {
+        let mut g = self.g.clone();
+        for (&v, s) in &self.params {
+            g.add_to_phase(v, s.eval(&value));
+        }
+        g
+    }
+}
+
+impl<G: GraphLike> GraphLike for SymbolicGraph<G> {
+    fn new() -> Self {
+        SymbolicGraph::new(G::new())
+    }
+
+    delegate_graph_like!(g);
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        self.g.add_vertex(ty)
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        self.g.add_vertex_with_data(d)
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        assert!(
+            !self.params.contains_key(&v),
+            "Removed a spider with a symbolic phase"
+        );
+        self.g.remove_vertex(v)
+    }
+
+    fn fuse_phases(&mut self, v0: V, v1: V) {
+        if let Some(p1) = self.params.remove(&v1) {
+            let p0 = self.params.entry(v0).or_default();
+            p0.add_sum(&p1);
+            if p0.is_zero() {
+                self.params.remove(&v0);
+            }
+        }
+    }
+
+    fn phase_negate(&mut self, v: V) {
+        if let Some(p) = self.params.get_mut(&v) {
+            p.negate();
+        }
+    }
+
+    fn has_symbolic_phase(&self, v: V) -> bool {
+        self.params.contains_key(&v)
+    }
+}
+
+impl Circuit {
+    /// The names of the parameters in the circuit, in order
+    pub fn param_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .gates
+            .iter()
+            .flat_map(|g| g.params.iter().map(|(name, _)| name.to_string()))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Whether any gate has parameters
+    pub fn is_symbolic(&self) -> bool {
+        self.gates.iter().any(|g| !g.params.is_zero())
+    }
+
+    /// Gives the parameter `name` a value, leaving any other parameters
+    pub fn bind(&mut self, name: &str, value: impl Into<Phase>) {
+        let value = value.into();
+        for g in &mut self.gates {
+            let c = g.params.coeff(name);
+            if c != 0 {
+                g.phase += value * c;
+                g.params.add(name, -c);
+            }
+        }
+    }
+
+    /// A copy of the circuit, with the given value for each parameter
+    pub fn substitute(&self, value: impl Fn(&str) -> Phase) -> Circuit {
+        let mut c = self.clone();
+        for g in &mut c.gates {
+            g.phase += g.params.eval(&value);
+            g.params = ParamSum::default();
+        }
+        c
+    }
+
+    /// The graph of the circuit, where the spider for each gate with parameters has
+    /// those parameters
+    pub fn to_symbolic_graph<G: GraphLike>(&self) -> SymbolicGraph<G> {
+        let mut symbolic = vec![];
+        let g: G = self.to_graph_with_callback(false, |i, _, qs| {
+            let gate = &self.gates[i];
+            if !gate.params.is_zero() {
+                if let Some(v) = qs[gate.qs[0]] {
+                    symbolic.push((v, &gate.params));
+                }
+            }
+        });
+        let mut s = SymbolicGraph::new(g);
+        for (v, params) in symbolic {
+            for (name, coeff) in params.iter() {
+                s.add_param(v, name, coeff);
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::*;
+    use crate::simplify::*;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    /// The graph of a circuit, with the phase of each T gate replaced by a parameter
+    fn symbolic_graph(c: &Circuit, names: &[&str]) -> SymbolicGraph<Graph> {
+        let mut ts = vec![];
+        let g: Graph = c.to_graph_with_callback(false, |i, _, qs| {
+            if c.gates[i].t == T {
+                ts.push(qs[c.gates[i].qs[0]].unwrap());
+            }
+        });
+        let mut s = SymbolicGraph::new(g);
+        for (&v, name) in ts.iter().zip(names) {
+            s.set_phase(v, 0);
+            match name.strip_prefix('-') {
+                Some(name) => s.add_param(v, name, -1),
+                None => s.add_param(v, name, 1),
+            }
+        }
+        s
+    }
+
+    #[test]
+    fn param_sum() {
+        let mut s = ParamSum::param("b");
+        s.add("a", -2);
+        s.add("c", 1);
+        assert_eq!(s.to_string(), "-2*a + b + c");
+        s.negate();
+        assert_eq!(s.to_string(), "2*a - b - c");
+        s.add_sum(&ParamSum::param("b"));
+        assert_eq!(s.coeff("b"), 0);
+        assert_eq!(s.to_string(), "2*a - c");
+        assert_eq!(
+            s.eval(|p| if p == "a" {
+                Phase::from((1, 4))
+            } else {
+                Phase::from((1, 8))
+            }),
+            Phase::from((3, 8))
+        );
+        assert_eq!(ParamSum::default().to_string(), "0");
+    }
+
+    #[test]
+    #[should_panic(expected = "Removed a spider with a symbolic phase")]
+    fn remove_symbolic_spider() {
+        let mut g = Graph::new();
+        let v = g.add_vertex(VType::Z);
+        let mut s = SymbolicGraph::new(g);
+        s.add_param(v, "a", 1);
+        s.remove_vertex(v);
+    }
+
+    #[test]
+    fn symbolic_full_simp() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            t q[0];
+            cx q[0], q[1];
+            h q[2];
+            cx q[1], q[2];
+            t q[1];
+            s q[2];
+            cx q[0], q[2];
+            h q[0];
+            t q[2];
+            cx q[1], q[0];
+            t q[0];
+        "#,
+        )
+        .unwrap();
+
+        let mut s = symbolic_graph(&c, &["a", "b", "c", "d"]);
+        full_simp(&mut s);
+        assert!(s.graph().is_graph_like());
+        let mut sums: Vec<String> = s
+            .graph()
+            .vertices()
+            .filter_map(|v| s.params(v).map(|p| p.to_string()))
+            .collect();
+        sums.sort();
+        // every parameter is still somewhere
+        for name in ["a", "b", "c", "d"] {
+            assert!(sums.iter().any(|p| p.contains(name)));
+        }
+
+        // substituting pi/4 for every parameter gives back the circuit
+        let h = s.substitute(|_| Phase::from((1, 4)));
+        assert_eq!(h.to_tensor4(), c.to_tensor4());
+
+        // a parameter and its negation cancel, and then the spider can be removed
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[2];
+            t q[0];
+            cx q[0], q[1];
+            t q[0];
+        "#,
+        )
+        .unwrap();
+        let mut s = symbolic_graph(&c, &["a", "-a"]);
+        full_simp(&mut s);
+        assert!(s.graph().vertices().all(|v| !s.has_symbolic_phase(v)));
+        assert_eq!(s.graph().tcount(), 0);
+        let id = Circuit::from_qasm("qreg q[2]; cx q[0], q[1];").unwrap();
+        assert_eq!(s.graph().to_tensor4(), id.to_tensor4());
+    }
+
+    #[test]
+    fn symbolic_circuit() {
+        let a = || ParamSum::param("a");
+        let mut b = ParamSum::param("b");
+        b.add("a", -2);
+        let mut c = Circuit::new(2);
+        c.push(Gate::new(HAD, vec![0]));
+        c.push(Gate::new_with_params(ZPhase, vec![0], 0, a()));
+        c.push(Gate::new(CNOT, vec![0, 1]));
+        c.push(Gate::new_with_params(XPhase, vec![1], (1, 4), b));
+        c.push(Gate::new(CNOT, vec![0, 1]));
+        c.push(Gate::new_with_params(ZPhase, vec![0], (1, 2), a()));
+        assert!(c.is_symbolic());
+        assert_eq!(c.param_names(), vec!["a", "b"]);
+        assert_eq!(c.gates[1].to_qasm(), "rz((a)*pi) q[0]");
+        assert_eq!(c.gates[3].to_qasm(), "rx((0.25 + -2*a + b)*pi) q[1]");
+
+        let value = |p: &str| {
+            if p == "a" {
+                Phase::from((1, 4))
+            } else {
+                Phase::from((-1, 2))
+            }
+        };
+        let c1 = c.substitute(value);
+        assert!(!c1.is_symbolic());
+        assert_eq!(c1.gates[5].phase, Phase::from((3, 4)));
+
+        let mut c2 = c.clone();
+        c2.bind("a", Phase::from((1, 4)));
+        assert_eq!(c2.param_names(), vec!["b"]);
+        c2.bind("b", Phase::from((-1, 2)));
+        assert_eq!(c2, c1);
+
+        // the graph keeps the parameters through simplification
+        let mut s: SymbolicGraph<Graph> = c.to_symbolic_graph();
+        full_simp(&mut s);
+        let h = s.substitute(value);
+        assert_eq!(h.to_tensor4(), c1.to_tensor4());
+
+        // the adjoint negates the parameters
+        let mut c3 = c.to_adjoint();
+        c3.gates.extend(c.gates.iter().cloned());
+        let h: Graph = c3.to_symbolic_graph().substitute(value);
+        let id = Circuit::new(2);
+        assert_eq!(h.to_tensor4(), id.to_tensor4());
+    }
+}
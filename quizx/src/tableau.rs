@@ -0,0 +1,211 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stabiliser tableaux of Clifford circuits
+//!
+//! A Clifford unitary `U` is determined, up to a global phase, by the images
+//! `U X_q U†` and `U Z_q U†` of the single-qubit Paulis, which are again Pauli
+//! operators. A [Tableau] stores these images, and can be built up gate by gate
+//! from a circuit with [Tableau::from_circuit].
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::tableau::Tableau;
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1];").unwrap();
+//! let t = Tableau::from_circuit(&c).unwrap();
+//! assert_eq!(t.x[0].to_string(), "+ZI");
+//! assert_eq!(t.z[0].to_string(), "+XX");
+//! assert_eq!(t.z[1].to_string(), "+ZZ");
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::pauli_simp::PauliString;
+
+/// The images of `X_q` and `Z_q` under conjugation by a Clifford unitary, see the
+/// [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tableau {
+    /// The images `U X_q U†`
+    pub x: Vec<PauliString>,
+    /// The images `U Z_q U†`
+    pub z: Vec<PauliString>,
+}
+
+/// Conjugate `p` by a basic Clifford gate. Returns false if the gate is not one.
+fn conjugate(p: &mut PauliString, t: GType, qs: &[usize]) -> bool {
+    // p = i^k X^x Z^z, so signs come from reordering the images into this form
+    match (t, qs) {
+        (HAD, &[q]) => {
+            if p.x[q] && p.z[q] {
+                p.k = (p.k + 2) % 4;
+            }
+            std::mem::swap(&mut p.x[q], &mut p.z[q]);
+        }
+        (S, &[q]) | (Sdg, &[q]) => {
+            // S X S† = i X Z and S† X S = -i X Z
+            if p.x[q] {
+                p.k = (p.k + if t == S { 1 } else { 3 }) % 4;
+                p.z[q] ^= true;
+            }
+        }
+        (Z, &[q]) => {
+            if p.x[q] {
+                p.k = (p.k + 2) % 4;
+            }
+        }
+        (NOT, &[q]) => {
+            if p.z[q] {
+                p.k = (p.k + 2) % 4;
+            }
+        }
+        (CNOT, &[c, t]) => {
+            p.x[t] ^= p.x[c];
+            p.z[c] ^= p.z[t];
+        }
+        (CZ, &[a, b]) => {
+            if p.x[a] && p.x[b] {
+                p.k = (p.k + 2) % 4;
+            }
+            p.z[b] ^= p.x[a];
+            p.z[a] ^= p.x[b];
+        }
+        (SWAP, &[a, b]) => {
+            p.x.swap(a, b);
+            p.z.swap(a, b);
+        }
+        _ => return false,
+    }
+    true
+}
+
+impl Tableau {
+    pub fn identity(n: usize) -> Self {
+        Tableau {
+            x: (0..n).map(|q| PauliString::new(n, &[q], &[], 0)).collect(),
+            z: (0..n).map(|q| PauliString::new(n, &[], &[q], 0)).collect(),
+        }
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.x.len()
+    }
+
+    /// The tableau of a circuit of Clifford gates
+    ///
+    /// Returns None if the circuit has a gate which is not Clifford, or not
+    /// unitary. Barriers are ignored.
+    pub fn from_circuit(c: &Circuit) -> Option<Tableau> {
+        let mut t = Tableau::identity(c.num_qubits());
+        for g in &c.gates {
+            if !t.apply(g) {
+                return None;
+            }
+        }
+        Some(t)
+    }
+
+    /// Append a gate to the unitary. Returns false, leaving the tableau unchanged,
+    /// if the gate is not Clifford.
+    pub fn apply(&mut self, g: &Gate) -> bool {
+        if g.condition.is_some() {
+            return false;
+        }
+        // the gate as basic Clifford gates, with the qubits each one acts on
+        let qs = &g.qs[..];
+        let basic: Vec<(GType, &[usize])> = match g.t {
+            ZPhase | XPhase if !g.params.is_zero() || !g.phase.is_clifford() => return false,
+            ZPhase | XPhase => {
+                let n = (g.phase.to_rational() * 2).to_integer().rem_euclid(4) as usize;
+                let mut gs = vec![(S, qs); n];
+                if g.t == XPhase {
+                    gs.insert(0, (HAD, qs));
+                    gs.push((HAD, qs));
+                }
+                gs
+            }
+            XCX => vec![(HAD, &qs[..1]), (CNOT, qs), (HAD, &qs[..1])],
+            Barrier => vec![],
+            t => vec![(t, qs)],
+        };
+
+        let mut rows = self.clone();
+        for (t, qs) in basic {
+            for p in rows.x.iter_mut().chain(rows.z.iter_mut()) {
+                if !conjugate(p, t, qs) {
+                    return false;
+                }
+            }
+        }
+        *self = rows;
+        true
+    }
+
+    /// The image `U P U†` of a Pauli operator
+    pub fn image(&self, p: &PauliString) -> PauliString {
+        let mut r = PauliString::identity(self.num_qubits());
+        r.k = p.k;
+        for (q, img) in self.x.iter().enumerate() {
+            if p.x[q] {
+                r = r.mul(img);
+            }
+        }
+        for (q, img) in self.z.iter().enumerate() {
+            if p.z[q] {
+                r = r.mul(img);
+            }
+        }
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_qubit_gates() {
+        let t = Tableau::from_circuit(&Circuit::from_qasm("qreg q[1]; s q[0];").unwrap()).unwrap();
+        assert_eq!(t.x[0].to_string(), "+Y");
+        assert_eq!(t.z[0].to_string(), "+Z");
+
+        let t = Tableau::from_circuit(
+            &Circuit::from_qasm("qreg q[1]; h q[0]; s q[0]; h q[0]; x q[0];").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(t.x[0].to_string(), "+X");
+        assert_eq!(t.z[0].to_string(), "+Y");
+
+        let c = Circuit::from_qasm("qreg q[1]; t q[0];").unwrap();
+        assert_eq!(Tableau::from_circuit(&c), None);
+    }
+
+    #[test]
+    fn images_of_products() {
+        let c = Circuit::from_qasm("qreg q[2]; h q[0]; cz q[0], q[1]; s q[1]; swap q[0], q[1];")
+            .unwrap();
+        let t = Tableau::from_circuit(&c).unwrap();
+        for (a, b) in [("XY", "ZZ"), ("YI", "IX"), ("ZX", "YZ")] {
+            let p = PauliString::from_letters(a).unwrap();
+            let q = PauliString::from_letters(b).unwrap();
+            assert_eq!(t.image(&p.mul(&q)), t.image(&p).mul(&t.image(&q)));
+        }
+        for q in 0..2 {
+            assert!(!t.x[q].commutes(&t.z[q]));
+            assert!(t.x[q].is_hermitian() && t.z[q].is_hermitian());
+        }
+    }
+}
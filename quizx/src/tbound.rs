@@ -0,0 +1,189 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lower bounds on the T-count
+//!
+//! The stabiliser nullity of a state on `n` qubits is `n - log2 |S|`, where `S` is
+//! the group of Pauli operators which fix the state up to a sign. It is 0 for
+//! stabiliser states, is not changed by Clifford operations, and grows by at most
+//! 1 with each non-Clifford phase (Beverland et al., "Lower bounds on the
+//! non-Clifford resources for quantum computations", QST 5, 035009 (2020)). So the
+//! nullity of the tensor of a diagram, read as a state on all of its boundaries,
+//! is a lower bound on the number of non-Clifford spiders of any diagram equal to
+//! it, and in particular on the T-count of any circuit implementing it.
+//!
+//! Computing the nullity needs the full tensor, so [nullity_bound] only does this
+//! for diagrams with at most [MAX_NULLITY_QUBITS] boundaries. [TCountReport] puts
+//! the bound next to the T-count of a diagram, e.g. to see how close an optimiser
+//! got to the optimum.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::simplify::full_simp;
+//! # use quizx::tbound::TCountReport;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[2]; t q[0]; cx q[0], q[1]; t q[1];").unwrap();
+//! let mut g: Graph = c.to_graph();
+//! full_simp(&mut g);
+//! let r = TCountReport::new(&g);
+//! assert_eq!(r.tcount, 2);
+//! assert_eq!(r.lower_bound, Some(2));
+//! assert!(r.is_optimal());
+//! ```
+
+use crate::graph::*;
+use crate::tensor::ToTensor;
+use num::Complex;
+use rayon::prelude::*;
+use std::fmt;
+
+/// The largest number of boundaries for which [nullity_bound] computes a tensor
+pub const MAX_NULLITY_QUBITS: usize = 12;
+
+/// The stabiliser nullity of a state, given by its amplitudes
+///
+/// The amplitudes are indexed by the computational basis states, so there must be
+/// `2^n` of them for some `n`. They need not be normalised. Returns None for the
+/// zero vector.
+pub fn stabilizer_nullity(psi: &[Complex<f64>]) -> Option<usize> {
+    let dim = psi.len();
+    assert!(dim.is_power_of_two(), "expected 2^n amplitudes");
+    let n = dim.trailing_zeros() as usize;
+    let norm: f64 = psi.iter().map(|a| a.norm_sqr()).sum();
+    if norm < 1e-12 {
+        return None;
+    }
+
+    // <psi| X^a Z^b |psi> is the Walsh-Hadamard transform of conj(psi(x + a)) psi(x)
+    let count: usize = (0..dim)
+        .into_par_iter()
+        .map(|a| {
+            let mut f: Vec<Complex<f64>> = (0..dim)
+                .map(|x| psi[x ^ a].conj() * psi[x] / norm)
+                .collect();
+            let mut h = 1;
+            while h < dim {
+                for i in (0..dim).step_by(2 * h) {
+                    for j in i..i + h {
+                        let (u, v) = (f[j], f[j + h]);
+                        f[j] = u + v;
+                        f[j + h] = u - v;
+                    }
+                }
+                h *= 2;
+            }
+            f.iter().filter(|e| e.norm() > 1.0 - 1e-6).count()
+        })
+        .sum();
+
+    Some(n - count.trailing_zeros() as usize)
+}
+
+/// A lower bound on the T-count of a diagram, from the stabiliser nullity of its
+/// tensor
+///
+/// Returns None if the diagram has more than [MAX_NULLITY_QUBITS] boundaries, or
+/// is zero.
+pub fn nullity_bound(g: &impl GraphLike) -> Option<usize> {
+    if g.inputs().len() + g.outputs().len() > MAX_NULLITY_QUBITS {
+        return None;
+    }
+    let t = g.to_tensorf();
+    let psi: Vec<Complex<f64>> = t.iter().copied().collect();
+    stabilizer_nullity(&psi)
+}
+
+/// The T-count of a diagram, together with a lower bound on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TCountReport {
+    /// The number of spiders with a non-Clifford phase
+    pub tcount: usize,
+    /// A lower bound on the T-count of any diagram equal to this one, if one could
+    /// be computed, see [nullity_bound]
+    pub lower_bound: Option<usize>,
+}
+
+impl TCountReport {
+    pub fn new(g: &impl GraphLike) -> Self {
+        TCountReport {
+            tcount: g.tcount(),
+            lower_bound: nullity_bound(g),
+        }
+    }
+
+    /// Whether the T-count is known to be optimal
+    pub fn is_optimal(&self) -> bool {
+        self.lower_bound == Some(self.tcount)
+    }
+}
+
+impl fmt::Display for TCountReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lower_bound {
+            Some(b) => write!(f, "T-count: {} (lower bound: {})", self.tcount, b),
+            None => write!(f, "T-count: {} (lower bound: unknown)", self.tcount),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::full_simp;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn nullity() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(3)
+            .depth(30)
+            .clifford_t(0.0)
+            .build();
+        let g: Graph = c.to_graph();
+        assert_eq!(nullity_bound(&g), Some(0));
+
+        // |0> + e^(i pi/4) |1> has nullity 1
+        let s = std::f64::consts::FRAC_1_SQRT_2;
+        let psi = [Complex::new(1.0, 0.0), Complex::new(s, s)];
+        assert_eq!(stabilizer_nullity(&psi), Some(1));
+        assert_eq!(stabilizer_nullity(&[Complex::new(0.0, 0.0); 4]), None);
+
+        // a CCZ needs 7 T gates, but its nullity is 3
+        let c = Circuit::from_qasm("qreg q[3]; ccz q[0], q[1], q[2];").unwrap();
+        let g: Graph = c.to_graph();
+        assert_eq!(nullity_bound(&g), Some(3));
+    }
+
+    #[test]
+    fn tcount_report() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .clifford_t(0.2)
+            .build();
+        let mut g: Graph = c.to_graph();
+        let before = TCountReport::new(&g);
+        full_simp(&mut g);
+        let after = TCountReport::new(&g);
+        assert_eq!(before.lower_bound, after.lower_bound);
+        let b = after.lower_bound.unwrap();
+        assert!(b <= after.tcount && after.tcount <= before.tcount);
+        assert!(after.to_string().starts_with("T-count: "));
+    }
+}
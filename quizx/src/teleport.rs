@@ -0,0 +1,251 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Phase teleportation
+//!
+//! Simplifying the ZX-diagram of a circuit fuses non-Clifford phases which are
+//! far apart in the circuit. Phase teleportation runs the simplification only to
+//! learn which phases fuse, then moves each fused phase back onto one of the
+//! original gates, and removes the others. The circuit keeps its structure, so
+//! no extraction is needed, but its non-Clifford phase count goes down.
+//!
+//! See Kissinger and van de Wetering, "Reducing T-count with the ZX-calculus",
+//! Phys. Rev. A 102, 022406 (2020).
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use crate::simplify::full_simp;
+use crate::vec_graph::Graph;
+use rustc_hash::FxHashMap;
+
+/// A graph which follows a set of phases through rewriting
+///
+/// Each tracked vertex starts out carrying its own phase. Rewrite rules report
+/// when they fuse, move, or negate phases, via [GraphLike::fuse_phases] and
+/// [GraphLike::phase_negate], and the tracker records which of the original
+/// phases each vertex now carries, and with which sign.
+#[derive(Debug, Clone)]
+pub struct PhaseTracker<G: GraphLike> {
+    g: G,
+    origins: FxHashMap<V, Vec<(V, bool)>>,
+    retired: Vec<Vec<(V, bool)>>,
+}
+
+impl<G: GraphLike> PhaseTracker<G> {
+    /// Start tracking the phases of the given vertices of `g`
+    pub fn new(g: G, tracked: impl IntoIterator<Item = V>) -> Self {
+        PhaseTracker {
+            g,
+            origins: tracked.into_iter().map(|v| (v, vec![(v, false)])).collect(),
+            retired: vec![],
+        }
+    }
+
+    /// The underlying graph
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    /// The groups of tracked vertices whose phases were fused together
+    ///
+    /// Each group is a list of pairs `(v, negated)`, where `v` is a tracked vertex
+    /// of the original graph. Up to a Clifford phase, every group adds up to the
+    /// phase of a single spider, namely the sum of the phases of its members, with
+    /// those marked `negated` counted negatively. Every tracked vertex occurs in
+    /// exactly one group.
+    pub fn groups(&self) -> Vec<Vec<(V, bool)>> {
+        let mut groups = self.retired.clone();
+        groups.extend(self.origins.values().filter(|o| !o.is_empty()).cloned());
+        groups
+    }
+}
+
+impl<G: GraphLike> GraphLike for PhaseTracker<G> {
+    fn new() -> Self {
+        PhaseTracker::new(G::new(), [])
+    }
+
+    delegate_graph_like!(g);
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        self.g.add_vertex(ty)
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        self.g.add_vertex_with_data(d)
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        if let Some(o) = self.origins.remove(&v) {
+            if !o.is_empty() {
+                self.retired.push(o);
+            }
+        }
+        self.g.remove_vertex(v)
+    }
+
+    fn fuse_phases(&mut self, v0: V, v1: V) {
+        if let Some(o1) = self.origins.remove(&v1) {
+            self.origins.entry(v0).or_default().extend(o1);
+        }
+    }
+
+    fn phase_negate(&mut self, v: V) {
+        if let Some(o) = self.origins.get_mut(&v) {
+            for (_, negated) in o.iter_mut() {
+                *negated = !*negated;
+            }
+        }
+    }
+}
+
+/// Reduce the number of non-Clifford phases in a circuit by phase teleportation
+///
+/// The returned circuit is equal to `c` up to a global phase, and consists of the
+/// basic gates of `c` with some phase gates changed or removed. The global phase
+/// changes when phases which were negated relative to each other are fused, e.g.
+/// `T X T` becomes `X`, which differs from it by a factor of `e^{iπ/4}`.
+pub fn teleport_reduce(c: &Circuit) -> Circuit {
+    let c = c.to_basic_gates();
+
+    // find the spider for each phase gate
+    let mut gates = FxHashMap::default();
+    let g: Graph = c.to_graph_with_callback(false, |i, _, qs| {
+        let gate = &c.gates[i];
+        if matches!(gate.t, ZPhase | XPhase | T | Tdg) {
+            if let Some(v) = qs[gate.qs[0]] {
+                gates.insert(v, i);
+            }
+        }
+    });
+    gates.retain(|&v, _| !g.phase(v).is_clifford());
+
+    let phases: FxHashMap<V, Phase> = gates.keys().map(|&v| (v, g.phase(v))).collect();
+    let types: FxHashMap<V, VType> = gates.keys().map(|&v| (v, g.vertex_type(v))).collect();
+    let spiders: FxHashMap<usize, V> = gates.iter().map(|(&v, &i)| (i, v)).collect();
+    let mut t = PhaseTracker::new(g, gates.keys().copied());
+    full_simp(&mut t);
+
+    // put the sum of each group on its first gate, and zero on the others
+    let mut new_phases: FxHashMap<usize, Phase> = FxHashMap::default();
+    for group in t.groups() {
+        if group.len() < 2 {
+            continue;
+        }
+        let &(m, neg_m) = group.iter().min_by_key(|(v, _)| gates[v]).unwrap();
+        let mut p = Phase::zero();
+        for &(v, neg) in &group {
+            if neg == neg_m {
+                p += phases[&v];
+            } else {
+                p += -phases[&v];
+            }
+            new_phases.insert(gates[&v], Phase::zero());
+        }
+        new_phases.insert(gates[&m], p);
+    }
+
+    let mut c1 = Circuit::new(c.num_qubits());
+    for (i, gate) in c.gates.iter().enumerate() {
+        match new_phases.get(&i) {
+            None => c1.push(gate.clone()),
+            Some(p) if p.is_zero() => {}
+            Some(&p) => {
+                let ty = if types[&spiders[&i]] == VType::X {
+                    XPhase
+                } else {
+                    ZPhase
+                };
+                c1.push(Gate::new_with_phase(ty, gate.qs.clone(), p));
+            }
+        }
+    }
+    c1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::ToTensor;
+
+    #[test]
+    fn teleport_simple() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[2];
+            t q[0];
+            cx q[0], q[1];
+            t q[0];
+            h q[1];
+            tdg q[1];
+        "#,
+        )
+        .unwrap();
+        let c1 = teleport_reduce(&c);
+        assert_eq!(c.stats().non_cliff, 3);
+        assert_eq!(c1.stats().non_cliff, 1);
+        assert_eq!(c.to_tensor4(), c1.to_tensor4());
+    }
+
+    #[test]
+    fn teleport_random() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(5)
+                .depth(60)
+                .p_t(0.3)
+                .with_cliffords()
+                .build();
+            let c1 = teleport_reduce(&c);
+            assert!(c1.stats().non_cliff <= c.to_basic_gates().stats().non_cliff);
+            assert_eq!(c.to_tensor4(), c1.to_tensor4());
+        }
+    }
+
+    #[test]
+    fn teleport_global_phase() {
+        let c = Circuit::from_qasm("qreg q[1]; t q[0]; x q[0]; t q[0];").unwrap();
+        let c1 = teleport_reduce(&c);
+        assert_eq!(c1.stats().non_cliff, 0);
+        assert_ne!(c.to_tensor4(), c1.to_tensor4());
+        let s = Scalar4::from_phase((1, 4));
+        assert_eq!(c.to_tensor4(), c1.to_tensor4().mapv(|x| x * s));
+    }
+
+    #[test]
+    fn tracker_groups() {
+        let mut g = Graph::new();
+        let b0 = g.add_vertex(VType::B);
+        let v0 = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let v1 = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(b0, v0);
+        g.add_edge(v0, v1);
+        g.add_edge(v1, b1);
+        g.set_inputs(vec![b0]);
+        g.set_outputs(vec![b1]);
+
+        let mut t = PhaseTracker::new(g, [v0, v1]);
+        t.phase_negate(v1);
+        crate::basic_rules::spider_fusion(&mut t, v0, v1);
+        assert_eq!(t.groups(), vec![vec![(v0, false), (v1, true)]]);
+        assert_eq!(t.graph().num_vertices(), 3);
+    }
+}
@@ -15,6 +15,7 @@
 // limitations under the License.
 
 // use crate::scalar::*;
+use crate::affine::ApForm;
 use crate::circuit::*;
 use crate::graph::*;
 use crate::phase::Phase;
@@ -23,7 +24,7 @@ use ndarray::parallel::prelude::*;
 use ndarray::prelude::*;
 use ndarray::*;
 use num::{Complex, Rational64};
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::iter::FromIterator;
@@ -61,8 +62,11 @@ impl FromPhase for Complex<f64> {
 }
 
 /// Wraps all the traits we need to compute tensors from ZX-diagrams.
+///
+/// This only asks for [Clone], so that numbers of arbitrary precision, like
+/// [BigScalar], can be tensor elements.
 pub trait TensorElem:
-    Copy
+    Clone
     + Send
     + Sync
     + PartialEq
@@ -77,7 +81,7 @@ pub trait TensorElem:
 {
 }
 impl<T> TensorElem for T where
-    T: Copy
+    T: Clone
         + Send
         + Sync
         + PartialEq
@@ -98,9 +102,64 @@ impl<T> TensorElem for T where
 /// implements [TensorElem], as well as two convenience methods [ToTensor::to_tensor4]
 /// and [ToTensor::to_tensorf] for [Scalar4] and floating-point [Complex] numbers,
 /// respectively.
+///
+/// For Clifford diagrams, the entries of the tensor are read off from their
+/// [ApForm] in polynomial time, rather than by contraction. Other diagrams are
+/// always contracted, even if they have an AP form. Use [ApForm::from_graph] and
+/// the [ToTensor] implementation of [ApForm] to read their tensors off directly.
 pub trait ToTensor {
     fn to_tensor<A: TensorElem>(&self) -> Tensor<A>;
 
+    /// The tensor as a [SparseTensor], which only stores its non-zero entries
+    ///
+    /// By default, this computes the dense tensor first. For diagrams, the
+    /// contraction itself is sparse, so this also works for diagrams with too
+    /// many boundaries for a dense tensor, as long as few entries are non-zero.
+    fn to_sparse_tensor<A: TensorElem>(&self) -> SparseTensor<A> {
+        SparseTensor::from_dense(&self.to_tensor())
+    }
+
+    /// The tensor with some of its boundaries plugged with normalised basis
+    /// elements, where `None` leaves a boundary open
+    ///
+    /// The remaining indices are the open inputs, followed by the open outputs.
+    /// If every boundary is plugged, this is a single amplitude. By default, this
+    /// computes the full tensor first. For diagrams, the basis elements are
+    /// plugged in before contracting, so only the smaller tensor is computed.
+    ///
+    /// Panics if `inputs` and `outputs` together do not have one entry for each
+    /// index of the tensor.
+    fn to_tensor_with_plugs<A: TensorElem>(
+        &self,
+        inputs: &[Option<BasisElem>],
+        outputs: &[Option<BasisElem>],
+    ) -> Tensor<A> {
+        let mut t: Tensor<A> = self.to_tensor();
+        assert_eq!(
+            inputs.len() + outputs.len(),
+            t.ndim(),
+            "Wrong number of plugs for the tensor"
+        );
+        // go backwards, so plugging an index doesn't move the ones still to plug
+        let plugs: Vec<_> = inputs.iter().chain(outputs).enumerate().collect();
+        for (i, b) in plugs.into_iter().rev() {
+            if let Some(b) = b {
+                t = plug_index(&t, i, *b);
+            }
+        }
+        t
+    }
+
+    /// The tensor with its indices ordered by the given [TensorConvention]
+    ///
+    /// By default, the first half of the indices are taken to be the inputs, as
+    /// for circuits.
+    fn to_tensor_with_convention<A: TensorElem>(&self, conv: TensorConvention) -> Tensor<A> {
+        let t = self.to_tensor();
+        let n = t.ndim() / 2;
+        conv.reorder(t, n)
+    }
+
     /// Shorthand for `to_tensor::<Scalar4>()`
     fn to_tensor4(&self) -> Tensor<Scalar4> {
         self.to_tensor()
@@ -112,6 +171,58 @@ pub trait ToTensor {
     }
 }
 
+/// The order of the indices of a tensor, for comparing with other frameworks
+///
+/// quizx puts the inputs before the outputs, and numbers the qubits from the
+/// left, so reshaping the tensor of a unitary `U` to a matrix gives the transpose
+/// of the usual matrix of `U`, with qubit 0 as the most significant bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TensorConvention {
+    /// Put the outputs before the inputs, so the rows of the matrix are outputs
+    pub outputs_first: bool,
+    /// Reverse the qubits within the inputs and the outputs, so qubit 0 is the
+    /// least significant bit of the flattened index
+    pub little_endian: bool,
+}
+
+impl TensorConvention {
+    /// The order of [ToTensor::to_tensor]
+    pub const QUIZX: TensorConvention = TensorConvention {
+        outputs_first: false,
+        little_endian: false,
+    };
+
+    /// The order of PyZX, with the outputs first
+    pub const PYZX: TensorConvention = TensorConvention {
+        outputs_first: true,
+        little_endian: false,
+    };
+
+    /// The order of Qiskit, i.e. the usual matrix, or statevector, with qubit 0
+    /// as the least significant bit
+    pub const QISKIT: TensorConvention = TensorConvention {
+        outputs_first: true,
+        little_endian: true,
+    };
+
+    /// Reorder a tensor in the quizx order, with `num_inputs` inputs, to this
+    /// convention
+    pub fn reorder<A: TensorElem>(&self, t: Tensor<A>, num_inputs: usize) -> Tensor<A> {
+        let mut ins: Vec<usize> = (0..num_inputs).collect();
+        let mut outs: Vec<usize> = (num_inputs..t.ndim()).collect();
+        if self.little_endian {
+            ins.reverse();
+            outs.reverse();
+        }
+        let perm: Vec<usize> = if self.outputs_first {
+            outs.into_iter().chain(ins).collect()
+        } else {
+            ins.into_iter().chain(outs).collect()
+        };
+        t.permuted_axes(perm).as_standard_layout().into_owned()
+    }
+}
+
 pub trait QubitOps<A: TensorElem> {
     fn ident(q: usize) -> Self;
     fn delta(q: usize) -> Self;
@@ -155,7 +266,7 @@ impl<A: TensorElem> CompareTensors for Tensor<A> {
                 }
                 // if they are different, we cross-multiply to check scalar equivalence
                 else {
-                    t0 * *b1 == t1 * *b0
+                    t0 * b1.clone() == t1 * b0.clone()
                 }
             }
             // all-zero tensors of the same dimension are equal
@@ -231,7 +342,7 @@ impl<A: TensorElem> QubitOps<A> for Tensor<A> {
     fn hadamard() -> Tensor<A> {
         let n = A::one_over_sqrt2();
         let minus = A::from_phase(1);
-        array![[n, n], [n, minus * n]].into_dyn()
+        array![[n.clone(), n.clone()], [n.clone(), minus * n]].into_dyn()
     }
 
     fn delta_at(&mut self, qs: &[usize]) {
@@ -273,9 +384,9 @@ impl<A: TensorElem> QubitOps<A> for Tensor<A> {
         // iterate over the pieces together and apply a hadamard to each of the
         // pairs of elements
         par_azip!((a in &mut ma, b in &mut mb) {
-            let a1 = *a;
-            *a = n * (*a + *b);
-            *b = n * (a1 + minus * *b);
+            let a1 = a.clone();
+            *a = n.clone() * (a.clone() + b.clone());
+            *b = n.clone() * (a1 + minus.clone() * b.clone());
         });
     }
 
@@ -301,93 +412,495 @@ impl<A: TensorElem> QubitOps<A> for Tensor<A> {
     }
 }
 
+/// A tensor where every index has dimension 2, stored as a map from the non-zero
+/// entries to their values
+///
+/// Index `i` of an entry is bit `i` of its key, so there can be at most 64
+/// indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseTensor<A> {
+    ndim: usize,
+    entries: FxHashMap<u64, A>,
+}
+
+impl<A: TensorElem> SparseTensor<A> {
+    /// The tensor with no indices and the given value
+    pub fn scalar(a: A) -> Self {
+        let mut entries = FxHashMap::default();
+        if !a.is_zero() {
+            entries.insert(0, a);
+        }
+        SparseTensor { ndim: 0, entries }
+    }
+
+    pub fn ndim(&self) -> usize {
+        self.ndim
+    }
+
+    /// The number of non-zero entries
+    pub fn nnz(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The entry at the given index, where each element is 0 or 1
+    pub fn get(&self, ix: &[usize]) -> A {
+        assert_eq!(ix.len(), self.ndim, "Wrong number of indices");
+        let key = ix
+            .iter()
+            .enumerate()
+            .fold(0, |k, (i, &b)| k | ((b as u64) << i));
+        self.entries.get(&key).cloned().unwrap_or_else(A::zero)
+    }
+
+    /// Iterate over the non-zero entries, as keys with index `i` in bit `i`
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &A)> + '_ {
+        self.entries.iter().map(|(&k, a)| (k, a))
+    }
+
+    pub fn from_dense(t: &Tensor<A>) -> Self {
+        assert!(t.ndim() <= 64, "Too many indices for a sparse tensor");
+        let entries = t
+            .indexed_iter()
+            .filter(|(_, a)| !a.is_zero())
+            .map(|(ix, a)| {
+                let key = (0..t.ndim()).fold(0, |k, i| k | ((ix[i] as u64) << i));
+                (key, a.clone())
+            })
+            .collect();
+        SparseTensor {
+            ndim: t.ndim(),
+            entries,
+        }
+    }
+
+    pub fn to_dense(&self) -> Tensor<A> {
+        Tensor::from_shape_fn(vec![2; self.ndim], |ix| {
+            let key = (0..self.ndim).fold(0, |k, i| k | ((ix[i] as u64) << i));
+            self.entries.get(&key).cloned().unwrap_or_else(A::zero)
+        })
+    }
+
+    /// Add a spider with phase `p` as the new index 0
+    fn push_spider(&mut self, p: Phase) {
+        assert!(self.ndim < 64, "Too many indices for a sparse tensor");
+        let f = A::from_phase(p);
+        let mut entries = FxHashMap::default();
+        for (&k, a) in &self.entries {
+            entries.insert(k << 1, a.clone());
+            entries.insert((k << 1) | 1, a.clone() * f.clone());
+        }
+        self.entries = entries;
+        self.ndim += 1;
+    }
+
+    /// Keep the entries where indices `i` and `j` are equal
+    fn delta_at(&mut self, i: usize, j: usize) {
+        self.entries.retain(|&k, _| (k >> i) & 1 == (k >> j) & 1);
+    }
+
+    /// Connect indices `i` and `j` with a Hadamard edge, i.e. a CZ and a factor of
+    /// 1/sqrt(2)
+    fn hadamard_edge_at(&mut self, i: usize, j: usize) {
+        let n = A::one_over_sqrt2();
+        let minus = A::minus_one();
+        for (&k, a) in self.entries.iter_mut() {
+            *a *= n.clone();
+            if (k >> i) & (k >> j) & 1 == 1 {
+                *a *= minus.clone();
+            }
+        }
+    }
+
+    /// Move index `perm[i]` to index `i`
+    fn permute(&mut self, perm: &[usize]) {
+        self.entries = self
+            .entries
+            .iter()
+            .map(|(&k, a)| {
+                let key = (0..perm.len()).fold(0, |k1, i| k1 | (((k >> perm[i]) & 1) << i));
+                (key, a.clone())
+            })
+            .collect();
+    }
+
+    fn sum_axis(&mut self, i: usize) {
+        let low = (1 << i) - 1;
+        let mut entries: FxHashMap<u64, A> = FxHashMap::default();
+        for (&k, a) in &self.entries {
+            let e = entries
+                .entry((k & low) | (k.checked_shr(i as u32 + 1).unwrap_or(0) << i))
+                .or_insert_with(A::zero);
+            *e = e.clone() + a.clone();
+        }
+        entries.retain(|_, a| !a.is_zero());
+        self.entries = entries;
+        self.ndim -= 1;
+    }
+}
+
+/// The tensor in the middle of contracting a diagram
+///
+/// This starts out sparse, and becomes dense once it has at least
+/// [DENSE_MIN_NDIM] indices and more than 1/[DENSE_RATIO] of its entries are
+/// non-zero, so diagrams whose tensors stay sparse, like basis states and many
+/// other stabiliser diagrams, don't need the memory for a dense tensor.
+pub(crate) enum Partial<A> {
+    Sparse(SparseTensor<A>),
+    Dense(Tensor<A>),
+}
+
+/// See [Partial]
+const DENSE_MIN_NDIM: usize = 12;
+
+/// See [Partial]
+const DENSE_RATIO: usize = 8;
+
+/// The steps of contracting a diagram, see [contract_into]
+pub(crate) trait Contraction {
+    /// Add the index of a new spider with phase `p`, as index 0
+    ///
+    /// If `densify` is set, the tensor may switch to a dense representation.
+    fn push_spider(&mut self, p: Phase, densify: bool);
+    /// Connect indices `i` and `j` by a normal edge
+    fn delta_at(&mut self, i: usize, j: usize);
+    /// Connect indices `i` and `j` by a Hadamard edge
+    fn hadamard_edge_at(&mut self, i: usize, j: usize);
+    /// Sum out index `i`
+    fn sum_axis(&mut self, i: usize);
+    /// Move index `perm[i]` to index `i`
+    fn permute(&mut self, perm: &[usize]);
+}
+
+impl<A: TensorElem> Contraction for Partial<A> {
+    fn push_spider(&mut self, p: Phase, densify: bool) {
+        match self {
+            Partial::Sparse(t) => {
+                t.push_spider(p);
+                if densify
+                    && (DENSE_MIN_NDIM..64).contains(&t.ndim)
+                    && t.nnz().saturating_mul(DENSE_RATIO) > 1 << t.ndim
+                {
+                    *self = Partial::Dense(t.to_dense());
+                }
+            }
+            Partial::Dense(a) => {
+                // the stack! call computes the tensor product of a new spider
+                // (1, e^(i pi p)) with the existing tensor 'a'
+                let a1 = if p.is_zero() {
+                    stack![Axis(0), *a, *a]
+                } else {
+                    let f = A::from_phase(p);
+                    stack![Axis(0), *a, &*a * f]
+                };
+                *a = a1;
+            }
+        }
+    }
+
+    fn delta_at(&mut self, i: usize, j: usize) {
+        match self {
+            Partial::Sparse(t) => t.delta_at(i, j),
+            Partial::Dense(a) => a.delta_at(&[i, j]),
+        }
+    }
+
+    fn hadamard_edge_at(&mut self, i: usize, j: usize) {
+        match self {
+            Partial::Sparse(t) => t.hadamard_edge_at(i, j),
+            Partial::Dense(a) => {
+                a.cphase_at(1, &[i, j]);
+                // TODO incorporate with cphase_at
+                *a *= A::one_over_sqrt2();
+            }
+        }
+    }
+
+    fn sum_axis(&mut self, i: usize) {
+        match self {
+            Partial::Sparse(t) => t.sum_axis(i),
+            Partial::Dense(a) => *a = a.sum_axis(Axis(i)),
+        }
+    }
+
+    fn permute(&mut self, perm: &[usize]) {
+        match self {
+            Partial::Sparse(t) => t.permute(perm),
+            Partial::Dense(a) => {
+                *a = a
+                    .view()
+                    .permuted_axes(perm)
+                    .as_standard_layout()
+                    .into_owned()
+            }
+        }
+    }
+}
+
+impl<A: TensorElem> Partial<A> {
+    pub(crate) fn into_dense(self) -> Tensor<A> {
+        match self {
+            Partial::Sparse(t) => t.to_dense(),
+            Partial::Dense(a) => a,
+        }
+    }
+
+    fn into_sparse(self) -> SparseTensor<A> {
+        match self {
+            Partial::Sparse(t) => t,
+            Partial::Dense(a) => SparseTensor::from_dense(&a),
+        }
+    }
+}
+
+/// Compute the tensor of a diagram containing the H-box `v`
+///
+/// An H-box with label a is the all-ones tensor, plus (a - 1) times the tensor
+/// which is 1 on |1...1> and 0 elsewhere. Both are products of states, so the
+/// H-box is replaced by a Z spider on each leg in two copies of the diagram,
+/// and their tensors are added. This is exponential in the number of H-boxes,
+/// but it is only meant for testing.
+fn hbox_tensor<A: TensorElem>(g: &impl GraphLike, v: V) -> Tensor<A> {
+    let nhd = g.incident_edge_vec(v);
+    let mut g0 = g.clone();
+    g0.remove_vertex(v);
+    let mut g1 = g0.clone();
+    for &(n, et) in &nhd {
+        // |0> + |1>
+        let z0 = g0.add_vertex(VType::Z);
+        g0.add_edge_with_type(z0, n, et);
+        // sqrt(2) |1>
+        let z1 = g1.add_vertex_with_phase(VType::Z, 1);
+        g1.add_edge_with_type(z1, n, et.opposite());
+    }
+    g1.scalar_mut().mul_sqrt2_pow(-(nhd.len() as i32));
+    let a = A::from_phase(g.phase(v)) + A::minus_one();
+    g0.to_tensor::<A>() + g1.to_tensor::<A>() * a
+}
+
+/// Contract index `i` of a tensor with a normalised basis element
+fn plug_index<A: TensorElem>(t: &Tensor<A>, i: usize, b: BasisElem) -> Tensor<A> {
+    let t0 = t.index_axis(Axis(i), 0);
+    let t1 = t.index_axis(Axis(i), 1);
+    match b {
+        BasisElem::Z0 => t0.to_owned(),
+        BasisElem::Z1 => t1.to_owned(),
+        _ => (&t0 + &(&t1 * A::from_phase(b.phase()))) * A::one_over_sqrt2(),
+    }
+}
+
 impl<G: GraphLike + Clone> ToTensor for G {
     fn to_tensor<A: TensorElem>(&self) -> Tensor<A> {
+        graph_tensor(self, true).into_dense()
+    }
+
+    fn to_tensor_with_plugs<A: TensorElem>(
+        &self,
+        inputs: &[Option<BasisElem>],
+        outputs: &[Option<BasisElem>],
+    ) -> Tensor<A> {
+        assert_eq!(
+            inputs.len(),
+            self.inputs().len(),
+            "Wrong number of input plugs"
+        );
+        assert_eq!(
+            outputs.len(),
+            self.outputs().len(),
+            "Wrong number of output plugs"
+        );
         let mut g = self.clone();
-        g.x_to_z();
-        // H-boxes are not implemented yet
-        for v in g.vertices() {
-            let t = g.vertex_type(v);
-            if t != VType::B && t != VType::Z {
-                panic!("Vertex type currently unsupported: {:?}", t);
+        let mut plugged = 0;
+        for (bs, plugs) in [(g.inputs().clone(), inputs), (g.outputs().clone(), outputs)] {
+            for (&v, b) in bs.iter().zip(plugs) {
+                if let Some(b) = b {
+                    g.plug_vertex(v, *b);
+                    plugged += 1;
+                }
             }
         }
+        let open = |bs: &[V], plugs: &[Option<BasisElem>]| -> Vec<V> {
+            bs.iter()
+                .zip(plugs)
+                .filter(|(_, b)| b.is_none())
+                .map(|(&v, _)| v)
+                .collect()
+        };
+        g.set_inputs(open(self.inputs(), inputs));
+        g.set_outputs(open(self.outputs(), outputs));
+        g.scalar_mut().mul_sqrt2_pow(-plugged);
+        graph_tensor(&g, true).into_dense()
+    }
 
-        // initialise the trivial tensor
-        let mut a = Tensor::from_shape_vec(vec![], vec![A::one()]).unwrap();
-        let inp = g.inputs().iter().copied();
-        let mid = g.vertices().filter(|&v| g.vertex_type(v) != VType::B);
-        let outp = g.outputs().iter().copied();
-        let mut vs: Vec<V> = inp.chain(mid.chain(outp)).collect();
+    fn to_sparse_tensor<A: TensorElem>(&self) -> SparseTensor<A> {
+        graph_tensor(self, false).into_sparse()
+    }
+
+    fn to_tensor_with_convention<A: TensorElem>(&self, conv: TensorConvention) -> Tensor<A> {
+        conv.reorder(self.to_tensor(), self.inputs().len())
+    }
+}
+
+/// The tensor of a diagram, from its AP form if it is Clifford, see [ApForm]
+///
+/// This takes polynomial time for each non-zero entry, so it works for Clifford
+/// diagrams with any number of spiders. Otherwise, the diagram is contracted.
+fn graph_tensor<A: TensorElem>(g: &impl GraphLike, densify: bool) -> Partial<A> {
+    if !g.vertices().all(|v| g.phase(v).is_clifford()) {
+        return contract(g, densify);
+    }
+    match ApForm::from_graph(g) {
+        Some(ap) if densify => Partial::Dense(ap.to_tensor()),
+        Some(ap) => Partial::Sparse(ap.to_sparse_tensor()),
+        None => contract(g, densify),
+    }
+}
 
-        if vs.len() < g.num_vertices() {
-            panic!("All boundary vertices must be an input or an output");
+impl ToTensor for ApForm {
+    fn to_tensor<A: TensorElem>(&self) -> Tensor<A> {
+        let mut t = Tensor::zeros(vec![2; self.num_qubits()]);
+        for x in self.solutions() {
+            let ix: Vec<usize> = x.iter().map(|&b| b as usize).collect();
+            t[&ix[..]] = A::from_scalar(&self.amplitude(&x));
         }
+        t
+    }
 
-        vs.reverse();
-        // TODO: pick a good sort order for mid
+    fn to_sparse_tensor<A: TensorElem>(&self) -> SparseTensor<A> {
+        assert!(
+            self.num_qubits() <= 64,
+            "Too many indices for a sparse tensor"
+        );
+        let entries = self
+            .solutions()
+            .map(|x| {
+                let key = (0..x.len()).fold(0, |k, i| k | ((x[i] as u64) << i));
+                (key, A::from_scalar(&self.amplitude(&x)))
+            })
+            .filter(|(_, a)| !a.is_zero())
+            .collect();
+        SparseTensor {
+            ndim: self.num_qubits(),
+            entries,
+        }
+    }
 
-        let mut indexv: VecDeque<V> = VecDeque::new();
-        let mut seenv: FxHashMap<V, usize> = FxHashMap::default();
+    fn to_tensor_with_convention<A: TensorElem>(&self, conv: TensorConvention) -> Tensor<A> {
+        conv.reorder(self.to_tensor(), self.num_inputs)
+    }
+}
 
-        // let mut num_had = 0;
-        // let mut i = 1;
-        // let tot = vs.len();
+/// Contract the tensor of a diagram, which only becomes dense if `densify` is
+/// set, see [Partial]
+fn contract<A: TensorElem>(g: &impl GraphLike, densify: bool) -> Partial<A> {
+    let mut g = g.clone();
+    g.x_to_z();
+    if let Some(v) = g.vertices().find(|&v| g.vertex_type(v) == VType::H) {
+        return Partial::Dense(hbox_tensor(&g, v));
+    }
 
-        for v in vs {
-            // println!("contracting {} ({}/{})", v, i, tot);
-            // i += 1;
-            let p = g.phase(v);
+    let mut a = Partial::Sparse(SparseTensor::scalar(A::one()));
+    contract_into(&g, &mut a, densify);
 
-            // the stack! call computes the tensor product of a new spider
-            // (1, e^(i pi p)) with the existing tensor 'a'
-            if p.is_zero() {
-                a = stack![Axis(0), a, a];
-            } else {
-                let f = A::from_phase(p);
-                a = stack![Axis(0), a, &a * f];
+    let s = A::from_scalar(g.scalar());
+    match a {
+        Partial::Sparse(mut t) => {
+            for e in t.entries.values_mut() {
+                *e *= s.clone();
             }
+            t.entries.retain(|_, e| !e.is_zero());
+            Partial::Sparse(t)
+        }
+        Partial::Dense(t) => Partial::Dense(t * s),
+    }
+}
 
-            indexv.push_front(v);
-            let mut deg_v = 0;
-
-            for (w, et) in g.incident_edges(v) {
-                if let Some(deg_w) = seenv.get_mut(&w) {
-                    deg_v += 1;
-                    *deg_w += 1;
-
-                    let wi = indexv
-                        .iter()
-                        .position(|x| *x == w)
-                        .expect("w should be in indexv");
-
-                    if et == EType::N {
-                        a.delta_at(&[0, wi]);
-                    } else {
-                        a.cphase_at(1, &[0, wi]);
-                        // TODO incorporate with cphase_at
-                        a *= A::one_over_sqrt2();
-                        // num_had += 1;
-                    }
-
-                    if g.vertex_type(w) != VType::B && g.degree(w) == *deg_w {
-                        a = a.sum_axis(Axis(wi));
-                        indexv.remove(wi);
-                    }
-                }
+/// Contract a diagram of Z spiders and boundaries into `a`, which should start
+/// out as the scalar 1
+///
+/// The scalar of the diagram is not included. Afterwards, the indices of `a` are
+/// the inputs followed by the outputs.
+pub(crate) fn contract_into(g: &impl GraphLike, a: &mut impl Contraction, densify: bool) {
+    for v in g.vertices() {
+        let t = g.vertex_type(v);
+        if t != VType::B && t != VType::Z {
+            panic!("Vertex type currently unsupported: {:?}", t);
+        }
+    }
+
+    let boundaries: Vec<V> = g.inputs().iter().chain(g.outputs()).copied().collect();
+    let mid: Vec<V> = g
+        .vertices()
+        .filter(|&v| g.vertex_type(v) != VType::B)
+        .collect();
+
+    if boundaries.len() + mid.len() < g.num_vertices() {
+        panic!("All boundary vertices must be an input or an output");
+    }
+
+    // add each boundary just before its spider, so that its index is constrained
+    // straight away, and the indices are put in order at the end
+    // TODO: pick a good sort order for mid
+    let mut vs: Vec<V> = vec![];
+    let mut placed: FxHashSet<V> = FxHashSet::default();
+    for &v in &mid {
+        for w in g.neighbors(v) {
+            if g.vertex_type(w) == VType::B && placed.insert(w) {
+                vs.push(w);
             }
+        }
+        vs.push(v);
+    }
+    for &b in &boundaries {
+        if placed.insert(b) {
+            vs.push(b);
+        }
+    }
+
+    let mut indexv: VecDeque<V> = VecDeque::new();
+    let mut seenv: FxHashMap<V, usize> = FxHashMap::default();
+
+    for v in vs {
+        a.push_spider(g.phase(v), densify);
+        indexv.push_front(v);
+        let mut deg_v = 0;
+
+        for (w, et) in g.incident_edges(v) {
+            if let Some(deg_w) = seenv.get_mut(&w) {
+                deg_v += 1;
+                *deg_w += 1;
+
+                let wi = indexv
+                    .iter()
+                    .position(|x| *x == w)
+                    .expect("w should be in indexv");
 
-            if g.vertex_type(v) != VType::B && g.degree(v) == deg_v {
-                a = a.sum_axis(Axis(0));
-                indexv.remove(0);
+                if et == EType::N {
+                    a.delta_at(0, wi);
+                } else {
+                    a.hadamard_edge_at(0, wi);
+                }
+
+                if g.vertex_type(w) != VType::B && g.degree(w) == *deg_w {
+                    a.sum_axis(wi);
+                    indexv.remove(wi);
+                }
             }
+        }
 
-            seenv.insert(v, deg_v);
+        if g.vertex_type(v) != VType::B && g.degree(v) == deg_v {
+            a.sum_axis(0);
+            indexv.remove(0);
         }
 
-        let s = A::from_scalar(g.scalar()); // * A::sqrt2_pow(-num_had);
-        a * s
+        seenv.insert(v, deg_v);
     }
+
+    // only the boundaries are left, so put them in order
+    let perm: Vec<usize> = boundaries
+        .iter()
+        .map(|b| indexv.iter().position(|w| w == b).unwrap())
+        .collect();
+    a.permute(&perm);
 }
 
 impl ToTensor for Circuit {
@@ -402,6 +915,9 @@ impl ToTensor for Circuit {
         // computes the transpose of the circuit, but all the gates are self-
         // transposed, so we can get the circuit itself if we just reverse the order.
         for g in self.gates.iter().rev() {
+            if g.condition.is_some() {
+                panic!("Unsupported classically controlled gate: {}", g.to_qasm());
+            }
             match g.t {
                 ZPhase => a.cphase_at(g.phase, &g.qs),
                 Z | CZ | CCZ => a.cphase_at(1, &g.qs),
@@ -449,6 +965,13 @@ impl ToTensor for Circuit {
                 PostSelect => {
                     panic!("Unsupported gate: PostSelect")
                 }
+                Measure => {
+                    panic!("Unsupported gate: Measure")
+                }
+                Reset => {
+                    panic!("Unsupported gate: Reset")
+                }
+                Barrier => {}
                 UnknownGate => {} // unknown gates are quietly ignored
             }
         }
@@ -605,4 +1128,239 @@ mod tests {
 
         assert_eq!(t3, c3.to_tensor4());
     }
+
+    #[test]
+    fn tensor_hbox() {
+        // an H-box with label -1 on three Z spiders is CCZ
+        let mut g = Graph::new();
+        let h = g.add_vertex_with_phase(VType::H, 1);
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        for _ in 0..3 {
+            let i = g.add_vertex(VType::B);
+            let z = g.add_vertex(VType::Z);
+            let o = g.add_vertex(VType::B);
+            g.add_edge(i, z);
+            g.add_edge(z, o);
+            g.add_edge(z, h);
+            inputs.push(i);
+            outputs.push(o);
+        }
+        g.set_inputs(inputs);
+        g.set_outputs(outputs);
+
+        let c = Circuit::from_qasm(
+            r#"
+        qreg q[3];
+        ccz q[0], q[1], q[2];
+        "#,
+        )
+        .unwrap();
+        assert_eq!(g.to_tensor4(), c.to_tensor4());
+
+        // an arity-2 H-box is sqrt(2) times a Hadamard gate
+        let mut g = Graph::new();
+        let i = g.add_vertex(VType::B);
+        let h = g.add_vertex_with_phase(VType::H, 1);
+        let o = g.add_vertex(VType::B);
+        g.add_edge(i, h);
+        g.add_edge(h, o);
+        g.set_inputs(vec![i]);
+        g.set_outputs(vec![o]);
+        let mut t = Tensor4::hadamard();
+        t *= Scalar4::sqrt2_pow(1);
+        assert_eq!(g.to_tensor4(), t);
+    }
+
+    #[test]
+    fn sparse_tensor() {
+        // 14 boundaries, so to_tensor becomes dense part of the way through
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(7)
+            .depth(30)
+            .clifford_t(0.3)
+            .build();
+        let g: Graph = c.to_graph();
+        let t = c.to_tensor4();
+        let sparse = g.to_sparse_tensor::<Scalar4>();
+        assert!(Tensor::scalar_eq(&t, &sparse.to_dense()));
+        assert!(Tensor::scalar_eq(&t, &g.to_tensor4()));
+        assert_eq!(SparseTensor::from_dense(&t).to_dense(), t);
+    }
+
+    #[test]
+    fn clifford_tensors() {
+        // too many spiders to contract, but the AP form only needs each amplitude
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(30)
+            .depth(300)
+            .clifford_t(0.0)
+            .build();
+        let g: Graph = (&c + &c.inverse()).to_graph();
+        let mut x = vec![Some(BasisElem::Z0); 30];
+        x[3] = Some(BasisElem::Z1);
+        x[17] = Some(BasisElem::X1);
+        let amp: Tensor4 = g.to_tensor_with_plugs(&x, &x);
+        assert!(amp[[]].is_one());
+        let mut y = x.clone();
+        y[4] = Some(BasisElem::Z1);
+        let amp: Tensor4 = g.to_tensor_with_plugs(&x, &y);
+        assert!(amp[[]].is_zero());
+
+        // a GHZ state on 40 qubits has 2 non-zero entries
+        let mut c = Circuit::new(40);
+        c.add_gate("h", vec![0]);
+        for q in 1..40 {
+            c.add_gate("cx", vec![q - 1, q]);
+        }
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&[BasisElem::Z0; 40]);
+        let t = g.to_sparse_tensor::<Scalar4>();
+        assert_eq!(t.nnz(), 2);
+        assert_eq!(t.get(&[1; 40]), Scalar4::one_over_sqrt2());
+    }
+
+    #[test]
+    fn ap_form_tensors() {
+        // the AP form and contraction agree on random Clifford diagrams
+        for seed in 0..10 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(40)
+                .clifford_t(0.0)
+                .build();
+            let mut g: Graph = c.to_graph();
+            if seed % 2 == 1 {
+                crate::simplify::clifford_simp(&mut g);
+            }
+            let ap = ApForm::from_graph(&g).unwrap();
+            let t: Tensor4 = contract(&g, true).into_dense();
+            assert_eq!(ap.to_tensor4(), t);
+            assert_eq!(g.to_tensor4(), t);
+        }
+
+        // non-Clifford diagrams are contracted, even if they have an AP form
+        let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; t q[1]; cx q[0], q[1];").unwrap();
+        let g: Graph = c.to_graph();
+        let ap = ApForm::from_graph(&g).unwrap();
+        assert_eq!(ap.to_tensor4(), g.to_tensor4());
+        assert_eq!(g.to_tensor4(), c.to_tensor4());
+    }
+
+    #[test]
+    fn sparse_basis_state() {
+        // a basis state on 40 qubits is much too big for a dense tensor
+        let mut g = Graph::new();
+        let mut outputs = vec![];
+        for q in 0..40 {
+            let x = g.add_vertex_with_phase(VType::X, q % 2);
+            let o = g.add_vertex(VType::B);
+            g.add_edge(x, o);
+            outputs.push(o);
+        }
+        g.set_outputs(outputs);
+        let t = g.to_sparse_tensor::<Scalar4>();
+        assert_eq!(t.ndim(), 40);
+        assert_eq!(t.nnz(), 1);
+        let ix: Vec<usize> = (0..40).map(|q| q % 2).collect();
+        assert!(!t.get(&ix).is_zero());
+    }
+
+    #[test]
+    fn big_scalar_tensor() {
+        // pi/8 and pi/16 phases don't fit in Scalar4, which falls back to floats
+        let c = Circuit::from_qasm(
+            r#"
+        qreg q[3];
+        h q[0];
+        rz(pi/8) q[0];
+        cx q[0], q[1];
+        rz(3*pi/8) q[1];
+        ccz q[0], q[1], q[2];
+        rx(pi/16) q[2];
+        cx q[2], q[0];
+        t q[1];
+        "#,
+        )
+        .unwrap();
+        let t: Tensor<BigScalar> = c.to_tensor();
+        let g: Graph = c.to_graph();
+        assert!(Tensor::scalar_eq(&t, &g.to_tensor()));
+
+        let id: Tensor<BigScalar> = (&c + &c.inverse()).to_tensor();
+        assert_eq!(id, Tensor::ident(3));
+
+        let tf = c.to_tensorf();
+        for (a, b) in t.iter().zip(tf.iter()) {
+            assert!((a.complex_value() - b).norm() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn tensor_with_plugs() {
+        use BasisElem::*;
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(20)
+            .clifford_t(0.3)
+            .build();
+        let g: Graph = c.to_graph();
+        let t = c.to_tensor4();
+
+        // a single amplitude
+        let amp: Tensor4 =
+            g.to_tensor_with_plugs(&[Some(Z0); 4], &[Some(Z1), Some(Z0), Some(Z1), Some(Z1)]);
+        assert_eq!(amp.ndim(), 0);
+        assert_eq!(amp[[]], t[&IxDyn(&[0, 0, 0, 0, 1, 0, 1, 1])]);
+
+        // a column of the unitary, i.e. the output state for one input
+        let inputs = [Some(Z1), Some(Z0), Some(Z0), Some(Z1)];
+        let col: Tensor4 = g.to_tensor_with_plugs(&inputs, &[None; 4]);
+        let col1 = Tensor::from_shape_fn(vec![2; 4], |ix| {
+            t[&[1, 0, 0, 1, ix[0], ix[1], ix[2], ix[3]][..]]
+        });
+        assert_eq!(col, col1);
+        assert_eq!(col, c.to_tensor_with_plugs::<Scalar4>(&inputs, &[None; 4]));
+
+        // X basis elements, on inputs and outputs
+        let inputs = [Some(X0), None, Some(Z1), None];
+        let outputs = [None, Some(X1), None, None];
+        let t1: Tensor4 = g.to_tensor_with_plugs(&inputs, &outputs);
+        assert_eq!(t1.ndim(), 5);
+        assert_eq!(t1, c.to_tensor_with_plugs::<Scalar4>(&inputs, &outputs));
+        let mut h = g.clone();
+        h.plug_inputs(&[X0]);
+        assert_eq!(
+            t1,
+            h.to_tensor_with_plugs::<Scalar4>(&inputs[1..], &outputs)
+        );
+    }
+
+    #[test]
+    fn tensor_conventions() {
+        // CNOT sends |10> to |11>
+        let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1];").unwrap();
+        let g: Graph = c.to_graph();
+        let one = Scalar4::one();
+        let t: Tensor4 = c.to_tensor_with_convention(TensorConvention::QUIZX);
+        assert_eq!(t, c.to_tensor4());
+        assert_eq!(t[[1, 0, 1, 1]], one);
+        let t: Tensor4 = c.to_tensor_with_convention(TensorConvention::PYZX);
+        assert_eq!(t[[1, 1, 1, 0]], one);
+        let t: Tensor4 = c.to_tensor_with_convention(TensorConvention::QISKIT);
+        assert_eq!(t[[1, 1, 0, 1]], one);
+        assert_eq!(t, g.to_tensor_with_convention(TensorConvention::QISKIT));
+
+        // as a statevector, X on qubit 0 gives index 1 in Qiskit
+        let mut g: Graph = Circuit::from_qasm("qreg q[3]; x q[0];").unwrap().to_graph();
+        g.plug_inputs(&[BasisElem::Z0; 3]);
+        let t: Tensor4 = g.to_tensor_with_convention(TensorConvention::QISKIT);
+        assert_eq!(t.into_shape(8).unwrap()[1], one);
+        let t: Tensor4 = g.to_tensor_with_convention(TensorConvention::QUIZX);
+        assert_eq!(t.into_shape(8).unwrap()[4], one);
+    }
 }
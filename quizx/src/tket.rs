@@ -0,0 +1,483 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion to and from pytket's json serialization of circuits
+//!
+//! This is the format of `Circuit.to_dict()` and `Circuit.from_dict()` in pytket. A
+//! tket circuit is a list of commands, each an operation applied to a list of
+//! units. The qubits are numbered in the order of the circuit's `qubits`, and
+//! parameters are given in half-turns, which are rounded to a fraction with
+//! denominator at most 256. Symbolic parameters are not supported.
+//!
+//! The implicit permutation of a circuit, which tket introduces when it removes
+//! swaps, is made explicit with [SWAP] gates at the end of the circuit, and created
+//! qubits start with an [InitAncilla] gate. Barriers on qubits are kept as
+//! [Barrier] gates, and global phases are ignored, as circuits are only considered
+//! up to global phase.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::tket::{decode_circuit, encode_circuit};
+//! let c = Circuit::from_qasm("qreg q[2]; h q[0]; cx q[0], q[1]; rz(pi/8) q[1];").unwrap();
+//! let json = encode_circuit(&c).unwrap();
+//! assert_eq!(decode_circuit(&json).unwrap(), c);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+use crate::phase::Phase;
+use crate::qasm3::angle_to_phase;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::path::Path;
+
+/// An error that can occur when converting to or from pytket's json.
+#[derive(Debug, thiserror::Error)]
+pub enum TketError {
+    /// The json does not describe a tket circuit.
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    /// A tket operation or feature with no counterpart in a [Circuit].
+    #[error("tket {0} is not supported")]
+    UnsupportedOp(String),
+    /// A gate with no counterpart in tket.
+    #[error("Gate {0} cannot be written to tket")]
+    UnsupportedGate(String),
+    /// A command has the wrong arguments for its operation.
+    #[error("Invalid command {0}")]
+    InvalidCommand(String),
+    /// Error reading or writing a file.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// A qubit or bit, as a register name and an index
+type UnitId = (String, Vec<i64>);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TketCircuit {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default)]
+    bits: Vec<UnitId>,
+    commands: Vec<TketCommand>,
+    #[serde(default)]
+    created_qubits: Vec<UnitId>,
+    #[serde(default)]
+    discarded_qubits: Vec<UnitId>,
+    #[serde(default)]
+    implicit_permutation: Vec<(UnitId, UnitId)>,
+    #[serde(default)]
+    phase: String,
+    qubits: Vec<UnitId>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TketCommand {
+    args: Vec<UnitId>,
+    op: TketOp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TketOp {
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    params: Vec<String>,
+    /// The kinds of units of a barrier, "Q" for qubits and "C" for bits
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    signature: Vec<String>,
+}
+
+/// Parses a parameter in half-turns, such as `0.25` or `-1/8`
+fn param_to_phase(p: &str) -> Option<Phase> {
+    let x = match p.split_once('/') {
+        Some((n, d)) => n.trim().parse::<f64>().ok()? / d.trim().parse::<f64>().ok()?,
+        None => p.trim().parse::<f64>().ok()?,
+    };
+    Some(angle_to_phase(x * PI))
+}
+
+/// Writes a phase in half-turns, as an exact fraction
+fn phase_to_param(p: Phase) -> String {
+    let r = p.to_rational();
+    if r.is_integer() {
+        format!("{}", r.numer())
+    } else {
+        format!("{}/{}", r.numer(), r.denom())
+    }
+}
+
+impl TketOp {
+    fn new(ty: &str, params: &[Phase]) -> Self {
+        TketOp {
+            ty: ty.to_string(),
+            params: params.iter().map(|&p| phase_to_param(p)).collect(),
+            signature: vec![],
+        }
+    }
+
+    /// The gates making up this operation, on the given qubits
+    fn to_gates(&self, qs: Vec<usize>) -> Result<Vec<Gate>, TketError> {
+        let ty = self.ty.as_str();
+        let mut ps = vec![];
+        for p in &self.params {
+            ps.push(
+                param_to_phase(p).ok_or_else(|| {
+                    TketError::UnsupportedOp(format!("parameter {} of {}", p, ty))
+                })?,
+            );
+        }
+        let (num_qubits, num_params) = match ty {
+            "noop" | "Phase" => return Ok(vec![]),
+            "CnX" => (qs.len().clamp(1, 3), 0),
+            "X" | "Y" | "Z" | "H" | "S" | "Sdg" | "T" | "Tdg" | "V" | "Vdg" | "SX" | "SXdg" => {
+                (1, 0)
+            }
+            "Rx" | "Ry" | "Rz" | "U1" => (1, 1),
+            "U2" | "PhasedX" => (1, 2),
+            "U3" | "TK1" => (1, 3),
+            "CX" | "CY" | "CZ" | "SWAP" | "ZZMax" => (2, 0),
+            "CRz" | "CU1" | "ZZPhase" => (2, 1),
+            "CCX" | "CSWAP" => (3, 0),
+            _ => return Err(TketError::UnsupportedOp(ty.to_string())),
+        };
+        if qs.len() != num_qubits || ps.len() != num_params {
+            return Err(TketError::InvalidCommand(ty.to_string()));
+        }
+
+        let q = qs[0];
+        let rz = |q: usize, p: Phase| Gate::new_with_phase(ZPhase, vec![q], p);
+        let rx = |q: usize, p: Phase| Gate::new_with_phase(XPhase, vec![q], p);
+        // Ry(a) = S Rx(a) Sdg, as in the qasm prelude
+        let ry = |q: usize, p: Phase| {
+            vec![
+                Gate::new(Sdg, vec![q]),
+                Gate::new_with_phase(XPhase, vec![q], p),
+                Gate::new(S, vec![q]),
+            ]
+        };
+        let half = Phase::from((1, 2));
+        let gates = match ty {
+            "X" => vec![Gate::new(NOT, qs)],
+            "Y" => vec![Gate::new(Z, qs.clone()), Gate::new(NOT, qs)],
+            "Z" => vec![Gate::new(Z, qs)],
+            "H" => vec![Gate::new(HAD, qs)],
+            "S" => vec![Gate::new(S, qs)],
+            "Sdg" => vec![Gate::new(Sdg, qs)],
+            "T" => vec![Gate::new(T, qs)],
+            "Tdg" => vec![Gate::new(Tdg, qs)],
+            "V" | "SX" => vec![rx(q, half)],
+            "Vdg" | "SXdg" => vec![rx(q, -half)],
+            "Rx" => vec![rx(q, ps[0])],
+            "Ry" => ry(q, ps[0]),
+            "Rz" | "U1" => vec![rz(q, ps[0])],
+            "U2" | "U3" => {
+                // U3(a, b, c) = Rz(b) Ry(a) Rz(c), and U2(b, c) = U3(1/2, b, c)
+                let (a, b, c) = match ps[..] {
+                    [b, c] => (half, b, c),
+                    _ => (ps[0], ps[1], ps[2]),
+                };
+                let mut gates = vec![rz(q, c)];
+                gates.extend(ry(q, a));
+                gates.push(rz(q, b));
+                gates
+            }
+            "TK1" => vec![rz(q, ps[2]), rx(q, ps[1]), rz(q, ps[0])],
+            "PhasedX" => vec![rz(q, -ps[1]), rx(q, ps[0]), rz(q, ps[1])],
+            "CnX" => match qs.len() {
+                1 => vec![Gate::new(NOT, qs)],
+                2 => vec![Gate::new(CNOT, qs)],
+                _ => vec![Gate::new(TOFF, qs)],
+            },
+            "CX" => vec![Gate::new(CNOT, qs)],
+            "CY" => vec![
+                Gate::new(Sdg, vec![qs[1]]),
+                Gate::new(CNOT, qs.clone()),
+                Gate::new(S, vec![qs[1]]),
+            ],
+            "CZ" => vec![Gate::new(CZ, qs)],
+            "SWAP" => vec![Gate::new(SWAP, qs)],
+            "ZZMax" => vec![Gate::new_with_phase(ParityPhase, qs, half)],
+            "ZZPhase" => vec![Gate::new_with_phase(ParityPhase, qs, ps[0])],
+            "CRz" => vec![
+                rz(qs[1], ps[0] / 2),
+                Gate::new(CNOT, qs.clone()),
+                rz(qs[1], -ps[0] / 2),
+                Gate::new(CNOT, qs),
+            ],
+            "CU1" => vec![
+                rz(qs[0], ps[0] / 2),
+                rz(qs[1], ps[0] / 2),
+                Gate::new(CNOT, qs.clone()),
+                rz(qs[1], -ps[0] / 2),
+                Gate::new(CNOT, qs),
+            ],
+            "CCX" => vec![Gate::new(TOFF, qs)],
+            _ => vec![
+                // a controlled swap is a Toffoli between two CNOTs
+                Gate::new(CNOT, vec![qs[2], qs[1]]),
+                Gate::new(TOFF, qs.clone()),
+                Gate::new(CNOT, vec![qs[2], qs[1]]),
+            ],
+        };
+        Ok(gates)
+    }
+}
+
+/// The tket operations making up a gate
+fn tket_ops(g: &Gate) -> Result<Vec<(TketOp, Vec<usize>)>, TketError> {
+    let op = match g.t {
+        NOT => TketOp::new("X", &[]),
+        XPhase => TketOp::new("Rx", &[g.phase]),
+        Z => TketOp::new("Z", &[]),
+        S => TketOp::new("S", &[]),
+        Sdg => TketOp::new("Sdg", &[]),
+        T => TketOp::new("T", &[]),
+        Tdg => TketOp::new("Tdg", &[]),
+        ZPhase => TketOp::new("Rz", &[g.phase]),
+        HAD => TketOp::new("H", &[]),
+        CNOT => TketOp::new("CX", &[]),
+        CZ => TketOp::new("CZ", &[]),
+        SWAP => TketOp::new("SWAP", &[]),
+        TOFF => TketOp::new("CCX", &[]),
+        Barrier => TketOp {
+            signature: vec!["Q".to_string(); g.qs.len()],
+            ..TketOp::new("Barrier", &[])
+        },
+        ParityPhase if g.qs.len() == 2 => TketOp::new("ZZPhase", &[g.phase]),
+        CCZ | XCX | ParityPhase => {
+            let mut c = Circuit::new(0);
+            match g.t {
+                CCZ => {
+                    c.push(Gate::new(HAD, vec![g.qs[2]]));
+                    c.push(Gate::new(TOFF, g.qs.clone()));
+                    c.push(Gate::new(HAD, vec![g.qs[2]]));
+                }
+                XCX => {
+                    c.push(Gate::new(HAD, vec![g.qs[0]]));
+                    c.push(Gate::new(CNOT, g.qs.clone()));
+                    c.push(Gate::new(HAD, vec![g.qs[0]]));
+                }
+                _ => g.push_basic_gates(&mut c),
+            }
+            let mut ops = vec![];
+            for h in &c.gates {
+                ops.extend(tket_ops(h)?);
+            }
+            return Ok(ops);
+        }
+        InitAncilla | PostSelect | Measure | Reset | UnknownGate => {
+            return Err(TketError::UnsupportedGate(g.qasm_name().to_string()))
+        }
+    };
+    Ok(vec![(op, g.qs.clone())])
+}
+
+/// Reads a circuit from pytket's json serialization.
+///
+/// See the [module docs](self) for what is supported.
+pub fn decode_circuit(s: &str) -> Result<Circuit, TketError> {
+    let tc: TketCircuit = serde_json::from_str(s)?;
+    if !tc.discarded_qubits.is_empty() {
+        return Err(TketError::UnsupportedOp("discarded qubits".to_string()));
+    }
+    let qubit = |u: &UnitId| {
+        tc.qubits
+            .iter()
+            .position(|q| q == u)
+            .ok_or_else(|| TketError::InvalidCommand(format!("{}{:?}", u.0, u.1)))
+    };
+
+    let mut c = Circuit::new(tc.qubits.len());
+    for u in &tc.created_qubits {
+        c.push(Gate::new(InitAncilla, vec![qubit(u)?]));
+    }
+    for cmd in &tc.commands {
+        if cmd.op.ty == "Barrier" {
+            // barriers may also be on classical bits, which are dropped
+            let qs = cmd.args.iter().filter_map(|u| qubit(u).ok()).collect();
+            c.push(Gate::new_barrier(qs));
+            continue;
+        }
+        let qs = cmd.args.iter().map(qubit).collect::<Result<Vec<_>, _>>()?;
+        for g in cmd.op.to_gates(qs)? {
+            c.push(g);
+        }
+    }
+
+    // move the state of each qubit to its wire in the implicit permutation
+    let mut target: Vec<usize> = (0..c.num_qubits()).collect();
+    for (from, to) in &tc.implicit_permutation {
+        target[qubit(from)?] = qubit(to)?;
+    }
+    let mut state: Vec<usize> = (0..c.num_qubits()).collect();
+    for q in 0..c.num_qubits() {
+        while target[state[q]] != q {
+            let q1 = target[state[q]];
+            c.push(Gate::new(SWAP, vec![q, q1]));
+            state.swap(q, q1);
+        }
+    }
+    Ok(c)
+}
+
+/// Reads a circuit from a file containing pytket's json serialization.
+pub fn read_circuit(filename: &Path) -> Result<Circuit, TketError> {
+    let s = std::fs::read_to_string(filename)?;
+    decode_circuit(&s)
+}
+
+/// Returns pytket's json serialization of a circuit.
+///
+/// Qubit `i` becomes `q[i]`. [InitAncilla] gates at the start of a wire become
+/// created qubits, and [PostSelect] gates are not supported.
+pub fn encode_circuit(c: &Circuit) -> Result<String, TketError> {
+    let unit = |q: usize| ("q".to_string(), vec![q as i64]);
+    let mut created = vec![];
+    let mut used = vec![false; c.num_qubits()];
+    let mut commands = vec![];
+    for g in &c.gates {
+        if g.t == InitAncilla && !used[g.qs[0]] {
+            used[g.qs[0]] = true;
+            created.push(unit(g.qs[0]));
+            continue;
+        }
+        for (op, qs) in tket_ops(g)? {
+            for &q in &qs {
+                used[q] = true;
+            }
+            commands.push(TketCommand {
+                args: qs.into_iter().map(unit).collect(),
+                op,
+            });
+        }
+    }
+
+    let tc = TketCircuit {
+        name: None,
+        bits: vec![],
+        commands,
+        created_qubits: created,
+        discarded_qubits: vec![],
+        implicit_permutation: (0..c.num_qubits()).map(|q| (unit(q), unit(q))).collect(),
+        phase: "0.0".to_string(),
+        qubits: (0..c.num_qubits()).map(unit).collect(),
+    };
+    Ok(serde_json::to_string(&tc)?)
+}
+
+/// Writes pytket's json serialization of a circuit to a file.
+pub fn write_circuit(c: &Circuit, filename: &Path) -> Result<(), TketError> {
+    std::fs::write(filename, encode_circuit(c)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+    use num::Rational64;
+
+    #[test]
+    fn tket_roundtrip() {
+        let mut c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(40)
+            .clifford_t(0.2)
+            .build();
+        c.push(Gate::new(TOFF, vec![2, 4, 0]));
+        c.push(Gate::new(SWAP, vec![1, 2]));
+        c.push(Gate::new_with_phase(
+            XPhase,
+            vec![1],
+            Rational64::new(-2, 3),
+        ));
+        c.push(Gate::new_with_phase(ZPhase, vec![1], Rational64::new(1, 8)));
+        c.push(Gate::new_with_phase(
+            ParityPhase,
+            vec![3, 0],
+            Rational64::new(3, 4),
+        ));
+        let json = encode_circuit(&c).unwrap();
+        assert_eq!(decode_circuit(&json).unwrap(), c);
+
+        let mut c = Circuit::new(3);
+        c.push(Gate::new(InitAncilla, vec![2]));
+        c.push(Gate::new(CCZ, vec![0, 1, 2]));
+        c.push(Gate::new(XCX, vec![0, 1]));
+        let c1 = decode_circuit(&encode_circuit(&c).unwrap()).unwrap();
+        assert_eq!(c1.gates[0], c.gates[0]);
+        let g: Graph = c.to_graph();
+        let g1: Graph = c1.to_graph();
+        assert!(Tensor4::scalar_compare(&g, &g1));
+
+        c.push(Gate::new(PostSelect, vec![2]));
+        assert!(encode_circuit(&c).is_err());
+    }
+
+    #[test]
+    fn tket_decode() {
+        let json = r#"{
+          "bits": [],
+          "commands": [
+            {"args": [["q", [1]]], "op": {"type": "U3", "params": ["0.5", "1/4", "-0.25"]}},
+            {"args": [["q", [0]], ["q", [1]]], "op": {"type": "CRz", "params": ["0.5"]}},
+            {"args": [["q", [0]], ["q", [1]]], "op": {"type": "Barrier"}},
+            {"args": [["q", [0]]], "op": {"type": "PhasedX", "params": ["0.5", "0.25"]}},
+            {"args": [["q", [1]], ["q", [2]]], "op": {"type": "CY"}}
+          ],
+          "created_qubits": [],
+          "discarded_qubits": [],
+          "implicit_permutation": [
+            [["q", [0]], ["q", [1]]],
+            [["q", [1]], ["q", [2]]],
+            [["q", [2]], ["q", [0]]]
+          ],
+          "phase": "0.0",
+          "qubits": [["q", [0]], ["q", [1]], ["q", [2]]]
+        }"#;
+        let c = decode_circuit(json).unwrap();
+
+        let qasm = "qreg q[3];
+            u3(pi/2, pi/4, -pi/4) q[1];
+            crz(pi/2) q[0], q[1];
+            rz(-pi/4) q[0]; rx(pi/2) q[0]; rz(pi/4) q[0];
+            cy q[1], q[2];
+            swap q[0], q[1];
+            swap q[0], q[2];";
+        let c1 = Circuit::from_qasm(qasm).unwrap();
+        assert!(Tensor4::scalar_compare(&c, &c1));
+    }
+
+    #[test]
+    fn tket_errors() {
+        for op in [
+            r#"{"type": "Measure"}"#,
+            r#"{"type": "Rz", "params": ["a"]}"#,
+            r#"{"type": "Rz"}"#,
+            r#"{"type": "CX"}"#,
+        ] {
+            let json = format!(
+                r#"{{"commands": [{{"args": [["q", [0]]], "op": {}}}], "qubits": [["q", [0]]]}}"#,
+                op
+            );
+            assert!(decode_circuit(&json).is_err(), "{}", op);
+        }
+    }
+}
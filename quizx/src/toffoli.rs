@@ -0,0 +1,277 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decomposing Toffoli, CCZ and multi-controlled gates into Clifford+T
+//!
+//! There are two ways to do this, given by [ToffoliMethod]. The first is the usual
+//! circuit with 7 T gates and no ancillas, as in [Gate::push_basic_gates]. The
+//! second computes the AND of the two controls into an ancilla with 4 T gates, using
+//! the logical-AND construction of Gidney (Quantum 2, 74, 2018), applies a CNOT or
+//! CZ from the ancilla to the target, and then uncomputes the ancilla without any T
+//! gates. On a device, the uncomputation measures the ancilla in the X basis and
+//! applies a CZ to the controls if the outcome is 1. Here, it is post-selected onto
+//! the outcome 0 with [PostSelect], so each ancilla is used only once, and the
+//! circuit can be converted to a graph but not straight to a tensor. As for
+//! [Gate::add_to_graph] with `postselect`, this is meant for applications which
+//! only need the diagram, such as T-count reduction and classical simulation.
+//!
+//! Gates with more controls compute the AND of the controls in a chain of ancillas.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::gate::*;
+//! # use quizx::toffoli::ToffoliMethod;
+//! let c = Circuit::from_qasm("qreg q[3]; ccx q[0], q[1], q[2];").unwrap();
+//! let tcount = |c: &Circuit| c.num_gates_of_type(T) + c.num_gates_of_type(Tdg);
+//! let c1 = c.decompose_toffolis(ToffoliMethod::AncillaFree);
+//! assert_eq!(tcount(&c1), 7);
+//! let c2 = c.decompose_toffolis(ToffoliMethod::LogicalAnd);
+//! assert_eq!(tcount(&c2), 4);
+//! assert_eq!(c2.num_qubits(), 4);
+//! ```
+
+use crate::circuit::Circuit;
+use crate::gate::*;
+
+/// How to decompose Toffoli and CCZ gates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToffoliMethod {
+    /// The usual circuit with 7 T gates, which needs no ancillas.
+    #[default]
+    AncillaFree,
+    /// Gidney's logical AND with 4 T gates, which needs a fresh ancilla that is
+    /// post-selected at the end.
+    LogicalAnd,
+}
+
+impl ToffoliMethod {
+    /// The number of ancillas needed for a gate with `ncontrols` controls
+    ///
+    /// For [ToffoliMethod::AncillaFree], these must start in `|0>` and are returned
+    /// to `|0>`, so they can be reused. For [ToffoliMethod::LogicalAnd], they must be
+    /// qubits without any gates so far, which are used up.
+    pub fn num_ancillas(&self, ncontrols: usize) -> usize {
+        match self {
+            ToffoliMethod::AncillaFree => ncontrols.saturating_sub(2),
+            ToffoliMethod::LogicalAnd => ncontrols.saturating_sub(1),
+        }
+    }
+}
+
+/// Computes the AND of `a` and `b` into a fresh ancilla with 4 T gates
+fn push_and(c: &mut Circuit, a: usize, b: usize, anc: usize) {
+    c.push(Gate::new(InitAncilla, vec![anc]));
+    c.push(Gate::new(HAD, vec![anc]));
+    // the phase polynomial π/4 (t - t⊕a + t⊕a⊕b - t⊕b) = π t·ab - π/2 ab, where
+    // the second term is fixed by an S gate once the ancilla holds ab
+    c.push(Gate::new(T, vec![anc]));
+    for (q, t) in [(a, Tdg), (b, T), (a, Tdg)] {
+        c.push(Gate::new(CNOT, vec![q, anc]));
+        c.push(Gate::new(t, vec![anc]));
+    }
+    c.push(Gate::new(CNOT, vec![b, anc]));
+    c.push(Gate::new(HAD, vec![anc]));
+    c.push(Gate::new(S, vec![anc]));
+}
+
+/// Uncomputes an ancilla from [push_and], by post-selecting it onto `|+>`
+fn push_unand(c: &mut Circuit, anc: usize) {
+    c.push(Gate::new(HAD, vec![anc]));
+    c.push(Gate::new(PostSelect, vec![anc]));
+}
+
+/// A Toffoli with 7 T gates
+fn push_toffoli(c: &mut Circuit, a: usize, b: usize, t: usize) {
+    Gate::new(TOFF, vec![a, b, t]).push_basic_gates(c);
+}
+
+fn push_controlled(
+    c: &mut Circuit,
+    controls: &[usize],
+    target: usize,
+    z: bool,
+    ancillas: &[usize],
+    method: ToffoliMethod,
+) {
+    let k = controls.len();
+    assert!(
+        ancillas.len() >= method.num_ancillas(k),
+        "Expected {} ancillas for {} controls, got {}",
+        method.num_ancillas(k),
+        k,
+        ancillas.len()
+    );
+    let g2 = if z { CZ } else { CNOT };
+    match (controls, method) {
+        ([], _) => c.push(Gate::new(if z { Z } else { NOT }, vec![target])),
+        (&[a], _) => c.push(Gate::new(g2, vec![a, target])),
+        (&[a, b], ToffoliMethod::AncillaFree) => {
+            Gate::new(if z { CCZ } else { TOFF }, vec![a, b, target]).push_basic_gates(c)
+        }
+        (_, ToffoliMethod::AncillaFree) => {
+            // ancilla i holds the AND of the first i + 2 controls
+            let step = |c: &mut Circuit, i: usize| {
+                if i == 0 {
+                    push_toffoli(c, controls[0], controls[1], ancillas[0]);
+                } else {
+                    push_toffoli(c, controls[i + 1], ancillas[i - 1], ancillas[i]);
+                }
+            };
+            for i in 0..k - 2 {
+                step(c, i);
+            }
+            if z {
+                Gate::new(CCZ, vec![controls[k - 1], ancillas[k - 3], target]).push_basic_gates(c);
+            } else {
+                push_toffoli(c, controls[k - 1], ancillas[k - 3], target);
+            }
+            for i in (0..k - 2).rev() {
+                step(c, i);
+            }
+        }
+        (_, ToffoliMethod::LogicalAnd) => {
+            push_and(c, controls[0], controls[1], ancillas[0]);
+            for i in 2..k {
+                push_and(c, controls[i], ancillas[i - 2], ancillas[i - 1]);
+            }
+            c.push(Gate::new(g2, vec![ancillas[k - 2], target]));
+            for &anc in ancillas[..k - 1].iter().rev() {
+                push_unand(c, anc);
+            }
+        }
+    }
+}
+
+/// Pushes a NOT on `target`, controlled on all of `controls`, as Clifford+T gates
+///
+/// This needs [ToffoliMethod::num_ancillas] ancillas, and panics if there are
+/// fewer.
+pub fn push_mcx(
+    c: &mut Circuit,
+    controls: &[usize],
+    target: usize,
+    ancillas: &[usize],
+    method: ToffoliMethod,
+) {
+    push_controlled(c, controls, target, false, ancillas, method);
+}
+
+/// Pushes a Z on `target`, controlled on all of `controls`, as Clifford+T gates
+///
+/// This needs [ToffoliMethod::num_ancillas] ancillas, and panics if there are
+/// fewer.
+pub fn push_mcz(
+    c: &mut Circuit,
+    controls: &[usize],
+    target: usize,
+    ancillas: &[usize],
+    method: ToffoliMethod,
+) {
+    push_controlled(c, controls, target, true, ancillas, method);
+}
+
+impl Circuit {
+    /// Decomposes the Toffoli and CCZ gates of the circuit into Clifford+T
+    ///
+    /// Any ancillas are added as new qubits after the existing ones. See the
+    /// [module docs](crate::toffoli).
+    pub fn decompose_toffolis(&self, method: ToffoliMethod) -> Circuit {
+        let n = self.num_qubits();
+        let per_gate = method.num_ancillas(2);
+        let ngates = self
+            .gates
+            .iter()
+            .filter(|g| matches!(g.t, TOFF | CCZ))
+            .count();
+        let mut c = Circuit::new(n + ngates * per_gate);
+        let mut next_anc = n;
+        for g in &self.gates {
+            if let TOFF | CCZ = g.t {
+                let ancillas: Vec<usize> = (next_anc..next_anc + per_gate).collect();
+                push_controlled(&mut c, &g.qs[..2], g.qs[2], g.t == CCZ, &ancillas, method);
+                next_anc += per_gate;
+            } else {
+                c.push(g.clone());
+            }
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::*;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn toffoli_methods() {
+        let mut c = Circuit::random()
+            .seed(1337)
+            .qubits(3)
+            .depth(20)
+            .clifford_t(0.2)
+            .build();
+        c.push(Gate::new(TOFF, vec![0, 1, 2]));
+        c.push(Gate::new(HAD, vec![1]));
+        c.push(Gate::new(CCZ, vec![2, 0, 1]));
+        c.push(Gate::new(TOFF, vec![2, 1, 0]));
+        let tcount = |c: &Circuit| c.num_gates_of_type(T) + c.num_gates_of_type(Tdg);
+        let t = tcount(&c);
+
+        let c1 = c.decompose_toffolis(ToffoliMethod::AncillaFree);
+        assert_eq!(c1.num_qubits(), 3);
+        assert_eq!(tcount(&c1), t + 21);
+        assert!(Tensor4::scalar_compare(&c, &c1));
+
+        let c2 = c.decompose_toffolis(ToffoliMethod::LogicalAnd);
+        assert_eq!(c2.num_qubits(), 6);
+        assert_eq!(tcount(&c2), t + 12);
+        let g: Graph = c2.to_graph();
+        assert!(Tensor4::scalar_compare(&c, &g));
+    }
+
+    #[test]
+    fn multi_controlled() {
+        let mut g = vec![];
+        for method in [ToffoliMethod::AncillaFree, ToffoliMethod::LogicalAnd] {
+            // four controls and a target, then the ancillas for each gate
+            let n1 = method.num_ancillas(4);
+            let n2 = method.num_ancillas(3);
+            let anc1: Vec<usize> = (5..5 + n1).collect();
+            let anc2: Vec<usize> = (5 + n1..5 + n1 + n2).collect();
+            let mut c = Circuit::new(5 + n1 + n2);
+            if method == ToffoliMethod::AncillaFree {
+                for q in 5..5 + n1 + n2 {
+                    c.push(Gate::new(InitAncilla, vec![q]));
+                }
+            }
+            for q in 0..5 {
+                c.push(Gate::new(HAD, vec![q]));
+                c.push(Gate::new(T, vec![q]));
+            }
+            push_mcx(&mut c, &[0, 1, 2, 3], 4, &anc1, method);
+            push_mcz(&mut c, &[4, 2, 0], 1, &anc2, method);
+            if method == ToffoliMethod::AncillaFree {
+                for q in 5..5 + n1 + n2 {
+                    c.push(Gate::new(PostSelect, vec![q]));
+                }
+            }
+            g.push(c.to_graph::<Graph>());
+        }
+        assert!(Tensor4::scalar_compare(&g[0], &g[1]));
+    }
+}
@@ -0,0 +1,772 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Machine-readable traces of simplifications
+//!
+//! A [Tracer] wraps a graph and records every rewrite which a simplification
+//! applies to it, using the hooks [GraphLike::begin_rewrite] and
+//! [GraphLike::end_rewrite]. Each [TraceStep] gives the name of the rule, the
+//! vertices it was applied to, the phases of those vertices and their neighbours
+//! before and after, and the scalar factor the rule introduced. The trace can be
+//! written as JSON, so that external tools can replay or check the rewrites.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::graph::*;
+//! # use quizx::simplify::full_simp;
+//! # use quizx::trace::Tracer;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; t q[1]; cx q[0], q[1];").unwrap();
+//! let g: Graph = c.to_graph();
+//! let mut t = Tracer::new(g);
+//! full_simp(&mut t);
+//! assert!(t.steps().iter().any(|s| s.rule == "spider_fusion"));
+//! let json = t.to_json().unwrap();
+//! ```
+//!
+//! Rules which are built from other rules, such as `gen_pivot`, are recorded as a
+//! single step.
+//!
+//! A [Profiler] instead counts how many times each rule fires and how long it
+//! takes, and can stop a simplification after a given number of rewrites.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::graph::*;
+//! # use quizx::simplify::full_simp;
+//! # use quizx::trace::Profiler;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; t q[1]; cx q[0], q[1];").unwrap();
+//! let g: Graph = c.to_graph();
+//! let mut p = Profiler::with_limit(g, 3);
+//! full_simp(&mut p);
+//! assert_eq!(p.stats().steps, 3);
+//! println!("{}", p.stats());
+//! ```
+//!
+//! A [TensorChecker] computes the tensor of the graph before and after every
+//! rewrite, and panics with a report as soon as a rule changes it. This is slow,
+//! but useful when writing new rules.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! # use quizx::simplify::full_simp;
+//! # use quizx::trace::simp_checked;
+//! # use quizx::vec_graph::Graph;
+//! let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; t q[1]; cx q[0], q[1];").unwrap();
+//! let mut g: Graph = c.to_graph();
+//! simp_checked(&mut g, full_simp);
+//! ```
+
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use crate::tensor::{Tensorf, ToTensor};
+use ndarray::Dimension;
+use num::Complex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The phase of a vertex, before and after a rewrite
+///
+/// Phases are given as a pair `[numerator, denominator]`, in units of pi. A phase
+/// of `None` means the vertex did not exist at that point.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseChange {
+    pub vertex: V,
+    pub before: Option<[i64; 2]>,
+    pub after: Option<[i64; 2]>,
+}
+
+/// A scalar factor, in a form which is easy to read back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TraceScalar {
+    /// The number `2^pow * sum_i coeffs[i] * omega^i`, where `omega` is the
+    /// `2N`-th root of unity and `N` is the number of coefficients
+    Exact { pow: i32, coeffs: Vec<isize> },
+    /// An approximate complex number
+    Float { re: f64, im: f64 },
+}
+
+impl TraceScalar {
+    pub fn from_scalar(s: &ScalarN) -> Self {
+        match s {
+            Scalar::Exact(pow, coeffs) => TraceScalar::Exact {
+                pow: *pow,
+                coeffs: coeffs.clone(),
+            },
+            Scalar::Float(c) => TraceScalar::Float { re: c.re, im: c.im },
+        }
+    }
+
+    pub fn to_scalar(&self) -> ScalarN {
+        match self {
+            TraceScalar::Exact { pow, coeffs } => Scalar::Exact(*pow, coeffs.clone()),
+            TraceScalar::Float { re, im } => Scalar::complex(*re, *im),
+        }
+    }
+}
+
+/// A record of a single rewrite
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceStep {
+    /// The name of the rule, e.g. `"spider_fusion"`
+    pub rule: String,
+    /// The vertices the rule was applied to
+    pub vertices: Vec<V>,
+    /// The phases of the vertices the rule was applied to and their neighbours, as
+    /// well as any vertices the rule added
+    pub phases: Vec<PhaseChange>,
+    /// The factor the rule multiplied the scalar of the graph by
+    pub scalar: TraceScalar,
+}
+
+fn phase_pair(p: Phase) -> [i64; 2] {
+    let r = p.to_rational();
+    [*r.numer(), *r.denom()]
+}
+
+/// A graph which records the rewrites applied to it, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Tracer<G: GraphLike> {
+    g: G,
+    steps: Vec<TraceStep>,
+    depth: usize,
+    pending: Option<(TraceStep, ScalarN, Vec<V>)>,
+}
+
+impl<G: GraphLike> Tracer<G> {
+    /// Start recording the rewrites applied to `g`
+    pub fn new(g: G) -> Self {
+        Tracer {
+            g,
+            steps: vec![],
+            depth: 0,
+            pending: None,
+        }
+    }
+
+    /// The underlying graph
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    /// Stop recording and return the graph and the recorded steps
+    pub fn into_parts(self) -> (G, Vec<TraceStep>) {
+        (self.g, self.steps)
+    }
+
+    /// The recorded steps, oldest first
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    fn added(&mut self, v: V) -> V {
+        if let Some((_, _, added)) = &mut self.pending {
+            added.push(v);
+        }
+        v
+    }
+
+    /// Encode the recorded steps as a JSON array
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&self.steps)
+    }
+
+    /// Write the recorded steps to a file, as a JSON array
+    pub fn write_json(&self, filename: &std::path::Path) -> std::io::Result<()> {
+        let file = std::fs::File::create(filename)?;
+        serde_json::to_writer(std::io::BufWriter::new(file), &self.steps)?;
+        Ok(())
+    }
+}
+
+impl<G: GraphLike> GraphLike for Tracer<G> {
+    fn new() -> Self {
+        Tracer::new(G::new())
+    }
+
+    delegate_graph_like!(g);
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        let v = self.g.add_vertex(ty);
+        self.added(v)
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        let v = self.g.add_vertex_with_data(d);
+        self.added(v)
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        self.g.remove_vertex(v)
+    }
+
+    fn begin_rewrite(&mut self, rule: &'static str, vertices: &[V]) {
+        self.depth += 1;
+        if self.depth > 1 {
+            return;
+        }
+
+        let mut region: Vec<V> = vertices.to_vec();
+        for &v in vertices {
+            region.extend(self.g.neighbors(v));
+        }
+        region.sort_unstable();
+        region.dedup();
+        let phases = region
+            .into_iter()
+            .map(|v| PhaseChange {
+                vertex: v,
+                before: Some(phase_pair(self.g.phase(v))),
+                after: None,
+            })
+            .collect();
+
+        // collect the factor introduced by the rule in the scalar of the graph
+        let scalar = std::mem::replace(self.g.scalar_mut(), ScalarN::one());
+        let step = TraceStep {
+            rule: rule.to_string(),
+            vertices: vertices.to_vec(),
+            phases,
+            scalar: TraceScalar::from_scalar(&ScalarN::one()),
+        };
+        self.pending = Some((step, scalar, vec![]));
+    }
+
+    fn end_rewrite(&mut self) {
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+
+        let (mut step, scalar, added) = self.pending.take().expect("end_rewrite without begin");
+        for pc in step.phases.iter_mut() {
+            if self.g.contains_vertex(pc.vertex) {
+                pc.after = Some(phase_pair(self.g.phase(pc.vertex)));
+            }
+        }
+        for v in added {
+            if self.g.contains_vertex(v) {
+                step.phases.push(PhaseChange {
+                    vertex: v,
+                    before: None,
+                    after: Some(phase_pair(self.g.phase(v))),
+                });
+            }
+        }
+
+        step.scalar = TraceScalar::from_scalar(self.g.scalar());
+        *self.g.scalar_mut() = &scalar * self.g.scalar();
+        self.steps.push(step);
+    }
+}
+
+/// How many times a rule fired, and how long it took in total
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleStats {
+    pub count: usize,
+    pub time: Duration,
+}
+
+/// Statistics for a simplification, see [Profiler]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpStats {
+    /// The statistics for each rule which fired, by name
+    pub rules: BTreeMap<&'static str, RuleStats>,
+    /// The total number of rewrites
+    pub steps: usize,
+    /// True if the simplification was stopped by the step limit
+    pub limit_reached: bool,
+}
+
+impl fmt::Display for SimpStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (rule, rs) in &self.rules {
+            writeln!(f, "{:>24}: {:>8} in {:>10.2?}", rule, rs.count, rs.time)?;
+        }
+        write!(f, "{:>24}: {:>8}", "total", self.steps)?;
+        if self.limit_reached {
+            write!(f, " (limit reached)")?;
+        }
+        Ok(())
+    }
+}
+
+/// A graph which counts and times the rewrites applied to it
+///
+/// Only the time spent applying rules is measured, not the time spent looking for
+/// places to apply them. Optionally, the number of rewrites can be limited, after
+/// which the simplifiers stop, see [GraphLike::can_rewrite].
+#[derive(Debug, Clone)]
+pub struct Profiler<G: GraphLike> {
+    g: G,
+    stats: SimpStats,
+    limit: Option<usize>,
+    depth: usize,
+    started: Option<(&'static str, Instant)>,
+}
+
+impl<G: GraphLike> Profiler<G> {
+    /// Start profiling the rewrites applied to `g`
+    pub fn new(g: G) -> Self {
+        Profiler {
+            g,
+            stats: SimpStats::default(),
+            limit: None,
+            depth: 0,
+            started: None,
+        }
+    }
+
+    /// Start profiling the rewrites applied to `g`, allowing at most `limit` of them
+    pub fn with_limit(g: G, limit: usize) -> Self {
+        Profiler {
+            limit: Some(limit),
+            ..Profiler::new(g)
+        }
+    }
+
+    /// The underlying graph
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    /// The statistics so far
+    pub fn stats(&self) -> &SimpStats {
+        &self.stats
+    }
+
+    /// Stop profiling and return the graph and the statistics
+    pub fn into_parts(self) -> (G, SimpStats) {
+        (self.g, self.stats)
+    }
+}
+
+impl<G: GraphLike> GraphLike for Profiler<G> {
+    fn new() -> Self {
+        Profiler::new(G::new())
+    }
+
+    delegate_graph_like!(g);
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        self.g.add_vertex(ty)
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        self.g.add_vertex_with_data(d)
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        self.g.remove_vertex(v)
+    }
+
+    fn begin_rewrite(&mut self, rule: &'static str, _vertices: &[V]) {
+        self.depth += 1;
+        if self.depth == 1 {
+            self.started = Some((rule, Instant::now()));
+        }
+    }
+
+    fn end_rewrite(&mut self) {
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+
+        let (rule, start) = self.started.take().expect("end_rewrite without begin");
+        let rs = self.stats.rules.entry(rule).or_default();
+        rs.count += 1;
+        rs.time += start.elapsed();
+        self.stats.steps += 1;
+    }
+
+    fn can_rewrite(&self) -> bool {
+        match self.limit {
+            Some(limit) => self.stats.steps < limit,
+            None => true,
+        }
+    }
+}
+
+/// Run a simplification with at most `limit` rewrites, and return its statistics
+///
+/// For example, `simp_with_limit(&mut g, full_simp, 1000)`.
+pub fn simp_with_limit<G: GraphLike>(
+    g: &mut G,
+    simp: fn(&mut Profiler<G>) -> bool,
+    limit: usize,
+) -> SimpStats {
+    let mut p = Profiler::with_limit(std::mem::replace(g, G::new()), limit);
+    simp(&mut p);
+    p.stats.limit_reached = !p.can_rewrite();
+    let (h, stats) = p.into_parts();
+    *g = h;
+    stats
+}
+
+/// A graph which checks that every rewrite applied to it preserves its tensor
+///
+/// The tensor is computed with floating point numbers, so it is only checked for
+/// graphs with few inputs and outputs, see [TensorChecker::max_boundary], and
+/// only containing spiders and H-boxes. If a rewrite changes the tensor, this
+/// panics with the name of the rule, the vertices it was applied to and their
+/// neighbourhood before the rewrite, and how the tensors differ.
+#[derive(Debug, Clone)]
+pub struct TensorChecker<G: GraphLike> {
+    g: G,
+    max_boundary: usize,
+    checked: usize,
+    depth: usize,
+    pending: Option<(String, Tensorf)>,
+}
+
+impl<G: GraphLike> TensorChecker<G> {
+    /// Start checking the rewrites applied to `g`
+    pub fn new(g: G) -> Self {
+        TensorChecker {
+            g,
+            max_boundary: 10,
+            checked: 0,
+            depth: 0,
+            pending: None,
+        }
+    }
+
+    /// Only check graphs with at most this many inputs and outputs, 10 by default
+    pub fn max_boundary(&mut self, max_boundary: usize) -> &mut Self {
+        self.max_boundary = max_boundary;
+        self
+    }
+
+    /// The underlying graph
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    pub fn into_graph(self) -> G {
+        self.g
+    }
+
+    /// The number of rewrites which were checked so far
+    pub fn checked(&self) -> usize {
+        self.checked
+    }
+
+    fn can_check(&self) -> bool {
+        let boundary = self.g.inputs().len() + self.g.outputs().len();
+        boundary <= self.max_boundary
+            && self.g.num_vertices_of_type(VType::B) == boundary
+            && self.g.vertices().all(|v| {
+                matches!(
+                    self.g.vertex_type(v),
+                    VType::B | VType::Z | VType::X | VType::H
+                )
+            })
+    }
+
+    /// Describe the given vertices and their neighbours
+    fn describe(&self, vertices: &[V]) -> String {
+        let mut vs: Vec<V> = vertices.to_vec();
+        for &v in vertices {
+            if self.g.contains_vertex(v) {
+                vs.extend(self.g.neighbors(v));
+            }
+        }
+        vs.sort_unstable();
+        vs.dedup();
+
+        let mut s = String::new();
+        for v in vs {
+            if !self.g.contains_vertex(v) {
+                continue;
+            }
+            let mut nhd = self.g.incident_edge_vec(v);
+            nhd.sort();
+            let nhd: Vec<String> = nhd
+                .iter()
+                .map(|(n, et)| match et {
+                    EType::H => format!("{}(H)", n),
+                    _ => format!("{}", n),
+                })
+                .collect();
+            s += &format!(
+                "  {}: {:?}({}) -- {}\n",
+                v,
+                self.g.vertex_type(v),
+                self.g.phase(v),
+                nhd.join(", ")
+            );
+        }
+        s
+    }
+}
+
+/// Describe how two tensors differ, or return None if they are equal up to
+/// rounding errors
+fn tensor_difference(t0: &Tensorf, t1: &Tensorf) -> Option<String> {
+    if t0.shape() != t1.shape() {
+        return Some(format!(
+            "the shape changed from {:?} to {:?}",
+            t0.shape(),
+            t1.shape()
+        ));
+    }
+
+    let scale = t0
+        .iter()
+        .chain(t1.iter())
+        .map(|x| x.norm())
+        .fold(1.0, f64::max);
+    let tol = 1e-6 * scale;
+    let (idx, d) = t0
+        .indexed_iter()
+        .zip(t1.iter())
+        .map(|((i, x), y)| (i, (x - y).norm()))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if d <= tol {
+        return None;
+    }
+
+    // check if the tensors only differ by a scalar factor
+    let (i, x) = t0
+        .indexed_iter()
+        .max_by(|a, b| a.1.norm().total_cmp(&b.1.norm()))?;
+    if x.norm() > tol {
+        let r: Complex<f64> = t1[&i] / x;
+        if t0
+            .iter()
+            .zip(t1.iter())
+            .all(|(x, y)| (x * r - y).norm() <= tol)
+        {
+            return Some(format!(
+                "the tensors differ by a factor of {:.6} (abs {:.6}), so the scalar is probably wrong",
+                r,
+                r.norm()
+            ));
+        }
+    }
+
+    Some(format!(
+        "the largest difference is {:.6} at {:?}: {:.6} before, {:.6} after",
+        d,
+        idx.slice(),
+        t0[&idx],
+        t1[&idx]
+    ))
+}
+
+impl<G: GraphLike> GraphLike for TensorChecker<G> {
+    fn new() -> Self {
+        TensorChecker::new(G::new())
+    }
+
+    delegate_graph_like!(g);
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        self.g.add_vertex(ty)
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        self.g.add_vertex_with_data(d)
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        self.g.remove_vertex(v)
+    }
+
+    fn begin_rewrite(&mut self, rule: &'static str, vertices: &[V]) {
+        self.depth += 1;
+        if self.depth == 1 && self.can_check() {
+            let report = format!(
+                "rewrite {} at {:?} changed the tensor\nbefore the rewrite:\n{}",
+                rule,
+                vertices,
+                self.describe(vertices)
+            );
+            self.pending = Some((report, self.g.to_tensorf()));
+        }
+    }
+
+    fn end_rewrite(&mut self) {
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+
+        if let Some((report, t0)) = self.pending.take() {
+            if self.can_check() {
+                let t1 = self.g.to_tensorf();
+                if let Some(diff) = tensor_difference(&t0, &t1) {
+                    panic!("{}{}", report, diff);
+                }
+                self.checked += 1;
+            }
+        }
+    }
+}
+
+/// Run a simplification, checking every rewrite with a [TensorChecker]
+///
+/// For example, `simp_checked(&mut g, full_simp)`.
+pub fn simp_checked<G: GraphLike>(g: &mut G, simp: fn(&mut TensorChecker<G>) -> bool) -> bool {
+    let mut c = TensorChecker::new(std::mem::replace(g, G::new()));
+    let m = simp(&mut c);
+    *g = c.into_graph();
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::*;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn trace_full_simp() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+        let mut h = g.clone();
+        full_simp(&mut h);
+
+        let mut t = Tracer::new(g.clone());
+        full_simp(&mut t);
+        let (h1, steps) = t.clone().into_parts();
+        assert_eq!(h, h1);
+        assert!(!steps.is_empty());
+
+        // the scalar factors multiply up to the final scalar
+        let mut s = g.scalar().clone();
+        for step in &steps {
+            s *= step.scalar.to_scalar();
+        }
+        assert_eq!(&s, h1.scalar());
+        assert_eq!(g.to_tensor4(), h1.to_tensor4());
+
+        let json = t.to_json().unwrap();
+        let steps1: Vec<TraceStep> = serde_json::from_str(&json).unwrap();
+        assert_eq!(steps, steps1);
+    }
+
+    #[test]
+    fn trace_spider_fusion() {
+        let mut g = Graph::new();
+        let b0 = g.add_vertex(VType::B);
+        let v0 = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let v1 = g.add_vertex_with_phase(VType::Z, (1, 2));
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(b0, v0);
+        g.add_edge(v0, v1);
+        g.add_edge(v1, b1);
+        g.set_inputs(vec![b0]);
+        g.set_outputs(vec![b1]);
+
+        let mut t = Tracer::new(g);
+        assert!(crate::basic_rules::spider_fusion(&mut t, v0, v1));
+        assert_eq!(t.steps().len(), 1);
+        let step = &t.steps()[0];
+        assert_eq!(step.rule, "spider_fusion");
+        assert_eq!(step.vertices, vec![v0, v1]);
+        assert_eq!(step.scalar.to_scalar(), ScalarN::one());
+        let change = |v| step.phases.iter().find(|pc| pc.vertex == v).unwrap();
+        assert_eq!(change(v0).before, Some([1, 4]));
+        assert_eq!(change(v0).after, Some([3, 4]));
+        assert_eq!(change(v1).before, Some([1, 2]));
+        assert_eq!(change(v1).after, None);
+        assert_eq!(change(b0).before, Some([0, 1]));
+    }
+
+    #[test]
+    fn profile_full_simp() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+        let mut h = g.clone();
+        full_simp(&mut h);
+
+        let mut p = Profiler::new(g.clone());
+        full_simp(&mut p);
+        let (h1, stats) = p.into_parts();
+        assert_eq!(h, h1);
+        assert!(!stats.limit_reached);
+        assert!(stats.rules["spider_fusion"].count > 0);
+        assert_eq!(
+            stats.rules.values().map(|rs| rs.count).sum::<usize>(),
+            stats.steps
+        );
+
+        for limit in [0, 1, 10, stats.steps - 1] {
+            let mut h2 = g.clone();
+            let stats2 = simp_with_limit(&mut h2, full_simp, limit);
+            assert_eq!(stats2.steps, limit);
+            assert!(stats2.limit_reached);
+            assert_eq!(g.to_tensor4(), h2.to_tensor4());
+        }
+
+        let mut h3 = g.clone();
+        let stats3 = simp_with_limit(&mut h3, full_simp, stats.steps + 1);
+        assert_eq!(stats3.steps, stats.steps);
+        assert!(!stats3.limit_reached);
+        assert_eq!(h, h3);
+    }
+
+    #[test]
+    fn tensor_checker() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(3)
+            .depth(20)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g = TensorChecker::new(c.to_graph::<Graph>());
+        full_simp(&mut g);
+        assert!(g.checked() > 0);
+
+        // graphs with too many inputs and outputs are not checked
+        let mut g = TensorChecker::new(c.to_graph::<Graph>());
+        g.max_boundary(4);
+        full_simp(&mut g);
+        assert_eq!(g.checked(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "differ by a factor")]
+    fn tensor_checker_wrong_scalar() {
+        let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1];").unwrap();
+        let mut g = TensorChecker::new(c.to_graph::<Graph>());
+        g.begin_rewrite("wrong_scalar", &[]);
+        g.scalar_mut().mul_sqrt2_pow(1);
+        g.end_rewrite();
+    }
+}
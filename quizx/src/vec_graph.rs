@@ -32,6 +32,8 @@ pub struct Graph {
     numv: usize,
     nume: usize,
     scalar: ScalarN,
+    stats: GraphStats,
+    bqubits: BoundaryQubits,
 }
 
 impl Graph {
@@ -62,7 +64,21 @@ impl Graph {
     /// more efficient.
     fn remove_half_edge(&mut self, s: V, t: V) {
         if let Some(Some(nhd)) = self.edata.get_mut(s) {
-            Graph::index(nhd, t).map(|i| nhd.swap_remove(i));
+            if let Some(i) = Graph::index(nhd, t) {
+                nhd.swap_remove(i);
+                self.stats.change_degree(nhd.len() + 1, nhd.len());
+            }
+        }
+    }
+
+    /// Applies `f` to the data of vertex `v`, keeping the cached stats up to date.
+    fn update_vdata(&mut self, v: V, f: impl FnOnce(&mut VData)) {
+        if let Some(Some(d)) = self.vdata.get_mut(v) {
+            let old = *d;
+            f(d);
+            self.stats.update_vertex(&old, d);
+        } else {
+            panic!("Vertex not found");
         }
     }
 
@@ -97,6 +113,8 @@ impl GraphLike for Graph {
             numv: 0,
             nume: 0,
             scalar: Scalar::one(),
+            stats: GraphStats::default(),
+            bqubits: BoundaryQubits::default(),
         }
     }
 
@@ -150,6 +168,7 @@ impl GraphLike for Graph {
 
     fn add_vertex_with_data(&mut self, d: VData) -> V {
         self.numv += 1;
+        self.stats.add_vertex(&d);
         if let Some(v) = self.holes.pop() {
             self.vdata[v] = Some(d);
             self.edata[v] = Some(Vec::new());
@@ -165,8 +184,10 @@ impl GraphLike for Graph {
         self.numv -= 1;
         self.holes.push(v);
 
-        self.vdata[v] = None;
+        let d = mem::take(&mut self.vdata[v]).expect("No such vertex.");
         let adj = mem::take(&mut self.edata[v]).expect("No such vertex.");
+        self.bqubits.remove(v);
+        self.stats.remove_vertex(&d, adj.len());
 
         for (v1, _) in adj {
             self.nume -= 1;
@@ -180,12 +201,14 @@ impl GraphLike for Graph {
 
         if let Some(Some(nhd)) = self.edata.get_mut(s) {
             nhd.push((t, ety));
+            self.stats.change_degree(nhd.len() - 1, nhd.len());
         } else {
             panic!("Source vertex not found");
         }
 
         if let Some(Some(nhd)) = self.edata.get_mut(t) {
             nhd.push((s, ety));
+            self.stats.change_degree(nhd.len() - 1, nhd.len());
         } else {
             panic!("Target vertex not found");
         }
@@ -198,11 +221,8 @@ impl GraphLike for Graph {
     }
 
     fn set_phase(&mut self, v: V, phase: impl Into<Phase>) {
-        if let Some(Some(d)) = self.vdata.get_mut(v) {
-            d.phase = phase.into();
-        } else {
-            panic!("Vertex not found");
-        }
+        let phase = phase.into();
+        self.update_vdata(v, |d| d.phase = phase);
     }
 
     fn phase(&self, v: V) -> Phase {
@@ -210,19 +230,12 @@ impl GraphLike for Graph {
     }
 
     fn add_to_phase(&mut self, v: V, phase: impl Into<Phase>) {
-        if let Some(Some(d)) = self.vdata.get_mut(v) {
-            d.phase = (d.phase + phase.into()).normalize();
-        } else {
-            panic!("Vertex not found");
-        }
+        let phase = phase.into();
+        self.update_vdata(v, |d| d.phase = (d.phase + phase).normalize());
     }
 
     fn set_vertex_type(&mut self, v: V, ty: VType) {
-        if let Some(Some(d)) = self.vdata.get_mut(v) {
-            d.ty = ty;
-        } else {
-            panic!("Vertex not found");
-        }
+        self.update_vdata(v, |d| d.ty = ty);
     }
 
     fn vertex_data(&self, v: V) -> VData {
@@ -327,6 +340,18 @@ impl GraphLike for Graph {
         &mut self.scalar
     }
 
+    fn stats(&self) -> &GraphStats {
+        &self.stats
+    }
+
+    fn boundary_qubits(&self) -> &BoundaryQubits {
+        &self.bqubits
+    }
+
+    fn boundary_qubits_mut(&mut self) -> &mut BoundaryQubits {
+        &mut self.bqubits
+    }
+
     fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
     where
         F: Fn(V, V, EType) -> bool,
@@ -0,0 +1,128 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checking that two circuits are equal with the ZX-calculus
+//!
+//! Two unitary circuits `C` and `C'` are equal up to a global phase exactly when
+//! `C' C†` is the identity. [Circuit::verify_equality] builds the diagram of
+//! `C' C†`, simplifies it with [full_simp], and checks that only plain wires from
+//! each input to the same output are left. The scalar is ignored, which handles
+//! global phases. For Clifford circuits this always decides equality, but for other
+//! circuits `full_simp` might not reduce the diagram all the way, so `false` only
+//! means that equality couldn't be shown.
+//!
+//! ```
+//! # use quizx::circuit::Circuit;
+//! let c = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; t q[1]; cx q[0], q[1];").unwrap();
+//! let c1 = Circuit::from_qasm("qreg q[2]; cx q[0], q[1]; tdg q[1]; cx q[0], q[1];").unwrap();
+//! let mut c2 = c.clone();
+//! c2.optimize();
+//! assert!(c.verify_equality(&c2));
+//! assert!(!c.verify_equality(&c1));
+//! ```
+
+use crate::circuit::Circuit;
+use crate::graph::*;
+use crate::simplify::full_simp;
+use crate::vec_graph::Graph;
+
+impl Circuit {
+    /// Checks whether the circuit is equal to `other` up to a global phase
+    ///
+    /// See the [module docs](crate::verify).
+    pub fn verify_equality(&self, other: &Circuit) -> bool {
+        self.verify_equality_up_to_permutation(other)
+            .is_some_and(|p| p.iter().enumerate().all(|(i, &j)| i == j))
+    }
+
+    /// Checks whether `other` is the circuit followed by a permutation of the qubits,
+    /// up to a global phase
+    ///
+    /// If so, this returns the permutation, which moves qubit `i` to qubit `p[i]`.
+    /// This is useful for circuits from extraction or routing, which may only be
+    /// correct up to swapping the qubits.
+    pub fn verify_equality_up_to_permutation(&self, other: &Circuit) -> Option<Vec<usize>> {
+        if self.num_qubits() != other.num_qubits() {
+            return None;
+        }
+        let c = self.to_adjoint() + other;
+        let mut g: Graph = c.to_graph();
+        full_simp(&mut g);
+
+        let n = self.num_qubits();
+        if g.inputs().len() != n || g.outputs().len() != n || g.num_vertices() != 2 * n {
+            return None;
+        }
+        let mut perm = vec![];
+        for &i in g.inputs() {
+            let (o, et) = g.incident_edges(i).next()?;
+            let j = g.outputs().iter().position(|&o1| o1 == o)?;
+            if et != EType::N || g.degree(i) != 1 {
+                return None;
+            }
+            perm.push(j);
+        }
+        Some(perm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::*;
+
+    #[test]
+    fn verify_random() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(5)
+                .depth(40)
+                .clifford_t(0.2)
+                .build();
+            assert!(c.verify_equality(&c.to_optimized()));
+            assert!(c.verify_equality(&c.to_basic_gates()));
+
+            let mut c1 = c.clone();
+            c1.push(Gate::new(HAD, vec![2]));
+            assert!(!c.verify_equality(&c1));
+            c1.push(Gate::new(HAD, vec![2]));
+            assert!(c.verify_equality(&c1));
+        }
+    }
+
+    #[test]
+    fn verify_permutation() {
+        let c = Circuit::from_qasm("qreg q[3]; h q[0]; cx q[0], q[1]; t q[2];").unwrap();
+        let mut c1 = c.clone();
+        c1.push(Gate::new(SWAP, vec![0, 2]));
+        assert!(!c.verify_equality(&c1));
+        assert_eq!(
+            c.verify_equality_up_to_permutation(&c1),
+            Some(vec![2, 1, 0])
+        );
+
+        // a global phase doesn't matter
+        let mut c2 = c.clone();
+        c2.push(Gate::new(Z, vec![1]));
+        c2.push(Gate::new(NOT, vec![1]));
+        c2.push(Gate::new(Z, vec![1]));
+        c2.push(Gate::new(NOT, vec![1]));
+        assert!(c.verify_equality(&c2));
+
+        assert!(!c.verify_equality(&Circuit::new(2)));
+    }
+}